@@ -0,0 +1,303 @@
+//! STM against lock and atomic baselines.
+//!
+//!     cargo bench --bench stm
+//!     cargo bench --bench stm -- transfer     # one group
+//!
+//! Every benchmark runs `THREADS` threads (or the count in its name) that
+//! together do the measured number of operations, so the reported time is
+//! per operation across all threads, and lower is better.
+//!
+//! - `hot_counter`: everybody increments one word, so every commit
+//!   conflicts with every concurrent one; the worst case for an optimistic
+//!   STM. The `stm/*` variants differ in backoff (`spin_limit`) and in how
+//!   many writers may run at once (`max_writers`), showing what each buys
+//!   under contention, and `stm/polite`, `stm/karma` and `stm/greedy` in
+//!   the contention manager deciding who waits for a held lock. `mutex`
+//!   and `atomic` are the floor.
+//! - `transfer`: moves between two random accounts of 64, against a mutex
+//!   per account locked in address order. Conflicts are rare, so the gap
+//!   to the locks is mostly the fixed cost of a write transaction.
+//! - `spike`: each thread alternates between runs of 64 updates of its
+//!   own stripe and runs of 64 transactions reading 16 hot stripes and
+//!   writing one of them, which conflict with each other. `stm/breaker`
+//!   has a circuit breaker that serializes writers while the restarts
+//!   per commit exceed 1, against the optimistic `stm/default`.
+//! - `disjoint/N`: each thread updates its own stripe. Nothing conflicts,
+//!   so this shows how commits scale with threads, the shared clock being
+//!   the one word they all write.
+//! - `read_mostly`: nine reads of 8 words for every write, against an
+//!   `RwLock`. Read transactions write nothing shared, unlike taking a
+//!   read lock, so watch how the two change from 1 to `THREADS` threads.
+//! - `hot_read`: one thread reading one word with `SeqlockCell::get` and
+//!   in a read transaction. The gap is what setting up a transaction and
+//!   checking its read version costs. `read_transaction/fast_path` is the
+//!   single attempt `read_transaction` makes before its retry loop,
+//!   `read_transaction/loop` goes straight into the loop (as it did
+//!   before the fast path, and still does with a priority hint).
+//!
+//! Compare runs with criterion's baselines (`--save-baseline` and
+//! `--baseline`) rather than across machines.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Barrier, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use tl2::{load, Greedy, Karma, Polite, STMBuilder, STMResult, SeqlockCell, TxPriority, STM};
+
+const THREADS: usize = 4;
+const ACCOUNTS: usize = 64;
+
+// Run `op(thread, i)` `iters` times spread over `threads` threads and time
+// them from a common start.
+fn run<F>(threads: usize, iters: u64, op: F) -> Duration
+where
+    F: Fn(usize, u64) + Sync,
+{
+    let per_thread = iters.div_ceil(threads as u64);
+    let barrier = Barrier::new(threads + 1);
+    thread::scope(|s| {
+        for t in 0..threads {
+            let (op, barrier) = (&op, &barrier);
+            s.spawn(move || {
+                barrier.wait();
+                for i in 0..per_thread {
+                    op(t, i);
+                }
+                barrier.wait();
+            });
+        }
+        barrier.wait();
+        let start = Instant::now();
+        barrier.wait();
+        start.elapsed()
+    })
+}
+
+// xorshift64*, for picking accounts without a shared generator
+fn rand(t: usize, i: u64) -> u64 {
+    let mut x = ((t as u64 + 1) * 0x9e37_79b9_7f4a_7c15) ^ i;
+    x ^= x >> 12;
+    x ^= x << 25;
+    x ^= x >> 27;
+    x.wrapping_mul(0x2545_f491_4f6c_dd1d)
+}
+
+fn accounts(t: usize, i: u64) -> (usize, usize) {
+    let r = rand(t, i);
+    let from = (r % ACCOUNTS as u64) as usize;
+    let to = (from + 1 + (r >> 32) as usize % (ACCOUNTS - 1)) % ACCOUNTS;
+    (from, to)
+}
+
+fn increment(stm: &STM, addr: usize) {
+    stm.write_transaction(|tr| {
+        let v = u64::from_le_bytes(load!(tr, addr));
+        tr.store(addr, (v + 1).to_le_bytes());
+        STMResult::Ok(())
+    });
+}
+
+fn stm_configs() -> Vec<(&'static str, STMBuilder)> {
+    vec![
+        ("stm/default", STM::builder()),
+        ("stm/spin4", STM::builder().spin_limit(4)),
+        ("stm/writers1", STM::builder().max_writers(1)),
+        (
+            "stm/polite",
+            STM::builder().contention_manager(Arc::new(Polite::default())),
+        ),
+        (
+            "stm/karma",
+            STM::builder().contention_manager(Arc::new(Karma::default())),
+        ),
+        (
+            "stm/greedy",
+            STM::builder().contention_manager(Arc::new(Greedy::default())),
+        ),
+    ]
+}
+
+fn hot_counter(c: &mut Criterion) {
+    let mut g = c.benchmark_group("hot_counter");
+    for (name, builder) in stm_configs() {
+        let stm = builder.build();
+        g.bench_function(name, |b| {
+            b.iter_custom(|iters| run(THREADS, iters, |_, _| increment(&stm, 0)))
+        });
+    }
+
+    let m = Mutex::new(0u64);
+    g.bench_function("mutex", |b| {
+        b.iter_custom(|iters| run(THREADS, iters, |_, _| *m.lock().unwrap() += 1))
+    });
+
+    let a = AtomicU64::new(0);
+    g.bench_function("atomic", |b| {
+        b.iter_custom(|iters| {
+            run(THREADS, iters, |_, _| {
+                a.fetch_add(1, Ordering::Relaxed);
+            })
+        })
+    });
+    g.finish();
+}
+
+fn transfer(c: &mut Criterion) {
+    let mut g = c.benchmark_group("transfer");
+    for (name, builder) in stm_configs() {
+        let stm = builder.capacity(8 * ACCOUNTS).build();
+        g.bench_function(name, |b| {
+            b.iter_custom(|iters| {
+                run(THREADS, iters, |t, i| {
+                    let (from, to) = accounts(t, i);
+                    stm.write_transaction(|tr| {
+                        let a = u64::from_le_bytes(load!(tr, 8 * from));
+                        let b = u64::from_le_bytes(load!(tr, 8 * to));
+                        tr.store(8 * from, a.wrapping_sub(1).to_le_bytes());
+                        tr.store(8 * to, b.wrapping_add(1).to_le_bytes());
+                        STMResult::Ok(())
+                    });
+                })
+            })
+        });
+    }
+
+    let locks: Vec<Mutex<u64>> = (0..ACCOUNTS).map(|_| Mutex::new(0)).collect();
+    g.bench_function("mutex_ordered", |b| {
+        b.iter_custom(|iters| {
+            run(THREADS, iters, |t, i| {
+                let (from, to) = accounts(t, i);
+                let (first, second) = (from.min(to), from.max(to));
+                let mut x = locks[first].lock().unwrap();
+                let mut y = locks[second].lock().unwrap();
+                if first == from {
+                    *x = x.wrapping_sub(1);
+                    *y = y.wrapping_add(1);
+                } else {
+                    *y = y.wrapping_sub(1);
+                    *x = x.wrapping_add(1);
+                }
+            })
+        })
+    });
+    g.finish();
+}
+
+fn spike(c: &mut Criterion) {
+    const HOT: usize = 16;
+
+    let mut g = c.benchmark_group("spike");
+    let configs = [
+        ("stm/default", STM::builder()),
+        ("stm/breaker", STM::builder().circuit_breaker(1.0)),
+    ];
+    for (name, builder) in configs {
+        let stm = builder.capacity(8 * HOT + 64 * THREADS).build();
+        g.bench_function(name, |b| {
+            b.iter_custom(|iters| {
+                run(THREADS, iters, |t, i| {
+                    if (i / 64).is_multiple_of(2) {
+                        increment(&stm, 8 * HOT + 64 * t);
+                        return;
+                    }
+                    stm.write_transaction(|tr| {
+                        let mut sum = 0u64;
+                        for w in 0..HOT {
+                            sum = sum.wrapping_add(u64::from_le_bytes(load!(tr, 8 * w)));
+                        }
+                        tr.store(8 * (i as usize % HOT), sum.wrapping_add(1).to_le_bytes());
+                        STMResult::Ok(())
+                    });
+                })
+            })
+        });
+    }
+    g.finish();
+}
+
+fn disjoint(c: &mut Criterion) {
+    let mut g = c.benchmark_group("disjoint");
+    for threads in [1, 2, 4, 8, 16] {
+        // a stripe per thread, 64 bytes apart to keep the words on
+        // different cache lines
+        let stm = STM::builder().capacity(64 * threads).build();
+        g.bench_with_input(BenchmarkId::new("stm", threads), &threads, |b, &n| {
+            b.iter_custom(|iters| run(n, iters, |t, _| increment(&stm, 64 * t)))
+        });
+    }
+    g.finish();
+}
+
+fn read_mostly(c: &mut Criterion) {
+    const WORDS: usize = 8;
+
+    let mut g = c.benchmark_group("read_mostly");
+    for threads in [1, THREADS] {
+        let stm = STM::builder().capacity(8 * WORDS).build();
+        g.bench_with_input(BenchmarkId::new("stm", threads), &threads, |b, &n| {
+            b.iter_custom(|iters| {
+                run(n, iters, |t, i| {
+                    if rand(t, i).is_multiple_of(10) {
+                        increment(&stm, 8 * (i as usize % WORDS));
+                    } else {
+                        stm.read_transaction(|tr| {
+                            let mut sum = 0u64;
+                            for w in 0..WORDS {
+                                sum = sum.wrapping_add(u64::from_le_bytes(load!(tr, 8 * w)));
+                            }
+                            STMResult::Ok(sum)
+                        });
+                    }
+                })
+            })
+        });
+
+        let words = RwLock::new([0u64; WORDS]);
+        g.bench_with_input(BenchmarkId::new("rwlock", threads), &threads, |b, &n| {
+            b.iter_custom(|iters| {
+                run(n, iters, |t, i| {
+                    if rand(t, i).is_multiple_of(10) {
+                        words.write().unwrap()[i as usize % WORDS] += 1;
+                    } else {
+                        let w = words.read().unwrap();
+                        criterion::black_box(w.iter().fold(0u64, |s, v| s.wrapping_add(*v)));
+                    }
+                })
+            })
+        });
+    }
+    g.finish();
+}
+
+fn hot_read(c: &mut Criterion) {
+    let stm = Arc::new(STM::new());
+    let cell = SeqlockCell::<u64>::new(stm.clone(), 0);
+    cell.set(1);
+
+    let mut g = c.benchmark_group("hot_read");
+    g.bench_function("seqlock", |b| b.iter(|| cell.get()));
+    g.bench_function("read_transaction/fast_path", |b| {
+        b.iter(|| stm.read_transaction(|tr| STMResult::Ok(u64::from_le_bytes(load!(tr, 0)))))
+    });
+    g.bench_function("read_transaction/loop", |b| {
+        b.iter(|| {
+            stm.read_transaction_with_priority(TxPriority::Normal, |tr| {
+                STMResult::Ok(u64::from_le_bytes(load!(tr, 0)))
+            })
+        })
+    });
+    g.finish();
+}
+
+criterion_group!(
+    benches,
+    hot_counter,
+    transfer,
+    spike,
+    disjoint,
+    read_mostly,
+    hot_read
+);
+criterion_main!(benches);
@@ -0,0 +1,23 @@
+// With the `ffi` feature, compile examples/ffi.c against include/tl2.h and
+// link it into the test binaries only, where tests/ffi.rs calls its `main`
+// as `tl2_ffi_example`. The library itself links nothing extra.
+
+fn main() {
+    #[cfg(feature = "ffi")]
+    {
+        println!("cargo:rerun-if-changed=examples/ffi.c");
+        println!("cargo:rerun-if-changed=include/tl2.h");
+
+        let out = std::env::var("OUT_DIR").unwrap();
+        cc::Build::new()
+            .file("examples/ffi.c")
+            .include("include")
+            .define("main", "tl2_ffi_example")
+            .flag("-pthread")
+            .warnings_into_errors(true)
+            .cargo_metadata(false)
+            .compile("tl2_ffi_example");
+        println!("cargo:rustc-link-arg-tests={}/libtl2_ffi_example.a", out);
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+}
@@ -0,0 +1,193 @@
+// Bank transfers: workers move money between accounts, aborting when the
+// source lacks the funds, while an auditor thread sums every balance in
+// read transactions and checks the total never changes.
+//
+//     cargo run --release --example bank -- --threads 8 --hot-accounts 2
+//
+// `--hot-accounts K` sends half of the transfers between the first K
+// accounts to make them contend.
+
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tl2::{load, store, STMResult, STM};
+
+const BALANCE: u64 = 1_000;
+
+struct Config {
+    seed: u64,
+    threads: usize,
+    accounts: usize,
+    iterations: usize,
+    hot: usize,
+}
+
+fn parse_args() -> Config {
+    let mut conf = Config {
+        seed: 1,
+        threads: 4,
+        accounts: 64,
+        iterations: 100_000,
+        hot: 0,
+    };
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    for pair in args.chunks(2) {
+        let val = pair.get(1).unwrap_or_else(|| usage());
+        let n = val.parse().unwrap_or_else(|_| usage());
+        match pair[0].as_str() {
+            "--seed" => conf.seed = n as u64,
+            "--threads" => conf.threads = n,
+            "--accounts" => conf.accounts = n,
+            "--iterations" => conf.iterations = n,
+            "--hot-accounts" => conf.hot = n,
+            _ => usage(),
+        }
+    }
+    if conf.accounts < 2 || conf.hot > conf.accounts || conf.hot == 1 {
+        usage();
+    }
+    conf
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: bank [--seed N] [--threads N] [--accounts N (>= 2)] \
+         [--iterations N (per thread)] [--hot-accounts K (0 or 2..=accounts)]"
+    );
+    process::exit(2);
+}
+
+// xorshift64*, seeded per thread so a failing run can be repeated
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+fn total(stm: &STM, accounts: usize) -> u64 {
+    stm.read_transaction(|tr| {
+        let mut sum = 0;
+        for i in 0..accounts {
+            sum += u64::from_le_bytes(load!(tr, 8 * i));
+        }
+        STMResult::Ok(sum)
+    })
+    .unwrap()
+}
+
+fn main() {
+    let conf = parse_args();
+    let expected = BALANCE * conf.accounts as u64;
+    let stm = Arc::new(
+        STM::builder()
+            .capacity(8 * conf.accounts)
+            .stats(true)
+            .build(),
+    );
+    stm.write_transaction(|tr| {
+        for i in 0..conf.accounts {
+            store!(tr, 8 * i, BALANCE.to_le_bytes());
+        }
+        STMResult::Ok(())
+    });
+
+    let start = Instant::now();
+    let mut workers = Vec::new();
+    for n in 0..conf.threads {
+        let stm = stm.clone();
+        let (accounts, hot, iterations) = (conf.accounts, conf.hot, conf.iterations);
+        let mut rng = Rng::new(conf.seed ^ n as u64);
+        workers.push(std::thread::spawn(move || {
+            for _ in 0..iterations {
+                let pool = if hot > 0 && rng.next() & 1 == 0 {
+                    hot
+                } else {
+                    accounts
+                };
+                let from = 8 * rng.below(pool);
+                let to = 8 * ((from / 8 + 1 + rng.below(pool - 1)) % pool);
+                let amount = rng.next() % (2 * BALANCE);
+                // aborts, leaving both untouched, when the funds are short
+                stm.write_transaction(|tr| {
+                    let a = u64::from_le_bytes(load!(tr, from));
+                    if a < amount {
+                        return STMResult::Abort;
+                    }
+                    let b = u64::from_le_bytes(load!(tr, to));
+                    store!(tr, from, (a - amount).to_le_bytes());
+                    store!(tr, to, (b + amount).to_le_bytes());
+                    STMResult::Ok(())
+                });
+            }
+        }));
+    }
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let auditor = {
+        let stm = stm.clone();
+        let stop = stop.clone();
+        let accounts = conf.accounts;
+        std::thread::spawn(move || {
+            let mut audits = 0u64;
+            while !stop.load(Ordering::Relaxed) {
+                let sum = total(&stm, accounts);
+                if sum != expected {
+                    eprintln!("audit {}: total {} (expected {})", audits, sum, expected);
+                    return Err(audits);
+                }
+                audits += 1;
+            }
+            Ok(audits)
+        })
+    };
+
+    for th in workers {
+        th.join().unwrap();
+    }
+    let elapsed = start.elapsed();
+    stop.store(true, Ordering::Relaxed);
+    let audited = auditor.join().unwrap();
+
+    let mut ok = audited.is_ok();
+    let sum = total(&stm, conf.accounts);
+    if sum != expected {
+        eprintln!("final audit: total {} (expected {})", sum, expected);
+        ok = false;
+    }
+
+    let stats = stm.stats().unwrap();
+    println!(
+        "threads={} accounts={} hot={} transfers={} in {:?}",
+        conf.threads,
+        conf.accounts,
+        conf.hot,
+        conf.threads * conf.iterations,
+        elapsed
+    );
+    println!(
+        "commits={} aborts={} restarts={} audits={} {}",
+        stats.commits,
+        stats.aborts,
+        stats.restarts(),
+        audited.unwrap_or_else(|n| n),
+        if ok { "ok" } else { "FAILED" }
+    );
+    if !ok {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,173 @@
+/*
+ * The C bindings end to end: writer threads move amounts between two
+ * accounts while a reader checks that their sum never changes, then the
+ * error codes are checked one by one.
+ *
+ *   cargo rustc --release --lib --features ffi --crate-type cdylib
+ *   cc -O2 -pthread -Iinclude examples/ffi.c -Ltarget/release -ltl2 -o target/ffi
+ *   LD_LIBRARY_PATH=target/release target/ffi
+ *
+ * `cargo test --features ffi --test ffi` builds it with build.rs and runs
+ * it as a test.
+ */
+
+#include <pthread.h>
+#include <stdio.h>
+#include <string.h>
+
+#include "tl2.h"
+
+#define THREADS 4
+#define TRANSFERS 100000
+#define TOTAL 1000000
+
+static tl2_stm *stm;
+
+static int load(tl2_txn *txn, size_t addr, int64_t *v) {
+    return tl2_load(txn, addr, (uint8_t *)v);
+}
+
+static int store(tl2_txn *txn, size_t addr, int64_t v) {
+    return tl2_store(txn, addr, (const uint8_t *)&v);
+}
+
+static int init(tl2_txn *txn, void *user_data) {
+    (void)user_data;
+    return store(txn, 0, TOTAL);
+}
+
+struct transfer {
+    size_t from, to;
+    int64_t amount;
+};
+
+static int transfer(tl2_txn *txn, void *user_data) {
+    const struct transfer *t = user_data;
+    int64_t from, to;
+    int rc;
+    if ((rc = load(txn, t->from, &from)) != TL2_OK) return rc;
+    if ((rc = load(txn, t->to, &to)) != TL2_OK) return rc;
+    if ((rc = store(txn, t->from, from - t->amount)) != TL2_OK) return rc;
+    return store(txn, t->to, to + t->amount);
+}
+
+static int sum(tl2_txn *txn, void *user_data) {
+    int64_t a, b;
+    int rc;
+    if ((rc = load(txn, 0, &a)) != TL2_OK) return rc;
+    if ((rc = load(txn, 8, &b)) != TL2_OK) return rc;
+    *(int64_t *)user_data = a + b;
+    return TL2_OK;
+}
+
+static void *writer(void *arg) {
+    unsigned seed = (unsigned)(uintptr_t)arg;
+    for (int i = 0; i < TRANSFERS; i++) {
+        seed = seed * 1103515245 + 12345;
+        struct transfer t = {0, 8, (int64_t)(seed >> 16) % 100};
+        if (seed & 1) {
+            t.from = 8;
+            t.to = 0;
+        }
+        if (tl2_write_txn(stm, transfer, &t) != TL2_OK) {
+            fprintf(stderr, "transfer failed\n");
+            return (void *)1;
+        }
+    }
+    return NULL;
+}
+
+static volatile int done;
+
+static void *checker(void *arg) {
+    long *checks = arg;
+    while (!done) {
+        int64_t s;
+        if (tl2_read_txn(stm, sum, &s) != TL2_OK || s != TOTAL) {
+            fprintf(stderr, "sum is %lld\n", (long long)s);
+            return (void *)1;
+        }
+        ++*checks;
+    }
+    return NULL;
+}
+
+static int misaligned(tl2_txn *txn, void *user_data) {
+    int64_t v;
+    (void)user_data;
+    return load(txn, 4, &v) == TL2_INVALID && load(txn, 16, &v) == TL2_INVALID
+               ? TL2_OK
+               : TL2_ABORT;
+}
+
+static int write_in_read(tl2_txn *txn, void *user_data) {
+    (void)user_data;
+    return store(txn, 0, 1) == TL2_INVALID ? TL2_OK : TL2_ABORT;
+}
+
+static int give_up(tl2_txn *txn, void *user_data) {
+    (void)txn;
+    (void)user_data;
+    return TL2_ABORT;
+}
+
+static int again(tl2_txn *txn, void *user_data) {
+    (void)txn;
+    (void)user_data;
+    return TL2_RETRY;
+}
+
+static int unknown(tl2_txn *txn, void *user_data) {
+    (void)txn;
+    (void)user_data;
+    return 42;
+}
+
+#define EXPECT(call, code)                                                  \
+    do {                                                                    \
+        int rc_ = (call);                                                   \
+        if (rc_ != (code)) {                                                \
+            fprintf(stderr, "%s = %d, want %d\n", #call, rc_, (code));      \
+            return 1;                                                       \
+        }                                                                   \
+    } while (0)
+
+int main(void) {
+    EXPECT(tl2_stm_new(12) == NULL, 1);
+    stm = tl2_stm_new(16);
+    EXPECT(stm != NULL, 1);
+    EXPECT(tl2_write_txn(stm, init, NULL), TL2_OK);
+
+    pthread_t writers[THREADS], reader;
+    long checks = 0;
+    void *failed = NULL;
+    pthread_create(&reader, NULL, checker, &checks);
+    for (uintptr_t i = 0; i < THREADS; i++)
+        pthread_create(&writers[i], NULL, writer, (void *)(i + 1));
+    for (int i = 0; i < THREADS; i++) {
+        void *r;
+        pthread_join(writers[i], &r);
+        failed = failed ? failed : r;
+    }
+    done = 1;
+    void *r;
+    pthread_join(reader, &r);
+    if (failed || r) return 1;
+
+    int64_t s;
+    EXPECT(tl2_read_txn(stm, sum, &s), TL2_OK);
+    EXPECT(s == TOTAL, 1);
+
+    EXPECT(tl2_write_txn(stm, misaligned, NULL), TL2_OK);
+    EXPECT(tl2_read_txn(stm, write_in_read, NULL), TL2_OK);
+    EXPECT(tl2_write_txn(stm, give_up, NULL), TL2_ABORT);
+    EXPECT(tl2_write_txn(stm, again, NULL), TL2_RETRY);
+    EXPECT(tl2_read_txn(stm, again, NULL), TL2_RETRY);
+    EXPECT(tl2_write_txn(stm, unknown, NULL), TL2_INVALID);
+    EXPECT(tl2_write_txn(stm, NULL, NULL), TL2_INVALID);
+    EXPECT(tl2_load(NULL, 0, (uint8_t *)&s), TL2_INVALID);
+
+    tl2_stm_free(stm);
+    printf("ok: %d transfers, %ld checks\n", THREADS * TRANSFERS, checks);
+    return 0;
+}
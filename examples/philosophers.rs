@@ -0,0 +1,282 @@
+// Dining philosophers: each picks up both chopsticks in one write
+// transaction, so there is no lock order to get wrong and no deadlock.
+// `--strategy` picks how a philosopher waits when a neighbour holds one:
+//
+//   retry     give up the transaction and try again at once
+//   blocking  return `Retry` and sleep until another transaction commits
+//   backoff   give up and sleep, twice as long each time, up to a millisecond
+//
+//     cargo run --release --example philosophers -- --philosophers 8 --strategy blocking
+//
+// `--contention polite|karma|greedy` also sets a contention manager, which
+// decides what a philosopher does about a chopstick another is committing.
+//
+// A watchdog fails the run if a philosopher goes `--starve-millis` without
+// a meal, an observer checks that chopsticks are only ever held in pairs,
+// and at the end every philosopher must have eaten `--meals` times.
+
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tl2::{load, store, ContentionManager, Greedy, Karma, Polite, STMResult, WriteTrans, STM};
+
+#[derive(Clone, Copy, Debug)]
+enum Strategy {
+    Retry,
+    Blocking,
+    Backoff,
+}
+
+struct Config {
+    philosophers: usize,
+    meals: u64,
+    strategy: Strategy,
+    starve: Duration,
+    contention: Option<(&'static str, Arc<dyn ContentionManager>)>,
+}
+
+fn parse_args() -> Config {
+    let mut conf = Config {
+        philosophers: 5,
+        meals: 10_000,
+        strategy: Strategy::Retry,
+        starve: Duration::from_secs(5),
+        contention: None,
+    };
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    for pair in args.chunks(2) {
+        let val = pair.get(1).unwrap_or_else(|| usage());
+        match pair[0].as_str() {
+            "--philosophers" => conf.philosophers = parse(val),
+            "--meals" => conf.meals = parse(val),
+            "--starve-millis" => conf.starve = Duration::from_millis(parse(val)),
+            "--strategy" => {
+                conf.strategy = match val.as_str() {
+                    "retry" => Strategy::Retry,
+                    "blocking" => Strategy::Blocking,
+                    "backoff" => Strategy::Backoff,
+                    _ => usage(),
+                }
+            }
+            "--contention" => {
+                conf.contention = Some(match val.as_str() {
+                    "polite" => ("polite", Arc::new(Polite::default())),
+                    "karma" => ("karma", Arc::new(Karma::default())),
+                    "greedy" => ("greedy", Arc::new(Greedy::default())),
+                    _ => usage(),
+                })
+            }
+            _ => usage(),
+        }
+    }
+    if conf.philosophers < 2 {
+        usage();
+    }
+    conf
+}
+
+fn parse<T: std::str::FromStr>(val: &str) -> T {
+    val.parse().unwrap_or_else(|_| usage())
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: philosophers [--philosophers N (>= 2)] [--meals M] \
+         [--strategy retry|blocking|backoff] [--starve-millis T] \
+         [--contention polite|karma|greedy]"
+    );
+    process::exit(2);
+}
+
+// Memory: one stripe per chopstick, then one meal counter per philosopher.
+fn chopstick(n: usize) -> usize {
+    8 * n
+}
+
+fn meals_addr(philosophers: usize, n: usize) -> usize {
+    8 * (philosophers + n)
+}
+
+// Take both chopsticks if both are free.
+fn try_pickup(tr: &mut WriteTrans, left: usize, right: usize) -> STMResult<bool> {
+    let mut l = load!(tr, left);
+    let mut r = load!(tr, right);
+    if l[0] != 0 || r[0] != 0 {
+        return STMResult::Ok(false);
+    }
+    l[0] = 1;
+    r[0] = 1;
+    store!(tr, left, l);
+    store!(tr, right, r);
+    STMResult::Ok(true)
+}
+
+fn pickup(stm: &STM, strategy: Strategy, left: usize, right: usize) {
+    match strategy {
+        Strategy::Retry => {
+            while !stm
+                .write_transaction_labeled("pickup", |tr| try_pickup(tr, left, right))
+                .unwrap()
+            {
+                thread::yield_now();
+            }
+        }
+        Strategy::Blocking => {
+            stm.write_transaction_blocking(|tr| match try_pickup(tr, left, right) {
+                STMResult::Ok(false) => STMResult::Retry,
+                other => other,
+            })
+            .unwrap();
+        }
+        Strategy::Backoff => {
+            let mut pause = Duration::from_micros(1);
+            while !stm
+                .write_transaction_labeled("pickup", |tr| try_pickup(tr, left, right))
+                .unwrap()
+            {
+                thread::sleep(pause);
+                pause = (pause * 2).min(Duration::from_millis(1));
+            }
+        }
+    }
+}
+
+// Put both chopsticks down and count the meal.
+fn release(stm: &STM, left: usize, right: usize, meals: usize) {
+    stm.write_transaction_labeled("release", |tr| {
+        let n = u64::from_le_bytes(load!(tr, meals));
+        store!(tr, left, [0; 8]);
+        store!(tr, right, [0; 8]);
+        store!(tr, meals, (n + 1).to_le_bytes());
+        STMResult::Ok(())
+    })
+    .unwrap();
+}
+
+fn main() {
+    let conf = parse_args();
+    let n = conf.philosophers;
+    let mut builder = STM::builder().capacity(16 * n).stats(true);
+    if let Some((_, cm)) = &conf.contention {
+        builder = builder.contention_manager(cm.clone());
+    }
+    let stm = Arc::new(builder.build());
+    // meals eaten so far, for the watchdog
+    let progress: Arc<Vec<AtomicU64>> = Arc::new((0..n).map(|_| AtomicU64::new(0)).collect());
+
+    let start = Instant::now();
+    let mut philosophers = Vec::new();
+    for i in 0..n {
+        let stm = stm.clone();
+        let progress = progress.clone();
+        let (strategy, meals) = (conf.strategy, conf.meals);
+        philosophers.push(thread::spawn(move || {
+            let (left, right) = (chopstick(i), chopstick((i + 1) % n));
+            for m in 1..=meals {
+                pickup(&stm, strategy, left, right);
+                release(&stm, left, right, meals_addr(n, i));
+                progress[i].store(m, Ordering::Relaxed);
+            }
+        }));
+    }
+
+    let done = Arc::new(AtomicBool::new(false));
+    let observer = {
+        let stm = stm.clone();
+        let done = done.clone();
+        thread::spawn(move || {
+            let mut looks = 0u64;
+            while !done.load(Ordering::Relaxed) {
+                let held = stm
+                    .read_transaction_labeled("observe", |tr| {
+                        let mut held = 0;
+                        for i in 0..n {
+                            held += load!(tr, chopstick(i))[0] as usize;
+                        }
+                        STMResult::Ok(held)
+                    })
+                    .unwrap();
+                if !held.is_multiple_of(2) {
+                    eprintln!("observer: {} chopsticks held, not in pairs", held);
+                    process::exit(1);
+                }
+                looks += 1;
+                thread::sleep(Duration::from_micros(100));
+            }
+            looks
+        })
+    };
+
+    let watchdog = {
+        let progress = progress.clone();
+        let done = done.clone();
+        let (meals, starve) = (conf.meals, conf.starve);
+        thread::spawn(move || {
+            let mut seen: Vec<(u64, Instant)> = (0..n).map(|_| (0, Instant::now())).collect();
+            while !done.load(Ordering::Relaxed) {
+                for (i, (last, at)) in seen.iter_mut().enumerate() {
+                    let now = progress[i].load(Ordering::Relaxed);
+                    if now != *last || now == meals {
+                        *last = now;
+                        *at = Instant::now();
+                    } else if at.elapsed() > starve {
+                        eprintln!(
+                            "watchdog: philosopher {} starved for {:?} after {} meals",
+                            i,
+                            at.elapsed(),
+                            now
+                        );
+                        process::exit(1);
+                    }
+                }
+                thread::sleep(starve / 10);
+            }
+        })
+    };
+
+    for th in philosophers {
+        th.join().unwrap();
+    }
+    let elapsed = start.elapsed();
+    done.store(true, Ordering::Relaxed);
+    let looks = observer.join().unwrap();
+    watchdog.join().unwrap();
+
+    let eaten = stm
+        .read_transaction(|tr| {
+            let mut eaten = Vec::with_capacity(n);
+            for i in 0..n {
+                eaten.push(u64::from_le_bytes(load!(tr, meals_addr(n, i))));
+            }
+            STMResult::Ok(eaten)
+        })
+        .unwrap();
+    let ok = eaten.iter().all(|&m| m == conf.meals);
+
+    let stats = stm.stats().unwrap();
+    println!(
+        "philosophers={} meals={} strategy={:?} contention={} in {:?}",
+        n,
+        conf.meals,
+        conf.strategy,
+        conf.contention.as_ref().map_or("none", |(name, _)| name),
+        elapsed
+    );
+    println!(
+        "commits={} restarts={} observations={} {}",
+        stats.commits,
+        stats.restarts(),
+        looks,
+        if ok { "ok" } else { "FAILED" }
+    );
+    for (label, stats) in stm.stats_by_label().unwrap_or_default() {
+        println!("{}: {:?}", label.unwrap_or("(unlabeled)"), stats);
+    }
+    if !ok {
+        eprintln!("meals eaten: {:?} (expected {} each)", eaten, conf.meals);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,91 @@
+// Crash recovery with `STMBuilder::pmem`: a child process moves units
+// between two stripes and is made to crash in the middle of a commit,
+// after one of the two stripes is written back. Reopened, the file breaks
+// the invariant until `STM::recover_pmem` undoes the torn commit.
+//
+//     cargo run --release --features pmem --example pmem
+
+use std::env;
+use std::process::{self, Command};
+use tl2::{load, store, PmemStorage, STMResult, STM};
+
+const TOTAL: u64 = 1_000_000;
+const SIZE: usize = 64;
+
+fn open(path: &str, crash_after: Option<usize>) -> STM {
+    let mut storage = PmemStorage::open(path, SIZE).expect("open the pmem file");
+    if let Some(n) = crash_after {
+        storage = storage.crash_after(n);
+    }
+    let mut stm = STM::builder().pmem(storage).build();
+    stm.recover_pmem();
+    stm
+}
+
+fn transfer(stm: &STM, amount: u64) {
+    stm.write_transaction(|tr| {
+        let a = u64::from_le_bytes(load!(tr, 0));
+        let b = u64::from_le_bytes(load!(tr, 8));
+        store!(tr, 0, (a - amount).to_le_bytes());
+        store!(tr, 8, (b + amount).to_le_bytes());
+        STMResult::Ok(())
+    });
+}
+
+fn balances(stm: &STM) -> (u64, u64) {
+    stm.read_transaction(|tr| {
+        let a = u64::from_le_bytes(load!(tr, 0));
+        let b = u64::from_le_bytes(load!(tr, 8));
+        STMResult::Ok((a, b))
+    })
+    .unwrap()
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let [cmd, path] = &args[..] {
+        if cmd == "crash" {
+            let stm = open(path, None);
+            for _ in 0..100 {
+                transfer(&stm, 1);
+            }
+            drop(stm);
+            transfer(&open(path, Some(1)), 500);
+            unreachable!("the commit did not crash");
+        }
+    }
+
+    let path = env::temp_dir().join(format!("tl2-pmem-{}", process::id()));
+    let path = path.to_str().unwrap();
+    let stm = open(path, None);
+    stm.write_transaction(|tr| {
+        store!(tr, 0, TOTAL.to_le_bytes());
+        STMResult::Ok(())
+    });
+    drop(stm);
+
+    let status = Command::new(env::current_exe().unwrap())
+        .args(["crash", path])
+        .status()
+        .expect("spawn a child");
+    println!("child: {}", status);
+
+    let storage = PmemStorage::open(path, SIZE).unwrap();
+    let mut stm = STM::builder().pmem(storage).build();
+    let (a, b) = balances(&stm);
+    println!("after the crash: {} + {} = {}", a, b, a + b);
+    let restored = stm.recover_pmem();
+    let (a, b) = balances(&stm);
+    println!(
+        "recovered {} stripe(s): {} + {} = {}",
+        restored,
+        a,
+        b,
+        a + b
+    );
+    std::fs::remove_file(path).unwrap();
+
+    if status.success() || restored != 1 || a + b != TOTAL || b != 100 {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,137 @@
+// The two-location invariant across processes: child processes move units
+// between stripes of a shared memory object while the parent checks the
+// total, then a child is killed while holding stripe locks and the parent
+// recovers them.
+//
+//     cargo run --release --features shm --example shm
+
+use std::env;
+use std::process::{self, Child, Command};
+use std::thread;
+use std::time::{Duration, Instant};
+use tl2::{load, store, STMResult, STM};
+
+const TOTAL: u64 = 1_000_000;
+const STRIPES: usize = 4;
+const CHILDREN: usize = 3;
+
+fn sum(stm: &STM) -> u64 {
+    stm.read_transaction(|tr| {
+        let mut s = 0;
+        for i in 0..STRIPES {
+            s += u64::from_le_bytes(load!(tr, 8 * i));
+        }
+        STMResult::Ok(s)
+    })
+    .unwrap()
+}
+
+// Transfers between the stripes until `millis` have passed.
+fn transfers(name: &str, seed: u64, millis: u64) {
+    let stm = STM::attach_shared(name).expect("attach");
+    let mut x = seed | 1;
+    let deadline = Instant::now() + Duration::from_millis(millis);
+    while Instant::now() < deadline {
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        let from = 8 * (x as usize % STRIPES);
+        let to = 8 * ((x >> 8) as usize % STRIPES);
+        let amount = (x >> 16) % 100;
+        stm.write_transaction(|tr| {
+            let a = u64::from_le_bytes(load!(tr, from));
+            if a < amount || from == to {
+                return STMResult::Ok(());
+            }
+            let b = u64::from_le_bytes(load!(tr, to));
+            store!(tr, from, (a - amount).to_le_bytes());
+            store!(tr, to, (b + amount).to_le_bytes());
+            STMResult::Ok(())
+        });
+    }
+}
+
+// Lock stripes 0 and 8 and never let go.
+fn hang(name: &str) {
+    let stm = STM::attach_shared(name).expect("attach");
+    stm.write_transaction(|tr| {
+        let a = u64::from_le_bytes(load!(tr, 0));
+        let b = u64::from_le_bytes(load!(tr, 8));
+        store!(tr, 0, b.to_le_bytes());
+        store!(tr, 8, a.to_le_bytes());
+        tr.commit_when(|_| loop {
+            thread::sleep(Duration::from_secs(1));
+        });
+        STMResult::Ok(())
+    });
+}
+
+fn spawn(args: &[&str]) -> Child {
+    Command::new(env::current_exe().unwrap())
+        .args(args)
+        .spawn()
+        .expect("spawn a child")
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["transfers", name, seed] => return transfers(name, seed.parse().unwrap(), 500),
+        ["hang", name] => return hang(name),
+        [] => {}
+        _ => {
+            eprintln!("usage: shm");
+            process::exit(2);
+        }
+    }
+
+    let name = format!("/tl2-example-{}", process::id());
+    let stm = STM::create_shared(&name, 8 * STRIPES).expect("create");
+    stm.write_transaction(|tr| {
+        store!(tr, 0, TOTAL.to_le_bytes());
+        STMResult::Ok(())
+    });
+
+    let mut ok = true;
+    let children: Vec<_> = (0..CHILDREN)
+        .map(|n| spawn(&["transfers", &name, &(n as u64 + 1).to_string()]))
+        .collect();
+    let mut snapshots = 0;
+    for mut child in children {
+        while child.try_wait().unwrap().is_none() {
+            let s = sum(&stm);
+            if s != TOTAL {
+                eprintln!("invariant violated: sum={} (expected {})", s, TOTAL);
+                ok = false;
+            }
+            snapshots += 1;
+        }
+        ok &= child.wait().unwrap().success();
+    }
+    println!(
+        "{} processes: snapshots={} sum={}",
+        CHILDREN,
+        snapshots,
+        sum(&stm)
+    );
+
+    // a dead process's locks block everyone until recovered
+    let mut child = spawn(&["hang", &name]);
+    while stm.locked_stripes().len() < 2 {
+        thread::sleep(Duration::from_millis(1));
+    }
+    child.kill().unwrap();
+    child.wait().unwrap();
+    let released = stm.recover_dead_owner(child.id());
+    let s = sum(&stm);
+    println!(
+        "recovered {} locks of a killed process: sum={}",
+        released, s
+    );
+    ok &= released == 2 && s == TOTAL && stm.locked_stripes().is_empty();
+
+    STM::unlink_shared(&name).unwrap();
+    if !ok {
+        process::exit(1);
+    }
+}
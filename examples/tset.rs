@@ -0,0 +1,97 @@
+// A concurrent set in STM memory: every thread inserts its own keys and
+// removes the odd ones again, while a reader checks that a key is never a
+// member unless someone inserted it.
+//
+//     cargo run --release --example tset
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tl2::{STMResult, TSet, STM};
+
+const THREADS: u64 = 4;
+const KEYS: u64 = 200; // per thread
+
+fn main() {
+    let stm = Arc::new(STM::builder().capacity(64 * 1024).build());
+    let set = Arc::new(TSet::<u64>::new(0, 64 * 1024));
+    let done = Arc::new(AtomicBool::new(false));
+
+    let mut workers = Vec::new();
+    for t in 0..THREADS {
+        let stm = stm.clone();
+        let set = set.clone();
+        workers.push(std::thread::spawn(move || {
+            for k in t * KEYS..(t + 1) * KEYS {
+                let added = stm
+                    .write_transaction(|tr| match set.insert(tr, k) {
+                        Some(Ok(added)) => STMResult::Ok(added),
+                        Some(Err(_)) => STMResult::Abort,
+                        None => STMResult::Retry,
+                    })
+                    .unwrap();
+                assert!(added, "{} was already a member", k);
+            }
+
+            for k in (t * KEYS..(t + 1) * KEYS).filter(|k| k % 2 == 1) {
+                let removed = stm
+                    .write_transaction(|tr| match set.remove(tr, &k) {
+                        Some(removed) => STMResult::Ok(removed),
+                        None => STMResult::Retry,
+                    })
+                    .unwrap();
+                assert!(removed, "{} was not a member", k);
+            }
+        }));
+    }
+
+    // keys at or above THREADS * KEYS are never inserted
+    let reader = {
+        let stm = stm.clone();
+        let set = set.clone();
+        let done = done.clone();
+        std::thread::spawn(move || {
+            let mut checks = 0;
+            while !done.load(Ordering::Relaxed) {
+                let phantom = THREADS * KEYS + checks % 100;
+                let found = stm
+                    .read_transaction(|tr| match set.contains(tr, &phantom) {
+                        Some(found) => STMResult::Ok(found),
+                        None => STMResult::Retry,
+                    })
+                    .unwrap();
+                assert!(!found, "phantom member {}", phantom);
+                checks += 1;
+            }
+            checks
+        })
+    };
+
+    for th in workers {
+        th.join().unwrap();
+    }
+    done.store(true, Ordering::Relaxed);
+    let checks = reader.join().unwrap();
+
+    let (len, members) = stm
+        .read_transaction(|tr| {
+            let len = match set.len(tr) {
+                Some(len) => len,
+                None => return STMResult::Retry,
+            };
+            let mut members = Vec::new();
+            for k in 0..THREADS * KEYS {
+                match set.contains(tr, &k) {
+                    Some(true) => members.push(k),
+                    Some(false) => (),
+                    None => return STMResult::Retry,
+                }
+            }
+            STMResult::Ok((len, members))
+        })
+        .unwrap();
+
+    assert_eq!(len, members.len() as u64);
+    assert!(members.iter().all(|k| k % 2 == 0));
+    assert_eq!(len, THREADS * KEYS / 2);
+    println!("{} members, {} phantom checks: ok", len, checks);
+}
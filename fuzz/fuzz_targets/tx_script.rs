@@ -0,0 +1,13 @@
+// Interleaved transactions decoded from the input, checked against a
+// model by `TxScript::run`.
+//
+//     cargo +nightly fuzz run tx_script
+//
+// `corpus/tx_script` starts from seeds of the known tricky interleavings.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tl2::TxScript;
+
+fuzz_target!(|data: &[u8]| TxScript::decode(data).run());
@@ -0,0 +1,119 @@
+/* Generated by cbindgen from src/ffi.rs; do not edit. */
+
+#ifndef TL2_H
+#define TL2_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Success; from a callback, commit.
+ */
+#define TL2_OK 0
+
+/**
+ * The attempt hit a conflict; from a callback, restart. From
+ * `tl2_write_txn` or `tl2_read_txn`, the callback returned it without a
+ * conflict.
+ */
+#define TL2_RETRY 1
+
+/**
+ * From a callback, give up. From `tl2_write_txn` or `tl2_read_txn`, the
+ * transaction was given up.
+ */
+#define TL2_ABORT 2
+
+/**
+ * A null pointer, an address that is not 8-byte aligned or lies outside
+ * the memory, a store in a read transaction, or an unknown status code
+ * returned by a callback.
+ */
+#define TL2_INVALID 3
+
+/**
+ * The library panicked; the transaction was given up.
+ */
+#define TL2_PANIC 4
+
+typedef struct tl2_stm tl2_stm;
+
+/**
+ * A running transaction, valid during the callback it is passed to.
+ */
+typedef struct tl2_txn tl2_txn;
+
+/**
+ * Body of a transaction, see the module docs.
+ */
+typedef int (*tl2_txn_fn)(tl2_txn *txn, void *user_data);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * A zeroed STM of `size` bytes, a multiple of 8, or null if `size` is
+ * not. Free it with `tl2_stm_free`. It may be shared between threads.
+ */
+tl2_stm *tl2_stm_new(size_t size);
+
+/**
+ * Free an STM from `tl2_stm_new`. Null is ignored.
+ *
+ * # Safety
+ *
+ * `stm` is null or came from `tl2_stm_new` and is not used afterwards;
+ * no transaction may be running on it.
+ */
+void tl2_stm_free(tl2_stm *stm);
+
+/**
+ * Run `f` as a write transaction until it commits or gives up.
+ *
+ * # Safety
+ *
+ * `stm` is null or a live STM from `tl2_stm_new`; `f` may be called with
+ * `user_data` any number of times.
+ */
+int tl2_write_txn(const tl2_stm *stm, tl2_txn_fn f, void *user_data);
+
+/**
+ * Run `f` as a read transaction until it completes or gives up.
+ * `tl2_store` fails in it.
+ *
+ * # Safety
+ *
+ * As for `tl2_write_txn`.
+ */
+int tl2_read_txn(const tl2_stm *stm, tl2_txn_fn f, void *user_data);
+
+/**
+ * Copy the 8 bytes at `addr` into `out`. `TL2_RETRY` means the attempt
+ * is doomed and the callback should return `TL2_RETRY`.
+ *
+ * # Safety
+ *
+ * `txn` is null or the handle passed to the running callback; `out` is
+ * null or points to 8 writable bytes.
+ */
+int tl2_load(tl2_txn *txn, size_t addr, uint8_t *out);
+
+/**
+ * Store the 8 bytes at `val` at `addr` when the transaction commits.
+ *
+ * # Safety
+ *
+ * `txn` is null or the handle passed to the running callback; `val` is
+ * null or points to 8 readable bytes.
+ */
+int tl2_store(tl2_txn *txn, size_t addr, const uint8_t *val);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* TL2_H */
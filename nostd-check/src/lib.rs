@@ -0,0 +1,72 @@
+// The two-location invariant of `tests/invariant.rs` against tl2 built
+// without default features: writers move amounts between two stripes and
+// readers check that the sum never changes. The library is `no_std`; the
+// binary is the shim that brings threads.
+//
+//     cargo run -p nostd-check --release
+//
+// Build it on its own: with `--workspace` cargo unifies features and tl2
+// gets `std` from the root package. tests/target.rs builds the library for
+// riscv32imac-unknown-none-elf, which has no std to fall back on:
+//
+//     cargo build -p nostd-check --lib --target riscv32imac-unknown-none-elf
+
+#![no_std]
+
+use tl2::{load, store, STMResult, STM};
+
+pub const TOTAL: u64 = 1000;
+
+const A: usize = 0;
+const B: usize = 8;
+
+// xorshift64*
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+}
+
+pub fn setup(stm: &STM) {
+    stm.write_transaction(|tr| {
+        store!(tr, A, TOTAL.to_le_bytes());
+        STMResult::Ok(())
+    });
+}
+
+// Move a random amount from one stripe to the other.
+pub fn transfer(stm: &STM, rng: &mut Rng) {
+    let (from, to) = if rng.next() & 1 == 0 { (A, B) } else { (B, A) };
+    let amount = rng.next() % 100;
+    stm.write_transaction(|tr| {
+        let a = u64::from_le_bytes(load!(tr, from));
+        if a < amount {
+            return STMResult::Ok(());
+        }
+        let b = u64::from_le_bytes(load!(tr, to));
+        store!(tr, from, (a - amount).to_le_bytes());
+        store!(tr, to, (b + amount).to_le_bytes());
+        STMResult::Ok(())
+    });
+}
+
+// Whether both stripes read at one version add up to `TOTAL`.
+pub fn check(stm: &STM) -> bool {
+    let sum = stm
+        .read_transaction(|tr| {
+            let a = u64::from_le_bytes(load!(tr, A));
+            let b = u64::from_le_bytes(load!(tr, B));
+            STMResult::Ok(a + b)
+        })
+        .unwrap();
+    sum == TOTAL
+}
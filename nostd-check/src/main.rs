@@ -0,0 +1,56 @@
+// The shim: std threads driving the `no_std` checks.
+
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+use nostd_check::{check, setup, transfer, Rng};
+use tl2::STM;
+
+const WRITERS: u64 = 4;
+const TRANSFERS: usize = 100_000;
+
+fn main() {
+    let stm = STM::new();
+    setup(&stm);
+
+    let done = AtomicBool::new(false);
+    let mut checks = 0u64;
+    let ok = thread::scope(|s| {
+        let writers: Vec<_> = (0..WRITERS)
+            .map(|n| {
+                let stm = &stm;
+                s.spawn(move || {
+                    let mut rng = Rng::new(n);
+                    for _ in 0..TRANSFERS {
+                        transfer(stm, &mut rng);
+                    }
+                })
+            })
+            .collect();
+        let stop = s.spawn(|| {
+            for w in writers {
+                w.join().unwrap();
+            }
+            done.store(true, Ordering::Relaxed);
+        });
+
+        let mut ok = true;
+        while ok && !done.load(Ordering::Relaxed) {
+            ok = check(&stm);
+            checks += 1;
+        }
+        stop.join().unwrap();
+        ok && check(&stm)
+    });
+
+    if !ok {
+        eprintln!("invariant violated after {} checks", checks);
+        process::exit(1);
+    }
+    println!(
+        "ok: {} transfers, {} checks",
+        WRITERS as usize * TRANSFERS,
+        checks
+    );
+}
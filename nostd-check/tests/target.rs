@@ -0,0 +1,40 @@
+// Build the library for a target that has no std at all, so that anything
+// in tl2 reaching for std without the feature fails here instead of
+// slipping through on a host where std is always there.
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+const TARGET: &str = "riscv32imac-unknown-none-elf";
+
+#[test]
+fn builds_for_a_no_std_target() {
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".into());
+    let sysroot = Command::new(rustc)
+        .args(["--print", "sysroot"])
+        .output()
+        .unwrap();
+    let sysroot = String::from_utf8(sysroot.stdout).unwrap();
+    if !Path::new(sysroot.trim())
+        .join("lib/rustlib")
+        .join(TARGET)
+        .exists()
+    {
+        eprintln!("skipped: `rustup target add {}` to run it", TARGET);
+        return;
+    }
+
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+    let out = Command::new(env!("CARGO"))
+        .current_dir(root)
+        .args(["build", "-p", "nostd-check", "--lib", "--target", TARGET])
+        .env("CARGO_TARGET_DIR", root.join("target/nostd"))
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+}
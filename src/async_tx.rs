@@ -0,0 +1,101 @@
+// Write transactions for async callers (the `tokio` feature). The body
+// stays synchronous and runs whole inside one poll, so a WriteTrans and its
+// locks never live across an await, nor do the serialization token or a
+// breaker turn, given back on every restart. The runs are nonblocking:
+// anything a run would spin or park for restarts it instead, and the waits
+// between attempts yield to the executor.
+
+use std::pin::pin;
+
+use tokio::sync::Notify;
+
+use crate::sync::{fence, AtomicUsize, Ordering};
+use crate::tl2::{STMResult, Step, WriteRun, WriteTrans, STM};
+
+// Yields before the next attempt after a conflict, doubling up to this.
+const MAX_BACKOFF: u32 = 64;
+
+// Wakes transactions waiting out a `Retry` whenever something commits.
+pub(crate) struct Wakeup {
+    notify: Notify,
+    waiting: AtomicUsize,
+}
+
+// A registered waiter, unregistered on drop, so a cancelled future leaves
+// nothing behind.
+struct Waiter<'a>(&'a AtomicUsize);
+
+impl<'a> Drop for Waiter<'a> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Wakeup {
+    pub(crate) fn new() -> Wakeup {
+        Wakeup {
+            notify: Notify::new(),
+            waiting: AtomicUsize::new(0),
+        }
+    }
+
+    // A waiter registers and then re-runs its body; a committer publishes
+    // and then checks for waiters. The SeqCst fences on both sides make
+    // sure the re-run sees the commit or the committer sees the waiter.
+    fn register(&self) -> Waiter<'_> {
+        self.waiting.fetch_add(1, Ordering::SeqCst);
+        fence(Ordering::SeqCst);
+        Waiter(&self.waiting)
+    }
+
+    // Called after every commit has published its versions.
+    pub(crate) fn committed(&self) {
+        fence(Ordering::SeqCst);
+        if self.waiting.load(Ordering::SeqCst) > 0 {
+            self.notify.notify_waiters();
+        }
+    }
+}
+
+impl STM {
+    /// Like `write_transaction`, for async code. The body still runs
+    /// synchronously and may run several times, but the waits between runs
+    /// give the executor back: a conflict yields, with backoff, as does
+    /// finding a writer slot, the serialization token or the commits paused,
+    /// where a blocking transaction would wait; and a body returning
+    /// `STMResult::Retry` without a conflict waits until another
+    /// transaction commits instead of failing. Dropping the future cancels
+    /// the transaction between runs, with no locks held.
+    pub async fn write_transaction_async<F, R>(&self, f: F) -> Option<R>
+    where
+        F: Fn(&mut WriteTrans) -> STMResult<R>,
+    {
+        let mut run = WriteRun::new(self, None);
+        run.nonblocking = true;
+        let mut backoff = 1;
+        loop {
+            match run.step(&f, true) {
+                Step::Done(r) => return r.ok(),
+                Step::Restart => {
+                    for _ in 0..backoff {
+                        tokio::task::yield_now().await;
+                    }
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+                Step::Wait => {
+                    // register before the re-run so that a commit landing
+                    // in between still wakes us
+                    let _waiter = self.wakeup().register();
+                    let mut notified = pin!(self.wakeup().notify.notified());
+                    notified.as_mut().enable();
+                    match run.step(&f, true) {
+                        Step::Done(r) => return r.ok(),
+                        Step::Restart => (),
+                        Step::Wait => notified.await,
+                    }
+                    backoff = 1;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use alloc::sync::Arc;
+
+use crate::tl2::{check_aligned, STMResult, Trans, TxError, WriteTrans, STM, STRIPE_SIZE};
+use crate::value::StripeValue;
+
+/// A reusable barrier for `n` threads kept in STM memory: a counter of the
+/// threads that arrived and a generation, bumped by the last of them.
+///
+/// Threads waiting for the generation to change sleep until some write
+/// transaction commits and then look again, in a read transaction, so
+/// waiting commits nothing and leaves the clock alone.
+pub struct TxBarrier {
+    stm: Arc<STM>,
+    base: usize,
+    n: u64,
+}
+
+impl TxBarrier {
+    /// Place the barrier for `n` threads at the stripe-aligned address
+    /// `base`; it takes `TxBarrier::size` bytes.
+    pub fn new(stm: Arc<STM>, base: usize, n: usize) -> TxBarrier {
+        assert!(n > 0, "a barrier needs at least one thread");
+        check_aligned(base);
+        base.checked_add(Self::size())
+            .expect("barrier end overflows usize");
+
+        TxBarrier {
+            stm,
+            base,
+            n: n as u64,
+        }
+    }
+
+    /// Bytes taken: the counter and the generation.
+    pub fn size() -> usize {
+        2 * STRIPE_SIZE
+    }
+
+    fn generation_addr(&self) -> usize {
+        self.base + STRIPE_SIZE
+    }
+
+    /// The current generation, bumped every time `n` threads arrived.
+    pub fn generation<R: Trans>(&self, tr: &mut R) -> Option<u64> {
+        tr.load(self.generation_addr()).map(u64::from_stripe)
+    }
+
+    /// Count the caller in, and return the generation it arrived in and
+    /// whether it was the last one to, which resets the counter and bumps
+    /// the generation. Another `n` arrivals in one transaction would
+    /// count as the next generation.
+    pub fn arrive(&self, tr: &mut WriteTrans) -> Option<(u64, bool)> {
+        let gen = self.generation(tr)?;
+        let count = u64::from_stripe(tr.load(self.base)?) + 1;
+        if count < self.n {
+            tr.store(self.base, count.to_stripe());
+            return Some((gen, false));
+        }
+        tr.store(self.base, 0u64.to_stripe());
+        tr.store(self.generation_addr(), (gen + 1).to_stripe());
+        Some((gen, true))
+    }
+
+    /// Arrive and block until all `n` threads have. Returns `true` for
+    /// the last one to arrive, like `std::sync::BarrierWaitResult`, or
+    /// `TxError::Poisoned` if the STM is poisoned.
+    pub fn wait(&self) -> Result<bool, TxError> {
+        let (gen, last) = self
+            .stm
+            .try_write_transaction(|tr| self.arrive(tr).map_or(STMResult::Retry, STMResult::Ok))?;
+        if !last {
+            self.stm
+                .wait_until(|tr| match self.generation(tr) {
+                    Some(g) => STMResult::Ok(g != gen),
+                    None => STMResult::Retry,
+                })
+                .ok_or(TxError::Poisoned)?;
+        }
+        Ok(last)
+    }
+}
@@ -0,0 +1,404 @@
+// A long-running mixed workload for release qualification: transfers
+// between accounts, churn of a `TSet`, a mailbox handed between threads
+// with blocking transactions, and read-only audits. Every `--report-secs`
+// it checks the invariants, samples the clock, the waiter count and the
+// resident set size, and prints a line; any violation dumps the state of
+// the STM and exits 1.
+//
+//     cargo run --release --bin tl2-soak -- --seconds 3600 --clock-jump 1000000000000
+//
+// `--clock-jump N` moves the clock ahead by N at every report, up to 2^32
+// versions short of the lock bit, to reach clock values a real run would
+// take years to.
+
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tl2::{load, AtomicClock, Clock, STMResult, TSet, STM};
+
+const LOCK_BIT: u64 = 1 << 63;
+const BALANCE: u64 = 1_000;
+const SET_VALUES: u64 = 64;
+const SET_BYTES: usize = 4096;
+
+struct Config {
+    seed: u64,
+    threads: usize,
+    duration: Duration,
+    report: Duration,
+    accounts: usize,
+    clock_jump: u64,
+    rss_slack: u64,
+}
+
+fn parse_args() -> Config {
+    let mut conf = Config {
+        seed: 1,
+        threads: 4,
+        duration: Duration::from_secs(30),
+        report: Duration::from_secs(1),
+        accounts: 256,
+        clock_jump: 0,
+        rss_slack: 256,
+    };
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    for pair in args.chunks(2) {
+        let val = pair.get(1).unwrap_or_else(|| usage());
+        match pair[0].as_str() {
+            "--seed" => conf.seed = parse(val),
+            "--threads" => conf.threads = parse(val),
+            "--seconds" => conf.duration = Duration::from_secs(parse(val)),
+            "--report-secs" => conf.report = Duration::from_secs(parse(val)),
+            "--accounts" => conf.accounts = parse(val),
+            "--clock-jump" => conf.clock_jump = parse(val),
+            "--rss-slack-mb" => conf.rss_slack = parse(val),
+            _ => usage(),
+        }
+    }
+    if conf.threads < 2 || conf.accounts < 2 || conf.report.is_zero() {
+        usage();
+    }
+    conf
+}
+
+fn parse<T: std::str::FromStr>(s: &str) -> T {
+    s.parse().unwrap_or_else(|_| usage())
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: tl2-soak [--seed N] [--threads N (>= 2)] [--seconds N] \
+         [--report-secs N (>= 1)] [--accounts N (>= 2)] [--clock-jump N] \
+         [--rss-slack-mb N]"
+    );
+    process::exit(2);
+}
+
+// xorshift64*, seeded per thread so a run can be repeated
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+// The default clock, plus jumps ahead. A clock may skip versions, so a
+// jump only makes the versions larger.
+struct JumpClock(AtomicClock);
+
+impl JumpClock {
+    fn jump(&self, n: u64) {
+        let target = self.sample().saturating_add(n).min(LOCK_BIT - (1 << 32));
+        self.0.set(target.max(self.sample()));
+    }
+}
+
+impl Clock for JumpClock {
+    fn sample(&self) -> u64 {
+        self.0.sample()
+    }
+
+    fn increment(&self) -> u64 {
+        self.0.increment()
+    }
+
+    fn set(&self, v: u64) {
+        self.0.set(v)
+    }
+}
+
+// Memory: the accounts, then the mailbox stripe, then the set.
+struct Layout {
+    accounts: usize,
+    mailbox: usize,
+    set: TSet<u64>,
+}
+
+impl Layout {
+    fn new(accounts: usize) -> Layout {
+        let mailbox = 8 * accounts;
+        Layout {
+            accounts,
+            mailbox,
+            set: TSet::new(mailbox + 8, SET_BYTES),
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        self.mailbox + 8 + SET_BYTES
+    }
+}
+
+// Tokens put into and taken out of the mailbox.
+#[derive(Default)]
+struct Mail {
+    sent: AtomicU64,
+    received: AtomicU64,
+}
+
+fn worker(stm: &STM, lay: &Layout, mail: &Mail, seed: u64, producer: bool, stop: &AtomicBool) {
+    let mut rng = Rng::new(seed);
+    let wait = Duration::from_millis(10);
+    while !stop.load(Ordering::Relaxed) {
+        match rng.next() % 10 {
+            0..=4 => {
+                let from = 8 * rng.below(lay.accounts);
+                let to = 8 * rng.below(lay.accounts);
+                let amount = rng.next() % BALANCE;
+                stm.write_transaction(|tr| {
+                    let a = u64::from_le_bytes(load!(tr, from));
+                    if a < amount || from == to {
+                        return STMResult::Ok(());
+                    }
+                    let b = u64::from_le_bytes(load!(tr, to));
+                    tr.store(from, (a - amount).to_le_bytes());
+                    tr.store(to, (b + amount).to_le_bytes());
+                    STMResult::Ok(())
+                });
+            }
+            5 | 6 => {
+                let v = rng.next() % SET_VALUES;
+                let insert = rng.next() & 1 == 0;
+                stm.write_transaction(|tr| {
+                    let done = if insert {
+                        lay.set
+                            .insert(tr, v)
+                            .map(|r| r.expect("set sized for every value"))
+                    } else {
+                        lay.set.remove(tr, &v)
+                    };
+                    done.map_or(STMResult::Retry, STMResult::Ok)
+                });
+            }
+            7 => {
+                // producers fill the mailbox, consumers empty it, waiting
+                // with a blocking retry until they can
+                let handed = stm.write_transaction_retry_timeout(wait, |tr| {
+                    let full = u64::from_le_bytes(load!(tr, lay.mailbox)) != 0;
+                    if full == producer {
+                        return STMResult::Retry;
+                    }
+                    tr.store(lay.mailbox, (producer as u64).to_le_bytes());
+                    STMResult::Ok(())
+                });
+                if handed.is_ok() {
+                    let n = if producer { &mail.sent } else { &mail.received };
+                    n.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            _ => {
+                let addrs: Vec<usize> = (0..8).map(|_| 8 * rng.below(lay.accounts)).collect();
+                stm.read_transaction(|tr| {
+                    for a in addrs.iter() {
+                        load!(tr, *a);
+                    }
+                    STMResult::Ok(())
+                });
+            }
+        }
+    }
+}
+
+// What the monitor checks, read in one transaction.
+struct Audit {
+    total: u64,
+    set_len: u64,
+    set_members: u64,
+    mailbox: u64,
+}
+
+fn audit(stm: &STM, lay: &Layout) -> Audit {
+    stm.read_transaction(|tr| {
+        let mut total = 0;
+        for i in 0..lay.accounts {
+            total += u64::from_le_bytes(load!(tr, 8 * i));
+        }
+        let mut set_members = 0;
+        for v in 0..SET_VALUES {
+            match lay.set.contains(tr, &v) {
+                Some(true) => set_members += 1,
+                Some(false) => (),
+                None => return STMResult::Retry,
+            }
+        }
+        let set_len = match lay.set.len(tr) {
+            Some(n) => n,
+            None => return STMResult::Retry,
+        };
+        STMResult::Ok(Audit {
+            total,
+            set_len,
+            set_members,
+            mailbox: u64::from_le_bytes(load!(tr, lay.mailbox)),
+        })
+    })
+    .unwrap()
+}
+
+// Resident set size in bytes, from /proc (with 4 KiB pages).
+fn rss() -> Option<u64> {
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    Some(pages * 4096)
+}
+
+fn fail(stm: &STM, why: String) -> ! {
+    eprintln!("VIOLATION: {}", why);
+    eprintln!("clock {} waiters {}", stm.current_version(), stm.waiters());
+    eprintln!("locked stripes {:?}", stm.locked_stripes());
+    for s in stm.check_stalled(Duration::from_secs(1), 1000) {
+        eprintln!("stalled {:?}", s);
+    }
+    for e in stm.recent_events().iter().rev().take(32) {
+        eprintln!("event {:?}", e);
+    }
+    if let Some(stats) = stm.stats() {
+        eprintln!("{}", stats);
+    }
+    process::exit(1);
+}
+
+fn main() {
+    let conf = parse_args();
+    let lay = Arc::new(Layout::new(conf.accounts));
+    let clock = Arc::new(JumpClock(AtomicClock::new()));
+    let stm = Arc::new(
+        STM::builder()
+            .capacity(lay.capacity())
+            .clock(clock.clone())
+            .stats(true)
+            .watchdog(true)
+            .event_ring(256)
+            .build(),
+    );
+    stm.write_transaction(|tr| {
+        for i in 0..lay.accounts {
+            tr.store(8 * i, BALANCE.to_le_bytes());
+        }
+        STMResult::Ok(())
+    });
+    let expected = BALANCE * lay.accounts as u64;
+    let mail = Arc::new(Mail::default());
+    let stop = Arc::new(AtomicBool::new(false));
+
+    let workers: Vec<_> = (0..conf.threads)
+        .map(|n| {
+            let (stm, lay, mail, stop) = (stm.clone(), lay.clone(), mail.clone(), stop.clone());
+            let seed = conf.seed ^ n as u64;
+            std::thread::spawn(move || worker(&stm, &lay, &mail, seed, n % 2 == 0, &stop))
+        })
+        .collect();
+
+    let start = Instant::now();
+    let mut last_clock = stm.current_version();
+    let mut base_rss = None;
+    while start.elapsed() < conf.duration {
+        std::thread::sleep(
+            conf.report
+                .min(conf.duration.saturating_sub(start.elapsed())),
+        );
+        let a = audit(&stm, &lay);
+        if a.total != expected {
+            fail(&stm, format!("total {} (expected {})", a.total, expected));
+        }
+        if a.set_len != a.set_members {
+            fail(
+                &stm,
+                format!("set len {} with {} members", a.set_len, a.set_members),
+            );
+        }
+        let clock_now = stm.current_version();
+        if clock_now < last_clock || clock_now >= LOCK_BIT {
+            fail(&stm, format!("clock {} after {}", clock_now, last_clock));
+        }
+        let waiters = stm.waiters();
+        if waiters > conf.threads {
+            fail(
+                &stm,
+                format!("{} waiters for {} threads", waiters, conf.threads),
+            );
+        }
+        let stalled = stm.check_stalled(Duration::from_secs(10), u32::MAX);
+        if !stalled.is_empty() {
+            fail(&stm, format!("{} transactions stalled", stalled.len()));
+        }
+        // the first sample sets the baseline, after warm-up allocations
+        let rss_now = rss();
+        if let (Some(base), Some(now)) = (base_rss, rss_now) {
+            if now > base + (conf.rss_slack << 20) {
+                fail(&stm, format!("RSS grew from {} to {} bytes", base, now));
+            }
+        }
+        base_rss = base_rss.or(rss_now);
+
+        println!(
+            "{:>6.0}s clock={} waiters={} rss={} set={} mail={}/{}",
+            start.elapsed().as_secs_f64(),
+            clock_now,
+            waiters,
+            rss_now.map_or("-".to_string(), |r| format!("{}K", r >> 10)),
+            a.set_len,
+            mail.sent.load(Ordering::Relaxed),
+            mail.received.load(Ordering::Relaxed),
+        );
+        if conf.clock_jump > 0 {
+            clock.jump(conf.clock_jump);
+        }
+        last_clock = stm.current_version();
+    }
+
+    stop.store(true, Ordering::Relaxed);
+    for w in workers {
+        w.join().unwrap();
+    }
+
+    let a = audit(&stm, &lay);
+    if a.total != expected {
+        fail(
+            &stm,
+            format!("final total {} (expected {})", a.total, expected),
+        );
+    }
+    let (sent, received) = (
+        mail.sent.load(Ordering::Relaxed),
+        mail.received.load(Ordering::Relaxed),
+    );
+    if sent != received + a.mailbox {
+        fail(
+            &stm,
+            format!(
+                "{} sent, {} received, {} in the mailbox",
+                sent, received, a.mailbox
+            ),
+        );
+    }
+    if stm.waiters() != 0 || !stm.locked_stripes().is_empty() || !stm.is_quiescent() {
+        fail(
+            &stm,
+            "state left behind after every thread stopped".to_string(),
+        );
+    }
+    let stats = stm.stats().unwrap();
+    println!(
+        "ok: {:.0}s, {} commits, {} reads, {} restarts, clock {}",
+        start.elapsed().as_secs_f64(),
+        stats.commits,
+        stats.reads,
+        stats.restarts(),
+        stm.current_version()
+    );
+}
@@ -0,0 +1,340 @@
+// A configurable workload for measuring the STM on a machine. Every write
+// transaction reads `--reads` stripes and adds 1 to `--writes` others, so
+// at the end the stripes must sum to the number of increments committed.
+//
+//     cargo run --release --bin tl2-stress -- --threads 8 --skew zipf:0.99
+//
+// The report ends with the ten stripes that made the most transactions
+// restart. `--json` prints it as one JSON object instead.
+
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tl2::{load, Histogram, STMResult, STM};
+
+enum Skew {
+    Uniform,
+    Zipf(f64),
+}
+
+struct Config {
+    seed: u64,
+    threads: usize,
+    duration: Duration,
+    stripes: usize,
+    reads: usize,
+    writes: usize,
+    write_pct: u64,
+    skew: Skew,
+    spin_limit: Option<u32>,
+    json: bool,
+}
+
+fn parse_args() -> Config {
+    let mut conf = Config {
+        seed: 1,
+        threads: 4,
+        duration: Duration::from_secs(1),
+        stripes: 1 << 16,
+        reads: 4,
+        writes: 2,
+        write_pct: 50,
+        skew: Skew::Uniform,
+        spin_limit: None,
+        json: false,
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        if flag == "--json" {
+            conf.json = true;
+            continue;
+        }
+        let val = args.next().unwrap_or_else(|| usage());
+        match flag.as_str() {
+            "--seed" => conf.seed = parse(&val),
+            "--threads" => conf.threads = parse(&val),
+            "--millis" => conf.duration = Duration::from_millis(parse(&val)),
+            "--stripes" => conf.stripes = parse(&val),
+            "--reads" => conf.reads = parse(&val),
+            "--writes" => conf.writes = parse(&val),
+            "--write-pct" => conf.write_pct = parse(&val),
+            "--skew" => {
+                conf.skew = match val.strip_prefix("zipf:") {
+                    Some(theta) => Skew::Zipf(parse(theta)),
+                    None if val == "uniform" => Skew::Uniform,
+                    None => usage(),
+                }
+            }
+            "--backoff" => {
+                conf.spin_limit = match val.strip_prefix("spin:") {
+                    Some(n) => Some(parse(n)),
+                    None if val == "none" => None,
+                    None => usage(),
+                }
+            }
+            _ => usage(),
+        }
+    }
+    if conf.writes == 0 || conf.reads + conf.writes > conf.stripes || conf.write_pct > 100 {
+        usage();
+    }
+    conf
+}
+
+fn parse<T: std::str::FromStr>(s: &str) -> T {
+    s.parse().unwrap_or_else(|_| usage())
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: tl2-stress [--seed N] [--threads N] [--millis N] [--stripes N] \
+         [--reads N] [--writes N (>= 1)] [--write-pct 0..=100] \
+         [--skew uniform|zipf:THETA] [--backoff none|spin:N] [--json]"
+    );
+    process::exit(2);
+}
+
+// xorshift64*, seeded per thread so a run can be repeated
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+// Picks stripe indices, uniformly or with stripe `i` weighted 1/(i+1)^theta.
+enum Picker {
+    Uniform(usize),
+    Zipf(Vec<f64>), // cumulative weights, the last one 1
+}
+
+impl Picker {
+    fn new(skew: &Skew, n: usize) -> Picker {
+        match *skew {
+            Skew::Uniform => Picker::Uniform(n),
+            Skew::Zipf(theta) => {
+                let mut cdf: Vec<f64> = (1..=n)
+                    .scan(0.0, |sum, i| {
+                        *sum += 1.0 / (i as f64).powf(theta);
+                        Some(*sum)
+                    })
+                    .collect();
+                let total = cdf[n - 1];
+                cdf.iter_mut().for_each(|c| *c /= total);
+                Picker::Zipf(cdf)
+            }
+        }
+    }
+
+    fn pick(&self, rng: &mut Rng) -> usize {
+        match self {
+            Picker::Uniform(n) => (rng.next() % *n as u64) as usize,
+            Picker::Zipf(cdf) => {
+                let u = rng.unit();
+                cdf.partition_point(|c| *c < u).min(cdf.len() - 1)
+            }
+        }
+    }
+
+    // `k` distinct stripe addresses.
+    fn addrs(&self, rng: &mut Rng, k: usize, out: &mut Vec<usize>) {
+        out.clear();
+        while out.len() < k {
+            let a = 8 * self.pick(rng);
+            if !out.contains(&a) {
+                out.push(a);
+            }
+        }
+    }
+}
+
+struct Tally {
+    reads: u64,
+    writes: u64,
+    increments: u64,
+}
+
+fn worker(stm: &STM, conf: &Config, picker: &Picker, seed: u64, stop: &AtomicBool) -> Tally {
+    let mut rng = Rng::new(seed);
+    let mut addrs = Vec::new();
+    let mut tally = Tally {
+        reads: 0,
+        writes: 0,
+        increments: 0,
+    };
+    while !stop.load(Ordering::Relaxed) {
+        if rng.next() % 100 < conf.write_pct {
+            picker.addrs(&mut rng, conf.reads + conf.writes, &mut addrs);
+            let (read, write) = addrs.split_at(conf.reads);
+            let done = stm.write_transaction(|tr| {
+                for a in read {
+                    load!(tr, *a);
+                }
+                for a in write {
+                    let v = u64::from_le_bytes(load!(tr, *a));
+                    tr.store(*a, (v + 1).to_le_bytes());
+                }
+                STMResult::Ok(())
+            });
+            if done.is_some() {
+                tally.writes += 1;
+                tally.increments += write.len() as u64;
+            }
+        } else {
+            picker.addrs(&mut rng, conf.reads.max(1), &mut addrs);
+            stm.read_transaction(|tr| {
+                for a in addrs.iter() {
+                    load!(tr, *a);
+                }
+                STMResult::Ok(())
+            });
+            tally.reads += 1;
+        }
+    }
+    tally
+}
+
+fn main() {
+    let conf = Arc::new(parse_args());
+    let mut builder = STM::builder()
+        .capacity(8 * conf.stripes)
+        .stats(true)
+        .conflict_heatmap(true);
+    if let Some(n) = conf.spin_limit {
+        builder = builder.spin_limit(n);
+    }
+    let stm = Arc::new(builder.build());
+    let picker = Arc::new(Picker::new(&conf.skew, conf.stripes));
+    let stop = Arc::new(AtomicBool::new(false));
+
+    let start = Instant::now();
+    let workers: Vec<_> = (0..conf.threads)
+        .map(|n| {
+            let (stm, conf, picker, stop) =
+                (stm.clone(), conf.clone(), picker.clone(), stop.clone());
+            std::thread::spawn(move || worker(&stm, &conf, &picker, conf.seed ^ n as u64, &stop))
+        })
+        .collect();
+    std::thread::sleep(conf.duration);
+    stop.store(true, Ordering::Relaxed);
+    let tallies: Vec<Tally> = workers.into_iter().map(|w| w.join().unwrap()).collect();
+    let elapsed = start.elapsed().as_secs_f64();
+
+    let reads: u64 = tallies.iter().map(|t| t.reads).sum();
+    let writes: u64 = tallies.iter().map(|t| t.writes).sum();
+    let increments: u64 = tallies.iter().map(|t| t.increments).sum();
+    let sum = stm
+        .read_transaction(|tr| {
+            let mut sum = 0;
+            for i in 0..conf.stripes {
+                sum += u64::from_le_bytes(load!(tr, 8 * i));
+            }
+            STMResult::Ok(sum)
+        })
+        .unwrap();
+    let ok = sum == increments;
+
+    let stats = stm.stats().unwrap();
+    let restarts = stats.restarts();
+    let attempts = stats.commits + stats.reads + stats.aborts + restarts;
+    let rate = |n: u64| n as f64 / attempts.max(1) as f64;
+    let latency = stm.latency_histograms().unwrap();
+    let hottest = stm.conflict_heatmap_top(10).unwrap();
+    let phases = [
+        ("execute", latency.execute),
+        ("lock", latency.lock),
+        ("validate", latency.validate),
+        ("publish", latency.publish),
+    ];
+
+    if conf.json {
+        let nanos = |h: &Histogram| {
+            format!(
+                "{{\"p50\":{},\"p95\":{},\"p99\":{}}}",
+                h.p50().as_nanos(),
+                h.p95().as_nanos(),
+                h.p99().as_nanos()
+            )
+        };
+        let latency: Vec<String> = phases
+            .iter()
+            .map(|(name, h)| format!("\"{}\":{}", name, nanos(h)))
+            .collect();
+        let hottest: Vec<String> = hottest
+            .iter()
+            .map(|(addr, n)| format!("{{\"stripe\":{},\"restarts\":{}}}", addr / 8, n))
+            .collect();
+        println!(
+            "{{\"threads\":{},\"seconds\":{:.3},\"ops_per_sec\":{:.0},\"reads\":{},\
+             \"writes\":{},\"restart_rate\":{{\"pre_validation\":{:.6},\
+             \"post_validation\":{:.6},\"lock\":{:.6},\"validation\":{:.6}}},\
+             \"latency_ns\":{{{}}},\"hottest\":[{}],\"invariant\":{}}}",
+            conf.threads,
+            elapsed,
+            (reads + writes) as f64 / elapsed,
+            reads,
+            writes,
+            rate(stats.pre_validation),
+            rate(stats.post_validation),
+            rate(stats.lock),
+            rate(stats.validation),
+            latency.join(","),
+            hottest.join(","),
+            ok
+        );
+    } else {
+        println!(
+            "threads={} {:.2}s: {:.0} ops/s ({} reads, {} writes)",
+            conf.threads,
+            elapsed,
+            (reads + writes) as f64 / elapsed,
+            reads,
+            writes
+        );
+        println!(
+            "restarts per attempt: {:.4} (pre-validation {:.4}, post-validation {:.4}, \
+             lock {:.4}, validation {:.4})",
+            rate(restarts),
+            rate(stats.pre_validation),
+            rate(stats.post_validation),
+            rate(stats.lock),
+            rate(stats.validation)
+        );
+        for (name, h) in phases.iter() {
+            println!(
+                "  {:<8} p50={:?} p95={:?} p99={:?}",
+                name,
+                h.p50(),
+                h.p95(),
+                h.p99()
+            );
+        }
+        println!("hottest stripes:");
+        for (addr, n) in hottest.iter() {
+            println!("  stripe {:<8} {} restarts", addr / 8, n);
+        }
+        println!(
+            "invariant: sum {} (expected {}) {}",
+            sum,
+            increments,
+            if ok { "ok" } else { "FAILED" }
+        );
+    }
+    if !ok {
+        process::exit(1);
+    }
+}
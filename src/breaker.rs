@@ -0,0 +1,138 @@
+// A circuit breaker serializing write transactions while they restart too
+// often, see `STMBuilder::circuit_breaker`.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::relax::Relax;
+
+// Write attempts per decision.
+const WINDOW: u64 = 256;
+
+// `counts` holds commits in the low half and restarts in the high one.
+const RESTART: u64 = 1 << 32;
+
+/// Time the STM spent in each mode of its circuit breaker, see
+/// `STM::breaker_stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BreakerStats {
+    /// Whether write transactions are serialized right now.
+    pub serialized: bool,
+    /// Switches from optimistic to serialized.
+    pub trips: u64,
+    pub optimistic_time: Duration,
+    pub serialized_time: Duration,
+}
+
+struct Mode {
+    since: Instant,
+    optimistic: Duration,
+    serialized: Duration,
+    trips: u64,
+}
+
+pub(crate) struct Breaker {
+    threshold: f64,
+    counts: AtomicU64,
+    serialized: AtomicBool,
+    mode: Mutex<Mode>,
+    next: AtomicU64,    // ticket of the next writer to come
+    serving: AtomicU64, // ticket of the writer that may run
+}
+
+impl Breaker {
+    pub(crate) fn new(threshold: f64) -> Breaker {
+        Breaker {
+            threshold,
+            counts: AtomicU64::new(0),
+            serialized: AtomicBool::new(false),
+            mode: Mutex::new(Mode {
+                since: Instant::now(),
+                optimistic: Duration::ZERO,
+                serialized: Duration::ZERO,
+                trips: 0,
+            }),
+            next: AtomicU64::new(0),
+            serving: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn is_serialized(&self) -> bool {
+        self.serialized.load(Ordering::Relaxed)
+    }
+
+    // Wait for the turn of a new ticket, turning `relax`.
+    pub(crate) fn ticket(&self, relax: &dyn Relax) -> Ticket<'_> {
+        let mine = self.next.fetch_add(1, Ordering::Relaxed);
+        while self.serving.load(Ordering::Acquire) != mine {
+            relax.relax();
+        }
+        Ticket { breaker: self }
+    }
+
+    // A ticket whose turn it is right away, unless another writer holds
+    // the turn or waits for one.
+    pub(crate) fn try_ticket(&self) -> Option<Ticket<'_>> {
+        let serving = self.serving.load(Ordering::Acquire);
+        self.next
+            .compare_exchange(serving, serving + 1, Ordering::Relaxed, Ordering::Relaxed)
+            .ok()?;
+        Some(Ticket { breaker: self })
+    }
+
+    // Count a write attempt that committed or restarted. The attempt that
+    // fills a window decides the mode for the next one: serialized while
+    // restarts per commit exceed the threshold, optimistic otherwise.
+    pub(crate) fn record(&self, committed: bool) {
+        let add = if committed { 1 } else { RESTART };
+        let counts = self.counts.fetch_add(add, Ordering::Relaxed) + add;
+        let (commits, restarts) = (counts & (RESTART - 1), counts >> 32);
+        if commits + restarts != WINDOW {
+            return;
+        }
+        self.counts.fetch_sub(counts, Ordering::Relaxed);
+        let ratio = restarts as f64 / commits.max(1) as f64;
+        self.switch(ratio > self.threshold);
+    }
+
+    fn switch(&self, serialized: bool) {
+        let mut mode = self.mode.lock().unwrap();
+        if self.is_serialized() == serialized {
+            return;
+        }
+        let now = Instant::now();
+        let spent = now.duration_since(mode.since);
+        if serialized {
+            mode.optimistic += spent;
+            mode.trips += 1;
+        } else {
+            mode.serialized += spent;
+        }
+        mode.since = now;
+        self.serialized.store(serialized, Ordering::Relaxed);
+    }
+
+    pub(crate) fn stats(&self) -> BreakerStats {
+        let mode = self.mode.lock().unwrap();
+        let serialized = self.is_serialized();
+        let spent = mode.since.elapsed();
+        BreakerStats {
+            serialized,
+            trips: mode.trips,
+            optimistic_time: mode.optimistic + if serialized { Duration::ZERO } else { spent },
+            serialized_time: mode.serialized + if serialized { spent } else { Duration::ZERO },
+        }
+    }
+}
+
+// A writer's turn in serialized mode, passed on when dropped.
+pub(crate) struct Ticket<'a> {
+    breaker: &'a Breaker,
+}
+
+impl<'a> Drop for Ticket<'a> {
+    fn drop(&mut self) {
+        self.breaker.serving.fetch_add(1, Ordering::Release);
+    }
+}
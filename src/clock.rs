@@ -0,0 +1,43 @@
+use crate::sync::{AtomicU64, Ordering};
+
+/// The global version-clock every commit is stamped with.
+///
+/// `increment` must return a value greater than every value returned so
+/// far by either method, and the two must order memory like an `AcqRel`
+/// `fetch_add` and an `Acquire` load of one atomic do: a transaction that
+/// samples a version must see every commit stamped with it or earlier.
+/// Versions must stay below 2^63, the top bit of a stripe's version word
+/// being its lock.
+pub trait Clock: Send + Sync {
+    /// The current version.
+    fn sample(&self) -> u64;
+    /// Advance the clock and return the new version.
+    fn increment(&self) -> u64;
+    /// Move the clock to `v` at once; only called while no transaction
+    /// runs, see `STM::set_clock`.
+    fn set(&self, v: u64);
+}
+
+/// The default clock, a counter starting at 0.
+#[derive(Default)]
+pub struct AtomicClock(AtomicU64);
+
+impl AtomicClock {
+    pub fn new() -> AtomicClock {
+        AtomicClock(AtomicU64::new(0))
+    }
+}
+
+impl Clock for AtomicClock {
+    fn sample(&self) -> u64 {
+        self.0.load(Ordering::Acquire)
+    }
+
+    fn increment(&self) -> u64 {
+        self.0.fetch_add(1, Ordering::AcqRel) + 1
+    }
+
+    fn set(&self, v: u64) {
+        self.0.store(v, Ordering::Release);
+    }
+}
@@ -0,0 +1,149 @@
+use std::convert::{TryFrom, TryInto};
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::tl2::{STM, STRIPE_SIZE};
+
+/// Receives every committed write-set, see `STMBuilder::commit_sink`.
+///
+/// `append` is called after the commit's versions are published and its
+/// locks released, so non-overlapping commits may arrive in either order;
+/// the version orders them.
+pub trait CommitSink: Send + Sync {
+    fn append(&self, version: u64, entries: &[(usize, [u8; STRIPE_SIZE])]);
+}
+
+/// A `CommitSink` writing framed records to a file, read back by
+/// `STM::replay_log`.
+///
+/// A frame is the payload length (u32), the payload and the CRC-32 of the
+/// payload (u32). The payload is the commit version (u64), the number of
+/// entries (u32) and then per entry the address (u64) and the stripe bytes,
+/// all little-endian.
+pub struct CommitLog {
+    out: Mutex<BufWriter<File>>,
+    failed: AtomicBool, // an append failed since the last flush
+}
+
+impl CommitLog {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<CommitLog> {
+        Ok(CommitLog {
+            out: Mutex::new(BufWriter::new(File::create(path)?)),
+            failed: AtomicBool::new(false),
+        })
+    }
+
+    /// Write out buffered records and sync them to the disk.
+    pub fn flush(&self) -> io::Result<()> {
+        let mut out = self.out.lock().unwrap();
+        out.flush()?;
+        out.get_ref().sync_data()?;
+        if self.failed.swap(false, Ordering::Relaxed) {
+            return Err(io::Error::other("a commit log append failed"));
+        }
+        Ok(())
+    }
+}
+
+impl CommitSink for CommitLog {
+    fn append(&self, version: u64, entries: &[(usize, [u8; STRIPE_SIZE])]) {
+        let len = 12 + entries.len() * (8 + STRIPE_SIZE);
+        let mut buf = Vec::with_capacity(len + 8);
+        buf.extend_from_slice(&(len as u32).to_le_bytes());
+        buf.extend_from_slice(&version.to_le_bytes());
+        buf.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+        for (addr, val) in entries {
+            buf.extend_from_slice(&(*addr as u64).to_le_bytes());
+            buf.extend_from_slice(val);
+        }
+        let crc = crc32(&buf[4..]);
+        buf.extend_from_slice(&crc.to_le_bytes());
+
+        if self.out.lock().unwrap().write_all(&buf).is_err() {
+            self.failed.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+// CRC-32 (IEEE 802.3, reflected).
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for b in bytes {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+fn invalid(offset: usize, what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("commit log record at offset {}: {}", offset, what),
+    )
+}
+
+impl STM {
+    /// Apply the records of a `CommitLog` in version order, bypassing
+    /// transactions, and return how many were applied. Meant for a freshly
+    /// built STM of the same capacity; versions and the clock are not
+    /// restored.
+    ///
+    /// A truncated or corrupt final record, as left by a crash during an
+    /// append, ends the log. Damage before it is an `InvalidData` error and
+    /// leaves the memory untouched.
+    pub fn replay_log<R: Read>(&mut self, mut reader: R) -> io::Result<usize> {
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf)?;
+
+        let mut records = Vec::new();
+        let mut pos = 0;
+        while buf.len() - pos >= 4 {
+            let len = u32::from_le_bytes(buf[pos..pos + 4].try_into().unwrap()) as usize;
+            let end = match pos.checked_add(8).and_then(|n| n.checked_add(len)) {
+                Some(end) if end <= buf.len() => end,
+                _ => break, // torn
+            };
+            let payload = &buf[pos + 4..end - 4];
+            let crc = u32::from_le_bytes(buf[end - 4..end].try_into().unwrap());
+            if crc32(payload) != crc {
+                if end == buf.len() {
+                    break; // torn
+                }
+                return Err(invalid(pos, "CRC mismatch"));
+            }
+
+            if len < 12 {
+                return Err(invalid(pos, "payload too short"));
+            }
+            let version = u64::from_le_bytes(payload[..8].try_into().unwrap());
+            let n = u32::from_le_bytes(payload[8..12].try_into().unwrap()) as usize;
+            if n.checked_mul(8 + STRIPE_SIZE) != Some(len - 12) {
+                return Err(invalid(pos, "entry count does not match the length"));
+            }
+            let mut entries = Vec::with_capacity(n);
+            for e in payload[12..].chunks_exact(8 + STRIPE_SIZE) {
+                let addr = u64::from_le_bytes(e[..8].try_into().unwrap());
+                let addr = match usize::try_from(addr) {
+                    Ok(a) if a.is_multiple_of(STRIPE_SIZE) && a < self.capacity() => a,
+                    _ => return Err(invalid(pos, "address out of range")),
+                };
+                entries.push((addr, &e[8..]));
+            }
+            records.push((version, entries));
+            pos = end;
+        }
+
+        records.sort_by_key(|(version, _)| *version);
+        for (_, entries) in records.iter() {
+            for (addr, val) in entries {
+                self.fill(*addr, val);
+            }
+        }
+        Ok(records.len())
+    }
+}
@@ -0,0 +1,20 @@
+/// Why a transaction was restarted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConflictCause {
+    /// A loaded stripe was locked or newer than the read version.
+    PreValidation,
+    /// A loaded stripe changed while it was being copied.
+    PostValidation,
+    /// A stripe of the write-set could not be locked, or a transaction of
+    /// higher priority took it over, see `STM::write_transaction_prio`.
+    Lock,
+    /// The read-set failed validation at commit.
+    Validation,
+}
+
+/// A restart and the stripe that caused it, if any.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Conflict {
+    pub cause: ConflictCause,
+    pub addr: Option<usize>,
+}
@@ -0,0 +1,181 @@
+// Contention managers: what a committing write transaction does about a
+// stripe lock another one holds, see `STMBuilder::contention_manager`.
+
+/// A hint of how much a transaction matters next to the others, see
+/// `STM::write_transaction_with_priority`. Unlike the priority of
+/// `STM::write_transaction_prio` it never wounds by itself: it tunes the
+/// backoff between attempts, how long a committer spins on a held lock,
+/// and which side `Karma` and `Greedy` take.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum TxPriority {
+    /// Best effort: backs off longer, and yields to `High` transactions
+    /// that restarted before each attempt.
+    Low,
+    #[default]
+    Normal,
+    /// Spins longer on locks held at commit, and beats `Normal` and `Low`
+    /// under `Karma` and `Greedy`.
+    High,
+}
+
+impl TxPriority {
+    // `base` ranked after every lower hint and before every higher one.
+    fn rank(self, base: u64) -> u64 {
+        (self as u64) << 62 | base.min((1 << 62) - 1)
+    }
+}
+
+/// The transaction meeting a held lock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Contender {
+    /// Attempts so far, this one included.
+    pub attempt: u32,
+    /// Stripes loaded by this attempt.
+    pub reads: usize,
+    /// Stripes loaded by every attempt so far, this one included.
+    pub work: u64,
+    /// The version of the clock the first attempt read at.
+    pub start: u64,
+    /// The priority of `STM::write_transaction_prio`, 0 otherwise.
+    pub prio: u8,
+    /// The `TxPriority` hint, `Normal` unless given.
+    pub priority: TxPriority,
+}
+
+/// A stripe lock held by another transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockInfo {
+    pub addr: usize,
+    /// The `ContentionManager::priority` of the holder.
+    pub priority: u64,
+    /// Times the manager was asked about this lock by this attempt before.
+    pub turns: u32,
+}
+
+/// What to do about a held lock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// Release the locks taken so far and restart.
+    AbortSelf,
+    /// Try the lock again, then ask again if it is still held.
+    Wait,
+    /// Ask the holder to restart, unless it is already past the point of
+    /// no return, and try the lock again. Only a transaction of higher
+    /// priority than the holder can do so; for others this is `Wait`, so
+    /// two transactions never kill each other.
+    AbortOther,
+}
+
+/// Decides, for a write transaction failing to take a stripe lock at
+/// commit, whether it gives up, waits or wounds the holder.
+///
+/// Locks are only held while committing, so waiting is short unless the
+/// holder waits in turn. `on_conflict` must return `AbortSelf` after
+/// finitely many turns for a holder of equal or higher priority, or two
+/// committers waiting for each other's locks never finish.
+pub trait ContentionManager: Send + Sync {
+    /// The priority of `me`, kept with the locks it takes.
+    fn priority(&self, me: &Contender) -> u64;
+
+    fn on_conflict(&self, me: &Contender, other: &LockInfo) -> Resolution;
+}
+
+// Back off for turn `turns`, up to 256 spins.
+fn spin(turns: u32) {
+    for _ in 0..1u32 << turns.min(8) {
+        core::hint::spin_loop();
+    }
+}
+
+/// Back off, spinning twice as long on every turn, and restart after
+/// `rounds` turns. Never wounds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Polite {
+    pub rounds: u32,
+}
+
+impl Default for Polite {
+    fn default() -> Self {
+        Polite { rounds: 8 }
+    }
+}
+
+impl ContentionManager for Polite {
+    fn priority(&self, me: &Contender) -> u64 {
+        me.prio as u64
+    }
+
+    fn on_conflict(&self, _me: &Contender, other: &LockInfo) -> Resolution {
+        if other.turns >= self.rounds {
+            return Resolution::AbortSelf;
+        }
+        spin(other.turns);
+        Resolution::Wait
+    }
+}
+
+/// Priority is the work done, as stripes loaded over every attempt, so a
+/// transaction that keeps losing gains on the others; a higher
+/// `TxPriority` beats any work. Wounds a holder that did less work;
+/// otherwise waits a turn for each stripe it is behind, up to
+/// `max_turns`, and restarts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Karma {
+    pub max_turns: u32,
+}
+
+impl Default for Karma {
+    fn default() -> Self {
+        Karma { max_turns: 64 }
+    }
+}
+
+impl ContentionManager for Karma {
+    fn priority(&self, me: &Contender) -> u64 {
+        me.priority.rank(me.work)
+    }
+
+    fn on_conflict(&self, me: &Contender, other: &LockInfo) -> Resolution {
+        let mine = self.priority(me);
+        if mine > other.priority {
+            return Resolution::AbortOther;
+        }
+        let behind = (other.priority - mine).min(self.max_turns as u64);
+        if other.turns as u64 >= behind {
+            return Resolution::AbortSelf;
+        }
+        spin(other.turns);
+        Resolution::Wait
+    }
+}
+
+/// Older transactions, by the clock version their first attempt read at,
+/// go first, after a higher `TxPriority`: a younger holder is wounded, an
+/// older one waited for up to `max_turns` turns before restarting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Greedy {
+    pub max_turns: u32,
+}
+
+impl Default for Greedy {
+    fn default() -> Self {
+        Greedy { max_turns: 64 }
+    }
+}
+
+impl ContentionManager for Greedy {
+    fn priority(&self, me: &Contender) -> u64 {
+        me.priority.rank((u64::MAX - me.start) >> 2)
+    }
+
+    fn on_conflict(&self, me: &Contender, other: &LockInfo) -> Resolution {
+        if self.priority(me) > other.priority {
+            return Resolution::AbortOther;
+        }
+        if other.turns >= self.max_turns {
+            return Resolution::AbortSelf;
+        }
+        spin(other.turns);
+        Resolution::Wait
+    }
+}
@@ -0,0 +1,52 @@
+use crate::packed::PackedArray;
+use crate::tl2::{Trans, WriteTrans};
+
+/// A counter sharded over `n` stripes. Writers pick a shard (e.g. per
+/// thread) so they rarely conflict, and `sum` reads every shard in one
+/// transaction to get a consistent total.
+pub struct Counters {
+    shards: PackedArray<u64>,
+}
+
+impl Counters {
+    /// Place `n` shards starting at the stripe-aligned address `base`.
+    pub fn new(base: usize, n: usize) -> Counters {
+        Counters {
+            shards: PackedArray::new(base, n),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shards.is_empty()
+    }
+
+    /// Add `delta` to shard `idx` (wrapping) and return the shard's new value.
+    pub fn add(&self, tr: &mut WriteTrans, idx: usize, delta: u64) -> Option<u64> {
+        let val = self.shards.get(tr, idx)?.wrapping_add(delta);
+        self.shards.set(tr, idx, val);
+        Some(val)
+    }
+
+    /// Add each `(idx, delta)` of `deltas` as `add` does, in the one
+    /// transaction, e.g. to flush increments a thread gathered locally.
+    /// A shard may appear more than once.
+    pub fn add_batch(&self, tr: &mut WriteTrans, deltas: &[(usize, u64)]) -> Option<()> {
+        for (idx, delta) in deltas.iter() {
+            self.add(tr, *idx, *delta)?;
+        }
+        Some(())
+    }
+
+    /// The total over all shards.
+    pub fn sum<R: Trans>(&self, tr: &mut R) -> Option<u64> {
+        let mut total: u64 = 0;
+        for idx in 0..self.shards.len() {
+            total = total.wrapping_add(self.shards.get(tr, idx)?);
+        }
+        Some(total)
+    }
+}
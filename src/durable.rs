@@ -0,0 +1,103 @@
+// Crash-consistent persistence: memory mapped from a heap file plus a
+// redo journal next to it. Commits journal their write-set before
+// applying it to the mapping; `STM::checkpoint` syncs the mapping and
+// empties the journal.
+
+use std::convert::TryFrom;
+use std::ffi::OsString;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+use crate::journal::{Durability, Journal};
+use crate::storage::{MmapStorage, Storage};
+use crate::tl2::{STMBuilder, STM, STRIPE_SIZE};
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut s = OsString::from(path.as_os_str());
+    s.push(suffix);
+    PathBuf::from(s)
+}
+
+impl STMBuilder {
+    /// Build a crash-consistent STM whose memory is the heap file at
+    /// `path`, mapped, with a redo journal at `path` with `.log` appended.
+    ///
+    /// Every commit writes its record to the journal before applying its
+    /// write-set to the mapping, so a crash can tear stripes of the heap
+    /// file but never loses a record for them. Opening first replays the
+    /// journal of a previous run onto the heap file, in version order and
+    /// stopping at a record cut short by the crash, which leaves the state
+    /// after some prefix of the commits. A missing heap file starts out
+    /// zeroed with the capacity set here; an existing one keeps its own.
+    ///
+    /// Commits are journaled with `durability`, replacing any journal set
+    /// on the builder. `Durability::Sync` survives a crash of the machine.
+    /// The others survive a crash of the process, but the kernel may write
+    /// mapped pages back before their records reach the disk, so after a
+    /// crash of the machine the heap file may hold torn commits. Call
+    /// `STM::checkpoint` to bound the journal's size.
+    pub fn open_durable<P: AsRef<Path>>(self, path: P, durability: Durability) -> io::Result<STM> {
+        let path = path.as_ref();
+        let log = with_suffix(path, ".log");
+
+        let size = match path.metadata() {
+            Ok(m) if m.len() > 0 => usize::try_from(m.len())
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "heap file too large"))?,
+            Ok(_) => self.configured_capacity(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => self.configured_capacity(),
+            Err(e) => return Err(e),
+        };
+        let heap = MmapStorage::open(path, size)?;
+
+        let mut records = match Journal::recover(&log) {
+            Ok(records) => records,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        records.sort_by_key(|r| r.version);
+        for r in records.iter() {
+            for (addr, val) in r.entries.iter() {
+                if !addr.is_multiple_of(STRIPE_SIZE) || *addr >= size {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("journal record {} writes outside the heap", r.version),
+                    ));
+                }
+                // SAFETY: in bounds, and nothing else uses the mapping yet
+                unsafe {
+                    ptr::copy_nonoverlapping(val.as_ptr(), heap.as_ptr().add(*addr), STRIPE_SIZE)
+                };
+            }
+        }
+
+        // the journal may only be emptied once the heap file holds it all
+        heap.sync(0..size)?;
+        let journal = Journal::create(&log, durability)?.write_through();
+
+        Ok(self.storage(heap).journal(journal).durable().build())
+    }
+}
+
+impl STM {
+    /// `STM::builder().open_durable(path, durability)`.
+    pub fn open_durable<P: AsRef<Path>>(path: P, durability: Durability) -> io::Result<STM> {
+        STM::builder().open_durable(path, durability)
+    }
+
+    /// Sync the mapped heap file and empty the journal. Commits wait until
+    /// it returns. A crash in between is harmless: replaying the journal
+    /// onto the synced heap file changes nothing.
+    ///
+    /// Fails unless the STM was built by `open_durable`.
+    pub fn checkpoint(&self) -> io::Result<()> {
+        let journal = match self.journal() {
+            Some(j) if self.is_durable() => j,
+            _ => return Err(io::Error::other("not opened with open_durable")),
+        };
+
+        let _paused = self.pause_commits();
+        self.storage().sync(0..self.capacity())?;
+        journal.truncate()
+    }
+}
@@ -0,0 +1,108 @@
+// Emission through the `metrics` facade. A transaction tallies its restarts
+// locally and everything is emitted once when it finishes. Without the
+// `metrics` feature there is no emitter and the tally is empty.
+
+use crate::conflict::ConflictCause;
+#[cfg(feature = "metrics")]
+use crate::latency::Timer;
+use crate::tl2::TxError;
+
+// Restarts of one transaction per `ConflictCause`.
+#[cfg(feature = "metrics")]
+pub(crate) struct Tally([u32; 4]);
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) struct Tally;
+
+#[cfg(feature = "metrics")]
+impl Tally {
+    pub(crate) fn new() -> Tally {
+        Tally([0; 4])
+    }
+
+    pub(crate) fn restart(&mut self, cause: ConflictCause) {
+        let n = &mut self.0[cause as usize];
+        *n = n.saturating_add(1);
+    }
+}
+
+#[cfg(not(feature = "metrics"))]
+impl Tally {
+    #[inline(always)]
+    pub(crate) fn new() -> Tally {
+        Tally
+    }
+
+    #[inline(always)]
+    pub(crate) fn restart(&mut self, _cause: ConflictCause) {}
+}
+
+// How a transaction finished.
+#[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+#[derive(Clone, Copy)]
+pub(crate) enum Finish {
+    Commit,
+    Read,
+    Failed(TxError),
+}
+
+#[cfg(feature = "metrics")]
+pub(crate) struct Emitter {
+    commits: std::sync::Arc<str>,
+    reads: std::sync::Arc<str>,
+    aborts: std::sync::Arc<str>,
+    retries: std::sync::Arc<str>,
+    restarts: std::sync::Arc<str>,
+    phase: std::sync::Arc<str>,
+}
+
+#[cfg(feature = "metrics")]
+impl Emitter {
+    pub(crate) fn new(prefix: &str) -> Emitter {
+        let name = |s: &str| -> std::sync::Arc<str> { format!("{}_{}", prefix, s).into() };
+        Emitter {
+            commits: name("commits_total"),
+            reads: name("reads_total"),
+            aborts: name("aborts_total"),
+            retries: name("retries_total"),
+            restarts: name("restarts_total"),
+            phase: name("phase_seconds"),
+        }
+    }
+
+    pub(crate) fn finish(
+        &self,
+        label: Option<&'static str>,
+        finish: Finish,
+        tally: &Tally,
+        timer: Option<&Timer>,
+    ) {
+        use metrics::{counter, histogram, SharedString};
+
+        let key = |n: &std::sync::Arc<str>| SharedString::from_shared(n.clone());
+        let label = label.unwrap_or("");
+
+        let name = match finish {
+            Finish::Commit => &self.commits,
+            Finish::Read => &self.reads,
+            Finish::Failed(TxError::Retry) => &self.retries,
+            Finish::Failed(_) => &self.aborts,
+        };
+        counter!(key(name), "label" => label).increment(1);
+
+        const CAUSES: [&str; 4] = ["pre_validation", "post_validation", "lock", "validation"];
+        for (cause, n) in CAUSES.iter().zip(tally.0.iter()) {
+            if *n > 0 {
+                counter!(key(&self.restarts), "label" => label, "cause" => *cause)
+                    .increment(*n as u64);
+            }
+        }
+
+        if let Some(t) = timer {
+            for (phase, d) in t.phases() {
+                histogram!(key(&self.phase), "label" => label, "phase" => phase)
+                    .record(d.as_secs_f64());
+            }
+        }
+    }
+}
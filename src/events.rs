@@ -0,0 +1,142 @@
+use std::sync::atomic::{fence, AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::conflict::ConflictCause;
+use crate::tl2::TxError;
+
+const NO_ADDR: u64 = u64::MAX;
+
+/// What happened to a transaction, see `STM::recent_events`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    Restart(ConflictCause),
+    Failed(TxError),
+}
+
+impl EventKind {
+    fn encode(self) -> u8 {
+        match self {
+            EventKind::Restart(ConflictCause::PreValidation) => 0,
+            EventKind::Restart(ConflictCause::PostValidation) => 1,
+            EventKind::Restart(ConflictCause::Lock) => 2,
+            EventKind::Restart(ConflictCause::Validation) => 3,
+            EventKind::Failed(TxError::Abort) => 4,
+            EventKind::Failed(TxError::Retry) => 5,
+            EventKind::Failed(TxError::ReadSetTooLarge) => 6,
+            EventKind::Failed(TxError::Journal) => 7,
+            EventKind::Failed(TxError::Rejected) => 8,
+            EventKind::Failed(TxError::Poisoned) => 9,
+        }
+    }
+
+    fn decode(n: u8) -> EventKind {
+        match n {
+            0 => EventKind::Restart(ConflictCause::PreValidation),
+            1 => EventKind::Restart(ConflictCause::PostValidation),
+            2 => EventKind::Restart(ConflictCause::Lock),
+            3 => EventKind::Restart(ConflictCause::Validation),
+            4 => EventKind::Failed(TxError::Abort),
+            5 => EventKind::Failed(TxError::Retry),
+            6 => EventKind::Failed(TxError::ReadSetTooLarge),
+            7 => EventKind::Failed(TxError::Journal),
+            8 => EventKind::Failed(TxError::Rejected),
+            _ => EventKind::Failed(TxError::Poisoned),
+        }
+    }
+}
+
+/// A restart or failure recorded in the event ring.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TxEvent {
+    /// Time since the STM was built.
+    pub at: Duration,
+    pub label: Option<&'static str>,
+    pub kind: EventKind,
+    /// The stripe that caused a restart, if known.
+    pub addr: Option<usize>,
+    pub attempt: u32,
+}
+
+#[derive(Default)]
+struct Entry {
+    seq: AtomicU64, // 2 * index + 2 once written, odd while being written
+    at: AtomicU64,
+    bucket: AtomicUsize,
+    kind: AtomicU8,
+    addr: AtomicU64,
+    attempt: AtomicU32,
+}
+
+// The last `entries.len()` events. A writer takes the next index with one
+// fetch_add and fills its entry like a seqlock; a writer lapping a slow one
+// just makes the reader drop that entry.
+pub(crate) struct Events {
+    epoch: Instant,
+    head: AtomicU64,
+    entries: Vec<Entry>,
+}
+
+impl Events {
+    pub(crate) fn new(n: usize) -> Events {
+        Events {
+            epoch: Instant::now(),
+            head: AtomicU64::new(0),
+            entries: (0..n).map(|_| Entry::default()).collect(),
+        }
+    }
+
+    pub(crate) fn push(&self, bucket: usize, kind: EventKind, addr: Option<usize>, attempt: u32) {
+        let idx = self.head.fetch_add(1, Ordering::Relaxed);
+        let at = self.epoch.elapsed().as_nanos().min(u64::MAX as u128) as u64;
+        let e = &self.entries[(idx % self.entries.len() as u64) as usize];
+
+        e.seq.store(2 * idx + 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+        e.at.store(at, Ordering::Relaxed);
+        e.bucket.store(bucket, Ordering::Relaxed);
+        e.kind.store(kind.encode(), Ordering::Relaxed);
+        e.addr
+            .store(addr.map_or(NO_ADDR, |a| a as u64), Ordering::Relaxed);
+        e.attempt.store(attempt, Ordering::Relaxed);
+        e.seq.store(2 * idx + 2, Ordering::Release);
+    }
+
+    // Completed events oldest first, with their label bucket.
+    pub(crate) fn recent(&self) -> Vec<(usize, TxEvent)> {
+        let head = self.head.load(Ordering::Acquire);
+        let start = head.saturating_sub(self.entries.len() as u64);
+        let mut v = Vec::new();
+        for idx in start..head {
+            let e = &self.entries[(idx % self.entries.len() as u64) as usize];
+            let seq = e.seq.load(Ordering::Acquire);
+            if seq != 2 * idx + 2 {
+                continue;
+            }
+            let at = e.at.load(Ordering::Relaxed);
+            let bucket = e.bucket.load(Ordering::Relaxed);
+            let kind = e.kind.load(Ordering::Relaxed);
+            let addr = e.addr.load(Ordering::Relaxed);
+            let attempt = e.attempt.load(Ordering::Relaxed);
+            fence(Ordering::Acquire);
+            if e.seq.load(Ordering::Relaxed) != seq {
+                continue;
+            }
+
+            v.push((
+                bucket,
+                TxEvent {
+                    at: Duration::from_nanos(at),
+                    label: None,
+                    kind: EventKind::decode(kind),
+                    addr: if addr == NO_ADDR {
+                        None
+                    } else {
+                        Some(addr as usize)
+                    },
+                    attempt,
+                },
+            ));
+        }
+        v
+    }
+}
@@ -0,0 +1,150 @@
+// Faults injected into write transactions, for testing how application
+// code copes with restarts, retries and slow commits (the `testing`
+// feature).
+
+use std::process;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use crate::sched::YieldPoint;
+
+/// What a `FaultInjector` does to a write attempt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Restart the attempt once its body returned `Ok`, as if a stripe it
+    /// read had changed before commit.
+    Restart,
+    /// Fail to lock the write-set, restarting the attempt.
+    LockFailure,
+    /// Sleep at a yield point, locks held from `Locked` on.
+    Delay(YieldPoint, Duration),
+    /// Fail the transaction with `TxError::Retry` once its body returned
+    /// `Ok`, as if it had returned `Retry`. A blocking transaction waits
+    /// for the next commit instead.
+    Retry,
+    /// Abort the process at a yield point, as a crash would, e.g. to test
+    /// the recovery of `STM::open_durable`.
+    Crash(YieldPoint),
+}
+
+impl Fault {
+    // Where an attempt meets the fault.
+    fn point(&self) -> YieldPoint {
+        match *self {
+            Fault::Restart | Fault::Retry => YieldPoint::Executed,
+            Fault::LockFailure => YieldPoint::Locked,
+            Fault::Delay(point, _) | Fault::Crash(point) => point,
+        }
+    }
+}
+
+/// Which attempts meeting a fault get it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Trigger {
+    Always,
+    /// Each with probability `p`, drawn from the injector's seeded
+    /// generator.
+    Chance(f64),
+    /// The ones at these positions, counting from 1, among the attempts
+    /// that met the fault since it was added.
+    At(Vec<u64>),
+}
+
+struct Rule {
+    label: Option<&'static str>,
+    fault: Fault,
+    trigger: Trigger,
+    met: u64,
+}
+
+/// Faults to inject into the write transactions of an STM, see
+/// `STMBuilder::fault_injector`. A fault added for a label only hits
+/// transactions run with that label, e.g. by
+/// `STM::write_transaction_labeled`; one added without hits all of them.
+///
+/// ```
+/// use std::sync::Arc;
+/// use tl2::{Fault, FaultInjector, STMResult, Trigger, STM};
+///
+/// let faults = Arc::new(FaultInjector::new(0));
+/// faults.add(Some("transfer"), Fault::Retry, Trigger::At(vec![1]));
+/// let stm = STM::builder().fault_injector(faults).build();
+///
+/// let transfer = || stm.write_transaction_labeled("transfer", |_| STMResult::Ok(()));
+/// assert_eq!(stm.try_write_transaction(|_| STMResult::Ok(())), Ok(()));
+/// assert_eq!(transfer(), None);
+/// assert_eq!(transfer(), Some(()));
+/// ```
+pub struct FaultInjector {
+    rules: Mutex<(Vec<Rule>, u64)>, // and the xorshift64* state
+}
+
+impl FaultInjector {
+    /// An injector without faults, drawing `Trigger::Chance` from a
+    /// generator seeded with `seed`.
+    pub fn new(seed: u64) -> FaultInjector {
+        FaultInjector {
+            rules: Mutex::new((Vec::new(), seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)),
+        }
+    }
+
+    /// Inject `fault` into the attempts of transactions labeled `label`,
+    /// or of all transactions with `None`, that `trigger` picks. Delays
+    /// hitting one attempt at one point add up; of the other faults, the
+    /// first added wins.
+    pub fn add(&self, label: Option<&'static str>, fault: Fault, trigger: Trigger) {
+        self.rules.lock().unwrap().0.push(Rule {
+            label,
+            fault,
+            trigger,
+            met: 0,
+        });
+    }
+
+    /// Remove every fault.
+    pub fn clear(&self) {
+        self.rules.lock().unwrap().0.clear();
+    }
+
+    // The fault hitting an attempt of a transaction labeled `label` at
+    // `point`, sleeping out delays here.
+    pub(crate) fn inject(&self, label: Option<&'static str>, point: YieldPoint) -> Option<Fault> {
+        let mut hit = None;
+        let mut delay = Duration::ZERO;
+        {
+            let (rules, rng) = &mut *self.rules.lock().unwrap();
+            for r in rules.iter_mut() {
+                if r.fault.point() != point || r.label.is_some() && r.label != label {
+                    continue;
+                }
+                r.met += 1;
+                let fire = match &r.trigger {
+                    Trigger::Always => true,
+                    Trigger::Chance(p) => unit(rng) < *p,
+                    Trigger::At(at) => at.contains(&r.met),
+                };
+                match r.fault {
+                    Fault::Delay(_, d) if fire => delay += d,
+                    f if fire && hit.is_none() => hit = Some(f),
+                    _ => (),
+                }
+            }
+        }
+        if delay > Duration::ZERO {
+            thread::sleep(delay);
+        }
+        if let Some(Fault::Crash(_)) = hit {
+            process::abort();
+        }
+        hit
+    }
+}
+
+// xorshift64*, as a float in [0, 1)
+fn unit(state: &mut u64) -> f64 {
+    *state ^= *state >> 12;
+    *state ^= *state << 25;
+    *state ^= *state >> 27;
+    (state.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11) as f64 / (1u64 << 53) as f64
+}
@@ -0,0 +1,86 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::RwLock;
+
+use crate::tl2::STRIPE_SIZE;
+
+/// A committed write-set, see `STM::subscribe`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitRecord {
+    pub version: u64,
+    /// The stripes written, sorted by address.
+    pub entries: Vec<(usize, [u8; STRIPE_SIZE])>,
+    /// Records were dropped for this receiver since the one before.
+    pub gap: bool,
+}
+
+struct Subscriber {
+    tx: SyncSender<CommitRecord>,
+    gone: AtomicBool,    // the receiver was dropped
+    dropped: AtomicBool, // a record was dropped since the last one sent
+}
+
+// Subscribers of the change feed. Committers only ever `try_send`, so a
+// slow subscriber loses records instead of holding up commits.
+pub(crate) struct Feed {
+    bound: usize,
+    subs: RwLock<Vec<Subscriber>>,
+    len: AtomicUsize, // subs.len(), read without the lock
+}
+
+impl Feed {
+    pub(crate) fn new(bound: usize) -> Feed {
+        Feed {
+            bound: bound.max(1),
+            subs: RwLock::new(Vec::new()),
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    pub(crate) fn subscribe(&self) -> Receiver<CommitRecord> {
+        let (tx, rx) = sync_channel(self.bound);
+        let mut subs = self.subs.write().unwrap();
+        subs.push(Subscriber {
+            tx,
+            gone: AtomicBool::new(false),
+            dropped: AtomicBool::new(false),
+        });
+        self.len.store(subs.len(), Ordering::Relaxed);
+        rx
+    }
+
+    pub(crate) fn is_active(&self) -> bool {
+        self.len.load(Ordering::Relaxed) > 0
+    }
+
+    // Send a record to every subscriber and return how many were full.
+    pub(crate) fn push(&self, version: u64, entries: &[(usize, [u8; STRIPE_SIZE])]) -> u64 {
+        let mut full = 0;
+        let mut gone = false;
+        for s in self.subs.read().unwrap().iter() {
+            let r = CommitRecord {
+                version,
+                entries: entries.to_vec(),
+                gap: s.dropped.swap(false, Ordering::Relaxed),
+            };
+            match s.tx.try_send(r) {
+                Ok(()) => (),
+                Err(TrySendError::Full(_)) => {
+                    s.dropped.store(true, Ordering::Relaxed);
+                    full += 1;
+                }
+                Err(TrySendError::Disconnected(_)) => {
+                    s.gone.store(true, Ordering::Relaxed);
+                    gone = true;
+                }
+            }
+        }
+
+        if gone {
+            let mut subs = self.subs.write().unwrap();
+            subs.retain(|s| !s.gone.load(Ordering::Relaxed));
+            self.len.store(subs.len(), Ordering::Relaxed);
+        }
+        full
+    }
+}
@@ -0,0 +1,245 @@
+//! C bindings, declared in `include/tl2.h` (regenerate it with
+//! `cbindgen --config cbindgen.toml --output include/tl2.h`). Build the
+//! shared library with
+//!
+//! ```text
+//! cargo rustc --release --lib --features ffi --crate-type cdylib
+//! ```
+//!
+//! A transaction body is a C callback that gets an opaque `tl2_txn` handle
+//! and the caller's `user_data`. Like a Rust body it may run several
+//! times, so it must not have side effects outside the transaction. It
+//! returns `TL2_OK` to commit, `TL2_RETRY` to restart and `TL2_ABORT` to
+//! give up; when `tl2_load` returns `TL2_RETRY` the attempt is doomed and
+//! the callback should return `TL2_RETRY` right away.
+//!
+//! Every call returns a status code instead of panicking: a panic inside
+//! the library is caught at the boundary and reported as `TL2_PANIC`.
+
+use std::cell::Cell;
+use std::os::raw::{c_int, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::thread;
+
+use crate::tl2::{ReadTrans, STMResult, WriteTrans, STM, STRIPE_SIZE};
+
+/// Success; from a callback, commit.
+pub const TL2_OK: c_int = 0;
+/// The attempt hit a conflict; from a callback, restart. From
+/// `tl2_write_txn` or `tl2_read_txn`, the callback returned it without a
+/// conflict.
+pub const TL2_RETRY: c_int = 1;
+/// From a callback, give up. From `tl2_write_txn` or `tl2_read_txn`, the
+/// transaction was given up.
+pub const TL2_ABORT: c_int = 2;
+/// A null pointer, an address that is not 8-byte aligned or lies outside
+/// the memory, a store in a read transaction, or an unknown status code
+/// returned by a callback.
+pub const TL2_INVALID: c_int = 3;
+/// The library panicked; the transaction was given up.
+pub const TL2_PANIC: c_int = 4;
+
+/// Body of a transaction, see the module docs.
+pub type TxnFn = Option<unsafe extern "C" fn(txn: *mut Txn, user_data: *mut c_void) -> c_int>;
+
+enum Trans<'t, 'a> {
+    Write(&'t mut WriteTrans<'a>),
+    Read(&'t mut ReadTrans<'a>),
+}
+
+/// A running transaction, valid during the callback it is passed to.
+pub struct Txn<'t, 'a> {
+    tr: Trans<'t, 'a>,
+    capacity: usize,
+    panicked: bool, // a call on the handle panicked
+}
+
+// What the attempts of a transaction left behind.
+#[derive(Default)]
+struct Outcome {
+    panicked: Cell<bool>,
+    invalid: Cell<bool>, // the callback returned an unknown status code
+    retry: Cell<bool>,   // the last callback returned `TL2_RETRY`
+}
+
+impl<'t, 'a> Txn<'t, 'a> {
+    fn valid(&self, addr: usize) -> bool {
+        addr.is_multiple_of(STRIPE_SIZE) && addr < self.capacity
+    }
+
+    // Run `f` on the transaction, turning a panic into `None`.
+    fn guard<R>(&mut self, f: impl FnOnce(&mut Trans) -> R) -> Option<R> {
+        let tr = &mut self.tr;
+        match panic::catch_unwind(AssertUnwindSafe(|| f(tr))) {
+            Ok(r) => Some(r),
+            Err(_) => {
+                self.panicked = true;
+                None
+            }
+        }
+    }
+}
+
+// Run the callback `f` on `tr` once.
+unsafe fn attempt(
+    tr: Trans,
+    capacity: usize,
+    f: unsafe extern "C" fn(*mut Txn, *mut c_void) -> c_int,
+    user_data: *mut c_void,
+    out: &Outcome,
+) -> STMResult<()> {
+    let mut txn = Txn {
+        tr,
+        capacity,
+        panicked: false,
+    };
+    let code = f(&mut txn, user_data);
+    out.retry.set(code == TL2_RETRY);
+    if txn.panicked {
+        out.panicked.set(true);
+        return STMResult::Abort;
+    }
+    match code {
+        TL2_OK => STMResult::Ok(()),
+        TL2_RETRY => STMResult::Retry,
+        TL2_ABORT => STMResult::Abort,
+        _ => {
+            out.invalid.set(true);
+            STMResult::Abort
+        }
+    }
+}
+
+// The status of a finished transaction that committed if `ok`.
+fn status(r: thread::Result<bool>, out: Outcome) -> c_int {
+    match r {
+        Err(_) => TL2_PANIC,
+        _ if out.panicked.get() => TL2_PANIC,
+        _ if out.invalid.get() => TL2_INVALID,
+        Ok(true) => TL2_OK,
+        Ok(false) if out.retry.get() => TL2_RETRY,
+        Ok(false) => TL2_ABORT,
+    }
+}
+
+/// A zeroed STM of `size` bytes, a multiple of 8, or null if `size` is
+/// not. Free it with `tl2_stm_free`. It may be shared between threads.
+#[no_mangle]
+pub extern "C" fn tl2_stm_new(size: usize) -> *mut STM {
+    if !size.is_multiple_of(STRIPE_SIZE) {
+        return ptr::null_mut();
+    }
+    match panic::catch_unwind(|| STM::builder().capacity(size).build()) {
+        Ok(stm) => Box::into_raw(Box::new(stm)),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Free an STM from `tl2_stm_new`. Null is ignored.
+///
+/// # Safety
+///
+/// `stm` is null or came from `tl2_stm_new` and is not used afterwards;
+/// no transaction may be running on it.
+#[no_mangle]
+pub unsafe extern "C" fn tl2_stm_free(stm: *mut STM) {
+    if !stm.is_null() {
+        drop(Box::from_raw(stm));
+    }
+}
+
+/// Run `f` as a write transaction until it commits or gives up.
+///
+/// # Safety
+///
+/// `stm` is null or a live STM from `tl2_stm_new`; `f` may be called with
+/// `user_data` any number of times.
+#[no_mangle]
+pub unsafe extern "C" fn tl2_write_txn(stm: *const STM, f: TxnFn, user_data: *mut c_void) -> c_int {
+    let (stm, f) = match (stm.as_ref(), f) {
+        (Some(stm), Some(f)) => (stm, f),
+        _ => return TL2_INVALID,
+    };
+    let out = Outcome::default();
+    let r = panic::catch_unwind(AssertUnwindSafe(|| {
+        stm.write_transaction(|tr| attempt(Trans::Write(tr), stm.capacity(), f, user_data, &out))
+            .is_some()
+    }));
+    status(r, out)
+}
+
+/// Run `f` as a read transaction until it completes or gives up.
+/// `tl2_store` fails in it.
+///
+/// # Safety
+///
+/// As for `tl2_write_txn`.
+#[no_mangle]
+pub unsafe extern "C" fn tl2_read_txn(stm: *const STM, f: TxnFn, user_data: *mut c_void) -> c_int {
+    let (stm, f) = match (stm.as_ref(), f) {
+        (Some(stm), Some(f)) => (stm, f),
+        _ => return TL2_INVALID,
+    };
+    let out = Outcome::default();
+    let r = panic::catch_unwind(AssertUnwindSafe(|| {
+        stm.read_transaction(|tr| attempt(Trans::Read(tr), stm.capacity(), f, user_data, &out))
+            .is_some()
+    }));
+    status(r, out)
+}
+
+/// Copy the 8 bytes at `addr` into `out`. `TL2_RETRY` means the attempt
+/// is doomed and the callback should return `TL2_RETRY`.
+///
+/// # Safety
+///
+/// `txn` is null or the handle passed to the running callback; `out` is
+/// null or points to 8 writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn tl2_load(txn: *mut Txn, addr: usize, out: *mut u8) -> c_int {
+    let txn = match txn.as_mut() {
+        Some(txn) if !out.is_null() && txn.valid(addr) => txn,
+        _ => return TL2_INVALID,
+    };
+    let v = txn.guard(|tr| match tr {
+        Trans::Write(tr) => tr.load(addr),
+        Trans::Read(tr) => tr.load(addr),
+    });
+    match v {
+        Some(Some(v)) => {
+            ptr::copy_nonoverlapping(v.as_ptr(), out, STRIPE_SIZE);
+            TL2_OK
+        }
+        Some(None) => TL2_RETRY,
+        None => TL2_PANIC,
+    }
+}
+
+/// Store the 8 bytes at `val` at `addr` when the transaction commits.
+///
+/// # Safety
+///
+/// `txn` is null or the handle passed to the running callback; `val` is
+/// null or points to 8 readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn tl2_store(txn: *mut Txn, addr: usize, val: *const u8) -> c_int {
+    let txn = match txn.as_mut() {
+        Some(txn) if !val.is_null() && txn.valid(addr) => txn,
+        _ => return TL2_INVALID,
+    };
+    let mut v = [0; STRIPE_SIZE];
+    ptr::copy_nonoverlapping(val, v.as_mut_ptr(), STRIPE_SIZE);
+    let stored = txn.guard(|tr| match tr {
+        Trans::Write(tr) => {
+            tr.store(addr, v);
+            true
+        }
+        Trans::Read(_) => false,
+    });
+    match stored {
+        Some(true) => TL2_OK,
+        Some(false) => TL2_INVALID,
+        None => TL2_PANIC,
+    }
+}
@@ -0,0 +1,122 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+use crate::label::MAX_LABELS;
+use crate::tl2::STRIPE_SIZE;
+
+// Up to this many stripes every stripe gets its own counter; above it a
+// count-min sketch of SKETCH_DEPTH x SKETCH_WIDTH counters bounds the memory.
+const EXACT_LIMIT: usize = 1 << 16;
+const SKETCH_DEPTH: usize = 4;
+const SKETCH_WIDTH: usize = 1 << 12;
+
+pub(crate) enum Heatmap {
+    Exact(Vec<AtomicU64>),
+    Sketch {
+        stripes: usize,
+        rows: Vec<AtomicU64>,
+    },
+}
+
+// A different multiplicative hash per sketch row.
+fn sketch_slot(row: usize, idx: usize) -> usize {
+    const SEEDS: [u64; SKETCH_DEPTH] = [
+        0x9e37_79b9_7f4a_7c15,
+        0xc2b2_ae3d_27d4_eb4f,
+        0x1656_67b1_9e37_79f9,
+        0x27d4_eb2f_1656_67c5,
+    ];
+    let h = (idx as u64 ^ (idx as u64 >> 29)).wrapping_mul(SEEDS[row]);
+    row * SKETCH_WIDTH + (h >> 52) as usize % SKETCH_WIDTH
+}
+
+impl Heatmap {
+    pub(crate) fn new(stripes: usize) -> Heatmap {
+        if stripes <= EXACT_LIMIT {
+            Heatmap::Exact((0..stripes).map(|_| AtomicU64::new(0)).collect())
+        } else {
+            Heatmap::Sketch {
+                stripes,
+                rows: (0..SKETCH_DEPTH * SKETCH_WIDTH)
+                    .map(|_| AtomicU64::new(0))
+                    .collect(),
+            }
+        }
+    }
+
+    // `idx` is the stripe (lock slot) index.
+    pub(crate) fn hit(&self, idx: usize) {
+        match self {
+            Heatmap::Exact(counts) => {
+                counts[idx].fetch_add(1, Ordering::Relaxed);
+            }
+            Heatmap::Sketch { rows, .. } => {
+                for row in 0..SKETCH_DEPTH {
+                    rows[sketch_slot(row, idx)].fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+
+    fn estimate(&self, idx: usize) -> u64 {
+        match self {
+            Heatmap::Exact(counts) => counts[idx].load(Ordering::Relaxed),
+            Heatmap::Sketch { rows, .. } => (0..SKETCH_DEPTH)
+                .map(|row| rows[sketch_slot(row, idx)].load(Ordering::Relaxed))
+                .min()
+                .unwrap_or(0),
+        }
+    }
+
+    // (address, count) of every stripe with a non-zero count, hottest first.
+    // Sketch counts are upper bounds.
+    pub(crate) fn report(&self) -> Vec<(usize, u64)> {
+        let stripes = match self {
+            Heatmap::Exact(counts) => counts.len(),
+            Heatmap::Sketch { stripes, .. } => *stripes,
+        };
+
+        let mut v: Vec<_> = (0..stripes)
+            .map(|idx| (idx * STRIPE_SIZE, self.estimate(idx)))
+            .filter(|(_, n)| *n > 0)
+            .collect();
+        v.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        v
+    }
+}
+
+// The heatmap over all transactions plus one per label bucket, allocated on
+// the first restart of that bucket.
+pub(crate) struct Heatmaps {
+    stripes: usize,
+    all: Heatmap,
+    labels: Vec<OnceLock<Heatmap>>,
+}
+
+impl Heatmaps {
+    pub(crate) fn new(stripes: usize) -> Heatmaps {
+        Heatmaps {
+            stripes,
+            all: Heatmap::new(stripes),
+            labels: (0..=MAX_LABELS).map(|_| OnceLock::new()).collect(),
+        }
+    }
+
+    pub(crate) fn hit(&self, label: usize, idx: usize) {
+        self.all.hit(idx);
+        self.labels[label]
+            .get_or_init(|| Heatmap::new(self.stripes))
+            .hit(idx);
+    }
+
+    pub(crate) fn report(&self) -> Vec<(usize, u64)> {
+        self.all.report()
+    }
+
+    pub(crate) fn report_label(&self, label: usize) -> Vec<(usize, u64)> {
+        self.labels[label]
+            .get()
+            .map(Heatmap::report)
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,44 @@
+// The last commits, kept for post-mortem debugging (the `history`
+// feature), see `STMBuilder::commit_history`.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use crate::feed::CommitRecord;
+use crate::tl2::STRIPE_SIZE;
+
+pub(crate) struct CommitHistory {
+    len: usize,
+    ring: Mutex<VecDeque<CommitRecord>>,
+}
+
+impl CommitHistory {
+    pub(crate) fn new(len: usize) -> CommitHistory {
+        CommitHistory {
+            len,
+            ring: Mutex::new(VecDeque::with_capacity(len)),
+        }
+    }
+
+    // Keep the commit of `entries` at `version`, forgetting the oldest one
+    // kept if the ring is full.
+    pub(crate) fn push(&self, version: u64, entries: &[(usize, [u8; STRIPE_SIZE])]) {
+        let mut ring = self.ring.lock().unwrap();
+        if ring.len() == self.len {
+            ring.pop_front();
+        }
+        ring.push_back(CommitRecord {
+            version,
+            entries: entries.to_vec(),
+            gap: false,
+        });
+    }
+
+    // Commits are pushed as they unlock, so sort them back into version
+    // order.
+    pub(crate) fn records(&self) -> Vec<CommitRecord> {
+        let mut records: Vec<CommitRecord> = self.ring.lock().unwrap().iter().cloned().collect();
+        records.sort_by_key(|r| r.version);
+        records
+    }
+}
@@ -0,0 +1,329 @@
+use std::collections::BTreeSet;
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::mem;
+use std::path::Path;
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::tl2::STRIPE_SIZE;
+
+/// When a commit's journal record must reach the disk.
+///
+/// Records are written before the commit's versions are published in every
+/// mode, so the file order follows the order in which commits observe each
+/// other and losing the tail of the file loses a suffix of the history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Durability {
+    /// The record is synced before the commit's versions are published, so
+    /// no reader can observe a value that is not durable yet, and
+    /// `STM::current_version` does not count the commit until then.
+    /// Commits waiting at the same time share one sync.
+    Sync,
+    /// The first commit after the interval has passed since the last sync
+    /// syncs every record written so far.
+    Interval(Duration),
+    /// The record is buffered and reaches the disk on `Journal::flush` or
+    /// when the buffer fills.
+    Lazy,
+}
+
+// A version taken for a `Sync` journal, kept out of `Journal::visible`
+// while alive.
+pub(crate) struct Reserved<'a> {
+    journal: &'a Journal,
+    ver: u64,
+}
+
+impl<'a> Drop for Reserved<'a> {
+    fn drop(&mut self) {
+        self.journal.pending.lock().unwrap().remove(&self.ver);
+    }
+}
+
+/// One committed write-set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalRecord {
+    pub version: u64,
+    pub entries: Vec<(usize, [u8; STRIPE_SIZE])>,
+}
+
+// Appended records wait in `buf` until a sync or a full buffer writes
+// them out. Everything is under one lock, released only while syncing.
+struct State {
+    buf: Vec<u8>,
+    appended: u64, // records appended so far
+    written: u64,  // bytes in the file
+    durable: u64,  // records known to be on the disk
+    durable_len: u64,
+    syncing: bool, // a leader is syncing for everyone
+    rollbacks: u64,
+    lost: Vec<(u64, u64)>, // ranges (from, to] of records dropped by a rollback
+    failed: bool,          // a rollback happened since the last flush
+    at: Instant,
+}
+
+/// An append-only file of committed write-sets.
+///
+/// A record is the commit version (u64), the number of entries (u32) and
+/// then per entry the address (u64) and the stripe bytes, all little-endian.
+/// Non-overlapping commits may append in either order; use the version to
+/// order records.
+///
+/// A failed write or sync truncates the file back to its last synced
+/// length, dropping every record after it, so the file never holds a
+/// commit that was reported as failed.
+pub struct Journal {
+    state: Mutex<State>,
+    file: File,
+    durability: Durability,
+    through: bool, // records go to the file on append, see `write_through`
+    cond: Condvar,
+    pending: Mutex<BTreeSet<u64>>, // versions not on the disk yet, for `Sync`
+}
+
+// Records buffered past this many bytes are written out on the next append.
+const BUF_LEN: usize = 64 << 10;
+
+impl Journal {
+    pub fn create<P: AsRef<Path>>(path: P, durability: Durability) -> io::Result<Journal> {
+        Ok(Journal {
+            state: Mutex::new(State {
+                buf: Vec::new(),
+                appended: 0,
+                written: 0,
+                durable: 0,
+                durable_len: 0,
+                syncing: false,
+                rollbacks: 0,
+                lost: Vec::new(),
+                failed: false,
+                at: Instant::now(),
+            }),
+            file: File::create(path)?,
+            durability,
+            through: false,
+            cond: Condvar::new(),
+            pending: Mutex::new(BTreeSet::new()),
+        })
+    }
+
+    pub fn durability(&self) -> Durability {
+        self.durability
+    }
+
+    // Write every record to the file as it is appended, so that it
+    // survives the process even before a sync.
+    #[cfg(feature = "mmap")]
+    pub(crate) fn write_through(mut self) -> Journal {
+        self.through = true;
+        self
+    }
+
+    /// Write out buffered records and sync them to the disk. Fails if a
+    /// record was dropped by a failed write since the last call.
+    pub fn flush(&self) -> io::Result<()> {
+        let appended = self.state.lock().unwrap().appended;
+        let r = self.sync_through(appended);
+        if mem::take(&mut self.state.lock().unwrap().failed) {
+            return Err(io::Error::other("a journal append failed"));
+        }
+        r
+    }
+
+    // Take the next version from `increment` for a commit about to be
+    // journaled. With `Sync`, it stays out of `visible` until the guard
+    // is dropped, once the record is on the disk or the commit is off.
+    pub(crate) fn reserve<F: FnOnce() -> u64>(&self, increment: F) -> (u64, Option<Reserved<'_>>) {
+        if self.durability != Durability::Sync {
+            return (increment(), None);
+        }
+        let mut pending = self.pending.lock().unwrap();
+        let ver = increment();
+        pending.insert(ver);
+        (ver, Some(Reserved { journal: self, ver }))
+    }
+
+    // The clock from `sample`, less any version still waiting for its
+    // record to reach the disk.
+    pub(crate) fn visible<F: FnOnce() -> u64>(&self, sample: F) -> u64 {
+        let pending = self.pending.lock().unwrap();
+        let now = sample();
+        match pending.iter().next() {
+            Some(first) => now.min(first - 1),
+            None => now,
+        }
+    }
+
+    // Write a record, and sync it if the durability asks for it. Only a
+    // `Sync` journal reports failures here; the others report them from
+    // `flush`.
+    pub(crate) fn append(
+        &self,
+        version: u64,
+        entries: &[(usize, [u8; STRIPE_SIZE])],
+    ) -> io::Result<()> {
+        let seq = {
+            let mut st = self.state.lock().unwrap();
+            st.buf.extend_from_slice(&version.to_le_bytes());
+            st.buf
+                .extend_from_slice(&(entries.len() as u32).to_le_bytes());
+            for (addr, val) in entries {
+                st.buf.extend_from_slice(&(*addr as u64).to_le_bytes());
+                st.buf.extend_from_slice(val);
+            }
+            st.appended += 1;
+            if (self.through || st.buf.len() >= BUF_LEN) && self.write_out(&mut st).is_err() {
+                self.rollback(&mut st);
+            }
+            st.appended
+        };
+
+        match self.durability {
+            Durability::Sync => self.sync_through(seq),
+            Durability::Interval(d) => {
+                if self.state.lock().unwrap().at.elapsed() >= d {
+                    // a failure is kept for `flush`
+                    let _ = self.sync_through(seq);
+                }
+                Ok(())
+            }
+            Durability::Lazy => Ok(()),
+        }
+    }
+
+    // Write the buffered records to the file.
+    fn write_out(&self, st: &mut State) -> io::Result<()> {
+        if st.buf.is_empty() {
+            return Ok(());
+        }
+        (&self.file).write_all(&st.buf)?;
+        st.written += st.buf.len() as u64;
+        st.buf.clear();
+        Ok(())
+    }
+
+    // Drop every record after the last sync, which a failed write or sync
+    // may have left half on the disk.
+    fn rollback(&self, st: &mut State) {
+        let _ = self
+            .file
+            .set_len(st.durable_len)
+            .and_then(|()| (&self.file).seek(SeekFrom::Start(st.durable_len)));
+        st.lost.push((st.durable, st.appended));
+        st.written = st.durable_len;
+        st.buf.clear();
+        st.rollbacks += 1;
+        st.failed = true;
+        self.cond.notify_all();
+    }
+
+    // Group commit: wait until the first `seq` records are on the disk.
+    // One waiter at a time becomes the leader and syncs every record
+    // written so far, covering the waiters that queued up behind it.
+    fn sync_through(&self, seq: u64) -> io::Result<()> {
+        let mut st = self.state.lock().unwrap();
+        loop {
+            if st.lost.iter().any(|&(from, to)| from < seq && seq <= to) {
+                return Err(io::Error::other("journal record dropped by a failed write"));
+            }
+            if st.durable >= seq {
+                return Ok(());
+            }
+            if !st.syncing {
+                st = self.lead_sync(st);
+                continue;
+            }
+            st = self.cond.wait(st).unwrap();
+        }
+    }
+
+    // Sync everything written so far as the leader of a group commit.
+    fn lead_sync<'a>(&'a self, mut st: MutexGuard<'a, State>) -> MutexGuard<'a, State> {
+        st.syncing = true;
+        let written = self.write_out(&mut st);
+        let (through, len, rollbacks) = (st.appended, st.written, st.rollbacks);
+        let r = match written {
+            Ok(()) => {
+                drop(st);
+                let r = self.file.sync_data();
+                st = self.state.lock().unwrap();
+                r
+            }
+            Err(e) => Err(e),
+        };
+        st.syncing = false;
+        match r {
+            Ok(()) if st.rollbacks == rollbacks => {
+                st.durable = st.durable.max(through);
+                st.durable_len = len;
+                st.at = Instant::now();
+            }
+            Ok(()) => (), // a rollback dropped what was synced
+            Err(_) => self.rollback(&mut st),
+        }
+        self.cond.notify_all();
+        st
+    }
+
+    // Drop every record, e.g. once they are all part of a checkpoint. The
+    // caller keeps commits out meanwhile.
+    #[cfg(feature = "mmap")]
+    pub(crate) fn truncate(&self) -> io::Result<()> {
+        let mut st = self.state.lock().unwrap();
+        st.buf.clear();
+        self.file.set_len(0)?;
+        (&self.file).seek(SeekFrom::Start(0))?;
+        self.file.sync_data()?;
+        st.written = 0;
+        st.durable = st.appended;
+        st.durable_len = 0;
+        Ok(())
+    }
+
+    /// Read every record of the journal at `path`.
+    pub fn records<P: AsRef<Path>>(path: P) -> io::Result<Vec<JournalRecord>> {
+        Self::read(path, false)
+    }
+
+    // Like `records`, but a record cut short, as left by a crash during an
+    // append, ends the journal instead of failing.
+    #[cfg(feature = "mmap")]
+    pub(crate) fn recover<P: AsRef<Path>>(path: P) -> io::Result<Vec<JournalRecord>> {
+        Self::read(path, true)
+    }
+
+    fn read<P: AsRef<Path>>(path: P, torn: bool) -> io::Result<Vec<JournalRecord>> {
+        let mut input = BufReader::new(File::open(path)?);
+        let mut records = Vec::new();
+        let mut head = [0; 12];
+        loop {
+            match input.read_exact(&mut head) {
+                Ok(()) => (),
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(records),
+                Err(e) => return Err(e),
+            }
+
+            let version = u64::from_le_bytes(head[..8].try_into().unwrap());
+            let len = u32::from_le_bytes(head[8..].try_into().unwrap());
+            let mut entries = Vec::with_capacity((len as usize).min(1024));
+            for _ in 0..len {
+                let mut addr = [0; 8];
+                let mut val = [0; STRIPE_SIZE];
+                match input
+                    .read_exact(&mut addr)
+                    .and_then(|()| input.read_exact(&mut val))
+                {
+                    Ok(()) => (),
+                    Err(e) if torn && e.kind() == io::ErrorKind::UnexpectedEof => {
+                        return Ok(records)
+                    }
+                    Err(e) => return Err(e),
+                }
+                entries.push((u64::from_le_bytes(addr) as usize, val));
+            }
+            records.push(JournalRecord { version, entries });
+        }
+    }
+}
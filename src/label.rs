@@ -0,0 +1,65 @@
+use std::sync::OnceLock;
+
+// Most distinct labels an STM keeps apart; later ones share the default
+// bucket with unlabeled transactions.
+pub(crate) const MAX_LABELS: usize = 64;
+
+// Slot 0 is the default bucket, slots 1..=MAX_LABELS hold labels in the order
+// they were first seen. A slot is claimed once and never changes, so lookups
+// need no lock. Claimants scan in order and only take the first empty slot,
+// hence a label never ends up in two slots.
+pub(crate) struct Labels {
+    slots: Vec<OnceLock<&'static str>>,
+}
+
+impl Labels {
+    pub(crate) fn new() -> Labels {
+        Labels {
+            slots: (0..MAX_LABELS).map(|_| OnceLock::new()).collect(),
+        }
+    }
+
+    // Bucket index of `label`, registering it on first use.
+    pub(crate) fn index(&self, label: Option<&'static str>) -> usize {
+        let label = match label {
+            Some(l) => l,
+            None => return 0,
+        };
+
+        for (i, slot) in self.slots.iter().enumerate() {
+            let l = match slot.get() {
+                Some(l) => *l,
+                None => *slot.get_or_init(|| label),
+            };
+            if l == label {
+                return i + 1;
+            }
+        }
+        0
+    }
+
+    // Bucket index of an already registered `label`.
+    pub(crate) fn find(&self, label: &str) -> Option<usize> {
+        self.buckets()
+            .find(|(_, l)| *l == Some(label))
+            .map(|(i, _)| i)
+    }
+
+    // Label of bucket `idx`, `None` for the default one.
+    pub(crate) fn name(&self, idx: usize) -> Option<&'static str> {
+        idx.checked_sub(1)
+            .and_then(|i| self.slots.get(i))
+            .and_then(|s| s.get().copied())
+    }
+
+    // Label of every bucket in use, `None` for the default one.
+    pub(crate) fn buckets(&self) -> impl Iterator<Item = (usize, Option<&'static str>)> + '_ {
+        let labels = self
+            .slots
+            .iter()
+            .map_while(|s| s.get().copied())
+            .enumerate()
+            .map(|(i, l)| (i + 1, Some(l)));
+        std::iter::once((0, None)).chain(labels)
+    }
+}
@@ -0,0 +1,183 @@
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::tl2::Phase;
+
+/// Histogram buckets. Bucket `i > 0` counts durations in
+/// `[2^(i-1), 2^i)` nanoseconds, bucket 0 counts zero.
+pub const BUCKETS: usize = 64;
+
+const PHASES: usize = 5;
+
+fn bucket(nanos: u64) -> usize {
+    (64 - nanos.leading_zeros() as usize).min(BUCKETS - 1)
+}
+
+// Time of one sampled transaction, summed per phase over its attempts.
+pub(crate) struct Timer {
+    last: Instant,
+    nanos: [u64; PHASES],
+}
+
+impl Timer {
+    // Charge the time since the previous mark to `phase`.
+    pub(crate) fn mark(&mut self, phase: Phase) {
+        let now = Instant::now();
+        let d = now.duration_since(self.last).as_nanos();
+        self.nanos[phase as usize] += d.min(u64::MAX as u128) as u64;
+        self.last = now;
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl Timer {
+    pub(crate) fn phases(&self) -> [(&'static str, Duration); PHASES] {
+        let d = |p: Phase| Duration::from_nanos(self.nanos[p as usize]);
+        [
+            ("execute", d(Phase::Execute)),
+            ("lock", d(Phase::Lock)),
+            ("validate", d(Phase::Validate)),
+            ("journal", d(Phase::Journal)),
+            ("publish", d(Phase::Publish)),
+        ]
+    }
+}
+
+pub(crate) fn mark(timer: &mut Option<Timer>, phase: Phase) {
+    if let Some(t) = timer {
+        t.mark(phase);
+    }
+}
+
+// One histogram per phase, recording every `every`-th transaction of each
+// thread.
+pub(crate) struct Latency {
+    every: u32,
+    buckets: Vec<AtomicU64>,
+}
+
+impl Latency {
+    pub(crate) fn new(every: u32) -> Latency {
+        Latency {
+            every,
+            buckets: (0..PHASES * BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+
+    // A timer if this transaction is sampled.
+    pub(crate) fn start(&self) -> Option<Timer> {
+        thread_local! {
+            static TICK: Cell<u32> = const { Cell::new(0) };
+        }
+        let tick = TICK.with(|t| {
+            let n = t.get();
+            t.set(n.wrapping_add(1));
+            n
+        });
+        if self.every == 0 || !tick.is_multiple_of(self.every) {
+            return None;
+        }
+        Some(Timer {
+            last: Instant::now(),
+            nanos: [0; PHASES],
+        })
+    }
+
+    pub(crate) fn record(&self, timer: &Timer) {
+        for (phase, nanos) in timer.nanos.iter().enumerate() {
+            self.buckets[phase * BUCKETS + bucket(*nanos)].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn snapshot(&self) -> LatencyHistograms {
+        let hist = |phase: Phase| {
+            let mut h = Histogram {
+                buckets: [0; BUCKETS],
+            };
+            let base = phase as usize * BUCKETS;
+            for (i, b) in h.buckets.iter_mut().enumerate() {
+                *b = self.buckets[base + i].load(Ordering::Relaxed);
+            }
+            h
+        };
+        LatencyHistograms {
+            execute: hist(Phase::Execute),
+            lock: hist(Phase::Lock),
+            validate: hist(Phase::Validate),
+            journal: hist(Phase::Journal),
+            publish: hist(Phase::Publish),
+        }
+    }
+
+    pub(crate) fn reset(&self) {
+        for b in self.buckets.iter() {
+            b.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Log-scale histogram of durations, see `BUCKETS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Histogram {
+    pub buckets: [u64; BUCKETS],
+}
+
+impl Histogram {
+    /// Count `d` in its bucket, as a sampled transaction does.
+    pub fn record(&mut self, d: Duration) {
+        let nanos = d.as_nanos().min(u64::MAX as u128) as u64;
+        self.buckets[bucket(nanos)] += 1;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    /// Upper bound of the bucket holding the `q`-quantile (`0.0..=1.0`),
+    /// or zero for an empty histogram.
+    pub fn percentile(&self, q: f64) -> Duration {
+        let count = self.count();
+        if count == 0 {
+            return Duration::ZERO;
+        }
+        let rank = ((q.clamp(0.0, 1.0) * count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (i, n) in self.buckets.iter().enumerate() {
+            seen += n;
+            if seen >= rank {
+                return Duration::from_nanos(if i == 0 { 0 } else { 1 << i });
+            }
+        }
+        Duration::from_nanos(u64::MAX)
+    }
+
+    pub fn p50(&self) -> Duration {
+        self.percentile(0.50)
+    }
+
+    pub fn p95(&self) -> Duration {
+        self.percentile(0.95)
+    }
+
+    pub fn p99(&self) -> Duration {
+        self.percentile(0.99)
+    }
+}
+
+/// Sampled write transaction latencies per phase, see
+/// `STMBuilder::latency_sample_every`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencyHistograms {
+    /// Running the body, restarts included.
+    pub execute: Histogram,
+    /// Locking the write-set.
+    pub lock: Histogram,
+    /// Validating the read-set.
+    pub validate: Histogram,
+    /// Appending to the journal and waiting for it to sync; zero without
+    /// a journal.
+    pub journal: Histogram,
+    /// Copying the write-set out and unlocking.
+    pub publish: Histogram,
+}
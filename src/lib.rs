@@ -0,0 +1,169 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+// wasm threads need a nightly toolchain anyway (std is rebuilt with
+// `-Z build-std`), and `Wait` needs this one
+#![cfg_attr(
+    all(target_family = "wasm", target_feature = "atomics"),
+    feature(stdarch_wasm_atomic_wait)
+)]
+
+extern crate alloc;
+
+#[cfg(feature = "tokio")]
+mod async_tx;
+mod barrier;
+#[cfg(feature = "std")]
+mod breaker;
+mod clock;
+#[cfg(feature = "commit-log")]
+mod commitlog;
+mod conflict;
+mod contention;
+mod counters;
+#[cfg(feature = "mmap")]
+mod durable;
+#[cfg(feature = "std")]
+mod emit;
+#[cfg(feature = "std")]
+mod events;
+#[cfg(feature = "testing")]
+mod fault;
+#[cfg(feature = "std")]
+mod feed;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "std")]
+mod heatmap;
+#[cfg(feature = "history")]
+mod history;
+#[cfg(feature = "std")]
+mod journal;
+#[cfg(feature = "std")]
+mod label;
+#[cfg(feature = "std")]
+mod latency;
+#[cfg(feature = "prometheus")]
+mod metrics;
+#[cfg(feature = "std")]
+mod mode;
+#[cfg(feature = "std")]
+mod observer;
+mod packed;
+#[cfg(feature = "rayon")]
+mod par;
+#[cfg(feature = "std")]
+mod persist;
+#[cfg(feature = "pmem")]
+mod pmem;
+#[cfg(feature = "std")]
+mod profile;
+mod record;
+#[cfg(feature = "std")]
+mod registry;
+mod relax;
+#[cfg(feature = "std")]
+mod replica;
+#[cfg(feature = "testing")]
+mod sched;
+mod seqlock;
+#[cfg(all(unix, feature = "shm"))]
+mod shm;
+mod soa;
+#[cfg(feature = "std")]
+mod stats;
+mod storage;
+mod sync;
+mod tbig;
+#[cfg(feature = "testkit")]
+mod testkit;
+mod tl2;
+mod tlog;
+mod trace;
+mod tset;
+mod txmutex;
+#[cfg(feature = "testing")]
+mod txscript;
+mod value;
+mod vclock;
+mod wait;
+#[cfg(feature = "std")]
+mod watchdog;
+#[cfg(feature = "std")]
+mod window;
+#[cfg(feature = "trace")]
+mod workload;
+
+pub use crate::barrier::TxBarrier;
+#[cfg(feature = "std")]
+pub use crate::breaker::BreakerStats;
+pub use crate::clock::{AtomicClock, Clock};
+#[cfg(feature = "commit-log")]
+pub use crate::commitlog::{CommitLog, CommitSink};
+pub use crate::conflict::{Conflict, ConflictCause};
+pub use crate::contention::{
+    Contender, ContentionManager, Greedy, Karma, LockInfo, Polite, Resolution, TxPriority,
+};
+pub use crate::counters::Counters;
+#[cfg(feature = "std")]
+pub use crate::events::{EventKind, TxEvent};
+#[cfg(feature = "testing")]
+pub use crate::fault::{Fault, FaultInjector, Trigger};
+#[cfg(feature = "std")]
+pub use crate::feed::CommitRecord;
+#[cfg(feature = "std")]
+pub use crate::journal::{Durability, Journal, JournalRecord};
+#[cfg(feature = "std")]
+pub use crate::latency::{Histogram, LatencyHistograms, BUCKETS};
+#[cfg(feature = "std")]
+pub use crate::mode::Mode;
+#[cfg(feature = "std")]
+pub use crate::observer::{TxInfo, TxObserver};
+pub use crate::packed::PackedArray;
+#[cfg(feature = "rayon")]
+pub use crate::par::ParError;
+#[cfg(feature = "std")]
+pub use crate::persist::LoadError;
+#[cfg(feature = "pmem")]
+pub use crate::pmem::PmemStorage;
+#[cfg(feature = "std")]
+pub use crate::profile::{AttemptOutcome, AttemptProfile, TxProfile};
+#[cfg(all(target_family = "wasm", target_feature = "atomics"))]
+pub use crate::relax::Wait;
+#[cfg(feature = "std")]
+pub use crate::relax::Yield;
+pub use crate::relax::{Relax, Spin};
+#[cfg(feature = "std")]
+pub use crate::replica::{ReplicaError, ReplicaSnapshot};
+#[cfg(feature = "testing")]
+pub use crate::sched::{Scheduler, Script, YieldPoint};
+pub use crate::seqlock::SeqlockCell;
+pub use crate::soa::SoaStm;
+#[cfg(feature = "std")]
+pub use crate::stats::{LabelStats, StatsSnapshot};
+#[cfg(feature = "mmap")]
+pub use crate::storage::MmapStorage;
+pub use crate::storage::{ExternalStorage, HeapStorage, Storage};
+pub use crate::tbig::TBig;
+#[cfg(feature = "testkit")]
+pub use crate::testkit::{
+    Call, CounterOp, CounterSpec, Harness, History, HistoryRecorder, LinearizeError, LogOp,
+    LogSpec, Mismatch, Operation, Schedule, SetOp, SetSpec, Spec,
+};
+pub use crate::tl2::*;
+pub use crate::tlog::TLog;
+pub use crate::tset::{RegionFull, TSet};
+pub use crate::txmutex::{TxMutex, TxMutexGuard};
+#[cfg(feature = "testing")]
+pub use crate::txscript::{TxScript, TxStep};
+pub use crate::value::{BigValue, StripeValue};
+pub use crate::vclock::VClock;
+#[cfg(all(target_os = "linux", feature = "futex"))]
+pub use crate::wait::FutexWait;
+#[cfg(feature = "std")]
+pub use crate::wait::ParkWait;
+pub use crate::wait::{SpinWait, WaitStrategy, WaitToken};
+#[cfg(feature = "std")]
+pub use crate::watchdog::StalledTx;
+#[cfg(feature = "std")]
+pub use crate::window::{ClockRate, ClockSample, ThroughputWindow, WINDOW_SECS};
+#[cfg(feature = "trace")]
+pub use crate::workload::{Replayer, Trace, TraceError, TraceRecord, TraceRecorder};
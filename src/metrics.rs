@@ -0,0 +1,155 @@
+// Prometheus text exposition (format 0.0.4) of the STM counters.
+
+use std::fmt::Write;
+
+use crate::tl2::{STM, STRIPE_SIZE};
+
+// Metric names must match [a-zA-Z_:][a-zA-Z0-9_:]*; anything else in the
+// namespace becomes '_'.
+fn sanitize_name(s: &str) -> String {
+    let mut out: String = s
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == ':' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if out.is_empty() || out.starts_with(|c: char| c.is_ascii_digit()) {
+        out.insert(0, '_');
+    }
+    out
+}
+
+// Label values escape backslash, double quote and line feed.
+fn escape_value(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+struct Exposition {
+    prefix: String,
+    out: String,
+}
+
+impl Exposition {
+    fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.out, "# HELP {}_{} {}", self.prefix, name, help);
+        let _ = writeln!(self.out, "# TYPE {}_{} {}", self.prefix, name, kind);
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: u64) {
+        let _ = write!(self.out, "{}_{}", self.prefix, name);
+        if !labels.is_empty() {
+            let labels: Vec<_> = labels
+                .iter()
+                .map(|(k, v)| format!("{}=\"{}\"", k, escape_value(v)))
+                .collect();
+            let _ = write!(self.out, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(self.out, " {}", value);
+    }
+}
+
+impl STM {
+    /// The metrics of this STM in the Prometheus text format, every name
+    /// prefixed with `namespace`. Counters are only present when enabled
+    /// with `STMBuilder::stats`; unlabeled transactions carry `label=""`.
+    pub fn metrics_prometheus(&self, namespace: &str) -> String {
+        let mut e = Exposition {
+            prefix: sanitize_name(namespace),
+            out: String::new(),
+        };
+
+        if let Some(s) = self.stats() {
+            e.family("commits_total", "counter", "Committed write transactions.");
+            e.sample("commits_total", &[], s.commits);
+            e.family("reads_total", "counter", "Completed read transactions.");
+            e.sample("reads_total", &[], s.reads);
+            e.family("aborts_total", "counter", "Aborted transactions.");
+            e.sample("aborts_total", &[], s.aborts);
+            e.family(
+                "retries_total",
+                "counter",
+                "Transactions that gave up on Retry.",
+            );
+            e.sample("retries_total", &[], s.retries);
+            e.family(
+                "restarts_total",
+                "counter",
+                "Transaction restarts by cause.",
+            );
+            e.sample(
+                "restarts_total",
+                &[("cause", "pre_validation")],
+                s.pre_validation,
+            );
+            e.sample(
+                "restarts_total",
+                &[("cause", "post_validation")],
+                s.post_validation,
+            );
+            e.sample("restarts_total", &[("cause", "lock")], s.lock);
+            e.sample("restarts_total", &[("cause", "validation")], s.validation);
+            e.family(
+                "feed_drops_total",
+                "counter",
+                "Commit records dropped by full subscribers.",
+            );
+            e.sample("feed_drops_total", &[], s.feed_drops);
+            e.family(
+                "escalations_total",
+                "counter",
+                "Write transactions escalated to run serialized.",
+            );
+            e.sample("escalations_total", &[], s.escalations);
+        }
+
+        if let Some(labels) = self.stats_by_label() {
+            e.family(
+                "label_outcomes_total",
+                "counter",
+                "Transaction outcomes by transaction label.",
+            );
+            for (label, s) in labels {
+                let label = label.unwrap_or("");
+                for (outcome, n) in [
+                    ("commit", s.commits),
+                    ("read", s.reads),
+                    ("abort", s.aborts),
+                    ("retry", s.retries),
+                    ("restart", s.restarts),
+                ] {
+                    e.sample(
+                        "label_outcomes_total",
+                        &[("label", label), ("outcome", outcome)],
+                        n,
+                    );
+                }
+            }
+        }
+
+        e.family("stripes", "gauge", "Stripes in the lock table.");
+        e.sample("stripes", &[], (self.capacity() / STRIPE_SIZE) as u64);
+        e.family(
+            "locked_stripes",
+            "gauge",
+            "Stripes locked by committing writers.",
+        );
+        e.sample("locked_stripes", &[], self.locked_stripes().len() as u64);
+        e.family("clock", "gauge", "Value of the global version-clock.");
+        e.sample("clock", &[], self.current_version());
+
+        e.out
+    }
+}
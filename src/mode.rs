@@ -0,0 +1,47 @@
+// The recent mix of read-only and read-write transactions of a thread,
+// see `STM::suggest_mode`.
+
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+// Transactions remembered per thread.
+const HISTORY: u8 = 64;
+
+/// Which kind of transaction to run, see `STM::suggest_mode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// `STM::read_transaction`
+    Read,
+    /// `STM::write_transaction`
+    Write,
+}
+
+// The last `HISTORY` committed transactions of one thread, a bit each, 1
+// for one that wrote nothing. Only the owning thread records, so plain
+// loads and stores do.
+#[derive(Default)]
+pub(crate) struct ModeHistory {
+    bits: AtomicU64,
+    len: AtomicU8,
+}
+
+impl ModeHistory {
+    pub(crate) fn record(&self, read_only: bool) {
+        let bits = self.bits.load(Ordering::Relaxed) << 1 | read_only as u64;
+        self.bits.store(bits, Ordering::Relaxed);
+        let len = self.len.load(Ordering::Relaxed);
+        if len < HISTORY {
+            self.len.store(len + 1, Ordering::Relaxed);
+        }
+    }
+
+    // `Read` once most of the remembered transactions wrote nothing.
+    pub(crate) fn suggest(&self) -> Mode {
+        let len = self.len.load(Ordering::Relaxed) as u32;
+        let read_only = self.bits.load(Ordering::Relaxed).count_ones();
+        if len > 0 && 2 * read_only > len {
+            Mode::Read
+        } else {
+            Mode::Write
+        }
+    }
+}
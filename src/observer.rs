@@ -0,0 +1,91 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::conflict::Conflict;
+use crate::watchdog::StalledTx;
+
+/// What an observer is told about a transaction.
+#[derive(Debug, Clone, Copy)]
+pub struct TxInfo {
+    /// The label given to a `*_labeled` transaction.
+    pub label: Option<&'static str>,
+    pub read_only: bool,
+    /// The current attempt, starting at 1.
+    pub attempt: u32,
+    /// Read-set and write-set sizes of the attempt (zero when beginning and
+    /// for read transactions, which keep no sets).
+    pub read_set: usize,
+    pub write_set: usize,
+    /// Time since the transaction began.
+    pub elapsed: Duration,
+}
+
+/// Callbacks from the retry loops, set with `STM::set_observer`.
+///
+/// They run on the transaction's thread after the attempt has released its
+/// stripe locks, so a slow observer delays only its own transaction.
+pub trait TxObserver: Send + Sync {
+    fn on_begin(&self, _info: &TxInfo) {}
+    fn on_restart(&self, _info: &TxInfo, _conflict: Conflict) {}
+    fn on_commit(&self, _info: &TxInfo) {}
+    fn on_abort(&self, _info: &TxInfo) {}
+    /// The attempt `info.attempt` is about to run serialized, see
+    /// `STMBuilder::escalate_after`. The sets are zero.
+    fn on_escalate(&self, _info: &TxInfo) {}
+    /// Called from `STM::check_stalled`, on the polling thread.
+    fn on_stall(&self, _tx: &StalledTx) {}
+}
+
+// The observer of one transaction, taken when it begins so that replacing
+// the observer never affects a transaction in flight.
+pub(crate) struct Observing {
+    observer: Arc<dyn TxObserver>,
+    label: Option<&'static str>,
+    read_only: bool,
+    start: Instant,
+}
+
+impl Observing {
+    pub(crate) fn begin(
+        observer: Arc<dyn TxObserver>,
+        label: Option<&'static str>,
+        read_only: bool,
+    ) -> Observing {
+        let o = Observing {
+            observer,
+            label,
+            read_only,
+            start: Instant::now(),
+        };
+        o.observer.on_begin(&o.info(1, 0, 0));
+        o
+    }
+
+    fn info(&self, attempt: u32, read_set: usize, write_set: usize) -> TxInfo {
+        TxInfo {
+            label: self.label,
+            read_only: self.read_only,
+            attempt,
+            read_set,
+            write_set,
+            elapsed: self.start.elapsed(),
+        }
+    }
+
+    pub(crate) fn restart(&self, attempt: u32, sets: (usize, usize), conflict: Conflict) {
+        self.observer
+            .on_restart(&self.info(attempt, sets.0, sets.1), conflict);
+    }
+
+    pub(crate) fn commit(&self, attempt: u32, sets: (usize, usize)) {
+        self.observer.on_commit(&self.info(attempt, sets.0, sets.1));
+    }
+
+    pub(crate) fn escalate(&self, attempt: u32) {
+        self.observer.on_escalate(&self.info(attempt, 0, 0));
+    }
+
+    pub(crate) fn abort(&self, attempt: u32, sets: (usize, usize)) {
+        self.observer.on_abort(&self.info(attempt, sets.0, sets.1));
+    }
+}
@@ -0,0 +1,51 @@
+use core::marker::PhantomData;
+
+use crate::tl2::{check_aligned, Trans, WriteTrans, STRIPE_SIZE};
+use crate::value::StripeValue;
+
+/// An array giving every element its own stripe. Neighbouring elements never
+/// share a lock/version word, so transactions touching different indices do
+/// not conflict, at the price of padding each element to a full stripe.
+pub struct PackedArray<T> {
+    base: usize,
+    len: usize,
+    _elem: PhantomData<T>,
+}
+
+impl<T: StripeValue> PackedArray<T> {
+    /// Lay out `len` elements starting at the stripe-aligned address `base`.
+    pub fn new(base: usize, len: usize) -> PackedArray<T> {
+        check_aligned(base);
+        len.checked_mul(STRIPE_SIZE)
+            .and_then(|size| base.checked_add(size))
+            .expect("array end overflows usize");
+
+        PackedArray {
+            base,
+            len,
+            _elem: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Address of the stripe holding element `idx`.
+    pub fn addr(&self, idx: usize) -> usize {
+        assert!(idx < self.len);
+        self.base + idx * STRIPE_SIZE
+    }
+
+    pub fn get<R: Trans>(&self, tr: &mut R, idx: usize) -> Option<T> {
+        tr.load(self.addr(idx)).map(T::from_stripe)
+    }
+
+    pub fn set(&self, tr: &mut WriteTrans, idx: usize, val: T) {
+        tr.store(self.addr(idx), val.to_stripe());
+    }
+}
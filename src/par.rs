@@ -0,0 +1,112 @@
+// Write transactions fanned out over an index range on the rayon pool (the
+// `rayon` feature).
+
+use std::cell::Cell;
+use std::fmt;
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+
+use rayon::prelude::*;
+
+use crate::tl2::{STMResult, TxError, WriteTrans, STM};
+
+/// Why `par_transact` returned no results. When several transactions
+/// fail, this is the one for the lowest index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParError {
+    /// The transaction for `index` failed with `error`.
+    Tx { index: usize, error: TxError },
+    /// The body panicked on `index`.
+    Panic { index: usize },
+}
+
+impl fmt::Display for ParError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParError::Tx { index, error } => write!(f, "index {}: {}", index, error),
+            ParError::Panic { index } => write!(f, "index {}: transaction panicked", index),
+        }
+    }
+}
+
+impl std::error::Error for ParError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParError::Tx { error, .. } => Some(error),
+            ParError::Panic { .. } => None,
+        }
+    }
+}
+
+impl STM {
+    /// Run `f` for every index in `range` on the rayon pool, one write
+    /// transaction per index, and return the results in index order.
+    ///
+    /// A failing or panicking transaction does not stop the others: every
+    /// other index still runs and commits, and the error for the lowest
+    /// failed index is returned.
+    pub fn par_transact<F, R>(&self, range: Range<usize>, f: F) -> Result<Vec<R>, ParError>
+    where
+        F: Fn(&mut WriteTrans, usize) -> STMResult<R> + Sync,
+        R: Send,
+    {
+        self.par_transact_batched(range, 1, f)
+    }
+
+    /// Like `par_transact`, but runs `batch` consecutive indices in one
+    /// transaction, which commits or fails as a whole. Larger batches cost
+    /// fewer commits and conflict more.
+    pub fn par_transact_batched<F, R>(
+        &self,
+        range: Range<usize>,
+        batch: usize,
+        f: F,
+    ) -> Result<Vec<R>, ParError>
+    where
+        F: Fn(&mut WriteTrans, usize) -> STMResult<R> + Sync,
+        R: Send,
+    {
+        assert!(batch > 0);
+        let starts: Vec<usize> = range.clone().step_by(batch).collect();
+        let batches: Vec<_> = starts
+            .into_par_iter()
+            .map(|start| self.transact_batch(start..range.end.min(start + batch), &f))
+            .collect();
+
+        let mut out = Vec::with_capacity(range.len());
+        for b in batches {
+            out.extend(b?);
+        }
+        Ok(out)
+    }
+
+    fn transact_batch<F, R>(&self, indices: Range<usize>, f: &F) -> Result<Vec<R>, ParError>
+    where
+        F: Fn(&mut WriteTrans, usize) -> STMResult<R>,
+    {
+        // the index the last run of the body stopped at
+        let at = Cell::new(indices.start);
+        let r = panic::catch_unwind(AssertUnwindSafe(|| {
+            self.try_write_transaction(|tr| {
+                let mut out = Vec::with_capacity(indices.len());
+                for i in indices.clone() {
+                    at.set(i);
+                    match f(tr, i) {
+                        STMResult::Ok(r) => out.push(r),
+                        STMResult::Retry => return STMResult::Retry,
+                        STMResult::Abort => return STMResult::Abort,
+                    }
+                }
+                STMResult::Ok(out)
+            })
+        }));
+        match r {
+            Ok(Ok(out)) => Ok(out),
+            Ok(Err(error)) => Err(ParError::Tx {
+                index: at.get(),
+                error,
+            }),
+            Err(_) => Err(ParError::Panic { index: at.get() }),
+        }
+    }
+}
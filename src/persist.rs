@@ -0,0 +1,236 @@
+//! Saving and loading the contents of an STM, see `STM::save_to` and
+//! `STM::load_from`.
+//!
+//! A snapshot file is, all integers little-endian:
+//!
+//! | offset         | size       | field                                  |
+//! |----------------|------------|----------------------------------------|
+//! | 0              | 8          | magic `b"TL2SNAP\0"`                   |
+//! | 8              | 4          | format version (u32), currently 1      |
+//! | 12             | 4          | stripe size in bytes (u32)             |
+//! | 16             | 8          | capacity in bytes (u64)                |
+//! | 24             | capacity   | memory contents                        |
+//! | 24 + capacity  | 8          | FNV-1a 64 of every byte before it      |
+//!
+//! Versions and the clock are not saved: a loaded STM starts with all of
+//! them at zero.
+
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::tl2::{STM, STRIPE_SIZE};
+
+const MAGIC: [u8; 8] = *b"TL2SNAP\0";
+const VERSION: u32 = 1;
+const HEADER: usize = 24;
+const TRAILER: usize = 8;
+
+/// Why `STM::load_from` refused a file.
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    /// The file does not start with the snapshot magic.
+    BadMagic,
+    /// The file was written in a format this build cannot read.
+    Version(u32),
+    /// The file was written with a different stripe size.
+    StripeSize {
+        found: u32,
+        expected: u32,
+    },
+    /// The capacity is not a multiple of the stripe size or does not fit in
+    /// a `usize`.
+    Capacity(u64),
+    /// The file is shorter or longer than its header says.
+    Length {
+        found: u64,
+        expected: u64,
+    },
+    /// The contents do not match the stored checksum.
+    Checksum {
+        found: u64,
+        expected: u64,
+    },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "snapshot read failed: {}", e),
+            LoadError::BadMagic => write!(f, "not an STM snapshot"),
+            LoadError::Version(v) => write!(
+                f,
+                "snapshot format version {} is not supported (expected {})",
+                v, VERSION
+            ),
+            LoadError::StripeSize { found, expected } => write!(
+                f,
+                "snapshot stripe size is {} bytes, this build uses {}",
+                found, expected
+            ),
+            LoadError::Capacity(c) => write!(f, "snapshot capacity {} is not usable", c),
+            LoadError::Length { found, expected } => write!(
+                f,
+                "snapshot is {} bytes, its header says {}",
+                found, expected
+            ),
+            LoadError::Checksum { found, expected } => write!(
+                f,
+                "snapshot checksum is {:#018x}, stored {:#018x}",
+                found, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> LoadError {
+        LoadError::Io(e)
+    }
+}
+
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Fnv {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+}
+
+fn header(capacity: usize) -> [u8; HEADER] {
+    let mut h = [0; HEADER];
+    h[0..8].copy_from_slice(&MAGIC);
+    h[8..12].copy_from_slice(&VERSION.to_le_bytes());
+    h[12..16].copy_from_slice(&(STRIPE_SIZE as u32).to_le_bytes());
+    h[16..24].copy_from_slice(&(capacity as u64).to_le_bytes());
+    h
+}
+
+// Write `data` as a snapshot file at `path`. The file is written and
+// synced under a temporary name next to it and then renamed over `path`,
+// so a crash leaves either the old snapshot or the new one, never a torn
+// one.
+pub(crate) fn write_snapshot(path: &Path, data: &[u8]) -> io::Result<()> {
+    let header = header(data.len());
+
+    let mut sum = Fnv::new();
+    sum.update(&header);
+    sum.update(data);
+
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let written = (|| {
+        let mut out = BufWriter::new(File::create(&tmp)?);
+        out.write_all(&header)?;
+        out.write_all(data)?;
+        out.write_all(&sum.0.to_le_bytes())?;
+        out.flush()?;
+        out.get_ref().sync_all()?;
+        fs::rename(&tmp, path)
+    })();
+    if written.is_err() {
+        let _ = fs::remove_file(&tmp);
+        return written;
+    }
+
+    // make the rename itself durable
+    #[cfg(unix)]
+    {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+// The memory contents of the snapshot file at `path`.
+pub(crate) fn read_snapshot(path: &Path) -> Result<Vec<u8>, LoadError> {
+    let mut file = fs::read(path)?;
+    if file.len() < HEADER || file[0..8] != MAGIC {
+        return Err(LoadError::BadMagic);
+    }
+
+    let version = u32::from_le_bytes(file[8..12].try_into().unwrap());
+    if version != VERSION {
+        return Err(LoadError::Version(version));
+    }
+    let stripe = u32::from_le_bytes(file[12..16].try_into().unwrap());
+    if stripe as usize != STRIPE_SIZE {
+        return Err(LoadError::StripeSize {
+            found: stripe,
+            expected: STRIPE_SIZE as u32,
+        });
+    }
+    let capacity = u64::from_le_bytes(file[16..24].try_into().unwrap());
+    let len = match usize::try_from(capacity) {
+        Ok(n) if n.is_multiple_of(STRIPE_SIZE) && n.checked_add(HEADER + TRAILER).is_some() => n,
+        _ => return Err(LoadError::Capacity(capacity)),
+    };
+    if file.len() != HEADER + len + TRAILER {
+        return Err(LoadError::Length {
+            found: file.len() as u64,
+            expected: (HEADER + len + TRAILER) as u64,
+        });
+    }
+
+    let (body, trailer) = file.split_at(HEADER + len);
+    let stored = u64::from_le_bytes(trailer.try_into().unwrap());
+    let mut sum = Fnv::new();
+    sum.update(body);
+    if sum.0 != stored {
+        return Err(LoadError::Checksum {
+            found: sum.0,
+            expected: stored,
+        });
+    }
+
+    file.truncate(HEADER + len);
+    file.drain(..HEADER);
+    Ok(file)
+}
+
+impl STM {
+    /// Write a consistent snapshot of the whole memory to `path`, replacing
+    /// the file if it exists. Transactions may run meanwhile; the snapshot
+    /// is taken in one read transaction. The file is written as
+    /// `<path>.tmp` and renamed over `path` once synced, so `path` always
+    /// holds a complete snapshot.
+    pub fn save_to<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let data = self
+            .snapshot_range(0, self.capacity())
+            .ok_or_else(|| io::Error::other("snapshot read failed"))?;
+        write_snapshot(path.as_ref(), &data)
+    }
+
+    /// Build an STM with the capacity and contents of a snapshot written by
+    /// `save_to`. Versions and the clock start at zero; other settings are
+    /// the defaults of `STM::builder`.
+    pub fn load_from<P: AsRef<Path>>(path: P) -> Result<STM, LoadError> {
+        let data = read_snapshot(path.as_ref())?;
+        let mut stm = STM::builder().capacity(data.len()).build();
+        stm.fill(0, &data);
+        Ok(stm)
+    }
+}
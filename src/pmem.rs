@@ -0,0 +1,365 @@
+//! Commits made durable in persistent memory, without a journal.
+//!
+//! A `PmemStorage` file holds the bytes and, per stripe, an undo record:
+//! the version of the last commit that wrote the stripe and the bytes it
+//! replaced. A commit claims a slot naming its version, then for every
+//! stripe persists the undo record before the new bytes, and clears the
+//! slot once all of them are persisted: that is the point the commit
+//! becomes durable. After a crash, `STM::recover_pmem` puts the old bytes
+//! back into every stripe whose undo record names a version still in a
+//! slot.
+//!
+//! Persisting is `clwb` (or `clflushopt`, or `clflush`) per cache line and
+//! an `sfence` on x86-64, which reaches the media when the file lives on a
+//! DAX mount; elsewhere it is `msync` of the pages involved.
+
+use alloc::sync::Arc;
+use std::fs::OpenOptions;
+use std::io;
+use std::path::Path;
+use std::process;
+
+use memmap2::MmapMut;
+
+use crate::relax::Relax;
+use crate::storage::Storage;
+use crate::sync::{AtomicU64, AtomicUsize, Byte, Ordering, RawByte};
+use crate::tl2::{STM, STRIPE_SIZE};
+
+const MAGIC: u64 = u64::from_le_bytes(*b"tl2-pmem");
+// Bump when the layout below changes.
+const LAYOUT_VERSION: u32 = 1;
+// Bytes before the slots: magic, layout version, stripe size and size.
+const HEADER: usize = 64;
+// Commits that can be writing back at once.
+const SLOTS: usize = 64;
+// An undo record: the version that wrote the stripe and its old bytes.
+const UNDO: usize = 8 + STRIPE_SIZE;
+const LINE: usize = 64;
+
+fn total_len(size: usize) -> usize {
+    data_offset(size) + size
+}
+
+fn data_offset(size: usize) -> usize {
+    HEADER + SLOTS * 8 + size / STRIPE_SIZE * UNDO
+}
+
+#[derive(Clone, Copy)]
+enum Flush {
+    #[cfg(target_arch = "x86_64")]
+    Clwb,
+    #[cfg(target_arch = "x86_64")]
+    Clflushopt,
+    #[cfg(target_arch = "x86_64")]
+    Clflush,
+    #[cfg_attr(target_arch = "x86_64", allow(dead_code))]
+    Msync,
+}
+
+impl Flush {
+    #[cfg(target_arch = "x86_64")]
+    fn detect() -> Flush {
+        use core::arch::x86_64::__cpuid_count;
+        // leaf 7: bit 24 of ebx is clwb, bit 23 clflushopt
+        let ebx = __cpuid_count(7, 0).ebx;
+        if ebx & 1 << 24 != 0 {
+            Flush::Clwb
+        } else if ebx & 1 << 23 != 0 {
+            Flush::Clflushopt
+        } else {
+            Flush::Clflush
+        }
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    fn detect() -> Flush {
+        Flush::Msync
+    }
+}
+
+// The mapping of a `PmemStorage` file.
+pub(crate) struct Pmem {
+    map: MmapMut,
+    base: *mut u8,
+    size: usize,
+    flush: Flush,
+    crash_after: Option<usize>,
+    written: AtomicUsize, // stripes, counted toward `crash_after`
+}
+
+// SAFETY: `base` points into `map`, which the region owns, and everything
+// behind it is accessed through atomics
+unsafe impl Send for Pmem {}
+unsafe impl Sync for Pmem {}
+
+impl Pmem {
+    fn words(&self, offset: usize, n: usize) -> &[AtomicU64] {
+        // SAFETY: in bounds, see `total_len`, and 8-byte aligned
+        unsafe { core::slice::from_raw_parts(self.base.add(offset) as *const AtomicU64, n) }
+    }
+
+    fn bytes(&self, offset: usize, n: usize) -> &[RawByte] {
+        // SAFETY: in bounds, see `total_len`
+        unsafe { core::slice::from_raw_parts(self.base.add(offset) as *const RawByte, n) }
+    }
+
+    fn slots(&self) -> &[AtomicU64] {
+        self.words(HEADER, SLOTS)
+    }
+
+    fn undo_offset(&self, addr: usize) -> usize {
+        HEADER + SLOTS * 8 + addr / STRIPE_SIZE * UNDO
+    }
+
+    fn undo_ver(&self, addr: usize) -> &AtomicU64 {
+        &self.words(self.undo_offset(addr), 1)[0]
+    }
+
+    fn undo_bytes(&self, addr: usize) -> &[RawByte] {
+        self.bytes(self.undo_offset(addr) + 8, STRIPE_SIZE)
+    }
+
+    fn data(&self) -> *mut u8 {
+        // SAFETY: in bounds, see `total_len`
+        unsafe { self.base.add(data_offset(self.size)) }
+    }
+
+    // Write the cache lines of `offset..offset + len` back to the media.
+    fn persist(&self, offset: usize, len: usize) {
+        match self.flush {
+            #[cfg(target_arch = "x86_64")]
+            Flush::Clwb | Flush::Clflushopt | Flush::Clflush => {
+                let mut line = offset & !(LINE - 1);
+                while line < offset + len {
+                    // SAFETY: the line is inside the mapping; the
+                    // instruction was detected on this CPU
+                    unsafe {
+                        let p = self.base.add(line);
+                        match self.flush {
+                            Flush::Clwb => {
+                                core::arch::asm!("clwb [{}]", in(reg) p, options(nostack))
+                            }
+                            Flush::Clflushopt => {
+                                core::arch::asm!("clflushopt [{}]", in(reg) p, options(nostack))
+                            }
+                            _ => core::arch::x86_64::_mm_clflush(p),
+                        }
+                    }
+                    line += LINE;
+                }
+            }
+            Flush::Msync => {
+                // an error leaves the commit in memory only; the next
+                // `STM::sync` reports it
+                let _ = self.map.flush_range(offset, len);
+            }
+        }
+    }
+
+    // Order the write-backs before the stores that follow.
+    fn fence(&self) {
+        #[cfg(target_arch = "x86_64")]
+        // SAFETY: sfence is available on every x86-64 CPU
+        unsafe {
+            core::arch::x86_64::_mm_sfence()
+        }
+    }
+
+    fn offset_of(&self, p: *const u8) -> usize {
+        p as usize - self.base as usize
+    }
+
+    // Claim a slot for the commit of version `ver`.
+    pub(crate) fn begin(&self, ver: u64, relax: &dyn Relax) -> usize {
+        loop {
+            for (i, slot) in self.slots().iter().enumerate() {
+                if slot
+                    .compare_exchange(0, ver, Ordering::AcqRel, Ordering::Relaxed)
+                    .is_ok()
+                {
+                    self.persist(HEADER + i * 8, 8);
+                    self.fence();
+                    return i;
+                }
+            }
+            relax.relax();
+        }
+    }
+
+    // Persist the undo record of the stripe at `addr`, about to be
+    // overwritten by the commit of version `ver`. Called with the stripe
+    // locked.
+    pub(crate) fn save(&self, addr: usize, ver: u64, old: &[Byte]) {
+        for (dst, src) in self.undo_bytes(addr).iter().zip(old) {
+            dst.store(src.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+        self.undo_ver(addr).store(ver, Ordering::Relaxed);
+        self.persist(self.undo_offset(addr), UNDO);
+        self.fence();
+    }
+
+    // Write back the new bytes of the stripe at `addr`.
+    pub(crate) fn written(&self, addr: usize) {
+        self.persist(self.offset_of(self.data()) + addr, STRIPE_SIZE);
+        if let Some(n) = self.crash_after {
+            if self.written.fetch_add(1, Ordering::Relaxed) + 1 == n {
+                process::abort();
+            }
+        }
+    }
+
+    // Make the commit in `slot` durable.
+    pub(crate) fn end(&self, slot: usize) {
+        self.fence();
+        self.slots()[slot].store(0, Ordering::Release);
+        self.persist(HEADER + slot * 8, 8);
+        self.fence();
+    }
+}
+
+/// A file on persistent memory holding the memory and what recovery
+/// needs, see `STMBuilder::pmem`.
+pub struct PmemStorage(Arc<Pmem>);
+
+// SAFETY: the bytes live as long as the mapping the storage keeps alive
+unsafe impl Storage for PmemStorage {
+    fn as_ptr(&self) -> *mut u8 {
+        self.0.data()
+    }
+
+    fn len(&self) -> usize {
+        self.0.size
+    }
+
+    fn sync(&self, _range: core::ops::Range<usize>) -> io::Result<()> {
+        self.0.map.flush()
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+impl PmemStorage {
+    /// Map the file at `path`, created with room for `size` bytes of
+    /// memory (a multiple of the stripe size) if it does not exist. An
+    /// existing file must have been created with the same `size`; call
+    /// `STM::recover_pmem` before using it.
+    pub fn open<P: AsRef<Path>>(path: P, size: usize) -> io::Result<PmemStorage> {
+        if !size.is_multiple_of(STRIPE_SIZE) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "size is not a multiple of the stripe size",
+            ));
+        }
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let len = total_len(size);
+        let fresh = file.metadata()?.len() == 0;
+        if fresh {
+            file.set_len(len as u64)?;
+        } else if file.metadata()?.len() != len as u64 {
+            return Err(invalid("pmem file of another size"));
+        }
+        // SAFETY: the file is only accessed through atomics while mapped
+        let mut map = unsafe { memmap2::MmapOptions::new().len(len).map_mut(&file)? };
+        let base = map.as_mut_ptr();
+        let pmem = Pmem {
+            map,
+            base,
+            size,
+            flush: Flush::detect(),
+            crash_after: None,
+            written: AtomicUsize::new(0),
+        };
+
+        let header = pmem.words(0, 3);
+        if fresh {
+            header[1].store(
+                LAYOUT_VERSION as u64 | (STRIPE_SIZE as u64) << 32,
+                Ordering::Relaxed,
+            );
+            header[2].store(size as u64, Ordering::Relaxed);
+            header[0].store(MAGIC, Ordering::Relaxed);
+            pmem.persist(0, HEADER);
+            pmem.fence();
+        } else if header[0].load(Ordering::Relaxed) != MAGIC
+            || header[1].load(Ordering::Relaxed)
+                != LAYOUT_VERSION as u64 | (STRIPE_SIZE as u64) << 32
+            || header[2].load(Ordering::Relaxed) != size as u64
+        {
+            return Err(invalid("not a tl2 pmem file of this layout"));
+        }
+        Ok(PmemStorage(Arc::new(pmem)))
+    }
+
+    /// Abort the process once `stripes` new stripes are written back,
+    /// in the middle of a commit that writes more, to test recovery.
+    #[doc(hidden)]
+    pub fn crash_after(mut self, stripes: usize) -> PmemStorage {
+        Arc::get_mut(&mut self.0)
+            .expect("storage in use")
+            .crash_after = Some(stripes);
+        self
+    }
+
+    pub(crate) fn pmem(&self) -> Arc<Pmem> {
+        self.0.clone()
+    }
+}
+
+impl STM {
+    /// Undo the commits a crash interrupted, see `STMBuilder::pmem`, and
+    /// move the clock past every version recorded. Returns the number of
+    /// stripes restored. Like `set_clock`, `&mut self` guarantees that no
+    /// transaction runs meanwhile.
+    ///
+    /// # Panics
+    ///
+    /// If the memory is not kept in a `PmemStorage`.
+    pub fn recover_pmem(&mut self) -> usize {
+        let pmem = self
+            .pmem()
+            .expect("not built with STMBuilder::pmem")
+            .clone();
+        let torn: Vec<u64> = pmem
+            .slots()
+            .iter()
+            .map(|s| s.load(Ordering::Relaxed))
+            .filter(|v| *v != 0)
+            .collect();
+
+        let mut restored = 0;
+        let mut max = 0;
+        for addr in (0..pmem.size).step_by(STRIPE_SIZE) {
+            let ver = pmem.undo_ver(addr).load(Ordering::Relaxed);
+            max = max.max(ver);
+            if !torn.contains(&ver) {
+                continue;
+            }
+            let data = pmem.bytes(data_offset(pmem.size) + addr, STRIPE_SIZE);
+            for (dst, src) in data.iter().zip(pmem.undo_bytes(addr)) {
+                dst.store(src.load(Ordering::Relaxed), Ordering::Relaxed);
+            }
+            pmem.persist(data_offset(pmem.size) + addr, STRIPE_SIZE);
+            restored += 1;
+        }
+        pmem.fence();
+        for (i, slot) in pmem.slots().iter().enumerate() {
+            slot.store(0, Ordering::Relaxed);
+            pmem.persist(HEADER + i * 8, 8);
+        }
+        pmem.fence();
+
+        let max = max.max(torn.iter().copied().max().unwrap_or(0));
+        if max > self.current_version() {
+            self.set_clock(max);
+        }
+        restored
+    }
+}
@@ -0,0 +1,136 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::conflict::Conflict;
+use crate::tl2::{Phase, TxError};
+
+/// How an attempt of a profiled transaction ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttemptOutcome {
+    Committed,
+    Restarted(Conflict),
+    Failed(TxError),
+}
+
+/// Timings of one attempt, see `TxProfile`. Phases an attempt did not
+/// reach are zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AttemptProfile {
+    /// Running the body.
+    pub execute: Duration,
+    /// Locking the write-set.
+    pub lock: Duration,
+    /// Validating the read-set.
+    pub validate: Duration,
+    /// Appending to the journal and waiting for it to sync.
+    pub journal: Duration,
+    /// Copying the write-set out and unlocking.
+    pub publish: Duration,
+    /// From the start of the attempt to its end, reporting included.
+    pub total: Duration,
+    pub read_set: usize,
+    pub write_set: usize,
+    pub outcome: AttemptOutcome,
+}
+
+/// Where one transaction spent its time, attempt by attempt, see
+/// `STM::profile_write_transaction`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxProfile {
+    pub attempts: Vec<AttemptProfile>,
+    /// From the start of the first attempt to the end of the last,
+    /// backoff between attempts included.
+    pub total: Duration,
+}
+
+impl fmt::Display for TxProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let n = self.attempts.len();
+        let plural = if n == 1 { "" } else { "s" };
+        write!(f, "{} attempt{} in {:?}", n, plural, self.total)?;
+        for (i, a) in self.attempts.iter().enumerate() {
+            writeln!(f)?;
+            write!(f, "  #{}: ", i + 1)?;
+            match a.outcome {
+                AttemptOutcome::Committed => write!(f, "committed")?,
+                AttemptOutcome::Restarted(c) => match c.addr {
+                    Some(addr) => write!(f, "restarted ({:?} at {})", c.cause, addr)?,
+                    None => write!(f, "restarted ({:?})", c.cause)?,
+                },
+                AttemptOutcome::Failed(e) => write!(f, "failed ({})", e)?,
+            }
+            write!(
+                f,
+                " in {:?}: execute {:?}, lock {:?}, validate {:?}, journal {:?}, \
+                 publish {:?}; read-set {}, write-set {}",
+                a.total,
+                a.execute,
+                a.lock,
+                a.validate,
+                a.journal,
+                a.publish,
+                a.read_set,
+                a.write_set
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// Collects a `TxProfile` as the attempts run.
+pub(crate) struct Profiler {
+    start: Instant,
+    began: Instant,
+    last: Instant,
+    phases: [Duration; 5],
+    attempts: Vec<AttemptProfile>,
+}
+
+impl Profiler {
+    pub(crate) fn new() -> Profiler {
+        let now = Instant::now();
+        Profiler {
+            start: now,
+            began: now,
+            last: now,
+            phases: [Duration::ZERO; 5],
+            attempts: Vec::new(),
+        }
+    }
+
+    pub(crate) fn begin(&mut self) {
+        let now = Instant::now();
+        self.began = now;
+        self.last = now;
+        self.phases = [Duration::ZERO; 5];
+    }
+
+    // Charge the time since the previous mark to `phase`.
+    pub(crate) fn mark(&mut self, phase: Phase) {
+        let now = Instant::now();
+        self.phases[phase as usize] += now.duration_since(self.last);
+        self.last = now;
+    }
+
+    pub(crate) fn end(&mut self, sets: (usize, usize), outcome: AttemptOutcome) {
+        let p = self.phases;
+        self.attempts.push(AttemptProfile {
+            execute: p[Phase::Execute as usize],
+            lock: p[Phase::Lock as usize],
+            validate: p[Phase::Validate as usize],
+            journal: p[Phase::Journal as usize],
+            publish: p[Phase::Publish as usize],
+            total: self.began.elapsed(),
+            read_set: sets.0,
+            write_set: sets.1,
+            outcome,
+        });
+    }
+
+    pub(crate) fn finish(self) -> TxProfile {
+        TxProfile {
+            attempts: self.attempts,
+            total: self.start.elapsed(),
+        }
+    }
+}
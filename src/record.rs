@@ -0,0 +1,95 @@
+use alloc::vec;
+
+use crate::tl2::{ReadTrans, Trans, WriteTrans, STRIPE_SIZE};
+use crate::value::BigValue;
+
+/// Define a struct and implement `BigValue` for it, a field per stripe in
+/// declaration order, so that `load_record` and `store_record` move it
+/// whole. Every field must be a `StripeValue`. Since a record is read and
+/// written inside one transaction, it never tears.
+///
+/// ```
+/// use tl2::{STMResult, STM};
+///
+/// tl2::trans_record! {
+///     #[derive(Debug, PartialEq)]
+///     pub struct Account {
+///         pub id: u64,
+///         pub balance: i64,
+///         pub frozen: bool,
+///     }
+/// }
+///
+/// let stm = STM::new();
+/// let acct = Account { id: 7, balance: -3, frozen: true };
+/// stm.write_transaction(|tr| {
+///     tr.store_record(64, &acct);
+///     STMResult::Ok(())
+/// });
+/// let back = stm.read_transaction(|tr| STMResult::Ok(tr.load_record::<Account>(64).unwrap()));
+/// assert_eq!(back, Some(acct));
+/// ```
+#[macro_export]
+macro_rules! trans_record {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($(#[$fmeta:meta])* $fvis:vis $field:ident: $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $($(#[$fmeta])* $fvis $field: $ty),*
+        }
+
+        impl $crate::BigValue for $name {
+            const STRIPES: usize = <[&str]>::len(&[$(stringify!($field)),*]);
+
+            fn to_stripes(&self, out: &mut [[u8; 8]]) {
+                let mut out = out.iter_mut();
+                $(*out.next().unwrap() = $crate::StripeValue::to_stripe(&self.$field);)*
+            }
+
+            fn from_stripes(stripes: &[[u8; 8]]) -> Self {
+                let mut stripes = stripes.iter();
+                $name {
+                    $($field: <$ty as $crate::StripeValue>::from_stripe(
+                        *stripes.next().unwrap(),
+                    ),)*
+                }
+            }
+        }
+    };
+}
+
+pub(crate) fn load<T: Trans, R: BigValue>(tr: &mut T, base: usize) -> Option<R> {
+    let mut block = vec![[0; STRIPE_SIZE]; R::STRIPES];
+    for (i, stripe) in block.iter_mut().enumerate() {
+        *stripe = tr.load(base + i * STRIPE_SIZE)?;
+    }
+    Some(R::from_stripes(&block))
+}
+
+impl<'a> WriteTrans<'a> {
+    /// Load the record at the stripe-aligned address `base`. `None` as for
+    /// `load`.
+    pub fn load_record<R: BigValue>(&mut self, base: usize) -> Option<R> {
+        load(self, base)
+    }
+
+    /// Store `r` at the stripe-aligned address `base`.
+    pub fn store_record<R: BigValue>(&mut self, base: usize, r: &R) {
+        let mut block = vec![[0; STRIPE_SIZE]; R::STRIPES];
+        r.to_stripes(&mut block);
+        for (i, stripe) in block.into_iter().enumerate() {
+            self.store(base + i * STRIPE_SIZE, stripe);
+        }
+    }
+}
+
+impl<'a> ReadTrans<'a> {
+    /// Load the record at the stripe-aligned address `base`.
+    pub fn load_record<R: BigValue>(&mut self, base: usize) -> Option<R> {
+        load(self, base)
+    }
+}
@@ -0,0 +1,128 @@
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+use crate::mode::ModeHistory;
+use crate::stats::ThreadStats;
+
+// State a thread keeps per STM. Only the owning thread updates it, so
+// nothing here is contended; readers on other threads aggregate over all
+// cells.
+#[derive(Default)]
+pub(crate) struct ThreadCell {
+    pub(crate) stats: ThreadStats,
+    pub(crate) mode: ModeHistory,
+}
+
+impl ThreadCell {
+    // Fold the counts of an exiting thread into the retired cell. Its
+    // `mode` history only ever served that thread.
+    fn absorb(&self, other: &ThreadCell) {
+        self.stats.absorb(&other.stats);
+    }
+}
+
+struct Inner {
+    id: u64,
+    live: Mutex<Vec<Arc<ThreadCell>>>,
+    retired: ThreadCell,
+}
+
+impl Inner {
+    fn retire(&self, cell: &Arc<ThreadCell>) {
+        let mut live = self.live.lock().unwrap();
+        live.retain(|c| !Arc::ptr_eq(c, cell));
+        self.retired.absorb(cell);
+    }
+}
+
+// A thread's cell in one registry, keyed by the registry's id.
+struct Entry {
+    id: u64,
+    registry: Weak<Inner>,
+    cell: Arc<ThreadCell>,
+}
+
+// The cells of one thread, retired when the thread exits.
+struct Local(RefCell<Vec<Entry>>);
+
+impl Drop for Local {
+    fn drop(&mut self) {
+        for e in self.0.borrow().iter() {
+            if let Some(r) = e.registry.upgrade() {
+                r.retire(&e.cell);
+            }
+        }
+    }
+}
+
+thread_local! {
+    static LOCAL: Local = const { Local(RefCell::new(Vec::new())) };
+}
+
+// Per-thread cells of one STM. A thread gets its cell on first use and the
+// cell is folded into `retired` by a TLS destructor when the thread exits,
+// so `visit` sees every thread that ever ran, live or not, exactly once.
+pub(crate) struct Registry {
+    inner: Arc<Inner>,
+}
+
+impl Registry {
+    pub(crate) fn new() -> Registry {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        Registry {
+            inner: Arc::new(Inner {
+                id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+                live: Mutex::new(Vec::new()),
+                retired: ThreadCell::default(),
+            }),
+        }
+    }
+
+    // Run `f` on the calling thread's cell, registering it on first use.
+    // During thread teardown, once TLS is gone, `f` runs on the retired
+    // cell instead.
+    pub(crate) fn with_local<R>(&self, f: impl FnOnce(&ThreadCell) -> R) -> R {
+        let id = self.inner.id;
+        let mut f = Some(f);
+        let r = LOCAL.try_with(|local| {
+            let mut cells = local.0.borrow_mut();
+            let idx = match cells.iter().position(|e| e.id == id) {
+                Some(idx) => idx,
+                None => {
+                    // drop the cells of registries that are gone
+                    cells.retain(|e| e.registry.strong_count() > 0);
+
+                    let cell = Arc::new(ThreadCell::default());
+                    self.inner.live.lock().unwrap().push(cell.clone());
+                    cells.push(Entry {
+                        id,
+                        registry: Arc::downgrade(&self.inner),
+                        cell,
+                    });
+                    cells.len() - 1
+                }
+            };
+            (f.take().unwrap())(&cells[idx].cell)
+        });
+        match r {
+            Ok(r) => r,
+            Err(_) => (f.take().unwrap())(&self.inner.retired),
+        }
+    }
+
+    // Run `f` on every live cell and then on the retired one. Threads
+    // cannot register or retire meanwhile.
+    pub(crate) fn visit(&self, mut f: impl FnMut(&ThreadCell)) {
+        let live = self.inner.live.lock().unwrap();
+        for c in live.iter() {
+            f(c);
+        }
+        f(&self.inner.retired);
+    }
+
+    // Threads holding a cell that have not exited yet.
+    pub(crate) fn live(&self) -> usize {
+        self.inner.live.lock().unwrap().len()
+    }
+}
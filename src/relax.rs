@@ -0,0 +1,83 @@
+use alloc::sync::Arc;
+#[cfg(all(target_family = "wasm", target_feature = "atomics"))]
+use core::sync::atomic::AtomicI32;
+
+/// How a thread waits for another inside the STM: a committer waiting for
+/// a pessimistic reader to finish, a pessimistic reader waiting for
+/// running commits to drain, and a writer waiting for a slot (see
+/// `STMBuilder::max_writers`). All spin on an atomic and call `relax` once
+/// per turn.
+///
+/// On a target without `std`, implement it with the scheduler's yield or
+/// a short wait so that the thread being waited for gets to run.
+pub trait Relax: Send + Sync {
+    /// One turn of a wait loop.
+    fn relax(&self);
+}
+
+/// Spins with `core::hint::spin_loop`, the default without the `std`
+/// feature.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Spin;
+
+impl Relax for Spin {
+    fn relax(&self) {
+        core::hint::spin_loop();
+    }
+}
+
+/// Yields the thread with `std::thread::yield_now`, the default with the
+/// `std` feature.
+#[cfg(feature = "std")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Yield;
+
+#[cfg(feature = "std")]
+impl Relax for Yield {
+    fn relax(&self) {
+        crate::sync::yield_now();
+    }
+}
+
+/// Blocks the thread for a moment with `memory.atomic.wait32`, the default
+/// on wasm with the `atomics` target feature, where yielding does nothing.
+/// Browsers forbid blocking on the main thread, so only run contending
+/// transactions there with `Spin`.
+#[cfg(all(target_family = "wasm", target_feature = "atomics"))]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Wait;
+
+#[cfg(all(target_family = "wasm", target_feature = "atomics"))]
+impl Relax for Wait {
+    fn relax(&self) {
+        // nothing notifies this word, so every wait runs into the timeout
+        static PARK: AtomicI32 = AtomicI32::new(0);
+        unsafe {
+            core::arch::wasm32::memory_atomic_wait32(PARK.as_ptr(), 0, WAIT_NANOS);
+        }
+    }
+}
+
+#[cfg(all(target_family = "wasm", target_feature = "atomics"))]
+const WAIT_NANOS: i64 = 1000;
+
+#[cfg(all(target_family = "wasm", target_feature = "atomics"))]
+pub(crate) fn default() -> Arc<dyn Relax> {
+    Arc::new(Wait)
+}
+
+#[cfg(all(
+    feature = "std",
+    not(all(target_family = "wasm", target_feature = "atomics"))
+))]
+pub(crate) fn default() -> Arc<dyn Relax> {
+    Arc::new(Yield)
+}
+
+#[cfg(not(any(
+    feature = "std",
+    all(target_family = "wasm", target_feature = "atomics")
+)))]
+pub(crate) fn default() -> Arc<dyn Relax> {
+    Arc::new(Spin)
+}
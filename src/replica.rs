@@ -0,0 +1,216 @@
+// Warm standbys: a follower STM replays the change feed of a primary in
+// version order, see `STM::apply_replica`.
+
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::fmt;
+use std::sync::mpsc::Receiver;
+use std::sync::Mutex;
+
+use crate::feed::CommitRecord;
+use crate::tl2::{STMResult, STM, STRIPE_SIZE};
+
+// Records a follower holds back waiting for an earlier one before it
+// declares the earlier one lost. Records carry `gap` when the feed drops
+// one, so this only bounds the memory held for a record lost otherwise.
+const REORDER_WINDOW: usize = 1 << 16;
+
+/// Why `STM::apply_replica` refused a record. `applied` is the primary
+/// version the follower has applied up to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplicaError {
+    /// The record is not newer than what was applied. Expected for the
+    /// records a resync snapshot already holds; skip them.
+    Stale { applied: u64, version: u64 },
+    /// A record after `applied` was lost. The follower refuses every
+    /// record from then on until `STM::resync_replica`.
+    Gap { applied: u64, version: u64 },
+    /// The record writes a stripe the follower does not have.
+    OutOfRange { version: u64, addr: usize },
+}
+
+impl fmt::Display for ReplicaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplicaError::Stale { applied, version } => {
+                write!(f, "record {} is stale, applied up to {}", version, applied)
+            }
+            ReplicaError::Gap { applied, version } => write!(
+                f,
+                "records after {} are missing (got up to {})",
+                applied, version
+            ),
+            ReplicaError::OutOfRange { version, addr } => {
+                write!(f, "record {} writes {:#x} out of range", version, addr)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplicaError {}
+
+/// The whole memory of a primary as of `version`, see `STM::replicate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplicaSnapshot {
+    pub version: u64,
+    pub data: Vec<u8>,
+}
+
+impl ReplicaSnapshot {
+    /// The stripes whose bytes differ from `other`, as (address, bytes
+    /// here, bytes in `other`) in address order.
+    ///
+    /// # Panics
+    ///
+    /// If the snapshots are of memories of different sizes.
+    pub fn diff(
+        &self,
+        other: &ReplicaSnapshot,
+    ) -> Vec<(usize, [u8; STRIPE_SIZE], [u8; STRIPE_SIZE])> {
+        assert_eq!(self.data.len(), other.data.len(), "snapshot size");
+        self.data
+            .chunks_exact(STRIPE_SIZE)
+            .zip(other.data.chunks_exact(STRIPE_SIZE))
+            .enumerate()
+            .filter(|(_, (a, b))| a != b)
+            .map(|(i, (a, b))| {
+                (
+                    i * STRIPE_SIZE,
+                    a.try_into().unwrap(),
+                    b.try_into().unwrap(),
+                )
+            })
+            .collect()
+    }
+}
+
+// Follower state: the primary version applied up to and the records that
+// arrived ahead of it.
+pub(crate) struct Replica {
+    applied: u64,
+    pending: BTreeMap<u64, Vec<(usize, [u8; STRIPE_SIZE])>>,
+    lost: bool, // a record went missing, wait for a resync
+}
+
+impl Replica {
+    pub(crate) fn new() -> Mutex<Replica> {
+        Mutex::new(Replica {
+            applied: 0,
+            pending: BTreeMap::new(),
+            lost: false,
+        })
+    }
+}
+
+impl STM {
+    /// Start replicating to a follower: a snapshot of the whole memory and
+    /// a receiver of every record after it. Load the snapshot with
+    /// `STM::resync_replica`, then pass the records to
+    /// `STM::apply_replica`, skipping `ReplicaError::Stale`.
+    ///
+    /// Replication relies on the clock handing out consecutive versions,
+    /// as the default `AtomicClock` does, and on every version reaching
+    /// the feed. Commits, `zero_region` and the release of a dead
+    /// process's locks do; after `set_clock` the follower waits for
+    /// versions that never come until it has 65536 records queued and
+    /// reports `ReplicaError::Gap`, so resync it right away. A memory
+    /// shared between processes cannot be a primary: its feed misses the
+    /// commits of the other processes.
+    pub fn replicate(&self) -> (ReplicaSnapshot, Receiver<CommitRecord>) {
+        let rx = self.subscribe();
+        let (version, data) = self
+            .read_transaction(|tr| {
+                let mut data = Vec::with_capacity(self.capacity());
+                for addr in (0..self.capacity()).step_by(STRIPE_SIZE) {
+                    data.extend_from_slice(&crate::load!(tr, addr));
+                }
+                STMResult::Ok((tr.read_version(), data))
+            })
+            .unwrap();
+        (ReplicaSnapshot { version, data }, rx)
+    }
+
+    /// Apply a record of a primary's change feed to this follower and
+    /// return the primary version applied up to.
+    ///
+    /// Records are applied in version order, each in one write transaction,
+    /// so local read transactions see the primary's state as of some
+    /// version. One that arrives ahead of an earlier one waits for it. A
+    /// record with `gap` set tells that the feed lost some, and so does a
+    /// backlog of 65536 records waiting for an earlier one.
+    /// A fresh follower starts at version 0, matching a fresh primary.
+    /// Writing to a follower other than through these methods breaks it.
+    pub fn apply_replica(&self, record: CommitRecord) -> Result<u64, ReplicaError> {
+        let mut r = self.replica().lock().unwrap();
+        r.lost |= record.gap;
+        if record.version <= r.applied {
+            return Err(ReplicaError::Stale {
+                applied: r.applied,
+                version: record.version,
+            });
+        }
+        for (addr, _) in record.entries.iter() {
+            if !addr.is_multiple_of(STRIPE_SIZE) || *addr >= self.capacity() {
+                return Err(ReplicaError::OutOfRange {
+                    version: record.version,
+                    addr: *addr,
+                });
+            }
+        }
+
+        let r = &mut *r;
+        if r.lost {
+            return Err(ReplicaError::Gap {
+                applied: r.applied,
+                version: record.version,
+            });
+        }
+        r.pending.insert(record.version, record.entries);
+        while let Some(entries) = r.pending.remove(&(r.applied + 1)) {
+            if !entries.is_empty() {
+                self.write_transaction(|tr| {
+                    for (addr, val) in entries.iter() {
+                        tr.store(*addr, *val);
+                    }
+                    STMResult::Ok(())
+                });
+            }
+            r.applied += 1;
+        }
+        if r.pending.len() > REORDER_WINDOW {
+            r.lost = true;
+            r.pending.clear();
+            return Err(ReplicaError::Gap {
+                applied: r.applied,
+                version: record.version,
+            });
+        }
+        Ok(r.applied)
+    }
+
+    /// Replace the whole memory with `snapshot` in one write transaction
+    /// and continue applying records after its version, dropping any
+    /// that wait.
+    ///
+    /// # Panics
+    ///
+    /// If the snapshot is not the size of this follower's memory.
+    pub fn resync_replica(&self, snapshot: &ReplicaSnapshot) {
+        assert_eq!(snapshot.data.len(), self.capacity(), "snapshot size");
+        let mut r = self.replica().lock().unwrap();
+        self.write_transaction(|tr| {
+            for (i, chunk) in snapshot.data.chunks_exact(STRIPE_SIZE).enumerate() {
+                tr.store(i * STRIPE_SIZE, chunk.try_into().unwrap());
+            }
+            STMResult::Ok(())
+        });
+        r.applied = snapshot.version;
+        r.pending.clear();
+        r.lost = false;
+    }
+
+    /// The primary version this follower has applied up to.
+    pub fn replica_version(&self) -> u64 {
+        self.replica().lock().unwrap().applied
+    }
+}
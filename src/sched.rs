@@ -0,0 +1,105 @@
+// Yield points inside write transactions, for tests that need one exact
+// interleaving (the `testing` feature).
+
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// How long `Script` waits for its turn before declaring the script stuck.
+const STEP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where a write transaction reports to the `Scheduler`, in the order an
+/// attempt passes them. A restart passes them again from `Sampled`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum YieldPoint {
+    /// The clock has been sampled; the body has not run.
+    Sampled,
+    /// The body has run, whether or not it hit a conflict.
+    Executed,
+    /// Locking the write-set was tried; a failure restarts right after.
+    Locked,
+    /// The read-set is valid and the new bytes are about to be written.
+    Publishing,
+    /// Half of the new bytes are written, the rest and the new versions
+    /// are not.
+    Applying,
+    /// The commit is visible and the locks are released.
+    Committed,
+}
+
+/// Called by every write transaction at each `YieldPoint`, on the thread
+/// running it, see `STMBuilder::scheduler`. Blocking in `reached` holds
+/// the transaction there, locks included.
+pub trait Scheduler: Send + Sync {
+    fn reached(&self, point: YieldPoint);
+}
+
+/// A `Scheduler` replaying a script of (thread name, point) steps: a
+/// thread reaching a point that is still ahead in the script for it waits
+/// until every step before that one has happened. Points the rest of the
+/// script does not mention for the thread, and threads without a name,
+/// pass freely. Name threads with `std::thread::Builder::name`.
+///
+/// Panics if a thread waits for its turn longer than 5 seconds, which
+/// means the script cannot happen.
+pub struct Script {
+    steps: Vec<(String, YieldPoint)>,
+    next: Mutex<usize>,
+    moved: Condvar,
+}
+
+impl Script {
+    pub fn new<I, S>(steps: I) -> Script
+    where
+        I: IntoIterator<Item = (S, YieldPoint)>,
+        S: Into<String>,
+    {
+        Script {
+            steps: steps.into_iter().map(|(n, p)| (n.into(), p)).collect(),
+            next: Mutex::new(0),
+            moved: Condvar::new(),
+        }
+    }
+
+    /// Whether every step has happened.
+    pub fn is_done(&self) -> bool {
+        *self.next.lock().unwrap() == self.steps.len()
+    }
+}
+
+impl Scheduler for Script {
+    fn reached(&self, point: YieldPoint) {
+        let me = thread::current();
+        let name = match me.name() {
+            Some(name) => name,
+            None => return,
+        };
+
+        let mut next = self.next.lock().unwrap();
+        loop {
+            let ahead = self.steps[*next..]
+                .iter()
+                .position(|(n, p)| n == name && *p == point);
+            match ahead {
+                None => return,
+                Some(0) => {
+                    *next += 1;
+                    self.moved.notify_all();
+                    return;
+                }
+                Some(_) => {
+                    let at = *next;
+                    let (guard, wait) = self.moved.wait_timeout(next, STEP_TIMEOUT).unwrap();
+                    next = guard;
+                    if wait.timed_out() && *next == at {
+                        drop(next); // leave the lock unpoisoned
+                        panic!(
+                            "script stuck at step {} {:?} with {} at {:?}",
+                            at, self.steps[at], name, point
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use alloc::sync::Arc;
+use core::marker::PhantomData;
+
+use crate::tl2::{check_aligned, Trans, WriteTrans, STM};
+use crate::value::StripeValue;
+
+/// A single hot value read without a transaction.
+///
+/// The value takes one stripe, and `get` reads it the way a transaction
+/// reads a stripe, between two loads of the stripe's version word, but
+/// with no transaction to set up and no read version to check: it returns
+/// the latest commit, spinning while one is being written. Writers go
+/// through transactions, `set` or `store`, whose commit moves the version
+/// word, so a read never sees half a write. Several cells are not read
+/// consistently with each other; use a read transaction for that.
+pub struct SeqlockCell<T> {
+    stm: Arc<STM>,
+    addr: usize,
+    _val: PhantomData<T>,
+}
+
+impl<T: StripeValue> SeqlockCell<T> {
+    /// A cell at the stripe-aligned address `addr`.
+    pub fn new(stm: Arc<STM>, addr: usize) -> SeqlockCell<T> {
+        check_aligned(addr);
+        SeqlockCell {
+            stm,
+            addr,
+            _val: PhantomData,
+        }
+    }
+
+    pub fn addr(&self) -> usize {
+        self.addr
+    }
+
+    /// The value of the latest commit.
+    pub fn get(&self) -> T {
+        T::from_stripe(self.stm.load_latest(self.addr))
+    }
+
+    /// Replace the value in a transaction of its own and return the
+    /// version it committed at; `None` if the commit failed, e.g. with a
+    /// journal error.
+    pub fn set(&self, val: T) -> Option<u64> {
+        self.stm.apply_batch(&[(self.addr, val.to_stripe())])
+    }
+
+    /// Read the value as part of the transaction `tr`.
+    pub fn load<R: Trans>(&self, tr: &mut R) -> Option<T> {
+        tr.load(self.addr).map(T::from_stripe)
+    }
+
+    /// Write the value as part of the transaction `tr`.
+    pub fn store(&self, tr: &mut WriteTrans, val: T) {
+        tr.store(self.addr, val.to_stripe());
+    }
+}
@@ -0,0 +1,215 @@
+//! Memory shared between processes through POSIX shared memory.
+//!
+//! A region holds a superblock, the lock/version words and the bytes, so
+//! every process attached to it runs transactions against the same memory
+//! and clock. The atomics work across processes as they do across threads.
+//! What stays per process is everything around the protocol: stats, the
+//! change feed (it sees only this process's commits), `pause_commits`
+//! and transaction priorities.
+
+use alloc::sync::Arc;
+use core::ptr;
+use std::ffi::CString;
+use std::fs::File;
+use std::io;
+use std::os::unix::io::FromRawFd;
+use std::process;
+
+use memmap2::MmapMut;
+
+use crate::clock::Clock;
+use crate::storage::Storage;
+use crate::sync::{AtomicU64, Ordering};
+use crate::tl2::{Locks, STM, STRIPE_SIZE};
+
+const MAGIC: u64 = u64::from_le_bytes(*b"tl2-shm\0");
+// Bump when the layout below changes.
+const LAYOUT_VERSION: u32 = 1;
+// Bytes before the lock words.
+const HEADER: usize = 64;
+
+// The start of a region, followed by a lock word per stripe and the bytes.
+#[repr(C)]
+struct Superblock {
+    magic: AtomicU64, // stored last by the creator
+    layout: u32,
+    stripe_size: u32,
+    size: u64, // bytes of memory
+    clock: AtomicU64,
+}
+
+struct Region {
+    _map: MmapMut,
+    base: *mut u8,
+    size: usize,
+}
+
+// SAFETY: `base` points into `map`, which the region owns, and everything
+// behind it is accessed through atomics
+unsafe impl Send for Region {}
+unsafe impl Sync for Region {}
+
+impl Region {
+    fn superblock(&self) -> &Superblock {
+        // SAFETY: the mapping is page aligned and at least HEADER bytes
+        unsafe { &*(self.base as *const Superblock) }
+    }
+
+    fn locks(&self) -> *const [AtomicU64] {
+        // SAFETY: the lock words follow the header, see `total_len`
+        let words = unsafe { self.base.add(HEADER) } as *const AtomicU64;
+        ptr::slice_from_raw_parts(words, self.size / STRIPE_SIZE)
+    }
+
+    fn bytes(&self) -> *mut u8 {
+        // SAFETY: the bytes follow the lock words, see `total_len`
+        unsafe { self.base.add(HEADER + self.size / STRIPE_SIZE * 8) }
+    }
+}
+
+fn total_len(size: usize) -> usize {
+    HEADER + size / STRIPE_SIZE * 8 + size
+}
+
+fn open(name: &str, flags: libc::c_int) -> io::Result<File> {
+    let name = CString::new(name).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    // SAFETY: `name` is a valid C string
+    let fd = unsafe { libc::shm_open(name.as_ptr(), flags, 0o600) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: `fd` was just opened and is owned by nobody else
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+fn map(file: &File, len: usize, size: usize) -> io::Result<Region> {
+    // SAFETY: the region is only accessed through atomics, by every
+    // process that maps it
+    let mut map = unsafe { memmap2::MmapOptions::new().len(len).map_mut(file)? };
+    let base = map.as_mut_ptr();
+    Ok(Region {
+        _map: map,
+        base,
+        size,
+    })
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+// The bytes of a region.
+struct ShmStorage(Arc<Region>);
+
+// SAFETY: the bytes live as long as the mapping the storage keeps alive
+unsafe impl Storage for ShmStorage {
+    fn as_ptr(&self) -> *mut u8 {
+        self.0.bytes()
+    }
+
+    fn len(&self) -> usize {
+        self.0.size
+    }
+}
+
+// The clock in the superblock of a region.
+struct ShmClock(Arc<Region>);
+
+impl Clock for ShmClock {
+    fn sample(&self) -> u64 {
+        self.0.superblock().clock.load(Ordering::Acquire)
+    }
+
+    fn increment(&self) -> u64 {
+        self.0.superblock().clock.fetch_add(1, Ordering::AcqRel) + 1
+    }
+
+    fn set(&self, v: u64) {
+        self.0.superblock().clock.store(v, Ordering::Release);
+    }
+}
+
+impl STM {
+    /// Create the shared memory object `name` (e.g. `"/bank"`) holding
+    /// `size` zeroed bytes, a multiple of the stripe size, and attach to
+    /// it. Fails if it exists. Other processes attach with
+    /// `attach_shared`; it lives until `unlink_shared`.
+    pub fn create_shared(name: &str, size: usize) -> io::Result<STM> {
+        if !size.is_multiple_of(STRIPE_SIZE) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "size is not a multiple of the stripe size",
+            ));
+        }
+        let file = open(name, libc::O_CREAT | libc::O_EXCL | libc::O_RDWR)?;
+        let len = total_len(size);
+        file.set_len(len as u64)?;
+        let region = map(&file, len, size)?;
+
+        // SAFETY: nobody else uses the region before `magic` is stored;
+        // the fields written here are not atomics
+        unsafe {
+            let sb = region.base as *mut Superblock;
+            (*sb).layout = LAYOUT_VERSION;
+            (*sb).stripe_size = STRIPE_SIZE as u32;
+            (*sb).size = size as u64;
+        }
+        region.superblock().magic.store(MAGIC, Ordering::Release);
+        Ok(STM::over(Arc::new(region)))
+    }
+
+    /// Attach to the shared memory object `name` made by `create_shared`.
+    pub fn attach_shared(name: &str) -> io::Result<STM> {
+        let file = open(name, libc::O_RDWR)?;
+        let len = file.metadata()?.len() as usize;
+        if len < HEADER {
+            return Err(invalid("not a tl2 region"));
+        }
+        let mut region = map(&file, len, 0)?;
+
+        let sb = region.superblock();
+        if sb.magic.load(Ordering::Acquire) != MAGIC {
+            return Err(invalid("not a tl2 region, or not initialized yet"));
+        }
+        if sb.layout != LAYOUT_VERSION || sb.stripe_size as usize != STRIPE_SIZE {
+            return Err(invalid("tl2 region of another layout"));
+        }
+        let size = sb.size as usize;
+        if total_len(size) != len {
+            return Err(invalid("tl2 region of the wrong length"));
+        }
+
+        region.size = size;
+        Ok(STM::over(Arc::new(region)))
+    }
+
+    /// Remove the shared memory object `name`. Attached processes keep
+    /// using it until they drop their STM.
+    pub fn unlink_shared(name: &str) -> io::Result<()> {
+        let name =
+            CString::new(name).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        // SAFETY: `name` is a valid C string
+        if unsafe { libc::shm_unlink(name.as_ptr()) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    fn over(region: Arc<Region>) -> STM {
+        STM::builder()
+            .storage(ShmStorage(region.clone()))
+            .clock(Arc::new(ShmClock(region.clone())))
+            .shared(Locks::Shared(region.locks()), process::id() as u64)
+            .build()
+    }
+
+    /// Release the stripe locks held by the process `pid`, which must have
+    /// died, so the others stop waiting for them; returns how many there
+    /// were. The locks carry the pid of their holder. A process that died
+    /// while writing its commit back leaves the stripes it already wrote,
+    /// so the commit may be partly visible.
+    pub fn recover_dead_owner(&self, pid: u32) -> usize {
+        assert_ne!(pid, process::id(), "recovering the locks of this process");
+        self.release_owner(pid as u64)
+    }
+}
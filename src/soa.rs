@@ -0,0 +1,84 @@
+use alloc::vec;
+use core::marker::PhantomData;
+
+use crate::tl2::{check_aligned, Trans, WriteTrans, STRIPE_SIZE};
+use crate::value::{BigValue, StripeValue};
+
+/// An array of records laid out struct-of-arrays: field `f` of every
+/// element sits in its own run of `len` stripes, so the stripes of element
+/// `i` are `len` apart rather than adjacent.
+///
+/// Fields are numbered in declaration order, as `trans_record!` lays them
+/// out. A transaction touching one field of an element only loads or
+/// stores that field's stripe, so updates of different fields of the same
+/// element do not conflict, and a scan of one field over many elements
+/// reads consecutive stripes.
+pub struct SoaStm<R> {
+    base: usize,
+    len: usize,
+    _elem: PhantomData<R>,
+}
+
+impl<R: BigValue> SoaStm<R> {
+    /// Lay out `len` elements starting at the stripe-aligned address
+    /// `base`, taking `len * R::STRIPES` stripes.
+    pub fn new(base: usize, len: usize) -> SoaStm<R> {
+        check_aligned(base);
+        len.checked_mul(R::STRIPES * STRIPE_SIZE)
+            .and_then(|size| base.checked_add(size))
+            .expect("array end overflows usize");
+
+        SoaStm {
+            base,
+            len,
+            _elem: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Address of the stripe holding field `field` of element `idx`.
+    pub fn addr(&self, idx: usize, field: usize) -> usize {
+        assert!(idx < self.len);
+        assert!(field < R::STRIPES, "no field {}", field);
+        self.base + (field * self.len + idx) * STRIPE_SIZE
+    }
+
+    /// Field `field` of element `idx`, decoded as a `T`.
+    pub fn get_field<T: StripeValue, X: Trans>(
+        &self,
+        tr: &mut X,
+        idx: usize,
+        field: usize,
+    ) -> Option<T> {
+        tr.load(self.addr(idx, field)).map(T::from_stripe)
+    }
+
+    pub fn set_field<T: StripeValue>(&self, tr: &mut WriteTrans, idx: usize, field: usize, val: T) {
+        tr.store(self.addr(idx, field), val.to_stripe());
+    }
+
+    /// Element `idx`, loading every field.
+    pub fn get<X: Trans>(&self, tr: &mut X, idx: usize) -> Option<R> {
+        let mut block = vec![[0; STRIPE_SIZE]; R::STRIPES];
+        for (f, stripe) in block.iter_mut().enumerate() {
+            *stripe = tr.load(self.addr(idx, f))?;
+        }
+        Some(R::from_stripes(&block))
+    }
+
+    /// Store every field of element `idx`.
+    pub fn set(&self, tr: &mut WriteTrans, idx: usize, r: &R) {
+        let mut block = vec![[0; STRIPE_SIZE]; R::STRIPES];
+        r.to_stripes(&mut block);
+        for (f, stripe) in block.into_iter().enumerate() {
+            tr.store(self.addr(idx, f), stripe);
+        }
+    }
+}
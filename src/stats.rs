@@ -0,0 +1,272 @@
+use std::fmt;
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::conflict::ConflictCause;
+use crate::contention::TxPriority;
+use crate::label::MAX_LABELS;
+use crate::registry::Registry;
+use crate::window::{ThroughputWindow, Window};
+
+// Counters of one thread, see `Registry`. Aligned to a cache line so two
+// threads never share one while counting.
+#[repr(align(64))]
+#[derive(Default)]
+pub(crate) struct ThreadStats {
+    commits: AtomicU64,
+    reads: AtomicU64,
+    aborts: AtomicU64,
+    retries: AtomicU64,
+    pre_validation: AtomicU64,
+    post_validation: AtomicU64,
+    lock: AtomicU64,
+    validation: AtomicU64,
+    feed_drops: AtomicU64,
+    escalations: AtomicU64,
+}
+
+impl ThreadStats {
+    fn restart(&self, cause: ConflictCause) -> &AtomicU64 {
+        match cause {
+            ConflictCause::PreValidation => &self.pre_validation,
+            ConflictCause::PostValidation => &self.post_validation,
+            ConflictCause::Lock => &self.lock,
+            ConflictCause::Validation => &self.validation,
+        }
+    }
+
+    fn counters(&self) -> [&AtomicU64; 10] {
+        [
+            &self.commits,
+            &self.reads,
+            &self.aborts,
+            &self.retries,
+            &self.pre_validation,
+            &self.post_validation,
+            &self.lock,
+            &self.validation,
+            &self.feed_drops,
+            &self.escalations,
+        ]
+    }
+
+    pub(crate) fn absorb(&self, other: &ThreadStats) {
+        for (a, b) in self.counters().iter().zip(other.counters().iter()) {
+            a.fetch_add(b.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+    }
+
+    fn add_to(&self, s: &mut StatsSnapshot) {
+        s.commits += self.commits.load(Ordering::Relaxed);
+        s.reads += self.reads.load(Ordering::Relaxed);
+        s.aborts += self.aborts.load(Ordering::Relaxed);
+        s.retries += self.retries.load(Ordering::Relaxed);
+        s.pre_validation += self.pre_validation.load(Ordering::Relaxed);
+        s.post_validation += self.post_validation.load(Ordering::Relaxed);
+        s.lock += self.lock.load(Ordering::Relaxed);
+        s.validation += self.validation.load(Ordering::Relaxed);
+        s.feed_drops += self.feed_drops.load(Ordering::Relaxed);
+        s.escalations += self.escalations.load(Ordering::Relaxed);
+    }
+}
+
+// Outcomes of the transactions carrying one label. Not per thread: a label
+// usually belongs to a few call sites, and this keeps the table small.
+#[repr(align(64))]
+#[derive(Default)]
+struct LabelShard {
+    commits: AtomicU64,
+    reads: AtomicU64,
+    aborts: AtomicU64,
+    retries: AtomicU64,
+    restarts: AtomicU64,
+}
+
+impl LabelShard {
+    fn snapshot(&self) -> LabelStats {
+        LabelStats {
+            commits: self.commits.load(Ordering::Relaxed),
+            reads: self.reads.load(Ordering::Relaxed),
+            aborts: self.aborts.load(Ordering::Relaxed),
+            retries: self.retries.load(Ordering::Relaxed),
+            restarts: self.restarts.load(Ordering::Relaxed),
+        }
+    }
+}
+
+// The totals live in the per-thread `ThreadStats` of the STM's registry;
+// `labels` is indexed by the bucket index from `Labels`, `priorities` by
+// `TxPriority`.
+pub(crate) struct Stats {
+    labels: Vec<LabelShard>,
+    priorities: [LabelShard; 3],
+    window: Window,
+}
+
+impl Stats {
+    pub(crate) fn new() -> Stats {
+        Stats {
+            labels: (0..=MAX_LABELS).map(|_| LabelShard::default()).collect(),
+            priorities: Default::default(),
+            window: Window::new(),
+        }
+    }
+
+    pub(crate) fn commit(&self, t: &ThreadStats, label: usize, priority: TxPriority) {
+        t.commits.fetch_add(1, Ordering::Relaxed);
+        self.labels[label].commits.fetch_add(1, Ordering::Relaxed);
+        self.priorities[priority as usize]
+            .commits
+            .fetch_add(1, Ordering::Relaxed);
+        self.window.commit();
+    }
+
+    // A run of a transaction body that read at `read_ver`.
+    pub(crate) fn attempt(&self, read_ver: u64) {
+        self.window.attempt(read_ver);
+    }
+
+    pub(crate) fn throughput(&self, clock: u64) -> ThroughputWindow {
+        self.window.report(clock)
+    }
+
+    #[cfg(feature = "testing")]
+    pub(crate) fn skip_window(&self, d: std::time::Duration) {
+        self.window.skip(d);
+    }
+
+    pub(crate) fn read(&self, t: &ThreadStats, label: usize, priority: TxPriority) {
+        t.reads.fetch_add(1, Ordering::Relaxed);
+        self.labels[label].reads.fetch_add(1, Ordering::Relaxed);
+        self.priorities[priority as usize]
+            .reads
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn abort(&self, t: &ThreadStats, label: usize, priority: TxPriority) {
+        t.aborts.fetch_add(1, Ordering::Relaxed);
+        self.labels[label].aborts.fetch_add(1, Ordering::Relaxed);
+        self.priorities[priority as usize]
+            .aborts
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn retry(&self, t: &ThreadStats, label: usize, priority: TxPriority) {
+        t.retries.fetch_add(1, Ordering::Relaxed);
+        self.labels[label].retries.fetch_add(1, Ordering::Relaxed);
+        self.priorities[priority as usize]
+            .retries
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn restart(
+        &self,
+        t: &ThreadStats,
+        label: usize,
+        priority: TxPriority,
+        cause: ConflictCause,
+    ) {
+        t.restart(cause).fetch_add(1, Ordering::Relaxed);
+        self.labels[label].restarts.fetch_add(1, Ordering::Relaxed);
+        self.priorities[priority as usize]
+            .restarts
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn feed_drops(&self, t: &ThreadStats, n: u64) {
+        t.feed_drops.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub(crate) fn escalate(&self, t: &ThreadStats) {
+        t.escalations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self, threads: &Registry) -> StatsSnapshot {
+        let mut s = StatsSnapshot::default();
+        threads.visit(|c| c.stats.add_to(&mut s));
+        s
+    }
+
+    pub(crate) fn label_snapshot(&self, label: usize) -> LabelStats {
+        self.labels[label].snapshot()
+    }
+
+    pub(crate) fn priority_snapshot(&self, priority: TxPriority) -> LabelStats {
+        self.priorities[priority as usize].snapshot()
+    }
+
+    pub(crate) fn reset(&self, threads: &Registry) {
+        threads.visit(|c| {
+            for a in c.stats.counters().iter() {
+                a.store(0, Ordering::Relaxed);
+            }
+        });
+        for l in self.labels.iter().chain(self.priorities.iter()) {
+            l.commits.store(0, Ordering::Relaxed);
+            l.reads.store(0, Ordering::Relaxed);
+            l.aborts.store(0, Ordering::Relaxed);
+            l.retries.store(0, Ordering::Relaxed);
+            l.restarts.store(0, Ordering::Relaxed);
+        }
+        self.window.reset();
+    }
+}
+
+/// Transaction counters summed over all threads.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StatsSnapshot {
+    /// Committed write transactions.
+    pub commits: u64,
+    /// Completed read transactions.
+    pub reads: u64,
+    /// Bodies that returned `STMResult::Abort` (or hit a limit).
+    pub aborts: u64,
+    /// Bodies that returned `STMResult::Retry` without a conflict.
+    pub retries: u64,
+    /// Restarts per `ConflictCause`.
+    pub pre_validation: u64,
+    pub post_validation: u64,
+    pub lock: u64,
+    pub validation: u64,
+    /// Commit records dropped because a `STM::subscribe` receiver was full.
+    pub feed_drops: u64,
+    /// Write transactions that escalated to run serialized, see
+    /// `STMBuilder::escalate_after`.
+    pub escalations: u64,
+}
+
+/// Counters of the transactions carrying one label, see
+/// `STM::stats_by_label`, or one `TxPriority`, see
+/// `STM::stats_by_priority`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LabelStats {
+    pub commits: u64,
+    pub reads: u64,
+    pub aborts: u64,
+    pub retries: u64,
+    /// Restarts of all causes.
+    pub restarts: u64,
+}
+
+impl StatsSnapshot {
+    /// Restarts of all causes.
+    pub fn restarts(&self) -> u64 {
+        self.pre_validation + self.post_validation + self.lock + self.validation
+    }
+}
+
+impl fmt::Display for StatsSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "commits: {}", self.commits)?;
+        writeln!(f, "reads: {}", self.reads)?;
+        writeln!(f, "aborts: {}", self.aborts)?;
+        writeln!(f, "retries: {}", self.retries)?;
+        writeln!(f, "restarts: {}", self.restarts())?;
+        writeln!(f, "  pre-validation: {}", self.pre_validation)?;
+        writeln!(f, "  post-validation: {}", self.post_validation)?;
+        writeln!(f, "  lock: {}", self.lock)?;
+        writeln!(f, "  validation: {}", self.validation)?;
+        writeln!(f, "feed drops: {}", self.feed_drops)?;
+        write!(f, "escalations: {}", self.escalations)
+    }
+}
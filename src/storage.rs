@@ -0,0 +1,177 @@
+use alloc::boxed::Box;
+#[cfg(feature = "std")]
+use core::ops::Range;
+use core::ptr;
+#[cfg(feature = "std")]
+use std::io;
+
+#[cfg(feature = "mmap")]
+use std::fs::OpenOptions;
+#[cfg(feature = "mmap")]
+use std::path::Path;
+
+#[cfg(feature = "mmap")]
+use memmap2::MmapMut;
+
+use crate::sync::RawByte;
+#[cfg(feature = "std")]
+use crate::tl2::STM;
+
+/// The bytes behind a `Memory`, see `STMBuilder::storage`.
+///
+/// # Safety
+///
+/// `as_ptr` always returns the same pointer, valid for reads and writes of
+/// `len` bytes until the storage is dropped, and nothing but the `Memory`
+/// using the storage touches those bytes meanwhile. The STM accesses them
+/// as `AtomicU8`s.
+pub unsafe trait Storage: Send + Sync {
+    fn as_ptr(&self) -> *mut u8;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Make the bytes in `range` durable, for storage that has a backing
+    /// beyond memory. Does nothing by default.
+    #[cfg(feature = "std")]
+    fn sync(&self, range: Range<usize>) -> io::Result<()> {
+        let _ = range;
+        Ok(())
+    }
+}
+
+// The bytes of `storage` as the atomics the STM reads and writes, valid
+// while `storage` lives. `RawByte` has the size and alignment of `u8`.
+pub(crate) fn bytes(storage: &dyn Storage) -> *const [RawByte] {
+    ptr::slice_from_raw_parts(storage.as_ptr() as *const RawByte, storage.len())
+}
+
+/// Zeroed bytes on the heap, the default.
+pub struct HeapStorage {
+    bytes: Box<[RawByte]>,
+}
+
+impl HeapStorage {
+    pub fn new(size: usize) -> HeapStorage {
+        HeapStorage {
+            bytes: (0..size).map(|_| RawByte::new(0)).collect(),
+        }
+    }
+}
+
+// SAFETY: the boxed bytes live as long as the storage and are atomics, so
+// writing through a pointer derived from a shared reference is allowed
+unsafe impl Storage for HeapStorage {
+    fn as_ptr(&self) -> *mut u8 {
+        self.bytes.as_ptr() as *mut u8
+    }
+
+    fn len(&self) -> usize {
+        self.bytes.len()
+    }
+}
+
+/// A buffer the caller owns, e.g. a static array or shared memory set up
+/// by another library. Its current contents are the initial memory.
+pub struct ExternalStorage {
+    ptr: *mut u8,
+    len: usize,
+}
+
+// SAFETY: the buffer is only accessed through atomics, see `Storage`
+unsafe impl Send for ExternalStorage {}
+unsafe impl Sync for ExternalStorage {}
+
+impl ExternalStorage {
+    pub fn new(buf: &'static mut [u8]) -> ExternalStorage {
+        ExternalStorage {
+            ptr: buf.as_mut_ptr(),
+            len: buf.len(),
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `ptr` is valid for reads and writes of `len` bytes for as long as
+    /// the storage lives, and nothing else accesses them non-atomically
+    /// meanwhile.
+    pub unsafe fn from_raw_parts(ptr: *mut u8, len: usize) -> ExternalStorage {
+        ExternalStorage { ptr, len }
+    }
+}
+
+// SAFETY: the constructors require what `Storage` promises
+unsafe impl Storage for ExternalStorage {
+    fn as_ptr(&self) -> *mut u8 {
+        self.ptr
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+/// A file mapped into memory. Commits change the mapping; `STM::sync`
+/// writes them back to the file.
+#[cfg(feature = "mmap")]
+pub struct MmapStorage {
+    map: MmapMut,
+    ptr: *mut u8,
+}
+
+// SAFETY: `ptr` points into `map`, which the storage owns
+#[cfg(feature = "mmap")]
+unsafe impl Send for MmapStorage {}
+#[cfg(feature = "mmap")]
+unsafe impl Sync for MmapStorage {}
+
+#[cfg(feature = "mmap")]
+impl MmapStorage {
+    /// Map the first `size` bytes of the file at `path`, creating it and
+    /// growing it with zeros as needed. The file's contents are the
+    /// initial memory; no other process may map it meanwhile.
+    pub fn open<P: AsRef<Path>>(path: P, size: usize) -> io::Result<MmapStorage> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        if file.metadata()?.len() < size as u64 {
+            file.set_len(size as u64)?;
+        }
+        // SAFETY: the mapping is private to this storage as documented
+        let mut map = unsafe { memmap2::MmapOptions::new().len(size).map_mut(&file)? };
+        let ptr = map.as_mut_ptr();
+        Ok(MmapStorage { map, ptr })
+    }
+}
+
+// SAFETY: the mapping lives as long as the storage and does not move
+#[cfg(feature = "mmap")]
+unsafe impl Storage for MmapStorage {
+    fn as_ptr(&self) -> *mut u8 {
+        self.ptr
+    }
+
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    fn sync(&self, range: Range<usize>) -> io::Result<()> {
+        self.map.flush_range(range.start, range.end - range.start)
+    }
+}
+
+#[cfg(feature = "std")]
+impl STM {
+    /// Make the whole memory durable through `Storage::sync`. Commits are
+    /// paused meanwhile, so the synced bytes are a consistent state.
+    pub fn sync(&self) -> io::Result<()> {
+        let _paused = self.pause_commits();
+        self.storage().sync(0..self.capacity())
+    }
+}
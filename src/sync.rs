@@ -0,0 +1,42 @@
+// Atomics used by the STM. Building with `RUSTFLAGS="--cfg loom"` swaps in
+// loom's model-checked types so the commit and read protocols can be
+// explored exhaustively.
+
+#[cfg(loom)]
+pub(crate) use loom::sync::atomic::{fence, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+#[cfg(loom)]
+pub(crate) use loom::thread::yield_now;
+
+#[cfg(all(
+    not(loom),
+    not(all(target_family = "wasm", not(target_feature = "atomics")))
+))]
+pub(crate) use core::sync::atomic::fence;
+#[cfg(not(loom))]
+pub(crate) use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+#[cfg(all(not(loom), feature = "std"))]
+pub(crate) use std::thread::yield_now;
+
+// The bytes of a `Storage`, accessed in place.
+pub(crate) use core::sync::atomic::AtomicU8 as RawByte;
+
+// The stripe bytes as transactions read and write them. Loom cannot model
+// memory it did not allocate, so under loom a `Memory` works on a copy of
+// its storage's bytes in loom's atomics instead, see `Memory::with_parts`.
+#[cfg(not(loom))]
+pub(crate) use core::sync::atomic::AtomicU8 as Byte;
+#[cfg(loom)]
+pub(crate) use loom::sync::atomic::AtomicU8 as Byte;
+
+// lock/version words and the clock stay 64-bit on every target; targets
+// without native 64-bit atomics get them emulated
+#[cfg(all(not(loom), target_has_atomic = "64"))]
+pub(crate) use core::sync::atomic::AtomicU64;
+#[cfg(all(not(loom), not(target_has_atomic = "64")))]
+pub(crate) use portable_atomic::AtomicU64;
+
+// wasm without the atomics target feature has a single thread, so no other
+// thread can observe an ordering and conflicts cannot happen.
+#[cfg(all(not(loom), target_family = "wasm", not(target_feature = "atomics")))]
+#[inline(always)]
+pub(crate) fn fence(_: Ordering) {}
@@ -0,0 +1,84 @@
+use alloc::vec;
+use core::marker::PhantomData;
+
+use crate::tl2::{check_aligned, Trans, WriteTrans, STRIPE_SIZE};
+use crate::value::{BigValue, StripeValue};
+
+/// A value larger than a stripe, kept in a block of `T::STRIPES` stripes
+/// behind a version stripe that `set` bumps on every write.
+///
+/// `get` loads the version before and after the block, seqlock style, and
+/// fails if they differ. A caller that only needs to know whether the value
+/// changed can compare `version` instead of reading the whole block.
+pub struct TBig<T> {
+    base: usize,
+    _val: PhantomData<T>,
+}
+
+impl<T: BigValue> TBig<T> {
+    /// Place the value at the stripe-aligned address `base`; it takes
+    /// `TBig::size` bytes.
+    pub fn new(base: usize) -> TBig<T> {
+        check_aligned(base);
+        base.checked_add(Self::size())
+            .expect("value end overflows usize");
+
+        TBig {
+            base,
+            _val: PhantomData,
+        }
+    }
+
+    /// Bytes taken, the version stripe included.
+    pub fn size() -> usize {
+        (T::STRIPES + 1) * STRIPE_SIZE
+    }
+
+    /// Number of times the value has been set.
+    pub fn version<R: Trans>(&self, tr: &mut R) -> Option<u64> {
+        tr.load(self.base).map(u64::from_stripe)
+    }
+
+    pub fn get<R: Trans>(&self, tr: &mut R) -> Option<T> {
+        let before = self.version(tr)?;
+        let mut block = vec![[0; STRIPE_SIZE]; T::STRIPES];
+        for (i, stripe) in block.iter_mut().enumerate() {
+            *stripe = tr.load(self.base + (i + 1) * STRIPE_SIZE)?;
+        }
+        if self.version(tr)? != before {
+            return None;
+        }
+        Some(T::from_stripes(&block))
+    }
+
+    pub fn set(&self, tr: &mut WriteTrans, val: &T) -> Option<()> {
+        let ver = self.version(tr)?;
+        let mut block = vec![[0; STRIPE_SIZE]; T::STRIPES];
+        val.to_stripes(&mut block);
+
+        tr.store(self.base, ver.wrapping_add(1).to_stripe());
+        for (i, stripe) in block.into_iter().enumerate() {
+            tr.store(self.base + (i + 1) * STRIPE_SIZE, stripe);
+        }
+        Some(())
+    }
+}
+
+impl<T: BigValue> TBig<Option<T>> {
+    /// Move the value into `to`, overwriting it, and leave this one
+    /// `None`, as part of the transaction `tr`: a handoff between two
+    /// cells that never loses or duplicates the item. Returns the value
+    /// moved; if there is none, neither cell changes.
+    pub fn transfer(&self, tr: &mut WriteTrans, to: &TBig<Option<T>>) -> Option<Option<T>> {
+        let val = match self.get(tr)? {
+            Some(val) => val,
+            None => return Some(None),
+        };
+        let val = Some(val);
+        if to.base != self.base {
+            to.set(tr, &val)?;
+            self.set(tr, &None)?;
+        }
+        Some(val)
+    }
+}
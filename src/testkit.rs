@@ -0,0 +1,301 @@
+// A harness checking a structure built on the STM against a sequential
+// model under random concurrent schedules (the `testkit` feature).
+
+use std::fmt::{self, Debug};
+use std::ops::Range;
+use std::thread;
+
+use proptest::collection::vec;
+use proptest::strategy::Strategy;
+use proptest::test_runner::{Config, TestCaseError, TestError, TestRunner};
+
+use crate::tl2::{STMResult, WriteTrans, STM};
+
+mod linearize;
+
+pub use self::linearize::{
+    CounterOp, CounterSpec, History, HistoryRecorder, LinearizeError, LogOp, LogSpec, Operation,
+    SetOp, SetSpec, Spec,
+};
+
+type Setup<S> = Box<dyn Fn() -> (STM, S) + Send + Sync>;
+type Pre<M> = Box<dyn Fn(&M, u64) -> bool + Send + Sync>;
+type Tx<S, O> = Box<dyn Fn(&S, &mut WriteTrans, u64) -> STMResult<O> + Send + Sync>;
+type Model<M, O> = Box<dyn Fn(&mut M, u64) -> O + Send + Sync>;
+
+struct Op<S, M, O> {
+    name: &'static str,
+    pre: Pre<M>,
+    tx: Tx<S, O>,
+    model: Model<M, O>,
+}
+
+/// One call of a `Schedule`: the operation added `op`-th to the harness,
+/// with `arg`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Call {
+    pub op: usize,
+    pub arg: u64,
+}
+
+/// The calls each thread makes, in order.
+pub type Schedule = Vec<Vec<Call>>;
+
+/// A committed call whose result differs from the model's, see
+/// `Harness::check`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    /// Position of the call in commit order.
+    pub position: usize,
+    pub thread: usize,
+    pub op: &'static str,
+    pub arg: u64,
+    /// `Debug` output of the model's result and of the transaction's.
+    pub expected: String,
+    pub actual: String,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "commit {}: {}({}) on thread {} returned {}, the model {}",
+            self.position, self.op, self.arg, self.thread, self.actual, self.expected
+        )
+    }
+}
+
+/// Checks a structure kept in STM memory against a sequential model `M`.
+///
+/// Each operation is a name, a precondition on the model, a write
+/// transaction body on the structure `S` and the same operation on the
+/// model; both get a `u64` argument drawn from `args` and must return
+/// equal results `O`. A case runs a random `Schedule` on `threads`
+/// threads, sorts the committed calls by the version they committed at,
+/// which is the order TL2 serializes them in, replays them in that order
+/// on the model and compares the results. Calls that abort are left out.
+///
+/// Preconditions only steer generation: they hold when the calls are
+/// applied to the model in generation order, but threads may commit in
+/// another order, so bodies and model updates must handle every state.
+/// `run` uses proptest, which shrinks a failing schedule by dropping
+/// calls and lowering arguments.
+///
+/// ```
+/// use std::collections::HashSet;
+/// use tl2::{Harness, STMResult, TSet, STM};
+///
+/// Harness::new(
+///     || (STM::builder().capacity(1024).build(), TSet::<u64>::new(0, 1024)),
+///     HashSet::new(),
+/// )
+/// .op(
+///     "insert",
+///     |_, _| true,
+///     |set, tr, v| set.insert(tr, v).map_or(STMResult::Retry, |r| STMResult::Ok(r.unwrap())),
+///     |model, v| model.insert(v),
+/// )
+/// .op(
+///     "remove",
+///     |model, v| model.contains(&v),
+///     |set, tr, v| set.remove(tr, &v).map_or(STMResult::Retry, STMResult::Ok),
+///     |model, v| model.remove(&v),
+/// )
+/// .op(
+///     "contains",
+///     |_, _| true,
+///     |set, tr, v| set.contains(tr, &v).map_or(STMResult::Retry, STMResult::Ok),
+///     |model, v| model.contains(&v),
+/// )
+/// .cases(16)
+/// .run();
+/// ```
+pub struct Harness<S, M, O> {
+    setup: Setup<S>,
+    model: M,
+    ops: Vec<Op<S, M, O>>,
+    threads: usize,
+    calls: usize,
+    args: Range<u64>,
+    cases: u32,
+}
+
+impl<S, M, O> Harness<S, M, O>
+where
+    S: Sync,
+    M: Clone,
+    O: PartialEq + Debug + Send,
+{
+    /// `setup` builds an STM and the structure in it afresh for every
+    /// run; `model` is the model of the structure as built. Defaults to 4
+    /// threads, up to 32 calls a schedule, arguments in `0..16` and 64
+    /// cases.
+    pub fn new<F>(setup: F, model: M) -> Harness<S, M, O>
+    where
+        F: Fn() -> (STM, S) + Send + Sync + 'static,
+    {
+        Harness {
+            setup: Box::new(setup),
+            model,
+            ops: Vec::new(),
+            threads: 4,
+            calls: 32,
+            args: 0..16,
+            cases: 64,
+        }
+    }
+
+    pub fn op<P, T, U>(mut self, name: &'static str, pre: P, tx: T, model: U) -> Self
+    where
+        P: Fn(&M, u64) -> bool + Send + Sync + 'static,
+        T: Fn(&S, &mut WriteTrans, u64) -> STMResult<O> + Send + Sync + 'static,
+        U: Fn(&mut M, u64) -> O + Send + Sync + 'static,
+    {
+        self.ops.push(Op {
+            name,
+            pre: Box::new(pre),
+            tx: Box::new(tx),
+            model: Box::new(model),
+        });
+        self
+    }
+
+    pub fn threads(mut self, n: usize) -> Self {
+        assert!(n > 0);
+        self.threads = n;
+        self
+    }
+
+    /// The most calls in a schedule, across all threads.
+    pub fn calls(mut self, n: usize) -> Self {
+        self.calls = n;
+        self
+    }
+
+    pub fn args(mut self, args: Range<u64>) -> Self {
+        assert!(!args.is_empty());
+        self.args = args;
+        self
+    }
+
+    /// How many schedules `run` checks.
+    pub fn cases(mut self, n: u32) -> Self {
+        self.cases = n;
+        self
+    }
+
+    /// Random schedules: calls whose preconditions hold one after the
+    /// other on the model, dealt to the threads in turn.
+    ///
+    /// # Panics
+    ///
+    /// If no operation was added.
+    pub fn schedules(&self) -> impl Strategy<Value = Schedule> + '_ {
+        assert!(!self.ops.is_empty(), "no operations");
+        vec((0..self.ops.len(), self.args.clone()), 0..=self.calls)
+            .prop_map(move |calls| self.deal(&calls))
+    }
+
+    fn deal(&self, calls: &[(usize, u64)]) -> Schedule {
+        let mut model = self.model.clone();
+        let mut schedule = vec![Vec::new(); self.threads];
+        let mut n = 0;
+        for &(op, arg) in calls {
+            let o = &self.ops[op];
+            if (o.pre)(&model, arg) {
+                (o.model)(&mut model, arg);
+                schedule[n % self.threads].push(Call { op, arg });
+                n += 1;
+            }
+        }
+        schedule
+    }
+
+    /// Run `schedule` once on a fresh structure, a thread per entry, and
+    /// compare the committed calls with the model in commit order.
+    pub fn check(&self, schedule: &Schedule) -> Result<(), Mismatch> {
+        let (stm, s) = (self.setup)();
+        let (stm, s, ops) = (&stm, &s, &self.ops);
+        let mut committed: Vec<(u64, usize, Call, O)> = thread::scope(|scope| {
+            let threads: Vec<_> = schedule
+                .iter()
+                .enumerate()
+                .map(|(t, calls)| {
+                    scope.spawn(move || {
+                        calls
+                            .iter()
+                            .filter_map(|c| {
+                                let tx = &ops[c.op].tx;
+                                let (out, ver) =
+                                    stm.write_transaction_with_version(|tr| tx(s, tr, c.arg))?;
+                                Some((ver, t, *c, out))
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            threads
+                .into_iter()
+                .flat_map(|th| th.join().unwrap())
+                .collect()
+        });
+        committed.sort_by_key(|c| c.0);
+
+        let mut model = self.model.clone();
+        for (position, (_, thread, call, actual)) in committed.into_iter().enumerate() {
+            let op = &ops[call.op];
+            let expected = (op.model)(&mut model, call.arg);
+            if expected != actual {
+                return Err(Mismatch {
+                    position,
+                    thread,
+                    op: op.name,
+                    arg: call.arg,
+                    expected: format!("{:?}", expected),
+                    actual: format!("{:?}", actual),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Check `cases` random schedules.
+    ///
+    /// # Panics
+    ///
+    /// On a mismatch, with the shrunk schedule and its mismatch.
+    pub fn run(&self) {
+        let config = Config {
+            cases: self.cases,
+            failure_persistence: None,
+            ..Config::default()
+        };
+        let result = TestRunner::new(config).run(&self.schedules(), |schedule| {
+            self.check(&schedule)
+                .map_err(|m| TestCaseError::fail(m.to_string()))
+        });
+        match result {
+            Ok(()) => (),
+            Err(TestError::Fail(why, schedule)) => {
+                panic!("{}\n{}", why, self.describe(&schedule))
+            }
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    // One line of calls per thread that makes any.
+    fn describe(&self, schedule: &Schedule) -> String {
+        let mut out = String::new();
+        for (t, calls) in schedule.iter().enumerate() {
+            if calls.is_empty() {
+                continue;
+            }
+            let calls: Vec<String> = calls
+                .iter()
+                .map(|c| format!("{}({})", self.ops[c.op].name, c.arg))
+                .collect();
+            out += &format!("thread {}: {}\n", t, calls.join(", "));
+        }
+        out
+    }
+}
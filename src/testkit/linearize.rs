@@ -0,0 +1,312 @@
+// Linearizability checking: operations recorded with invocation and
+// response stamps during a concurrent run, searched for an order that a
+// sequential specification accepts (Wing and Gong's search, with Lowe's
+// cache of visited states).
+
+use std::collections::{BTreeSet, HashSet};
+use std::fmt::{self, Debug};
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// A sequential specification: the state of a structure, applying
+/// operations one at a time. States are hashed to prune the search, so
+/// keep them small.
+pub trait Spec: Clone + Eq + Hash {
+    type Op: Clone + Debug;
+    type Ret: PartialEq + Debug + Clone;
+
+    fn apply(&mut self, op: &Self::Op) -> Self::Ret;
+}
+
+/// A completed operation: `op` was invoked on `thread` at `invoke` and
+/// returned `ret` at `response`. Stamps come from one counter, so they
+/// order every invocation and response of a history.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Operation<O, R> {
+    pub thread: usize,
+    pub op: O,
+    pub ret: R,
+    pub invoke: u64,
+    pub response: u64,
+}
+
+/// Records operations from many threads into a `History`.
+///
+/// ```
+/// use std::thread;
+/// use tl2::{HistoryRecorder, SetOp, SetSpec, STMResult, TSet, STM};
+///
+/// let stm = STM::builder().capacity(1024).build();
+/// let set = TSet::<u64>::new(0, 1024);
+/// let rec = HistoryRecorder::new();
+/// thread::scope(|s| {
+///     for t in 0..4 {
+///         let (stm, set, rec) = (&stm, &set, &rec);
+///         s.spawn(move || {
+///             for i in 0..8 {
+///                 let v = (t + i) as u64 % 5;
+///                 rec.call(t, SetOp::Insert(v), || {
+///                     stm.write_transaction(|tr| {
+///                         set.insert(tr, v).map_or(STMResult::Retry, |r| STMResult::Ok(r.unwrap()))
+///                     })
+///                 });
+///                 rec.call(t, SetOp::Remove(v), || {
+///                     stm.write_transaction(|tr| {
+///                         set.remove(tr, &v).map_or(STMResult::Retry, STMResult::Ok)
+///                     })
+///                 });
+///             }
+///         });
+///     }
+/// });
+/// rec.take().check(SetSpec::default()).unwrap();
+/// ```
+pub struct HistoryRecorder<O, R> {
+    stamp: AtomicU64,
+    ops: Mutex<Vec<Operation<O, R>>>,
+}
+
+impl<O: Clone, R: Clone> Default for HistoryRecorder<O, R> {
+    fn default() -> Self {
+        HistoryRecorder::new()
+    }
+}
+
+impl<O: Clone, R: Clone> HistoryRecorder<O, R> {
+    pub fn new() -> HistoryRecorder<O, R> {
+        HistoryRecorder {
+            stamp: AtomicU64::new(0),
+            ops: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Run `f`, which performs `op`, stamping its invocation and response,
+    /// and return its result. `None` means the operation took no effect,
+    /// like a transaction that failed, and leaves it out of the history.
+    pub fn call<F>(&self, thread: usize, op: O, f: F) -> Option<R>
+    where
+        F: FnOnce() -> Option<R>,
+    {
+        let invoke = self.stamp.fetch_add(1, Ordering::SeqCst);
+        let ret = f()?;
+        let response = self.stamp.fetch_add(1, Ordering::SeqCst);
+        self.ops.lock().unwrap().push(Operation {
+            thread,
+            op,
+            ret: ret.clone(),
+            invoke,
+            response,
+        });
+        Some(ret)
+    }
+
+    /// Take what was recorded so far.
+    pub fn take(&self) -> History<O, R> {
+        let mut ops = std::mem::take(&mut *self.ops.lock().unwrap());
+        ops.sort_by_key(|o| o.invoke);
+        History { ops }
+    }
+}
+
+/// Why `History::check` found no linearization.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinearizeError {
+    /// No order the specification accepts exists. `longest` is the longest
+    /// accepted prefix found, as positions in `History::operations`.
+    NotLinearizable { longest: Vec<usize> },
+    /// The search took more than its step limit.
+    Exhausted,
+}
+
+impl fmt::Display for LinearizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinearizeError::NotLinearizable { longest } => write!(
+                f,
+                "history is not linearizable; longest linearizable prefix has {} operations",
+                longest.len()
+            ),
+            LinearizeError::Exhausted => write!(f, "linearizability search exhausted"),
+        }
+    }
+}
+
+impl std::error::Error for LinearizeError {}
+
+/// The completed operations of a concurrent run, by invocation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct History<O, R> {
+    ops: Vec<Operation<O, R>>,
+}
+
+impl<O, R> History<O, R> {
+    /// A history of `ops`, e.g. recorded some other way.
+    pub fn new(ops: Vec<Operation<O, R>>) -> History<O, R> {
+        let mut ops = ops;
+        ops.sort_by_key(|o| o.invoke);
+        History { ops }
+    }
+
+    pub fn operations(&self) -> &[Operation<O, R>] {
+        &self.ops
+    }
+}
+
+impl<O, R: PartialEq> History<O, R> {
+    /// Find an order of the operations that `spec`, as initially built,
+    /// accepts, and in which an operation that responded before another
+    /// was invoked comes first. Returns it as positions in `operations`.
+    /// Gives up after a million steps.
+    pub fn check<S>(&self, spec: S) -> Result<Vec<usize>, LinearizeError>
+    where
+        S: Spec<Op = O, Ret = R>,
+    {
+        self.check_bounded(spec, 1_000_000)
+    }
+
+    /// Like `check`, giving up after `steps` steps, each linearizing or
+    /// taking back one operation.
+    pub fn check_bounded<S>(&self, spec: S, steps: usize) -> Result<Vec<usize>, LinearizeError>
+    where
+        S: Spec<Op = O, Ret = R>,
+    {
+        let ops = &self.ops;
+        let n = ops.len();
+        let mut done = vec![0u64; n.div_ceil(64)];
+        let mut seen: HashSet<(Vec<u64>, S)> = HashSet::new();
+        // linearized operations and the state before each
+        let mut stack: Vec<(usize, S)> = Vec::with_capacity(n);
+        let mut longest = Vec::new();
+        let mut state = spec;
+        let mut from = 0;
+
+        for _ in 0..steps {
+            if stack.len() == n {
+                return Ok(stack.into_iter().map(|(i, _)| i).collect());
+            }
+            // an operation can go next if it was invoked before every
+            // pending one responded
+            let horizon = (0..n)
+                .filter(|&i| done[i / 64] & 1 << (i % 64) == 0)
+                .map(|i| ops[i].response)
+                .min()
+                .unwrap_or(u64::MAX);
+            let mut next = None;
+            for i in from..n {
+                if ops[i].invoke > horizon {
+                    break;
+                }
+                if done[i / 64] & 1 << (i % 64) != 0 {
+                    continue;
+                }
+                let mut after = state.clone();
+                if after.apply(&ops[i].op) != ops[i].ret {
+                    continue;
+                }
+                done[i / 64] |= 1 << (i % 64);
+                if seen.insert((done.clone(), after.clone())) {
+                    next = Some((i, after));
+                    break;
+                }
+                done[i / 64] &= !(1 << (i % 64));
+            }
+
+            match next {
+                Some((i, after)) => {
+                    stack.push((i, std::mem::replace(&mut state, after)));
+                    from = 0;
+                    if stack.len() > longest.len() {
+                        longest = stack.iter().map(|(i, _)| *i).collect();
+                    }
+                }
+                None => match stack.pop() {
+                    Some((i, before)) => {
+                        done[i / 64] &= !(1 << (i % 64));
+                        state = before;
+                        from = i + 1;
+                    }
+                    None => return Err(LinearizeError::NotLinearizable { longest }),
+                },
+            }
+        }
+        Err(LinearizeError::Exhausted)
+    }
+}
+
+/// A `u64` counter, like one kept with `WriteTrans::incref`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct CounterSpec(pub u64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CounterOp {
+    /// Add and return the new value.
+    Add(u64),
+    Get,
+}
+
+impl Spec for CounterSpec {
+    type Op = CounterOp;
+    type Ret = u64;
+
+    fn apply(&mut self, op: &CounterOp) -> u64 {
+        if let CounterOp::Add(n) = op {
+            self.0 += n;
+        }
+        self.0
+    }
+}
+
+/// A `TLog` of `u64` entries.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct LogSpec(pub Vec<u64>);
+
+/// Operations on a `LogSpec`. `Append` returns the slot, `Len` the length
+/// and `Get` the entry, or `None` past the head.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogOp {
+    Append(u64),
+    Len,
+    Get(usize),
+}
+
+impl Spec for LogSpec {
+    type Op = LogOp;
+    type Ret = Option<u64>;
+
+    fn apply(&mut self, op: &LogOp) -> Option<u64> {
+        match *op {
+            LogOp::Append(v) => {
+                self.0.push(v);
+                Some(self.0.len() as u64 - 1)
+            }
+            LogOp::Len => Some(self.0.len() as u64),
+            LogOp::Get(i) => self.0.get(i).copied(),
+        }
+    }
+}
+
+/// A `TSet` of `u64`s.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct SetSpec(pub BTreeSet<u64>);
+
+/// Operations on a `SetSpec`, returning what the `TSet` methods do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetOp {
+    Insert(u64),
+    Remove(u64),
+    Contains(u64),
+}
+
+impl Spec for SetSpec {
+    type Op = SetOp;
+    type Ret = bool;
+
+    fn apply(&mut self, op: &SetOp) -> bool {
+        match op {
+            SetOp::Insert(v) => self.0.insert(*v),
+            SetOp::Remove(v) => self.0.remove(v),
+            SetOp::Contains(v) => self.0.contains(v),
+        }
+    }
+}
@@ -1,18 +1,123 @@
-use std::cell::UnsafeCell;
-use std::collections::HashMap;
-use std::collections::HashSet;
-use std::sync::atomic::{fence, AtomicU64, Ordering};
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cell::Cell;
+use core::convert::TryInto;
+use core::fmt;
+use core::marker::PhantomData;
+use core::sync::atomic::AtomicBool;
+#[cfg(feature = "std")]
+use std::fmt::Write;
+#[cfg(feature = "std")]
+use std::sync::mpsc::Receiver;
+#[cfg(feature = "std")]
+use std::sync::{Mutex, RwLock};
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
 
-const STRIPE_SIZE: usize = 8; // u64, 8B
+use hashbrown::{HashMap, HashSet};
+
+#[cfg(feature = "tokio")]
+use crate::async_tx::Wakeup;
+#[cfg(feature = "std")]
+use crate::breaker::{Breaker, BreakerStats, Ticket};
+use crate::clock::{AtomicClock, Clock};
+#[cfg(feature = "commit-log")]
+use crate::commitlog::CommitSink;
+use crate::conflict::{Conflict, ConflictCause};
+use crate::contention::{Contender, ContentionManager, LockInfo, Resolution, TxPriority};
+#[cfg(feature = "metrics")]
+use crate::emit::Emitter;
+#[cfg(feature = "std")]
+use crate::emit::{Finish, Tally};
+#[cfg(feature = "std")]
+use crate::events::{EventKind, Events, TxEvent};
+#[cfg(feature = "testing")]
+use crate::fault::{Fault, FaultInjector};
+#[cfg(feature = "std")]
+use crate::feed::{CommitRecord, Feed};
+#[cfg(feature = "std")]
+use crate::heatmap::Heatmaps;
+#[cfg(feature = "history")]
+use crate::history::CommitHistory;
+#[cfg(feature = "std")]
+use crate::journal::Journal;
+#[cfg(feature = "std")]
+use crate::label::Labels;
+#[cfg(feature = "std")]
+use crate::latency::{self, Latency, LatencyHistograms};
+#[cfg(feature = "std")]
+use crate::mode::Mode;
+#[cfg(feature = "std")]
+use crate::observer::{Observing, TxObserver};
+#[cfg(feature = "pmem")]
+use crate::pmem::{Pmem, PmemStorage};
+#[cfg(feature = "std")]
+use crate::profile::{AttemptOutcome, Profiler, TxProfile};
+#[cfg(feature = "std")]
+use crate::registry::Registry;
+use crate::relax::{self, Relax};
+#[cfg(feature = "std")]
+use crate::replica::Replica;
+#[cfg(feature = "testing")]
+use crate::sched::{Scheduler, YieldPoint};
+#[cfg(feature = "std")]
+use crate::stats::{LabelStats, Stats, StatsSnapshot, ThreadStats};
+use crate::storage::{self, HeapStorage, Storage};
+use crate::sync::{fence, AtomicU64, AtomicU8, AtomicUsize, Byte, Ordering};
+#[cfg(feature = "std")]
+use crate::trace;
+use crate::trace::TxSpan;
+use crate::wait::{self, WaitStrategy, Waiter, Waits};
+#[cfg(feature = "std")]
+use crate::watchdog::{StalledTx, Watchdog, Watched};
+#[cfg(feature = "std")]
+use crate::window::{ClockSample, ThroughputWindow};
+#[cfg(feature = "trace")]
+use crate::workload::{TraceRecorder, Traced};
+
+pub(crate) const STRIPE_SIZE: usize = 8; // u64, 8B
 const MEM_SIZE: usize = 512;
 
+// Panic, reporting the caller, unless `addr` is a multiple of the stripe
+// size.
+#[inline]
+#[track_caller]
+pub(crate) fn check_aligned(addr: usize) {
+    if addr & (STRIPE_SIZE - 1) != 0 {
+        panic!(
+            "address {:#x} not aligned to stripe size {}",
+            addr, STRIPE_SIZE
+        );
+    }
+}
+#[cfg(feature = "std")]
+const LATENCY_SAMPLE_EVERY: u32 = 64;
+#[cfg(all(
+    feature = "std",
+    not(all(target_family = "wasm", target_os = "unknown"))
+))]
+const EVENT_RING: usize = 1024;
+// events carry timestamps and std on wasm32-unknown-unknown has no clock
+#[cfg(all(feature = "std", target_family = "wasm", target_os = "unknown"))]
+const EVENT_RING: usize = 0;
+#[cfg(feature = "std")]
+const FEED_BUFFER: usize = 1024;
+const READ_FALLBACK_AFTER: usize = 64;
+// Backoff after a conflict: up to 2^10 spins, then up to 2^10 µs asleep.
+const MAX_SPIN_SHIFT: u32 = 10;
+#[cfg(feature = "std")]
+const MAX_SLEEP_SHIFT: u32 = 10;
+// Tries a `TxPriority::High` committer spins on a held lock before giving up.
+const HIGH_LOCK_SPINS: u32 = 64;
+
 #[macro_export]
 macro_rules! load {
     ($t:ident, $a:expr) => {
         if let Some(v) = ($t).load($a) {
             v
         } else {
-            return tl2::STMResult::Retry;
+            return $crate::STMResult::Retry;
         }
     };
 }
@@ -25,10 +130,47 @@ macro_rules! store {
 }
 
 pub struct Memory {
-    mem: Vec<u8>,
-    lock_ver: Vec<AtomicU64>, // write-locks
-    global_clock: AtomicU64,
+    #[cfg_attr(not(feature = "std"), allow(dead_code))] // owns `mem`
+    storage: Box<dyn Storage>,
+    mem: *const [Byte], // the bytes of `storage`
+    #[cfg(loom)]
+    #[allow(dead_code)] // owns `mem` under loom, see `sync::Byte`
+    model: Box<[Byte]>,
+    lock_ver: Locks, // write-locks
+    owner: u64,      // tag of the locks this process takes
+    #[cfg(feature = "pmem")]
+    pmem: Option<Arc<Pmem>>, // where commits are made durable
+    holder: Vec<AtomicU64>, // priority of the lock holder, 0 when unlocked
+    contention: Option<Arc<dyn ContentionManager>>,
+    wounded: Vec<AtomicU8>, // lock holder asked to restart, 0 or 1
+    clock: Arc<dyn Clock>,
+    relax: Arc<dyn Relax>, // turns of the spin-waits below
+    waits: Waits,          // wound-wait lock waits and blocking retries
     shift_size: usize,
+    writers_blocked: AtomicUsize, // pessimistic readers pausing commits
+    serial: AtomicBool,           // the serialization token is taken
+    urgent: AtomicUsize,          // `TxPriority::High` runs past attempt 1
+    committing: AtomicUsize,      // writers between locking and unlocking
+    active: AtomicUsize,          // live WriteTrans and ReadTrans
+}
+
+// The lock/version words of a Memory, one per stripe.
+pub(crate) enum Locks {
+    Owned(Box<[AtomicU64]>),
+    // in a mapping the memory's storage keeps alive
+    #[cfg(all(unix, feature = "shm"))]
+    Shared(*const [AtomicU64]),
+}
+
+impl Locks {
+    fn words(&self) -> &[AtomicU64] {
+        match self {
+            Locks::Owned(words) => words,
+            // SAFETY: the mapping outlives the Memory holding self
+            #[cfg(all(unix, feature = "shm"))]
+            Locks::Shared(words) => unsafe { &**words },
+        }
+    }
 }
 
 pub enum STMResult<T> {
@@ -37,10 +179,182 @@ pub enum STMResult<T> {
     Abort,
 }
 
+/// Why a transaction finished without a result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxError {
+    /// The body returned `STMResult::Abort`.
+    Abort,
+    /// The body returned `STMResult::Retry` without a conflict.
+    Retry,
+    /// The body loaded more distinct stripes than `max_read_set` allows.
+    ReadSetTooLarge,
+    /// The commit could not be written to a synchronous journal.
+    Journal,
+    /// The `WriteTrans::commit_when` predicate rejected the commit.
+    Rejected,
+    /// The STM is poisoned, see `STM::poison`.
+    Poisoned,
+}
+
+impl fmt::Display for TxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TxError::Abort => write!(f, "transaction aborted"),
+            TxError::Retry => write!(f, "transaction gave up with retry"),
+            TxError::ReadSetTooLarge => write!(f, "read-set size limit exceeded"),
+            TxError::Journal => write!(f, "journal write failed"),
+            TxError::Rejected => write!(f, "commit rejected by predicate"),
+            TxError::Poisoned => write!(f, "STM poisoned"),
+        }
+    }
+}
+
+impl core::error::Error for TxError {}
+
+/// A `TxError` with the state of the attempt that ended the transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TxFailure {
+    pub error: TxError,
+    pub label: Option<&'static str>,
+    /// Runs of the body, including the final one.
+    pub attempts: u32,
+    /// Read-set and write-set sizes of the final run.
+    pub read_set: usize,
+    pub write_set: usize,
+}
+
+impl fmt::Display for TxFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(l) = self.label {
+            write!(f, "{}: ", l)?;
+        }
+        write!(
+            f,
+            "{} after {} attempts (read-set {}, write-set {})",
+            self.error, self.attempts, self.read_set, self.write_set
+        )
+    }
+}
+
+impl core::error::Error for TxFailure {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Memory taken by an STM, in bytes, see `STM::footprint`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Footprint {
+    /// The transactional memory, `STM::capacity`.
+    pub data_bytes: usize,
+    /// The lock/version words, one per stripe.
+    pub lock_bytes: usize,
+    /// Per-stripe lock holder state and the `STM` itself. Buffers of
+    /// optional features, like stats and the journal, are not counted.
+    pub overhead: usize,
+}
+
+impl Footprint {
+    pub fn total(&self) -> usize {
+        self.data_bytes + self.lock_bytes + self.overhead
+    }
+}
+
+/// Why `STM::write_transaction_retry_timeout` produced no result.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryTimeout {
+    /// The body still returned `STMResult::Retry` when the time was up.
+    TimedOut,
+    /// The transaction failed for another reason.
+    Failed(TxError),
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for RetryTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RetryTimeout::TimedOut => write!(f, "timed out waiting to stop retrying"),
+            RetryTimeout::Failed(e) => e.fmt(f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RetryTimeout {}
+
+/// Loads shared by read and write transactions, so helpers that only read
+/// can take either.
+pub trait Trans {
+    fn load(&mut self, addr: usize) -> Option<[u8; STRIPE_SIZE]>;
+}
+
+impl<'a> Trans for WriteTrans<'a> {
+    fn load(&mut self, addr: usize) -> Option<[u8; STRIPE_SIZE]> {
+        WriteTrans::load(self, addr)
+    }
+}
+
+impl<'a> Trans for ReadTrans<'a> {
+    fn load(&mut self, addr: usize) -> Option<[u8; STRIPE_SIZE]> {
+        ReadTrans::load(self, addr)
+    }
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Memory::new()
+    }
+}
+
 impl Memory {
     pub fn new() -> Memory {
-        let mem = [0].repeat(MEM_SIZE);
-        let mut lock_ver = Vec::new();
+        Memory::with_capacity(MEM_SIZE)
+    }
+
+    /// Memory of `size` bytes, a multiple of the stripe size.
+    pub fn with_capacity(size: usize) -> Memory {
+        Memory::with_storage(Box::new(HeapStorage::new(size)))
+    }
+
+    /// Memory kept in `storage`, whose length is a multiple of the stripe
+    /// size.
+    pub fn with_storage(storage: Box<dyn Storage>) -> Memory {
+        Memory::with_parts(
+            storage,
+            None,
+            Arc::new(AtomicClock::new()),
+            relax::default(),
+            wait::default(),
+        )
+    }
+
+    fn with_parts(
+        storage: Box<dyn Storage>,
+        locks: Option<Locks>,
+        clock: Arc<dyn Clock>,
+        relax: Arc<dyn Relax>,
+        waits: Arc<dyn WaitStrategy>,
+    ) -> Memory {
+        let size = storage.len();
+        assert!(
+            size & (STRIPE_SIZE - 1) == 0,
+            "memory size {} not a multiple of stripe size {}",
+            size,
+            STRIPE_SIZE
+        );
+        #[cfg(not(loom))]
+        let mem = storage::bytes(&*storage);
+        #[cfg(loom)]
+        let model: Box<[Byte]> = {
+            // SAFETY: `storage` is alive and not yet shared
+            let raw = unsafe { &*storage::bytes(&*storage) };
+            raw.iter()
+                .map(|b| Byte::new(b.load(Ordering::Relaxed)))
+                .collect()
+        };
+        #[cfg(loom)]
+        let mem = &*model as *const [Byte];
 
         let mut shift = 0;
         loop {
@@ -50,312 +364,3417 @@ impl Memory {
             shift += 1;
         }
 
-        for _ in 0..MEM_SIZE >> shift {
-            lock_ver.push(AtomicU64::new(0));
-        }
+        let stripes = size >> shift;
+        let lock_ver = locks
+            .unwrap_or_else(|| Locks::Owned((0..stripes).map(|_| AtomicU64::new(0)).collect()));
+        assert_eq!(lock_ver.words().len(), stripes);
+        let holder = (0..stripes).map(|_| AtomicU64::new(0)).collect();
+        let wounded = (0..stripes).map(|_| AtomicU8::new(0)).collect();
 
         Memory {
-            mem: mem,
-            lock_ver: lock_ver,
-            global_clock: AtomicU64::new(0),
+            storage,
+            mem,
+            #[cfg(loom)]
+            model,
+            lock_ver,
+            owner: 0,
+            #[cfg(feature = "pmem")]
+            pmem: None,
+            holder,
+            contention: None,
+            wounded,
+            clock,
+            relax,
+            waits: Waits::new(waits),
             shift_size: shift,
+            writers_blocked: AtomicUsize::new(0),
+            serial: AtomicBool::new(false),
+            urgent: AtomicUsize::new(0),
+            committing: AtomicUsize::new(0),
+            active: AtomicUsize::new(0),
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        self.bytes().len()
+    }
+
+    fn lock_ver(&self) -> &[AtomicU64] {
+        self.lock_ver.words()
+    }
+
+    fn footprint(&self) -> Footprint {
+        Footprint {
+            data_bytes: self.capacity(),
+            lock_bytes: core::mem::size_of_val(self.lock_ver()),
+            overhead: core::mem::size_of_val(&self.holder[..])
+                + core::mem::size_of_val(&self.wounded[..])
+                + core::mem::size_of::<STM>(),
         }
     }
 
+    fn bytes(&self) -> &[Byte] {
+        // SAFETY: `mem` points into `storage`, which lives as long as self
+        unsafe { &*self.mem }
+    }
+
+    // The bytes of the stripe at `addr`. The end is computed with checked
+    // arithmetic since `addr + STRIPE_SIZE` can wrap on 32-bit targets.
+    fn stripe(&self, addr: usize) -> &[Byte] {
+        let end = addr
+            .checked_add(STRIPE_SIZE)
+            .expect("stripe address overflows usize");
+        &self.bytes()[addr..end]
+    }
+
+    // Whether the stripe of `addr` is unlocked at a version the clock has
+    // not reached. Commits take their version from the clock before
+    // publishing it, so only corruption leaves one behind.
+    fn version_ahead(&self, addr: usize) -> bool {
+        let n = self.lock_ver()[addr >> self.shift_size].load(Ordering::Acquire);
+        n & (1 << 63) == 0 && n > self.clock.sample()
+    }
+
     fn test_not_modify(&self, addr: usize, rv: u64) -> bool {
-        let n = self.lock_ver[addr >> self.shift_size].load(Ordering::Relaxed);
+        let n = self.lock_ver()[addr >> self.shift_size].load(Ordering::Acquire);
         n <= rv
     }
 
-    fn get_addr_ver(&self, addr: usize) -> u64 {
-        let n = self.lock_ver[addr >> self.shift_size].load(Ordering::Relaxed);
-        n & !(1 << 63)
+    // Seqlock read of one stripe. The committer (see WriteTrans::commit) does
+    //
+    //   W1. lock_ver.CAS(v -> v | LOCK)   (lock_addr, Acquire)
+    //   W2. fence(Release)
+    //   W3. mem.store(..., Relaxed)       (the stripe bytes)
+    //   W4. lock_ver.store(wv, Release)   (publish and unlock)
+    //
+    // and the reader below does
+    //
+    //   R1. v1 = lock_ver.load(Acquire)
+    //   R2. mem.load(..., Relaxed)
+    //   R3. fence(Acquire)
+    //   R4. v2 = lock_ver.load(Relaxed)
+    //
+    // If R1 reads W4, W3 happens-before R2, so the copy sees that commit's
+    // bytes or a later commit's. If any byte read at R2 comes from a W3, the
+    // fence W2 synchronizes-with R3, so W1 happens-before R4 and v2 observes
+    // the lock bit or a later version; v1 != v2 then rejects the torn copy.
+    // R3 also keeps the copy from sinking below R4, and the Acquire at R1
+    // keeps it from being hoisted above R1.
+    //
+    // Also returns the version the stripe was read at.
+    fn load_versioned(
+        &self,
+        addr: usize,
+        rv: u64,
+    ) -> Result<([u8; STRIPE_SIZE], u64), ConflictCause> {
+        let lock = &self.lock_ver()[addr >> self.shift_size];
+
+        // pre validation (a locked stripe is always greater than rv)
+        let v1 = lock.load(Ordering::Acquire);
+        if v1 > rv {
+            return Err(ConflictCause::PreValidation);
+        }
+
+        // read from memory
+        let mut buf = [0; STRIPE_SIZE];
+        for (dst, src) in buf.iter_mut().zip(self.stripe(addr)) {
+            *dst = src.load(Ordering::Relaxed);
+        }
+
+        fence(Ordering::Acquire);
+
+        // post validation
+        let v2 = lock.load(Ordering::Relaxed);
+        if v1 != v2 {
+            return Err(ConflictCause::PostValidation);
+        }
+
+        Ok((buf, v1))
+    }
+
+    fn load_stripe(&self, addr: usize, rv: u64) -> Result<[u8; STRIPE_SIZE], ConflictCause> {
+        self.load_versioned(addr, rv).map(|(buf, _)| buf)
+    }
+
+    // The latest committed bytes of a stripe, without a transaction: the
+    // seqlock read above accepting any version, turning `relax` while a
+    // commit holds the stripe.
+    fn load_latest(&self, addr: usize) -> [u8; STRIPE_SIZE] {
+        loop {
+            match self.load_versioned(addr, (1 << 63) - 1) {
+                Ok((buf, _)) => return buf,
+                Err(_) => self.relax.relax(),
+            }
+        }
     }
 
-    fn lock_addr(&mut self, addr: usize) -> bool {
-        match self.lock_ver[addr >> self.shift_size].fetch_update(
-            Ordering::Relaxed,
-            Ordering::Relaxed,
-            |val| {
+    // Lock the stripe at `addr` and return its version. A locked word
+    // holds the lock bit and the owner tag instead of the version, so a
+    // lock left behind by a dead process can be told apart, see
+    // `STM::recover_dead_owner`.
+    fn lock_addr(&self, addr: usize) -> Option<u64> {
+        self.lock_ver()[addr >> self.shift_size]
+            .fetch_update(Ordering::Acquire, Ordering::Relaxed, |val| {
                 let n = val & (1 << 63);
                 if n == 0 {
-                    Some(val | (1 << 63))
+                    Some((1 << 63) | self.owner)
                 } else {
                     None
                 }
-            },
-        ) {
-            Ok(_) => true,
-            Err(_) => false,
+            })
+            .ok()
+    }
+
+    // Take the lock of `addr` for a transaction of priority `prio`. A
+    // holder of lower priority is wounded and waited for: it restarts
+    // unless it is already past the point of no return, so the wait is
+    // short. Priority 0 never waits, and as only a higher priority waits
+    // for a lower one there is no cycle to deadlock on. Without `wait` it
+    // gives up at once, as priority 0 does.
+    fn lock_addr_prio(&self, addr: usize, prio: u8, wait: bool) -> Option<u64> {
+        let idx = addr >> self.shift_size;
+        let mut waiter = None;
+        loop {
+            if let Some(ver) = self.lock_addr(addr) {
+                self.holder[idx].store(prio as u64, Ordering::Relaxed);
+                self.wounded[idx].store(0, Ordering::Relaxed);
+                return Some(ver);
+            }
+            if !wait || prio as u64 <= self.holder[idx].load(Ordering::Relaxed) {
+                return None;
+            }
+            // set on every turn, in case a new holder cleared it
+            self.wounded[idx].store(1, Ordering::Relaxed);
+            // wait only after a try made while registered, so the release
+            // cannot slip in between
+            if let Some(w) = waiter.take() {
+                Waiter::wait(w);
+            }
+            waiter = Some(self.waits.register());
         }
     }
 
-    fn inc_global_clock(&mut self) -> u64 {
-        self.global_clock.fetch_add(1, Ordering::AcqRel)
+    // Take the lock of `addr` for `me`, asking `cm` what to do while
+    // another transaction holds it. Only a holder of lower priority is
+    // wounded, so of two transactions wanting each other's locks at most
+    // one wounds and the other gives up in the end. Without `wait` it gives
+    // up instead of waiting a turn.
+    fn lock_addr_managed(
+        &self,
+        addr: usize,
+        cm: &dyn ContentionManager,
+        me: &Contender,
+        wait: bool,
+    ) -> Option<u64> {
+        let idx = addr >> self.shift_size;
+        let priority = cm.priority(me);
+        let mut turns = 0;
+        loop {
+            if let Some(ver) = self.lock_addr(addr) {
+                self.holder[idx].store(priority, Ordering::Relaxed);
+                self.wounded[idx].store(0, Ordering::Relaxed);
+                return Some(ver);
+            }
+            let other = LockInfo {
+                addr,
+                priority: self.holder[idx].load(Ordering::Relaxed),
+                turns,
+            };
+            match cm.on_conflict(me, &other) {
+                Resolution::AbortSelf => return None,
+                Resolution::AbortOther if priority > other.priority => {
+                    self.wounded[idx].store(1, Ordering::Relaxed);
+                    if !wait {
+                        return None;
+                    }
+                    self.relax.relax();
+                }
+                Resolution::AbortOther | Resolution::Wait if !wait => return None,
+                Resolution::AbortOther | Resolution::Wait => (),
+            }
+            turns = turns.saturating_add(1);
+        }
     }
 
-    fn unlock_addr(&mut self, addr: usize) {
-        self.lock_ver[addr >> self.shift_size].fetch_and(!(1 << 63), Ordering::Relaxed);
+    fn is_wounded(&self, addr: usize) -> bool {
+        self.wounded[addr >> self.shift_size].load(Ordering::Relaxed) != 0
     }
-}
 
-pub struct WriteTrans<'a> {
-    read_ver: u64,
-    read_set: HashSet<usize>,
-    write_set: HashMap<usize, [u8; STRIPE_SIZE]>,
-    locked: Vec<usize>,
-    is_abort: bool,
-    mem: &'a mut Memory,
-}
+    // The version of a new commit.
+    fn inc_global_clock(&self) -> u64 {
+        self.clock.increment()
+    }
 
-impl<'a> WriteTrans<'a> {
-    fn new(mem: &mut Memory) -> WriteTrans {
-        WriteTrans {
-            read_set: HashSet::new(),
-            write_set: HashMap::new(),
-            locked: Vec::new(),
-            is_abort: false,
-            read_ver: mem.global_clock.load(Ordering::Acquire),
-            mem: mem,
+    // Unlock the stripe at `addr`, locked at version `ver`, unchanged.
+    fn unlock_addr(&self, addr: usize, ver: u64) {
+        self.holder[addr >> self.shift_size].store(0, Ordering::Relaxed);
+        self.lock_ver()[addr >> self.shift_size].store(ver, Ordering::Release);
+    }
+
+    // Unlock the stripes locked with the tag `owner`. The versions they
+    // were locked at are gone, so they get a new one from the clock, which
+    // is returned with how many there were.
+    #[cfg(all(unix, feature = "shm"))]
+    fn release_owner(&self, owner: u64) -> (usize, Option<u64>) {
+        let locked = (1 << 63) | owner;
+        let mut ver = None;
+        let mut n = 0;
+        for (l, h) in self.lock_ver().iter().zip(self.holder.iter()) {
+            if l.load(Ordering::Relaxed) != locked {
+                continue;
+            }
+            let v = *ver.get_or_insert_with(|| self.inc_global_clock());
+            h.store(0, Ordering::Relaxed);
+            if l.compare_exchange(locked, v, Ordering::Release, Ordering::Relaxed)
+                .is_ok()
+            {
+                n += 1;
+            }
         }
+        (n, ver)
     }
 
-    pub fn load(&mut self, addr: usize) -> Option<[u8; STRIPE_SIZE]> {
-        if self.is_abort {
+    // Plain stores, only for callers with exclusive access. The stripes
+    // touched get a fresh version, so that versions never go back, which
+    // is returned.
+    fn zero(&self, addr: usize, len: usize) -> Option<u64> {
+        for b in self.bytes()[addr..addr + len].iter() {
+            b.store(0, Ordering::Relaxed);
+        }
+        if len == 0 {
             return None;
         }
+        let ver = self.inc_global_clock();
+        let first = addr >> self.shift_size;
+        let last = (addr + len - 1) >> self.shift_size;
+        for l in self.lock_ver()[first..=last].iter() {
+            l.store(ver, Ordering::Relaxed);
+        }
+        Some(ver)
+    }
+
+    // Plain stores of `bytes` from `addr`, versions left as they are.
+    // Only for callers with exclusive access.
+    #[cfg(feature = "std")]
+    fn fill(&self, addr: usize, bytes: &[u8]) {
+        for (b, v) in self.bytes()[addr..addr + bytes.len()].iter().zip(bytes) {
+            b.store(*v, Ordering::Relaxed);
+        }
+    }
 
-        assert_eq!(addr & (STRIPE_SIZE - 1), 0);
+    // The highest version of any stripe.
+    fn max_version(&self) -> u64 {
+        self.lock_ver()
+            .iter()
+            .map(|l| l.load(Ordering::Relaxed) & !(1 << 63))
+            .max()
+            .unwrap_or(0)
+    }
 
-        self.read_set.insert(addr);
+    // Addresses of the stripes locked right now.
+    fn locked_stripes(&self) -> Vec<usize> {
+        self.lock_ver()
+            .iter()
+            .enumerate()
+            .filter(|(_, l)| l.load(Ordering::Relaxed) & (1 << 63) != 0)
+            .map(|(i, _)| i << self.shift_size)
+            .collect()
+    }
 
-        // read from write-set
-        if let Some(m) = self.write_set.get(&addr) {
-            return Some(*m);
+    // Writers announce themselves in `committing` before taking stripe locks
+    // and pessimistic readers raise `writers_blocked` before waiting for
+    // `committing` to drain. Both sides store and then load the other
+    // counter with SeqCst, so at least one of them sees the other. A writer
+    // finding them blocked waits until they are not, with `wait`, and
+    // fails either way.
+    fn enter_commit(&self, wait: bool) -> bool {
+        self.committing.fetch_add(1, Ordering::SeqCst);
+        if self.writers_blocked.load(Ordering::SeqCst) == 0 {
+            return true;
         }
 
-        // pre validation
-        if !self.mem.test_not_modify(addr, self.read_ver) {
-            self.is_abort = true;
-            return None;
+        self.leave_commit();
+        while wait && self.writers_blocked.load(Ordering::Relaxed) > 0 {
+            self.relax.relax();
         }
+        false
+    }
 
-        fence(Ordering::Acquire);
+    fn leave_commit(&self) {
+        self.committing.fetch_sub(1, Ordering::Release);
+    }
 
-        // read from memory
-        let mut mem = [0; STRIPE_SIZE];
-        for (dst, src) in mem
-            .iter_mut()
-            .zip(self.mem.mem[addr..addr + STRIPE_SIZE].iter())
-        {
-            *dst = *src;
+    fn block_writers(&self) -> WritersBlocked<'_> {
+        self.writers_blocked.fetch_add(1, Ordering::SeqCst);
+        while self.committing.load(Ordering::SeqCst) > 0 {
+            self.relax.relax();
         }
+        WritersBlocked { mem: self }
+    }
 
-        fence(Ordering::SeqCst);
-
-        // post validation
-        if !self.mem.test_not_modify(addr, self.read_ver) {
-            self.is_abort = true;
-            return None;
+    // Take the serialization token, which one transaction holds at a time,
+    // and pause the commits of everybody else, see
+    // `STMBuilder::escalate_after`.
+    fn serialize(&self) -> Serialized<'_> {
+        loop {
+            if let Some(s) = self.try_serialize() {
+                return s;
+            }
+            self.relax.relax();
         }
-
-        Some(mem)
     }
 
-    pub fn store(&mut self, addr: usize, val: [u8; STRIPE_SIZE]) {
-        assert_eq!(addr & (STRIPE_SIZE - 1), 0);
-        self.write_set.insert(addr, val);
+    // `serialize` if nobody holds the token. Only the commits already under
+    // way are waited for, and none of them waits for anything.
+    fn try_serialize(&self) -> Option<Serialized<'_>> {
+        self.serial
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .ok()?;
+        Some(Serialized {
+            _blocked: self.block_writers(),
+            mem: self,
+        })
     }
 
-    fn lock_write_set(&mut self) -> bool {
-        for (addr, _) in self.write_set.iter() {
-            if self.mem.lock_addr(*addr) {
-                self.locked.push(*addr);
-            } else {
+    // `enter_commit` for the holder of the serialization token, whose own
+    // pause is counted in `writers_blocked`: it only waits for pessimistic
+    // readers, and fails instead without `wait`.
+    fn enter_commit_serial(&self, wait: bool) -> bool {
+        loop {
+            self.committing.fetch_add(1, Ordering::SeqCst);
+            if self.writers_blocked.load(Ordering::SeqCst) == 1 {
+                return true;
+            }
+            self.leave_commit();
+            if !wait {
                 return false;
             }
+            while self.writers_blocked.load(Ordering::Relaxed) > 1 {
+                self.relax.relax();
+            }
         }
-        true
     }
 
-    fn validate_read_set(&self) -> bool {
-        for addr in self.read_set.iter() {
-            if self.write_set.contains_key(addr) {
-                let ver = self.mem.get_addr_ver(*addr);
-                if ver > self.read_ver {
-                    return false;
-                }
-            } else {
-                if !self.mem.test_not_modify(*addr, self.read_ver) {
-                    return false;
-                }
-            }
-        }
-        true
+    // Mark a `TxPriority::High` transaction as restarting until the
+    // returned guard is dropped.
+    fn urge(&self) -> Urgent<'_> {
+        self.urgent.fetch_add(1, Ordering::Relaxed);
+        Urgent { mem: self }
     }
 
-    fn commit(&mut self, ver: u64) {
-        for (addr, val) in self.write_set.iter() {
-            let addr = *addr as usize;
-            for (dst, src) in self.mem.mem[addr..addr + STRIPE_SIZE].iter_mut().zip(val) {
-                *dst = *src;
-            }
+    // Let restarting `TxPriority::High` transactions finish before a `Low`
+    // attempt, which must hold nothing they could wait for.
+    fn yield_to_urgent(&self) {
+        while self.is_urgent() {
+            self.relax.relax();
         }
+    }
 
-        fence(Ordering::Release);
+    fn is_urgent(&self) -> bool {
+        self.urgent.load(Ordering::Relaxed) > 0
+    }
+}
 
-        for (addr, _) in self.write_set.iter() {
-            let idx = addr >> self.mem.shift_size;
-            self.mem.lock_ver[idx].store(ver, Ordering::Relaxed);
-        }
+// A restarting `TxPriority::High` transaction, see `Memory::urge`.
+struct Urgent<'a> {
+    mem: &'a Memory,
+}
 
-        self.locked.clear();
+impl<'a> Drop for Urgent<'a> {
+    fn drop(&mut self) {
+        self.mem.urgent.fetch_sub(1, Ordering::Relaxed);
     }
 }
 
-impl<'a> Drop for WriteTrans<'a> {
+// Commits are paused while this is alive.
+struct WritersBlocked<'a> {
+    mem: &'a Memory,
+}
+
+impl<'a> Drop for WritersBlocked<'a> {
+    fn drop(&mut self) {
+        self.mem.writers_blocked.fetch_sub(1, Ordering::Release);
+    }
+}
+
+// The serialization token, held with commits of others paused.
+pub(crate) struct Serialized<'a> {
+    _blocked: WritersBlocked<'a>,
+    mem: &'a Memory,
+}
+
+impl<'a> Drop for Serialized<'a> {
     fn drop(&mut self) {
-        for addr in self.locked.iter() {
-            self.mem.unlock_addr(*addr);
+        self.mem.serial.store(false, Ordering::Release);
+    }
+}
+
+type CommitPredicate<'a> = Box<dyn Fn(&CommitView) -> bool + 'a>;
+
+/// The values a write transaction is about to commit, see
+/// `WriteTrans::commit_when`.
+pub struct CommitView<'t, 'a> {
+    tr: &'t WriteTrans<'a>,
+    conflict: Cell<Option<Conflict>>,
+}
+
+impl<'t, 'a> CommitView<'t, 'a> {
+    /// The value `addr` will hold once committed: the stored value, or
+    /// else the value at the read version. `None` means the stripe changed
+    /// since; the transaction then restarts whatever the predicate returns.
+    pub fn get(&self, addr: usize) -> Option<[u8; STRIPE_SIZE]> {
+        check_aligned(addr);
+        if let Some(v) = self.tr.write_set.get(&addr) {
+            return Some(*v);
+        }
+        match self.tr.mem.load_stripe(addr, self.tr.read_ver) {
+            Ok(v) => Some(v),
+            Err(cause) => {
+                self.conflict.set(Some(Conflict {
+                    cause,
+                    addr: Some(addr),
+                }));
+                None
+            }
         }
     }
 }
 
-pub struct ReadTrans<'a> {
+// A write-set sorted by address, as journaled and published.
+#[cfg(feature = "std")]
+type Entries = Vec<(usize, [u8; STRIPE_SIZE])>;
+
+// See `STM::inject_conflict_before_commit`.
+#[cfg(feature = "testing")]
+type Injected = Box<dyn FnOnce(&STM) + Send>;
+
+/// A write transaction. It belongs to the attempt of the thread running
+/// `STM::write_transaction` and may not be moved or shared across threads.
+///
+/// ```compile_fail
+/// fn assert_send<T: Send>() {}
+/// assert_send::<tl2::WriteTrans>();
+/// ```
+///
+/// ```compile_fail
+/// fn assert_sync<T: Sync>() {}
+/// assert_sync::<tl2::WriteTrans>();
+/// ```
+pub struct WriteTrans<'a> {
     read_ver: u64,
+    read_set: HashSet<usize>,
+    write_set: HashMap<usize, [u8; STRIPE_SIZE]>,
+    locked: Vec<(usize, u64)>, // with the version each was locked at
     is_abort: bool,
+    conflict: Option<Conflict>,
+    stale: Cell<Option<Conflict>>, // found by should_yield
+    commit_when: Option<CommitPredicate<'a>>,
+    is_committing: bool,
+    version: u64, // of the commit, once made
+    fail_fast: bool,
+    max_read_set: usize,
+    prio: u8,
+    // for the contention manager, see `Contender`
+    priority: TxPriority,
+    attempt: u32,
+    start: u64,
+    work: u64,
+    serial: bool,      // holds the serialization token
+    nonblocking: bool, // see `WriteRun::nonblocking`
+    error: Option<TxError>,
     mem: &'a Memory,
+    _not_send: PhantomData<*const ()>,
 }
 
-impl<'a> ReadTrans<'a> {
-    fn new(mem: &Memory) -> ReadTrans {
-        ReadTrans {
+impl<'a> WriteTrans<'a> {
+    fn new(mem: &Memory, max_read_set: usize, prio: u8) -> WriteTrans<'_> {
+        mem.active.fetch_add(1, Ordering::AcqRel);
+        WriteTrans {
+            read_set: HashSet::new(),
+            write_set: HashMap::new(),
+            locked: Vec::new(),
             is_abort: false,
-            read_ver: mem.global_clock.load(Ordering::Acquire),
-            mem: mem,
+            conflict: None,
+            stale: Cell::new(None),
+            commit_when: None,
+            is_committing: false,
+            version: 0,
+            fail_fast: false,
+            max_read_set,
+            prio,
+            priority: TxPriority::Normal,
+            attempt: 1,
+            start: 0,
+            work: 0,
+            serial: false,
+            nonblocking: false,
+            error: None,
+            read_ver: mem.clock.sample(),
+            mem,
+            _not_send: PhantomData,
         }
     }
 
+    /// The version of the global clock this transaction reads at.
+    pub fn read_version(&self) -> u64 {
+        self.read_ver
+    }
+
+    /// Read the stripe at `addr`. `None` means the attempt is doomed: unless
+    /// the body returns `STMResult::Abort`, its result is thrown away and
+    /// the transaction restarts, or fails if the read-set limit was hit.
+    /// See `fail_fast` to leave the body right away instead.
     pub fn load(&mut self, addr: usize) -> Option<[u8; STRIPE_SIZE]> {
         if self.is_abort {
             return None;
         }
 
-        assert_eq!(addr & (STRIPE_SIZE - 1), 0);
+        check_aligned(addr);
 
-        // pre validation
-        if !self.mem.test_not_modify(addr, self.read_ver) {
+        if self.read_set.len() >= self.max_read_set && !self.read_set.contains(&addr) {
+            self.error = Some(TxError::ReadSetTooLarge);
             self.is_abort = true;
             return None;
         }
+        self.read_set.insert(addr);
 
-        fence(Ordering::Acquire);
+        // read from write-set
+        if let Some(m) = self.write_set.get(&addr) {
+            return Some(*m);
+        }
 
-        // read from memory
-        let mut mem = [0; STRIPE_SIZE];
-        for (dst, src) in mem
-            .iter_mut()
-            .zip(self.mem.mem[addr..addr + STRIPE_SIZE].iter())
-        {
-            *dst = *src;
+        // read from memory with pre and post validation
+        match self.mem.load_stripe(addr, self.read_ver) {
+            Ok(mem) => Some(mem),
+            Err(cause) => {
+                self.is_abort = true;
+                self.conflict = Some(Conflict {
+                    cause,
+                    addr: Some(addr),
+                });
+                None
+            }
         }
+    }
 
-        fence(Ordering::SeqCst);
+    /// Give up the attempt at its first conflicting `load`, whatever the
+    /// body goes on to do.
+    ///
+    /// A `load` that finds its stripe changed or locked returns `None`,
+    /// and so does every later `load` of the attempt, without touching
+    /// memory; `load!` returns `STMResult::Retry` right there. A body
+    /// calling `load` directly may instead carry on with made-up values
+    /// and return `Ok` or `Abort`. In fail-fast mode the rest of such a
+    /// body is cheap, as its `store`s are dropped too, and the transaction
+    /// restarts as if it had returned `STMResult::Retry`, whatever it
+    /// returned. The body still runs to its end: leave it early by
+    /// propagating the `None`, e.g. with `?` in a helper returning
+    /// `Option`. The mode lasts for the current attempt, so call this
+    /// first thing in the body.
+    pub fn fail_fast(&mut self) {
+        self.fail_fast = true;
+    }
 
-        // post validation
-        if !self.mem.test_not_modify(addr, self.read_ver) {
-            self.is_abort = true;
+    /// Read the committed value at `addr` without adding it to the read-set.
+    ///
+    /// The value is consistent with `read_ver` when it is returned, but the
+    /// stripe is not validated at commit, so it may have changed by the time
+    /// the transaction commits. Use it for logging and heuristics only; a
+    /// stripe that is newer than `read_ver` yields `None` without aborting
+    /// the transaction.
+    pub fn peek(&self, addr: usize) -> Option<[u8; STRIPE_SIZE]> {
+        if self.is_abort {
             return None;
         }
 
-        Some(mem)
+        check_aligned(addr);
+        self.mem.load_stripe(addr, self.read_ver).ok()
     }
-}
-
-pub struct STM {
-    mem: UnsafeCell<Memory>,
-}
-
-unsafe impl Sync for STM {}
 
-impl STM {
-    pub fn new() -> STM {
-        STM {
-            mem: UnsafeCell::new(Memory::new()),
+    /// Whether a stripe of the read-set has been written since `read_ver`,
+    /// which dooms this attempt. A long body may poll it and return
+    /// `STMResult::Retry` to restart early instead of finishing work that
+    /// will be thrown away at commit.
+    pub fn should_yield(&self) -> bool {
+        let stale = self
+            .read_set
+            .iter()
+            .find(|addr| !self.mem.test_not_modify(**addr, self.read_ver));
+        match stale {
+            Some(addr) => {
+                self.stale.set(Some(Conflict {
+                    cause: ConflictCause::Validation,
+                    addr: Some(*addr),
+                }));
+                true
+            }
+            None => false,
         }
     }
 
-    pub fn write_transaction<F, R>(&self, f: F) -> Option<R>
+    /// Check `pred` at commit time, with the write-set locked and the
+    /// read-set validated, and fail the transaction with
+    /// `TxError::Rejected` if it returns false. A later call replaces an
+    /// earlier predicate.
+    pub fn commit_when<P>(&mut self, pred: P)
     where
-        F: Fn(&mut WriteTrans) -> STMResult<R>,
+        P: Fn(&CommitView) -> bool + 'a,
     {
-        loop {
-            // 1. Sample global version-clock
-            let mut tr = WriteTrans::new(unsafe { &mut *self.mem.get() });
-
-            // 2. Run through a speculative execution
-            let result;
-            match f(&mut tr) {
-                STMResult::Abort => return None,
-                STMResult::Retry => {
-                    if tr.is_abort {
-                        continue;
-                    }
-                    return None;
-                }
-                STMResult::Ok(val) => {
-                    if tr.is_abort {
-                        continue;
-                    }
-                    result = val;
-                }
-            }
-
-            // 3. Lock the write-set
-            if !tr.lock_write_set() {
-                continue;
-            }
-
-            // 4. Increment global version-clock
-            let ver = 1 + tr.mem.inc_global_clock();
-
-            // 5. Validate the read-set
-            if ver != tr.read_ver + 1 && !tr.validate_read_set() {
-                continue;
-            }
-
-            // 6. Commit and release the locks
-            tr.commit(ver);
+        self.commit_when = Some(Box::new(pred));
+    }
 
-            return Some(result);
+    pub fn store(&mut self, addr: usize, val: [u8; STRIPE_SIZE]) {
+        check_aligned(addr);
+        if self.fail_fast && self.conflict.is_some() {
+            return;
         }
+        self.write_set.insert(addr, val);
+    }
+
+    /// Store `new` at `addr` and return the value it replaces, like
+    /// `AtomicU64::swap`.
+    pub fn exchange(&mut self, addr: usize, new: [u8; STRIPE_SIZE]) -> Option<[u8; STRIPE_SIZE]> {
+        let old = self.load(addr)?;
+        self.store(addr, new);
+        Some(old)
+    }
+
+    /// Move the stripe at `addr` from `from` to `to`; returns whether it
+    /// held `from`. Otherwise it is left alone, like a compare-and-swap
+    /// for states kept in stripes.
+    pub fn transition(
+        &mut self,
+        addr: usize,
+        from: [u8; STRIPE_SIZE],
+        to: [u8; STRIPE_SIZE],
+    ) -> Option<bool> {
+        if self.load(addr)? != from {
+            return Some(false);
+        }
+        self.store(addr, to);
+        Some(true)
+    }
+
+    /// Copy the `len` bytes at `addr`, both multiples of the stripe size.
+    /// Every stripe joins the read-set, so the copy is consistent with the
+    /// rest of the transaction; mind `max_read_set` for large regions.
+    pub fn export_region(&mut self, addr: usize, len: usize) -> Option<Vec<u8>> {
+        self.check_region(addr, len);
+        let mut buf = Vec::with_capacity(len);
+        for a in (addr..addr + len).step_by(STRIPE_SIZE) {
+            buf.extend_from_slice(&self.load(a)?);
+        }
+        Some(buf)
+    }
+
+    /// Store `data` at `addr`, the reverse of `export_region`.
+    pub fn import_region(&mut self, addr: usize, data: &[u8]) {
+        self.check_region(addr, data.len());
+        for (i, chunk) in data.chunks_exact(STRIPE_SIZE).enumerate() {
+            self.store(addr + i * STRIPE_SIZE, chunk.try_into().unwrap());
+        }
+    }
+
+    fn check_region(&self, addr: usize, len: usize) {
+        check_aligned(addr);
+        assert!(
+            len & (STRIPE_SIZE - 1) == 0,
+            "region length {} not a multiple of stripe size {}",
+            len,
+            STRIPE_SIZE
+        );
+        let end = addr.checked_add(len).expect("region end overflows usize");
+        assert!(end <= self.mem.capacity(), "region out of bounds");
+    }
+
+    // Stripes holding integers are little-endian u64s.
+    fn update_u64<F>(&mut self, addr: usize, f: F) -> Option<u64>
+    where
+        F: FnOnce(u64) -> u64,
+    {
+        let cur = u64::from_le_bytes(self.load(addr)?);
+        let val = f(cur);
+        if val != cur {
+            self.store(addr, val.to_le_bytes());
+        }
+        Some(val)
+    }
+
+    /// Store `max(current, candidate)` at `addr` and return the new value.
+    pub fn update_max_u64(&mut self, addr: usize, candidate: u64) -> Option<u64> {
+        self.update_u64(addr, |cur| cur.max(candidate))
+    }
+
+    /// Store `min(current, candidate)` at `addr` and return the new value.
+    pub fn update_min_u64(&mut self, addr: usize, candidate: u64) -> Option<u64> {
+        self.update_u64(addr, |cur| cur.min(candidate))
+    }
+
+    /// Increment the reference count at `addr` and return the new count.
+    pub fn incref(&mut self, addr: usize) -> Option<u64> {
+        self.update_u64(addr, |cur| cur.checked_add(1).expect("refcount overflow"))
+    }
+
+    /// Decrement the reference count at `addr` and return the new count.
+    /// The transaction that sees it reach zero owns the object and may free
+    /// it before committing.
+    ///
+    /// # Panics
+    ///
+    /// If the count is already zero.
+    pub fn decref(&mut self, addr: usize) -> Option<u64> {
+        self.update_u64(addr, |cur| {
+            cur.checked_sub(1).expect("decref of a zero refcount")
+        })
+    }
+
+    /// A transactional once cell: a flag stripe at `addr` and the value in
+    /// the stripe after it. If the flag is clear, store `init()` and set
+    /// the flag; either way return the value.
+    ///
+    /// Of the transactions racing to initialize, exactly one commits its
+    /// value and the others see it after restarting. `init` may still run
+    /// in attempts that are later thrown away, so keep it free of side
+    /// effects.
+    pub fn init_once<F>(&mut self, addr: usize, init: F) -> Option<[u8; STRIPE_SIZE]>
+    where
+        F: FnOnce() -> [u8; STRIPE_SIZE],
+    {
+        if u64::from_le_bytes(self.load(addr)?) != 0 {
+            return self.load(addr + STRIPE_SIZE);
+        }
+        let val = init();
+        self.store(addr, 1u64.to_le_bytes());
+        self.store(addr + STRIPE_SIZE, val);
+        Some(val)
+    }
+
+    // Take the lock of `addr` at `prio`, spinning a while on a held lock
+    // for a `TxPriority::High` transaction.
+    fn lock_addr_spinning(&self, addr: usize) -> Option<u64> {
+        let mut spins = if self.priority == TxPriority::High {
+            HIGH_LOCK_SPINS
+        } else {
+            0
+        };
+        loop {
+            match self.mem.lock_addr_prio(addr, self.prio, !self.nonblocking) {
+                None if spins > 0 => {
+                    spins -= 1;
+                    core::hint::spin_loop();
+                }
+                locked => return locked,
+            }
+        }
+    }
+
+    fn lock_write_set(&mut self) -> bool {
+        let entered = if self.serial {
+            self.mem.enter_commit_serial(!self.nonblocking)
+        } else {
+            self.mem.enter_commit(!self.nonblocking)
+        };
+        if !entered {
+            self.conflict = Some(Conflict {
+                cause: ConflictCause::Lock,
+                addr: None,
+            });
+            return false;
+        }
+        self.is_committing = true;
+
+        let me = Contender {
+            attempt: self.attempt,
+            reads: self.read_set.len(),
+            work: self.work + self.read_set.len() as u64,
+            start: self.start,
+            prio: self.prio,
+            priority: self.priority,
+        };
+        // in address order, so that two commits wanting the same stripes
+        // take them in the same order and the outcome does not hang on how
+        // the write-set happens to iterate
+        let mut addrs: Vec<usize> = self.write_set.keys().copied().collect();
+        addrs.sort_unstable();
+        for addr in addrs.iter() {
+            let locked = match &self.mem.contention {
+                Some(cm) => self
+                    .mem
+                    .lock_addr_managed(*addr, &**cm, &me, !self.nonblocking),
+                None => self.lock_addr_spinning(*addr),
+            };
+            let ver = match locked {
+                Some(ver) => ver,
+                None => {
+                    self.conflict = Some(Conflict {
+                        cause: ConflictCause::Lock,
+                        addr: Some(*addr),
+                    });
+                    return false;
+                }
+            };
+            self.locked.push((*addr, ver));
+
+            // the version cannot change while locked, so a stripe read
+            // before is validated here instead of in validate_read_set
+            if ver > self.read_ver && self.read_set.contains(addr) {
+                self.conflict = Some(Conflict {
+                    cause: ConflictCause::Validation,
+                    addr: Some(*addr),
+                });
+                return false;
+            }
+        }
+        true
+    }
+
+    // A stripe of the write-set whose lock a higher-priority transaction
+    // wants.
+    fn wounded(&self) -> Option<Conflict> {
+        self.locked
+            .iter()
+            .find(|(addr, _)| self.mem.is_wounded(*addr))
+            .map(|(addr, _)| Conflict {
+                cause: ConflictCause::Lock,
+                addr: Some(*addr),
+            })
+    }
+
+    fn validate_read_set(&mut self) -> bool {
+        for addr in self.read_set.iter() {
+            // stripes of the write-set were validated by lock_write_set
+            if !self.write_set.contains_key(addr) && !self.mem.test_not_modify(*addr, self.read_ver)
+            {
+                self.conflict = Some(Conflict {
+                    cause: ConflictCause::Validation,
+                    addr: Some(*addr),
+                });
+                return false;
+            }
+        }
+        true
+    }
+
+    #[cfg(feature = "std")]
+    fn entries(&self) -> Entries {
+        let mut entries: Vec<_> = self.write_set.iter().map(|(a, v)| (*a, *v)).collect();
+        entries.sort_unstable_by_key(|(a, _)| *a);
+        entries
+    }
+
+    // Write the new bytes and publish them at `ver`, calling `halfway`
+    // once half of the write-set is written.
+    fn commit<H: FnOnce()>(&mut self, ver: u64, halfway: H) {
+        // order the lock bits set by lock_write_set before the bytes below,
+        // pairing with the Acquire fence in Memory::load_stripe
+        fence(Ordering::Release);
+
+        // with persistent memory, the old bytes of each stripe are
+        // persisted before the new ones, see `pmem`
+        #[cfg(feature = "pmem")]
+        let pmem = self
+            .mem
+            .pmem
+            .as_ref()
+            .map(|p| (p, p.begin(ver, &*self.mem.relax)));
+
+        // `locked` holds the write-set in address order
+        let half = self.locked.len().saturating_sub(1) / 2;
+        let mut halfway = Some(halfway);
+        for (i, (addr, _)) in self.locked.iter().enumerate() {
+            let (addr, val) = (*addr, &self.write_set[addr]);
+            #[cfg(feature = "pmem")]
+            if let Some((p, _)) = pmem {
+                p.save(addr, ver, self.mem.stripe(addr));
+            }
+            for (dst, src) in self.mem.stripe(addr).iter().zip(val) {
+                dst.store(*src, Ordering::Relaxed);
+            }
+            #[cfg(feature = "pmem")]
+            if let Some((p, _)) = pmem {
+                p.written(addr);
+            }
+            if i == half {
+                if let Some(h) = halfway.take() {
+                    h();
+                }
+            }
+        }
+        #[cfg(feature = "pmem")]
+        if let Some((p, slot)) = pmem {
+            p.end(slot);
+        }
+
+        // publish the bytes with the new version and release the locks
+        for (addr, _) in self.locked.iter() {
+            let idx = addr >> self.mem.shift_size;
+            self.mem.holder[idx].store(0, Ordering::Relaxed);
+            self.mem.lock_ver()[idx].store(ver, Ordering::Release);
+        }
+
+        self.locked.clear();
+        self.version = ver;
+    }
+}
+
+impl<'a> Drop for WriteTrans<'a> {
+    fn drop(&mut self) {
+        for (addr, ver) in self.locked.iter() {
+            self.mem.unlock_addr(*addr, *ver);
+        }
+
+        if self.is_committing {
+            self.mem.leave_commit();
+        }
+        if !self.write_set.is_empty() {
+            self.mem.waits.released();
+        }
+
+        self.mem.active.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// A read-only transaction, bound to its thread like `WriteTrans`.
+///
+/// ```compile_fail
+/// fn assert_send<T: Send>() {}
+/// assert_send::<tl2::ReadTrans>();
+/// ```
+///
+/// ```compile_fail
+/// fn assert_sync<T: Sync>() {}
+/// assert_sync::<tl2::ReadTrans>();
+/// ```
+pub struct ReadTrans<'a> {
+    read_ver: u64,
+    is_abort: bool,
+    conflict: Option<Conflict>,
+    read_set: HashMap<usize, u64>, // address -> version first loaded at
+    refreshed: bool,               // whether `refresh` moved a version past read_ver
+    mem: &'a Memory,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<'a> ReadTrans<'a> {
+    fn new(mem: &Memory) -> ReadTrans<'_> {
+        mem.active.fetch_add(1, Ordering::AcqRel);
+        ReadTrans {
+            is_abort: false,
+            conflict: None,
+            read_set: HashMap::new(),
+            refreshed: false,
+            read_ver: mem.clock.sample(),
+            mem,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// The version of the global clock this transaction reads at.
+    pub fn read_version(&self) -> u64 {
+        self.read_ver
+    }
+
+    pub fn load(&mut self, addr: usize) -> Option<[u8; STRIPE_SIZE]> {
+        if self.is_abort {
+            return None;
+        }
+
+        check_aligned(addr);
+
+        // a refreshed stripe is read at the version it was refreshed at
+        let rv = match self.read_set.get(&addr) {
+            Some(ver) if self.refreshed => self.read_ver.max(*ver),
+            _ => self.read_ver,
+        };
+
+        // read from memory with pre and post validation
+        match self.mem.load_versioned(addr, rv) {
+            Ok((mem, ver)) => {
+                self.read_set.entry(addr).or_insert(ver);
+                Some(mem)
+            }
+            Err(cause) => {
+                self.is_abort = true;
+                self.conflict = Some(Conflict {
+                    cause,
+                    addr: Some(addr),
+                });
+                None
+            }
+        }
+    }
+
+    /// Load `addr` at the current version of the global clock instead of
+    /// `read_version` and record that version for it, so a stripe written
+    /// since the transaction began can be read without a restart. A
+    /// transaction aborted by a failed `load` of `addr` continues; `None`
+    /// if the stripe is being written right now, or if the transaction was
+    /// aborted by anything else.
+    ///
+    /// This gives up consistency for `addr`: its value may be newer than
+    /// everything else the body read, so together they need not match any
+    /// committed state. Other stripes are still read at `read_version`,
+    /// while later `load`s of `addr` and `validate` check it against the
+    /// refreshed version. Only refresh a field that is meaningful on its
+    /// own, like a statistic or a hint.
+    pub fn refresh(&mut self, addr: usize) -> Option<[u8; STRIPE_SIZE]> {
+        if self.is_abort {
+            match self.conflict {
+                Some(c) if c.addr == Some(addr) && c.cause != ConflictCause::Validation => {
+                    self.is_abort = false;
+                    self.conflict = None;
+                }
+                _ => return None,
+            }
+        }
+
+        check_aligned(addr);
+
+        match self.mem.load_versioned(addr, self.mem.clock.sample()) {
+            Ok((mem, ver)) => {
+                self.read_set.insert(addr, ver);
+                self.refreshed = true;
+                Some(mem)
+            }
+            Err(cause) => {
+                self.is_abort = true;
+                self.conflict = Some(Conflict {
+                    cause,
+                    addr: Some(addr),
+                });
+                None
+            }
+        }
+    }
+
+    /// Load every stripe of `addrs`, keyed by address. Like separate
+    /// `load`s, they are read at one version; `None` if any load fails.
+    pub fn load_many(&mut self, addrs: &[usize]) -> Option<HashMap<usize, [u8; STRIPE_SIZE]>> {
+        addrs.iter().map(|&a| Some((a, self.load(a)?))).collect()
+    }
+
+    /// Check that no stripe loaded so far has been written since, i.e.
+    /// that what was read is still current. On failure the transaction
+    /// is aborted like a failed `load`, so it restarts whatever the body
+    /// returns.
+    pub fn validate(&mut self) -> bool {
+        if self.is_abort {
+            return false;
+        }
+
+        for (addr, ver) in self.read_set.iter() {
+            if self.mem.lock_ver()[addr >> self.mem.shift_size].load(Ordering::Acquire) != *ver {
+                self.is_abort = true;
+                self.conflict = Some(Conflict {
+                    cause: ConflictCause::Validation,
+                    addr: Some(*addr),
+                });
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl<'a> Drop for ReadTrans<'a> {
+    fn drop(&mut self) {
+        self.mem.active.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+// Where a write transaction spends its time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Phase {
+    // Running the body, restarts included.
+    Execute = 0,
+    // Locking the write-set.
+    Lock = 1,
+    // Validating the read-set.
+    Validate = 2,
+    // Appending to the journal and waiting for it to sync.
+    Journal = 3,
+    // Copying the write-set out and unlocking.
+    Publish = 4,
+}
+
+// How one run of a transaction body ended.
+enum Outcome<R> {
+    Commit(R),
+    Restart(Conflict),
+    Fail(TxError),
+}
+
+// How a run of a write transaction body ended, for the loop driving it.
+pub(crate) enum Step<R> {
+    Done(Result<R, TxFailure>),
+    Restart,
+    // The body returned `Retry` without a conflict and the loop waits
+    // for a commit instead of failing.
+    Wait,
+}
+
+// Everything that reports on a transaction across its attempts: stats,
+// latency, the event ring, heatmaps, the watchdog, the observer, `tracing`
+// and `metrics`. Without the `std` feature there is none of it and every
+// method is an empty inline function.
+#[cfg(feature = "std")]
+struct Report<'s> {
+    stm: &'s STM,
+    label: Option<&'static str>,
+    read_only: bool,
+    priority: TxPriority,
+    observing: Option<Observing>,
+    bucket: usize,
+    timer: Option<latency::Timer>,
+    tally: Tally,
+    watched: Option<Watched<'s>>,
+    profiler: Option<Profiler>,
+    #[cfg(feature = "trace")]
+    traced: Option<Traced>,
+}
+
+#[cfg(not(feature = "std"))]
+struct Report<'s>(PhantomData<&'s STM>);
+
+#[cfg(feature = "std")]
+impl<'s> Report<'s> {
+    fn new(stm: &'s STM, label: Option<&'static str>, read_only: bool) -> Report<'s> {
+        let bucket = stm.bucket(label);
+        Report {
+            stm,
+            label,
+            read_only,
+            priority: TxPriority::Normal,
+            observing: stm.observing(label, read_only),
+            bucket,
+            timer: if read_only {
+                None
+            } else {
+                stm.latency.as_ref().and_then(Latency::start)
+            },
+            tally: Tally::new(),
+            watched: stm
+                .watchdog
+                .as_ref()
+                .and_then(|w| w.watch(bucket, read_only)),
+            profiler: None,
+            #[cfg(feature = "trace")]
+            traced: stm.recorder.as_ref().and_then(|r| r.sample()),
+        }
+    }
+
+    // The start of attempt `attempt`, at the hint `priority`.
+    fn begin(&mut self, attempt: u32, priority: TxPriority) {
+        self.priority = priority;
+        if let Some(w) = &self.watched {
+            w.attempt(attempt, priority);
+        }
+        if let Some(p) = &mut self.profiler {
+            p.begin();
+        }
+    }
+
+    // Attempt `attempt` runs serialized.
+    fn escalate(&mut self, attempt: u32) {
+        self.stm.count(|s, t| s.escalate(t));
+        if let Some(o) = &self.observing {
+            o.escalate(attempt);
+        }
+    }
+
+    // The attempt read at `read_ver`.
+    fn attempted(&self, read_ver: u64) {
+        if let Some(s) = &self.stm.stats {
+            s.attempt(read_ver);
+        }
+    }
+
+    fn mark(&mut self, phase: Phase) {
+        latency::mark(&mut self.timer, phase);
+        if let Some(p) = &mut self.profiler {
+            p.mark(phase);
+        }
+    }
+
+    // The addresses the attempt read and wrote, kept if it is traced.
+    #[cfg(feature = "trace")]
+    fn trace_sets<'a, R, W>(&mut self, reads: R, writes: W)
+    where
+        R: Iterator<Item = &'a usize>,
+        W: Iterator<Item = &'a usize>,
+    {
+        if let Some(t) = &mut self.traced {
+            t.sets(reads, writes);
+        }
+    }
+
+    #[cfg(feature = "trace")]
+    fn trace_finish(&self, attempt: u32, committed: bool) {
+        if let (Some(r), Some(t)) = (&self.stm.recorder, &self.traced) {
+            r.record(self.label, self.read_only, committed, attempt, t);
+        }
+    }
+
+    fn commit(&mut self, span: &TxSpan, attempt: u32, sets: (usize, usize)) {
+        if let Some(p) = &mut self.profiler {
+            p.end(sets, AttemptOutcome::Committed);
+        }
+        #[cfg(feature = "trace")]
+        self.trace_finish(attempt, true);
+        let stm = self.stm;
+        let bucket = self.bucket;
+        if stm.track_mode {
+            let read_only = self.read_only || sets.1 == 0;
+            stm.threads.with_local(|t| t.mode.record(read_only));
+        }
+        if self.read_only {
+            stm.count(|s, t| s.read(t, bucket, self.priority));
+            stm.emit(self.label, Finish::Read, &self.tally, None);
+        } else {
+            stm.count(|s, t| s.commit(t, bucket, self.priority));
+            if let (Some(l), Some(t)) = (&stm.latency, &self.timer) {
+                l.record(t);
+            }
+            stm.emit(self.label, Finish::Commit, &self.tally, self.timer.as_ref());
+        }
+        span.finish(attempt, sets.0, sets.1);
+        if let Some(o) = &self.observing {
+            o.commit(attempt, sets);
+        }
+    }
+
+    fn restart(&mut self, span: &TxSpan, c: Conflict, attempt: u32, sets: (usize, usize)) {
+        if let Some(p) = &mut self.profiler {
+            p.end(sets, AttemptOutcome::Restarted(c));
+        }
+        self.stm
+            .restarted(span, self.bucket, self.priority, c, attempt);
+        self.tally.restart(c.cause);
+        if let Some(o) = &self.observing {
+            o.restart(attempt, sets, c);
+        }
+    }
+
+    fn fail(&mut self, span: &TxSpan, e: TxError, attempt: u32, sets: (usize, usize)) {
+        if let Some(p) = &mut self.profiler {
+            p.end(sets, AttemptOutcome::Failed(e));
+        }
+        #[cfg(feature = "trace")]
+        self.trace_finish(attempt, false);
+        self.stm.failed(self.bucket, self.priority, e, attempt);
+        self.stm
+            .emit(self.label, Finish::Failed(e), &self.tally, None);
+        span.finish(attempt, sets.0, sets.1);
+        if let Some(o) = &self.observing {
+            o.abort(attempt, sets);
+        }
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl<'s> Report<'s> {
+    #[inline(always)]
+    fn new(_stm: &'s STM, _label: Option<&'static str>, _read_only: bool) -> Report<'s> {
+        Report(PhantomData)
+    }
+
+    #[inline(always)]
+    fn begin(&mut self, _attempt: u32, _priority: TxPriority) {}
+
+    #[inline(always)]
+    fn escalate(&mut self, _attempt: u32) {}
+
+    #[inline(always)]
+    fn attempted(&self, _read_ver: u64) {}
+
+    #[inline(always)]
+    fn mark(&mut self, _phase: Phase) {}
+
+    #[inline(always)]
+    fn commit(&mut self, _span: &TxSpan, _attempt: u32, _sets: (usize, usize)) {}
+
+    #[inline(always)]
+    fn restart(&mut self, _span: &TxSpan, _c: Conflict, _attempt: u32, _sets: (usize, usize)) {}
+
+    #[inline(always)]
+    fn fail(&mut self, _span: &TxSpan, _e: TxError, _attempt: u32, _sets: (usize, usize)) {}
+}
+
+// Slots for write attempts, see `STMBuilder::max_writers`.
+struct WriterSlots {
+    limit: usize,
+    used: AtomicUsize,
+}
+
+impl WriterSlots {
+    fn new(limit: usize) -> WriterSlots {
+        WriterSlots {
+            limit,
+            used: AtomicUsize::new(0),
+        }
+    }
+
+    fn acquire(&self, relax: &dyn Relax) -> WriterSlot<'_> {
+        loop {
+            if let Some(slot) = self.try_acquire() {
+                return slot;
+            }
+            relax.relax();
+        }
+    }
+
+    // A slot, unless all of them are taken.
+    fn try_acquire(&self) -> Option<WriterSlot<'_>> {
+        let mut n = self.used.load(Ordering::Relaxed);
+        while n < self.limit {
+            match self
+                .used
+                .compare_exchange_weak(n, n + 1, Ordering::Acquire, Ordering::Relaxed)
+            {
+                Ok(_) => return Some(WriterSlot { slots: self }),
+                Err(now) => n = now,
+            }
+        }
+        None
+    }
+}
+
+// A slot taken from `WriterSlots`, given back when dropped.
+struct WriterSlot<'a> {
+    slots: &'a WriterSlots,
+}
+
+impl<'a> Drop for WriterSlot<'a> {
+    fn drop(&mut self) {
+        self.slots.used.fetch_sub(1, Ordering::Release);
+    }
+}
+
+// A write transaction across its attempts, shared by the blocking and
+// async loops.
+pub(crate) struct WriteRun<'s> {
+    stm: &'s STM,
+    label: Option<&'static str>,
+    span: TxSpan,
+    report: Report<'s>,
+    pub(crate) attempt: u32,
+    pub(crate) prio: u8,
+    pub(crate) priority: TxPriority,
+    version: u64,       // of the commit, once made
+    start: Option<u64>, // read version of the first attempt
+    work: u64,          // stripes loaded by the attempts so far
+    pub(crate) escalate_after: Option<u32>,
+    // Never spin or park: whatever a run would wait for, a writer slot, a
+    // turn, the serialization token, a lock or paused commits, makes it
+    // return `Step::Restart` instead, so an async caller can yield. What it
+    // holds is let go of on every restart, as others on the same thread
+    // could not run to take it while the caller yields.
+    pub(crate) nonblocking: bool,
+    held: Held<'s>,
+}
+
+// What a write transaction holds across its attempts to keep others from
+// committing.
+#[derive(Default)]
+struct Held<'s> {
+    serial: Option<Serialized<'s>>,
+    #[cfg(feature = "std")]
+    ticket: Option<Ticket<'s>>, // the turn of a serialized writer
+    urgent: Option<Urgent<'s>>,
+}
+
+impl<'s> Held<'s> {
+    fn release(&mut self) {
+        *self = Held::default();
+    }
+
+    // For a nonblocking run that could not start.
+    fn busy<R>(&mut self) -> Step<R> {
+        self.release();
+        Step::Restart
+    }
+
+    // Whether it holds anything another transaction could wait for.
+    fn is_empty(&self) -> bool {
+        #[cfg(feature = "std")]
+        let ticket = self.ticket.is_some();
+        #[cfg(not(feature = "std"))]
+        let ticket = false;
+        self.serial.is_none() && !ticket
+    }
+}
+
+impl<'s> WriteRun<'s> {
+    pub(crate) fn new(stm: &'s STM, label: Option<&'static str>) -> WriteRun<'s> {
+        WriteRun {
+            stm,
+            label,
+            span: TxSpan::write(label),
+            report: Report::new(stm, label, false),
+            attempt: 0,
+            prio: 0,
+            priority: TxPriority::Normal,
+            version: 0,
+            start: None,
+            work: 0,
+            escalate_after: stm.escalate_after,
+            nonblocking: false,
+            held: Held::default(),
+        }
+    }
+
+    // `wait` for something, or in a nonblocking run only `try` to get it.
+    fn take<T, W, G>(&self, try_: G, wait: W) -> Option<T>
+    where
+        G: FnOnce() -> Option<T>,
+        W: FnOnce() -> T,
+    {
+        if self.nonblocking {
+            try_()
+        } else {
+            Some(wait())
+        }
+    }
+
+    // Run the body once and report how it ended. With `wait`, a `Retry`
+    // without a conflict is left to the caller instead of failing. A
+    // nonblocking run that cannot start returns `Step::Restart` without
+    // counting an attempt.
+    pub(crate) fn step<F, R>(&mut self, f: &F, wait: bool) -> Step<R>
+    where
+        F: Fn(&mut WriteTrans) -> STMResult<R>,
+    {
+        let stm = self.stm;
+        let _entered = self.span.enter();
+        let attempt = self.attempt.saturating_add(1);
+        self.report.begin(attempt, self.priority);
+        match self.priority {
+            TxPriority::High if attempt > 1 && self.held.urgent.is_none() => {
+                self.held.urgent = Some(stm.mem.urge());
+            }
+            TxPriority::Low if self.held.is_empty() => {
+                if self.nonblocking && stm.mem.is_urgent() {
+                    return self.held.busy();
+                }
+                stm.mem.yield_to_urgent();
+            }
+            _ => (),
+        }
+        // a ticket before the token: a writer holding the token never waits
+        // for a turn, as the one whose turn it is could not commit
+        #[cfg(feature = "std")]
+        if let Some(b) = &stm.breaker {
+            if self.held.ticket.is_none() && self.held.serial.is_none() && b.is_serialized() {
+                match self.take(|| b.try_ticket(), || b.ticket(&*stm.mem.relax)) {
+                    Some(t) => self.held.ticket = Some(t),
+                    None => return self.held.busy(),
+                }
+            }
+        }
+        if self.held.serial.is_none() && self.escalate_after.is_some_and(|k| attempt > k) {
+            match self.take(|| stm.mem.try_serialize(), || stm.mem.serialize()) {
+                Some(s) => self.held.serial = Some(s),
+                None => return self.held.busy(),
+            }
+            self.report.escalate(attempt);
+        }
+        let slot = match &stm.writers {
+            Some(w) => match self.take(|| w.try_acquire(), || w.acquire(&*stm.mem.relax)) {
+                None => return self.held.busy(),
+                slot => slot,
+            },
+            None => None,
+        };
+
+        self.attempt = attempt;
+        let mut tr = WriteTrans::new(&stm.mem, stm.max_read_set, self.prio);
+        tr.attempt = attempt;
+        tr.start = *self.start.get_or_insert(tr.read_ver);
+        tr.work = self.work;
+        tr.serial = self.held.serial.is_some();
+        tr.nonblocking = self.nonblocking;
+        tr.priority = self.priority;
+        let outcome = stm.write_attempt(&mut tr, f, &mut self.report);
+        let sets = (tr.read_set.len(), tr.write_set.len());
+        self.work += sets.0 as u64;
+        #[cfg(feature = "trace")]
+        self.report
+            .trace_sets(tr.read_set.iter(), tr.write_set.keys());
+        let (read_ver, version) = (tr.read_ver, tr.version);
+        drop(tr); // release the locks before reporting
+        drop(slot);
+        self.report.attempted(read_ver);
+
+        #[cfg(feature = "std")]
+        if let Some(b) = &stm.breaker {
+            match outcome {
+                Outcome::Commit(_) => b.record(true),
+                Outcome::Restart(_) => b.record(false),
+                Outcome::Fail(_) => (),
+            }
+        }
+
+        match outcome {
+            Outcome::Commit(val) => {
+                self.held.release();
+                self.version = version;
+                self.report.commit(&self.span, attempt, sets);
+                Step::Done(Ok(val))
+            }
+            Outcome::Restart(c) => {
+                if self.nonblocking {
+                    self.held.release();
+                }
+                stm.check_corruption(c);
+                self.report.restart(&self.span, c, attempt, sets);
+                Step::Restart
+            }
+            Outcome::Fail(TxError::Retry) if wait => {
+                // let the commit it waits for happen
+                self.held.release();
+                Step::Wait
+            }
+            Outcome::Fail(e) => {
+                self.held.release();
+                self.report.fail(&self.span, e, attempt, sets);
+                Step::Done(Err(TxFailure {
+                    error: e,
+                    label: self.label,
+                    attempts: attempt,
+                    read_set: sets.0,
+                    write_set: sets.1,
+                }))
+            }
+        }
+    }
+}
+
+pub struct STM {
+    mem: Memory,
+    read_fallback_after: usize,
+    max_read_set: usize,
+    spin_limit: Option<u32>,
+    escalate_after: Option<u32>,
+    writers: Option<WriterSlots>,
+    poison_on_corruption: bool,
+    poisoned: AtomicBool,
+    #[cfg(feature = "std")]
+    threads: Registry,
+    #[cfg(feature = "std")]
+    stats: Option<Stats>,
+    #[cfg(feature = "std")]
+    track_mode: bool,
+    #[cfg(feature = "std")]
+    latency: Option<Latency>,
+    #[cfg(feature = "std")]
+    heatmap: Option<Heatmaps>,
+    #[cfg(feature = "std")]
+    labels: Labels,
+    #[cfg(feature = "std")]
+    watchdog: Option<Watchdog>,
+    #[cfg(feature = "std")]
+    events: Option<Events>,
+    #[cfg(feature = "std")]
+    feed: Feed,
+    #[cfg(feature = "std")]
+    breaker: Option<Breaker>,
+    #[cfg(feature = "history")]
+    history: Option<CommitHistory>,
+    #[cfg(feature = "std")]
+    replica: Mutex<Replica>,
+    #[cfg(feature = "tokio")]
+    wakeup: Wakeup,
+    #[cfg(feature = "metrics")]
+    emitter: Option<Emitter>,
+    #[cfg(feature = "std")]
+    journal: Option<Journal>,
+    #[cfg(feature = "commit-log")]
+    sink: Option<Arc<dyn CommitSink>>,
+    #[cfg(feature = "mmap")]
+    durable: bool,
+    #[cfg(feature = "std")]
+    observer: RwLock<Option<Arc<dyn TxObserver>>>,
+    #[cfg(feature = "std")]
+    has_observer: AtomicBool,
+    #[cfg(feature = "trace")]
+    recorder: Option<Arc<TraceRecorder>>,
+    #[cfg(feature = "testing")]
+    injected: Mutex<Option<Injected>>,
+    #[cfg(feature = "testing")]
+    scheduler: Option<Arc<dyn Scheduler>>,
+    #[cfg(feature = "testing")]
+    faults: Option<Arc<FaultInjector>>,
+}
+
+// STM is shared between threads through `Arc<STM>`. Memory holds nothing but
+// atomics (bytes, lock/version words and the clock), so STM is Send and Sync;
+// every concurrent access goes through the lock/version protocol. Keep it
+// that way: a field that breaks this must come with an explicit `unsafe impl`
+// and the argument for it.
+//
+// The one such field is `Memory::mem`, a raw pointer to the bytes of
+// `Memory::storage`, along with `Locks::Shared`, one to the lock words next
+// to them. They are only turned into slices of atomics, which are Send and
+// Sync, and the storage they point into is Send and Sync and owned by the
+// same Memory.
+unsafe impl Send for Memory {}
+unsafe impl Sync for Memory {}
+
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<STM>();
+    assert_send_sync::<Memory>();
+};
+
+impl Default for STM {
+    fn default() -> Self {
+        STM::new()
+    }
+}
+
+pub struct STMBuilder {
+    capacity: usize,
+    read_fallback_after: usize,
+    max_read_set: usize,
+    spin_limit: Option<u32>,
+    escalate_after: Option<u32>,
+    max_writers: Option<usize>,
+    poison_on_corruption: bool,
+    #[cfg(feature = "std")]
+    stats: bool,
+    #[cfg(feature = "std")]
+    track_mode: bool,
+    #[cfg(feature = "std")]
+    latency_sample_every: u32,
+    #[cfg(feature = "std")]
+    heatmap: bool,
+    #[cfg(feature = "std")]
+    watchdog: bool,
+    #[cfg(feature = "std")]
+    event_ring: usize,
+    #[cfg(feature = "std")]
+    feed_buffer: usize,
+    #[cfg(feature = "std")]
+    breaker: Option<f64>,
+    #[cfg(feature = "history")]
+    history: usize,
+    #[cfg(feature = "metrics")]
+    metrics_prefix: Option<String>,
+    storage: Option<Box<dyn Storage>>,
+    locks: Option<Locks>,
+    owner: u64,
+    #[cfg(feature = "pmem")]
+    pmem: Option<Arc<Pmem>>,
+    contention: Option<Arc<dyn ContentionManager>>,
+    clock: Option<Arc<dyn Clock>>,
+    relax: Option<Arc<dyn Relax>>,
+    wait: Option<Arc<dyn WaitStrategy>>,
+    #[cfg(feature = "std")]
+    journal: Option<Journal>,
+    #[cfg(feature = "commit-log")]
+    sink: Option<Arc<dyn CommitSink>>,
+    #[cfg(feature = "mmap")]
+    durable: bool,
+    #[cfg(feature = "testing")]
+    scheduler: Option<Arc<dyn Scheduler>>,
+    #[cfg(feature = "testing")]
+    faults: Option<Arc<FaultInjector>>,
+    #[cfg(feature = "trace")]
+    recorder: Option<Arc<TraceRecorder>>,
+}
+
+impl Default for STMBuilder {
+    fn default() -> Self {
+        STMBuilder::new()
+    }
+}
+
+impl STMBuilder {
+    pub fn new() -> STMBuilder {
+        STMBuilder {
+            capacity: MEM_SIZE,
+            read_fallback_after: READ_FALLBACK_AFTER,
+            max_read_set: usize::MAX,
+            spin_limit: None,
+            escalate_after: None,
+            max_writers: None,
+            poison_on_corruption: false,
+            #[cfg(feature = "std")]
+            stats: false,
+            #[cfg(feature = "std")]
+            track_mode: false,
+            #[cfg(feature = "std")]
+            latency_sample_every: LATENCY_SAMPLE_EVERY,
+            #[cfg(feature = "std")]
+            heatmap: false,
+            #[cfg(feature = "std")]
+            watchdog: false,
+            #[cfg(feature = "std")]
+            event_ring: EVENT_RING,
+            #[cfg(feature = "std")]
+            feed_buffer: FEED_BUFFER,
+            #[cfg(feature = "std")]
+            breaker: None,
+            #[cfg(feature = "history")]
+            history: 0,
+            #[cfg(feature = "metrics")]
+            metrics_prefix: None,
+            storage: None,
+            locks: None,
+            owner: 0,
+            #[cfg(feature = "pmem")]
+            pmem: None,
+            contention: None,
+            clock: None,
+            relax: None,
+            wait: None,
+            #[cfg(feature = "std")]
+            journal: None,
+            #[cfg(feature = "commit-log")]
+            sink: None,
+            #[cfg(feature = "mmap")]
+            durable: false,
+            #[cfg(feature = "testing")]
+            scheduler: None,
+            #[cfg(feature = "testing")]
+            faults: None,
+            #[cfg(feature = "trace")]
+            recorder: None,
+        }
+    }
+
+    /// Size of the transactional memory in bytes, a multiple of the stripe
+    /// size (8). Defaults to 512.
+    pub fn capacity(mut self, bytes: usize) -> STMBuilder {
+        self.capacity = bytes;
+        self
+    }
+
+    /// Number of restarts after which `read_transaction` pauses commits and
+    /// reads pessimistically, so heavy write traffic cannot starve it.
+    pub fn read_fallback_after(mut self, restarts: usize) -> STMBuilder {
+        self.read_fallback_after = restarts;
+        self
+    }
+
+    /// Largest number of distinct stripes a write transaction may load.
+    /// Going past it fails the transaction with `TxError::ReadSetTooLarge`
+    /// instead of letting a runaway body grow its read-set without bound.
+    pub fn max_read_set(mut self, stripes: usize) -> STMBuilder {
+        self.max_read_set = stripes;
+        self
+    }
+
+    /// Poison the STM when a conflict turns out to come from a stripe
+    /// whose version is ahead of the clock, which no commit leaves behind:
+    /// the memory is corrupt, and every later transaction fails with
+    /// `TxError::Poisoned` instead of restarting on it forever. Off by
+    /// default.
+    pub fn poison_on_corruption(mut self, enable: bool) -> STMBuilder {
+        self.poison_on_corruption = enable;
+        self
+    }
+
+    /// Back off before restarting a write transaction after a conflict:
+    /// spin `2^n` times before the `n`-th restart while `n <= restarts`,
+    /// after that sleep `2^(n - restarts - 1)` µs (without `std`, call
+    /// `Relax::relax` instead). Spinning rides out short conflicts cheaply
+    /// and sleeping keeps long ones from burning a core. Both are capped at
+    /// 2^10. By default a transaction restarts right away.
+    pub fn spin_limit(mut self, restarts: u32) -> STMBuilder {
+        self.spin_limit = Some(restarts);
+        self
+    }
+
+    /// After `attempts` failed attempts, run a write transaction
+    /// serialized: it takes a token only one transaction holds at a time
+    /// and pauses the commits of every other writer until it finishes, so
+    /// its next attempt cannot lose a conflict. Read transactions keep
+    /// running. Escalations are counted in `StatsSnapshot::escalations`
+    /// and reported to `TxObserver::on_escalate`. Off by default; see
+    /// `STM::write_transaction_escalating` to set it per call.
+    pub fn escalate_after(mut self, attempts: u32) -> STMBuilder {
+        self.escalate_after = Some(attempts);
+        self
+    }
+
+    /// Keep the memory in `storage` instead of a zeroed heap buffer, e.g. a
+    /// mapped file. Its length, a multiple of the stripe size, replaces
+    /// `capacity`, and its current contents are the initial memory.
+    pub fn storage<S: Storage + 'static>(mut self, storage: S) -> STMBuilder {
+        self.capacity = storage.len();
+        self.storage = Some(Box::new(storage));
+        self
+    }
+
+    // Lock words kept outside the memory, and the tag this process puts
+    // in the ones it locks, see `STM::create_shared`.
+    #[cfg(all(unix, feature = "shm"))]
+    pub(crate) fn shared(mut self, locks: Locks, owner: u64) -> STMBuilder {
+        self.locks = Some(locks);
+        self.owner = owner;
+        self
+    }
+
+    /// Run at most `k` write transactions at a time; more callers wait
+    /// for a slot with `Relax::relax`. Under heavy contention fewer
+    /// concurrent writers abort each other less often. A slot is held
+    /// for one attempt, not across restarts or a blocking retry's wait.
+    /// Unlimited by default.
+    pub fn max_writers(mut self, k: usize) -> STMBuilder {
+        assert!(k > 0, "max_writers must be at least 1");
+        self.max_writers = Some(k);
+        self
+    }
+
+    /// Let `cm` decide what a committing write transaction does when
+    /// another holds a stripe lock it needs, see `ContentionManager`.
+    /// Without one, the transaction restarts unless it has a higher
+    /// `write_transaction_prio` priority than the holder.
+    pub fn contention_manager(mut self, cm: Arc<dyn ContentionManager>) -> STMBuilder {
+        self.contention = Some(cm);
+        self
+    }
+
+    /// Keep the memory in `storage` and make every commit durable there
+    /// before it is published, so a crash loses no committed transaction
+    /// and leaves none half-applied once `STM::recover_pmem` ran. Call it
+    /// before the first transaction.
+    #[cfg(feature = "pmem")]
+    pub fn pmem(mut self, storage: PmemStorage) -> STMBuilder {
+        self.pmem = Some(storage.pmem());
+        self.storage(storage)
+    }
+
+    /// Stamp commits with `clock` instead of a private `AtomicClock`, e.g.
+    /// to share a logical clock with another system.
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> STMBuilder {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Wait with `relax` instead of the default when a commit and a
+    /// pessimistic read have to wait for each other, see `Relax`.
+    pub fn relax(mut self, relax: Arc<dyn Relax>) -> STMBuilder {
+        self.relax = Some(relax);
+        self
+    }
+
+    /// Block with `strategy` instead of the default when waiting for a
+    /// write transaction to finish, see `WaitStrategy`.
+    pub fn wait_strategy(mut self, strategy: Arc<dyn WaitStrategy>) -> STMBuilder {
+        self.wait = Some(strategy);
+        self
+    }
+
+    pub fn build(self) -> STM {
+        #[cfg(feature = "metrics")]
+        let timed = self.stats || self.metrics_prefix.is_some();
+        #[cfg(all(feature = "std", not(feature = "metrics")))]
+        let timed = self.stats;
+        let capacity = self.capacity;
+        let mut mem = Memory::with_parts(
+            self.storage
+                .unwrap_or_else(|| Box::new(HeapStorage::new(capacity))),
+            self.locks,
+            self.clock.unwrap_or_else(|| Arc::new(AtomicClock::new())),
+            self.relax.unwrap_or_else(relax::default),
+            self.wait.unwrap_or_else(wait::default),
+        );
+        mem.owner = self.owner;
+        mem.contention = self.contention;
+        #[cfg(feature = "pmem")]
+        {
+            mem.pmem = self.pmem;
+        }
+
+        STM {
+            mem,
+            read_fallback_after: self.read_fallback_after,
+            max_read_set: self.max_read_set,
+            spin_limit: self.spin_limit,
+            escalate_after: self.escalate_after,
+            writers: self.max_writers.map(WriterSlots::new),
+            poison_on_corruption: self.poison_on_corruption,
+            poisoned: AtomicBool::new(false),
+            #[cfg(feature = "std")]
+            threads: Registry::new(),
+            #[cfg(feature = "std")]
+            stats: if self.stats { Some(Stats::new()) } else { None },
+            #[cfg(feature = "std")]
+            track_mode: self.track_mode,
+            #[cfg(feature = "std")]
+            latency: if timed && self.latency_sample_every > 0 {
+                Some(Latency::new(self.latency_sample_every))
+            } else {
+                None
+            },
+            #[cfg(feature = "std")]
+            heatmap: if self.heatmap {
+                Some(Heatmaps::new(self.capacity / STRIPE_SIZE))
+            } else {
+                None
+            },
+            #[cfg(feature = "std")]
+            labels: Labels::new(),
+            #[cfg(feature = "std")]
+            watchdog: if self.watchdog {
+                Some(Watchdog::new())
+            } else {
+                None
+            },
+            #[cfg(feature = "std")]
+            events: if self.event_ring > 0 {
+                Some(Events::new(self.event_ring))
+            } else {
+                None
+            },
+            #[cfg(feature = "std")]
+            feed: Feed::new(self.feed_buffer),
+            #[cfg(feature = "std")]
+            breaker: self.breaker.map(Breaker::new),
+            #[cfg(feature = "history")]
+            history: if self.history > 0 {
+                Some(CommitHistory::new(self.history))
+            } else {
+                None
+            },
+            #[cfg(feature = "std")]
+            replica: Replica::new(),
+            #[cfg(feature = "tokio")]
+            wakeup: Wakeup::new(),
+            #[cfg(feature = "metrics")]
+            emitter: self.metrics_prefix.as_deref().map(Emitter::new),
+            #[cfg(feature = "std")]
+            journal: self.journal,
+            #[cfg(feature = "commit-log")]
+            sink: self.sink,
+            #[cfg(feature = "mmap")]
+            durable: self.durable,
+            #[cfg(feature = "std")]
+            observer: RwLock::new(None),
+            #[cfg(feature = "std")]
+            has_observer: AtomicBool::new(false),
+            #[cfg(feature = "trace")]
+            recorder: self.recorder,
+            #[cfg(feature = "testing")]
+            injected: Mutex::new(None),
+            #[cfg(feature = "testing")]
+            scheduler: self.scheduler,
+            #[cfg(feature = "testing")]
+            faults: self.faults,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl STMBuilder {
+    /// Count commits, aborts, retries and restarts, see `STM::stats`.
+    /// When disabled (the default) no counter is touched.
+    pub fn stats(mut self, enable: bool) -> STMBuilder {
+        self.stats = enable;
+        self
+    }
+
+    /// Remember per thread whether its recent transactions wrote anything,
+    /// see `STM::suggest_mode`. Off by default.
+    pub fn track_mode(mut self, enable: bool) -> STMBuilder {
+        self.track_mode = enable;
+        self
+    }
+
+    /// With stats enabled, time every `n`-th write transaction of each
+    /// thread per phase, see `STM::latency_histograms`. Defaults to 64;
+    /// 0 turns timing off.
+    pub fn latency_sample_every(mut self, n: u32) -> STMBuilder {
+        self.latency_sample_every = n;
+        self
+    }
+
+    /// Count per stripe how often it made a transaction restart, see
+    /// `STM::conflict_heatmap`.
+    pub fn conflict_heatmap(mut self, enable: bool) -> STMBuilder {
+        self.heatmap = enable;
+        self
+    }
+
+    /// Record the transactions `recorder` samples, to replay them later
+    /// with a `Replayer`.
+    #[cfg(feature = "trace")]
+    pub fn trace_recorder(mut self, recorder: Arc<TraceRecorder>) -> STMBuilder {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Track running transactions so `STM::check_stalled` can report the
+    /// ones that take too long.
+    pub fn watchdog(mut self, enable: bool) -> STMBuilder {
+        self.watchdog = enable;
+        self
+    }
+
+    /// Keep the last `n` restarts and failures, see `STM::recent_events`.
+    /// Defaults to 1024; 0 turns the ring off.
+    pub fn event_ring(mut self, n: usize) -> STMBuilder {
+        self.event_ring = n;
+        self
+    }
+
+    /// Records each `STM::subscribe` receiver buffers before further ones
+    /// are dropped. Defaults to 1024.
+    pub fn feed_buffer(mut self, n: usize) -> STMBuilder {
+        self.feed_buffer = n;
+        self
+    }
+
+    /// Serialize write transactions while they restart more than
+    /// `threshold` times per commit. The ratio is taken over each window
+    /// of 256 write attempts; one above it switches to serialized mode,
+    /// where write transactions take turns through a ticket lock before
+    /// every attempt, and one at or below it switches back. Attempts
+    /// already running when the mode changes finish as they started,
+    /// validated as usual, so both modes commit the same way. Read
+    /// transactions are never serialized. See `STM::breaker_stats`.
+    pub fn circuit_breaker(mut self, threshold: f64) -> STMBuilder {
+        self.breaker = Some(threshold);
+        self
+    }
+
+    /// Keep the last `n` commits that wrote something, with their
+    /// write-sets, see `STM::recent_commits`. Every such commit then
+    /// copies its write-set under a lock. Defaults to 0, off. Needs the
+    /// `history` feature.
+    #[cfg(feature = "history")]
+    pub fn commit_history(mut self, n: usize) -> STMBuilder {
+        self.history = n;
+        self
+    }
+
+    /// Emit counters and phase latency histograms through the `metrics`
+    /// facade, named `<prefix>_commits_total` and so on and labeled with the
+    /// transaction label. Each transaction emits once, when it finishes.
+    #[cfg(feature = "metrics")]
+    pub fn metrics_prefix(mut self, prefix: &str) -> STMBuilder {
+        self.metrics_prefix = Some(prefix.to_string());
+        self
+    }
+
+    /// Record every committed write-set in `journal`.
+    pub fn journal(mut self, journal: Journal) -> STMBuilder {
+        self.journal = Some(journal);
+        self
+    }
+
+    // Mark the STM as opened by `open_durable`, for `STM::checkpoint`.
+    #[cfg(feature = "mmap")]
+    pub(crate) fn durable(mut self) -> STMBuilder {
+        self.durable = true;
+        self
+    }
+
+    /// Report every write transaction's `YieldPoint`s to `scheduler`, to
+    /// script an interleaving in a test, e.g. with `Script`. Needs the
+    /// `testing` feature.
+    #[cfg(feature = "testing")]
+    pub fn scheduler(mut self, scheduler: Arc<dyn Scheduler>) -> STMBuilder {
+        self.scheduler = Some(scheduler);
+        self
+    }
+
+    /// Inject the faults of `faults` into write transactions, to test how
+    /// the code running them copes. Needs the `testing` feature.
+    #[cfg(feature = "testing")]
+    pub fn fault_injector(mut self, faults: Arc<FaultInjector>) -> STMBuilder {
+        self.faults = Some(faults);
+        self
+    }
+
+    #[cfg(feature = "mmap")]
+    pub(crate) fn configured_capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Pass every committed write-set to `sink` once it is published.
+    #[cfg(feature = "commit-log")]
+    pub fn commit_sink(mut self, sink: Arc<dyn CommitSink>) -> STMBuilder {
+        self.sink = Some(sink);
+        self
+    }
+}
+
+impl STM {
+    pub fn new() -> STM {
+        STMBuilder::new().build()
+    }
+
+    pub fn builder() -> STMBuilder {
+        STMBuilder::new()
+    }
+
+    /// Size of the transactional memory in bytes.
+    pub fn capacity(&self) -> usize {
+        self.mem.capacity()
+    }
+
+    /// The memory this STM takes: its bytes, their lock/version words and
+    /// fixed overhead.
+    pub fn footprint(&self) -> Footprint {
+        self.mem.footprint()
+    }
+
+    /// Whether no transaction is running and no stripe lock is held.
+    ///
+    /// This is a racy snapshot: a transaction may start right after it
+    /// returns `true`, so it only suits opportunistic maintenance that can
+    /// cope with that.
+    pub fn is_quiescent(&self) -> bool {
+        self.mem.active.load(Ordering::Acquire) == 0
+            && self.mem.committing.load(Ordering::Acquire) == 0
+    }
+
+    /// The current value of the global version-clock. With a
+    /// `Durability::Sync` journal, it stops short of any commit whose
+    /// record is not on the disk yet.
+    pub fn current_version(&self) -> u64 {
+        #[cfg(feature = "std")]
+        if let Some(j) = &self.journal {
+            return j.visible(|| self.mem.clock.sample());
+        }
+        self.mem.clock.sample()
+    }
+
+    /// Number of threads registered to wait, out a blocking `Retry` or
+    /// for a lock held by a lower priority. A racy snapshot; once every
+    /// transaction has returned it must be 0.
+    pub fn waiters(&self) -> usize {
+        self.mem.waits.waiting()
+    }
+
+    /// Stop the STM for good, e.g. when a check of the structures in it
+    /// finds them broken: every transaction starting afterwards fails with
+    /// `TxError::Poisoned` without running its body. Transactions already
+    /// running finish their attempt.
+    pub fn poison(&self) {
+        self.poisoned.store(true, Ordering::Release);
+    }
+
+    /// Whether `poison` was called or, with
+    /// `STMBuilder::poison_on_corruption`, corruption was found.
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned.load(Ordering::Acquire)
+    }
+
+    // A restart on `c`: poison the STM if it came from a corrupt stripe.
+    fn check_corruption(&self, c: Conflict) {
+        if !self.poison_on_corruption {
+            return;
+        }
+        let stale = matches!(
+            c.cause,
+            ConflictCause::PreValidation | ConflictCause::Validation
+        );
+        if let (true, Some(addr)) = (stale, c.addr) {
+            if self.mem.version_ahead(addr) {
+                self.poison();
+            }
+        }
+    }
+
+    /// Same as `current_version`.
+    pub fn global_clock(&self) -> u64 {
+        self.current_version()
+    }
+
+    /// The global clock and the time now; see `ClockSample::since`.
+    #[cfg(feature = "std")]
+    pub fn clock_sample(&self) -> ClockSample {
+        ClockSample {
+            clock: self.current_version(),
+            at: Instant::now(),
+        }
+    }
+
+    /// Addresses of the stripes whose lock bit is set, a debugging aid.
+    /// Locks are only held while a write transaction commits, so once every
+    /// transaction has returned this should be empty. Racy while
+    /// transactions run.
+    pub fn locked_stripes(&self) -> Vec<usize> {
+        self.mem.locked_stripes()
+    }
+
+    /// Threads that ran a transaction on this STM and have not exited, a
+    /// debugging aid: an exited thread's per-thread state is folded into
+    /// the totals and dropped.
+    #[cfg(feature = "std")]
+    pub fn live_threads(&self) -> usize {
+        self.threads.live()
+    }
+
+    // The latest committed bytes at `addr`, read outside any transaction.
+    pub(crate) fn load_latest(&self, addr: usize) -> [u8; STRIPE_SIZE] {
+        self.mem.load_latest(addr)
+    }
+
+    pub fn write_transaction<F, R>(&self, f: F) -> Option<R>
+    where
+        F: Fn(&mut WriteTrans) -> STMResult<R>,
+    {
+        self.try_write_transaction(f).ok()
+    }
+
+    /// Like `write_transaction`, tagging stats, heatmaps, `tracing` spans
+    /// and observer events with `label`.
+    pub fn write_transaction_labeled<F, R>(&self, label: &'static str, f: F) -> Option<R>
+    where
+        F: Fn(&mut WriteTrans) -> STMResult<R>,
+    {
+        self.write_loop(Some(label), 0, f, |_| {}).ok()
+    }
+
+    /// Like `write_transaction`, but reports why no result was produced.
+    pub fn try_write_transaction<F, R>(&self, f: F) -> Result<R, TxError>
+    where
+        F: Fn(&mut WriteTrans) -> STMResult<R>,
+    {
+        self.write_loop(None, 0, f, |_| {}).map_err(|e| e.error)
+    }
+
+    /// Like `try_write_transaction`, but the error also tells how many
+    /// attempts were made and how large the sets of the final one were.
+    pub fn try_write_transaction_detailed<F, R>(&self, f: F) -> Result<R, TxFailure>
+    where
+        F: Fn(&mut WriteTrans) -> STMResult<R>,
+    {
+        self.write_loop(None, 0, f, |_| {})
+    }
+
+    /// Like `write_transaction`, but calls `on_retry` with the attempt number
+    /// (starting at 1) every time a conflict restarts the transaction, so the
+    /// caller decides whether to spin, yield or sleep before the next run.
+    pub fn write_transaction_poll<F, P, R>(&self, f: F, on_retry: P) -> Option<R>
+    where
+        F: Fn(&mut WriteTrans) -> STMResult<R>,
+        P: FnMut(u32),
+    {
+        self.write_loop(None, 0, f, on_retry).ok()
+    }
+
+    /// Like `write_transaction`, but on lock contention a transaction of
+    /// higher `priority` wounds the holder of the lock and waits for it
+    /// instead of restarting: the holder restarts unless it is already
+    /// writing its commit. Equal or lower priorities restart as usual, and
+    /// `write_transaction` runs at priority 0.
+    pub fn write_transaction_prio<F, R>(&self, priority: u8, f: F) -> Option<R>
+    where
+        F: Fn(&mut WriteTrans) -> STMResult<R>,
+    {
+        self.write_loop(None, priority, f, |_| {}).ok()
+    }
+
+    /// Like `write_transaction`, with the `TxPriority` hint `priority`
+    /// for the backoff between attempts, the spinning on held locks, the
+    /// contention manager, stats and the watchdog.
+    pub fn write_transaction_with_priority<F, R>(&self, priority: TxPriority, f: F) -> Option<R>
+    where
+        F: Fn(&mut WriteTrans) -> STMResult<R>,
+    {
+        let mut run = WriteRun::new(self, None);
+        run.priority = priority;
+        self.run_loop(&mut run, f, |_| {}).ok()
+    }
+
+    /// Like `write_transaction`, but escalating to run serialized after
+    /// `attempts` failed attempts, whatever `STMBuilder::escalate_after`
+    /// says.
+    pub fn write_transaction_escalating<F, R>(&self, attempts: u32, f: F) -> Option<R>
+    where
+        F: Fn(&mut WriteTrans) -> STMResult<R>,
+    {
+        let mut run = WriteRun::new(self, None);
+        run.escalate_after = Some(attempts);
+        self.run_loop(&mut run, f, |_| {}).ok()
+    }
+
+    /// Like `write_transaction`, also returning the version the commit was
+    /// stamped with. Every commit gets its own version, and the order of
+    /// the versions is the order the commits serialize in.
+    pub fn write_transaction_with_version<F, R>(&self, f: F) -> Option<(R, u64)>
+    where
+        F: Fn(&mut WriteTrans) -> STMResult<R>,
+    {
+        let mut run = WriteRun::new(self, None);
+        let val = self.run_loop(&mut run, f, |_| {}).ok()?;
+        Some((val, run.version))
+    }
+
+    /// Like `write_transaction`, also timing every attempt of this call:
+    /// its phases, set sizes and how it ended, with the conflict that
+    /// restarted it. Other transactions are not timed.
+    #[cfg(feature = "std")]
+    pub fn profile_write_transaction<F, R>(&self, f: F) -> (Option<R>, TxProfile)
+    where
+        F: Fn(&mut WriteTrans) -> STMResult<R>,
+    {
+        let mut run = WriteRun::new(self, None);
+        run.report.profiler = Some(Profiler::new());
+        let val = self.run_loop(&mut run, f, |_| {}).ok();
+        let profile = run.report.profiler.take().unwrap().finish();
+        (val, profile)
+    }
+
+    /// Store every (address, bytes) pair of `batch` in one write
+    /// transaction and return the version it committed at. The batch only
+    /// writes, so nothing is validated; a later pair for the same address
+    /// wins. `None` if the commit failed, e.g. with a journal error.
+    ///
+    /// # Panics
+    ///
+    /// If an address is not stripe-aligned or out of bounds.
+    pub fn apply_batch(&self, batch: &[(usize, [u8; STRIPE_SIZE])]) -> Option<u64> {
+        for (addr, _) in batch {
+            check_aligned(*addr);
+            assert!(*addr < self.mem.capacity(), "address out of bounds");
+        }
+        let mut run = WriteRun::new(self, None);
+        let body = |tr: &mut WriteTrans| {
+            for (addr, val) in batch {
+                tr.store(*addr, *val);
+            }
+            STMResult::Ok(())
+        };
+        self.run_loop(&mut run, body, |_| {}).ok()?;
+        Some(run.version)
+    }
+
+    /// Replace the little-endian `u64` at `addr` with `f` of it in a write
+    /// transaction of its own, restarting on conflict, and return the value
+    /// committed. `f` runs once per attempt. `None` if the commit failed,
+    /// e.g. on a poisoned STM.
+    pub fn cas_update_u64<F>(&self, addr: usize, f: F) -> Option<u64>
+    where
+        F: Fn(u64) -> u64,
+    {
+        self.write_transaction(|tr| {
+            tr.update_u64(addr, &f)
+                .map_or(STMResult::Retry, STMResult::Ok)
+        })
+    }
+
+    /// Like `write_transaction`, but a body returning `STMResult::Retry`
+    /// without a conflict blocks, with the `WaitStrategy`, until another
+    /// write transaction finishes and then runs again, instead of failing.
+    /// Suits waiting for a condition, like an item in an empty queue.
+    pub fn write_transaction_blocking<F, R>(&self, f: F) -> Option<R>
+    where
+        F: Fn(&mut WriteTrans) -> STMResult<R>,
+    {
+        self.write_blocking(f, || false).and_then(Result::ok)
+    }
+
+    /// Like `write_transaction_blocking`, but gives up with
+    /// `RetryTimeout::TimedOut` if the body still returns `Retry` after
+    /// `timeout`. The time is checked whenever a wait returns, which the
+    /// provided `WaitStrategy`s do at least every millisecond or so.
+    #[cfg(feature = "std")]
+    pub fn write_transaction_retry_timeout<F, R>(
+        &self,
+        timeout: Duration,
+        f: F,
+    ) -> Result<R, RetryTimeout>
+    where
+        F: Fn(&mut WriteTrans) -> STMResult<R>,
+    {
+        let deadline = Instant::now().checked_add(timeout);
+        let expired = || deadline.is_some_and(|d| Instant::now() >= d);
+        match self.write_blocking(f, expired) {
+            Some(r) => r.map_err(RetryTimeout::Failed),
+            None => Err(RetryTimeout::TimedOut),
+        }
+    }
+
+    // Run `f`, waiting out a `Retry` without a conflict until another
+    // write transaction finishes. `None` once `expired` after a wait.
+    fn write_blocking<F, E, R>(&self, f: F, expired: E) -> Option<Result<R, TxError>>
+    where
+        F: Fn(&mut WriteTrans) -> STMResult<R>,
+        E: Fn() -> bool,
+    {
+        let mut run = WriteRun::new(self, None);
+        loop {
+            match run.step(&f, true) {
+                Step::Done(r) => return Some(r.map_err(|e| e.error)),
+                Step::Restart => self.backoff(run.attempt, run.priority),
+                Step::Wait => {
+                    // register before the re-run so that a commit landing
+                    // in between still wakes us
+                    let waiter = self.mem.waits.register();
+                    match run.step(&f, true) {
+                        Step::Done(r) => return Some(r.map_err(|e| e.error)),
+                        Step::Restart => (),
+                        Step::Wait => {
+                            waiter.wait();
+                            if expired() {
+                                return None;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Block until the read transaction `ready` returns `true`, sleeping
+    // with the `WaitStrategy` until the next write commit whenever it
+    // returns `false`. Unlike a blocking write transaction, nothing is
+    // committed, so waiting does not move the clock. `None` if the STM is
+    // poisoned.
+    pub(crate) fn wait_until<F>(&self, ready: F) -> Option<()>
+    where
+        F: Fn(&mut ReadTrans) -> STMResult<bool>,
+    {
+        loop {
+            if self.read_transaction(&ready)? {
+                return Some(());
+            }
+            // register before the re-run so that a commit landing in
+            // between still wakes us
+            let waiter = self.mem.waits.register();
+            if self.read_transaction(&ready)? {
+                return Some(());
+            }
+            waiter.wait();
+        }
+    }
+
+    fn write_loop<F, P, R>(
+        &self,
+        label: Option<&'static str>,
+        prio: u8,
+        f: F,
+        on_retry: P,
+    ) -> Result<R, TxFailure>
+    where
+        F: Fn(&mut WriteTrans) -> STMResult<R>,
+        P: FnMut(u32),
+    {
+        let mut run = WriteRun::new(self, label);
+        run.prio = prio;
+        self.run_loop(&mut run, f, on_retry)
+    }
+
+    fn run_loop<F, P, R>(&self, run: &mut WriteRun, f: F, mut on_retry: P) -> Result<R, TxFailure>
+    where
+        F: Fn(&mut WriteTrans) -> STMResult<R>,
+        P: FnMut(u32),
+    {
+        loop {
+            if run.attempt > 0 {
+                self.backoff(run.attempt, run.priority);
+                on_retry(run.attempt);
+            }
+            if let Step::Done(r) = run.step(&f, false) {
+                return r;
+            }
+        }
+    }
+
+    // Wait before restart number `attempt`, see `STMBuilder::spin_limit`.
+    // `TxPriority::Low` spins for half as many restarts, then sleeps twice
+    // as long, and yields even without a limit.
+    fn backoff(&self, attempt: u32, priority: TxPriority) {
+        // loom only switches threads where it is told to
+        #[cfg(loom)]
+        crate::sync::yield_now();
+        let limit = match (self.spin_limit, priority) {
+            (Some(limit), TxPriority::Low) => limit / 2,
+            (Some(limit), _) => limit,
+            (None, TxPriority::Low) => return self.mem.relax.relax(),
+            (None, _) => return,
+        };
+        if attempt <= limit {
+            for _ in 0..1u32 << attempt.min(MAX_SPIN_SHIFT) {
+                core::hint::spin_loop();
+            }
+            return;
+        }
+        #[cfg(feature = "std")]
+        std::thread::sleep(Duration::from_micros(
+            1 << (attempt - limit - 1 + (priority == TxPriority::Low) as u32).min(MAX_SLEEP_SHIFT),
+        ));
+        #[cfg(not(feature = "std"))]
+        self.mem.relax.relax();
+    }
+
+    fn write_attempt<F, R>(&self, tr: &mut WriteTrans, f: &F, report: &mut Report) -> Outcome<R>
+    where
+        F: Fn(&mut WriteTrans) -> STMResult<R>,
+    {
+        // 0. Refuse to run on a poisoned STM
+        if self.is_poisoned() {
+            return Outcome::Fail(TxError::Poisoned);
+        }
+
+        // 1. Sample global version-clock (done by WriteTrans::new)
+        #[cfg(feature = "testing")]
+        self.reached(YieldPoint::Sampled, report.label);
+
+        // 2. Run through a speculative execution
+        let result = match f(tr) {
+            _ if tr.fail_fast && tr.conflict.is_some() => STMResult::Retry,
+            result => result,
+        };
+        report.mark(Phase::Execute);
+        #[cfg(feature = "testing")]
+        let fault = self.reached(YieldPoint::Executed, report.label);
+        let result = match result {
+            STMResult::Abort => return Outcome::Fail(TxError::Abort),
+            STMResult::Retry => {
+                if let Some(e) = tr.error {
+                    return Outcome::Fail(e);
+                }
+                if let Some(c) = tr.conflict.or_else(|| tr.stale.get()) {
+                    return Outcome::Restart(c);
+                }
+                return Outcome::Fail(TxError::Retry);
+            }
+            STMResult::Ok(val) => val,
+        };
+
+        // 2''. Inject a fault, see `STMBuilder::fault_injector`
+        #[cfg(feature = "testing")]
+        match fault {
+            Some(Fault::Restart) => {
+                return Outcome::Restart(Conflict {
+                    cause: ConflictCause::Validation,
+                    addr: None,
+                })
+            }
+            Some(Fault::Retry) => return Outcome::Fail(TxError::Retry),
+            _ => (),
+        }
+        self.commit_attempt(tr, result, report)
+    }
+
+    // Steps 2' to 7 of `write_attempt`, for a body that returned `Ok`.
+    fn commit_attempt<R>(&self, tr: &mut WriteTrans, result: R, report: &mut Report) -> Outcome<R> {
+        if let Some(e) = tr.error {
+            return Outcome::Fail(e);
+        }
+        if let Some(c) = tr.conflict {
+            return Outcome::Restart(c);
+        }
+
+        // 2'. Let a test interfere, see `STM::inject_conflict_before_commit`
+        #[cfg(feature = "testing")]
+        {
+            let injected = self.injected.lock().unwrap().take();
+            if let Some(f) = injected {
+                f(self);
+            }
+        }
+
+        // 3. Lock the write-set
+        let locked = tr.lock_write_set();
+        report.mark(Phase::Lock);
+        #[cfg(feature = "testing")]
+        let locked = match self.reached(YieldPoint::Locked, report.label) {
+            Some(Fault::LockFailure) if locked => {
+                tr.conflict = Some(Conflict {
+                    cause: ConflictCause::Lock,
+                    addr: tr.locked.first().map(|(addr, _)| *addr),
+                });
+                false
+            }
+            _ => locked,
+        };
+        if !locked {
+            return Outcome::Restart(tr.conflict.unwrap());
+        }
+
+        // 4. Increment global version-clock; a `Sync` journal keeps the
+        //    version out of `current_version` until the commit is over
+        #[cfg(feature = "std")]
+        let (ver, _reserved) = match &self.journal {
+            Some(j) => j.reserve(|| tr.mem.inc_global_clock()),
+            None => (tr.mem.inc_global_clock(), None),
+        };
+        #[cfg(not(feature = "std"))]
+        let ver = tr.mem.inc_global_clock();
+        // the change feed gets every version, see `STM::subscribe`
+        let skip = |outcome| {
+            #[cfg(feature = "std")]
+            self.feed_push(ver, &[]);
+            outcome
+        };
+
+        // 5. Validate the read-set
+        let valid = ver == tr.read_ver + 1 || tr.validate_read_set();
+        report.mark(Phase::Validate);
+        if !valid {
+            return skip(Outcome::Restart(tr.conflict.unwrap()));
+        }
+
+        // 5'. Check the commit predicate against the values to commit
+        if let Some(pred) = &tr.commit_when {
+            let view = CommitView {
+                tr,
+                conflict: Cell::new(None),
+            };
+            let ok = pred(&view);
+            if let Some(c) = view.conflict.get() {
+                return skip(Outcome::Restart(c));
+            }
+            if !ok {
+                return skip(Outcome::Fail(TxError::Rejected));
+            }
+        }
+
+        // 5''. Give the locks up to a higher-priority transaction
+        if let Some(c) = tr.wounded() {
+            return skip(Outcome::Restart(c));
+        }
+        report.mark(Phase::Validate);
+
+        // 6. Journal the write-set before any reader can see the new
+        //    values, syncing it first if the journal asks for it
+        #[cfg(feature = "std")]
+        let entries = match self.journal_write_set(tr, ver) {
+            Ok(entries) => entries,
+            Err(e) => return skip(Outcome::Fail(e)),
+        };
+        // without a journal, building the entries counts as publishing
+        #[cfg(feature = "std")]
+        let journaled = self.journal.is_some();
+        #[cfg(not(feature = "std"))]
+        let journaled = false;
+        if journaled {
+            report.mark(Phase::Journal);
+        }
+
+        // 7. Commit and release the locks
+        #[cfg(feature = "testing")]
+        self.reached(YieldPoint::Publishing, report.label);
+        #[cfg(feature = "testing")]
+        tr.commit(ver, || {
+            self.reached(YieldPoint::Applying, report.label);
+        });
+        #[cfg(not(feature = "testing"))]
+        tr.commit(ver, || ());
+        #[cfg(feature = "std")]
+        self.publish_write_set(ver, entries);
+        report.mark(Phase::Publish);
+        #[cfg(feature = "testing")]
+        self.reached(YieldPoint::Committed, report.label);
+
+        Outcome::Commit(result)
     }
 
     pub fn read_transaction<F, R>(&self, f: F) -> Option<R>
     where
         F: Fn(&mut ReadTrans) -> STMResult<R>,
     {
+        // Fast path: with nobody to tell about a completed read, run the
+        // body once and only enter the loop if it did not complete there.
+        // The loop runs it again, so restarts and failures are reported
+        // as usual, and counts the run towards `read_fallback_after`.
+        let mut ran = 0;
+        if self.quiet_reads() && !self.is_poisoned() {
+            let mut tr = ReadTrans::new(&self.mem);
+            if let Outcome::Commit(val) = Self::read_attempt(&mut tr, &f) {
+                return Some(val);
+            }
+            ran = 1;
+        }
+        self.read_loop(None, false, TxPriority::Normal, ran, f)
+            .map(|(val, _)| val)
+    }
+
+    // Whether a completed read transaction has nothing to update: no
+    // stats, metrics, watchdog, observer, trace or `tracing` span.
+    #[cfg(feature = "std")]
+    fn quiet_reads(&self) -> bool {
+        #[cfg(feature = "metrics")]
+        let emitting = self.emitter.is_some();
+        #[cfg(not(feature = "metrics"))]
+        let emitting = false;
+        #[cfg(feature = "trace")]
+        let tracing = self.recorder.is_some();
+        #[cfg(not(feature = "trace"))]
+        let tracing = false;
+        !cfg!(feature = "tracing")
+            && !emitting
+            && !tracing
+            && self.stats.is_none()
+            && !self.track_mode
+            && self.watchdog.is_none()
+            && !self.has_observer.load(Ordering::Acquire)
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn quiet_reads(&self) -> bool {
+        true
+    }
+
+    /// Like `read_transaction`, with the `TxPriority` hint `priority`: a
+    /// `Low` one backs off between restarts, as for write transactions.
+    pub fn read_transaction_with_priority<F, R>(&self, priority: TxPriority, f: F) -> Option<R>
+    where
+        F: Fn(&mut ReadTrans) -> STMResult<R>,
+    {
+        self.read_loop(None, false, priority, 0, f)
+            .map(|(val, _)| val)
+    }
+
+    /// Like `read_transaction`, tagging stats, heatmaps, `tracing` spans
+    /// and observer events with `label`.
+    pub fn read_transaction_labeled<F, R>(&self, label: &'static str, f: F) -> Option<R>
+    where
+        F: Fn(&mut ReadTrans) -> STMResult<R>,
+    {
+        self.read_loop(Some(label), false, TxPriority::Normal, 0, f)
+            .map(|(val, _)| val)
+    }
+
+    /// Like `read_transaction`, also returning the version of every stripe
+    /// the body loaded, sorted by address. A later run that sees different
+    /// versions for these addresses knows one of them was written since.
+    pub fn read_transaction_versioned<F, R>(&self, f: F) -> Option<(R, Vec<(usize, u64)>)>
+    where
+        F: Fn(&mut ReadTrans) -> STMResult<R>,
+    {
+        self.read_loop(None, true, TxPriority::Normal, 0, f)
+    }
+
+    // `ran` counts runs of the body made before, by the fast path.
+    fn read_loop<F, R>(
+        &self,
+        label: Option<&'static str>,
+        versioned: bool,
+        priority: TxPriority,
+        ran: usize,
+        f: F,
+    ) -> Option<(R, Vec<(usize, u64)>)>
+    where
+        F: Fn(&mut ReadTrans) -> STMResult<R>,
+    {
+        let span = TxSpan::read(label);
+        let mut report = Report::new(self, label, true);
+        let mut attempt: u32 = 0;
         loop {
-            // 1. Sample global version-clock
-            let mut tr = ReadTrans::new(unsafe { &*self.mem.get() });
-
-            // 2. Run through a speculative execution
-            match f(&mut tr) {
-                STMResult::Abort => return None,
-                STMResult::Retry => {
-                    if tr.is_abort {
-                        continue;
+            // only a low priority backs off between reads, and it lets
+            // restarting high ones go first
+            if priority == TxPriority::Low {
+                if attempt > 0 {
+                    self.backoff(attempt, priority);
+                }
+                self.mem.yield_to_urgent();
+            }
+            let _entered = span.enter();
+            attempt = attempt.saturating_add(1);
+            report.begin(attempt, priority);
+
+            // 0. Too many restarts: keep writers out while reading
+            let blocked = if attempt as usize + ran > self.read_fallback_after {
+                Some(self.mem.block_writers())
+            } else {
+                None
+            };
+
+            let mut tr = ReadTrans::new(&self.mem);
+            let outcome = if self.is_poisoned() {
+                Outcome::Fail(TxError::Poisoned)
+            } else {
+                Self::read_attempt(&mut tr, &f)
+            };
+            let read_ver = tr.read_ver;
+            let read_set = core::mem::take(&mut tr.read_set);
+            #[cfg(feature = "trace")]
+            report.trace_sets(read_set.keys(), [].iter());
+            drop(tr);
+            drop(blocked);
+            report.attempted(read_ver);
+
+            match outcome {
+                Outcome::Commit(val) => {
+                    report.commit(&span, attempt, (0, 0));
+
+                    let mut versions = Vec::new();
+                    if versioned {
+                        versions.extend(read_set);
+                        versions.sort_unstable();
                     }
+                    return Some((val, versions));
+                }
+                Outcome::Restart(c) => {
+                    self.check_corruption(c);
+                    report.restart(&span, c, attempt, (0, 0));
+                    // loom only switches threads where it is told to
+                    #[cfg(loom)]
+                    crate::sync::yield_now();
+                }
+                Outcome::Fail(e) => {
+                    report.fail(&span, e, attempt, (0, 0));
                     return None;
                 }
-                STMResult::Ok(val) => {
-                    if tr.is_abort == true {
-                        continue;
-                    } else {
-                        return Some(val);
-                    }
+            }
+        }
+    }
+
+    fn read_attempt<F, R>(tr: &mut ReadTrans, f: &F) -> Outcome<R>
+    where
+        F: Fn(&mut ReadTrans) -> STMResult<R>,
+    {
+        // 1. Sample global version-clock (done by ReadTrans::new)
+
+        // 2. Run through a speculative execution
+        match f(tr) {
+            STMResult::Abort => Outcome::Fail(TxError::Abort),
+            STMResult::Retry => match tr.conflict {
+                Some(c) => Outcome::Restart(c),
+                None => Outcome::Fail(TxError::Retry),
+            },
+            STMResult::Ok(val) => match tr.conflict {
+                Some(c) => Outcome::Restart(c),
+                None => Outcome::Commit(val),
+            },
+        }
+    }
+
+    /// Set the global clock to `v`, e.g. to resume version numbering after
+    /// restoring a snapshot. Like `zero_region`, `&mut self` guarantees
+    /// that no transaction runs meanwhile. The versions skipped never reach
+    /// the change feed, so followers of `STM::replicate` need a resync.
+    ///
+    /// # Panics
+    ///
+    /// If `v` is below the version of a stripe, which would make every
+    /// read of that stripe conflict, or the clock cannot move to `v`.
+    pub fn set_clock(&mut self, v: u64) {
+        assert!(v < 1 << 63, "clock {} overlaps the lock bit", v);
+        let max = self.mem.max_version();
+        assert!(v >= max, "clock {} below stripe version {}", v, max);
+        self.mem.clock.set(v);
+        assert_eq!(self.mem.clock.sample(), v, "clock cannot move to {}", v);
+    }
+
+    /// Zero `len` bytes starting at `addr`, bypassing transactions, and
+    /// give the stripes covering them the next version of the clock, as a
+    /// commit would, so that versions seen before still tell the change.
+    /// An unaligned range zeroes only its own bytes of the stripes at its
+    /// ends. Meant for setup: `&mut self` guarantees that no transaction
+    /// runs meanwhile, so with an `Arc<STM>` call it before sharing (or
+    /// through `Arc::get_mut`). The change feed gets the stripes as of
+    /// the new version, like the record of a commit.
+    ///
+    /// # Panics
+    ///
+    /// If the range does not fit in the memory.
+    pub fn zero_region(&mut self, addr: usize, len: usize) {
+        let end = addr.checked_add(len).expect("region end overflows usize");
+        assert!(end <= self.mem.capacity(), "region out of bounds");
+        let ver = self.mem.zero(addr, len);
+        #[cfg(feature = "std")]
+        if let (Some(ver), true) = (ver, self.feed.is_active()) {
+            let start = addr & !(STRIPE_SIZE - 1);
+            let entries: Vec<_> = (start..end)
+                .step_by(STRIPE_SIZE)
+                .map(|a| (a, self.mem.load_latest(a)))
+                .collect();
+            self.feed_push(ver, &entries);
+        }
+        #[cfg(not(feature = "std"))]
+        let _ = ver;
+    }
+
+    /// Copy `len` bytes starting at `addr` as a consistent view.
+    /// All stripes covering the range are read at one version.
+    pub fn snapshot_range(&self, addr: usize, len: usize) -> Option<Vec<u8>> {
+        let end = addr.checked_add(len)?;
+        if end > self.mem.capacity() {
+            return None;
+        }
+
+        let start = addr & !(STRIPE_SIZE - 1);
+        self.read_transaction(|tr| {
+            let mut buf = Vec::with_capacity(end - start + STRIPE_SIZE);
+            for a in (start..end).step_by(STRIPE_SIZE) {
+                buf.extend_from_slice(&load!(tr, a));
+            }
+            STMResult::Ok(buf[addr - start..end - start].to_vec())
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl STM {
+    /// Counters summed over all threads, or `None` unless enabled with
+    /// `STMBuilder::stats`.
+    pub fn stats(&self) -> Option<StatsSnapshot> {
+        self.stats.as_ref().map(|s| s.snapshot(&self.threads))
+    }
+
+    /// Whether the calling thread's next transaction had better be a read
+    /// or a write transaction: `Read` once most of its last 64 committed
+    /// ones wrote nothing, write transactions with an empty write-set
+    /// included. `Write` until there is such a history, and always without
+    /// `STMBuilder::track_mode`.
+    pub fn suggest_mode(&self) -> Mode {
+        if !self.track_mode {
+            return Mode::Write;
+        }
+        self.threads.with_local(|t| t.mode.suggest())
+    }
+
+    /// Counters per transaction label, the default bucket (`None`) first,
+    /// then the labels in the order they were first used. Unlabeled
+    /// transactions, and labels beyond the first 64, count towards the
+    /// default bucket. `None` unless enabled with `STMBuilder::stats`.
+    pub fn stats_by_label(&self) -> Option<Vec<(Option<&'static str>, LabelStats)>> {
+        let stats = self.stats.as_ref()?;
+        Some(
+            self.labels
+                .buckets()
+                .map(|(i, l)| (l, stats.label_snapshot(i)))
+                .collect(),
+        )
+    }
+
+    /// Counters per `TxPriority` hint, `Low` first. `None` unless enabled
+    /// with `STMBuilder::stats`.
+    pub fn stats_by_priority(&self) -> Option<Vec<(TxPriority, LabelStats)>> {
+        let stats = self.stats.as_ref()?;
+        Some(
+            [TxPriority::Low, TxPriority::Normal, TxPriority::High]
+                .iter()
+                .map(|&p| (p, stats.priority_snapshot(p)))
+                .collect(),
+        )
+    }
+
+    /// Per-phase latencies of the sampled committed write transactions, or
+    /// `None` unless latency sampling and stats (or `metrics`) are enabled.
+    pub fn latency_histograms(&self) -> Option<LatencyHistograms> {
+        self.latency.as_ref().map(|l| l.snapshot())
+    }
+
+    pub fn reset_stats(&self) {
+        if let Some(s) = &self.stats {
+            s.reset(&self.threads);
+        }
+        if let Some(l) = &self.latency {
+            l.reset();
+        }
+    }
+
+    pub fn journal(&self) -> Option<&Journal> {
+        self.journal.as_ref()
+    }
+
+    #[cfg(feature = "tokio")]
+    pub(crate) fn wakeup(&self) -> &Wakeup {
+        &self.wakeup
+    }
+
+    #[cfg(feature = "mmap")]
+    pub(crate) fn is_durable(&self) -> bool {
+        self.durable
+    }
+
+    pub(crate) fn replica(&self) -> &Mutex<Replica> {
+        &self.replica
+    }
+
+    pub(crate) fn storage(&self) -> &dyn Storage {
+        &*self.mem.storage
+    }
+
+    #[cfg(feature = "pmem")]
+    pub(crate) fn pmem(&self) -> Option<&Arc<Pmem>> {
+        self.mem.pmem.as_ref()
+    }
+
+    // Unlock the stripes locked with the tag `owner`; returns how many.
+    // Their new version goes to the change feed as an empty record, so
+    // that followers do not wait for it.
+    #[cfg(all(unix, feature = "shm"))]
+    pub(crate) fn release_owner(&self, owner: u64) -> usize {
+        let (n, ver) = self.mem.release_owner(owner);
+        if let Some(ver) = ver {
+            self.feed_push(ver, &[]);
+        }
+        n
+    }
+
+    // Commits wait while the returned guard is alive.
+    pub(crate) fn pause_commits(&self) -> impl Drop + '_ {
+        self.mem.block_writers()
+    }
+
+    /// Time spent optimistic and serialized, or `None` without
+    /// `STMBuilder::circuit_breaker`.
+    pub fn breaker_stats(&self) -> Option<BreakerStats> {
+        self.breaker.as_ref().map(Breaker::stats)
+    }
+
+    /// Commits, attempts and clock advance over the last minute, or `None`
+    /// unless enabled with `STMBuilder::stats`.
+    pub fn throughput_window(&self) -> Option<ThroughputWindow> {
+        let s = self.stats.as_ref()?;
+        Some(s.throughput(self.current_version()))
+    }
+
+    /// Move the throughput window on by `d` as if that much time had
+    /// passed, so a test can roll its buckets over without waiting. Needs
+    /// the `testing` feature; does nothing without stats.
+    #[cfg(feature = "testing")]
+    pub fn advance_throughput_window(&self, d: Duration) {
+        if let Some(s) = &self.stats {
+            s.skip_window(d);
+        }
+    }
+
+    /// Stripes that caused restarts (failed validation or lock acquisition)
+    /// with their counts, hottest first. `None` unless enabled with
+    /// `STMBuilder::conflict_heatmap`.
+    pub fn conflict_heatmap(&self) -> Option<Vec<(usize, u64)>> {
+        self.heatmap.as_ref().map(|h| h.report())
+    }
+
+    /// The `k` hottest entries of `conflict_heatmap`.
+    pub fn conflict_heatmap_top(&self, k: usize) -> Option<Vec<(usize, u64)>> {
+        self.conflict_heatmap().map(|mut v| {
+            v.truncate(k);
+            v
+        })
+    }
+
+    /// Like `conflict_heatmap`, counting only restarts of transactions
+    /// labeled `label`.
+    pub fn conflict_heatmap_labeled(&self, label: &str) -> Option<Vec<(usize, u64)>> {
+        let h = self.heatmap.as_ref()?;
+        Some(match self.labels.find(label) {
+            Some(i) => h.report_label(i),
+            None => Vec::new(),
+        })
+    }
+
+    // Bucket of `label` for stats, heatmaps, the watchdog and the event
+    // ring; labels are only registered when one of them is enabled.
+    fn bucket(&self, label: Option<&'static str>) -> usize {
+        if self.stats.is_none()
+            && self.heatmap.is_none()
+            && self.watchdog.is_none()
+            && self.events.is_none()
+        {
+            return 0;
+        }
+        self.labels.index(label)
+    }
+
+    /// Running transactions older than `max_age` or past `max_attempts`
+    /// attempts, each also reported to the observer and as a `tracing`
+    /// event. Empty unless enabled with `STMBuilder::watchdog`; the host
+    /// decides how often to poll.
+    ///
+    /// At most 64 transactions are watched at a time; beyond that new ones
+    /// go unwatched until a slot frees up.
+    pub fn check_stalled(&self, max_age: Duration, max_attempts: u32) -> Vec<StalledTx> {
+        let w = match &self.watchdog {
+            Some(w) => w,
+            None => return Vec::new(),
+        };
+        let stalled: Vec<_> = w
+            .stalled(max_age, max_attempts)
+            .into_iter()
+            .map(|(bucket, read_only, priority, attempts, age)| StalledTx {
+                label: self.labels.name(bucket),
+                read_only,
+                priority,
+                attempts,
+                age,
+            })
+            .collect();
+
+        let observer = if self.has_observer.load(Ordering::Acquire) {
+            self.observer.read().unwrap().clone()
+        } else {
+            None
+        };
+        for tx in stalled.iter() {
+            trace::stalled(tx);
+            if let Some(o) = &observer {
+                o.on_stall(tx);
+            }
+        }
+        stalled
+    }
+
+    /// A receiver of every write-set committed from now on.
+    ///
+    /// Every version the clock hands out is sent once: a commit that took
+    /// a version and then failed, or wrote nothing, sends a record without
+    /// entries. Records are sent after the commit releases its locks, so
+    /// commits to the same stripe may arrive out of order: apply a record
+    /// to a stripe only if its version is newer than the last one applied,
+    /// or apply records in version order as `STM::apply_replica` does. A
+    /// subscriber that falls `STMBuilder::feed_buffer` records behind
+    /// loses the following ones, counted in `StatsSnapshot::feed_drops`
+    /// and flagged by `CommitRecord::gap` on the next one it gets;
+    /// committers never wait for it. Dropping the receiver unsubscribes.
+    pub fn subscribe(&self) -> Receiver<CommitRecord> {
+        self.feed.subscribe()
+    }
+
+    /// The last commits kept by `STMBuilder::commit_history`, in version
+    /// order, each with the stripes it wrote sorted by address. Empty
+    /// without it. Needs the `history` feature.
+    #[cfg(feature = "history")]
+    pub fn recent_commits(&self) -> Vec<CommitRecord> {
+        self.history
+            .as_ref()
+            .map_or_else(Vec::new, CommitHistory::records)
+    }
+
+    /// The last restarts and failures, oldest first; see
+    /// `STMBuilder::event_ring`. Events being written while this runs are
+    /// left out.
+    pub fn recent_events(&self) -> Vec<TxEvent> {
+        let events = match &self.events {
+            Some(e) => e.recent(),
+            None => return Vec::new(),
+        };
+        events
+            .into_iter()
+            .map(|(bucket, e)| TxEvent {
+                label: self.labels.name(bucket),
+                ..e
+            })
+            .collect()
+    }
+
+    /// A human-readable dump of the stats, the ten hottest stripes and the
+    /// recent events, for bug reports.
+    pub fn debug_report(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "capacity: {}, version: {}, threads: {}",
+            self.capacity(),
+            self.current_version(),
+            self.live_threads()
+        );
+        if let Some(s) = self.stats() {
+            let _ = writeln!(out, "{}", s);
+        }
+        if let Some(labels) = self.stats_by_label() {
+            for (label, s) in labels {
+                let _ = writeln!(out, "label {}: {:?}", label.unwrap_or("(none)"), s);
+            }
+        }
+        if let Some(top) = self.conflict_heatmap_top(10) {
+            let _ = writeln!(out, "hottest stripes:");
+            for (addr, n) in top {
+                let _ = writeln!(out, "  {:#x}: {}", addr, n);
+            }
+        }
+        let events = self.recent_events();
+        if !events.is_empty() {
+            let _ = writeln!(out, "recent events:");
+            for e in events {
+                let _ = write!(
+                    out,
+                    "  {:?} {} attempt {}: {:?}",
+                    e.at,
+                    e.label.unwrap_or("(none)"),
+                    e.attempt,
+                    e.kind
+                );
+                if let Some(a) = e.addr {
+                    let _ = write!(out, " at {:#x}", a);
                 }
+                let _ = writeln!(out);
+            }
+        }
+        out
+    }
+
+    #[cfg(feature = "metrics")]
+    fn emit(
+        &self,
+        label: Option<&'static str>,
+        finish: Finish,
+        tally: &Tally,
+        timer: Option<&latency::Timer>,
+    ) {
+        if let Some(e) = &self.emitter {
+            e.finish(label, finish, tally, timer);
+        }
+    }
+
+    #[cfg(not(feature = "metrics"))]
+    #[inline(always)]
+    fn emit(
+        &self,
+        _label: Option<&'static str>,
+        _finish: Finish,
+        _tally: &Tally,
+        _timer: Option<&latency::Timer>,
+    ) {
+    }
+
+    fn count<G: FnOnce(&Stats, &ThreadStats)>(&self, g: G) {
+        if let Some(s) = &self.stats {
+            self.threads.with_local(|t| g(s, &t.stats));
+        }
+    }
+
+    fn failed(&self, bucket: usize, priority: TxPriority, e: TxError, attempt: u32) {
+        if let Some(ev) = &self.events {
+            ev.push(bucket, EventKind::Failed(e), None, attempt);
+        }
+        match e {
+            TxError::Retry => self.count(|s, t| s.retry(t, bucket, priority)),
+            _ => self.count(|s, t| s.abort(t, bucket, priority)),
+        }
+    }
+
+    fn observing(&self, label: Option<&'static str>, read_only: bool) -> Option<Observing> {
+        if !self.has_observer.load(Ordering::Acquire) {
+            return None;
+        }
+        let observer = self.observer.read().unwrap().clone()?;
+        Some(Observing::begin(observer, label, read_only))
+    }
+
+    /// Report transaction events to `observer`, replacing any previous one.
+    /// Transactions already running keep the observer they started with.
+    pub fn set_observer(&self, observer: Arc<dyn TxObserver>) {
+        *self.observer.write().unwrap() = Some(observer);
+        self.has_observer.store(true, Ordering::Release);
+    }
+
+    pub fn clear_observer(&self) {
+        self.has_observer.store(false, Ordering::Release);
+        *self.observer.write().unwrap() = None;
+    }
+
+    /// Run `f` once, when the next write transaction has run its body and
+    /// is about to lock its write-set. `f` may commit transactions of its
+    /// own; writing a stripe the paused one read makes it restart, so a
+    /// test can force a conflict without racing threads. Needs the
+    /// `testing` feature.
+    #[cfg(feature = "testing")]
+    pub fn inject_conflict_before_commit<F>(&self, f: F)
+    where
+        F: FnOnce(&STM) + Send + 'static,
+    {
+        *self.injected.lock().unwrap() = Some(Box::new(f));
+    }
+
+    // Report `point` to the scheduler and return the fault injected
+    // there, if any.
+    #[cfg(feature = "testing")]
+    fn reached(&self, point: YieldPoint, label: Option<&'static str>) -> Option<Fault> {
+        if let Some(s) = &self.scheduler {
+            s.reached(point);
+        }
+        self.faults.as_ref().and_then(|f| f.inject(label, point))
+    }
+
+    // Transactions driven step by step from outside a body, for `TxScript`,
+    // which interleaves several of them on one thread.
+    #[cfg(feature = "testing")]
+    pub(crate) fn open_read(&self) -> ReadTrans<'_> {
+        ReadTrans::new(&self.mem)
+    }
+
+    #[cfg(feature = "testing")]
+    pub(crate) fn open_write(&self) -> WriteTrans<'_> {
+        WriteTrans::new(&self.mem, self.max_read_set, 0)
+    }
+
+    // Commit `tr` as if its body had returned `Ok`: the version it
+    // committed at, or the conflict that restarts it. Other failures need
+    // a journal, a commit predicate or a read-set limit, and panic.
+    #[cfg(feature = "testing")]
+    pub(crate) fn commit_open(&self, tr: &mut WriteTrans) -> Result<u64, Conflict> {
+        let mut report = Report::new(self, None, false);
+        match self.commit_attempt(tr, (), &mut report) {
+            Outcome::Commit(()) => Ok(tr.version),
+            Outcome::Restart(c) => Err(c),
+            Outcome::Fail(e) => panic!("open transaction failed: {:?}", e),
+        }
+    }
+
+    // The sorted write-set, if the journal, the commit sink or the feed
+    // wants it, journaled under `ver` first if there is a journal.
+    fn journal_write_set(&self, tr: &WriteTrans, ver: u64) -> Result<Option<Entries>, TxError> {
+        if tr.write_set.is_empty() {
+            return Ok(None);
+        }
+        #[cfg(feature = "commit-log")]
+        let sink = self.sink.is_some();
+        #[cfg(not(feature = "commit-log"))]
+        let sink = false;
+        #[cfg(feature = "history")]
+        let sink = sink || self.history.is_some();
+        if self.journal.is_none() && !sink && !self.feed.is_active() {
+            return Ok(None);
+        }
+
+        let entries = tr.entries();
+        if let Some(j) = &self.journal {
+            j.append(ver, &entries).map_err(|_| TxError::Journal)?;
+        }
+        Ok(Some(entries))
+    }
+
+    // Tell whoever waits for commits about the one just published.
+    fn publish_write_set(&self, ver: u64, entries: Option<Entries>) {
+        #[cfg(feature = "tokio")]
+        self.wakeup.committed();
+
+        #[cfg(feature = "commit-log")]
+        if let (Some(s), Some(entries)) = (&self.sink, &entries) {
+            s.append(ver, entries);
+        }
+        #[cfg(feature = "history")]
+        if let (Some(h), Some(entries)) = (&self.history, &entries) {
+            h.push(ver, entries);
+        }
+        self.feed_push(ver, entries.as_deref().unwrap_or(&[]));
+    }
+
+    fn feed_push(&self, ver: u64, entries: &[(usize, [u8; STRIPE_SIZE])]) {
+        if self.feed.is_active() {
+            let full = self.feed.push(ver, entries);
+            if full > 0 {
+                self.count(|s, t| s.feed_drops(t, full));
             }
         }
     }
+
+    fn restarted(
+        &self,
+        span: &TxSpan,
+        bucket: usize,
+        priority: TxPriority,
+        c: Conflict,
+        attempt: u32,
+    ) {
+        span.restart(&c, attempt);
+        self.count(|s, t| s.restart(t, bucket, priority, c.cause));
+        if let Some(ev) = &self.events {
+            ev.push(bucket, EventKind::Restart(c.cause), c.addr, attempt);
+        }
+        if let (Some(h), Some(addr)) = (&self.heatmap, c.addr) {
+            h.hit(bucket, addr >> self.mem.shift_size);
+        }
+    }
+
+    // Copy `bytes` to `addr` bypassing transactions, versions and the
+    // clock left as they are. For restoring a freshly built STM.
+    pub(crate) fn fill(&mut self, addr: usize, bytes: &[u8]) {
+        self.mem.fill(addr, bytes);
+    }
 }
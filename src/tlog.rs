@@ -0,0 +1,69 @@
+use core::convert::TryFrom;
+
+use crate::tl2::{check_aligned, Trans, WriteTrans, STRIPE_SIZE};
+use crate::tset::RegionFull;
+
+/// An append-only log of stripes kept in a region of STM memory.
+///
+/// The region holds the head (the number of entries) followed by the
+/// entries. `append` reads and bumps the head and stores into the slot it
+/// pointed at, so concurrent appenders conflict only on the head stripe,
+/// and entries get consecutive slots in commit order. A zeroed region is
+/// an empty log.
+pub struct TLog {
+    base: usize,
+    slots: usize,
+}
+
+impl TLog {
+    /// A log in the `len` bytes starting at the stripe-aligned `base`.
+    pub fn new(base: usize, len: usize) -> TLog {
+        check_aligned(base);
+        base.checked_add(len).expect("log end overflows usize");
+        let slots = len.saturating_sub(STRIPE_SIZE) / STRIPE_SIZE;
+        assert!(slots > 0, "TLog region is too small");
+
+        TLog { base, slots }
+    }
+
+    /// Number of entries the region has room for.
+    pub fn capacity(&self) -> usize {
+        self.slots
+    }
+
+    fn slot_addr(&self, idx: usize) -> usize {
+        self.base + (idx + 1) * STRIPE_SIZE
+    }
+
+    /// Number of entries. A head past the region, only left by stores
+    /// bypassing the log, reads as a full log.
+    pub fn len<R: Trans>(&self, tr: &mut R) -> Option<usize> {
+        let head = u64::from_le_bytes(tr.load(self.base)?);
+        Some(usize::try_from(head).map_or(self.slots, |n| n.min(self.slots)))
+    }
+
+    /// The entry in slot `idx`, or `Some(None)` past the head.
+    pub fn get<R: Trans>(&self, tr: &mut R, idx: usize) -> Option<Option<[u8; STRIPE_SIZE]>> {
+        if idx >= self.len(tr)? {
+            return Some(None);
+        }
+        tr.load(self.slot_addr(idx)).map(Some)
+    }
+
+    /// Store `val` at the head and advance it; returns the slot it went
+    /// in, or `RegionFull` when every slot is taken.
+    pub fn append(
+        &self,
+        tr: &mut WriteTrans,
+        val: [u8; STRIPE_SIZE],
+    ) -> Option<Result<usize, RegionFull>> {
+        let idx = self.len(tr)?;
+        if idx == self.slots {
+            return Some(Err(RegionFull));
+        }
+
+        tr.store(self.slot_addr(idx), val);
+        tr.store(self.base, (idx as u64 + 1).to_le_bytes());
+        Some(Ok(idx))
+    }
+}
@@ -0,0 +1,102 @@
+// `tracing` spans and events for transactions. Without the `tracing` feature
+// every method is an empty inline function, so nothing is compiled in.
+
+use crate::conflict::Conflict;
+#[cfg(feature = "std")]
+use crate::watchdog::StalledTx;
+
+// Each attempt enters the transaction's span, rather than the transaction
+// as a whole: an async transaction may move between threads while it
+// waits, and an entered span must not.
+#[cfg(feature = "tracing")]
+pub(crate) struct TxSpan(tracing::Span);
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct TxSpan;
+
+#[cfg(feature = "tracing")]
+pub(crate) struct Entered<'a> {
+    _guard: tracing::span::Entered<'a>,
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct Entered;
+
+#[cfg(feature = "tracing")]
+impl TxSpan {
+    pub(crate) fn write(label: Option<&'static str>) -> TxSpan {
+        TxSpan(tracing::debug_span!(
+            "write_transaction",
+            label = label.unwrap_or(""),
+            attempts = tracing::field::Empty,
+            read_set = tracing::field::Empty,
+            write_set = tracing::field::Empty,
+        ))
+    }
+
+    pub(crate) fn read(label: Option<&'static str>) -> TxSpan {
+        TxSpan(tracing::debug_span!(
+            "read_transaction",
+            label = label.unwrap_or(""),
+            attempts = tracing::field::Empty,
+        ))
+    }
+
+    pub(crate) fn enter(&self) -> Entered<'_> {
+        Entered {
+            _guard: self.0.enter(),
+        }
+    }
+
+    pub(crate) fn restart(&self, c: &Conflict, attempt: u32) {
+        tracing::debug!(cause = ?c.cause, addr = ?c.addr, attempt, "transaction restarted");
+    }
+
+    pub(crate) fn finish(&self, attempts: u32, read_set: usize, write_set: usize) {
+        self.0.record("attempts", attempts);
+        self.0.record("read_set", read_set);
+        self.0.record("write_set", write_set);
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+impl TxSpan {
+    #[inline(always)]
+    pub(crate) fn write(_label: Option<&'static str>) -> TxSpan {
+        TxSpan
+    }
+
+    #[inline(always)]
+    pub(crate) fn read(_label: Option<&'static str>) -> TxSpan {
+        TxSpan
+    }
+
+    #[inline(always)]
+    pub(crate) fn enter(&self) -> Entered {
+        Entered
+    }
+
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    #[inline(always)]
+    pub(crate) fn restart(&self, _c: &Conflict, _attempt: u32) {}
+
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    #[inline(always)]
+    pub(crate) fn finish(&self, _attempts: u32, _read_set: usize, _write_set: usize) {}
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn stalled(tx: &StalledTx) {
+    tracing::warn!(
+        label = tx.label.unwrap_or(""),
+        read_only = tx.read_only,
+        priority = ?tx.priority,
+        attempts = tx.attempts,
+        age_us = tx.age.as_micros() as u64,
+        "transaction stalled"
+    );
+}
+
+#[cfg(all(feature = "std", not(feature = "tracing")))]
+#[inline(always)]
+pub(crate) fn stalled(_tx: &StalledTx) {}
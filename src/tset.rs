@@ -0,0 +1,181 @@
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+
+use crate::tl2::{check_aligned, Trans, WriteTrans, STRIPE_SIZE};
+use crate::value::StripeValue;
+
+const INITIAL_BUCKETS: u64 = 8;
+const HEADER: usize = 2 * STRIPE_SIZE; // bucket count, element count
+const SLOT: usize = 2 * STRIPE_SIZE; // state, value
+
+const EMPTY: u64 = 0;
+const OCCUPIED: u64 = 1;
+const DELETED: u64 = 2;
+
+/// A structure's region has no room left for another element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegionFull;
+
+impl fmt::Display for RegionFull {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "region is full")
+    }
+}
+
+impl core::error::Error for RegionFull {}
+
+/// A hash set of stripe-sized values kept in a region of STM memory.
+///
+/// The region holds the bucket count, the element count and an
+/// open-addressing table of (state, value) slots. Every operation is a
+/// handful of loads and stores inside the caller's transaction, so several
+/// set operations (or other data) compose atomically. A zeroed region is an
+/// empty set. When the table gets 3/4 full, `insert` rehashes it into twice
+/// as many buckets within the same transaction.
+pub struct TSet<T> {
+    base: usize,
+    max_buckets: u64,
+    _elem: PhantomData<T>,
+}
+
+fn hash(stripe: [u8; STRIPE_SIZE]) -> u64 {
+    // FNV-1a followed by a multiplicative mix
+    let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in stripe.iter() {
+        h ^= *b as u64;
+        h = h.wrapping_mul(0x0100_0000_01b3);
+    }
+    (h ^ (h >> 32)).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+}
+
+fn load_u64<R: Trans>(tr: &mut R, addr: usize) -> Option<u64> {
+    tr.load(addr).map(u64::from_le_bytes)
+}
+
+impl<T: StripeValue + PartialEq> TSet<T> {
+    /// A set in the `len` bytes starting at the stripe-aligned `base`.
+    pub fn new(base: usize, len: usize) -> TSet<T> {
+        check_aligned(base);
+        let slots = (len.saturating_sub(HEADER) / SLOT) as u64;
+        assert!(slots >= INITIAL_BUCKETS, "TSet region is too small");
+
+        // largest power of two that fits
+        let max_buckets = 1 << (63 - slots.leading_zeros());
+        TSet {
+            base,
+            max_buckets,
+            _elem: PhantomData,
+        }
+    }
+
+    fn state_addr(&self, idx: u64) -> usize {
+        self.base + HEADER + idx as usize * SLOT
+    }
+
+    fn buckets<R: Trans>(&self, tr: &mut R) -> Option<u64> {
+        let n = load_u64(tr, self.base)?;
+        Some(if n == 0 { INITIAL_BUCKETS } else { n })
+    }
+
+    /// Number of elements.
+    pub fn len<R: Trans>(&self, tr: &mut R) -> Option<u64> {
+        load_u64(tr, self.base + STRIPE_SIZE)
+    }
+
+    // Probe for `val`. Returns the slot holding it, or else the first free
+    // (empty or deleted) slot seen, if any.
+    fn find<R: Trans>(&self, tr: &mut R, n: u64, val: &T) -> Option<Result<u64, Option<u64>>> {
+        let key = val.to_stripe();
+        let mut free = None;
+        let mut idx = hash(key) & (n - 1);
+        for _ in 0..n {
+            let addr = self.state_addr(idx);
+            match load_u64(tr, addr)? {
+                EMPTY => return Some(Err(free.or(Some(idx)))),
+                DELETED => {
+                    free = free.or(Some(idx));
+                }
+                _ => {
+                    if tr.load(addr + STRIPE_SIZE)? == key {
+                        return Some(Ok(idx));
+                    }
+                }
+            }
+            idx = (idx + 1) & (n - 1);
+        }
+        Some(Err(free))
+    }
+
+    pub fn contains<R: Trans>(&self, tr: &mut R, val: &T) -> Option<bool> {
+        let n = self.buckets(tr)?;
+        Some(self.find(tr, n, val)?.is_ok())
+    }
+
+    /// Insert `val`; returns whether it was newly added, or `RegionFull`
+    /// when the region has no room left for it.
+    pub fn insert(&self, tr: &mut WriteTrans, val: T) -> Option<Result<bool, RegionFull>> {
+        let mut n = self.buckets(tr)?;
+        let len = self.len(tr)?;
+        if (len + 1) * 4 > n * 3 && n < self.max_buckets {
+            n = self.grow(tr)?;
+        }
+
+        let idx = match self.find(tr, n, &val)? {
+            Ok(_) => return Some(Ok(false)),
+            Err(Some(idx)) => idx,
+            Err(None) => return Some(Err(RegionFull)),
+        };
+
+        let addr = self.state_addr(idx);
+        tr.store(addr, OCCUPIED.to_le_bytes());
+        tr.store(addr + STRIPE_SIZE, val.to_stripe());
+        tr.store(self.base + STRIPE_SIZE, (len + 1).to_le_bytes());
+        Some(Ok(true))
+    }
+
+    /// Remove `val`; returns whether it was present.
+    pub fn remove(&self, tr: &mut WriteTrans, val: &T) -> Option<bool> {
+        let n = self.buckets(tr)?;
+        let idx = match self.find(tr, n, val)? {
+            Ok(idx) => idx,
+            Err(_) => return Some(false),
+        };
+
+        let len = self.len(tr)?;
+        tr.store(self.state_addr(idx), DELETED.to_le_bytes());
+        tr.store(self.base + STRIPE_SIZE, (len - 1).to_le_bytes());
+        Some(true)
+    }
+
+    /// Rehash into twice as many buckets (bounded by the region), dropping
+    /// deleted slots. Returns the new bucket count.
+    pub fn grow(&self, tr: &mut WriteTrans) -> Option<u64> {
+        let n = self.buckets(tr)?;
+        let new_n = (n * 2).min(self.max_buckets);
+
+        let mut vals = Vec::new();
+        for idx in 0..n {
+            let addr = self.state_addr(idx);
+            if load_u64(tr, addr)? == OCCUPIED {
+                vals.push(tr.load(addr + STRIPE_SIZE)?);
+            }
+        }
+
+        for idx in 0..new_n {
+            tr.store(self.state_addr(idx), EMPTY.to_le_bytes());
+        }
+        tr.store(self.base, new_n.to_le_bytes());
+
+        for key in vals {
+            let mut idx = hash(key) & (new_n - 1);
+            while load_u64(tr, self.state_addr(idx))? != EMPTY {
+                idx = (idx + 1) & (new_n - 1);
+            }
+            let addr = self.state_addr(idx);
+            tr.store(addr, OCCUPIED.to_le_bytes());
+            tr.store(addr + STRIPE_SIZE, key);
+        }
+        Some(new_n)
+    }
+}
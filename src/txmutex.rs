@@ -0,0 +1,160 @@
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+
+use crate::record;
+use crate::tl2::{check_aligned, STMResult, Trans, TxError, STM, STRIPE_SIZE};
+use crate::value::BigValue;
+
+/// A `Mutex<T>` kept in STM memory, for moving code off mutexes one value
+/// at a time: code still on the lock API and transactions can use the
+/// same value.
+///
+/// Nothing is locked. `lock` copies the value into the guard, and the
+/// guard commits it back when dropped, in a write transaction that
+/// retries until it commits. So no thread ever waits for a guard, and
+/// taking guards of several mutexes in any order cannot deadlock; the
+/// price is that the last guard dropped wins, overwriting what was
+/// committed since its `lock`. `update` runs a critical section as one
+/// transaction instead, rerunning it on a conflict, which is what a
+/// read-modify-write ported from a `Mutex` wants.
+pub struct TxMutex<T> {
+    stm: Arc<STM>,
+    base: usize,
+    _val: PhantomData<T>,
+}
+
+/// The value of a `TxMutex` as of `lock`, committed back by `commit` or
+/// when dropped. The commit retries on conflicts and fails only if the
+/// STM is poisoned, which dropping reports by panicking; call `commit` to
+/// handle it. If the thread panics while holding it, the changes are
+/// thrown away.
+pub struct TxMutexGuard<'a, T: BigValue> {
+    mutex: &'a TxMutex<T>,
+    val: T,
+    seen: Vec<[u8; STRIPE_SIZE]>, // the stripes of the value at `lock`
+    done: bool,
+}
+
+impl<T: BigValue> TxMutex<T> {
+    /// Place the mutex at the stripe-aligned address `base`; it takes
+    /// `TxMutex::size` bytes.
+    pub fn new(stm: Arc<STM>, base: usize) -> TxMutex<T> {
+        check_aligned(base);
+        base.checked_add(Self::size())
+            .expect("mutex end overflows usize");
+
+        TxMutex {
+            stm,
+            base,
+            _val: PhantomData,
+        }
+    }
+
+    /// Bytes taken.
+    pub fn size() -> usize {
+        T::STRIPES * STRIPE_SIZE
+    }
+
+    fn stripes<R: Trans>(&self, tr: &mut R) -> Option<Vec<[u8; STRIPE_SIZE]>> {
+        (0..T::STRIPES)
+            .map(|i| tr.load(self.base + i * STRIPE_SIZE))
+            .collect()
+    }
+
+    /// Copy the value into a guard. Never waits; fails only if the STM is
+    /// poisoned.
+    pub fn lock(&self) -> Result<TxMutexGuard<'_, T>, TxError> {
+        let seen = self
+            .stm
+            .read_transaction(|tr| match self.stripes(tr) {
+                Some(seen) => STMResult::Ok(seen),
+                None => STMResult::Retry,
+            })
+            .ok_or(TxError::Poisoned)?;
+        Ok(TxMutexGuard {
+            mutex: self,
+            val: T::from_stripes(&seen),
+            seen,
+            done: false,
+        })
+    }
+
+    /// Run `f` on the value and store the result, in one transaction that
+    /// reruns `f` on a conflict, like a critical section under a `Mutex`.
+    pub fn update<F, R>(&self, f: F) -> Result<R, TxError>
+    where
+        F: Fn(&mut T) -> R,
+    {
+        self.stm.try_write_transaction(|tr| {
+            let mut val = match self.get(tr) {
+                Some(val) => val,
+                None => return STMResult::Retry,
+            };
+            let r = f(&mut val);
+            tr.store_record(self.base, &val);
+            STMResult::Ok(r)
+        })
+    }
+
+    /// The value, as of the last guard committed.
+    pub fn get<R: Trans>(&self, tr: &mut R) -> Option<T> {
+        record::load(tr, self.base)
+    }
+}
+
+impl<'a, T: BigValue> TxMutexGuard<'a, T> {
+    /// Store the value back, over any commit made since `lock`, retrying
+    /// on conflicts. Fails only if the STM is poisoned. A value left as it
+    /// was is not stored and never fails.
+    pub fn commit(mut self) -> Result<(), TxError> {
+        self.done = true;
+        self.store()
+    }
+
+    fn store(&self) -> Result<(), TxError> {
+        let m = self.mutex;
+        let mut block = vec![[0; STRIPE_SIZE]; T::STRIPES];
+        self.val.to_stripes(&mut block);
+        if block == self.seen {
+            return Ok(());
+        }
+        m.stm.try_write_transaction(|tr| {
+            for (i, stripe) in block.iter().enumerate() {
+                tr.store(m.base + i * STRIPE_SIZE, *stripe);
+            }
+            STMResult::Ok(())
+        })
+    }
+}
+
+impl<'a, T: BigValue> Deref for TxMutexGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.val
+    }
+}
+
+impl<'a, T: BigValue> DerefMut for TxMutexGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.val
+    }
+}
+
+impl<'a, T: BigValue> Drop for TxMutexGuard<'a, T> {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+        #[cfg(feature = "std")]
+        if std::thread::panicking() {
+            return;
+        }
+        if let Err(e) = self.store() {
+            panic!("TxMutexGuard dropped without committing: {}", e);
+        }
+    }
+}
@@ -0,0 +1,314 @@
+// An interpreter for fuzzing the protocol (the `testing` feature): bytes
+// decode into a script of transaction steps on a few simulated threads,
+// which runs against a small STM on one thread while a model predicts
+// every load, commit and conflict.
+
+use crate::tl2::{ReadTrans, STMResult, WriteTrans, STM, STRIPE_SIZE};
+use crate::{load, ConflictCause};
+
+const THREADS: usize = 3;
+const STRIPES: usize = 8;
+const MAX_STEPS: usize = 256;
+
+/// One step of a `TxScript`, on simulated thread `thread` (below 3), of
+/// stripe `stripe` (below 8), storing `val` (below 32). Steps needing a
+/// transaction do nothing on a thread without one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxStep {
+    /// Begin a write or a read transaction, unless one is open.
+    Begin {
+        thread: u8,
+        write: bool,
+    },
+    Load {
+        thread: u8,
+        stripe: u8,
+    },
+    /// Store in a write transaction; nothing in a read one.
+    Store {
+        thread: u8,
+        stripe: u8,
+        val: u8,
+    },
+    /// Commit a write transaction, validate a read one, and end it.
+    Commit {
+        thread: u8,
+    },
+    /// End the transaction without committing.
+    Abort {
+        thread: u8,
+    },
+    /// End the transaction and begin one of the same kind.
+    Retry {
+        thread: u8,
+    },
+}
+
+/// A bounded script of interleaved transaction steps, see `decode`.
+///
+/// `run` checks that every load returns the value of the stripe at the
+/// transaction's read version, or fails exactly when the stripe was
+/// written since; that a commit succeeds exactly when nothing the
+/// transaction read was written since, and at the next clock version;
+/// and that memory ends up as the committed stores left it, unlocked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxScript {
+    steps: Vec<TxStep>,
+}
+
+impl TxScript {
+    /// A script of the first 256 `steps`.
+    pub fn new(steps: Vec<TxStep>) -> TxScript {
+        let mut steps = steps;
+        steps.truncate(MAX_STEPS);
+        TxScript { steps }
+    }
+
+    /// Decode any bytes into a script. A step is a byte whose low 3 bits
+    /// pick the kind and whose rest picks the thread; loads and stores
+    /// take a second byte, whose low 3 bits pick the stripe and whose
+    /// rest is the value stored.
+    pub fn decode(data: &[u8]) -> TxScript {
+        let mut steps = Vec::new();
+        let mut bytes = data.iter();
+        while let Some(b) = bytes.next() {
+            if steps.len() == MAX_STEPS {
+                break;
+            }
+            let thread = (b >> 3) % THREADS as u8;
+            let step = match b & 7 {
+                0 | 1 => TxStep::Begin {
+                    thread,
+                    write: b & 7 == 1,
+                },
+                2 | 7 => match bytes.next() {
+                    Some(a) => TxStep::Load {
+                        thread,
+                        stripe: a & 7,
+                    },
+                    None => break,
+                },
+                3 => match bytes.next() {
+                    Some(a) => TxStep::Store {
+                        thread,
+                        stripe: a & 7,
+                        val: a >> 3,
+                    },
+                    None => break,
+                },
+                4 => TxStep::Commit { thread },
+                5 => TxStep::Abort { thread },
+                _ => TxStep::Retry { thread },
+            };
+            steps.push(step);
+        }
+        TxScript { steps }
+    }
+
+    /// The bytes `decode` turns into this script, e.g. to write a seed
+    /// corpus.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        let op = |kind: u8, thread: u8| kind | (thread % THREADS as u8) << 3;
+        for step in self.steps.iter() {
+            match *step {
+                TxStep::Begin { thread, write } => out.push(op(write as u8, thread)),
+                TxStep::Load { thread, stripe } => out.extend([op(2, thread), stripe & 7]),
+                TxStep::Store {
+                    thread,
+                    stripe,
+                    val,
+                } => out.extend([op(3, thread), stripe & 7 | (val & 31) << 3]),
+                TxStep::Commit { thread } => out.push(op(4, thread)),
+                TxStep::Abort { thread } => out.push(op(5, thread)),
+                TxStep::Retry { thread } => out.push(op(6, thread)),
+            }
+        }
+        out
+    }
+
+    pub fn steps(&self) -> &[TxStep] {
+        &self.steps
+    }
+
+    /// Run the script on a fresh STM.
+    ///
+    /// # Panics
+    ///
+    /// If the STM and the model disagree, naming the step.
+    pub fn run(&self) {
+        let stm = STM::builder().capacity(STRIPES * STRIPE_SIZE).build();
+        let mut model = Model {
+            val: [0; STRIPES],
+            ver: [0; STRIPES],
+        };
+        let mut threads: Vec<Option<Open>> = (0..THREADS).map(|_| None).collect();
+
+        for (i, step) in self.steps.iter().enumerate() {
+            match *step {
+                TxStep::Begin { thread, write } => {
+                    let t = &mut threads[thread as usize % THREADS];
+                    if t.is_none() {
+                        *t = Some(Open::begin(&stm, write, i));
+                    }
+                }
+                TxStep::Load { thread, stripe } => {
+                    if let Some(o) = &mut threads[thread as usize % THREADS] {
+                        o.load(&model, stripe as usize % STRIPES, i);
+                    }
+                }
+                TxStep::Store {
+                    thread,
+                    stripe,
+                    val,
+                } => {
+                    if let Some(o) = &mut threads[thread as usize % THREADS] {
+                        o.store(stripe as usize % STRIPES, val as u64);
+                    }
+                }
+                TxStep::Commit { thread } => {
+                    if let Some(o) = threads[thread as usize % THREADS].take() {
+                        o.commit(&stm, &mut model, i);
+                    }
+                }
+                TxStep::Abort { thread } => threads[thread as usize % THREADS] = None,
+                TxStep::Retry { thread } => {
+                    let t = &mut threads[thread as usize % THREADS];
+                    if let Some(o) = t.take() {
+                        let write = matches!(o.tr, Tx::Write(_));
+                        drop(o);
+                        *t = Some(Open::begin(&stm, write, i));
+                    }
+                }
+            }
+        }
+        drop(threads);
+
+        assert_eq!(stm.locked_stripes(), Vec::<usize>::new(), "locks left");
+        let mem = stm
+            .read_transaction(|tr| {
+                let mut mem = [0; STRIPES];
+                for (s, m) in mem.iter_mut().enumerate() {
+                    *m = u64::from_le_bytes(load!(tr, s * STRIPE_SIZE));
+                }
+                STMResult::Ok(mem)
+            })
+            .unwrap();
+        assert_eq!(mem, model.val, "memory after the script");
+    }
+}
+
+// The committed state: each stripe's value and the version of its last
+// commit.
+struct Model {
+    val: [u64; STRIPES],
+    ver: [u64; STRIPES],
+}
+
+enum Tx<'a> {
+    Read(ReadTrans<'a>),
+    Write(WriteTrans<'a>),
+}
+
+// An open transaction and what the model expects of it.
+struct Open<'a> {
+    tr: Tx<'a>,
+    rv: u64,
+    read: [bool; STRIPES],
+    written: [Option<u64>; STRIPES],
+    doomed: bool, // a load failed
+}
+
+impl<'a> Open<'a> {
+    fn begin(stm: &'a STM, write: bool, step: usize) -> Open<'a> {
+        let (tr, rv) = if write {
+            let tr = stm.open_write();
+            let rv = tr.read_version();
+            (Tx::Write(tr), rv)
+        } else {
+            let tr = stm.open_read();
+            let rv = tr.read_version();
+            (Tx::Read(tr), rv)
+        };
+        assert_eq!(rv, stm.current_version(), "step {}: read version", step);
+        Open {
+            tr,
+            rv,
+            read: [false; STRIPES],
+            written: [None; STRIPES],
+            doomed: false,
+        }
+    }
+
+    // Whether a stripe this transaction read was committed to since.
+    fn stale(&self, model: &Model) -> bool {
+        (0..STRIPES).any(|s| self.read[s] && model.ver[s] > self.rv)
+    }
+
+    fn load(&mut self, model: &Model, stripe: usize, step: usize) {
+        let addr = stripe * STRIPE_SIZE;
+        let got = match &mut self.tr {
+            Tx::Read(tr) => tr.load(addr),
+            Tx::Write(tr) => tr.load(addr),
+        };
+        let expected = if self.doomed {
+            None
+        } else if let Some(v) = self.written[stripe] {
+            Some(v)
+        } else if model.ver[stripe] > self.rv {
+            None
+        } else {
+            Some(model.val[stripe])
+        };
+        assert_eq!(
+            got.map(u64::from_le_bytes),
+            expected,
+            "step {}: load of stripe {} at version {}",
+            step,
+            stripe,
+            self.rv
+        );
+        self.read[stripe] = true;
+        self.doomed = expected.is_none();
+    }
+
+    fn store(&mut self, stripe: usize, val: u64) {
+        if let Tx::Write(tr) = &mut self.tr {
+            tr.store(stripe * STRIPE_SIZE, val.to_le_bytes());
+            self.written[stripe] = Some(val);
+        }
+    }
+
+    fn commit(self, stm: &STM, model: &mut Model, step: usize) {
+        let ok = !self.doomed && !self.stale(model);
+        let mut tr = match self.tr {
+            Tx::Read(mut tr) => {
+                assert_eq!(tr.validate(), ok, "step {}: validation", step);
+                return;
+            }
+            Tx::Write(tr) => tr,
+        };
+
+        let clock = stm.current_version();
+        match stm.commit_open(&mut tr) {
+            Ok(ver) => {
+                assert!(ok, "step {}: committed a stale transaction", step);
+                assert_eq!(ver, clock + 1, "step {}: commit version", step);
+                for (s, v) in self.written.iter().enumerate() {
+                    if let Some(v) = v {
+                        model.val[s] = *v;
+                        model.ver[s] = ver;
+                    }
+                }
+            }
+            Err(c) => {
+                assert!(!ok, "step {}: spurious conflict {:?}", step, c);
+                if !self.doomed {
+                    assert_eq!(c.cause, ConflictCause::Validation, "step {}", step);
+                    let s = c.addr.unwrap() / STRIPE_SIZE;
+                    assert!(self.read[s] && model.ver[s] > self.rv, "step {}", step);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use crate::tl2::STRIPE_SIZE;
+
+/// A value that fits in one stripe. Integers are stored little-endian and
+/// zero-extended, so an untouched (zeroed) stripe decodes as zero/false.
+pub trait StripeValue: Copy {
+    fn to_stripe(&self) -> [u8; STRIPE_SIZE];
+    fn from_stripe(stripe: [u8; STRIPE_SIZE]) -> Self;
+}
+
+macro_rules! impl_stripe_value {
+    ($($t:ty),*) => {
+        $(
+            impl StripeValue for $t {
+                fn to_stripe(&self) -> [u8; STRIPE_SIZE] {
+                    let mut buf = [0; STRIPE_SIZE];
+                    let bytes = self.to_le_bytes();
+                    buf[..bytes.len()].copy_from_slice(&bytes);
+                    buf
+                }
+
+                fn from_stripe(stripe: [u8; STRIPE_SIZE]) -> Self {
+                    let mut bytes = [0; core::mem::size_of::<$t>()];
+                    let len = bytes.len();
+                    bytes.copy_from_slice(&stripe[..len]);
+                    <$t>::from_le_bytes(bytes)
+                }
+            }
+        )*
+    };
+}
+
+impl_stripe_value!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+impl StripeValue for bool {
+    fn to_stripe(&self) -> [u8; STRIPE_SIZE] {
+        (*self as u8).to_stripe()
+    }
+
+    fn from_stripe(stripe: [u8; STRIPE_SIZE]) -> Self {
+        stripe[0] != 0
+    }
+}
+
+impl StripeValue for [u8; STRIPE_SIZE] {
+    fn to_stripe(&self) -> [u8; STRIPE_SIZE] {
+        *self
+    }
+
+    fn from_stripe(stripe: [u8; STRIPE_SIZE]) -> Self {
+        stripe
+    }
+}
+
+/// A value spanning `STRIPES` consecutive stripes, see `TBig` and
+/// `WriteTrans::load_record`. `trans_record!` implements it for a struct
+/// of `StripeValue` fields.
+pub trait BigValue: Sized {
+    const STRIPES: usize;
+    /// Encode into `out`, which holds `STRIPES` zeroed stripes.
+    fn to_stripes(&self, out: &mut [[u8; STRIPE_SIZE]]);
+    fn from_stripes(stripes: &[[u8; STRIPE_SIZE]]) -> Self;
+}
+
+// A single stripe is one too, so `load_record` and `TxMutex` take plain
+// integers. Not for every `StripeValue`, which would include the arrays
+// below.
+macro_rules! impl_big_value {
+    ($($t:ty),*) => {
+        $(
+            impl BigValue for $t {
+                const STRIPES: usize = 1;
+
+                fn to_stripes(&self, out: &mut [[u8; STRIPE_SIZE]]) {
+                    out[0] = self.to_stripe();
+                }
+
+                fn from_stripes(stripes: &[[u8; STRIPE_SIZE]]) -> Self {
+                    <$t>::from_stripe(stripes[0])
+                }
+            }
+        )*
+    };
+}
+
+impl_big_value!(u8, u16, u32, u64, i8, i16, i32, i64, bool);
+
+impl<T: StripeValue, const N: usize> BigValue for [T; N] {
+    const STRIPES: usize = N;
+
+    fn to_stripes(&self, out: &mut [[u8; STRIPE_SIZE]]) {
+        for (o, v) in out.iter_mut().zip(self.iter()) {
+            *o = v.to_stripe();
+        }
+    }
+
+    fn from_stripes(stripes: &[[u8; STRIPE_SIZE]]) -> Self {
+        core::array::from_fn(|i| T::from_stripe(stripes[i]))
+    }
+}
+
+// A presence stripe, 1 for `Some`, ahead of the value, so that an empty
+// value is told apart from a zero one. `None` is all zeros, which is also
+// what never-written memory reads as.
+impl<T: BigValue> BigValue for Option<T> {
+    const STRIPES: usize = T::STRIPES + 1;
+
+    fn to_stripes(&self, out: &mut [[u8; STRIPE_SIZE]]) {
+        if let Some(val) = self {
+            out[0] = true.to_stripe();
+            val.to_stripes(&mut out[1..]);
+        }
+    }
+
+    fn from_stripes(stripes: &[[u8; STRIPE_SIZE]]) -> Self {
+        if bool::from_stripe(stripes[0]) {
+            Some(T::from_stripes(&stripes[1..]))
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use alloc::vec::Vec;
+
+use crate::tl2::{check_aligned, Trans, WriteTrans, STRIPE_SIZE};
+use crate::value::StripeValue;
+
+/// A vector clock with one `u64` entry per node, each in its own stripe, so
+/// nodes ticking their own entries never conflict with each other.
+pub struct VClock {
+    base: usize,
+    nodes: usize,
+}
+
+impl VClock {
+    /// Lay out a clock for `nodes` nodes starting at the stripe-aligned
+    /// address `base`; it takes `nodes * 8` bytes.
+    pub fn new(base: usize, nodes: usize) -> VClock {
+        check_aligned(base);
+        nodes
+            .checked_mul(STRIPE_SIZE)
+            .and_then(|size| base.checked_add(size))
+            .expect("clock end overflows usize");
+
+        VClock { base, nodes }
+    }
+
+    pub fn nodes(&self) -> usize {
+        self.nodes
+    }
+
+    fn addr(&self, node: usize) -> usize {
+        assert!(node < self.nodes);
+        self.base + node * STRIPE_SIZE
+    }
+
+    /// The entry of `node`.
+    pub fn get<R: Trans>(&self, tr: &mut R, node: usize) -> Option<u64> {
+        tr.load(self.addr(node)).map(u64::from_stripe)
+    }
+
+    /// All entries, read at one version.
+    pub fn snapshot<R: Trans>(&self, tr: &mut R) -> Option<Vec<u64>> {
+        (0..self.nodes).map(|node| self.get(tr, node)).collect()
+    }
+
+    /// Tick the entry of `node` and return its new value.
+    pub fn increment(&self, tr: &mut WriteTrans, node: usize) -> Option<u64> {
+        let n = self.get(tr, node)?.wrapping_add(1);
+        tr.store(self.addr(node), n.to_stripe());
+        Some(n)
+    }
+
+    /// Raise every entry to at least the matching one of `other`, which
+    /// must have an entry per node. Only entries that grow are stored.
+    pub fn merge(&self, tr: &mut WriteTrans, other: &[u64]) -> Option<()> {
+        assert_eq!(other.len(), self.nodes);
+        for (node, &v) in other.iter().enumerate() {
+            tr.update_max_u64(self.addr(node), v)?;
+        }
+        Some(())
+    }
+}
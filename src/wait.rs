@@ -0,0 +1,262 @@
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicU32, Ordering};
+#[cfg(feature = "std")]
+use std::sync::Mutex;
+#[cfg(feature = "std")]
+use std::thread::{self, Thread};
+#[cfg(feature = "std")]
+use std::time::Duration;
+
+use crate::sync::{fence, AtomicUsize};
+
+// Turns `SpinWait` spins before returning for the caller to check again.
+const SPIN_TURNS: u32 = 1 << 10;
+
+// Longest `ParkWait` and `FutexWait` sleep without a notify. Commits of
+// another process attached to a shared region do not notify this one.
+#[cfg(feature = "std")]
+const PARK_TIMEOUT: Duration = Duration::from_millis(1);
+
+/// What a blocked thread waits for: `word` to move away from `seen`.
+#[derive(Debug, Clone, Copy)]
+pub struct WaitToken<'a> {
+    word: &'a AtomicU32,
+    seen: u32,
+}
+
+impl<'a> WaitToken<'a> {
+    /// The word that moves, e.g. to wait on with a futex.
+    pub fn word(&self) -> &'a AtomicU32 {
+        self.word
+    }
+
+    /// The value of the word the waiter saw.
+    pub fn seen(&self) -> u32 {
+        self.seen
+    }
+
+    /// Whether the word has moved, ending the wait.
+    pub fn is_done(&self) -> bool {
+        self.word.load(Ordering::Acquire) != self.seen
+    }
+}
+
+/// How a thread blocks until a write transaction finishes: a
+/// `write_transaction_blocking` body that returned `Retry`, and a
+/// prioritized writer waiting for the lock holder it wounded (see
+/// `write_transaction_prio`). Set with `STMBuilder::wait_strategy`; the
+/// other spin-waits use `Relax`.
+///
+/// `wait` returns once the token is done but may return earlier: the
+/// caller checks again and waits anew. `notify` is called after the
+/// word of the token moved, with the value it moved from, and must wake
+/// every thread waiting on that word.
+pub trait WaitStrategy: Send + Sync {
+    fn wait(&self, token: WaitToken<'_>);
+    fn notify(&self, token: WaitToken<'_>);
+}
+
+/// Spins on the word, returning after a bounded number of turns. The
+/// default without the `std` feature.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SpinWait;
+
+impl WaitStrategy for SpinWait {
+    fn wait(&self, token: WaitToken<'_>) {
+        for _ in 0..SPIN_TURNS {
+            if token.is_done() {
+                return;
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    fn notify(&self, _: WaitToken<'_>) {}
+}
+
+/// Spins a few turns, then parks the thread with `std::thread::park`
+/// until notified. The default with the `std` feature.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct ParkWait {
+    spins: u32,
+    parked: Mutex<Vec<(usize, Thread)>>, // the word waited on, the waiter
+}
+
+#[cfg(feature = "std")]
+impl ParkWait {
+    /// Spin `spins` turns before parking; 0 parks right away.
+    pub fn new(spins: u32) -> ParkWait {
+        ParkWait {
+            spins,
+            parked: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for ParkWait {
+    fn default() -> Self {
+        ParkWait::new(100)
+    }
+}
+
+#[cfg(feature = "std")]
+impl WaitStrategy for ParkWait {
+    fn wait(&self, token: WaitToken<'_>) {
+        for _ in 0..self.spins {
+            if token.is_done() {
+                return;
+            }
+            core::hint::spin_loop();
+        }
+
+        let key = token.word() as *const AtomicU32 as usize;
+        let me = thread::current();
+        {
+            // the word moves before `notify` takes the lock, so checking
+            // under it does not miss a notify
+            let mut parked = self.parked.lock().unwrap();
+            if token.is_done() {
+                return;
+            }
+            parked.push((key, me.clone()));
+        }
+        thread::park_timeout(PARK_TIMEOUT);
+        let id = me.id();
+        self.parked.lock().unwrap().retain(|(_, t)| t.id() != id);
+    }
+
+    fn notify(&self, token: WaitToken<'_>) {
+        let key = token.word() as *const AtomicU32 as usize;
+        self.parked.lock().unwrap().retain(|(k, t)| {
+            if *k == key {
+                t.unpark();
+            }
+            *k != key
+        });
+    }
+}
+
+/// Sleeps in the kernel with a private futex on the word (Linux, the
+/// `futex` feature).
+#[cfg(all(target_os = "linux", feature = "futex"))]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FutexWait;
+
+#[cfg(all(target_os = "linux", feature = "futex"))]
+impl WaitStrategy for FutexWait {
+    fn wait(&self, token: WaitToken<'_>) {
+        let timeout = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: PARK_TIMEOUT.as_nanos() as libc::c_long,
+        };
+        // SAFETY: the word outlives the call; the kernel only reads it.
+        // Interrupted or timed out waits return early, which is allowed.
+        unsafe {
+            libc::syscall(
+                libc::SYS_futex,
+                token.word().as_ptr(),
+                libc::FUTEX_WAIT | libc::FUTEX_PRIVATE_FLAG,
+                token.seen(),
+                &timeout as *const libc::timespec,
+            );
+        }
+    }
+
+    fn notify(&self, token: WaitToken<'_>) {
+        // SAFETY: as in `wait`
+        unsafe {
+            libc::syscall(
+                libc::SYS_futex,
+                token.word().as_ptr(),
+                libc::FUTEX_WAKE | libc::FUTEX_PRIVATE_FLAG,
+                i32::MAX,
+            );
+        }
+    }
+}
+
+#[cfg(all(
+    feature = "std",
+    not(all(target_family = "wasm", target_feature = "atomics"))
+))]
+pub(crate) fn default() -> Arc<dyn WaitStrategy> {
+    Arc::new(ParkWait::default())
+}
+
+// browsers forbid blocking on the main thread
+#[cfg(not(all(
+    feature = "std",
+    not(all(target_family = "wasm", target_feature = "atomics"))
+)))]
+pub(crate) fn default() -> Arc<dyn WaitStrategy> {
+    Arc::new(SpinWait)
+}
+
+// The waiters of a Memory. A waiter registers and reads `epoch`, then
+// checks its condition again before waiting; a write transaction that
+// finishes bumps `epoch` if anyone is registered. The SeqCst fences on
+// both sides make sure the check sees the change or the bump sees the
+// waiter.
+pub(crate) struct Waits {
+    strategy: Arc<dyn WaitStrategy>,
+    epoch: AtomicU32,
+    waiting: AtomicUsize,
+}
+
+// A registered waiter, unregistered on drop.
+pub(crate) struct Waiter<'a> {
+    waits: &'a Waits,
+    seen: u32,
+}
+
+impl Waits {
+    pub(crate) fn new(strategy: Arc<dyn WaitStrategy>) -> Waits {
+        Waits {
+            strategy,
+            epoch: AtomicU32::new(0),
+            waiting: AtomicUsize::new(0),
+        }
+    }
+
+    pub(crate) fn register(&self) -> Waiter<'_> {
+        self.waiting.fetch_add(1, Ordering::SeqCst);
+        fence(Ordering::SeqCst);
+        Waiter {
+            waits: self,
+            seen: self.epoch.load(Ordering::SeqCst),
+        }
+    }
+
+    pub(crate) fn waiting(&self) -> usize {
+        self.waiting.load(Ordering::SeqCst)
+    }
+
+    // Called after a write transaction released its locks.
+    pub(crate) fn released(&self) {
+        fence(Ordering::SeqCst);
+        if self.waiting.load(Ordering::SeqCst) > 0 {
+            let seen = self.epoch.fetch_add(1, Ordering::SeqCst);
+            self.strategy.notify(WaitToken {
+                word: &self.epoch,
+                seen,
+            });
+        }
+    }
+}
+
+impl<'a> Waiter<'a> {
+    pub(crate) fn wait(self) {
+        self.waits.strategy.wait(WaitToken {
+            word: &self.waits.epoch,
+            seen: self.seen,
+        });
+    }
+}
+
+impl<'a> Drop for Waiter<'a> {
+    fn drop(&mut self) {
+        self.waits.waiting.fetch_sub(1, Ordering::SeqCst);
+    }
+}
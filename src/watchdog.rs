@@ -0,0 +1,157 @@
+use std::sync::atomic::{fence, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::contention::TxPriority;
+
+const NUM_SLOTS: usize = 64;
+
+// Low two bits of a slot's state; the rest is a generation bumped on every
+// release so a reader can tell a reused slot from the one it started with.
+const FREE: u64 = 0;
+const CLAIMED: u64 = 1;
+const BUSY: u64 = 2;
+const STATE: u64 = 3;
+
+/// A transaction reported by `STM::check_stalled`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StalledTx {
+    pub label: Option<&'static str>,
+    pub read_only: bool,
+    /// The `TxPriority` hint of the attempt currently running.
+    pub priority: TxPriority,
+    /// The attempt currently running, starting at 1.
+    pub attempts: u32,
+    /// Time since the transaction began.
+    pub age: Duration,
+}
+
+#[repr(align(64))]
+#[derive(Default)]
+struct Slot {
+    state: AtomicU64,
+    bucket: AtomicUsize, // label bucket, see `Labels`
+    read_only: AtomicU32,
+    priority: AtomicU32,
+    start: AtomicU64, // nanoseconds since `Watchdog::epoch`
+    attempts: AtomicU32,
+}
+
+// Running transactions, one slot each. A transaction claims a slot with a
+// single CAS, probing at most NUM_SLOTS slots starting at its thread's own,
+// so registering is wait-free; when every slot is taken the transaction
+// simply goes unwatched.
+//
+// A slot is published like a seqlock: the owner claims it (FREE -> CLAIMED),
+// fills in the fields, then marks it BUSY with Release. A reader only trusts
+// fields read between two equal loads of a BUSY state.
+pub(crate) struct Watchdog {
+    epoch: Instant,
+    slots: Vec<Slot>,
+}
+
+fn slot_hint() -> usize {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    thread_local! {
+        static HINT: usize = NEXT.fetch_add(1, Ordering::Relaxed) % NUM_SLOTS;
+    }
+    HINT.with(|h| *h)
+}
+
+impl Watchdog {
+    pub(crate) fn new() -> Watchdog {
+        Watchdog {
+            epoch: Instant::now(),
+            slots: (0..NUM_SLOTS).map(|_| Slot::default()).collect(),
+        }
+    }
+
+    fn now(&self) -> u64 {
+        self.epoch.elapsed().as_nanos().min(u64::MAX as u128) as u64
+    }
+
+    pub(crate) fn watch(&self, bucket: usize, read_only: bool) -> Option<Watched<'_>> {
+        let hint = slot_hint();
+        for i in 0..NUM_SLOTS {
+            let slot = &self.slots[(hint + i) % NUM_SLOTS];
+            let s = slot.state.load(Ordering::Relaxed);
+            if s & STATE != FREE {
+                continue;
+            }
+            if slot
+                .state
+                .compare_exchange(s, s | CLAIMED, Ordering::Acquire, Ordering::Relaxed)
+                .is_err()
+            {
+                continue;
+            }
+            fence(Ordering::Release);
+
+            slot.bucket.store(bucket, Ordering::Relaxed);
+            slot.read_only.store(read_only as u32, Ordering::Relaxed);
+            slot.start.store(self.now(), Ordering::Relaxed);
+            slot.attempts.store(0, Ordering::Relaxed);
+            slot.priority
+                .store(TxPriority::Normal as u32, Ordering::Relaxed);
+            slot.state.store(s | BUSY, Ordering::Release);
+            return Some(Watched { slot });
+        }
+        None
+    }
+
+    // (label bucket, read-only, priority, attempts, age) of every watched transaction
+    // older than `max_age` or past `max_attempts`.
+    pub(crate) fn stalled(
+        &self,
+        max_age: Duration,
+        max_attempts: u32,
+    ) -> Vec<(usize, bool, TxPriority, u32, Duration)> {
+        let now = self.now();
+        let mut v = Vec::new();
+        for slot in self.slots.iter() {
+            let s1 = slot.state.load(Ordering::Acquire);
+            if s1 & STATE != BUSY {
+                continue;
+            }
+            let bucket = slot.bucket.load(Ordering::Relaxed);
+            let read_only = slot.read_only.load(Ordering::Relaxed) != 0;
+            let start = slot.start.load(Ordering::Relaxed);
+            let attempts = slot.attempts.load(Ordering::Relaxed);
+            let priority = match slot.priority.load(Ordering::Relaxed) {
+                0 => TxPriority::Low,
+                1 => TxPriority::Normal,
+                _ => TxPriority::High,
+            };
+            fence(Ordering::Acquire);
+            if slot.state.load(Ordering::Relaxed) != s1 {
+                continue;
+            }
+
+            let age = Duration::from_nanos(now.saturating_sub(start));
+            if age > max_age || attempts > max_attempts {
+                v.push((bucket, read_only, priority, attempts, age));
+            }
+        }
+        v
+    }
+}
+
+// The slot of a running transaction, released on drop.
+pub(crate) struct Watched<'a> {
+    slot: &'a Slot,
+}
+
+impl<'a> Watched<'a> {
+    pub(crate) fn attempt(&self, attempt: u32, priority: TxPriority) {
+        self.slot.attempts.store(attempt, Ordering::Relaxed);
+        self.slot.priority.store(priority as u32, Ordering::Relaxed);
+    }
+}
+
+impl<'a> Drop for Watched<'a> {
+    fn drop(&mut self) {
+        let s = self.slot.state.load(Ordering::Relaxed);
+        self.slot
+            .state
+            .store((s & !STATE) + (STATE + 1), Ordering::Release);
+    }
+}
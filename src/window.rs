@@ -0,0 +1,183 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Length of the throughput window in seconds.
+pub const WINDOW_SECS: u64 = 60;
+
+/// Commits, attempts and clock movement over the last `WINDOW_SECS`
+/// seconds (or since the STM was built, if that is shorter), see
+/// `STM::throughput_window`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThroughputWindow {
+    pub span: Duration,
+    /// Committed write transactions.
+    pub commits: u64,
+    /// Runs of read and write transaction bodies, restarts included.
+    pub attempts: u64,
+    /// How far the global clock advanced.
+    pub clock_delta: u64,
+}
+
+impl ThroughputWindow {
+    pub fn commits_per_sec(&self) -> f64 {
+        per_sec(self.commits, self.span)
+    }
+
+    pub fn attempts_per_sec(&self) -> f64 {
+        per_sec(self.attempts, self.span)
+    }
+
+    pub fn clock_per_sec(&self) -> f64 {
+        per_sec(self.clock_delta, self.span)
+    }
+}
+
+/// The global clock at an instant, see `STM::clock_sample`. Two samples
+/// give the clock's advance between them without enabling stats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockSample {
+    pub clock: u64,
+    pub at: Instant,
+}
+
+impl ClockSample {
+    /// How far the clock moved from `earlier` to this sample.
+    pub fn since(&self, earlier: &ClockSample) -> ClockRate {
+        ClockRate {
+            span: self.at.saturating_duration_since(earlier.at),
+            delta: self.clock.saturating_sub(earlier.clock),
+        }
+    }
+}
+
+/// Clock advance over a span, see `ClockSample::since`. With the default
+/// clock every commit that writes advances it by one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockRate {
+    pub span: Duration,
+    pub delta: u64,
+}
+
+impl ClockRate {
+    pub fn per_sec(&self) -> f64 {
+        per_sec(self.delta, self.span)
+    }
+}
+
+fn per_sec(n: u64, span: Duration) -> f64 {
+    if span.is_zero() {
+        0.0
+    } else {
+        n as f64 / span.as_secs_f64()
+    }
+}
+
+#[derive(Default)]
+struct Bucket {
+    second: AtomicU64, // seconds since the epoch, plus one; 0 = never used
+    commits: AtomicU64,
+    attempts: AtomicU64,
+    clock: AtomicU64, // lowest clock sampled during the second
+}
+
+// One bucket per second in a ring. Nothing advances the ring in the
+// background: the first update in a new second claims and clears the
+// bucket, and readers skip buckets older than the window. Updates racing
+// with a claim may be lost, which only makes the numbers approximate.
+pub(crate) struct Window {
+    epoch: Instant,
+    buckets: Vec<Bucket>,
+    #[cfg(feature = "testing")]
+    skipped: AtomicU64, // nanoseconds added to the time since `epoch`
+}
+
+impl Window {
+    pub(crate) fn new() -> Window {
+        Window {
+            epoch: Instant::now(),
+            buckets: (0..WINDOW_SECS).map(|_| Bucket::default()).collect(),
+            #[cfg(feature = "testing")]
+            skipped: AtomicU64::new(0),
+        }
+    }
+
+    fn bucket(&self, second: u64) -> &Bucket {
+        let b = &self.buckets[(second % WINDOW_SECS) as usize];
+        let cur = b.second.load(Ordering::Acquire);
+        if cur != second + 1
+            && b.second
+                .compare_exchange(cur, second + 1, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+        {
+            b.commits.store(0, Ordering::Relaxed);
+            b.attempts.store(0, Ordering::Relaxed);
+            b.clock.store(u64::MAX, Ordering::Relaxed);
+        }
+        b
+    }
+
+    fn elapsed(&self) -> Duration {
+        let elapsed = self.epoch.elapsed();
+        #[cfg(feature = "testing")]
+        let elapsed = elapsed + Duration::from_nanos(self.skipped.load(Ordering::Relaxed));
+        elapsed
+    }
+
+    // Act as if `d` more time had passed.
+    #[cfg(feature = "testing")]
+    pub(crate) fn skip(&self, d: Duration) {
+        let nanos = d.as_nanos().min(u64::MAX as u128) as u64;
+        self.skipped.fetch_add(nanos, Ordering::Relaxed);
+    }
+
+    fn now(&self) -> u64 {
+        self.elapsed().as_secs()
+    }
+
+    // An attempt that began with the clock at `clock`.
+    pub(crate) fn attempt(&self, clock: u64) {
+        let b = self.bucket(self.now());
+        b.attempts.fetch_add(1, Ordering::Relaxed);
+        b.clock.fetch_min(clock, Ordering::Relaxed);
+    }
+
+    pub(crate) fn commit(&self) {
+        self.bucket(self.now())
+            .commits
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    // `clock` is the current value of the global clock.
+    pub(crate) fn report(&self, clock: u64) -> ThroughputWindow {
+        let elapsed = self.elapsed();
+        let now = elapsed.as_secs();
+        let oldest = (now + 1).saturating_sub(WINDOW_SECS);
+        let mut w = ThroughputWindow {
+            span: elapsed.saturating_sub(Duration::from_secs(oldest)),
+            commits: 0,
+            attempts: 0,
+            clock_delta: 0,
+        };
+
+        let mut lowest = u64::MAX;
+        for b in self.buckets.iter() {
+            match b.second.load(Ordering::Acquire).checked_sub(1) {
+                Some(s) if s >= oldest && s <= now => {}
+                _ => continue,
+            }
+            w.commits += b.commits.load(Ordering::Relaxed);
+            w.attempts += b.attempts.load(Ordering::Relaxed);
+            lowest = lowest.min(b.clock.load(Ordering::Relaxed));
+        }
+        if lowest != u64::MAX {
+            w.clock_delta = clock.saturating_sub(lowest);
+        }
+        w
+    }
+
+    pub(crate) fn reset(&self) {
+        for b in self.buckets.iter() {
+            b.second.store(0, Ordering::Release);
+        }
+    }
+}
@@ -0,0 +1,478 @@
+// Recording what transactions touch and replaying it (the `trace`
+// feature), to reproduce a contention profile away from the workload.
+
+use std::cell::Cell;
+use std::convert::TryInto;
+use std::fmt;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::tl2::{STMResult, STM, STRIPE_SIZE};
+
+const MAGIC: &[u8; 4] = b"TL2T";
+const FORMAT: u8 = 1;
+
+static NEXT_THREAD: AtomicU32 = AtomicU32::new(0);
+
+thread_local! {
+    static THREAD: u32 = NEXT_THREAD.fetch_add(1, Ordering::Relaxed);
+    static TICK: Cell<u32> = const { Cell::new(0) };
+}
+
+/// Records the transactions of an STM, set with
+/// `STMBuilder::trace_recorder`: for every sampled transaction, its
+/// thread, label, start and duration, attempts, and the addresses the
+/// last attempt read and wrote. Values are not recorded.
+///
+/// Sampling is per thread and counts transactions, so recording every
+/// 100th costs the others a thread-local increment. Read transactions
+/// take the slower path that reports them while a recorder is set.
+pub struct TraceRecorder {
+    every: u32,
+    start: Instant,
+    records: Mutex<Vec<Recorded>>,
+}
+
+struct Recorded {
+    thread: u32,
+    label: Option<&'static str>,
+    start: Duration,
+    duration: Duration,
+    attempts: u32,
+    read_only: bool,
+    committed: bool,
+    reads: Vec<usize>,
+    writes: Vec<usize>,
+}
+
+// The sets of a sampled transaction's latest attempt.
+pub(crate) struct Traced {
+    start: Instant,
+    reads: Vec<usize>,
+    writes: Vec<usize>,
+}
+
+impl Traced {
+    pub(crate) fn sets<'a, R, W>(&mut self, reads: R, writes: W)
+    where
+        R: Iterator<Item = &'a usize>,
+        W: Iterator<Item = &'a usize>,
+    {
+        self.reads.clear();
+        self.reads.extend(reads);
+        self.writes.clear();
+        self.writes.extend(writes);
+    }
+}
+
+impl TraceRecorder {
+    /// Record every `every`-th transaction of each thread; 1 records all.
+    pub fn new(every: u32) -> TraceRecorder {
+        assert!(every > 0);
+        TraceRecorder {
+            every,
+            start: Instant::now(),
+            records: Mutex::new(Vec::new()),
+        }
+    }
+
+    // A transaction begins: `Some` if it is to be recorded.
+    pub(crate) fn sample(&self) -> Option<Traced> {
+        let tick = TICK.with(|t| {
+            let n = t.get();
+            t.set(n.wrapping_add(1));
+            n
+        });
+        if !tick.is_multiple_of(self.every) {
+            return None;
+        }
+        Some(Traced {
+            start: Instant::now(),
+            reads: Vec::new(),
+            writes: Vec::new(),
+        })
+    }
+
+    pub(crate) fn record(
+        &self,
+        label: Option<&'static str>,
+        read_only: bool,
+        committed: bool,
+        attempts: u32,
+        t: &Traced,
+    ) {
+        let rec = Recorded {
+            thread: THREAD.with(|n| *n),
+            label,
+            start: t.start.saturating_duration_since(self.start),
+            duration: t.start.elapsed(),
+            attempts,
+            read_only,
+            committed,
+            reads: t.reads.clone(),
+            writes: t.writes.clone(),
+        };
+        self.records.lock().unwrap().push(rec);
+    }
+
+    /// Take what was recorded so far, ordered by start. Threads are
+    /// numbered from 0 in the order they first appear.
+    pub fn take(&self) -> Trace {
+        let mut recorded = std::mem::take(&mut *self.records.lock().unwrap());
+        recorded.sort_by_key(|r| r.start);
+
+        let mut trace = Trace {
+            labels: Vec::new(),
+            records: Vec::with_capacity(recorded.len()),
+        };
+        let mut threads = Vec::new();
+        for r in recorded {
+            let thread = match threads.iter().position(|t| *t == r.thread) {
+                Some(i) => i,
+                None => {
+                    threads.push(r.thread);
+                    threads.len() - 1
+                }
+            };
+            let label = r
+                .label
+                .map(|l| match trace.labels.iter().position(|have| have == l) {
+                    Some(i) => i,
+                    None => {
+                        trace.labels.push(l.to_string());
+                        trace.labels.len() - 1
+                    }
+                });
+            let mut reads = r.reads;
+            let mut writes = r.writes;
+            reads.sort_unstable();
+            writes.sort_unstable();
+            trace.records.push(TraceRecord {
+                thread: thread as u32,
+                label,
+                start: r.start,
+                duration: r.duration,
+                attempts: r.attempts,
+                read_only: r.read_only,
+                committed: r.committed,
+                reads,
+                writes,
+            });
+        }
+        trace
+    }
+}
+
+/// A recorded transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceRecord {
+    pub thread: u32,
+    /// Index into `Trace::labels`.
+    pub label: Option<usize>,
+    /// Since the recorder was created.
+    pub start: Duration,
+    /// From the start of the first attempt to the end of the last.
+    pub duration: Duration,
+    pub attempts: u32,
+    pub read_only: bool,
+    /// Whether it committed rather than failed or aborted.
+    pub committed: bool,
+    /// Addresses loaded and stored by the last attempt, sorted.
+    pub reads: Vec<usize>,
+    pub writes: Vec<usize>,
+}
+
+/// Transactions taken from a `TraceRecorder`, saved and loaded with
+/// `to_bytes` and `from_bytes`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Trace {
+    labels: Vec<String>,
+    records: Vec<TraceRecord>,
+}
+
+/// Why `Trace::from_bytes` failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceError {
+    /// Not a trace, or one of another format version.
+    Header,
+    /// The data ends early or holds an impossible value.
+    Corrupt,
+}
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceError::Header => write!(f, "not a trace of format {}", FORMAT),
+            TraceError::Corrupt => write!(f, "trace data is corrupt"),
+        }
+    }
+}
+
+impl std::error::Error for TraceError {}
+
+impl Trace {
+    pub fn labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    pub fn records(&self) -> &[TraceRecord] {
+        &self.records
+    }
+
+    /// Bytes of memory an STM needs to replay the trace.
+    pub fn capacity(&self) -> usize {
+        self.records
+            .iter()
+            .flat_map(|r| r.reads.iter().chain(r.writes.iter()))
+            .max()
+            .map_or(0, |a| a + STRIPE_SIZE)
+    }
+
+    /// The compact form: a header, the labels, and the records with
+    /// variable-length integers, addresses in stripes and delta-coded.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.push(FORMAT);
+        put(&mut out, self.labels.len() as u64);
+        for l in self.labels.iter() {
+            put(&mut out, l.len() as u64);
+            out.extend_from_slice(l.as_bytes());
+        }
+        put(&mut out, self.records.len() as u64);
+        for r in self.records.iter() {
+            put(&mut out, r.thread as u64);
+            put(&mut out, r.label.map_or(0, |l| l as u64 + 1));
+            put(&mut out, r.start.as_nanos() as u64);
+            put(&mut out, r.duration.as_nanos() as u64);
+            put(&mut out, r.attempts as u64);
+            out.push(r.read_only as u8 | (r.committed as u8) << 1);
+            put_addrs(&mut out, &r.reads);
+            put_addrs(&mut out, &r.writes);
+        }
+        out
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Trace, TraceError> {
+        if data.len() < 5 || &data[..4] != MAGIC || data[4] != FORMAT {
+            return Err(TraceError::Header);
+        }
+        let mut r = Reader(&data[5..]);
+        let mut trace = Trace::default();
+        for _ in 0..r.get()? {
+            let len = r.get()? as usize;
+            let bytes = r.bytes(len)?;
+            let label = std::str::from_utf8(bytes).map_err(|_| TraceError::Corrupt)?;
+            trace.labels.push(label.to_string());
+        }
+        for _ in 0..r.get()? {
+            let thread = r.get()?.try_into().map_err(|_| TraceError::Corrupt)?;
+            let label = match r.get()? {
+                0 => None,
+                l if l as usize <= trace.labels.len() => Some(l as usize - 1),
+                _ => return Err(TraceError::Corrupt),
+            };
+            let start = Duration::from_nanos(r.get()?);
+            let duration = Duration::from_nanos(r.get()?);
+            let attempts = r.get()?.try_into().map_err(|_| TraceError::Corrupt)?;
+            let flags = r.bytes(1)?[0];
+            trace.records.push(TraceRecord {
+                thread,
+                label,
+                start,
+                duration,
+                attempts,
+                read_only: flags & 1 != 0,
+                committed: flags & 2 != 0,
+                reads: r.addrs()?,
+                writes: r.addrs()?,
+            });
+        }
+        if !r.0.is_empty() {
+            return Err(TraceError::Corrupt);
+        }
+        Ok(trace)
+    }
+}
+
+// LEB128
+fn put(out: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        out.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+fn put_addrs(out: &mut Vec<u8>, addrs: &[usize]) {
+    put(out, addrs.len() as u64);
+    let mut prev = 0;
+    for a in addrs.iter() {
+        let stripe = (a / STRIPE_SIZE) as u64;
+        put(out, stripe - prev);
+        prev = stripe;
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn get(&mut self) -> Result<u64, TraceError> {
+        let mut v = 0u64;
+        for shift in (0..64).step_by(7) {
+            let (b, rest) = self.0.split_first().ok_or(TraceError::Corrupt)?;
+            self.0 = rest;
+            v |= ((b & 0x7f) as u64) << shift;
+            if b & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+        Err(TraceError::Corrupt)
+    }
+
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], TraceError> {
+        if self.0.len() < n {
+            return Err(TraceError::Corrupt);
+        }
+        let (b, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(b)
+    }
+
+    fn addrs(&mut self) -> Result<Vec<usize>, TraceError> {
+        let n = self.get()? as usize;
+        let mut addrs = Vec::with_capacity(n.min(self.0.len()));
+        let mut stripe = 0u64;
+        for _ in 0..n {
+            stripe = stripe.checked_add(self.get()?).ok_or(TraceError::Corrupt)?;
+            let addr = (stripe as usize)
+                .checked_mul(STRIPE_SIZE)
+                .ok_or(TraceError::Corrupt)?;
+            addrs.push(addr);
+        }
+        Ok(addrs)
+    }
+}
+
+/// Runs the transactions of a `Trace` again on an STM: each loads what the
+/// recorded one read, stores made-up values where it wrote, and fails if
+/// it did not commit, under its label. A body takes as long as one
+/// recorded attempt did on average, by spinning, so that conflicts have
+/// the windows they had.
+pub struct Replayer {
+    trace: Trace,
+    threads: Option<usize>,
+    speed: f64,
+}
+
+impl Replayer {
+    /// Replay on as many threads as were recorded, at recorded speed.
+    pub fn new(trace: Trace) -> Replayer {
+        Replayer {
+            trace,
+            threads: None,
+            speed: 1.0,
+        }
+    }
+
+    /// Run recorded thread `t` on replay thread `t % n`.
+    pub fn threads(mut self, n: usize) -> Replayer {
+        assert!(n > 0);
+        self.threads = Some(n);
+        self
+    }
+
+    /// Start transactions `factor` times faster than recorded, and run
+    /// bodies that much shorter; `f64::INFINITY` starts each as soon as
+    /// the one before on its thread finishes.
+    pub fn speed(mut self, factor: f64) -> Replayer {
+        assert!(factor > 0.0);
+        self.speed = factor;
+        self
+    }
+
+    /// Replay the trace on `stm`, which needs `Trace::capacity` bytes,
+    /// and return once every transaction has run.
+    pub fn run(&self, stm: &STM) {
+        let recorded = self.trace.records.iter().map(|r| r.thread + 1).max();
+        let n = self.threads.unwrap_or(recorded.unwrap_or(0) as usize);
+        // the `*_labeled` methods take static labels; a replay leaks one
+        // copy of each
+        let labels: Vec<&'static str> = self
+            .trace
+            .labels
+            .iter()
+            .map(|l| &*Box::leak(l.clone().into_boxed_str()))
+            .collect();
+
+        let start = Instant::now();
+        thread::scope(|s| {
+            for t in 0..n {
+                let labels = &labels;
+                s.spawn(move || {
+                    let mine = self
+                        .trace
+                        .records
+                        .iter()
+                        .filter(|r| r.thread as usize % n == t);
+                    for r in mine {
+                        let at = start + r.start.div_f64(self.speed.min(f64::MAX));
+                        let now = Instant::now();
+                        if at > now && self.speed.is_finite() {
+                            thread::sleep(at - now);
+                        }
+                        self.replay(stm, r, r.label.map(|l| labels[l]));
+                    }
+                });
+            }
+        });
+    }
+
+    fn replay(&self, stm: &STM, r: &TraceRecord, label: Option<&'static str>) {
+        let work = (r.duration / r.attempts.max(1)).div_f64(self.speed.min(f64::MAX));
+        let spin = || {
+            let until = Instant::now() + work;
+            while Instant::now() < until {
+                core::hint::spin_loop();
+            }
+        };
+
+        if r.read_only {
+            let body = |tr: &mut crate::ReadTrans| {
+                for a in r.reads.iter() {
+                    crate::load!(tr, *a);
+                }
+                spin();
+                if r.committed {
+                    STMResult::Ok(())
+                } else {
+                    STMResult::Abort
+                }
+            };
+            match label {
+                Some(l) => stm.read_transaction_labeled(l, body),
+                None => stm.read_transaction(body),
+            };
+        } else {
+            let body = |tr: &mut crate::WriteTrans| {
+                for a in r.reads.iter() {
+                    crate::load!(tr, *a);
+                }
+                spin();
+                for a in r.writes.iter() {
+                    tr.store(*a, (r.start.as_nanos() as u64).to_le_bytes());
+                }
+                if r.committed {
+                    STMResult::Ok(())
+                } else {
+                    STMResult::Abort
+                }
+            };
+            match label {
+                Some(l) => stm.write_transaction_labeled(l, body),
+                None => stm.write_transaction(body),
+            };
+        }
+    }
+}
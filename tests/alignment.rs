@@ -0,0 +1,55 @@
+use std::panic;
+
+use tl2::{STMResult, StripeValue, TLog, STM};
+
+// The message a panic of `f` carries.
+fn panic_message<F: FnOnce() + panic::UnwindSafe>(f: F) -> String {
+    let payload = panic::catch_unwind(f).unwrap_err();
+    match payload.downcast::<String>() {
+        Ok(s) => *s,
+        Err(payload) => payload.downcast_ref::<&str>().unwrap().to_string(),
+    }
+}
+
+#[test]
+#[should_panic(expected = "address 0x13 not aligned to stripe size 8")]
+fn a_misaligned_store_names_the_address() {
+    STM::new().write_transaction(|tr| {
+        tr.store(0x13, 1u64.to_stripe());
+        STMResult::Ok(())
+    });
+}
+
+#[test]
+#[should_panic(expected = "address 0x44 not aligned to stripe size 8")]
+fn a_misaligned_read_names_the_address() {
+    STM::new().read_transaction(|tr| STMResult::Ok(tr.load(0x44)));
+}
+
+#[test]
+#[should_panic(expected = "memory size 100 not a multiple of stripe size 8")]
+fn a_memory_size_off_the_stripe_size_is_refused() {
+    STM::builder().capacity(100).build();
+}
+
+#[test]
+#[should_panic(expected = "region length 12 not a multiple of stripe size 8")]
+fn a_region_length_off_the_stripe_size_is_refused() {
+    STM::new().write_transaction(|tr| STMResult::Ok(tr.export_region(0, 12)));
+}
+
+#[test]
+fn structures_report_their_misaligned_base() {
+    assert_eq!(
+        panic_message(|| {
+            TLog::new(0x21, 64);
+        }),
+        "address 0x21 not aligned to stripe size 8"
+    );
+    assert_eq!(
+        panic_message(|| {
+            STM::new().apply_batch(&[(0, [0; 8]), (0x0f, [0; 8])]);
+        }),
+        "address 0xf not aligned to stripe size 8"
+    );
+}
@@ -0,0 +1,75 @@
+use tl2::{STMResult, StripeValue, STM};
+
+const SIZE: usize = 256;
+
+// xorshift64*
+fn rng(x: &mut u64) -> u64 {
+    *x ^= *x >> 12;
+    *x ^= *x << 25;
+    *x ^= *x >> 27;
+    x.wrapping_mul(0x2545_f491_4f6c_dd1d)
+}
+
+fn scribble(stm: &STM, seed: u64, n: usize) {
+    let mut x = seed | 1;
+    for _ in 0..n {
+        let addr = rng(&mut x) as usize % (SIZE / 8) * 8;
+        let v = rng(&mut x);
+        stm.write_transaction(|tr| {
+            tr.store(addr, v.to_stripe());
+            STMResult::Ok(())
+        })
+        .unwrap();
+    }
+}
+
+#[test]
+fn a_batch_from_a_snapshot_diff_brings_a_copy_up_to_date() {
+    let primary = STM::builder().capacity(SIZE).build();
+    scribble(&primary, 1, 40);
+    let (before, _) = primary.replicate();
+    scribble(&primary, 2, 10);
+    let (after, _) = primary.replicate();
+
+    // a copy of the memory as it was, then only what changed since
+    let copy = STM::builder().capacity(SIZE).build();
+    copy.resync_replica(&before);
+    assert_eq!(copy.snapshot_range(0, SIZE).unwrap(), before.data);
+
+    let diff = before.diff(&after);
+    assert!(!diff.is_empty());
+    let batch: Vec<(usize, [u8; 8])> = diff.iter().map(|(addr, _, new)| (*addr, *new)).collect();
+    let version = copy.apply_batch(&batch).unwrap();
+    assert_eq!(version, copy.current_version());
+    assert_eq!(copy.snapshot_range(0, SIZE).unwrap(), after.data);
+}
+
+#[test]
+fn the_last_pair_for_an_address_wins() {
+    let stm = STM::new();
+    let v1 = stm
+        .apply_batch(&[
+            (0, 1u64.to_stripe()),
+            (8, 2u64.to_stripe()),
+            (0, 3u64.to_stripe()),
+        ])
+        .unwrap();
+    let v2 = stm.apply_batch(&[]).unwrap();
+    assert!(v2 > v1);
+
+    let got = stm
+        .read_transaction(|tr| {
+            let a = u64::from_stripe(tl2::load!(tr, 0));
+            let b = u64::from_stripe(tl2::load!(tr, 8));
+            STMResult::Ok((a, b))
+        })
+        .unwrap();
+    assert_eq!(got, (3, 2));
+}
+
+#[test]
+#[should_panic(expected = "address out of bounds")]
+fn an_address_past_the_end_is_refused() {
+    let stm = STM::builder().capacity(64).build();
+    stm.apply_batch(&[(0, [1; 8]), (64, [1; 8])]);
+}
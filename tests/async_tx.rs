@@ -0,0 +1,265 @@
+#![cfg(feature = "tokio")]
+
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use tl2::{STMResult, StripeValue, WriteTrans, STM};
+
+// A bounded FIFO queue of u64 in STM memory: head and tail counters, then
+// SLOTS slots. Pushing to a full queue or popping an empty one retries,
+// so an async caller waits for the commit that makes room or data.
+const HEAD: usize = 0;
+const TAIL: usize = 8;
+const SLOTS: u64 = 4;
+
+fn slot(i: u64) -> usize {
+    16 + (i % SLOTS) as usize * 8
+}
+
+fn push(tr: &mut WriteTrans, v: u64) -> STMResult<()> {
+    let head = u64::from_stripe(tl2::load!(tr, HEAD));
+    let tail = u64::from_stripe(tl2::load!(tr, TAIL));
+    if tail - head == SLOTS {
+        return STMResult::Retry;
+    }
+    tr.store(slot(tail), v.to_stripe());
+    tr.store(TAIL, (tail + 1).to_stripe());
+    STMResult::Ok(())
+}
+
+fn pop(tr: &mut WriteTrans) -> STMResult<u64> {
+    let head = u64::from_stripe(tl2::load!(tr, HEAD));
+    let tail = u64::from_stripe(tl2::load!(tr, TAIL));
+    if head == tail {
+        return STMResult::Retry;
+    }
+    let v = u64::from_stripe(tl2::load!(tr, slot(head)));
+    tr.store(HEAD, (head + 1).to_stripe());
+    STMResult::Ok(v)
+}
+
+// Producers and consumers passing values through the queue, as tasks.
+async fn exchange(stm: Arc<STM>) {
+    const PRODUCERS: u64 = 4;
+    const CONSUMERS: u64 = 4;
+    const EACH: u64 = 200;
+
+    let mut producers = Vec::new();
+    for p in 0..PRODUCERS {
+        let stm = stm.clone();
+        producers.push(tokio::spawn(async move {
+            for i in 0..EACH {
+                let v = p * EACH + i;
+                stm.write_transaction_async(|tr| push(tr, v)).await.unwrap();
+            }
+        }));
+    }
+    let mut consumers = Vec::new();
+    for _ in 0..CONSUMERS {
+        let stm = stm.clone();
+        consumers.push(tokio::spawn(async move {
+            let mut got = Vec::new();
+            for _ in 0..PRODUCERS * EACH / CONSUMERS {
+                got.push(stm.write_transaction_async(pop).await.unwrap());
+            }
+            got
+        }));
+    }
+
+    for p in producers {
+        p.await.unwrap();
+    }
+    let mut all = Vec::new();
+    for c in consumers {
+        let got = c.await.unwrap();
+        // each producer's values arrive in the order it pushed them
+        for p in 0..PRODUCERS {
+            let mine: Vec<u64> = got.iter().copied().filter(|v| v / EACH == p).collect();
+            assert!(mine.windows(2).all(|w| w[0] < w[1]), "{:?}", mine);
+        }
+        all.extend(got);
+    }
+    all.sort_unstable();
+    assert_eq!(all, (0..PRODUCERS * EACH).collect::<Vec<_>>());
+    assert!(stm.is_quiescent());
+    assert!(stm.locked_stripes().is_empty());
+}
+
+// Run `fut` on a current-thread runtime, failing if it does not finish in
+// time: a task blocking the thread would keep the others from running and
+// hang it for good.
+fn on_one_thread<F>(fut: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap()
+            .block_on(fut);
+        tx.send(()).unwrap();
+    });
+    match rx.recv_timeout(Duration::from_secs(10)) {
+        Ok(()) => (),
+        Err(RecvTimeoutError::Timeout) => panic!("the runtime's thread is blocked"),
+        Err(RecvTimeoutError::Disconnected) => panic!("the runtime panicked"),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn producers_and_consumers_exchange_through_a_queue() {
+    exchange(Arc::new(STM::new())).await;
+}
+
+#[test]
+fn producers_and_consumers_exchange_on_one_thread() {
+    on_one_thread(exchange(Arc::new(STM::builder().max_writers(1).build())));
+}
+
+#[test]
+fn waiting_for_a_writer_slot_leaves_the_thread_to_other_tasks() {
+    let stm = Arc::new(STM::builder().max_writers(1).build());
+    let (started, release) = (
+        Arc::new(AtomicBool::new(false)),
+        Arc::new(AtomicBool::new(false)),
+    );
+
+    // a blocking writer on another thread holds the only slot until told
+    let holder = {
+        let (stm, started, release) = (stm.clone(), started.clone(), release.clone());
+        thread::spawn(move || {
+            stm.write_transaction(|tr| {
+                started.store(true, Ordering::SeqCst);
+                while !release.load(Ordering::SeqCst) {
+                    thread::yield_now();
+                }
+                tr.store(64, 1u64.to_stripe());
+                STMResult::Ok(())
+            })
+            .unwrap();
+        })
+    };
+    while !started.load(Ordering::SeqCst) {
+        thread::yield_now();
+    }
+
+    on_one_thread(async move {
+        let writer = {
+            let stm = stm.clone();
+            tokio::spawn(async move { stm.write_transaction_async(|tr| push(tr, 7)).await })
+        };
+        // the writer waits for the slot, and this task still gets to run
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!writer.is_finished());
+        release.store(true, Ordering::SeqCst);
+        assert_eq!(writer.await.unwrap(), Some(()));
+        assert_eq!(stm.write_transaction_async(pop).await, Some(7));
+    });
+    holder.join().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn a_waiting_pop_is_woken_by_a_push() {
+    let stm = Arc::new(STM::new());
+    let consumer = {
+        let stm = stm.clone();
+        tokio::spawn(async move { stm.write_transaction_async(pop).await })
+    };
+    // let the consumer find the queue empty and wait
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(!consumer.is_finished());
+
+    stm.write_transaction(|tr| push(tr, 7)).unwrap();
+    let v = tokio::time::timeout(Duration::from_secs(5), consumer)
+        .await
+        .expect("the push never woke the consumer")
+        .unwrap();
+    assert_eq!(v, Some(7));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn a_cancelled_wait_leaves_nothing_behind() {
+    let stm = Arc::new(STM::new());
+
+    // the pop waits on an empty queue until the timeout drops it
+    let r = tokio::time::timeout(Duration::from_millis(20), stm.write_transaction_async(pop)).await;
+    assert!(r.is_err());
+    assert!(stm.is_quiescent());
+    assert!(stm.locked_stripes().is_empty());
+
+    // the queue still works for later callers, with nothing popped
+    stm.write_transaction_async(|tr| push(tr, 1)).await.unwrap();
+    stm.write_transaction_async(|tr| push(tr, 2)).await.unwrap();
+    assert_eq!(stm.write_transaction_async(pop).await, Some(1));
+    assert_eq!(stm.write_transaction_async(pop).await, Some(2));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn an_abort_still_ends_the_transaction() {
+    let stm = STM::new();
+    let r = stm
+        .write_transaction_async(|_| STMResult::<()>::Abort)
+        .await;
+    assert_eq!(r, None);
+    assert!(stm.is_quiescent());
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn escalated_tasks_on_one_thread_hold_nothing_across_a_yield() {
+    use tl2::{Fault, FaultInjector, Trigger};
+
+    const TASKS: u64 = 4;
+    const EACH: u64 = 50;
+    // every write restarts now and then, escalated runs too, so that
+    // tasks escalate and restart while serialized
+    let faults = Arc::new(FaultInjector::new(7));
+    faults.add(None, Fault::Restart, Trigger::Chance(0.3));
+    let stm = Arc::new(
+        STM::builder()
+            .escalate_after(1)
+            .fault_injector(faults)
+            .stats(true)
+            .build(),
+    );
+
+    let inc = |tr: &mut WriteTrans| {
+        let n = u64::from_stripe(tl2::load!(tr, 0));
+        tr.store(0, (n + 1).to_stripe());
+        STMResult::Ok(())
+    };
+    let s = stm.clone();
+    on_one_thread(async move {
+        let mut tasks = Vec::new();
+        for _ in 0..TASKS {
+            let stm = s.clone();
+            tasks.push(tokio::spawn(async move {
+                for _ in 0..EACH {
+                    stm.write_transaction_async(inc).await.unwrap();
+                }
+            }));
+        }
+        // a blocking write would wait on the thread forever for a token
+        // kept by a task that yielded
+        for _ in 0..EACH {
+            s.write_transaction(inc).unwrap();
+            tokio::task::yield_now().await;
+        }
+        for t in tasks {
+            t.await.unwrap();
+        }
+    });
+
+    let n = stm
+        .read_transaction(|tr| STMResult::Ok(u64::from_stripe(tl2::load!(tr, 0))))
+        .unwrap();
+    assert_eq!(n, (TASKS + 1) * EACH);
+    assert!(stm.stats().unwrap().escalations > 0);
+    assert!(stm.is_quiescent());
+}
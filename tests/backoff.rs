@@ -0,0 +1,71 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tl2::{STMResult, StripeValue, STM};
+
+// Run a write transaction that conflicts `restarts` times, each time by a
+// commit at the end of its body, and return the gaps between the end of
+// one attempt and the start of the next: the backoff before each restart.
+fn restart_gaps(stm: &STM, restarts: u32) -> Vec<Duration> {
+    let runs = AtomicU32::new(0);
+    let ended = Mutex::new(None::<Instant>);
+    let gaps = Mutex::new(Vec::new());
+    stm.write_transaction(|tr| {
+        if let Some(end) = ended.lock().unwrap().take() {
+            gaps.lock().unwrap().push(end.elapsed());
+        }
+        let v = u64::from_stripe(tl2::load!(tr, 0));
+        tr.store(0, (v + 1).to_stripe());
+        if runs.fetch_add(1, Ordering::SeqCst) < restarts {
+            thread::scope(|s| {
+                s.spawn(|| {
+                    stm.write_transaction(|tr| {
+                        tr.store(0, 0u64.to_stripe());
+                        STMResult::Ok(())
+                    })
+                });
+            });
+            *ended.lock().unwrap() = Some(Instant::now());
+        }
+        STMResult::Ok(())
+    })
+    .unwrap();
+    assert_eq!(runs.load(Ordering::SeqCst), restarts + 1);
+    gaps.into_inner().unwrap()
+}
+
+#[test]
+fn short_conflicts_spin_and_long_ones_sleep() {
+    const SPINS: u32 = 4;
+    const SLEEPS: u32 = 12;
+    let stm = STM::builder().spin_limit(SPINS).build();
+    let gaps = restart_gaps(&stm, SPINS + SLEEPS);
+    let (spun, slept) = gaps.split_at(SPINS as usize);
+
+    // a sleep is never shorter than asked for: 2^(n - limit - 1) µs before
+    // restart n, capped at 2^10
+    for (i, gap) in slept.iter().enumerate() {
+        let least = Duration::from_micros(1 << (i as u32).min(10));
+        assert!(*gap >= least, "restart {} waited {:?}", i + 5, gap);
+    }
+    // spinning is far quicker than all that sleeping
+    let spun: Duration = spun.iter().sum();
+    let slept: Duration = slept.iter().sum();
+    assert!(spun < slept, "spun {:?}, slept {:?}", spun, slept);
+}
+
+#[test]
+fn a_zero_limit_always_sleeps_and_no_limit_never_does() {
+    const RESTARTS: u32 = 12;
+    let sleeping = restart_gaps(&STM::builder().spin_limit(0).build(), RESTARTS);
+    let eager = restart_gaps(&STM::new(), RESTARTS);
+
+    // spin_limit(0) sleeps before every restart, at least 1 + 2 + ... µs
+    let least: u64 = (0..RESTARTS).map(|n| 1 << n.min(10)).sum();
+    let slept: Duration = sleeping.iter().sum();
+    assert!(slept >= Duration::from_micros(least), "{:?}", slept);
+    let eager: Duration = eager.iter().sum();
+    assert!(eager < slept, "eager {:?}, slept {:?}", eager, slept);
+}
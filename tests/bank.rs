@@ -0,0 +1,114 @@
+// The bank of examples/bank.rs, reduced: workers move money between
+// accounts, half of it between two hot ones, aborting when the source is
+// short, while an auditor sums every balance in read transactions.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+use tl2::{load, store, STMResult, STM};
+
+const ACCOUNTS: usize = 16;
+const HOT: usize = 2;
+const BALANCE: u64 = 1_000;
+const THREADS: usize = 4;
+const TRANSFERS: usize = 5_000;
+
+// xorshift64*
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+fn total(stm: &STM) -> u64 {
+    stm.read_transaction(|tr| {
+        let mut sum = 0;
+        for i in 0..ACCOUNTS {
+            sum += u64::from_le_bytes(load!(tr, 8 * i));
+        }
+        STMResult::Ok(sum)
+    })
+    .unwrap()
+}
+
+#[test]
+fn the_auditor_always_sees_the_same_total() {
+    let expected = BALANCE * ACCOUNTS as u64;
+    let stm = STM::builder().capacity(8 * ACCOUNTS).stats(true).build();
+    stm.write_transaction(|tr| {
+        for i in 0..ACCOUNTS {
+            store!(tr, 8 * i, BALANCE.to_le_bytes());
+        }
+        STMResult::Ok(())
+    })
+    .unwrap();
+
+    let stop = AtomicBool::new(false);
+    let audits = thread::scope(|s| {
+        let auditor = s.spawn(|| {
+            let mut audits = 0u64;
+            // at least one audit, even if the workers are done first
+            while audits == 0 || !stop.load(Ordering::Relaxed) {
+                assert_eq!(total(&stm), expected, "audit {}", audits);
+                audits += 1;
+            }
+            audits
+        });
+
+        let workers: Vec<_> = (0..THREADS)
+            .map(|n| {
+                let stm = &stm;
+                s.spawn(move || {
+                    let mut rng = Rng::new(n as u64 + 1);
+                    for _ in 0..TRANSFERS {
+                        let pool = if rng.next() & 1 == 0 { HOT } else { ACCOUNTS };
+                        let from = 8 * rng.below(pool);
+                        let to = 8 * ((from / 8 + 1 + rng.below(pool - 1)) % pool);
+                        let amount = rng.next() % (2 * BALANCE);
+                        let _ = stm.write_transaction(|tr| {
+                            let a = u64::from_le_bytes(load!(tr, from));
+                            if a < amount {
+                                return STMResult::Abort;
+                            }
+                            let b = u64::from_le_bytes(load!(tr, to));
+                            store!(tr, from, (a - amount).to_le_bytes());
+                            store!(tr, to, (b + amount).to_le_bytes());
+                            STMResult::Ok(())
+                        });
+                    }
+                })
+            })
+            .collect();
+        for w in workers {
+            w.join().unwrap();
+        }
+        stop.store(true, Ordering::Relaxed);
+        auditor.join().unwrap()
+    });
+
+    assert!(audits > 0);
+    assert_eq!(total(&stm), expected);
+    let stats = stm.stats().unwrap();
+    // every transfer either committed or aborted on short funds
+    assert_eq!(
+        stats.commits + stats.aborts,
+        1 + (THREADS * TRANSFERS) as u64,
+        "{:?}",
+        stats
+    );
+    assert_eq!(stats.reads, audits + 1);
+    assert!(stats.aborts > 0, "no transfer was ever short: {:?}", stats);
+}
@@ -0,0 +1,55 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use tl2::{STMResult, TxBarrier, TxError, STM};
+
+const THREADS: usize = 4;
+const ROUNDS: usize = 20;
+
+#[test]
+fn threads_pass_together_round_after_round() {
+    let stm = Arc::new(STM::new());
+    let barrier = TxBarrier::new(stm.clone(), 0, THREADS);
+    let arrived: Vec<AtomicUsize> = (0..ROUNDS).map(|_| AtomicUsize::new(0)).collect();
+    let last: Vec<AtomicUsize> = (0..ROUNDS).map(|_| AtomicUsize::new(0)).collect();
+    let before = stm.current_version();
+
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                for round in 0..ROUNDS {
+                    arrived[round].fetch_add(1, Ordering::SeqCst);
+                    if barrier.wait().unwrap() {
+                        last[round].fetch_add(1, Ordering::SeqCst);
+                    }
+                    // nobody leaves a round before everybody entered it,
+                    // and the next one cannot fill up without us
+                    assert_eq!(arrived[round].load(Ordering::SeqCst), THREADS);
+                    if round + 1 < ROUNDS {
+                        assert!(arrived[round + 1].load(Ordering::SeqCst) < THREADS);
+                    }
+                }
+            });
+        }
+    });
+
+    for l in &last {
+        assert_eq!(l.load(Ordering::SeqCst), 1);
+    }
+    let gen = stm.read_transaction(|tr| match barrier.generation(tr) {
+        Some(g) => STMResult::Ok(g),
+        None => STMResult::Retry,
+    });
+    assert_eq!(gen, Some(ROUNDS as u64));
+    // one commit per arrival; waiting commits nothing
+    assert_eq!(stm.current_version() - before, (THREADS * ROUNDS) as u64);
+}
+
+#[test]
+fn poisoned_stm_is_an_error() {
+    let stm = Arc::new(STM::new());
+    let barrier = TxBarrier::new(stm.clone(), 0, 2);
+    stm.poison();
+    assert_eq!(barrier.wait(), Err(TxError::Poisoned));
+}
@@ -0,0 +1,117 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::thread;
+use std::time::Instant;
+
+use tl2::{STMResult, StripeValue, STM};
+
+fn write(stm: &STM, addr: usize, v: u64) {
+    stm.write_transaction(|tr| {
+        tr.store(addr, v.to_stripe());
+        STMResult::Ok(())
+    })
+    .unwrap();
+}
+
+fn read(stm: &STM, addr: usize) -> u64 {
+    stm.read_transaction(|tr| STMResult::Ok(u64::from_stripe(tl2::load!(tr, addr))))
+        .unwrap()
+}
+
+// Increment stripe 0, restarting once: the first run has stripe 0
+// written from another thread before it commits. That makes a restart for
+// every two commits.
+fn bump_with_restart(stm: &STM) {
+    let runs = AtomicU32::new(0);
+    stm.write_transaction(|tr| {
+        let n = u64::from_stripe(tl2::load!(tr, 0));
+        if runs.fetch_add(1, Ordering::SeqCst) == 0 {
+            thread::scope(|s| {
+                s.spawn(|| write(stm, 0, n + 1));
+            });
+        }
+        tr.store(0, (n + 1).to_stripe());
+        STMResult::Ok(())
+    })
+    .unwrap();
+    assert_eq!(runs.into_inner(), 2);
+}
+
+#[test]
+fn the_breaker_trips_on_restarts_and_recovers_once_they_stop() {
+    let start = Instant::now();
+    let stm = STM::builder().circuit_breaker(0.25).build();
+    let stats = || stm.breaker_stats().unwrap();
+    assert!(!stats().serialized);
+
+    // a window of 256 attempts decides: about 85 of these fill one
+    let mut bumps = 0;
+    while !stats().serialized {
+        assert!(bumps < 100, "not tripped after {} restarts", bumps);
+        bump_with_restart(&stm);
+        bumps += 1;
+    }
+    assert_eq!(stats().trips, 1);
+    // every increment went through, the extra write and the retried one
+    assert_eq!(read(&stm, 0), bumps * 2);
+
+    // serialized writers commit as usual, and a window of them without
+    // restarts switches back; the window started with the retried run of
+    // the last bump
+    let mut n = 0;
+    while stats().serialized {
+        assert!(n < 256, "not recovered after {} commits", n);
+        write(&stm, 8, n);
+        n += 1;
+    }
+    assert!(n > 250, "recovered after {} commits", n);
+    let s = stats();
+    assert_eq!(s.trips, 1);
+    assert_eq!(read(&stm, 8), n - 1);
+    assert!(!s.serialized_time.is_zero());
+    assert!(!s.optimistic_time.is_zero());
+    assert!(s.serialized_time + s.optimistic_time <= start.elapsed());
+}
+
+#[test]
+fn restarts_below_the_threshold_do_not_trip() {
+    // about one restart for every two commits
+    let stm = STM::builder().circuit_breaker(0.6).build();
+    for _ in 0..200 {
+        bump_with_restart(&stm);
+    }
+    let s = stm.breaker_stats().unwrap();
+    assert!(!s.serialized);
+    assert_eq!(s.trips, 0);
+    assert!(s.serialized_time.is_zero());
+}
+
+#[test]
+fn writers_keep_their_counts_across_mode_switches() {
+    const THREADS: u64 = 4;
+    const N: u64 = 500;
+    // any restart at all trips it, and a window of none switches back
+    let stm = STM::builder().circuit_breaker(0.0).build();
+
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                for _ in 0..N {
+                    stm.write_transaction(|tr| {
+                        let n = u64::from_stripe(tl2::load!(tr, 0));
+                        thread::yield_now();
+                        tr.store(0, (n + 1).to_stripe());
+                        STMResult::Ok(())
+                    })
+                    .unwrap();
+                }
+            });
+        }
+    });
+    assert_eq!(read(&stm, 0), THREADS * N);
+    assert!(stm.breaker_stats().unwrap().trips >= 1);
+}
+
+#[test]
+fn no_breaker_no_stats() {
+    assert_eq!(STM::new().breaker_stats(), None);
+}
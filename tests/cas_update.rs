@@ -0,0 +1,63 @@
+use std::sync::Mutex;
+use std::thread;
+
+use tl2::{STMResult, StripeValue, STM};
+
+const THREADS: u64 = 8;
+const EACH: u64 = 500;
+
+fn read(stm: &STM, addr: usize) -> u64 {
+    stm.read_transaction(|tr| STMResult::Ok(u64::from_stripe(tl2::load!(tr, addr))))
+        .unwrap()
+}
+
+#[test]
+fn many_threads_build_a_correct_total() {
+    let stm = STM::builder().stats(true).build();
+    let seen = Mutex::new(Vec::new());
+    thread::scope(|s| {
+        for t in 1..=THREADS {
+            let (stm, seen) = (&stm, &seen);
+            s.spawn(move || {
+                let mut mine = Vec::with_capacity(EACH as usize);
+                for _ in 0..EACH {
+                    mine.push(stm.cas_update_u64(0, |n| n + t).unwrap());
+                }
+                // each thread's results only grow
+                assert!(mine.windows(2).all(|w| w[0] < w[1]));
+                seen.lock().unwrap().extend(mine);
+            });
+        }
+    });
+
+    let total = EACH * THREADS * (THREADS + 1) / 2;
+    assert_eq!(read(&stm, 0), total);
+    // every update committed a value of its own
+    let mut seen = seen.into_inner().unwrap();
+    seen.sort_unstable();
+    seen.dedup();
+    assert_eq!(seen.len() as u64, THREADS * EACH);
+    assert_eq!(seen.last(), Some(&total));
+    assert_eq!(stm.stats().unwrap().commits, THREADS * EACH);
+}
+
+#[test]
+fn the_update_returns_the_committed_value() {
+    let stm = STM::new();
+    assert_eq!(stm.cas_update_u64(8, |n| n + 5), Some(5));
+    assert_eq!(stm.cas_update_u64(8, |n| n * 3), Some(15));
+    assert_eq!(
+        stm.cas_update_u64(8, |n| n.wrapping_sub(16)),
+        Some(u64::MAX)
+    );
+    assert_eq!(read(&stm, 8), u64::MAX);
+    // neighbours are left alone
+    assert_eq!((read(&stm, 0), read(&stm, 16)), (0, 0));
+}
+
+#[test]
+fn a_poisoned_stm_refuses_the_update() {
+    let stm = STM::new();
+    stm.poison();
+    assert_eq!(stm.cas_update_u64(0, |n| n + 1), None);
+}
@@ -0,0 +1,139 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use tl2::{Clock, EventKind, STMResult, StripeValue, STM};
+
+// Steps by `stride` and lets the test hold `sample` back at `cap`, which
+// makes transactions start with an older read version than they could.
+struct MockClock {
+    now: AtomicU64,
+    stride: u64,
+    cap: AtomicU64,
+}
+
+impl MockClock {
+    fn new(stride: u64) -> Arc<MockClock> {
+        Arc::new(MockClock {
+            now: AtomicU64::new(0),
+            stride,
+            cap: AtomicU64::new(u64::MAX),
+        })
+    }
+}
+
+impl Clock for MockClock {
+    fn sample(&self) -> u64 {
+        let now = self.now.load(Ordering::Acquire);
+        now.min(self.cap.load(Ordering::SeqCst))
+    }
+
+    fn increment(&self) -> u64 {
+        self.now.fetch_add(self.stride, Ordering::AcqRel) + self.stride
+    }
+
+    fn set(&self, v: u64) {
+        self.now.store(v, Ordering::Release);
+    }
+}
+
+fn write(stm: &STM, addr: usize, v: u64) {
+    stm.write_transaction(|tr| {
+        tr.store(addr, v.to_stripe());
+        STMResult::Ok(())
+    })
+    .unwrap();
+}
+
+fn read(stm: &STM, addr: usize) -> (u64, u64) {
+    let (v, versions) = stm
+        .read_transaction_versioned(|tr| STMResult::Ok(u64::from_stripe(tl2::load!(tr, addr))))
+        .unwrap();
+    (v, versions[0].1)
+}
+
+#[test]
+fn a_pre_warmed_clock_stamps_commits_above_it() {
+    let mut stm = STM::new();
+    let start = Instant::now();
+    stm.set_clock(1 << 40);
+    assert!(start.elapsed().as_secs() < 1, "set_clock is not O(1)");
+    assert_eq!(stm.current_version(), 1 << 40);
+
+    write(&stm, 0, 7);
+    assert_eq!(read(&stm, 0), (7, (1 << 40) + 1));
+}
+
+#[test]
+fn reads_after_a_clock_jump_see_the_old_commits() {
+    let mut stm = STM::new();
+    for (addr, v) in [(0, 1), (8, 2), (0, 3)] {
+        write(&stm, addr, v);
+    }
+    let high_water = stm.current_version();
+    assert_eq!(high_water, 3);
+
+    // as after restoring a snapshot taken at a later clock
+    stm.set_clock(1000);
+    assert_eq!(read(&stm, 0), (3, 3));
+    assert_eq!(read(&stm, 8), (2, 2));
+
+    write(&stm, 8, 4);
+    assert_eq!(read(&stm, 8), (4, 1001));
+    assert_eq!(read(&stm, 0), (3, 3));
+}
+
+#[test]
+#[should_panic(expected = "below stripe version")]
+fn the_clock_cannot_go_below_a_stripe_version() {
+    let mut stm = STM::new();
+    write(&stm, 0, 1);
+    write(&stm, 0, 2);
+    stm.set_clock(1);
+}
+
+#[test]
+fn commits_are_stamped_by_a_custom_clock() {
+    let clock = MockClock::new(10);
+    let stm = STM::builder().clock(clock.clone()).build();
+
+    write(&stm, 0, 1);
+    write(&stm, 8, 2);
+    assert_eq!(stm.current_version(), 20);
+    assert_eq!(read(&stm, 0), (1, 10));
+    assert_eq!(read(&stm, 8), (2, 20));
+    assert_eq!(clock.now.load(Ordering::SeqCst), 20);
+}
+
+#[test]
+fn a_stripe_newer_than_the_read_version_restarts_the_transaction() {
+    let clock = MockClock::new(1);
+    let stm = STM::builder().clock(clock.clone()).build();
+    write(&stm, 0, 1); // version 1
+    write(&stm, 8, 2); // version 2
+
+    // the transaction starts at version 1, so stripe 8 is too new to read
+    // until the clock lets it start at 2
+    clock.cap.store(1, Ordering::SeqCst);
+    let mut retries = Vec::new();
+    let (sum, rv) = stm
+        .write_transaction_poll(
+            |tr| {
+                let a = u64::from_stripe(tl2::load!(tr, 0));
+                let b = u64::from_stripe(tl2::load!(tr, 8));
+                STMResult::Ok((a + b, tr.read_version()))
+            },
+            |attempt| {
+                retries.push(attempt);
+                clock.cap.store(u64::MAX, Ordering::SeqCst);
+            },
+        )
+        .unwrap();
+
+    assert_eq!((sum, rv), (3, 2));
+    assert_eq!(retries, [1]);
+    let events = stm.recent_events();
+    assert_eq!(events.len(), 1);
+    assert!(matches!(events[0].kind, EventKind::Restart(_)));
+    assert_eq!(events[0].addr, Some(8));
+}
@@ -0,0 +1,60 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tl2::{STMResult, StripeValue, STM};
+
+const THREADS: u64 = 3;
+
+#[test]
+fn commits_over_a_short_window_show_a_positive_rate() {
+    let stm = STM::new();
+    let before = stm.clock_sample();
+    let commits: u64 = thread::scope(|s| {
+        let workers: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let stm = &stm;
+                s.spawn(move || {
+                    // disjoint stripes, so every commit goes through once
+                    let end = Instant::now() + Duration::from_millis(50);
+                    let mut n = 0u64;
+                    while Instant::now() < end {
+                        stm.write_transaction(|tr| {
+                            tr.store(t as usize * 8, n.to_stripe());
+                            STMResult::Ok(())
+                        })
+                        .unwrap();
+                        n += 1;
+                    }
+                    n
+                })
+            })
+            .collect();
+        workers.into_iter().map(|w| w.join().unwrap()).sum()
+    });
+    let rate = stm.clock_sample().since(&before);
+
+    assert!(commits > 0);
+    assert_eq!(rate.delta, commits);
+    assert!(rate.span >= Duration::from_millis(50));
+    assert!(rate.per_sec() > 0.0);
+    let expected = commits as f64 / rate.span.as_secs_f64();
+    assert!((rate.per_sec() - expected).abs() < 1e-6 * expected);
+}
+
+#[test]
+fn reads_alone_do_not_move_the_clock() {
+    let stm = STM::new();
+    let before = stm.clock_sample();
+    for _ in 0..100 {
+        stm.read_transaction(|tr| STMResult::Ok(tl2::load!(tr, 0)))
+            .unwrap();
+    }
+    let rate = stm.clock_sample().since(&before);
+    assert_eq!(rate.delta, 0);
+    assert_eq!(rate.per_sec(), 0.0);
+
+    // samples taken out of order saturate rather than go negative
+    let backwards = before.since(&stm.clock_sample());
+    assert_eq!(backwards.span, Duration::ZERO);
+    assert_eq!(backwards.per_sec(), 0.0);
+}
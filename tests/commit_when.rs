@@ -0,0 +1,75 @@
+use std::sync::Arc;
+use std::thread;
+
+use tl2::{STMResult, StripeValue, TxError, STM};
+
+fn balance(stm: &STM) -> i64 {
+    stm.read_transaction(|tr| STMResult::Ok(i64::from_stripe(tl2::load!(tr, 0))))
+        .unwrap()
+}
+
+fn withdraw(stm: &STM, amount: i64) -> Result<(), TxError> {
+    stm.try_write_transaction(|tr| {
+        let b = i64::from_stripe(tl2::load!(tr, 0));
+        tr.store(0, (b - amount).to_stripe());
+        tr.commit_when(|view| i64::from_stripe(view.get(0).unwrap()) >= 0);
+        STMResult::Ok(())
+    })
+}
+
+#[test]
+fn false_predicate_rejects_and_leaves_memory_unchanged() {
+    let stm = STM::new();
+    stm.write_transaction(|tr| {
+        tr.store(0, 10i64.to_stripe());
+        tr.store(8, 1i64.to_stripe());
+        STMResult::Ok(())
+    })
+    .unwrap();
+
+    let r = stm.try_write_transaction(|tr| {
+        tr.store(0, (-5i64).to_stripe());
+        tr.store(8, 2i64.to_stripe());
+        tr.commit_when(|view| i64::from_stripe(view.get(0).unwrap()) >= 0);
+        STMResult::Ok(())
+    });
+    assert_eq!(r, Err(TxError::Rejected));
+    let kept = stm.read_transaction(|tr| {
+        STMResult::Ok((
+            i64::from_stripe(tl2::load!(tr, 0)),
+            i64::from_stripe(tl2::load!(tr, 8)),
+        ))
+    });
+    assert_eq!(kept, Some((10, 1)));
+
+    // the same withdrawal goes through once the balance covers it
+    assert_eq!(withdraw(&stm, 15), Err(TxError::Rejected));
+    stm.write_transaction(|tr| {
+        tr.store(0, 20i64.to_stripe());
+        STMResult::Ok(())
+    })
+    .unwrap();
+    assert_eq!(withdraw(&stm, 15), Ok(()));
+    assert_eq!(balance(&stm), 5);
+}
+
+#[test]
+fn concurrent_withdrawals_never_overdraw() {
+    let stm = Arc::new(STM::new());
+    stm.write_transaction(|tr| {
+        tr.store(0, 1000i64.to_stripe());
+        STMResult::Ok(())
+    })
+    .unwrap();
+
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let stm = stm.clone();
+            thread::spawn(move || (0..100).filter(|_| withdraw(&stm, 7).is_ok()).count() as i64)
+        })
+        .collect();
+    let done: i64 = threads.into_iter().map(|t| t.join().unwrap()).sum();
+
+    assert_eq!(done, 1000 / 7);
+    assert_eq!(balance(&stm), 1000 - 7 * done);
+}
@@ -0,0 +1,168 @@
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{fs, process};
+
+use tl2::{StripeValue, Trans, WriteTrans};
+
+/// A fresh path in the temporary directory, unique to this process, test
+/// binary and call.
+pub fn temp_path(name: &str) -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let n = NEXT.fetch_add(1, Ordering::Relaxed);
+    let dir = std::env::temp_dir().join(format!("tl2-test-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir.join(format!("{}-{}", name, n))
+}
+
+/// A bounded FIFO of `u64`s in a region of STM memory, built on the public
+/// API the way a user's own structure would be: the count of pushes and
+/// of pops, then a ring of `slots` entries.
+pub struct TQueue {
+    base: usize,
+    slots: u64,
+}
+
+impl TQueue {
+    pub fn new(base: usize, slots: u64) -> TQueue {
+        assert!(slots > 0);
+        TQueue { base, slots }
+    }
+
+    /// Bytes taken by a queue of `slots` entries.
+    pub fn size(slots: u64) -> usize {
+        (slots as usize + 2) * 8
+    }
+
+    fn counts<R: Trans>(&self, tr: &mut R) -> Option<(u64, u64)> {
+        let pushed = u64::from_stripe(tr.load(self.base)?);
+        let popped = u64::from_stripe(tr.load(self.base + 8)?);
+        Some((pushed, popped))
+    }
+
+    fn slot(&self, n: u64) -> usize {
+        self.base + 16 + (n % self.slots) as usize * 8
+    }
+
+    pub fn len<R: Trans>(&self, tr: &mut R) -> Option<u64> {
+        let (pushed, popped) = self.counts(tr)?;
+        Some(pushed - popped)
+    }
+
+    /// The front, or `Some(None)` when empty.
+    pub fn peek<R: Trans>(&self, tr: &mut R) -> Option<Option<u64>> {
+        let (pushed, popped) = self.counts(tr)?;
+        if pushed == popped {
+            return Some(None);
+        }
+        tr.load(self.slot(popped))
+            .map(|v| Some(u64::from_stripe(v)))
+    }
+
+    /// Push `v` at the back; returns false, changing nothing, when full.
+    pub fn push(&self, tr: &mut WriteTrans, v: u64) -> Option<bool> {
+        let (pushed, popped) = self.counts(tr)?;
+        if pushed - popped == self.slots {
+            return Some(false);
+        }
+        tr.store(self.slot(pushed), v.to_stripe());
+        tr.store(self.base, (pushed + 1).to_stripe());
+        Some(true)
+    }
+
+    /// Pop the front, or `Some(None)` when empty.
+    pub fn pop(&self, tr: &mut WriteTrans) -> Option<Option<u64>> {
+        let (pushed, popped) = self.counts(tr)?;
+        if pushed == popped {
+            return Some(None);
+        }
+        let v = self.peek(tr)?;
+        tr.store(self.base + 8, (popped + 1).to_stripe());
+        Some(v)
+    }
+}
+
+const EMPTY: u64 = 0;
+const OCCUPIED: u64 = 1;
+const DELETED: u64 = 2;
+
+/// A hash map from `u64` to `u64` in a region of STM memory, built on the
+/// public API like `TQueue`: a fixed open-addressing table of (state, key,
+/// value) slots with linear probing.
+pub struct THashMap {
+    base: usize,
+    buckets: u64,
+}
+
+impl THashMap {
+    /// A map of `buckets` slots, a power of two, at `base`.
+    pub fn new(base: usize, buckets: u64) -> THashMap {
+        assert!(buckets.is_power_of_two());
+        THashMap { base, buckets }
+    }
+
+    /// Bytes taken by a map of `buckets` slots.
+    pub fn size(buckets: u64) -> usize {
+        buckets as usize * 24
+    }
+
+    fn slot(&self, idx: u64) -> usize {
+        self.base + idx as usize * 24
+    }
+
+    // The slot holding `key`, or else the first free one seen, if any.
+    fn find<R: Trans>(&self, tr: &mut R, key: u64) -> Option<Result<u64, Option<u64>>> {
+        let mut free = None;
+        let mut idx = key.wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 32 & (self.buckets - 1);
+        for _ in 0..self.buckets {
+            let addr = self.slot(idx);
+            match u64::from_stripe(tr.load(addr)?) {
+                EMPTY => return Some(Err(free.or(Some(idx)))),
+                DELETED => free = free.or(Some(idx)),
+                _ => {
+                    if u64::from_stripe(tr.load(addr + 8)?) == key {
+                        return Some(Ok(idx));
+                    }
+                }
+            }
+            idx = (idx + 1) & (self.buckets - 1);
+        }
+        Some(Err(free))
+    }
+
+    pub fn get<R: Trans>(&self, tr: &mut R, key: u64) -> Option<Option<u64>> {
+        match self.find(tr, key)? {
+            Ok(idx) => tr
+                .load(self.slot(idx) + 16)
+                .map(|v| Some(u64::from_stripe(v))),
+            Err(_) => Some(None),
+        }
+    }
+
+    /// Map `key` to `v`; returns the value it replaced, or `Err` when the
+    /// table is full.
+    pub fn insert(&self, tr: &mut WriteTrans, key: u64, v: u64) -> Option<Result<Option<u64>, ()>> {
+        let (idx, old) = match self.find(tr, key)? {
+            Ok(idx) => (idx, Some(u64::from_stripe(tr.load(self.slot(idx) + 16)?))),
+            Err(Some(idx)) => (idx, None),
+            Err(None) => return Some(Err(())),
+        };
+        let addr = self.slot(idx);
+        tr.store(addr, OCCUPIED.to_stripe());
+        tr.store(addr + 8, key.to_stripe());
+        tr.store(addr + 16, v.to_stripe());
+        Some(Ok(old))
+    }
+
+    /// Remove `key`; returns the value it had.
+    pub fn remove(&self, tr: &mut WriteTrans, key: u64) -> Option<Option<u64>> {
+        let idx = match self.find(tr, key)? {
+            Ok(idx) => idx,
+            Err(_) => return Some(None),
+        };
+        let old = u64::from_stripe(tr.load(self.slot(idx) + 16)?);
+        tr.store(self.slot(idx), DELETED.to_stripe());
+        Some(Some(old))
+    }
+}
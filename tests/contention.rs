@@ -0,0 +1,258 @@
+use std::sync::Arc;
+use std::thread;
+
+use tl2::{
+    Contender, ContentionManager, Greedy, Karma, LockInfo, Polite, Resolution, STMResult,
+    StripeValue, TxPriority, STM,
+};
+
+fn me(work: u64, start: u64, priority: TxPriority) -> Contender {
+    Contender {
+        attempt: 1,
+        reads: work as usize,
+        work,
+        start,
+        prio: 0,
+        priority,
+    }
+}
+
+fn held(priority: u64, turns: u32) -> LockInfo {
+    LockInfo {
+        addr: 0,
+        priority,
+        turns,
+    }
+}
+
+#[test]
+fn polite_waits_its_rounds_then_gives_up() {
+    let cm = Polite { rounds: 3 };
+    let c = me(5, 0, TxPriority::High);
+    for turns in 0..3 {
+        assert_eq!(cm.on_conflict(&c, &held(0, turns)), Resolution::Wait);
+    }
+    assert_eq!(cm.on_conflict(&c, &held(0, 3)), Resolution::AbortSelf);
+}
+
+#[test]
+fn karma_wounds_less_work_and_waits_out_more() {
+    let cm = Karma { max_turns: 64 };
+    let busy = me(10, 0, TxPriority::Normal);
+    let idle = me(4, 0, TxPriority::Normal);
+    let (busy_p, idle_p) = (cm.priority(&busy), cm.priority(&idle));
+    assert!(busy_p > idle_p);
+
+    assert_eq!(
+        cm.on_conflict(&busy, &held(idle_p, 0)),
+        Resolution::AbortOther
+    );
+    // six stripes behind: six turns of waiting
+    for turns in 0..6 {
+        assert_eq!(
+            cm.on_conflict(&idle, &held(busy_p, turns)),
+            Resolution::Wait
+        );
+    }
+    assert_eq!(
+        cm.on_conflict(&idle, &held(busy_p, 6)),
+        Resolution::AbortSelf
+    );
+    // equal work never wounds
+    assert_eq!(
+        cm.on_conflict(&idle, &held(idle_p, 0)),
+        Resolution::AbortSelf
+    );
+
+    // a higher hint beats any work
+    let urgent = me(0, 0, TxPriority::High);
+    assert!(cm.priority(&urgent) > busy_p);
+}
+
+#[test]
+fn greedy_lets_the_older_transaction_go_first() {
+    let cm = Greedy { max_turns: 2 };
+    let old = me(0, 5, TxPriority::Normal);
+    let young = me(100, 9, TxPriority::Normal);
+    let (old_p, young_p) = (cm.priority(&old), cm.priority(&young));
+    assert!(old_p > young_p);
+
+    assert_eq!(
+        cm.on_conflict(&old, &held(young_p, 0)),
+        Resolution::AbortOther
+    );
+    assert_eq!(cm.on_conflict(&young, &held(old_p, 0)), Resolution::Wait);
+    assert_eq!(cm.on_conflict(&young, &held(old_p, 1)), Resolution::Wait);
+    assert_eq!(
+        cm.on_conflict(&young, &held(old_p, 2)),
+        Resolution::AbortSelf
+    );
+
+    let urgent = me(0, 9, TxPriority::High);
+    assert!(cm.priority(&urgent) > old_p);
+}
+
+// One write transaction "a" stores stripe 0 and, once it holds the lock,
+// commits only after "b" met the lock twice; "b" reads `b_reads` stripes
+// and also stores stripe 0, with the hint `b_priority`. Returns how many
+// times each body ran and what `cm` decided for b.
+#[cfg(feature = "testing")]
+fn contend(
+    cm: Arc<dyn ContentionManager>,
+    b_reads: usize,
+    b_priority: TxPriority,
+) -> (u32, u32, Vec<Resolution>) {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+    use tl2::{Scheduler, YieldPoint};
+
+    // Passes decisions through, keeping those about stripe 0.
+    struct Recording {
+        inner: Arc<dyn ContentionManager>,
+        decisions: Mutex<Vec<Resolution>>,
+    }
+
+    impl ContentionManager for Recording {
+        fn priority(&self, me: &Contender) -> u64 {
+            self.inner.priority(me)
+        }
+
+        fn on_conflict(&self, me: &Contender, other: &LockInfo) -> Resolution {
+            let r = self.inner.on_conflict(me, other);
+            if other.addr == 0 {
+                self.decisions.lock().unwrap().push(r);
+            }
+            r
+        }
+    }
+
+    struct HoldA(Arc<Recording>);
+
+    impl Scheduler for HoldA {
+        fn reached(&self, point: YieldPoint) {
+            if point != YieldPoint::Locked || thread::current().name() != Some("a") {
+                return;
+            }
+            // by the second turn the first decision has taken effect
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while self.0.decisions.lock().unwrap().len() < 2 {
+                assert!(Instant::now() < deadline, "b never met the lock");
+                thread::yield_now();
+            }
+        }
+    }
+
+    let cm = Arc::new(Recording {
+        inner: cm,
+        decisions: Mutex::new(Vec::new()),
+    });
+    let stm = STM::builder()
+        .contention_manager(cm.clone())
+        .scheduler(Arc::new(HoldA(cm.clone())))
+        .build();
+    let (a_runs, b_runs) = (AtomicU32::new(0), AtomicU32::new(0));
+    thread::scope(|s| {
+        thread::Builder::new()
+            .name("a".into())
+            .spawn_scoped(s, || {
+                stm.write_transaction(|tr| {
+                    a_runs.fetch_add(1, Ordering::SeqCst);
+                    tr.store(0, 1u64.to_stripe());
+                    STMResult::Ok(())
+                })
+                .unwrap()
+            })
+            .unwrap();
+        s.spawn(|| {
+            stm.write_transaction_with_priority(b_priority, |tr| {
+                if b_runs.fetch_add(1, Ordering::SeqCst) == 0 {
+                    while stm.locked_stripes().is_empty() {
+                        thread::yield_now();
+                    }
+                }
+                for i in 1..=b_reads {
+                    tl2::load!(tr, i * 8);
+                }
+                tr.store(0, 2u64.to_stripe());
+                STMResult::Ok(())
+            })
+            .unwrap()
+        });
+    });
+    let decisions = cm.decisions.lock().unwrap().clone();
+    (a_runs.into_inner(), b_runs.into_inner(), decisions)
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn polite_never_wounds_the_holder() {
+    let (a, b, decisions) = contend(Arc::new(Polite::default()), 3, TxPriority::Normal);
+    assert_eq!(a, 1);
+    assert!(b >= 1);
+    assert!(decisions.len() >= 2);
+    assert!(!decisions.contains(&Resolution::AbortOther));
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn karma_wounds_a_holder_that_did_less_work() {
+    let (a, b, decisions) = contend(Arc::new(Karma::default()), 3, TxPriority::Normal);
+    assert_eq!(decisions[0], Resolution::AbortOther);
+    // a restarted once it saw the wound, and b got the lock
+    assert_eq!((a, b), (2, 1));
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn greedy_wounds_only_for_a_higher_priority() {
+    let (a, b, decisions) = contend(Arc::new(Greedy::default()), 0, TxPriority::High);
+    assert_eq!(decisions[0], Resolution::AbortOther);
+    assert_eq!((a, b), (2, 1));
+
+    // b started no earlier than a, so it waits and restarts itself
+    let (a, _, decisions) = contend(Arc::new(Greedy::default()), 0, TxPriority::Normal);
+    assert_eq!(a, 1);
+    assert!(!decisions.contains(&Resolution::AbortOther));
+}
+
+#[test]
+fn every_policy_makes_progress_on_a_hot_pair() {
+    const THREADS: u64 = 4;
+    const EACH: u64 = 200;
+    let managers: [Arc<dyn ContentionManager>; 3] = [
+        Arc::new(Polite::default()),
+        Arc::new(Karma::default()),
+        Arc::new(Greedy::default()),
+    ];
+    for cm in managers {
+        let stm = STM::builder().contention_manager(cm).build();
+        thread::scope(|s| {
+            for t in 0..THREADS {
+                let stm = &stm;
+                s.spawn(move || {
+                    // half of the threads take the pair in the other order
+                    let (x, y) = if t % 2 == 0 { (0, 8) } else { (8, 0) };
+                    for _ in 0..EACH {
+                        stm.write_transaction(|tr| {
+                            let a = u64::from_stripe(tl2::load!(tr, x));
+                            let b = u64::from_stripe(tl2::load!(tr, y));
+                            tr.store(x, (a + 1).to_stripe());
+                            tr.store(y, (b + 1).to_stripe());
+                            STMResult::Ok(())
+                        })
+                        .unwrap();
+                    }
+                });
+            }
+        });
+        let got = stm
+            .read_transaction(|tr| {
+                let a = u64::from_stripe(tl2::load!(tr, 0));
+                let b = u64::from_stripe(tl2::load!(tr, 8));
+                STMResult::Ok((a, b))
+            })
+            .unwrap();
+        assert_eq!(got, (THREADS * EACH, THREADS * EACH));
+    }
+}
@@ -0,0 +1,116 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread;
+
+use tl2::{Counters, STMResult, STM};
+
+const SHARDS: usize = 8;
+const THREADS: u64 = 4;
+const ROUNDS: u64 = 2_000;
+
+// xorshift64*
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+fn sum(stm: &STM, c: &Counters) -> u64 {
+    stm.read_transaction(|tr| match c.sum(tr) {
+        Some(s) => STMResult::Ok(s),
+        None => STMResult::Retry,
+    })
+    .unwrap()
+}
+
+#[test]
+fn add_batch_adds_every_delta() {
+    let stm = STM::new();
+    let c = Counters::new(64, SHARDS);
+    stm.write_transaction(|tr| STMResult::Ok(c.add_batch(tr, &[(0, 1), (3, 2), (0, 4)])))
+        .unwrap()
+        .unwrap();
+    // adding 0 reads a shard back
+    let shards = stm
+        .write_transaction(|tr| match (c.add(tr, 0, 0), c.add(tr, 3, 0)) {
+            (Some(a), Some(b)) => STMResult::Ok((a, b)),
+            _ => STMResult::Retry,
+        })
+        .unwrap();
+    assert_eq!(shards, (5, 2));
+    assert_eq!(sum(&stm, &c), 7);
+}
+
+// Threads add to random shards, one at a time or in batches, counting
+// what they are about to add and what they have added. A sum taken in
+// between must lie between what was added before it started and what was
+// about to be added by the time it ended.
+#[test]
+fn sum_is_always_a_consistent_total() {
+    let stm = STM::new();
+    let c = Counters::new(0, SHARDS);
+    let started = AtomicU64::new(0);
+    let finished = AtomicU64::new(0);
+    let done = AtomicBool::new(false);
+
+    thread::scope(|s| {
+        let reader = s.spawn(|| {
+            let mut sums = 0;
+            while !done.load(Ordering::SeqCst) || sums == 0 {
+                let low = finished.load(Ordering::SeqCst);
+                let total = sum(&stm, &c);
+                let high = started.load(Ordering::SeqCst);
+                assert!(
+                    low <= total && total <= high,
+                    "sum {} outside {}..={}",
+                    total,
+                    low,
+                    high
+                );
+                sums += 1;
+            }
+        });
+
+        let writers: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let (stm, c, started, finished) = (&stm, &c, &started, &finished);
+                s.spawn(move || {
+                    let mut rng = Rng(t + 1);
+                    for n in 0..ROUNDS {
+                        let deltas: Vec<(usize, u64)> = (0..1 + n % 3)
+                            .map(|_| (rng.below(SHARDS), 1 + rng.next() % 10))
+                            .collect();
+                        let amount: u64 = deltas.iter().map(|(_, d)| d).sum();
+                        started.fetch_add(amount, Ordering::SeqCst);
+                        stm.write_transaction(|tr| match deltas.as_slice() {
+                            [(idx, delta)] => STMResult::Ok(c.add(tr, *idx, *delta).map(|_| ())),
+                            _ => STMResult::Ok(c.add_batch(tr, &deltas)),
+                        })
+                        .unwrap()
+                        .unwrap();
+                        finished.fetch_add(amount, Ordering::SeqCst);
+                    }
+                })
+            })
+            .collect();
+        for w in writers {
+            w.join().unwrap();
+        }
+        done.store(true, Ordering::SeqCst);
+        reader.join().unwrap();
+    });
+
+    assert_eq!(sum(&stm, &c), finished.load(Ordering::SeqCst));
+    assert_eq!(
+        started.load(Ordering::SeqCst),
+        finished.load(Ordering::SeqCst)
+    );
+}
@@ -0,0 +1,97 @@
+// The 32-bit check: the overflow tests and the philosophers example on
+// i686 (musl, so that no 32-bit libc has to be installed), and the
+// philosophers example on armv7 under qemu. Both need toolchains a default
+// setup lacks, so they are ignored; run them with
+//
+//     rustup target add i686-unknown-linux-musl armv7-unknown-linux-gnueabihf
+//     apt install gcc-arm-linux-gnueabihf qemu-user
+//     cargo test --test cross -- --ignored
+//
+// The armv7 linker and runner default to `arm-linux-gnueabihf-gcc` and
+// `qemu-arm -L /usr/arm-linux-gnueabihf`, unless set through the usual
+// `CARGO_TARGET_ARMV7_UNKNOWN_LINUX_GNUEABIHF_*` variables.
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+const ARMV7_ENV: &str = "CARGO_TARGET_ARMV7_UNKNOWN_LINUX_GNUEABIHF";
+
+fn require_target(target: &str) {
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".into());
+    let out = Command::new(rustc)
+        .args(["--print", "sysroot"])
+        .output()
+        .unwrap();
+    let sysroot = String::from_utf8(out.stdout).unwrap();
+    let found = Path::new(sysroot.trim())
+        .join("lib/rustlib")
+        .join(target)
+        .exists();
+    assert!(found, "`rustup target add {}` to run it", target);
+}
+
+fn require_tool(tool: &str) {
+    let found = Command::new(tool)
+        .arg("--version")
+        .output()
+        .is_ok_and(|o| o.status.success());
+    assert!(found, "`{}` is needed to run it", tool);
+}
+
+fn cargo(target: &str, args: &[&str], envs: &[(String, String)]) -> String {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let out = Command::new(env!("CARGO"))
+        .current_dir(root)
+        .args(args)
+        .args(["--target", target])
+        .env("CARGO_TARGET_DIR", Path::new(root).join("target/cross"))
+        .envs(envs.iter().map(|(k, v)| (k, v)))
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&out.stdout).into_owned();
+    assert!(
+        out.status.success(),
+        "cargo {:?} failed:\n{}{}",
+        args,
+        stdout,
+        String::from_utf8_lossy(&out.stderr)
+    );
+    stdout
+}
+
+#[test]
+#[ignore = "needs the i686-unknown-linux-musl target; run with --ignored"]
+fn i686() {
+    const TARGET: &str = "i686-unknown-linux-musl";
+    require_target(TARGET);
+    cargo(
+        TARGET,
+        &["test", "--test", "overflow", "--test", "tbig"],
+        &[],
+    );
+    let out = cargo(TARGET, &["run", "--example", "philosophers"], &[]);
+    assert!(out.contains("meals=10000"), "{}", out);
+}
+
+#[test]
+#[ignore = "needs the armv7-unknown-linux-gnueabihf target, its linker and qemu-arm; run with --ignored"]
+fn armv7() {
+    const TARGET: &str = "armv7-unknown-linux-gnueabihf";
+    require_target(TARGET);
+
+    // the tools given, or the Debian cross packages
+    let mut envs = Vec::new();
+    for (var, default) in [
+        ("LINKER", "arm-linux-gnueabihf-gcc"),
+        ("RUNNER", "qemu-arm -L /usr/arm-linux-gnueabihf"),
+    ] {
+        let var = format!("{}_{}", ARMV7_ENV, var);
+        let val = env::var(&var).unwrap_or_else(|_| default.into());
+        require_tool(val.split(' ').next().unwrap());
+        envs.push((var, val));
+    }
+
+    let out = cargo(TARGET, &["run", "--example", "philosophers"], &envs);
+    assert!(out.contains("meals=10000"), "{}", out);
+}
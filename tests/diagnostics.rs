@@ -0,0 +1,95 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::thread;
+
+use tl2::{STMBuilder, STMResult, StripeValue, TxError, STM};
+
+const STRIPE_SIZE: usize = 8;
+
+#[test]
+fn an_abort_reports_the_sets_of_its_run() {
+    let stm = STM::new();
+    let err = stm
+        .try_write_transaction_detailed(|tr| {
+            tr.load(0);
+            tr.load(STRIPE_SIZE);
+            tr.load(2 * STRIPE_SIZE);
+            tr.store(0, 1u64.to_stripe());
+            // a stripe stored twice counts once
+            tr.store(3 * STRIPE_SIZE, 1u64.to_stripe());
+            tr.store(3 * STRIPE_SIZE, 2u64.to_stripe());
+            STMResult::<()>::Abort
+        })
+        .unwrap_err();
+
+    assert_eq!(err.error, TxError::Abort);
+    assert_eq!(err.label, None);
+    assert_eq!(err.attempts, 1);
+    assert_eq!(err.read_set, 3);
+    assert_eq!(err.write_set, 2);
+    assert_eq!(
+        err.to_string(),
+        "transaction aborted after 1 attempts (read-set 3, write-set 2)"
+    );
+}
+
+#[test]
+fn attempts_count_the_restarts_before_the_abort() {
+    let stm = STM::new();
+    let runs = AtomicU32::new(0);
+    let err = stm
+        .try_write_transaction_detailed(|tr| {
+            tr.load(0);
+            if runs.fetch_add(1, Ordering::SeqCst) == 0 {
+                // a commit to what was read makes the first run restart
+                thread::scope(|s| {
+                    s.spawn(|| {
+                        stm.write_transaction(|tr| {
+                            tr.store(0, 7u64.to_stripe());
+                            STMResult::Ok(())
+                        })
+                    });
+                });
+                tr.store(STRIPE_SIZE, 1u64.to_stripe());
+                return STMResult::Ok(());
+            }
+            STMResult::Abort
+        })
+        .unwrap_err();
+
+    assert_eq!(runs.load(Ordering::SeqCst), 2);
+    assert_eq!(err.error, TxError::Abort);
+    assert_eq!(err.attempts, 2);
+    // the sets are those of the final run, not the first
+    assert_eq!((err.read_set, err.write_set), (1, 0));
+    // nothing of the aborted runs was written
+    let val = stm
+        .read_transaction(|tr| match tr.load(STRIPE_SIZE) {
+            Some(v) => STMResult::Ok(u64::from_stripe(v)),
+            None => STMResult::Retry,
+        })
+        .unwrap();
+    assert_eq!(val, 0);
+}
+
+#[test]
+fn other_errors_carry_diagnostics_too() {
+    let stm = STMBuilder::new().max_read_set(2).build();
+    let err = stm
+        .try_write_transaction_detailed(|tr| {
+            for i in 0..3 {
+                if tr.load(i * STRIPE_SIZE).is_none() {
+                    return STMResult::Retry;
+                }
+            }
+            STMResult::Ok(())
+        })
+        .unwrap_err();
+
+    assert_eq!(err.error, TxError::ReadSetTooLarge);
+    assert_eq!(err.attempts, 1);
+    // the load over the limit is refused, not added
+    assert_eq!(err.read_set, 2);
+    assert_eq!(err.write_set, 0);
+    let source = std::error::Error::source(&err).unwrap();
+    assert_eq!(source.to_string(), TxError::ReadSetTooLarge.to_string());
+}
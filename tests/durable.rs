@@ -0,0 +1,181 @@
+#![cfg(all(feature = "mmap", feature = "testing"))]
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+use std::sync::Arc;
+
+use tl2::{Durability, Fault, FaultInjector, STMResult, StripeValue, Trigger, YieldPoint, STM};
+
+mod common;
+
+const CHILD: &str = "TL2_DURABLE_CHILD";
+const STRIPES: usize = 8; // written by every commit
+const SUM: usize = STRIPES * 8; // the running sum of the commit numbers
+
+fn durability(name: &str) -> Durability {
+    match name {
+        "sync" => Durability::Sync,
+        "lazy" => Durability::Lazy,
+        _ => unreachable!(),
+    }
+}
+
+fn point(name: &str) -> YieldPoint {
+    match name {
+        "locked" => YieldPoint::Locked,
+        "publishing" => YieldPoint::Publishing,
+        "applying" => YieldPoint::Applying,
+        "committed" => YieldPoint::Committed,
+        _ => unreachable!(),
+    }
+}
+
+// Commit `k` writes `k` to every stripe and adds it to the sum, so any
+// torn or missing commit shows after recovery.
+fn run_commit(stm: &STM, k: u64) {
+    stm.write_transaction(|tr| {
+        let sum = u64::from_stripe(tl2::load!(tr, SUM));
+        for i in 0..STRIPES {
+            tr.store(i * 8, k.to_stripe());
+        }
+        tr.store(SUM, (sum + k).to_stripe());
+        STMResult::Ok(())
+    })
+    .unwrap();
+}
+
+// The number of commits the heap holds, checking that it holds exactly
+// commits 1 to that number.
+fn recovered(path: &Path, d: Durability) -> u64 {
+    let stm = STM::builder().capacity(4096).open_durable(path, d).unwrap();
+    stm.read_transaction(|tr| {
+        let k = u64::from_stripe(tl2::load!(tr, 0));
+        for i in 1..STRIPES {
+            assert_eq!(
+                u64::from_stripe(tl2::load!(tr, i * 8)),
+                k,
+                "torn commit {}",
+                k
+            );
+        }
+        assert_eq!(u64::from_stripe(tl2::load!(tr, SUM)), k * (k + 1) / 2);
+        STMResult::Ok(k)
+    })
+    .unwrap()
+}
+
+// Run as a child process by `crash_and_recover`: commit until the fault
+// aborts the process, printing each commit acknowledged.
+#[test]
+fn crash_child() {
+    let arg = match env::var(CHILD) {
+        Ok(arg) => arg,
+        Err(_) => return,
+    };
+    let parts: Vec<&str> = arg.split(',').collect();
+    let (path, d, at, crash_at) = (parts[0], parts[1], parts[2], parts[3].parse().unwrap());
+
+    let faults = Arc::new(FaultInjector::new(0));
+    faults.add(None, Fault::Crash(point(at)), Trigger::At(vec![crash_at]));
+    let stm = STM::builder()
+        .capacity(4096)
+        .fault_injector(faults)
+        .open_durable(path, durability(d))
+        .unwrap();
+    let start = recovered_in(&stm);
+    for k in start + 1.. {
+        run_commit(&stm, k);
+        println!("acked {}", k);
+        if k == start + 5 {
+            stm.checkpoint().unwrap();
+        }
+    }
+}
+
+fn recovered_in(stm: &STM) -> u64 {
+    stm.read_transaction(|tr| STMResult::Ok(u64::from_stripe(tl2::load!(tr, 0))))
+        .unwrap()
+}
+
+// Crash a child at the `crash_at`th attempt reaching `at`, and return the
+// last commit it acknowledged.
+fn crash(path: &Path, d: &str, at: &str, crash_at: u64) -> u64 {
+    let out = Command::new(env::current_exe().unwrap())
+        .args(["crash_child", "--exact", "--nocapture", "--test-threads=1"])
+        .env(
+            CHILD,
+            format!("{},{},{},{}", path.display(), d, at, crash_at),
+        )
+        .output()
+        .unwrap();
+    assert!(!out.status.success(), "the child did not crash");
+    String::from_utf8(out.stdout)
+        .unwrap()
+        .lines()
+        .filter_map(|l| l.strip_prefix("acked "))
+        .map(|k| k.parse().unwrap())
+        .max()
+        .unwrap_or(0)
+}
+
+fn crash_and_recover(d: &str) {
+    for at in ["locked", "publishing", "applying", "committed"] {
+        let path = common::temp_path(&format!("durable-{}-{}", d, at));
+        let mut acked = 0;
+        // crash a second time in the middle of the first recovery's work
+        for crash_at in [12, 3] {
+            acked = acked.max(crash(&path, d, at, crash_at));
+            let k = recovered(&path, durability(d));
+            assert!(
+                k >= acked,
+                "{} at {}: lost commit {} of {}",
+                d,
+                at,
+                acked,
+                k
+            );
+            // the commit being crashed is either kept whole or lost
+            assert!(
+                k <= acked + 1,
+                "{} at {}: {} commits after {}",
+                d,
+                at,
+                k,
+                acked
+            );
+            acked = k;
+        }
+    }
+}
+
+#[test]
+fn sync_recovers_every_acknowledged_commit() {
+    crash_and_recover("sync");
+}
+
+#[test]
+fn lazy_recovers_every_acknowledged_commit_after_a_process_crash() {
+    crash_and_recover("lazy");
+}
+
+#[test]
+fn checkpoint_empties_the_journal_and_keeps_the_heap() {
+    let path = common::temp_path("durable-checkpoint");
+    let log = format!("{}.log", path.display());
+    {
+        let stm = STM::builder()
+            .capacity(4096)
+            .open_durable(&path, Durability::Sync)
+            .unwrap();
+        for k in 1..=4 {
+            run_commit(&stm, k);
+        }
+        assert!(std::fs::metadata(&log).unwrap().len() > 0);
+        stm.checkpoint().unwrap();
+        assert_eq!(std::fs::metadata(&log).unwrap().len(), 0);
+        run_commit(&stm, 5);
+    }
+    assert_eq!(recovered(&path, Durability::Sync), 5);
+    assert!(STM::new().checkpoint().is_err());
+}
@@ -0,0 +1,157 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tl2::{STMResult, StripeValue, TxInfo, TxObserver, STM};
+
+// The stripes the small writers keep incrementing; the big transaction
+// reads all of them and stores their sum at `SUM`.
+const HOT: usize = 12;
+const SUM: usize = HOT * 8;
+const WRITERS: usize = 2;
+
+// Records the attempt at which each escalation happened.
+#[derive(Default)]
+struct Escalations(Mutex<Vec<u32>>);
+
+impl TxObserver for Escalations {
+    fn on_escalate(&self, info: &TxInfo) {
+        self.0.lock().unwrap().push(info.attempt);
+    }
+}
+
+fn read(stm: &STM, addr: usize) -> u64 {
+    stm.read_transaction(|tr| STMResult::Ok(u64::from_stripe(tl2::load!(tr, addr))))
+        .unwrap()
+}
+
+// Run `big` against small writers committing to the hot stripes until it
+// returns. Each run of the big body waits for a small commit before it
+// returns, so that it conflicts for as long as the writers can commit.
+// Returns what `big` returned and how many times its body ran.
+fn starve<F>(stm: &STM, big: F) -> (Option<u64>, u32)
+where
+    F: FnOnce(&dyn Fn(&mut tl2::WriteTrans) -> STMResult<u64>) -> Option<u64>,
+{
+    let done = AtomicBool::new(false);
+    let commits = AtomicU64::new(0);
+    let runs = AtomicU32::new(0);
+
+    let got = thread::scope(|s| {
+        for t in 0..WRITERS {
+            let (done, commits) = (&done, &commits);
+            s.spawn(move || {
+                let mut i = t;
+                while !done.load(Ordering::SeqCst) {
+                    let addr = i % HOT * 8;
+                    stm.write_transaction(|tr| {
+                        let n = u64::from_stripe(tl2::load!(tr, addr));
+                        tr.store(addr, (n + 1).to_stripe());
+                        STMResult::Ok(())
+                    })
+                    .unwrap();
+                    commits.fetch_add(1, Ordering::SeqCst);
+                    i += 1;
+                    thread::yield_now();
+                }
+            });
+        }
+
+        let body = |tr: &mut tl2::WriteTrans| {
+            runs.fetch_add(1, Ordering::SeqCst);
+            let seen = commits.load(Ordering::SeqCst);
+            let mut sum = 0;
+            for i in 0..HOT {
+                sum += u64::from_stripe(tl2::load!(tr, i * 8));
+            }
+            tr.store(SUM, sum.to_stripe());
+            // give up waiting when nobody can commit
+            let start = Instant::now();
+            while commits.load(Ordering::SeqCst) == seen
+                && start.elapsed() < Duration::from_millis(20)
+            {
+                thread::yield_now();
+            }
+            STMResult::Ok(sum)
+        };
+        let got = big(&body);
+        done.store(true, Ordering::SeqCst);
+        got
+    });
+    (got, runs.into_inner())
+}
+
+#[test]
+fn escalation_lets_a_starved_big_transaction_finish() {
+    const K: u32 = 3;
+    let stm = STM::builder().capacity(256).stats(true).build();
+    let rec = Arc::new(Escalations::default());
+    stm.set_observer(rec.clone());
+
+    let (got, runs) = starve(&stm, |body| stm.write_transaction_escalating(K, body));
+    // every run before the escalation lost to a small writer, and the
+    // serialized one could not
+    assert_eq!(runs, K + 1);
+    assert_eq!(read(&stm, SUM), got.unwrap());
+    assert_eq!(*rec.0.lock().unwrap(), [K + 1]);
+    assert_eq!(stm.stats().unwrap().escalations, 1);
+}
+
+#[test]
+fn the_builder_escalates_every_write_transaction() {
+    const K: u32 = 2;
+    let stm = STM::builder()
+        .capacity(256)
+        .stats(true)
+        .escalate_after(K)
+        .build();
+
+    let (got, runs) = starve(&stm, |body| stm.write_transaction(body));
+    assert_eq!(runs, K + 1);
+    assert_eq!(read(&stm, SUM), got.unwrap());
+    // a small writer may escalate too, if the other beat it often enough
+    assert!(stm.stats().unwrap().escalations >= 1);
+}
+
+#[test]
+fn without_escalation_the_big_transaction_starves() {
+    const CAP: u32 = 30;
+    let stm = STM::builder().capacity(256).stats(true).build();
+    let attempts = AtomicU32::new(0);
+
+    let (got, runs) = starve(&stm, |body| {
+        stm.write_transaction(|tr| {
+            // abort the run past the cap instead of restarting forever
+            if attempts.fetch_add(1, Ordering::SeqCst) == CAP {
+                return STMResult::Abort;
+            }
+            body(tr)
+        })
+    });
+    assert_eq!(got, None);
+    assert_eq!(runs, CAP);
+    assert_eq!(read(&stm, SUM), 0);
+    assert_eq!(stm.stats().unwrap().escalations, 0);
+}
+
+#[test]
+fn readers_run_while_a_transaction_is_serialized() {
+    let stm = STM::builder().capacity(256).build();
+    let runs = AtomicU32::new(0);
+    let read_during = AtomicU64::new(u64::MAX);
+
+    stm.write_transaction_escalating(0, |tr| {
+        runs.fetch_add(1, Ordering::SeqCst);
+        tr.store(0, 7u64.to_stripe());
+        // the other writers are paused, not the readers
+        thread::scope(|s| {
+            s.spawn(|| read_during.store(read(&stm, 0), Ordering::SeqCst));
+        });
+        STMResult::Ok(())
+    })
+    .unwrap();
+    assert_eq!(runs.into_inner(), 1);
+    assert_eq!(read_during.into_inner(), 0);
+    assert_eq!(read(&stm, 0), 7);
+}
@@ -0,0 +1,128 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::thread;
+
+use tl2::{ConflictCause, EventKind, STMResult, StripeValue, TxError, STM};
+
+const STRIPE_SIZE: usize = 8;
+
+fn write_other(stm: &STM, addr: usize) {
+    thread::scope(|s| {
+        s.spawn(|| {
+            stm.write_transaction(|tr| {
+                tr.store(addr, 7u64.to_stripe());
+                STMResult::Ok(())
+            })
+        });
+    });
+}
+
+// Restart once on a validation conflict at `addr`, then commit.
+fn conflict_at(stm: &STM, label: &'static str, addr: usize) {
+    let runs = AtomicU32::new(0);
+    stm.write_transaction_labeled(label, |tr| {
+        tr.load(addr);
+        if runs.fetch_add(1, Ordering::SeqCst) == 0 {
+            write_other(stm, addr);
+        }
+        tr.store(addr, 1u64.to_stripe());
+        STMResult::Ok(())
+    })
+    .unwrap();
+}
+
+fn events(stm: &STM) -> Vec<(Option<&'static str>, EventKind, Option<usize>, u32)> {
+    stm.recent_events()
+        .iter()
+        .map(|e| (e.label, e.kind, e.addr, e.attempt))
+        .collect()
+}
+
+#[test]
+fn the_ring_keeps_the_most_recent_events_in_order() {
+    let stm = STM::builder().event_ring(4).build();
+    assert!(stm.recent_events().is_empty());
+
+    conflict_at(&stm, "first", 0);
+    stm.write_transaction_labeled("abort", |_| STMResult::<()>::Abort);
+    conflict_at(&stm, "a", STRIPE_SIZE);
+    conflict_at(&stm, "b", 2 * STRIPE_SIZE);
+    assert_eq!(
+        stm.try_write_transaction(|_| STMResult::<()>::Retry),
+        Err(TxError::Retry)
+    );
+    conflict_at(&stm, "c", 3 * STRIPE_SIZE);
+
+    let restart = EventKind::Restart(ConflictCause::Validation);
+    assert_eq!(
+        events(&stm),
+        [
+            (Some("a"), restart, Some(STRIPE_SIZE), 1),
+            (Some("b"), restart, Some(2 * STRIPE_SIZE), 1),
+            (None, EventKind::Failed(TxError::Retry), None, 1),
+            (Some("c"), restart, Some(3 * STRIPE_SIZE), 1),
+        ]
+    );
+    let at: Vec<_> = stm.recent_events().iter().map(|e| e.at).collect();
+    assert!(at.windows(2).all(|w| w[0] <= w[1]), "{:?}", at);
+}
+
+#[test]
+fn commits_leave_no_events_and_zero_turns_the_ring_off() {
+    let stm = STM::new();
+    stm.write_transaction(|tr| {
+        tr.store(0, 1u64.to_stripe());
+        STMResult::Ok(())
+    })
+    .unwrap();
+    assert!(stm.recent_events().is_empty());
+
+    let off = STM::builder().event_ring(0).build();
+    conflict_at(&off, "x", 0);
+    assert!(off.recent_events().is_empty());
+}
+
+#[test]
+fn concurrent_writers_never_produce_torn_events() {
+    const THREADS: usize = 4;
+    const EACH: usize = 200;
+    let stm = STM::builder().event_ring(64).build();
+
+    thread::scope(|s| {
+        for t in 0..THREADS {
+            let stm = &stm;
+            s.spawn(move || {
+                let label = ["t0", "t1", "t2", "t3"][t];
+                for _ in 0..EACH {
+                    stm.write_transaction_labeled(label, |_| STMResult::<()>::Abort);
+                }
+            });
+        }
+    });
+
+    let events = stm.recent_events();
+    assert_eq!(events.len(), 64);
+    for e in events {
+        assert!(matches!(e.label, Some("t0" | "t1" | "t2" | "t3")));
+        assert_eq!(e.kind, EventKind::Failed(TxError::Abort));
+        assert_eq!((e.addr, e.attempt), (None, 1));
+    }
+}
+
+#[test]
+fn the_debug_report_lists_the_recent_events() {
+    let stm = STM::builder().stats(true).conflict_heatmap(true).build();
+    conflict_at(&stm, "hot", 5 * STRIPE_SIZE);
+
+    let report = stm.debug_report();
+    assert!(report.starts_with("capacity: "), "{}", report);
+    assert!(
+        report.contains("hottest stripes:\n  0x28: 1\n"),
+        "{}",
+        report
+    );
+    assert!(
+        report.contains("hot attempt 1: Restart(Validation) at 0x28\n"),
+        "{}",
+        report
+    );
+}
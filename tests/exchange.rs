@@ -0,0 +1,61 @@
+use std::thread;
+
+use tl2::{STMResult, StripeValue, STM};
+
+const THREADS: u64 = 8;
+const EACH: u64 = 500;
+
+fn exchange(stm: &STM, addr: usize, new: u64) -> u64 {
+    stm.write_transaction(|tr| match tr.exchange(addr, new.to_stripe()) {
+        Some(old) => STMResult::Ok(u64::from_stripe(old)),
+        None => STMResult::Retry,
+    })
+    .unwrap()
+}
+
+#[test]
+fn old_values_and_the_final_one_are_every_value_stored() {
+    let stm = STM::new();
+    // 0 is there before anyone stores; the values stored are 1..
+    let olds: Vec<u64> = thread::scope(|s| {
+        let handles: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let stm = &stm;
+                s.spawn(move || {
+                    (0..EACH)
+                        .map(|i| exchange(stm, 0, 1 + t * EACH + i))
+                        .collect::<Vec<u64>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap())
+            .collect()
+    });
+
+    let last = stm
+        .read_transaction(|tr| STMResult::Ok(u64::from_stripe(tl2::load!(tr, 0))))
+        .unwrap();
+    let mut seen = olds;
+    seen.push(last);
+    seen.sort_unstable();
+    assert_eq!(seen, (0..=THREADS * EACH).collect::<Vec<_>>());
+}
+
+#[test]
+fn exchange_reads_its_own_writes() {
+    let stm = STM::new();
+    let r = stm
+        .write_transaction(|tr| {
+            let a = tr.exchange(0, 1u64.to_stripe());
+            let b = tr.exchange(0, 2u64.to_stripe());
+            match (a, b) {
+                (Some(a), Some(b)) => STMResult::Ok((u64::from_stripe(a), u64::from_stripe(b))),
+                _ => STMResult::Retry,
+            }
+        })
+        .unwrap();
+    assert_eq!(r, (0, 1));
+    assert_eq!(exchange(&stm, 0, 3), 2);
+}
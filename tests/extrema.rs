@@ -0,0 +1,75 @@
+use std::thread;
+
+use tl2::{STMResult, STM};
+
+const THREADS: u64 = 8;
+const CANDIDATES: u64 = 500;
+
+// A spread of candidates per thread, out of order, with the extrema in
+// the middle of one thread's run.
+fn candidate(t: u64, n: u64) -> u64 {
+    (n * 7919 + t * 104_729) % 1_000_003 + 10
+}
+
+#[test]
+fn the_stripes_end_at_the_true_extrema() {
+    let stm = STM::new();
+    stm.write_transaction(|tr| {
+        tr.store(8, u64::MAX.to_le_bytes());
+        STMResult::Ok(())
+    })
+    .unwrap();
+
+    thread::scope(|s| {
+        for t in 0..THREADS {
+            let stm = &stm;
+            s.spawn(move || {
+                for n in 0..CANDIDATES {
+                    let c = match (t, n) {
+                        (3, 250) => 5_000_000,
+                        (5, 100) => 1,
+                        _ => candidate(t, n),
+                    };
+                    let (max, min) = stm
+                        .write_transaction(|tr| {
+                            match (tr.update_max_u64(0, c), tr.update_min_u64(8, c)) {
+                                (Some(max), Some(min)) => STMResult::Ok((max, min)),
+                                _ => STMResult::Retry,
+                            }
+                        })
+                        .unwrap();
+                    assert!(max >= c && min <= c);
+                }
+            });
+        }
+    });
+
+    let (max, min) = stm
+        .read_transaction(|tr| {
+            let max = u64::from_le_bytes(tl2::load!(tr, 0));
+            let min = u64::from_le_bytes(tl2::load!(tr, 8));
+            STMResult::Ok((max, min))
+        })
+        .unwrap();
+    assert_eq!((max, min), (5_000_000, 1));
+}
+
+fn version(stm: &STM) -> u64 {
+    let (_, versions) = stm
+        .read_transaction_versioned(|tr| STMResult::Ok(tr.load(0)))
+        .unwrap();
+    versions[0].1
+}
+
+#[test]
+fn a_candidate_that_changes_nothing_is_not_written() {
+    let stm = STM::new();
+    stm.write_transaction(|tr| STMResult::Ok(tr.update_max_u64(0, 9)))
+        .unwrap();
+    let ver = version(&stm);
+    let r = stm
+        .write_transaction(|tr| STMResult::Ok((tr.update_max_u64(0, 3), tr.update_min_u64(0, 20))))
+        .unwrap();
+    assert_eq!(r, (Some(9), Some(9)));
+    assert_eq!(version(&stm), ver, "the stripe was written");
+}
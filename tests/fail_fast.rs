@@ -0,0 +1,59 @@
+use std::cell::Cell;
+use std::thread;
+
+use tl2::{STMResult, StripeValue, TxError, STM};
+
+fn write_other(stm: &STM) {
+    thread::scope(|s| {
+        s.spawn(|| {
+            stm.write_transaction(|tr| {
+                tr.store(8, 1u64.to_stripe());
+                STMResult::Ok(())
+            })
+        });
+    });
+}
+
+// The body reads stripe 8 after another thread changed it, carries on with
+// made-up values and returns `Abort`, as it would on a broken invariant.
+fn run(stm: &STM, fail_fast: bool) -> (Result<u64, TxError>, u32) {
+    let attempts = Cell::new(0);
+    let r = stm.try_write_transaction(|tr| {
+        if fail_fast {
+            tr.fail_fast();
+        }
+        attempts.set(attempts.get() + 1);
+        let first = attempts.get() == 1;
+        let _ = tr.load(0);
+        if first {
+            write_other(stm);
+        }
+        let b = tr.load(8).map(u64::from_stripe);
+        if first {
+            assert_eq!(b, None);
+            assert_eq!(tr.load(0), None, "a doomed attempt keeps reading");
+        }
+        tr.store(16, b.unwrap_or(99).to_stripe());
+        match b {
+            Some(b) => STMResult::Ok(b),
+            None => STMResult::Abort,
+        }
+    });
+    (r, attempts.get())
+}
+
+#[test]
+fn fail_fast_restarts_a_body_that_carries_on_after_a_conflict() {
+    let stm = STM::new();
+    assert_eq!(run(&stm, true), (Ok(1), 2));
+    let stored = stm.read_transaction(|tr| STMResult::Ok(u64::from_stripe(tl2::load!(tr, 16))));
+    assert_eq!(stored, Some(1));
+}
+
+#[test]
+fn without_fail_fast_the_body_result_stands() {
+    let stm = STM::new();
+    assert_eq!(run(&stm, false), (Err(TxError::Abort), 1));
+    let stored = stm.read_transaction(|tr| STMResult::Ok(u64::from_stripe(tl2::load!(tr, 16))));
+    assert_eq!(stored, Some(0));
+}
@@ -0,0 +1,165 @@
+#![cfg(feature = "testing")]
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tl2::{
+    ConflictCause, EventKind, Fault, FaultInjector, STMResult, StripeValue, Trigger, TxError,
+    YieldPoint, STM,
+};
+
+const BUDGET: u32 = 3;
+
+// Application code under test: a transfer giving up after `BUDGET` tries.
+#[derive(Debug, PartialEq)]
+enum TransferError {
+    BudgetExhausted,
+}
+
+fn transfer(stm: &STM, amount: u64) -> Result<u32, TransferError> {
+    for tries in 1..=BUDGET {
+        let done = stm.write_transaction_labeled("transfer", |tr| {
+            let from = u64::from_stripe(tl2::load!(tr, 0));
+            let to = u64::from_stripe(tl2::load!(tr, 8));
+            tr.store(0, (from - amount).to_stripe());
+            tr.store(8, (to + amount).to_stripe());
+            STMResult::Ok(())
+        });
+        if done.is_some() {
+            return Ok(tries);
+        }
+    }
+    Err(TransferError::BudgetExhausted)
+}
+
+fn setup(faults: &Arc<FaultInjector>) -> STM {
+    let stm = STM::builder()
+        .fault_injector(faults.clone())
+        .stats(true)
+        .build();
+    stm.write_transaction(|tr| {
+        tr.store(0, 100u64.to_stripe());
+        STMResult::Ok(())
+    })
+    .unwrap();
+    stm
+}
+
+fn balances(stm: &STM) -> (u64, u64) {
+    stm.read_transaction(|tr| {
+        let a = u64::from_stripe(tl2::load!(tr, 0));
+        let b = u64::from_stripe(tl2::load!(tr, 8));
+        STMResult::Ok((a, b))
+    })
+    .unwrap()
+}
+
+#[test]
+fn scripted_retries_exhaust_the_transfer_budget() {
+    let faults = Arc::new(FaultInjector::new(0));
+    let stm = setup(&faults);
+
+    faults.add(Some("transfer"), Fault::Retry, Trigger::At(vec![1, 2]));
+    assert_eq!(transfer(&stm, 10), Ok(3));
+
+    faults.clear();
+    faults.add(Some("transfer"), Fault::Retry, Trigger::At(vec![1, 2, 3]));
+    assert_eq!(transfer(&stm, 10), Err(TransferError::BudgetExhausted));
+    // nothing was committed by the failed tries
+    assert_eq!(balances(&stm), (90, 10));
+    let failed = stm
+        .recent_events()
+        .iter()
+        .filter(|e| e.label == Some("transfer") && e.kind == EventKind::Failed(TxError::Retry))
+        .count();
+    assert_eq!(failed, 5);
+
+    // the script is used up
+    assert_eq!(transfer(&stm, 10), Ok(1));
+    assert_eq!(balances(&stm), (80, 20));
+}
+
+#[test]
+fn a_labeled_fault_leaves_other_transactions_alone() {
+    let faults = Arc::new(FaultInjector::new(0));
+    let stm = setup(&faults);
+    faults.add(Some("transfer"), Fault::Retry, Trigger::Always);
+
+    assert_eq!(transfer(&stm, 10), Err(TransferError::BudgetExhausted));
+    assert_eq!(
+        stm.try_write_transaction(|tr| {
+            tr.store(16, 1u64.to_stripe());
+            STMResult::Ok(())
+        }),
+        Ok(())
+    );
+}
+
+#[test]
+fn forced_restarts_and_lock_failures_rerun_the_body() {
+    let faults = Arc::new(FaultInjector::new(0));
+    let stm = setup(&faults);
+    faults.add(None, Fault::Restart, Trigger::At(vec![1]));
+    faults.add(None, Fault::LockFailure, Trigger::At(vec![1]));
+
+    let runs = AtomicU32::new(0);
+    stm.write_transaction_labeled("bump", |tr| {
+        runs.fetch_add(1, Ordering::SeqCst);
+        tr.store(16, 1u64.to_stripe());
+        STMResult::Ok(())
+    })
+    .unwrap();
+    // the restart hits the first attempt before it locks, so the lock
+    // failure hits the second
+    assert_eq!(runs.into_inner(), 3);
+    let causes: Vec<_> = stm
+        .recent_events()
+        .iter()
+        .filter(|e| e.label == Some("bump"))
+        .map(|e| e.kind)
+        .collect();
+    assert_eq!(
+        causes,
+        [
+            EventKind::Restart(ConflictCause::Validation),
+            EventKind::Restart(ConflictCause::Lock),
+        ]
+    );
+}
+
+#[test]
+fn a_delay_holds_the_transaction_at_its_point() {
+    let faults = Arc::new(FaultInjector::new(0));
+    let stm = setup(&faults);
+    let delay = Duration::from_millis(30);
+    faults.add(
+        Some("transfer"),
+        Fault::Delay(YieldPoint::Locked, delay),
+        Trigger::Always,
+    );
+
+    let start = Instant::now();
+    assert_eq!(transfer(&stm, 10), Ok(1));
+    assert!(start.elapsed() >= delay);
+}
+
+#[test]
+fn chance_triggers_repeat_with_the_seed() {
+    let outcomes = |seed| {
+        let faults = Arc::new(FaultInjector::new(seed));
+        let stm = setup(&faults);
+        faults.add(Some("transfer"), Fault::Retry, Trigger::Chance(0.5));
+        (0..64)
+            .map(|_| transfer(&stm, 1).is_ok())
+            .collect::<Vec<_>>()
+    };
+    let first = outcomes(7);
+    assert_eq!(outcomes(7), first);
+    assert!(first.contains(&true));
+
+    let faults = Arc::new(FaultInjector::new(7));
+    let stm = setup(&faults);
+    faults.add(Some("transfer"), Fault::Retry, Trigger::Chance(0.0));
+    assert!((0..64).all(|_| transfer(&stm, 1) == Ok(1)));
+}
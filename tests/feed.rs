@@ -0,0 +1,122 @@
+use std::sync::mpsc::Receiver;
+use std::thread;
+
+use tl2::{CommitRecord, STMResult, StripeValue, STM};
+
+const CAPACITY: usize = 256;
+const THREADS: u64 = 4;
+const COMMITS: u64 = 500;
+
+fn commit(stm: &STM, addr: usize, v: u64) {
+    stm.write_transaction(|tr| {
+        tr.store(addr, v.to_stripe());
+        STMResult::Ok(())
+    })
+    .unwrap();
+}
+
+// Writers moving amounts between stripes, so commits conflict.
+fn workload(stm: &STM) {
+    thread::scope(|s| {
+        for t in 0..THREADS {
+            s.spawn(move || {
+                for n in 0..COMMITS {
+                    let from = ((t * 7 + n) % 32 * 8) as usize;
+                    let to = ((t + n * 3) % 32 * 8) as usize;
+                    stm.write_transaction(|tr| {
+                        let a = u64::from_stripe(tl2::load!(tr, from));
+                        let b = u64::from_stripe(tl2::load!(tr, to));
+                        tr.store(from, a.wrapping_sub(n).to_stripe());
+                        tr.store(to, b.wrapping_add(n).to_stripe());
+                        STMResult::Ok(())
+                    })
+                    .unwrap();
+                }
+            });
+        }
+    });
+}
+
+fn drain(rx: &Receiver<CommitRecord>) -> Vec<CommitRecord> {
+    rx.try_iter().collect()
+}
+
+#[test]
+fn records_applied_in_version_order_rebuild_memory() {
+    let stm = STM::builder()
+        .capacity(CAPACITY)
+        .feed_buffer(1 << 16)
+        .build();
+    let (rx1, rx2) = (stm.subscribe(), stm.subscribe());
+    workload(&stm);
+
+    // concurrent commits may reach the two in different orders
+    let mut records = drain(&rx1);
+    let mut other = drain(&rx2);
+    records.sort_by_key(|r| r.version);
+    other.sort_by_key(|r| r.version);
+    assert_eq!(records, other, "subscribers see the same records");
+
+    // every version handed out arrives once, failed commits included
+    let versions: Vec<u64> = records.iter().map(|r| r.version).collect();
+    let want: Vec<u64> = (1..=stm.current_version()).collect();
+    assert_eq!(versions, want);
+    assert!(records.iter().all(|r| !r.gap));
+
+    let mut mirror = vec![0u8; CAPACITY];
+    for r in records.iter() {
+        assert!(r.entries.windows(2).all(|w| w[0].0 < w[1].0));
+        for (addr, bytes) in r.entries.iter() {
+            mirror[*addr..*addr + 8].copy_from_slice(bytes);
+        }
+    }
+    assert_eq!(mirror, stm.snapshot_range(0, CAPACITY).unwrap());
+    // one record with entries per commit, the rest from failed attempts
+    let written = records.iter().filter(|r| !r.entries.is_empty()).count();
+    assert_eq!(written as u64, THREADS * COMMITS);
+}
+
+#[test]
+fn a_full_subscriber_loses_records_and_sees_a_gap() {
+    let stm = STM::builder().stats(true).feed_buffer(2).build();
+    let rx = stm.subscribe();
+    for v in 1..=5 {
+        commit(&stm, 0, v);
+    }
+
+    let got = drain(&rx);
+    let versions: Vec<u64> = got.iter().map(|r| r.version).collect();
+    assert_eq!(versions, [1, 2]);
+    assert!(got.iter().all(|r| !r.gap));
+    assert_eq!(stm.stats().unwrap().feed_drops, 3);
+
+    // the next record flags the loss, the one after it does not
+    commit(&stm, 0, 6);
+    commit(&stm, 0, 7);
+    let got = drain(&rx);
+    assert_eq!(got.len(), 2);
+    assert_eq!((got[0].version, got[0].gap), (6, true));
+    assert_eq!(got[0].entries, [(0, 6u64.to_stripe())]);
+    assert_eq!((got[1].version, got[1].gap), (7, false));
+}
+
+#[test]
+fn a_stalled_subscriber_does_not_stall_the_workload() {
+    let stm = STM::builder().stats(true).feed_buffer(4).build();
+    let stalled = stm.subscribe();
+
+    // nobody reads `stalled` while the writers run
+    workload(&stm);
+
+    let total = stm.current_version();
+    assert!(total >= THREADS * COMMITS);
+    assert_eq!(stm.stats().unwrap().feed_drops, total - 4);
+    let kept: Vec<u64> = drain(&stalled).iter().map(|r| r.version).collect();
+    assert_eq!(kept.len(), 4);
+
+    // dropping the receiver unsubscribes it, so nothing more is dropped
+    drop(stalled);
+    commit(&stm, 0, 1);
+    commit(&stm, 0, 2);
+    assert_eq!(stm.stats().unwrap().feed_drops, total - 4);
+}
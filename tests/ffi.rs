@@ -0,0 +1,26 @@
+#![cfg(feature = "ffi")]
+
+use std::hint::black_box;
+use std::os::raw::c_int;
+
+use tl2::ffi;
+
+extern "C" {
+    // examples/ffi.c, compiled by build.rs with `main` renamed
+    fn tl2_ffi_example() -> c_int;
+}
+
+#[test]
+fn c_example_passes() {
+    // The C archive is linked after tl2, so the functions it calls have to
+    // be pulled in from here.
+    black_box([
+        ffi::tl2_stm_new as *const (),
+        ffi::tl2_stm_free as *const (),
+        ffi::tl2_write_txn as *const (),
+        ffi::tl2_read_txn as *const (),
+        ffi::tl2_load as *const (),
+        ffi::tl2_store as *const (),
+    ]);
+    assert_eq!(unsafe { tl2_ffi_example() }, 0);
+}
@@ -0,0 +1,41 @@
+use std::mem;
+
+use tl2::{Footprint, STMResult, STM};
+
+#[test]
+fn data_bytes_is_the_configured_capacity() {
+    assert_eq!(STM::new().footprint().data_bytes, STM::new().capacity());
+    for capacity in [8, 512, 4096, 1 << 20] {
+        let stm = STM::builder().capacity(capacity).build();
+        let f = stm.footprint();
+        assert_eq!(f.data_bytes, capacity);
+        // a version word per stripe
+        assert_eq!(f.lock_bytes, capacity / 8 * mem::size_of::<u64>());
+        assert!(f.overhead >= mem::size_of::<STM>());
+        assert_eq!(f.total(), f.data_bytes + f.lock_bytes + f.overhead);
+    }
+}
+
+#[test]
+fn only_the_per_stripe_parts_grow_with_the_capacity() {
+    let small = STM::builder().capacity(4096).build().footprint();
+    let large = STM::builder().capacity(8192).build().footprint();
+    assert_eq!(large.data_bytes, 2 * small.data_bytes);
+    assert_eq!(large.lock_bytes, 2 * small.lock_bytes);
+    let fixed = mem::size_of::<STM>();
+    assert_eq!(large.overhead - fixed, 2 * (small.overhead - fixed));
+}
+
+#[test]
+fn the_footprint_does_not_change_with_use() {
+    let stm = STM::builder().capacity(1024).stats(true).build();
+    let before: Footprint = stm.footprint();
+    for i in 0..100u64 {
+        stm.write_transaction(|tr| {
+            tr.store((i as usize % 128) * 8, i.to_le_bytes());
+            STMResult::Ok(())
+        })
+        .unwrap();
+    }
+    assert_eq!(stm.footprint(), before);
+}
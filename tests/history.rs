@@ -0,0 +1,96 @@
+#![cfg(feature = "history")]
+
+use std::thread;
+
+use tl2::{STMResult, StripeValue, STM};
+
+const CAPACITY: usize = 256;
+
+// Store `v` at each of `addrs`; returns the commit's version.
+fn commit(stm: &STM, addrs: &[usize], v: u64) -> u64 {
+    stm.write_transaction_with_version(|tr| {
+        for &a in addrs {
+            tr.store(a, v.to_stripe());
+        }
+        STMResult::Ok(())
+    })
+    .unwrap()
+    .1
+}
+
+#[test]
+fn the_last_commits_come_back_in_version_order() {
+    let stm = STM::builder().capacity(CAPACITY).commit_history(3).build();
+    assert!(stm.recent_commits().is_empty());
+
+    let versions: Vec<u64> = (1..=5u64)
+        .map(|n| commit(&stm, &[n as usize * 16, 8], n))
+        .collect();
+
+    // the ring forgot the first two
+    let records = stm.recent_commits();
+    let got: Vec<u64> = records.iter().map(|r| r.version).collect();
+    assert_eq!(got, versions[2..]);
+    for (r, n) in records.iter().zip(3..=5u64) {
+        assert_eq!(
+            r.entries,
+            [(8, n.to_stripe()), (n as usize * 16, n.to_stripe())]
+        );
+        assert!(!r.gap);
+    }
+}
+
+#[test]
+fn commits_writing_nothing_are_not_kept() {
+    let stm = STM::builder().capacity(CAPACITY).commit_history(8).build();
+    let v = commit(&stm, &[0], 1);
+    stm.read_transaction(|tr| STMResult::Ok(tl2::load!(tr, 0)))
+        .unwrap();
+    commit(&stm, &[], 2);
+
+    let records = stm.recent_commits();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].version, v);
+}
+
+#[test]
+fn concurrent_commits_replayed_in_order_rebuild_memory() {
+    const THREADS: u64 = 4;
+    const COMMITS: u64 = 200;
+    let stm = STM::builder()
+        .capacity(CAPACITY)
+        .commit_history((THREADS * COMMITS) as usize)
+        .build();
+
+    thread::scope(|s| {
+        for t in 0..THREADS {
+            let stm = &stm;
+            s.spawn(move || {
+                for n in 0..COMMITS {
+                    let a = ((t * 5 + n) % 32 * 8) as usize;
+                    let b = ((t + n * 3) % 32 * 8) as usize;
+                    commit(stm, &[a, b], t * COMMITS + n);
+                }
+            });
+        }
+    });
+
+    let records = stm.recent_commits();
+    assert_eq!(records.len(), (THREADS * COMMITS) as usize);
+    assert!(records.windows(2).all(|w| w[0].version < w[1].version));
+
+    let mut memory = vec![0u8; CAPACITY];
+    for r in &records {
+        for (addr, v) in &r.entries {
+            memory[*addr..*addr + 8].copy_from_slice(v);
+        }
+    }
+    assert_eq!(stm.snapshot_range(0, CAPACITY).unwrap(), memory);
+}
+
+#[test]
+fn without_a_history_nothing_is_kept() {
+    let stm = STM::new();
+    commit(&stm, &[0], 1);
+    assert!(stm.recent_commits().is_empty());
+}
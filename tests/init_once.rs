@@ -0,0 +1,85 @@
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Barrier;
+use std::thread;
+
+use tl2::{STMResult, StripeValue, STM};
+
+const THREADS: u64 = 16;
+
+// Initialize the cell at 0 with `v`; returns the value and whether the
+// committed attempt was the one that ran `init`.
+fn init(stm: &STM, v: u64, runs: &AtomicU32) -> (u64, bool) {
+    stm.write_transaction(|tr| {
+        let ran = Cell::new(false);
+        let val = tr.init_once(0, || {
+            runs.fetch_add(1, Ordering::SeqCst);
+            ran.set(true);
+            v.to_stripe()
+        });
+        match val {
+            Some(val) => STMResult::Ok((u64::from_stripe(val), ran.get())),
+            None => STMResult::Retry,
+        }
+    })
+    .unwrap()
+}
+
+#[test]
+fn of_many_racing_threads_exactly_one_initializes() {
+    let stm = STM::new();
+    let runs = AtomicU32::new(0);
+    let start = Barrier::new(THREADS as usize);
+
+    let results: Vec<(u64, bool)> = thread::scope(|s| {
+        let handles: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let (stm, runs, start) = (&stm, &runs, &start);
+                s.spawn(move || {
+                    start.wait();
+                    init(stm, 100 + t, runs)
+                })
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    let winners: Vec<u64> = results
+        .iter()
+        .filter(|(_, ran)| *ran)
+        .map(|(v, _)| *v)
+        .collect();
+    assert_eq!(winners.len(), 1, "{:?}", results);
+    let won = winners[0];
+    assert!((100..100 + THREADS).contains(&won));
+    assert!(results.iter().all(|(v, _)| *v == won));
+    // attempts thrown away may have run `init` too, but never committed
+    assert!(runs.load(Ordering::SeqCst) >= 1);
+
+    // once set, `init` never runs again
+    let before = runs.load(Ordering::SeqCst);
+    assert_eq!(init(&stm, 1, &runs), (won, false));
+    assert_eq!(runs.load(Ordering::SeqCst), before);
+}
+
+#[test]
+fn a_zero_value_still_counts_as_initialized() {
+    let stm = STM::new();
+    let runs = AtomicU32::new(0);
+    assert_eq!(init(&stm, 0, &runs), (0, true));
+    assert_eq!(init(&stm, 5, &runs), (0, false));
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn an_aborted_initialization_leaves_the_cell_empty() {
+    let stm = STM::new();
+    let r = stm.write_transaction(|tr| {
+        tr.init_once(0, || 9u64.to_stripe());
+        STMResult::<()>::Abort
+    });
+    assert_eq!(r, None);
+
+    let runs = AtomicU32::new(0);
+    assert_eq!(init(&stm, 3, &runs), (3, true));
+}
@@ -0,0 +1,68 @@
+#![cfg(feature = "testing")]
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use tl2::{ConflictCause, EventKind, STMResult, StripeValue, STM};
+
+fn write(stm: &STM, addr: usize, v: u64) {
+    stm.write_transaction(|tr| {
+        tr.store(addr, v.to_stripe());
+        STMResult::Ok(())
+    })
+    .unwrap();
+}
+
+fn read(stm: &STM, addr: usize) -> u64 {
+    stm.read_transaction(|tr| STMResult::Ok(u64::from_stripe(tl2::load!(tr, addr))))
+        .unwrap()
+}
+
+// Copy stripe 0 to stripe 8; returns how many times the body ran.
+fn copy(stm: &STM) -> u32 {
+    let runs = AtomicU32::new(0);
+    stm.write_transaction_labeled("copy", |tr| {
+        runs.fetch_add(1, Ordering::SeqCst);
+        let v = u64::from_stripe(tl2::load!(tr, 0));
+        tr.store(8, v.to_stripe());
+        STMResult::Ok(())
+    })
+    .unwrap();
+    runs.into_inner()
+}
+
+#[test]
+fn an_injected_write_forces_exactly_one_restart() {
+    let stm = STM::builder().stats(true).build();
+    stm.inject_conflict_before_commit(|stm| write(stm, 0, 5));
+
+    assert_eq!(copy(&stm), 2);
+    // the second run read what the injected transaction committed
+    assert_eq!((read(&stm, 0), read(&stm, 8)), (5, 5));
+
+    let stats = stm.stats().unwrap();
+    assert_eq!(stats.commits, 2);
+    assert_eq!(stats.validation, 1);
+    assert_eq!(stats.restarts(), 1);
+    let events = stm.recent_events();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].label, Some("copy"));
+    assert_eq!(
+        events[0].kind,
+        EventKind::Restart(ConflictCause::Validation)
+    );
+    assert_eq!(events[0].addr, Some(0));
+
+    // the injection is used up: the next transaction runs once
+    assert_eq!(copy(&stm), 1);
+}
+
+#[test]
+fn an_injected_write_outside_the_read_set_changes_nothing() {
+    let stm = STM::builder().stats(true).build();
+    write(&stm, 0, 3);
+    stm.inject_conflict_before_commit(|stm| write(stm, 16, 9));
+
+    assert_eq!(copy(&stm), 1);
+    assert_eq!((read(&stm, 8), read(&stm, 16)), (3, 9));
+    assert_eq!(stm.stats().unwrap().restarts(), 0);
+}
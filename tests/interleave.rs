@@ -0,0 +1,207 @@
+#![cfg(feature = "testing")]
+
+// Scripted interleavings of two write transactions, "a" and "b", one per
+// window of the commit protocol. Each script holds a thread at a
+// `YieldPoint` until the other has reached the step before it.
+
+use std::sync::Arc;
+use std::thread::{self, Scope, ScopedJoinHandle};
+
+use tl2::{
+    ConflictCause, EventKind, STMResult, Script, StripeValue, TxEvent, WriteTrans, YieldPoint, STM,
+};
+
+fn stm(script: &Arc<Script>) -> STM {
+    STM::builder().stats(true).scheduler(script.clone()).build()
+}
+
+// Run `f` as a write transaction labeled and on a thread named `name`.
+fn spawn<'s, F, R>(
+    s: &'s Scope<'s, '_>,
+    stm: &'s STM,
+    name: &'static str,
+    f: F,
+) -> ScopedJoinHandle<'s, R>
+where
+    F: Fn(&mut WriteTrans) -> STMResult<R> + Send + 's,
+    R: Send + 's,
+{
+    thread::Builder::new()
+        .name(name.into())
+        .spawn_scoped(s, move || stm.write_transaction_labeled(name, f).unwrap())
+        .unwrap()
+}
+
+fn put(addr: usize, v: u64) -> impl Fn(&mut WriteTrans) -> STMResult<()> {
+    move |tr| {
+        tr.store(addr, v.to_stripe());
+        STMResult::Ok(())
+    }
+}
+
+fn read(stm: &STM, addr: usize) -> u64 {
+    stm.read_transaction(|tr| STMResult::Ok(u64::from_stripe(tl2::load!(tr, addr))))
+        .unwrap()
+}
+
+fn restarts(events: &[TxEvent]) -> Vec<(Option<&'static str>, ConflictCause, Option<usize>)> {
+    events
+        .iter()
+        .filter_map(|e| match e.kind {
+            EventKind::Restart(cause) => Some((e.label, cause, e.addr)),
+            EventKind::Failed(_) => None,
+        })
+        .collect()
+}
+
+#[test]
+fn a_writer_finding_the_stripe_locked_restarts_with_lock() {
+    let script = Arc::new(Script::new([
+        ("a", YieldPoint::Locked),
+        ("b", YieldPoint::Sampled),
+        ("b", YieldPoint::Locked),
+        ("a", YieldPoint::Publishing),
+        ("a", YieldPoint::Committed),
+        ("b", YieldPoint::Sampled),
+    ]));
+    let stm = stm(&script);
+    thread::scope(|s| {
+        spawn(s, &stm, "a", put(0, 1));
+        spawn(s, &stm, "b", put(0, 2));
+    });
+    assert!(script.is_done());
+    assert_eq!(
+        restarts(&stm.recent_events()),
+        [(Some("b"), ConflictCause::Lock, Some(0))]
+    );
+    // b committed last
+    assert_eq!(read(&stm, 0), 2);
+}
+
+#[test]
+fn a_load_of_a_stripe_committed_after_the_sample_restarts() {
+    let script = Arc::new(Script::new([
+        ("b", YieldPoint::Sampled),
+        ("a", YieldPoint::Sampled),
+        ("a", YieldPoint::Committed),
+    ]));
+    let stm = stm(&script);
+    let got = thread::scope(|s| {
+        spawn(s, &stm, "a", put(0, 1));
+        let b = spawn(s, &stm, "b", |tr| {
+            // the first run loads only once a has committed
+            while !script.is_done() {
+                thread::yield_now();
+            }
+            STMResult::Ok(u64::from_stripe(tl2::load!(tr, 0)))
+        });
+        b.join().unwrap()
+    });
+    assert_eq!(got, 1);
+    assert_eq!(
+        restarts(&stm.recent_events()),
+        [(Some("b"), ConflictCause::PreValidation, Some(0))]
+    );
+}
+
+#[test]
+fn a_stripe_written_between_the_body_and_the_commit_fails_validation() {
+    let script = Arc::new(Script::new([
+        ("b", YieldPoint::Executed),
+        ("a", YieldPoint::Sampled),
+        ("a", YieldPoint::Committed),
+        ("b", YieldPoint::Locked),
+    ]));
+    let stm = stm(&script);
+    thread::scope(|s| {
+        spawn(s, &stm, "a", put(0, 5));
+        // copy stripe 0 to stripe 8
+        spawn(s, &stm, "b", |tr| {
+            let v = u64::from_stripe(tl2::load!(tr, 0));
+            tr.store(8, v.to_stripe());
+            STMResult::Ok(())
+        });
+    });
+    assert!(script.is_done());
+    assert_eq!(
+        restarts(&stm.recent_events()),
+        [(Some("b"), ConflictCause::Validation, Some(0))]
+    );
+    assert_eq!(read(&stm, 8), 5);
+}
+
+#[test]
+fn a_load_of_a_stripe_locked_for_publishing_restarts() {
+    let script = Arc::new(Script::new([
+        ("a", YieldPoint::Locked),
+        ("b", YieldPoint::Sampled),
+        ("b", YieldPoint::Executed),
+        ("a", YieldPoint::Publishing),
+    ]));
+    let stm = stm(&script);
+    let got = thread::scope(|s| {
+        spawn(s, &stm, "a", put(0, 7));
+        let b = spawn(s, &stm, "b", |tr| {
+            let v = u64::from_stripe(tl2::load!(tr, 0));
+            tr.store(16, v.to_stripe());
+            STMResult::Ok(v)
+        });
+        b.join().unwrap()
+    });
+    assert_eq!(got, 7);
+    let events = restarts(&stm.recent_events());
+    assert_eq!(
+        events[0],
+        (Some("b"), ConflictCause::PreValidation, Some(0))
+    );
+    assert!(events.iter().all(|e| e.0 == Some("b")), "{:?}", events);
+}
+
+#[test]
+fn a_half_applied_commit_is_never_read() {
+    let script = Arc::new(Script::new([
+        ("a", YieldPoint::Publishing),
+        ("b", YieldPoint::Sampled),
+        ("b", YieldPoint::Executed),
+        ("a", YieldPoint::Applying),
+    ]));
+    let stm = stm(&script);
+    let got = thread::scope(|s| {
+        // a moves both stripes from 0 to 1 together
+        spawn(s, &stm, "a", |tr| {
+            tr.store(0, 1u64.to_stripe());
+            tr.store(8, 1u64.to_stripe());
+            STMResult::Ok(())
+        });
+        let b = spawn(s, &stm, "b", |tr| {
+            let x = u64::from_stripe(tl2::load!(tr, 0));
+            let y = u64::from_stripe(tl2::load!(tr, 8));
+            tr.store(16, (x + y).to_stripe());
+            STMResult::Ok((x, y))
+        });
+        b.join().unwrap()
+    });
+    assert!(script.is_done());
+    assert_eq!(got, (1, 1));
+    let events = restarts(&stm.recent_events());
+    assert_eq!(events[0].1, ConflictCause::PreValidation);
+}
+
+#[test]
+fn disjoint_writers_commit_past_each_others_locks() {
+    let script = Arc::new(Script::new([
+        ("a", YieldPoint::Locked),
+        ("b", YieldPoint::Sampled),
+        ("b", YieldPoint::Committed),
+        ("a", YieldPoint::Publishing),
+    ]));
+    let stm = stm(&script);
+    thread::scope(|s| {
+        spawn(s, &stm, "a", put(0, 1));
+        spawn(s, &stm, "b", put(8, 2));
+    });
+    assert!(script.is_done());
+    assert!(stm.recent_events().is_empty());
+    assert_eq!(stm.stats().unwrap().commits, 2);
+    assert_eq!((read(&stm, 0), read(&stm, 8)), (1, 2));
+}
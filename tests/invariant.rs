@@ -0,0 +1,182 @@
+// The k-location invariant: transfer transactions move units between
+// stripes whose total must stay constant, while a reader checks the total
+// on every snapshot it takes. Runs are bounded by transfers, not time, and
+// seeded per thread; a failure prints the seed, to be repeated with
+// `TL2_SEED=<seed>`. Every run is repeated on each storage backend.
+// tests/loom.rs model-checks the two-stripe case.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use tl2::{load, store, ExternalStorage, STMBuilder, STMResult, STM};
+
+const TOTAL: u64 = 1_000_000;
+const TRANSFERS: usize = 2_000;
+
+fn seed() -> u64 {
+    std::env::var("TL2_SEED")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(1)
+}
+
+// xorshift64*
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+// Move amounts from one stripe to up to `width` others in one transaction.
+fn transfer(stm: &STM, rng: &mut Rng, stripes: usize, width: usize) {
+    let from = 8 * rng.below(stripes);
+    let moves: Vec<(usize, u64)> = (0..1 + rng.below(width))
+        .map(|_| (8 * rng.below(stripes), rng.next() % 100))
+        .collect();
+    stm.write_transaction(|tr| {
+        let mut a = u64::from_le_bytes(load!(tr, from));
+        for (to, amount) in moves.iter() {
+            if a < *amount || from == *to {
+                continue;
+            }
+            let b = u64::from_le_bytes(load!(tr, *to));
+            a -= amount;
+            store!(tr, *to, (b + amount).to_le_bytes());
+        }
+        store!(tr, from, a.to_le_bytes());
+        STMResult::Ok(())
+    })
+    .unwrap();
+}
+
+// The stripes and their versions, read in one transaction.
+fn snapshot(stm: &STM, stripes: usize) -> (Vec<u64>, Vec<(usize, u64)>) {
+    stm.read_transaction_versioned(|tr| {
+        let mut v = Vec::with_capacity(stripes);
+        for i in 0..stripes {
+            v.push(u64::from_le_bytes(load!(tr, 8 * i)));
+        }
+        STMResult::Ok(v)
+    })
+    .unwrap()
+}
+
+#[cfg(not(feature = "mmap"))]
+const STORAGE: [&str; 2] = ["heap", "external"];
+#[cfg(feature = "mmap")]
+const STORAGE: [&str; 3] = ["heap", "external", "mmap"];
+
+fn builder(storage: &str, size: usize) -> STMBuilder {
+    match storage {
+        "heap" => STM::builder().capacity(size),
+        "external" => STM::builder().storage(ExternalStorage::new(Box::leak(
+            vec![0; size].into_boxed_slice(),
+        ))),
+        #[cfg(feature = "mmap")]
+        "mmap" => {
+            let path = std::env::temp_dir().join(format!(
+                "tl2-invariant-{}-{:?}",
+                std::process::id(),
+                thread::current().id()
+            ));
+            let storage = tl2::MmapStorage::open(&path, size).expect("map the heap file");
+            std::fs::remove_file(&path).expect("remove the heap file");
+            STM::builder().storage(storage)
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn run(threads: usize, stripes: usize, width: usize) {
+    for storage in STORAGE {
+        run_on(storage, threads, stripes, width);
+    }
+}
+
+fn run_on(storage: &str, threads: usize, stripes: usize, width: usize) {
+    let seed = seed();
+    let stm = builder(storage, 8 * stripes).build();
+    stm.write_transaction(|tr| {
+        store!(tr, 0, TOTAL.to_le_bytes());
+        STMResult::Ok(())
+    })
+    .unwrap();
+
+    let done = AtomicUsize::new(0);
+    let mut snapshots = 0;
+    thread::scope(|s| {
+        for n in 0..threads {
+            let (stm, done) = (&stm, &done);
+            s.spawn(move || {
+                let mut rng = Rng::new(seed ^ ((n as u64 + 1) << 32));
+                for _ in 0..TRANSFERS {
+                    transfer(stm, &mut rng, stripes, width);
+                }
+                done.fetch_add(1, Ordering::Relaxed);
+            });
+        }
+
+        while done.load(Ordering::Relaxed) < threads || snapshots == 0 {
+            let (values, versions) = snapshot(&stm, stripes);
+            snapshots += 1;
+            if values.iter().sum::<u64>() != TOTAL {
+                let mut dump = String::new();
+                for (v, (addr, ver)) in values.iter().zip(versions) {
+                    dump += &format!("\n  stripe {:#x}: {} at version {}", addr, v, ver);
+                }
+                panic!(
+                    "invariant violated: storage={} threads={} stripes={} width={} \
+                     seed={} snapshot={} sum={} (expected {}){}",
+                    storage,
+                    threads,
+                    stripes,
+                    width,
+                    seed,
+                    snapshots,
+                    values.iter().sum::<u64>(),
+                    TOTAL,
+                    dump
+                );
+            }
+        }
+    });
+
+    assert_eq!(snapshot(&stm, stripes).0.iter().sum::<u64>(), TOTAL);
+    assert_eq!(stm.locked_stripes(), vec![]);
+}
+
+#[test]
+fn two_stripes() {
+    for threads in [2, 4] {
+        run(threads, 2, 1);
+    }
+}
+
+#[test]
+fn four_stripes() {
+    for threads in [2, 4] {
+        run(threads, 4, 1);
+        run(threads, 4, 3);
+    }
+}
+
+#[test]
+fn sixteen_stripes() {
+    for threads in [2, 4, 8] {
+        run(threads, 16, 1);
+        run(threads, 16, 8);
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use tl2::{Durability, Journal, STMResult, StripeValue, TxError, STM};
+
+mod common;
+
+#[test]
+fn sync_journal_holds_a_record_before_current_version_counts_it() {
+    let path = common::temp_path("journal-sync");
+    let journal = Journal::create(&path, Durability::Sync).unwrap();
+    let stm = Arc::new(STM::builder().journal(journal).build());
+    let done = Arc::new(AtomicBool::new(false));
+
+    let writers: Vec<_> = (0..2)
+        .map(|i| {
+            let stm = stm.clone();
+            thread::spawn(move || {
+                for n in 0..200u64 {
+                    stm.write_transaction(|tr| {
+                        tr.store(i * 64, n.to_stripe());
+                        STMResult::Ok(())
+                    })
+                    .unwrap();
+                }
+            })
+        })
+        .collect();
+
+    let watcher = {
+        let (stm, done, path) = (stm.clone(), done.clone(), path.clone());
+        thread::spawn(move || {
+            let mut checked = 0;
+            while !done.load(Ordering::Relaxed) {
+                let seen = stm.current_version();
+                // a record may be half written at the end of the file
+                let records = match Journal::records(&path) {
+                    Ok(records) => records,
+                    Err(_) => continue,
+                };
+                let versions: BTreeSet<u64> = records.iter().map(|r| r.version).collect();
+                for v in 1..=seen {
+                    assert!(
+                        versions.contains(&v),
+                        "version {} visible, not journaled",
+                        v
+                    );
+                }
+                checked += 1;
+            }
+            checked
+        })
+    };
+
+    for w in writers {
+        w.join().unwrap();
+    }
+    done.store(true, Ordering::Relaxed);
+    assert!(watcher.join().unwrap() > 0);
+    assert_eq!(stm.current_version(), 400);
+    assert_eq!(Journal::records(&path).unwrap().len(), 400);
+}
+
+#[test]
+fn lazy_journal_writes_on_flush() {
+    let path = common::temp_path("journal-lazy");
+    let stm = STM::builder()
+        .journal(Journal::create(&path, Durability::Lazy).unwrap())
+        .build();
+    for n in 1..=3u64 {
+        stm.write_transaction(|tr| {
+            tr.store(0, n.to_stripe());
+            STMResult::Ok(())
+        })
+        .unwrap();
+    }
+    assert!(Journal::records(&path).unwrap().is_empty());
+
+    stm.journal().unwrap().flush().unwrap();
+    let records = Journal::records(&path).unwrap();
+    let versions: Vec<u64> = records.iter().map(|r| r.version).collect();
+    assert_eq!(versions, [1, 2, 3]);
+    assert_eq!(records[2].entries, [(0, 3u64.to_stripe())]);
+}
+
+// /dev/full fails every write, as a full disk would.
+#[cfg(target_os = "linux")]
+#[test]
+fn failed_sync_write_fails_the_commit_and_later_flushes() {
+    let stm = STM::builder()
+        .journal(Journal::create("/dev/full", Durability::Sync).unwrap())
+        .build();
+    let r = stm.try_write_transaction(|tr| {
+        tr.store(0, 7u64.to_stripe());
+        STMResult::Ok(())
+    });
+    assert_eq!(r, Err(TxError::Journal));
+    let v = stm.read_transaction(|tr| STMResult::Ok(u64::from_stripe(tl2::load!(tr, 0))));
+    assert_eq!(v, Some(0));
+    assert!(stm.journal().unwrap().flush().is_err());
+}
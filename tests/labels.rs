@@ -0,0 +1,137 @@
+use std::cell::Cell;
+use std::sync::Barrier;
+use std::thread;
+use std::time::Duration;
+
+use tl2::{LabelStats, STMResult, StripeValue, STM};
+
+fn bump(stm: &STM, label: Option<&'static str>, addr: usize) {
+    let f = |tr: &mut tl2::WriteTrans| {
+        let n = match tr.load(addr) {
+            Some(n) => u64::from_stripe(n),
+            None => return STMResult::Retry,
+        };
+        tr.store(addr, (n + 1).to_stripe());
+        STMResult::Ok(())
+    };
+    match label {
+        Some(l) => stm.write_transaction_labeled(l, f),
+        None => stm.write_transaction(f),
+    }
+    .unwrap();
+}
+
+fn label(stats: &[(Option<&'static str>, LabelStats)], l: Option<&str>) -> LabelStats {
+    stats
+        .iter()
+        .find(|(n, _)| *n == l)
+        .map_or_else(LabelStats::default, |(_, s)| *s)
+}
+
+#[test]
+fn two_labels_accumulate_independently() {
+    let stm = STM::builder().stats(true).build();
+    thread::scope(|s| {
+        for t in 0..4 {
+            let stm = &stm;
+            s.spawn(move || {
+                for _ in 0..200 {
+                    bump(stm, Some("transfer"), 8 * t);
+                    stm.read_transaction_labeled("audit", |tr| STMResult::Ok(tr.load(0)))
+                        .unwrap();
+                    if t == 0 {
+                        bump(stm, None, 64);
+                    }
+                }
+            });
+        }
+    });
+    let r = stm.write_transaction_labeled("transfer", |_| STMResult::<()>::Abort);
+    assert_eq!(r, None);
+
+    let stats = stm.stats_by_label().unwrap();
+    assert_eq!(stats[0].0, None, "the default bucket comes first");
+    let transfer = label(&stats, Some("transfer"));
+    assert_eq!(
+        (transfer.commits, transfer.reads, transfer.aborts),
+        (800, 0, 1)
+    );
+    let audit = label(&stats, Some("audit"));
+    assert_eq!((audit.commits, audit.reads, audit.aborts), (0, 800, 0));
+    assert_eq!(label(&stats, None).commits, 200);
+
+    let total = stm.stats().unwrap();
+    assert_eq!(total.commits, 1000);
+    assert_eq!(
+        total.restarts(),
+        stats.iter().map(|(_, s)| s.restarts).sum::<u64>()
+    );
+}
+
+#[test]
+fn labels_past_the_registry_count_as_unlabeled() {
+    let names: Vec<&'static str> = (0..70)
+        .map(|i| &*Box::leak(format!("l{:02}", i).into_boxed_str()))
+        .collect();
+    let stm = STM::builder().stats(true).build();
+    for name in names {
+        bump(&stm, Some(name), 0);
+    }
+    let stats = stm.stats_by_label().unwrap();
+    assert_eq!(stats.len(), 65, "the default bucket and 64 labels");
+    assert_eq!(label(&stats, None).commits, 6);
+    assert_eq!(label(&stats, Some("l63")).commits, 1);
+    assert_eq!(label(&stats, Some("l64")), LabelStats::default());
+}
+
+#[test]
+fn the_watchdog_names_a_stalled_transaction() {
+    let stm = STM::builder().watchdog(true).build();
+    let (started, checked) = (Barrier::new(2), Barrier::new(2));
+    let stalled = thread::scope(|s| {
+        s.spawn(|| {
+            stm.write_transaction_labeled("slow", |tr| {
+                started.wait();
+                checked.wait();
+                tr.store(0, 1u64.to_stripe());
+                STMResult::Ok(())
+            })
+            .unwrap()
+        });
+        started.wait();
+        thread::sleep(Duration::from_millis(20));
+        let stalled = stm.check_stalled(Duration::from_millis(10), u32::MAX);
+        checked.wait();
+        stalled
+    });
+    assert_eq!(stalled.len(), 1, "{:?}", stalled);
+    assert_eq!(stalled[0].label, Some("slow"));
+    assert!(!stalled[0].read_only);
+    assert_eq!(stalled[0].attempts, 1);
+    assert!(stalled[0].age >= Duration::from_millis(20));
+    assert!(stm
+        .check_stalled(Duration::from_millis(10), u32::MAX)
+        .is_empty());
+}
+
+#[test]
+fn the_heatmap_tells_labels_apart() {
+    let stm = STM::builder().conflict_heatmap(true).build();
+    let first = Cell::new(true);
+    stm.write_transaction_labeled("hot", |tr| {
+        let v = match tr.load(8) {
+            Some(v) => v,
+            None => return STMResult::Retry,
+        };
+        if first.replace(false) {
+            thread::scope(|s| {
+                s.spawn(|| bump(&stm, None, 8));
+            });
+        }
+        tr.store(16, v);
+        STMResult::Ok(())
+    })
+    .unwrap();
+    assert_eq!(stm.conflict_heatmap_labeled("hot"), Some(vec![(8, 1)]));
+    assert_eq!(stm.conflict_heatmap_labeled("cold"), Some(vec![]));
+}
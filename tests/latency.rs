@@ -0,0 +1,110 @@
+use std::thread;
+use std::time::Duration;
+
+use tl2::{Histogram, STMResult, StripeValue, BUCKETS, STM};
+
+fn empty() -> Histogram {
+    Histogram {
+        buckets: [0; BUCKETS],
+    }
+}
+
+fn nanos(n: u64) -> Duration {
+    Duration::from_nanos(n)
+}
+
+#[test]
+fn fake_timings_land_in_log_scale_buckets() {
+    let mut h = empty();
+    for n in [0, 1, 2, 3, 4, 1_000, 1_023, 1_024] {
+        h.record(nanos(n));
+    }
+    h.record(Duration::MAX);
+
+    let mut want = [0; BUCKETS];
+    want[0] = 1; // zero
+    want[1] = 1; // [1, 2)
+    want[2] = 2; // [2, 4)
+    want[3] = 1; // [4, 8)
+    want[10] = 2; // [512, 1024)
+    want[11] = 1; // [1024, 2048)
+    want[BUCKETS - 1] = 1; // anything longer
+    assert_eq!(h.buckets, want);
+    assert_eq!(h.count(), 9);
+}
+
+#[test]
+fn percentiles_report_the_upper_bound_of_their_bucket() {
+    let mut h = empty();
+    assert_eq!(h.p50(), Duration::ZERO);
+
+    for _ in 0..90 {
+        h.record(nanos(100));
+    }
+    for _ in 0..9 {
+        h.record(nanos(10_000));
+    }
+    h.record(nanos(1_000_000));
+
+    assert_eq!(h.p50(), nanos(128));
+    assert_eq!(h.percentile(0.90), nanos(128));
+    assert_eq!(h.percentile(0.91), nanos(16_384));
+    assert_eq!(h.p95(), nanos(16_384));
+    assert_eq!(h.p99(), nanos(16_384));
+    assert_eq!(h.percentile(1.0), nanos(1 << 20));
+    // out of range quantiles are clamped
+    assert_eq!(h.percentile(-1.0), nanos(128));
+    assert_eq!(h.percentile(2.0), nanos(1 << 20));
+}
+
+// Commit `n` write transactions from a new thread, whose sampling tick
+// starts at zero.
+fn commit_from_new_thread(stm: &STM, n: u64) {
+    thread::scope(|s| {
+        s.spawn(|| {
+            for i in 0..n {
+                stm.write_transaction(|tr| {
+                    tr.store(0, i.to_stripe());
+                    STMResult::Ok(())
+                })
+                .unwrap();
+            }
+        });
+    });
+}
+
+#[test]
+fn every_nth_commit_is_sampled_in_every_phase() {
+    let stm = STM::builder().stats(true).latency_sample_every(4).build();
+    commit_from_new_thread(&stm, 10);
+    // reads are never timed
+    stm.read_transaction(|tr| STMResult::Ok(tr.load(0)))
+        .unwrap();
+
+    let l = stm.latency_histograms().unwrap();
+    for h in [l.execute, l.lock, l.validate, l.journal, l.publish] {
+        assert_eq!(h.count(), 3, "the 1st, 5th and 9th commits");
+    }
+    // without a journal that phase takes no time
+    assert_eq!(l.journal.buckets[0], 3);
+
+    stm.reset_stats();
+    assert_eq!(stm.latency_histograms().unwrap().execute.count(), 0);
+}
+
+#[test]
+fn sampling_needs_stats_and_a_nonzero_period() {
+    assert_eq!(STM::new().latency_histograms(), None);
+    assert_eq!(
+        STM::builder()
+            .stats(true)
+            .latency_sample_every(0)
+            .build()
+            .latency_histograms(),
+        None
+    );
+
+    let stm = STM::builder().stats(true).latency_sample_every(1).build();
+    commit_from_new_thread(&stm, 5);
+    assert_eq!(stm.latency_histograms().unwrap().publish.count(), 5);
+}
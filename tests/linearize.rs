@@ -0,0 +1,305 @@
+#![cfg(feature = "testkit")]
+
+mod common;
+
+use std::collections::VecDeque;
+use std::sync::Barrier;
+use std::thread;
+
+use common::TQueue;
+use tl2::{
+    CounterOp, CounterSpec, History, HistoryRecorder, LinearizeError, LogOp, LogSpec, Operation,
+    STMResult, SetOp, SetSpec, Spec, StripeValue, TLog, TSet, STM,
+};
+
+const THREADS: usize = 4;
+const OPS: usize = 40;
+const QUEUE_SLOTS: u64 = 4;
+
+// The sequential model of a `TQueue` of `QUEUE_SLOTS` entries.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+struct QueueSpec(VecDeque<u64>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum QueueOp {
+    // the value pushed, or None when full
+    Push(u64),
+    Pop,
+    Len,
+}
+
+impl Spec for QueueSpec {
+    type Op = QueueOp;
+    type Ret = Option<u64>;
+
+    fn apply(&mut self, op: &QueueOp) -> Option<u64> {
+        match op {
+            QueueOp::Push(_) if self.0.len() as u64 == QUEUE_SLOTS => None,
+            QueueOp::Push(v) => {
+                self.0.push_back(*v);
+                Some(*v)
+            }
+            QueueOp::Pop => self.0.pop_front(),
+            QueueOp::Len => Some(self.0.len() as u64),
+        }
+    }
+}
+
+// xorshift64*
+fn rng(x: &mut u64) -> u64 {
+    *x ^= *x >> 12;
+    *x ^= *x << 25;
+    *x ^= *x >> 27;
+    x.wrapping_mul(0x2545_f491_4f6c_dd1d)
+}
+
+fn op(
+    thread: usize,
+    op: CounterOp,
+    ret: u64,
+    invoke: u64,
+    response: u64,
+) -> Operation<CounterOp, u64> {
+    Operation {
+        thread,
+        op,
+        ret,
+        invoke,
+        response,
+    }
+}
+
+#[test]
+fn a_concurrent_counter_is_linearizable() {
+    let stm = STM::new();
+    let rec = HistoryRecorder::new();
+    thread::scope(|s| {
+        for t in 0..THREADS {
+            let (stm, rec) = (&stm, &rec);
+            s.spawn(move || {
+                let mut x = t as u64 + 1;
+                for _ in 0..OPS {
+                    let n = rng(&mut x) % 4;
+                    if n == 0 {
+                        rec.call(t, CounterOp::Get, || {
+                            stm.read_transaction(|tr| {
+                                STMResult::Ok(u64::from_stripe(tl2::load!(tr, 0)))
+                            })
+                        });
+                        continue;
+                    }
+                    rec.call(t, CounterOp::Add(n), || {
+                        stm.write_transaction(|tr| {
+                            let v = u64::from_stripe(tl2::load!(tr, 0)) + n;
+                            tr.store(0, v.to_stripe());
+                            STMResult::Ok(v)
+                        })
+                    });
+                }
+            });
+        }
+    });
+    let history = rec.take();
+    assert_eq!(history.operations().len(), THREADS * OPS);
+    let order = history.check(CounterSpec::default()).unwrap();
+    assert_eq!(order.len(), THREADS * OPS);
+}
+
+#[test]
+fn a_concurrent_log_is_linearizable() {
+    let stm = STM::builder().capacity(1024).build();
+    let log = TLog::new(0, 1024);
+    let rec = HistoryRecorder::new();
+    thread::scope(|s| {
+        for t in 0..THREADS {
+            let (stm, log, rec) = (&stm, &log, &rec);
+            s.spawn(move || {
+                let mut x = t as u64 + 7;
+                for i in 0..OPS {
+                    let op = match rng(&mut x) % 3 {
+                        0 => LogOp::Len,
+                        1 => LogOp::Get(i % 8),
+                        _ => LogOp::Append((t * OPS + i) as u64),
+                    };
+                    rec.call(t, op, || {
+                        stm.write_transaction(|tr| {
+                            let got = match op {
+                                LogOp::Len => log.len(tr).map(|n| Some(n as u64)),
+                                LogOp::Get(i) => log.get(tr, i).map(|e| e.map(u64::from_stripe)),
+                                LogOp::Append(v) => log
+                                    .append(tr, v.to_stripe())
+                                    .map(|r| Some(r.unwrap() as u64)),
+                            };
+                            got.map_or(STMResult::Retry, STMResult::Ok)
+                        })
+                    });
+                }
+            });
+        }
+    });
+    rec.take().check(LogSpec::default()).unwrap();
+}
+
+#[test]
+fn a_concurrent_set_is_linearizable() {
+    let stm = STM::builder().capacity(1024).build();
+    let set = TSet::<u64>::new(0, 1024);
+    let rec = HistoryRecorder::new();
+    thread::scope(|s| {
+        for t in 0..THREADS {
+            let (stm, set, rec) = (&stm, &set, &rec);
+            s.spawn(move || {
+                let mut x = t as u64 + 13;
+                for _ in 0..OPS {
+                    let v = rng(&mut x) % 6;
+                    let op = match rng(&mut x) % 3 {
+                        0 => SetOp::Insert(v),
+                        1 => SetOp::Remove(v),
+                        _ => SetOp::Contains(v),
+                    };
+                    rec.call(t, op, || {
+                        stm.write_transaction(|tr| {
+                            let got = match op {
+                                SetOp::Insert(v) => set.insert(tr, v).map(|r| r.unwrap()),
+                                SetOp::Remove(v) => set.remove(tr, &v),
+                                SetOp::Contains(v) => set.contains(tr, &v),
+                            };
+                            got.map_or(STMResult::Retry, STMResult::Ok)
+                        })
+                    });
+                }
+            });
+        }
+    });
+    rec.take().check(SetSpec::default()).unwrap();
+}
+
+#[test]
+fn a_concurrent_queue_is_linearizable() {
+    let stm = STM::builder().capacity(TQueue::size(QUEUE_SLOTS)).build();
+    let queue = TQueue::new(0, QUEUE_SLOTS);
+    let rec = HistoryRecorder::new();
+    thread::scope(|s| {
+        for t in 0..THREADS {
+            let (stm, queue, rec) = (&stm, &queue, &rec);
+            s.spawn(move || {
+                let mut x = t as u64 + 17;
+                for i in 0..OPS {
+                    let op = match rng(&mut x) % 5 {
+                        0 => QueueOp::Len,
+                        1 | 2 => QueueOp::Pop,
+                        _ => QueueOp::Push((t * OPS + i) as u64),
+                    };
+                    rec.call(t, op, || {
+                        stm.write_transaction(|tr| {
+                            let got = match op {
+                                QueueOp::Push(v) => {
+                                    queue.push(tr, v).map(|ok| Some(v).filter(|_| ok))
+                                }
+                                QueueOp::Pop => queue.pop(tr),
+                                QueueOp::Len => queue.len(tr).map(Some),
+                            };
+                            got.map_or(STMResult::Retry, STMResult::Ok)
+                        })
+                    });
+                }
+            });
+        }
+    });
+    let history = rec.take();
+    assert_eq!(history.operations().len(), THREADS * OPS);
+    history.check(QueueSpec::default()).unwrap();
+}
+
+#[test]
+fn a_pop_split_over_two_transactions_is_caught() {
+    let stm = STM::builder().capacity(TQueue::size(QUEUE_SLOTS)).build();
+    let queue = TQueue::new(0, QUEUE_SLOTS);
+    let rec = HistoryRecorder::new();
+    for v in [1, 2] {
+        rec.call(0, QueueOp::Push(v), || {
+            stm.write_transaction(|tr| {
+                queue
+                    .push(tr, v)
+                    .map_or(STMResult::Retry, |_| STMResult::Ok(Some(v)))
+            })
+        });
+    }
+    let both_peeked = Barrier::new(2);
+    thread::scope(|s| {
+        for t in 1..3 {
+            let (stm, queue, rec, both_peeked) = (&stm, &queue, &rec, &both_peeked);
+            s.spawn(move || {
+                rec.call(t, QueueOp::Pop, || {
+                    // peek at the front, then pop in a second transaction
+                    let front = stm.read_transaction(|tr| {
+                        queue.peek(tr).map_or(STMResult::Retry, STMResult::Ok)
+                    })?;
+                    both_peeked.wait();
+                    stm.write_transaction(|tr| {
+                        queue.pop(tr).map_or(STMResult::Retry, STMResult::Ok)
+                    })?;
+                    Some(front)
+                });
+            });
+        }
+    });
+    // both report the front, 1, and 2 is popped unseen
+    let history = rec.take();
+    let err = history.check(QueueSpec::default()).unwrap_err();
+    assert!(matches!(err, LinearizeError::NotLinearizable { ref longest } if longest.len() == 3));
+}
+
+#[test]
+fn an_add_split_over_two_transactions_is_caught() {
+    let stm = STM::new();
+    let rec = HistoryRecorder::new();
+    let both_read = Barrier::new(2);
+    thread::scope(|s| {
+        for t in 0..2 {
+            let (stm, rec, both_read) = (&stm, &rec, &both_read);
+            s.spawn(move || {
+                rec.call(t, CounterOp::Add(1), || {
+                    let n = stm.read_transaction(|tr| {
+                        STMResult::Ok(u64::from_stripe(tl2::load!(tr, 0)))
+                    })?;
+                    // both read 0 before either writes: a lost update
+                    both_read.wait();
+                    stm.write_transaction(|tr| {
+                        tr.store(0, (n + 1).to_stripe());
+                        STMResult::Ok(n + 1)
+                    })
+                });
+            });
+        }
+    });
+    let history = rec.take();
+    assert!(history.operations().iter().all(|o| o.ret == 1));
+    let err = history.check(CounterSpec::default()).unwrap_err();
+    assert!(matches!(err, LinearizeError::NotLinearizable { ref longest } if longest.len() == 1));
+}
+
+#[test]
+fn real_time_order_is_respected() {
+    // Get returned 0 after Add(1) had responded with 1
+    let late = History::new(vec![
+        op(0, CounterOp::Add(1), 1, 0, 1),
+        op(1, CounterOp::Get, 0, 2, 3),
+    ]);
+    assert_eq!(
+        late.check(CounterSpec::default()),
+        Err(LinearizeError::NotLinearizable { longest: vec![0] })
+    );
+
+    // overlapping, the Get may go first
+    let overlapping = History::new(vec![
+        op(0, CounterOp::Add(1), 1, 0, 3),
+        op(1, CounterOp::Get, 0, 1, 2),
+    ]);
+    assert_eq!(overlapping.check(CounterSpec::default()), Ok(vec![1, 0]));
+
+    assert_eq!(
+        overlapping.check_bounded(CounterSpec::default(), 1),
+        Err(LinearizeError::Exhausted)
+    );
+}
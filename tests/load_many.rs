@@ -0,0 +1,75 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+use tl2::{STMResult, StripeValue, STM};
+
+// Scattered over the memory; writers always set all of them at once.
+const ADDRS: [usize; 5] = [0x18, 0x88, 0x140, 0x3f8, 0x200];
+
+#[test]
+fn scattered_loads_see_one_version_against_concurrent_writers() {
+    let stm = STM::builder().capacity(0x400).build();
+    let done = AtomicBool::new(false);
+
+    thread::scope(|s| {
+        for t in 0..2u64 {
+            let (stm, done) = (&stm, &done);
+            s.spawn(move || {
+                for k in 0..2_000u64 {
+                    stm.write_transaction(|tr| {
+                        for a in ADDRS {
+                            tr.store(a, (k * 2 + t).to_stripe());
+                        }
+                        // and an unrelated neighbour, to be left out
+                        tr.store(0x10, k.to_stripe());
+                        STMResult::Ok(())
+                    })
+                    .unwrap();
+                }
+                done.store(true, Ordering::SeqCst);
+            });
+        }
+
+        let mut reads = 0;
+        while !done.load(Ordering::SeqCst) || reads == 0 {
+            let got = stm
+                .read_transaction(|tr| tr.load_many(&ADDRS).map_or(STMResult::Retry, STMResult::Ok))
+                .unwrap();
+            assert_eq!(got.len(), ADDRS.len());
+            let first = got[&ADDRS[0]];
+            for a in ADDRS {
+                assert_eq!(got[&a], first, "{:#x} torn from {:#x}", a, ADDRS[0]);
+            }
+            reads += 1;
+        }
+    });
+}
+
+#[test]
+fn repeated_addresses_come_back_once() {
+    let stm = STM::new();
+    stm.write_transaction(|tr| {
+        tr.store(8, 1u64.to_stripe());
+        tr.store(16, 2u64.to_stripe());
+        STMResult::Ok(())
+    })
+    .unwrap();
+
+    let got = stm
+        .read_transaction(|tr| {
+            tr.load_many(&[16, 8, 16, 0])
+                .map_or(STMResult::Retry, STMResult::Ok)
+        })
+        .unwrap();
+    let mut keys: Vec<usize> = got.keys().copied().collect();
+    keys.sort_unstable();
+    assert_eq!(keys, [0, 8, 16]);
+    assert_eq!(u64::from_stripe(got[&8]), 1);
+    assert_eq!(u64::from_stripe(got[&16]), 2);
+    assert_eq!(u64::from_stripe(got[&0]), 0);
+
+    let none = stm
+        .read_transaction(|tr| tr.load_many(&[]).map_or(STMResult::Retry, STMResult::Ok))
+        .unwrap();
+    assert!(none.is_empty());
+}
@@ -0,0 +1,102 @@
+use std::thread;
+
+use tl2::{STMResult, StripeValue, TxError, STM};
+
+const STRIPE_SIZE: usize = 8;
+const STRIPES: usize = 16;
+const THREADS: usize = 4;
+const ROUNDS: usize = 500;
+
+#[test]
+fn no_stripe_stays_locked_once_transactions_finish() {
+    let stm = STM::new();
+    assert!(stm.locked_stripes().is_empty());
+
+    thread::scope(|s| {
+        for t in 0..THREADS {
+            let stm = &stm;
+            s.spawn(move || {
+                for n in 0..ROUNDS {
+                    let a = (t + n) % STRIPES * STRIPE_SIZE;
+                    let b = (t * 7 + n * 3) % STRIPES * STRIPE_SIZE;
+                    // commits, aborts and commits rejected after locking
+                    let r = stm.try_write_transaction(|tr| {
+                        let x = u64::from_stripe(tl2::load!(tr, a));
+                        let y = u64::from_stripe(tl2::load!(tr, b));
+                        tr.store(a, (x + 1).to_stripe());
+                        tr.store(b, (y + 1).to_stripe());
+                        match n % 3 {
+                            0 => STMResult::Ok(()),
+                            1 => {
+                                tr.commit_when(|_| false);
+                                STMResult::Ok(())
+                            }
+                            _ => STMResult::Abort,
+                        }
+                    });
+                    let want = match n % 3 {
+                        0 => Ok(()),
+                        1 => Err(TxError::Rejected),
+                        _ => Err(TxError::Abort),
+                    };
+                    assert_eq!(r, want);
+                }
+            });
+        }
+    });
+
+    assert_eq!(stm.locked_stripes(), Vec::<usize>::new());
+}
+
+// While a commit holds its locks, exactly its write-set is locked.
+#[cfg(feature = "testing")]
+#[test]
+fn a_committing_transaction_holds_its_write_set() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use tl2::{STMBuilder, Scheduler, YieldPoint};
+
+    #[derive(Default)]
+    struct HoldAtLocked {
+        release: AtomicBool,
+    }
+
+    impl Scheduler for HoldAtLocked {
+        fn reached(&self, point: YieldPoint) {
+            if point == YieldPoint::Locked && thread::current().name() == Some("writer") {
+                while !self.release.load(Ordering::SeqCst) {
+                    thread::yield_now();
+                }
+            }
+        }
+    }
+
+    let hold = Arc::new(HoldAtLocked::default());
+    let stm = STMBuilder::new().scheduler(hold.clone()).build();
+
+    let locked = thread::scope(|s| {
+        thread::Builder::new()
+            .name("writer".into())
+            .spawn_scoped(s, || {
+                stm.write_transaction(|tr| {
+                    tr.load(STRIPE_SIZE);
+                    for addr in [9, 2, 5] {
+                        tr.store(addr * STRIPE_SIZE, 1u64.to_stripe());
+                    }
+                    STMResult::Ok(())
+                })
+            })
+            .unwrap();
+        let mut locked = stm.locked_stripes();
+        while locked.len() < 3 {
+            thread::yield_now();
+            locked = stm.locked_stripes();
+        }
+        hold.release.store(true, Ordering::SeqCst);
+        locked
+    });
+
+    // sorted, and the stripe only read is not locked
+    assert_eq!(locked, [2 * STRIPE_SIZE, 5 * STRIPE_SIZE, 9 * STRIPE_SIZE]);
+    assert!(stm.locked_stripes().is_empty());
+}
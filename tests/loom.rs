@@ -0,0 +1,143 @@
+// Model-checked runs of the commit and read protocols, exploring every
+// interleaving up to loom's bounds:
+//
+//   RUSTFLAGS="--cfg loom" cargo test --release --test loom
+#![cfg(loom)]
+
+use loom::sync::Arc;
+use loom::thread;
+
+use tl2::{STMResult, StripeValue, STM};
+
+fn model<F: Fn() + Sync + Send + 'static>(f: F) {
+    let mut builder = loom::model::Builder::new();
+    // a bound of 2 overflows the store history loom keeps per atomic
+    builder.preemption_bound = Some(1);
+    builder.max_branches = 100_000;
+    builder.check(f);
+}
+
+fn stm() -> Arc<STM> {
+    Arc::new(STM::builder().capacity(16).build())
+}
+
+fn increment(stm: &STM, addr: usize) {
+    stm.write_transaction(|tr| {
+        let n = u64::from_stripe(tl2::load!(tr, addr));
+        tr.store(addr, (n + 1).to_stripe());
+        STMResult::Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn concurrent_increments_are_not_lost() {
+    model(|| {
+        let stm = stm();
+        let other = {
+            let stm = stm.clone();
+            thread::spawn(move || increment(&stm, 0))
+        };
+        increment(&stm, 0);
+        other.join().unwrap();
+
+        let n = stm.read_transaction(|tr| STMResult::Ok(u64::from_stripe(tl2::load!(tr, 0))));
+        assert_eq!(n, Some(2));
+    });
+}
+
+// A stripe written whole is never read torn, see `Memory::load_versioned`.
+#[test]
+fn a_stripe_is_never_read_torn() {
+    model(|| {
+        let stm = stm();
+        let writer = {
+            let stm = stm.clone();
+            thread::spawn(move || {
+                stm.write_transaction(|tr| {
+                    tr.store(0, [0xff; 8]);
+                    STMResult::Ok(())
+                })
+                .unwrap()
+            })
+        };
+        let seen = stm
+            .read_transaction(|tr| STMResult::Ok(tl2::load!(tr, 0)))
+            .unwrap();
+        assert!(seen == [0; 8] || seen == [0xff; 8], "torn read {:?}", seen);
+        writer.join().unwrap();
+    });
+}
+
+// A reader sees both stripes of a commit or neither.
+#[test]
+fn a_read_transaction_sees_whole_commits() {
+    model(|| {
+        let stm = stm();
+        let writer = {
+            let stm = stm.clone();
+            thread::spawn(move || {
+                stm.write_transaction(|tr| {
+                    tr.store(0, 1u64.to_stripe());
+                    tr.store(8, 1u64.to_stripe());
+                    STMResult::Ok(())
+                })
+                .unwrap()
+            })
+        };
+        let (a, b) = stm
+            .read_transaction(|tr| {
+                let a = u64::from_stripe(tl2::load!(tr, 0));
+                let b = u64::from_stripe(tl2::load!(tr, 8));
+                STMResult::Ok((a, b))
+            })
+            .unwrap();
+        assert_eq!(a, b);
+        writer.join().unwrap();
+    });
+}
+
+// Wrapping, so that the total is 0 from the start and no commit has to set
+// it up: loom keeps a short store history per atomic, which also limits
+// the model to one writer.
+fn transfer(stm: &STM, from: usize, to: usize, amount: u64) {
+    stm.write_transaction(|tr| {
+        let a = u64::from_stripe(tl2::load!(tr, from));
+        let b = u64::from_stripe(tl2::load!(tr, to));
+        tr.store(from, a.wrapping_sub(amount).to_stripe());
+        tr.store(to, b.wrapping_add(amount).to_stripe());
+        STMResult::Ok(())
+    })
+    .unwrap();
+}
+
+fn total(stm: &STM, first: usize, second: usize) -> u64 {
+    stm.read_transaction(|tr| {
+        let a = u64::from_stripe(tl2::load!(tr, first));
+        let b = u64::from_stripe(tl2::load!(tr, second));
+        STMResult::Ok(a.wrapping_add(b))
+    })
+    .unwrap()
+}
+
+// The two-location invariant of tests/invariant.rs: a transfer, and
+// readers loading the stripes in either order that must always see the
+// same total.
+#[test]
+fn transfers_keep_the_total() {
+    model(|| {
+        let stm = stm();
+        let writer = {
+            let stm = stm.clone();
+            thread::spawn(move || transfer(&stm, 0, 8, 3))
+        };
+        let reader = {
+            let stm = stm.clone();
+            thread::spawn(move || total(&stm, 8, 0))
+        };
+        assert_eq!(total(&stm, 0, 8), 0);
+        assert_eq!(reader.join().unwrap(), 0);
+        writer.join().unwrap();
+        assert_eq!(total(&stm, 0, 8), 0);
+    });
+}
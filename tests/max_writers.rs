@@ -0,0 +1,99 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+
+use tl2::{STMResult, StatsSnapshot, StripeValue, STM};
+
+const THREADS: usize = 8;
+const EACH: u64 = 1_000;
+
+fn conflicts(s: &StatsSnapshot) -> u64 {
+    s.pre_validation + s.post_validation + s.lock + s.validation
+}
+
+// Every thread increments the same two stripes; returns the conflicts.
+fn contend(stm: &STM) -> u64 {
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                for _ in 0..EACH {
+                    stm.write_transaction(|tr| {
+                        for addr in [0, 8] {
+                            let v = u64::from_stripe(tl2::load!(tr, addr));
+                            tr.store(addr, (v + 1).to_stripe());
+                        }
+                        STMResult::Ok(())
+                    })
+                    .unwrap();
+                }
+            });
+        }
+    });
+    let total = stm
+        .read_transaction(|tr| STMResult::Ok(u64::from_stripe(tl2::load!(tr, 8))))
+        .unwrap();
+    assert_eq!(total, THREADS as u64 * EACH);
+    conflicts(&stm.stats().unwrap())
+}
+
+#[test]
+fn one_writer_at_a_time_never_conflicts() {
+    let free = contend(&STM::builder().stats(true).build());
+    let limited = contend(&STM::builder().stats(true).max_writers(1).build());
+    assert_eq!(limited, 0);
+    assert!(free >= limited, "{} vs {}", free, limited);
+}
+
+#[test]
+fn no_more_than_k_bodies_run_at_once() {
+    const K: usize = 2;
+    let stm = STM::builder().max_writers(K).build();
+    let running = AtomicUsize::new(0);
+    let most = AtomicUsize::new(0);
+    thread::scope(|s| {
+        for t in 0..THREADS {
+            let (stm, running, most) = (&stm, &running, &most);
+            s.spawn(move || {
+                for _ in 0..200 {
+                    stm.write_transaction(|tr| {
+                        let n = running.fetch_add(1, Ordering::SeqCst) + 1;
+                        most.fetch_max(n, Ordering::SeqCst);
+                        thread::yield_now();
+                        tr.store(8 * t, 1u64.to_stripe());
+                        running.fetch_sub(1, Ordering::SeqCst);
+                        STMResult::Ok(())
+                    })
+                    .unwrap();
+                }
+            });
+        }
+    });
+    let most = most.load(Ordering::SeqCst);
+    assert!((1..=K).contains(&most), "{} bodies at once", most);
+}
+
+#[test]
+fn a_waiting_retry_does_not_keep_its_slot() {
+    let stm = STM::builder().max_writers(1).build();
+    let waiting = AtomicBool::new(false);
+    thread::scope(|s| {
+        // waits for the flag, which only another writer can set
+        let consumer = s.spawn(|| {
+            stm.write_transaction_blocking(|tr| {
+                waiting.store(true, Ordering::SeqCst);
+                match u64::from_stripe(tl2::load!(tr, 0)) {
+                    0 => STMResult::Retry,
+                    v => STMResult::Ok(v),
+                }
+            })
+        });
+        while !waiting.load(Ordering::SeqCst) {
+            thread::yield_now();
+        }
+        stm.write_transaction(|tr| {
+            tr.store(0, 7u64.to_stripe());
+            STMResult::Ok(())
+        })
+        .unwrap();
+        assert_eq!(consumer.join().unwrap(), Some(7));
+    });
+}
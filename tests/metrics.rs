@@ -0,0 +1,196 @@
+#![cfg(feature = "metrics")]
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use metrics::{
+    Counter, CounterFn, Gauge, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder,
+    SharedString, Unit,
+};
+use tl2::{STMResult, StripeValue, TxError, STM};
+
+// Every counter and histogram by `name{label=value,...}`, labels sorted.
+#[derive(Default)]
+struct Recorded {
+    counters: Mutex<BTreeMap<String, u64>>,
+    histograms: Mutex<BTreeMap<String, Vec<f64>>>,
+}
+
+struct Handle(Arc<Recorded>, String);
+
+impl CounterFn for Handle {
+    fn increment(&self, value: u64) {
+        let mut counters = self.0.counters.lock().unwrap();
+        *counters.entry(self.1.clone()).or_default() += value;
+    }
+
+    fn absolute(&self, value: u64) {
+        let mut counters = self.0.counters.lock().unwrap();
+        let c = counters.entry(self.1.clone()).or_default();
+        *c = (*c).max(value);
+    }
+}
+
+impl HistogramFn for Handle {
+    fn record(&self, value: f64) {
+        let mut histograms = self.0.histograms.lock().unwrap();
+        histograms.entry(self.1.clone()).or_default().push(value);
+    }
+}
+
+struct Recording(Arc<Recorded>);
+
+impl Recording {
+    fn handle(&self, key: &Key) -> Arc<Handle> {
+        let mut labels: Vec<String> = key
+            .labels()
+            .map(|l| format!("{}={}", l.key(), l.value()))
+            .collect();
+        labels.sort();
+        let name = format!("{}{{{}}}", key.name(), labels.join(","));
+        Arc::new(Handle(self.0.clone(), name))
+    }
+
+    fn counters(&self) -> BTreeMap<String, u64> {
+        self.0.counters.lock().unwrap().clone()
+    }
+
+    fn histograms(&self) -> BTreeMap<String, Vec<f64>> {
+        self.0.histograms.lock().unwrap().clone()
+    }
+}
+
+impl Recorder for Recording {
+    fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+    fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+    fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+        Counter::from_arc(self.handle(key))
+    }
+
+    fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+        Gauge::noop()
+    }
+
+    fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+        Histogram::from_arc(self.handle(key))
+    }
+}
+
+// Commit on another thread, where nothing is recorded.
+fn write_other(stm: &STM, addr: usize) {
+    thread::scope(|s| {
+        s.spawn(|| {
+            stm.write_transaction(|tr| {
+                tr.store(addr, 7u64.to_stripe());
+                STMResult::Ok(())
+            })
+        });
+    });
+}
+
+// A commit that restarts once, a labeled commit, a read, an abort and a
+// retry.
+fn workload(stm: &STM) {
+    let runs = AtomicU32::new(0);
+    stm.write_transaction(|tr| {
+        tr.load(0);
+        if runs.fetch_add(1, Ordering::SeqCst) == 0 {
+            write_other(stm, 0);
+        }
+        tr.store(0, 1u64.to_stripe());
+        STMResult::Ok(())
+    })
+    .unwrap();
+    stm.write_transaction_labeled("order", |tr| {
+        tr.store(8, 1u64.to_stripe());
+        STMResult::Ok(())
+    })
+    .unwrap();
+    stm.read_transaction(|tr| STMResult::Ok(tr.load(0)))
+        .unwrap();
+    assert_eq!(
+        stm.try_write_transaction(|_| STMResult::<()>::Abort),
+        Err(TxError::Abort)
+    );
+    assert_eq!(
+        stm.try_write_transaction(|_| STMResult::<()>::Retry),
+        Err(TxError::Retry)
+    );
+}
+
+#[test]
+fn a_run_emits_the_expected_metrics() {
+    let recording = Recording(Arc::default());
+    let stm = STM::builder()
+        .metrics_prefix("orders_stm")
+        .latency_sample_every(1)
+        .build();
+    metrics::with_local_recorder(&recording, || workload(&stm));
+
+    let want: BTreeMap<String, u64> = [
+        ("orders_stm_commits_total{label=}", 1),
+        ("orders_stm_commits_total{label=order}", 1),
+        ("orders_stm_reads_total{label=}", 1),
+        ("orders_stm_aborts_total{label=}", 1),
+        ("orders_stm_retries_total{label=}", 1),
+        ("orders_stm_restarts_total{cause=validation,label=}", 1),
+    ]
+    .iter()
+    .map(|(k, v)| (k.to_string(), *v))
+    .collect();
+    assert_eq!(recording.counters(), want);
+
+    // every sampled commit records each phase once
+    let histograms = recording.histograms();
+    let phases: Vec<&str> = histograms.keys().map(String::as_str).collect();
+    assert_eq!(
+        phases,
+        [
+            "orders_stm_phase_seconds{label=,phase=execute}",
+            "orders_stm_phase_seconds{label=,phase=journal}",
+            "orders_stm_phase_seconds{label=,phase=lock}",
+            "orders_stm_phase_seconds{label=,phase=publish}",
+            "orders_stm_phase_seconds{label=,phase=validate}",
+            "orders_stm_phase_seconds{label=order,phase=execute}",
+            "orders_stm_phase_seconds{label=order,phase=journal}",
+            "orders_stm_phase_seconds{label=order,phase=lock}",
+            "orders_stm_phase_seconds{label=order,phase=publish}",
+            "orders_stm_phase_seconds{label=order,phase=validate}",
+        ]
+    );
+    for (name, values) in histograms {
+        assert_eq!(values.len(), 1, "{}", name);
+        assert!(values[0] >= 0.0, "{}", name);
+    }
+}
+
+#[test]
+fn counters_only_grow_across_runs() {
+    let recording = Recording(Arc::default());
+    let stm = STM::builder().metrics_prefix("orders_stm").build();
+
+    let mut last = BTreeMap::new();
+    for _ in 0..3 {
+        metrics::with_local_recorder(&recording, || workload(&stm));
+        let now = recording.counters();
+        for (name, n) in last.iter() {
+            assert!(now[name] > *n, "{} went from {} to {}", name, n, now[name]);
+        }
+        last = now;
+    }
+    assert_eq!(last["orders_stm_commits_total{label=order}"], 3);
+}
+
+#[test]
+fn nothing_is_emitted_without_a_prefix() {
+    let recording = Recording(Arc::default());
+    let stm = STM::builder().stats(true).latency_sample_every(1).build();
+    metrics::with_local_recorder(&recording, || workload(&stm));
+
+    assert!(recording.counters().is_empty());
+    assert!(recording.histograms().is_empty());
+}
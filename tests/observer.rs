@@ -0,0 +1,185 @@
+use std::cell::Cell;
+use std::sync::{Arc, Barrier, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use tl2::{Conflict, STMResult, StripeValue, TxInfo, TxObserver, STM};
+
+// Records the callbacks for transactions labeled "t".
+#[derive(Default)]
+struct Recording(Mutex<Vec<String>>);
+
+impl Recording {
+    fn note(&self, info: &TxInfo, what: String) {
+        if info.label == Some("t") {
+            let kind = if info.read_only { "read" } else { "write" };
+            self.0.lock().unwrap().push(format!("{} {}", kind, what));
+        }
+    }
+
+    fn take(&self) -> Vec<String> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+impl TxObserver for Recording {
+    fn on_begin(&self, info: &TxInfo) {
+        self.note(info, format!("begin {}", info.attempt));
+    }
+
+    fn on_restart(&self, info: &TxInfo, conflict: Conflict) {
+        self.note(
+            info,
+            format!(
+                "restart {} {:?} at {:?}",
+                info.attempt, conflict.cause, conflict.addr
+            ),
+        );
+    }
+
+    fn on_commit(&self, info: &TxInfo) {
+        self.note(
+            info,
+            format!(
+                "commit {} sets {}/{}",
+                info.attempt, info.read_set, info.write_set
+            ),
+        );
+    }
+
+    fn on_abort(&self, info: &TxInfo) {
+        self.note(info, format!("abort {}", info.attempt));
+    }
+}
+
+fn write_other(stm: &STM) {
+    thread::scope(|s| {
+        s.spawn(|| {
+            stm.write_transaction(|tr| {
+                tr.store(8, 5u64.to_stripe());
+                STMResult::Ok(())
+            })
+        });
+    });
+}
+
+fn observed() -> (STM, Arc<Recording>) {
+    let stm = STM::new();
+    let rec = Arc::new(Recording::default());
+    stm.set_observer(rec.clone());
+    (stm, rec)
+}
+
+#[test]
+fn commit_path() {
+    let (stm, rec) = observed();
+    stm.write_transaction_labeled("t", |tr| {
+        let v = tr.load(8).unwrap_or_default();
+        tr.store(16, v);
+        tr.store(24, v);
+        STMResult::Ok(())
+    })
+    .unwrap();
+    stm.read_transaction_labeled("t", |tr| STMResult::Ok(tr.load(16)))
+        .unwrap();
+    assert_eq!(
+        rec.take(),
+        [
+            "write begin 1",
+            "write commit 1 sets 1/2",
+            "read begin 1",
+            "read commit 1 sets 0/0",
+        ]
+    );
+}
+
+#[test]
+fn restart_path() {
+    let (stm, rec) = observed();
+    let first = Cell::new(true);
+    stm.write_transaction_labeled("t", |tr| {
+        let v = match tr.load(8) {
+            Some(v) => v,
+            None => return STMResult::Retry,
+        };
+        if first.replace(false) {
+            write_other(&stm);
+        }
+        tr.store(16, v);
+        STMResult::Ok(())
+    })
+    .unwrap();
+    assert_eq!(
+        rec.take(),
+        [
+            "write begin 1",
+            "write restart 1 Validation at Some(8)",
+            "write commit 2 sets 1/1",
+        ]
+    );
+}
+
+#[test]
+fn abort_path() {
+    let (stm, rec) = observed();
+    let r = stm.write_transaction_labeled("t", |tr| {
+        tr.store(16, 1u64.to_stripe());
+        STMResult::<()>::Abort
+    });
+    assert_eq!(r, None);
+    assert_eq!(rec.take(), ["write begin 1", "write abort 1"]);
+}
+
+#[test]
+fn elapsed_time_covers_the_body() {
+    let stm = STM::new();
+    let elapsed = Arc::new(Mutex::new(None));
+    struct Timing(Arc<Mutex<Option<Duration>>>);
+    impl TxObserver for Timing {
+        fn on_commit(&self, info: &TxInfo) {
+            *self.0.lock().unwrap() = Some(info.elapsed);
+        }
+    }
+    stm.set_observer(Arc::new(Timing(elapsed.clone())));
+    stm.write_transaction(|tr| {
+        thread::sleep(Duration::from_millis(5));
+        tr.store(0, 1u64.to_stripe());
+        STMResult::Ok(())
+    })
+    .unwrap();
+    assert!(elapsed.lock().unwrap().unwrap() >= Duration::from_millis(5));
+}
+
+// A transaction keeps the observer it began with; the next one gets the
+// replacement, and none after `clear_observer`.
+#[test]
+fn replacing_the_observer_while_a_transaction_runs() {
+    let (stm, old) = observed();
+    let new = Arc::new(Recording::default());
+    let (started, replaced) = (Barrier::new(2), Barrier::new(2));
+    thread::scope(|s| {
+        s.spawn(|| {
+            stm.write_transaction_labeled("t", |tr| {
+                started.wait();
+                replaced.wait();
+                tr.store(0, 1u64.to_stripe());
+                STMResult::Ok(())
+            })
+            .unwrap()
+        });
+        started.wait();
+        stm.set_observer(new.clone());
+        replaced.wait();
+    });
+    assert_eq!(old.take(), ["write begin 1", "write commit 1 sets 0/1"]);
+    assert!(new.take().is_empty());
+
+    stm.read_transaction_labeled("t", |tr| STMResult::Ok(tr.load(0)))
+        .unwrap();
+    assert_eq!(new.take(), ["read begin 1", "read commit 1 sets 0/0"]);
+
+    stm.clear_observer();
+    stm.read_transaction_labeled("t", |tr| STMResult::Ok(tr.load(0)))
+        .unwrap();
+    assert!(new.take().is_empty() && old.take().is_empty());
+}
@@ -0,0 +1,99 @@
+// Ranges whose end does not fit in usize. They must be caught by the
+// checked arithmetic instead of wrapping around to a small address, which
+// on a 32-bit target is only 4 GiB away.
+
+use std::sync::Arc;
+
+use tl2::{PackedArray, STMResult, SoaStm, TBig, TLog, TxBarrier, TxMutex, VClock, STM};
+
+const STRIPE_SIZE: usize = 8;
+const LAST: usize = usize::MAX - (STRIPE_SIZE - 1); // the last aligned address
+
+#[test]
+fn snapshot_range_is_none() {
+    let stm = STM::new();
+    assert_eq!(stm.snapshot_range(LAST, 2 * STRIPE_SIZE), None);
+    assert_eq!(stm.snapshot_range(8, usize::MAX), None);
+    assert_eq!(stm.snapshot_range(0, 16), Some(vec![0; 16]));
+}
+
+#[test]
+#[should_panic(expected = "region end overflows usize")]
+fn zero_region() {
+    STM::new().zero_region(LAST, 2 * STRIPE_SIZE);
+}
+
+#[test]
+#[should_panic(expected = "region end overflows usize")]
+fn export_region() {
+    STM::new().write_transaction(|tr| {
+        tr.export_region(LAST, 2 * STRIPE_SIZE);
+        STMResult::Ok(())
+    });
+}
+
+#[test]
+#[should_panic(expected = "region end overflows usize")]
+fn import_region() {
+    STM::new().write_transaction(|tr| {
+        tr.import_region(LAST, &[0; 2 * STRIPE_SIZE]);
+        STMResult::Ok(())
+    });
+}
+
+#[test]
+#[should_panic]
+fn load_past_the_end() {
+    STM::new().read_transaction(|tr| {
+        tr.load(LAST);
+        STMResult::Ok(())
+    });
+}
+
+#[test]
+#[should_panic(expected = "log end overflows usize")]
+fn tlog() {
+    TLog::new(LAST, 2 * STRIPE_SIZE);
+}
+
+#[test]
+#[should_panic(expected = "value end overflows usize")]
+fn tbig() {
+    TBig::<[u8; 16]>::new(LAST);
+}
+
+#[test]
+#[should_panic(expected = "array end overflows usize")]
+fn packed_array() {
+    PackedArray::<u64>::new(LAST, 2);
+}
+
+#[test]
+#[should_panic(expected = "array end overflows usize")]
+fn packed_array_length() {
+    PackedArray::<u64>::new(0, usize::MAX / 4);
+}
+
+#[test]
+#[should_panic(expected = "array end overflows usize")]
+fn soa() {
+    SoaStm::<[u8; 16]>::new(LAST, 1);
+}
+
+#[test]
+#[should_panic(expected = "clock end overflows usize")]
+fn vclock() {
+    VClock::new(LAST, 2);
+}
+
+#[test]
+#[should_panic(expected = "mutex end overflows usize")]
+fn txmutex() {
+    TxMutex::<[u8; 16]>::new(Arc::new(STM::new()), LAST);
+}
+
+#[test]
+#[should_panic(expected = "barrier end overflows usize")]
+fn barrier() {
+    TxBarrier::new(Arc::new(STM::new()), LAST, 2);
+}
@@ -0,0 +1,99 @@
+use std::cell::Cell;
+use std::thread;
+
+use tl2::{PackedArray, STMResult, WriteTrans, STM};
+
+const THREADS: usize = 4;
+
+// Bump a `u16` with `inc` while another thread bumps its neighbour with
+// `other` in the middle; returns the attempts of the first.
+fn interleave<F, G>(stm: &STM, inc: F, other: G) -> u32
+where
+    F: Fn(&mut WriteTrans) -> Option<()>,
+    G: Fn(&mut WriteTrans) -> Option<()> + Sync,
+{
+    let attempts = Cell::new(0);
+    stm.write_transaction(|tr| {
+        attempts.set(attempts.get() + 1);
+        if inc(tr).is_none() {
+            return STMResult::Retry;
+        }
+        if attempts.get() == 1 {
+            thread::scope(|s| {
+                s.spawn(|| stm.write_transaction(|tr| STMResult::Ok(other(tr))));
+            });
+        }
+        STMResult::Ok(())
+    })
+    .unwrap();
+    attempts.get()
+}
+
+#[test]
+fn adjacent_elements_do_not_conflict() {
+    let stm = STM::new();
+    let arr: PackedArray<u16> = PackedArray::new(0, 4);
+    let arr = &arr;
+    let bump = |idx| {
+        move |tr: &mut WriteTrans| {
+            let v = arr.get(tr, idx)?;
+            arr.set(tr, idx, v + 1);
+            Some(())
+        }
+    };
+    assert_eq!(interleave(&stm, bump(0), bump(1)), 1);
+
+    let vals = stm
+        .read_transaction(|tr| STMResult::Ok((arr.get(tr, 0), arr.get(tr, 1))))
+        .unwrap();
+    assert_eq!(vals, (Some(1), Some(1)));
+}
+
+// The same two `u16`s sharing one stripe, as a plain layout would put
+// them, conflict.
+#[test]
+fn values_sharing_a_stripe_do() {
+    let stm = STM::new();
+    let bump = |byte: usize| {
+        move |tr: &mut WriteTrans| {
+            let mut s = tr.load(0)?;
+            let v = u16::from_le_bytes([s[byte], s[byte + 1]]) + 1;
+            s[byte..byte + 2].copy_from_slice(&v.to_le_bytes());
+            tr.store(0, s);
+            Some(())
+        }
+    };
+    assert_eq!(interleave(&stm, bump(0), bump(2)), 2);
+}
+
+#[test]
+fn threads_on_their_own_elements_never_restart() {
+    let stm = STM::builder().stats(true).build();
+    let arr: PackedArray<u64> = PackedArray::new(64, THREADS);
+    thread::scope(|s| {
+        for t in 0..THREADS {
+            let (stm, arr) = (&stm, &arr);
+            s.spawn(move || {
+                for _ in 0..1_000 {
+                    stm.write_transaction(|tr| match arr.get(tr, t) {
+                        Some(v) => {
+                            arr.set(tr, t, v + 1);
+                            STMResult::Ok(())
+                        }
+                        None => STMResult::Retry,
+                    })
+                    .unwrap();
+                }
+            });
+        }
+    });
+
+    let vals = stm
+        .read_transaction(|tr| {
+            STMResult::Ok((0..THREADS).map(|t| arr.get(tr, t)).collect::<Vec<_>>())
+        })
+        .unwrap();
+    assert_eq!(vals, vec![Some(1_000); THREADS]);
+    let stats = stm.stats().unwrap();
+    assert_eq!(stats.restarts(), 0, "{:?}", stats);
+}
@@ -0,0 +1,107 @@
+#![cfg(feature = "rayon")]
+
+use tl2::{ParError, STMResult, StripeValue, TxError, WriteTrans, STM};
+
+const ACCOUNTS: usize = 10_000;
+const START: u64 = 100;
+// one balance stripe per account, then one visit counter per account
+const VISITS: usize = ACCOUNTS * 8;
+
+fn setup() -> STM {
+    let stm = STM::builder().capacity(2 * VISITS).build();
+    stm.write_transaction(|tr| {
+        for i in 0..ACCOUNTS {
+            tr.store(i * 8, START.to_stripe());
+        }
+        STMResult::Ok(())
+    })
+    .unwrap();
+    stm
+}
+
+// Move from account `i` to a neighbour and count the visit of `i`; returns
+// the amount moved.
+fn adjust(tr: &mut WriteTrans, i: usize) -> STMResult<u64> {
+    let to = (i * 7 + 1) % ACCOUNTS;
+    let amount = (i % 13) as u64;
+    let a = u64::from_stripe(tl2::load!(tr, i * 8));
+    let b = u64::from_stripe(tl2::load!(tr, to * 8));
+    let seen = u64::from_stripe(tl2::load!(tr, VISITS + i * 8));
+    if to != i {
+        tr.store(i * 8, (a - amount).to_stripe());
+        tr.store(to * 8, (b + amount).to_stripe());
+    }
+    tr.store(VISITS + i * 8, (seen + 1).to_stripe());
+    STMResult::Ok(amount)
+}
+
+fn totals(stm: &STM) -> (u64, Vec<u64>) {
+    stm.read_transaction(|tr| {
+        let mut sum = 0;
+        for i in 0..ACCOUNTS {
+            sum += u64::from_stripe(tl2::load!(tr, i * 8));
+        }
+        let mut visits = Vec::with_capacity(ACCOUNTS);
+        for i in 0..ACCOUNTS {
+            visits.push(u64::from_stripe(tl2::load!(tr, VISITS + i * 8)));
+        }
+        STMResult::Ok((sum, visits))
+    })
+    .unwrap()
+}
+
+#[test]
+fn a_parallel_adjustment_keeps_the_total_and_visits_each_index_once() {
+    for batch in [1, 64] {
+        let stm = setup();
+        let moved = stm
+            .par_transact_batched(0..ACCOUNTS, batch, adjust)
+            .unwrap();
+        let want: Vec<u64> = (0..ACCOUNTS).map(|i| (i % 13) as u64).collect();
+        assert_eq!(moved, want, "results come back in index order");
+
+        let (sum, visits) = totals(&stm);
+        assert_eq!(sum, START * ACCOUNTS as u64);
+        assert!(visits.iter().all(|&v| v == 1), "batch {}", batch);
+    }
+}
+
+#[test]
+fn a_panic_on_one_index_leaves_the_others_committed() {
+    let stm = setup();
+    let r = stm.par_transact(0..ACCOUNTS, |tr, i| {
+        if i == 4321 {
+            panic!("bad record");
+        }
+        adjust(tr, i)
+    });
+    assert_eq!(r, Err(ParError::Panic { index: 4321 }));
+
+    let (sum, visits) = totals(&stm);
+    assert_eq!(sum, START * ACCOUNTS as u64);
+    for (i, v) in visits.iter().enumerate() {
+        assert_eq!(*v, (i != 4321) as u64, "index {}", i);
+    }
+    assert!(stm.is_quiescent());
+    assert!(stm.locked_stripes().is_empty());
+}
+
+#[test]
+fn the_lowest_failed_index_is_reported() {
+    let stm = setup();
+    let r = stm.par_transact(0..ACCOUNTS, |tr, i| {
+        if i % 1000 == 999 {
+            return STMResult::Abort;
+        }
+        adjust(tr, i)
+    });
+    assert_eq!(
+        r,
+        Err(ParError::Tx {
+            index: 999,
+            error: TxError::Abort
+        })
+    );
+    let (_, visits) = totals(&stm);
+    assert_eq!(visits.iter().sum::<u64>(), ACCOUNTS as u64 - 10);
+}
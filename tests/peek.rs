@@ -0,0 +1,76 @@
+use std::cell::Cell;
+use std::thread;
+
+use tl2::{STMResult, StripeValue, WriteTrans, STM};
+
+fn write_other(stm: &STM, val: u64) {
+    thread::scope(|s| {
+        s.spawn(|| {
+            stm.write_transaction(|tr| {
+                tr.store(8, val.to_stripe());
+                STMResult::Ok(())
+            })
+        });
+    });
+}
+
+// Read stripe 8 with `read`, have another thread change it, then write
+// stripe 0 from what was read; returns it and the attempts made.
+fn run<F>(stm: &STM, read: F) -> (Option<u64>, u32)
+where
+    F: Fn(&mut WriteTrans) -> Option<[u8; 8]>,
+{
+    let attempts = Cell::new(0);
+    let seen = stm
+        .write_transaction(|tr| {
+            attempts.set(attempts.get() + 1);
+            let seen = read(tr).map(u64::from_stripe);
+            if attempts.get() == 1 {
+                write_other(stm, 2);
+            }
+            tr.store(0, seen.unwrap_or(99).to_stripe());
+            STMResult::Ok(seen)
+        })
+        .unwrap();
+    (seen, attempts.get())
+}
+
+fn setup() -> STM {
+    let stm = STM::new();
+    stm.write_transaction(|tr| {
+        tr.store(8, 1u64.to_stripe());
+        STMResult::Ok(())
+    })
+    .unwrap();
+    stm
+}
+
+#[test]
+fn a_peeked_stripe_changed_concurrently_does_not_abort_the_commit() {
+    let stm = setup();
+    assert_eq!(run(&stm, |tr| tr.peek(8)), (Some(1), 1));
+}
+
+#[test]
+fn a_loaded_stripe_changed_concurrently_does() {
+    let stm = setup();
+    assert_eq!(run(&stm, |tr| tr.load(8)), (Some(2), 2));
+}
+
+#[test]
+fn peeking_a_stripe_newer_than_the_snapshot_returns_none_and_carries_on() {
+    let stm = setup();
+    let attempts = Cell::new(0);
+    let (before, after) = stm
+        .write_transaction(|tr| {
+            attempts.set(attempts.get() + 1);
+            let before = tr.peek(8).map(u64::from_stripe);
+            write_other(&stm, 3);
+            let after = tr.peek(8).map(u64::from_stripe);
+            tr.store(0, 7u64.to_stripe());
+            STMResult::Ok((before, after))
+        })
+        .unwrap();
+    assert_eq!((before, after), (Some(1), None));
+    assert_eq!(attempts.get(), 1);
+}
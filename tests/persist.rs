@@ -0,0 +1,131 @@
+use std::fs;
+
+use tl2::{LoadError, STMResult, STM};
+
+mod common;
+
+const SIZE: usize = 4096;
+
+fn contents(stm: &STM) -> Vec<u8> {
+    stm.snapshot_range(0, stm.capacity()).unwrap()
+}
+
+fn round_trip(stm: &STM, name: &str) -> STM {
+    let path = common::temp_path(name);
+    stm.save_to(&path).unwrap();
+    let loaded = STM::load_from(&path).unwrap();
+    assert_eq!(loaded.capacity(), stm.capacity());
+    assert_eq!(contents(&loaded), contents(stm));
+    loaded
+}
+
+#[test]
+fn empty_heap() {
+    let stm = STM::builder().capacity(SIZE).build();
+    round_trip(&stm, "persist-empty");
+
+    let none = STM::builder().capacity(0).build();
+    round_trip(&none, "persist-none");
+}
+
+#[test]
+fn full_heap() {
+    let stm = STM::builder().capacity(SIZE).build();
+    stm.write_transaction(|tr| {
+        for a in (0..SIZE).step_by(8) {
+            tr.store(a, (a as u64 * 0x0101_0101 + 1).to_le_bytes());
+        }
+        STMResult::Ok(())
+    })
+    .unwrap();
+    assert!(contents(&stm).chunks(8).all(|c| c != [0; 8]));
+    round_trip(&stm, "persist-full");
+}
+
+#[test]
+fn partial_heap() {
+    let stm = STM::builder().capacity(SIZE).build();
+    stm.write_transaction(|tr| {
+        tr.store(0, [1; 8]);
+        tr.store(1024, [2; 8]);
+        tr.store(SIZE - 8, [3; 8]);
+        STMResult::Ok(())
+    })
+    .unwrap();
+    let loaded = round_trip(&stm, "persist-partial");
+
+    // the loaded STM works as any other, from version zero
+    assert_eq!(loaded.current_version(), 0);
+    loaded
+        .write_transaction(|tr| {
+            tr.store(8, [4; 8]);
+            STMResult::Ok(())
+        })
+        .unwrap();
+    assert_eq!(
+        loaded.snapshot_range(0, 16).unwrap(),
+        [[1; 8], [4; 8]].concat()
+    );
+}
+
+#[test]
+fn save_replaces_the_file_through_a_temporary() {
+    let path = common::temp_path("persist-replace");
+    let stm = STM::builder().capacity(SIZE).build();
+    stm.save_to(&path).unwrap();
+    stm.write_transaction(|tr| {
+        tr.store(0, [9; 8]);
+        STMResult::Ok(())
+    })
+    .unwrap();
+    stm.save_to(&path).unwrap();
+
+    let mut tmp = path.clone().into_os_string();
+    tmp.push(".tmp");
+    assert!(!std::path::Path::new(&tmp).exists());
+    assert_eq!(contents(&STM::load_from(&path).unwrap()), contents(&stm));
+}
+
+#[test]
+fn corrupt_checksum() {
+    let path = common::temp_path("persist-corrupt");
+    let stm = STM::builder().capacity(SIZE).build();
+    stm.save_to(&path).unwrap();
+
+    let mut file = fs::read(&path).unwrap();
+    file[24 + 100] ^= 1;
+    fs::write(&path, &file).unwrap();
+    match STM::load_from(&path) {
+        Err(LoadError::Checksum { found, expected }) => assert_ne!(found, expected),
+        r => panic!("{:?}", r.map(|_| ())),
+    }
+
+    // the stored checksum itself damaged
+    file[24 + 100] ^= 1;
+    let last = file.len() - 1;
+    file[last] ^= 0x80;
+    fs::write(&path, &file).unwrap();
+    assert!(matches!(
+        STM::load_from(&path),
+        Err(LoadError::Checksum { .. })
+    ));
+}
+
+#[test]
+fn truncated_and_foreign_files() {
+    let path = common::temp_path("persist-short");
+    STM::builder()
+        .capacity(SIZE)
+        .build()
+        .save_to(&path)
+        .unwrap();
+    let file = fs::read(&path).unwrap();
+
+    fs::write(&path, &file[..file.len() - 1]).unwrap();
+    assert!(matches!(
+        STM::load_from(&path),
+        Err(LoadError::Length { .. })
+    ));
+    fs::write(&path, b"not a snapshot at all, just text").unwrap();
+    assert!(matches!(STM::load_from(&path), Err(LoadError::BadMagic)));
+}
@@ -0,0 +1,182 @@
+// The dining philosophers of examples/philosophers.rs, reduced: every
+// philosopher must get its meals under each waiting strategy and
+// contention manager, with chopsticks only ever held in pairs.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tl2::{load, store, ContentionManager, Greedy, Karma, Polite, STMResult, WriteTrans, STM};
+
+const PHILOSOPHERS: usize = 5;
+const MEALS: u64 = 200;
+// without a meal for this long, a philosopher starved
+const STARVE: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Copy, Debug)]
+enum Strategy {
+    Retry,
+    Blocking,
+    Backoff,
+}
+
+fn chopstick(n: usize) -> usize {
+    8 * n
+}
+
+fn meals_addr(n: usize) -> usize {
+    8 * (PHILOSOPHERS + n)
+}
+
+fn try_pickup(tr: &mut WriteTrans, left: usize, right: usize) -> STMResult<bool> {
+    let l = load!(tr, left);
+    let r = load!(tr, right);
+    if l[0] != 0 || r[0] != 0 {
+        return STMResult::Ok(false);
+    }
+    store!(tr, left, [1; 8]);
+    store!(tr, right, [1; 8]);
+    STMResult::Ok(true)
+}
+
+fn pickup(stm: &STM, strategy: Strategy, left: usize, right: usize) {
+    match strategy {
+        Strategy::Retry => {
+            while !stm
+                .write_transaction(|tr| try_pickup(tr, left, right))
+                .unwrap()
+            {
+                thread::yield_now();
+            }
+        }
+        Strategy::Blocking => {
+            stm.write_transaction_blocking(|tr| match try_pickup(tr, left, right) {
+                STMResult::Ok(false) => STMResult::Retry,
+                other => other,
+            })
+            .unwrap();
+        }
+        Strategy::Backoff => {
+            let mut pause = Duration::from_micros(1);
+            while !stm
+                .write_transaction(|tr| try_pickup(tr, left, right))
+                .unwrap()
+            {
+                thread::sleep(pause);
+                pause = (pause * 2).min(Duration::from_millis(1));
+            }
+        }
+    }
+}
+
+fn release(stm: &STM, left: usize, right: usize, meals: usize) {
+    stm.write_transaction(|tr| {
+        let n = u64::from_le_bytes(load!(tr, meals));
+        store!(tr, left, [0; 8]);
+        store!(tr, right, [0; 8]);
+        store!(tr, meals, (n + 1).to_le_bytes());
+        STMResult::Ok(())
+    })
+    .unwrap();
+}
+
+fn held(stm: &STM) -> usize {
+    stm.read_transaction(|tr| {
+        let mut held = 0;
+        for i in 0..PHILOSOPHERS {
+            held += load!(tr, chopstick(i))[0] as usize;
+        }
+        STMResult::Ok(held)
+    })
+    .unwrap()
+}
+
+fn dine(strategy: Strategy, cm: Option<Arc<dyn ContentionManager>>) {
+    let mut builder = STM::builder().capacity(16 * PHILOSOPHERS);
+    if let Some(cm) = cm {
+        builder = builder.contention_manager(cm);
+    }
+    let stm = Arc::new(builder.build());
+    let progress: Arc<Vec<AtomicU64>> =
+        Arc::new((0..PHILOSOPHERS).map(|_| AtomicU64::new(0)).collect());
+
+    // not scoped, so that a starved run fails instead of hanging
+    let philosophers: Vec<_> = (0..PHILOSOPHERS)
+        .map(|i| {
+            let (stm, progress) = (stm.clone(), progress.clone());
+            thread::spawn(move || {
+                let (left, right) = (chopstick(i), chopstick((i + 1) % PHILOSOPHERS));
+                for m in 1..=MEALS {
+                    pickup(&stm, strategy, left, right);
+                    release(&stm, left, right, meals_addr(i));
+                    progress[i].store(m, Ordering::Relaxed);
+                }
+            })
+        })
+        .collect();
+
+    let mut seen: Vec<(u64, Instant)> = (0..PHILOSOPHERS).map(|_| (0, Instant::now())).collect();
+    while !philosophers.iter().all(|th| th.is_finished()) {
+        let held = held(&stm);
+        assert_eq!(held % 2, 0, "{} chopsticks held, not in pairs", held);
+        for (i, (last, at)) in seen.iter_mut().enumerate() {
+            let now = progress[i].load(Ordering::Relaxed);
+            if now != *last || now == MEALS {
+                *last = now;
+                *at = Instant::now();
+            }
+            assert!(
+                at.elapsed() < STARVE,
+                "{:?}: philosopher {} starved after {} meals",
+                strategy,
+                i,
+                now
+            );
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    for th in philosophers {
+        th.join().unwrap();
+    }
+
+    let eaten = stm
+        .read_transaction(|tr| {
+            let mut eaten = Vec::with_capacity(PHILOSOPHERS);
+            for i in 0..PHILOSOPHERS {
+                eaten.push(u64::from_le_bytes(load!(tr, meals_addr(i))));
+            }
+            STMResult::Ok(eaten)
+        })
+        .unwrap();
+    assert_eq!(eaten, vec![MEALS; PHILOSOPHERS], "{:?}", strategy);
+    assert_eq!(held(&stm), 0);
+}
+
+#[test]
+fn everyone_eats_retrying() {
+    dine(Strategy::Retry, None);
+}
+
+#[test]
+fn everyone_eats_blocking() {
+    dine(Strategy::Blocking, None);
+}
+
+#[test]
+fn everyone_eats_backing_off() {
+    dine(Strategy::Backoff, None);
+}
+
+#[test]
+fn everyone_eats_under_contention_managers() {
+    let managers: [Arc<dyn ContentionManager>; 3] = [
+        Arc::new(Polite::default()),
+        Arc::new(Karma::default()),
+        Arc::new(Greedy::default()),
+    ];
+    for cm in managers.iter() {
+        dine(Strategy::Retry, Some(cm.clone()));
+        dine(Strategy::Blocking, Some(cm.clone()));
+    }
+}
@@ -0,0 +1,117 @@
+#![cfg(feature = "pmem")]
+
+// Crash recovery with `STMBuilder::pmem`. The crashing process is this
+// test binary run again on `pmem_child`, which aborts in the middle of a
+// commit once the first of its two stripes is written back.
+
+use std::env;
+use std::path::PathBuf;
+use std::process::{self, Command, Stdio};
+
+use tl2::{load, store, PmemStorage, STMResult, STM};
+
+const TOTAL: u64 = 1_000_000;
+const SIZE: usize = 64;
+const CHILD: &str = "TL2_PMEM_CHILD";
+
+// Removes the file when the test ends, passing or not.
+struct File(PathBuf);
+
+impl File {
+    fn new(test: &str) -> File {
+        File(env::temp_dir().join(format!("tl2-pmem-{}-{}", test, process::id())))
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+fn open(path: &PathBuf) -> STM {
+    STM::builder()
+        .pmem(PmemStorage::open(path, SIZE).unwrap())
+        .build()
+}
+
+fn transfer(stm: &STM, amount: u64) {
+    stm.write_transaction(|tr| {
+        let a = u64::from_le_bytes(load!(tr, 0));
+        let b = u64::from_le_bytes(load!(tr, 8));
+        store!(tr, 0, (a - amount).to_le_bytes());
+        store!(tr, 8, (b + amount).to_le_bytes());
+        STMResult::Ok(())
+    })
+    .unwrap();
+}
+
+fn balances(stm: &STM) -> (u64, u64) {
+    stm.read_transaction(|tr| {
+        let a = u64::from_le_bytes(load!(tr, 0));
+        let b = u64::from_le_bytes(load!(tr, 8));
+        STMResult::Ok((a, b))
+    })
+    .unwrap()
+}
+
+#[test]
+fn pmem_child() {
+    let Ok(path) = env::var(CHILD) else {
+        return;
+    };
+    let storage = PmemStorage::open(&path, SIZE).unwrap().crash_after(1);
+    let mut stm = STM::builder().pmem(storage).build();
+    stm.recover_pmem();
+    transfer(&stm, 500);
+    unreachable!("the commit did not crash");
+}
+
+#[test]
+fn recovery_undoes_a_commit_torn_by_a_crash() {
+    let file = File::new("torn");
+    let mut stm = open(&file.0);
+    assert_eq!(stm.recover_pmem(), 0, "a new file has nothing to undo");
+    stm.write_transaction(|tr| {
+        store!(tr, 0, TOTAL.to_le_bytes());
+        STMResult::Ok(())
+    })
+    .unwrap();
+    for _ in 0..100 {
+        transfer(&stm, 1);
+    }
+    let version = stm.current_version();
+    drop(stm);
+
+    let status = Command::new(env::current_exe().unwrap())
+        .args(["pmem_child", "--exact", "--quiet"])
+        .env(CHILD, &file.0)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(!status.success(), "the child did not crash");
+
+    // the first stripe of the transfer got written back, the second not
+    let mut stm = open(&file.0);
+    assert_eq!(balances(&stm), (TOTAL - 600, 100));
+    assert_eq!(stm.recover_pmem(), 1);
+    assert_eq!(balances(&stm), (TOTAL - 100, 100));
+    // the torn commit's version is not handed out again
+    assert!(stm.current_version() > version);
+
+    transfer(&stm, 1);
+    drop(stm);
+    let mut stm = open(&file.0);
+    assert_eq!(stm.recover_pmem(), 0);
+    assert_eq!(balances(&stm), (TOTAL - 101, 101));
+}
+
+#[test]
+fn a_file_of_another_size_is_refused() {
+    let file = File::new("size");
+    drop(PmemStorage::open(&file.0, SIZE).unwrap());
+    assert!(PmemStorage::open(&file.0, SIZE * 2).is_err());
+    assert!(PmemStorage::open(&file.0, SIZE + 1).is_err());
+    assert!(PmemStorage::open(&file.0, SIZE).is_ok());
+}
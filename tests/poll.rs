@@ -0,0 +1,94 @@
+use std::cell::{Cell, RefCell};
+use std::thread;
+
+use tl2::{STMResult, StripeValue, STM};
+
+fn write_other(stm: &STM) {
+    thread::scope(|s| {
+        s.spawn(|| {
+            stm.write_transaction(|tr| {
+                let n = tr.load(8).map_or(0, u64::from_stripe);
+                tr.store(8, (n + 1).to_stripe());
+                STMResult::Ok(())
+            })
+        });
+    });
+}
+
+#[test]
+fn on_retry_runs_once_per_conflict_with_the_attempt_number() {
+    let stm = STM::new();
+    let attempts = Cell::new(0);
+    let retries = RefCell::new(Vec::new());
+    let r = stm.write_transaction_poll(
+        |tr| {
+            attempts.set(attempts.get() + 1);
+            let n = match tr.load(8) {
+                Some(n) => u64::from_stripe(n),
+                None => return STMResult::Retry,
+            };
+            // the first three attempts lose to another commit
+            if attempts.get() <= 3 {
+                write_other(&stm);
+            }
+            tr.store(0, n.to_stripe());
+            STMResult::Ok(n)
+        },
+        |attempt| retries.borrow_mut().push(attempt),
+    );
+    assert_eq!(r, Some(3));
+    assert_eq!(attempts.get(), 4);
+    assert_eq!(*retries.borrow(), [1, 2, 3]);
+}
+
+#[test]
+fn on_retry_is_not_called_without_a_conflict() {
+    let stm = STM::new();
+    let mut called = false;
+    let r = stm.write_transaction_poll(
+        |tr| {
+            tr.store(0, 5u64.to_stripe());
+            STMResult::Ok(())
+        },
+        |_| called = true,
+    );
+    assert_eq!(r, Some(()));
+    assert!(!called);
+}
+
+// The caller's cadence: a thread that sleeps in `on_retry` still gets its
+// increments through against a busy neighbour.
+#[test]
+fn callers_can_back_off_in_on_retry() {
+    let stm = STM::new();
+    thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                for _ in 0..200 {
+                    stm.write_transaction_poll(
+                        |tr| {
+                            let n = tr.load(0).map(u64::from_stripe);
+                            match n {
+                                Some(n) => {
+                                    tr.store(0, (n + 1).to_stripe());
+                                    STMResult::Ok(())
+                                }
+                                None => STMResult::Retry,
+                            }
+                        },
+                        |attempt| {
+                            if attempt > 2 {
+                                thread::sleep(std::time::Duration::from_micros(attempt as u64));
+                            } else {
+                                thread::yield_now();
+                            }
+                        },
+                    )
+                    .unwrap();
+                }
+            });
+        }
+    });
+    let n = stm.read_transaction(|tr| STMResult::Ok(tr.load(0).map(u64::from_stripe)));
+    assert_eq!(n, Some(Some(800)));
+}
@@ -0,0 +1,169 @@
+#![cfg(feature = "testing")]
+
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tl2::{
+    ParkWait, STMBuilder, STMResult, Scheduler, StripeValue, WaitStrategy, WaitToken, YieldPoint,
+    STM,
+};
+
+// Blocks `held` once at `Locked`, locks taken, until `go` holds, and
+// records the threads in the order they commit.
+struct Hold<G> {
+    held: &'static str,
+    go: G,
+    done: AtomicBool,
+    committed: Mutex<Vec<String>>,
+}
+
+impl<G: Fn() -> bool + Send + Sync> Scheduler for Hold<G> {
+    fn reached(&self, point: YieldPoint) {
+        let me = thread::current().name().unwrap_or("").to_string();
+        match point {
+            YieldPoint::Locked if me == self.held && !self.done.swap(true, Ordering::SeqCst) => {
+                let start = Instant::now();
+                while !(self.go)() {
+                    assert!(start.elapsed() < Duration::from_secs(5), "never released");
+                    thread::yield_now();
+                }
+            }
+            YieldPoint::Committed => self.committed.lock().unwrap().push(me),
+            _ => (),
+        }
+    }
+}
+
+// Parks like the default, noting that someone waited.
+#[derive(Default)]
+struct Noted {
+    inner: ParkWait,
+    waited: AtomicBool,
+}
+
+impl WaitStrategy for Noted {
+    fn wait(&self, token: WaitToken<'_>) {
+        self.waited.store(true, Ordering::SeqCst);
+        self.inner.wait(token);
+    }
+
+    fn notify(&self, token: WaitToken<'_>) {
+        self.inner.notify(token);
+    }
+}
+
+// Run "low" and "high", both writing stripes 0 and 8, with "low" starting
+// first and holding its locks at `Locked` until its scheduler lets it go.
+// Counts the attempts of each in `attempts`.
+fn race(stm: &Arc<STM>, low: u8, high: u8, attempts: &Arc<[AtomicU32; 2]>) {
+    thread::scope(|s| {
+        let run = |i: usize, prio: u8, val: u64| {
+            let attempts = attempts.clone();
+            let stm = stm.clone();
+            move || {
+                stm.write_transaction_prio(prio, |tr| {
+                    attempts[i].fetch_add(1, Ordering::SeqCst);
+                    tr.store(0, val.to_stripe());
+                    tr.store(8, val.to_stripe());
+                    STMResult::Ok(())
+                })
+                .unwrap()
+            }
+        };
+        thread::Builder::new()
+            .name("low".into())
+            .spawn_scoped(s, run(0, low, 1))
+            .unwrap();
+        while stm.locked_stripes().is_empty() {
+            thread::yield_now();
+        }
+        thread::Builder::new()
+            .name("high".into())
+            .spawn_scoped(s, run(1, high, 2))
+            .unwrap();
+    });
+}
+
+fn values(stm: &STM) -> (u64, u64) {
+    stm.read_transaction(|tr| {
+        let a = tr.load(0).map(u64::from_stripe);
+        let b = tr.load(8).map(u64::from_stripe);
+        match (a, b) {
+            (Some(a), Some(b)) => STMResult::Ok((a, b)),
+            _ => STMResult::Retry,
+        }
+    })
+    .unwrap()
+}
+
+#[test]
+fn higher_priority_wounds_the_holder() {
+    let noted = Arc::new(Noted::default());
+    let waited = noted.clone();
+    // "low" carries on only once "high" waits for it, after wounding it
+    let hold = Arc::new(Hold {
+        held: "low",
+        go: move || waited.waited.load(Ordering::SeqCst),
+        done: AtomicBool::new(false),
+        committed: Mutex::new(Vec::new()),
+    });
+    let stm = Arc::new(
+        STMBuilder::new()
+            .wait_strategy(noted)
+            .scheduler(hold.clone())
+            .build(),
+    );
+
+    let attempts = Arc::new([AtomicU32::new(0), AtomicU32::new(0)]);
+    race(&stm, 1, 5, &attempts);
+    let [low, high] = &*attempts;
+
+    assert_eq!(
+        high.load(Ordering::SeqCst),
+        1,
+        "the higher priority never restarts"
+    );
+    assert!(
+        low.load(Ordering::SeqCst) >= 2,
+        "the wounded holder restarts"
+    );
+    // the restarted holder may take the locks again before the waiter
+    // wakes up, so either commits last
+    assert_eq!(hold.committed.lock().unwrap().len(), 2);
+    let (a, b) = values(&stm);
+    assert_eq!(a, b);
+    assert!(stm.locked_stripes().is_empty());
+}
+
+#[test]
+fn equal_priority_restarts_instead() {
+    let attempts = Arc::new([AtomicU32::new(0), AtomicU32::new(0)]);
+    let seen = attempts.clone();
+    // "low" carries on only after "high" ran its body again, which it
+    // does only by restarting on the lock instead of wounding
+    let hold = Arc::new(Hold {
+        held: "low",
+        go: move || seen[1].load(Ordering::SeqCst) >= 2,
+        done: AtomicBool::new(false),
+        committed: Mutex::new(Vec::new()),
+    });
+    let stm = Arc::new(STMBuilder::new().scheduler(hold.clone()).build());
+
+    race(&stm, 3, 3, &attempts);
+    let [low, high] = &*attempts;
+
+    assert_eq!(
+        low.load(Ordering::SeqCst),
+        1,
+        "an equal priority does not wound the holder"
+    );
+    assert!(
+        high.load(Ordering::SeqCst) >= 2,
+        "the contender restarts on the lock"
+    );
+    assert_eq!(*hold.committed.lock().unwrap(), ["low", "high"]);
+    assert_eq!(values(&stm), (2, 2));
+    assert!(stm.locked_stripes().is_empty());
+}
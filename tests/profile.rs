@@ -0,0 +1,125 @@
+#![cfg(feature = "testing")]
+
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use tl2::{
+    AttemptOutcome, AttemptProfile, Conflict, ConflictCause, STMResult, Scheduler, StripeValue,
+    TxError, TxProfile, YieldPoint, STM,
+};
+
+// Sleeps at one yield point, which the profile charges to the phase that
+// point falls in.
+struct Delay(YieldPoint, Duration);
+
+impl Scheduler for Delay {
+    fn reached(&self, point: YieldPoint) {
+        if point == self.0 {
+            thread::sleep(self.1);
+        }
+    }
+}
+
+fn copy(stm: &STM) -> (Option<()>, TxProfile) {
+    stm.profile_write_transaction(|tr| {
+        let v = u64::from_stripe(tl2::load!(tr, 0));
+        tr.store(8, v.to_stripe());
+        STMResult::Ok(())
+    })
+}
+
+fn phases(a: &AttemptProfile) -> [Duration; 5] {
+    [a.execute, a.lock, a.validate, a.journal, a.publish]
+}
+
+#[test]
+fn a_delay_shows_up_in_its_phase_and_grows_with_it() {
+    // the point, and the phase between the marks around it
+    let cases = [
+        (YieldPoint::Sampled, 0),
+        (YieldPoint::Executed, 1),
+        (YieldPoint::Locked, 2),
+        (YieldPoint::Publishing, 4),
+    ];
+    for (point, phase) in cases {
+        let mut last = Duration::ZERO;
+        for ms in [0, 10, 20, 40] {
+            let delay = Duration::from_millis(ms);
+            let stm = STM::builder()
+                .scheduler(Arc::new(Delay(point, delay)))
+                .build();
+            let (got, profile) = copy(&stm);
+            assert_eq!(got, Some(()));
+            assert_eq!(profile.attempts.len(), 1);
+
+            let a = &profile.attempts[0];
+            let took = phases(a)[phase];
+            assert!(took >= delay, "{:?}: {:?} < {:?}", point, took, delay);
+            assert!(took >= last, "{:?}: {:?} after {:?}", point, took, last);
+            last = took;
+            assert!(a.total >= phases(a).iter().sum());
+            assert!(profile.total >= a.total);
+        }
+    }
+}
+
+#[test]
+fn a_restart_is_profiled_as_its_own_attempt() {
+    let stm = STM::new();
+    stm.inject_conflict_before_commit(|stm| {
+        stm.write_transaction(|tr| {
+            tr.store(0, 5u64.to_stripe());
+            STMResult::Ok(())
+        })
+        .unwrap()
+    });
+    let (got, profile) = copy(&stm);
+    assert_eq!(got, Some(()));
+    assert_eq!(profile.attempts.len(), 2);
+
+    let (first, second) = (&profile.attempts[0], &profile.attempts[1]);
+    assert_eq!(
+        first.outcome,
+        AttemptOutcome::Restarted(Conflict {
+            cause: ConflictCause::Validation,
+            addr: Some(0),
+        })
+    );
+    assert_eq!((first.read_set, first.write_set), (1, 1));
+    // the failed attempt never published
+    assert_eq!(first.publish, Duration::ZERO);
+    assert_eq!(second.outcome, AttemptOutcome::Committed);
+    assert!(profile.total >= first.total + second.total);
+
+    let text = profile.to_string();
+    assert!(text.starts_with("2 attempts in "), "{}", text);
+    assert!(
+        text.contains("\n  #1: restarted (Validation at 0) in "),
+        "{}",
+        text
+    );
+    assert!(text.contains("\n  #2: committed in "), "{}", text);
+    assert!(text.contains("read-set 1, write-set 1"), "{}", text);
+}
+
+#[test]
+fn an_abort_ends_the_profile() {
+    let stm = STM::new();
+    let (got, profile) = stm.profile_write_transaction(|tr| {
+        tr.store(0, 1u64.to_stripe());
+        STMResult::<()>::Abort
+    });
+    assert_eq!(got, None);
+    assert_eq!(profile.attempts.len(), 1);
+    let a = &profile.attempts[0];
+    assert_eq!(a.outcome, AttemptOutcome::Failed(TxError::Abort));
+    assert_eq!(
+        (a.lock, a.validate, a.publish),
+        (Duration::ZERO, Duration::ZERO, Duration::ZERO)
+    );
+    assert!(profile.to_string().starts_with("1 attempt in "));
+    assert!(profile
+        .to_string()
+        .contains("#1: failed (transaction aborted)"));
+}
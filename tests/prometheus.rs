@@ -0,0 +1,105 @@
+#![cfg(feature = "prometheus")]
+
+use std::collections::HashMap;
+use std::thread;
+
+use tl2::{STMResult, StripeValue, STM};
+
+// A fixed run: two labeled commits, an unlabeled one, a read and an abort.
+fn scripted() -> STM {
+    let stm = STM::builder().capacity(64).stats(true).build();
+    for _ in 0..2 {
+        stm.write_transaction_labeled("move \"funds\"\n", |tr| {
+            tr.store(0, 1u64.to_stripe());
+            STMResult::Ok(())
+        })
+        .unwrap();
+    }
+    stm.write_transaction(|tr| {
+        tr.store(8, 1u64.to_stripe());
+        STMResult::Ok(())
+    })
+    .unwrap();
+    stm.read_transaction_labeled("audit", |tr| STMResult::Ok(tr.load(0)))
+        .unwrap();
+    let _ = stm.write_transaction_labeled("audit", |_| STMResult::<()>::Abort);
+    stm
+}
+
+#[test]
+fn the_exposition_matches_the_golden_file() {
+    let out = scripted().metrics_prometheus("my-app.tl2");
+    let golden = include_str!("golden/prometheus.txt");
+    assert_eq!(out, golden, "\n{}", out);
+}
+
+#[test]
+fn without_stats_only_the_gauges_are_there() {
+    let stm = STM::builder().capacity(64).build();
+    let out = stm.metrics_prometheus("9lives");
+    assert!(out.starts_with("# HELP _9lives_stripes "), "{}", out);
+    assert!(!out.contains("_total"), "{}", out);
+    assert!(out.contains("\n_9lives_stripes 8\n"), "{}", out);
+}
+
+// Sample name with labels -> value, checking every sample has a TYPE.
+fn parse(out: &str) -> HashMap<String, (String, u64)> {
+    let mut kinds = HashMap::new();
+    let mut samples = HashMap::new();
+    for line in out.lines() {
+        if let Some(t) = line.strip_prefix("# TYPE ") {
+            let (name, kind) = t.split_once(' ').unwrap();
+            kinds.insert(name.to_string(), kind.to_string());
+        } else if !line.starts_with('#') {
+            let (key, value) = line.rsplit_once(' ').unwrap();
+            let family = key.split('{').next().unwrap();
+            let kind = kinds.get(family).expect("sample without TYPE").clone();
+            samples.insert(key.to_string(), (kind, value.parse().unwrap()));
+        }
+    }
+    samples
+}
+
+#[test]
+fn counters_never_go_down_between_scrapes() {
+    let stm = STM::builder().stats(true).build();
+    let mut last = parse(&stm.metrics_prometheus("tl2"));
+    thread::scope(|s| {
+        for t in 0..4 {
+            let stm = &stm;
+            s.spawn(move || {
+                for _ in 0..500 {
+                    stm.write_transaction_labeled("bump", |tr| {
+                        let n = tr.load(8 * (t % 2)).map_or(0, u64::from_stripe);
+                        tr.store(8 * (t % 2), (n + 1).to_stripe());
+                        STMResult::Ok(())
+                    })
+                    .unwrap();
+                }
+            });
+        }
+        for _ in 0..50 {
+            let now = parse(&stm.metrics_prometheus("tl2"));
+            for (key, (kind, value)) in now.iter() {
+                if kind == "counter" {
+                    let before = last.get(key).map_or(0, |(_, v)| *v);
+                    assert!(
+                        *value >= before,
+                        "{} went from {} to {}",
+                        key,
+                        before,
+                        value
+                    );
+                }
+            }
+            last = now;
+            thread::yield_now();
+        }
+    });
+    let end = parse(&stm.metrics_prometheus("tl2"));
+    assert_eq!(end["tl2_commits_total"].1, 2000);
+    assert_eq!(
+        end["tl2_label_outcomes_total{label=\"bump\",outcome=\"commit\"}"].1,
+        2000
+    );
+}
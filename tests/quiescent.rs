@@ -0,0 +1,54 @@
+use std::sync::Barrier;
+use std::thread;
+
+use tl2::{STMResult, StripeValue, STM};
+
+#[test]
+fn busy_inside_transactions_and_quiescent_after() {
+    let stm = STM::new();
+    assert!(stm.is_quiescent());
+
+    let inside = stm
+        .write_transaction(|tr| {
+            tr.store(0, 1u64.to_stripe());
+            STMResult::Ok(stm.is_quiescent())
+        })
+        .unwrap();
+    assert!(!inside);
+    assert!(stm.is_quiescent());
+
+    let inside = stm
+        .read_transaction(|tr| {
+            let _ = tr.load(0);
+            STMResult::Ok(stm.is_quiescent())
+        })
+        .unwrap();
+    assert!(!inside);
+    assert!(stm.is_quiescent());
+
+    // a failed transaction leaves nothing behind either
+    let _ = stm.try_write_transaction(|_| STMResult::<()>::Abort);
+    assert!(stm.is_quiescent());
+}
+
+#[test]
+fn another_threads_transaction_is_seen() {
+    let stm = STM::new();
+    let (entered, leave) = (Barrier::new(2), Barrier::new(2));
+    thread::scope(|s| {
+        s.spawn(|| {
+            stm.write_transaction(|tr| {
+                tr.store(0, 1u64.to_stripe());
+                entered.wait();
+                leave.wait();
+                STMResult::Ok(())
+            })
+            .unwrap()
+        });
+        entered.wait();
+        let quiescent = stm.is_quiescent();
+        leave.wait();
+        assert!(!quiescent);
+    });
+    assert!(stm.is_quiescent());
+}
@@ -0,0 +1,78 @@
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use tl2::{load, STMResult, STM};
+
+const STRIPES: usize = 64;
+const FALLBACK_AFTER: usize = 3;
+
+// Stops the writer when dropped, also when the reader panics.
+struct Stop<'a>(&'a AtomicBool);
+
+impl Drop for Stop<'_> {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+// A writer setting every stripe to the same new value, as fast as it can,
+// against a reader of all of them that dawdles mid-read, so that nearly
+// every optimistic pass finds a stripe newer than its snapshot.
+#[test]
+fn a_large_reader_completes_in_bounded_attempts_under_a_writer() {
+    let stm = STM::builder()
+        .capacity(8 * STRIPES)
+        .read_fallback_after(FALLBACK_AFTER)
+        .build();
+    let stop = AtomicBool::new(false);
+
+    let most = thread::scope(|s| {
+        s.spawn(|| {
+            let mut n = 0u64;
+            while !stop.load(Ordering::Relaxed) {
+                n += 1;
+                stm.write_transaction(|tr| {
+                    for i in 0..STRIPES {
+                        tr.store(8 * i, n.to_le_bytes());
+                    }
+                    STMResult::Ok(())
+                })
+                .unwrap();
+            }
+        });
+
+        let _stop = Stop(&stop);
+        let mut most = 0;
+        for _ in 0..50 {
+            let attempts = Cell::new(0);
+            let vals = stm
+                .read_transaction(|tr| {
+                    attempts.set(attempts.get() + 1);
+                    let mut vals = Vec::with_capacity(STRIPES);
+                    for i in 0..STRIPES {
+                        vals.push(u64::from_le_bytes(load!(tr, 8 * i)));
+                        if i == 0 {
+                            thread::sleep(Duration::from_micros(200));
+                        }
+                    }
+                    STMResult::Ok(vals)
+                })
+                .unwrap();
+            assert!(
+                vals.iter().all(|v| *v == vals[0]),
+                "torn snapshot {:?}",
+                vals
+            );
+            assert!(
+                attempts.get() <= FALLBACK_AFTER + 1,
+                "{} attempts",
+                attempts.get()
+            );
+            most = most.max(attempts.get());
+        }
+        most
+    });
+    assert_eq!(most, FALLBACK_AFTER + 1, "the reader never fell back");
+}
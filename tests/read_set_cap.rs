@@ -0,0 +1,55 @@
+use std::cell::Cell;
+
+use tl2::{STMResult, StripeValue, TxError, STM};
+
+const CAP: usize = 100;
+
+fn stm() -> STM {
+    STM::builder()
+        .capacity(8 * 100_000)
+        .max_read_set(CAP)
+        .build()
+}
+
+#[test]
+fn a_runaway_body_fails_at_the_cap() {
+    let stm = stm();
+    let attempts = Cell::new(0);
+    let loaded = Cell::new(0);
+    let r = stm.try_write_transaction(|tr| {
+        attempts.set(attempts.get() + 1);
+        tr.store(0, 1u64.to_stripe());
+        // a buggy loop that would walk the whole memory
+        for addr in (0..).step_by(8) {
+            match tr.load(addr) {
+                Some(_) => loaded.set(loaded.get() + 1),
+                None => return STMResult::Retry,
+            }
+        }
+        STMResult::Ok(())
+    });
+    assert_eq!(r, Err(TxError::ReadSetTooLarge));
+    assert_eq!(attempts.get(), 1, "the cap fails, it does not restart");
+    assert_eq!(loaded.get(), CAP);
+
+    // nothing of the failed transaction was committed
+    let v = stm.read_transaction(|tr| STMResult::Ok(tr.load(0).map(u64::from_stripe)));
+    assert_eq!(v, Some(Some(0)));
+}
+
+#[test]
+fn loads_up_to_the_cap_and_repeated_loads_are_fine() {
+    let stm = stm();
+    let r = stm.try_write_transaction(|tr| {
+        for _ in 0..3 {
+            for i in 0..CAP {
+                if tr.load(8 * i).is_none() {
+                    return STMResult::Retry;
+                }
+            }
+        }
+        tr.store(0, 1u64.to_stripe());
+        STMResult::Ok(())
+    });
+    assert_eq!(r, Ok(()));
+}
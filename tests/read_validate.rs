@@ -0,0 +1,91 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use tl2::{ConflictCause, EventKind, STMResult, StripeValue, STM};
+
+const STRIPE_SIZE: usize = 8;
+
+fn write_other(stm: &STM, addr: usize, v: u64) {
+    thread::scope(|s| {
+        s.spawn(|| {
+            stm.write_transaction(|tr| {
+                tr.store(addr, v.to_stripe());
+                STMResult::Ok(())
+            })
+        });
+    });
+}
+
+#[test]
+fn a_write_between_loads_fails_validation_and_restarts() {
+    let stm = STM::new();
+    write_other(&stm, 0, 1);
+    write_other(&stm, STRIPE_SIZE, 1);
+
+    let runs = AtomicU32::new(0);
+    let checks = Mutex::new(Vec::new());
+    let (a, b) = stm
+        .read_transaction_labeled("r", |tr| {
+            let run = runs.fetch_add(1, Ordering::SeqCst);
+            let a = u64::from_stripe(tl2::load!(tr, 0));
+            let b = u64::from_stripe(tl2::load!(tr, STRIPE_SIZE));
+            if run == 0 {
+                // invalidates the first load after it was made
+                write_other(&stm, 0, 2);
+            }
+            checks.lock().unwrap().push(tr.validate());
+            // returned as if fine; a failed validate restarts anyway
+            STMResult::Ok((a, b))
+        })
+        .unwrap();
+
+    assert_eq!(runs.load(Ordering::SeqCst), 2);
+    assert_eq!(*checks.lock().unwrap(), [false, true]);
+    assert_eq!((a, b), (2, 1));
+    let events = stm.recent_events();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].label, Some("r"));
+    assert_eq!(
+        events[0].kind,
+        EventKind::Restart(ConflictCause::Validation)
+    );
+    assert_eq!(events[0].addr, Some(0));
+}
+
+#[test]
+fn only_the_stripes_loaded_are_validated() {
+    let stm = STM::new();
+    let runs = AtomicU32::new(0);
+    stm.read_transaction(|tr| {
+        runs.fetch_add(1, Ordering::SeqCst);
+        // nothing loaded yet
+        assert!(tr.validate());
+        tl2::load!(tr, 0);
+        write_other(&stm, STRIPE_SIZE, 1);
+        // a stripe written since but never loaded does not matter
+        assert!(tr.validate());
+        STMResult::Ok(())
+    })
+    .unwrap();
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn loads_after_a_failed_validation_fail_too() {
+    let stm = STM::new();
+    let runs = AtomicU32::new(0);
+    let after = Mutex::new(Vec::new());
+    stm.read_transaction(|tr| {
+        let run = runs.fetch_add(1, Ordering::SeqCst);
+        tr.load(0);
+        if run == 0 {
+            write_other(&stm, 0, 1);
+            assert!(!tr.validate());
+        }
+        after.lock().unwrap().push(tr.load(STRIPE_SIZE).is_some());
+        STMResult::Ok(())
+    })
+    .unwrap();
+    assert_eq!(*after.lock().unwrap(), [false, true]);
+}
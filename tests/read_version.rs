@@ -0,0 +1,62 @@
+use std::cell::RefCell;
+use std::thread;
+
+use tl2::{STMResult, StripeValue, STM};
+
+fn commit(stm: &STM, addr: usize) {
+    stm.write_transaction(|tr| {
+        tr.store(addr, 1u64.to_stripe());
+        STMResult::Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn read_version_is_the_clock_at_the_start() {
+    let stm = STM::new();
+    for _ in 0..3 {
+        commit(&stm, 0);
+    }
+
+    let now = stm.current_version();
+    let ver = stm
+        .write_transaction(|tr| {
+            tr.store(8, 2u64.to_stripe());
+            STMResult::Ok(tr.read_version())
+        })
+        .unwrap();
+    assert_eq!(ver, now);
+
+    let now = stm.current_version();
+    let ver = stm
+        .read_transaction(|tr| STMResult::Ok(tr.read_version()))
+        .unwrap();
+    assert_eq!(ver, now);
+}
+
+// A restart samples the clock anew, past the commit that caused it.
+#[test]
+fn a_restart_reads_at_a_newer_version() {
+    let stm = STM::new();
+    let seen = RefCell::new(Vec::new());
+    stm.write_transaction(|tr| {
+        seen.borrow_mut().push(tr.read_version());
+        let v = match tr.load(0) {
+            Some(v) => v,
+            None => return STMResult::Retry,
+        };
+        if seen.borrow().len() == 1 {
+            thread::scope(|s| {
+                s.spawn(|| commit(&stm, 0));
+            });
+        }
+        tr.store(8, v);
+        STMResult::Ok(())
+    })
+    .unwrap();
+
+    let seen = seen.into_inner();
+    assert_eq!(seen.len(), 2);
+    assert!(seen[1] > seen[0], "{:?}", seen);
+    assert!(stm.current_version() > seen[1]);
+}
@@ -0,0 +1,78 @@
+use tl2::{BigValue, STMResult, STM};
+
+tl2::trans_record! {
+    #[derive(Debug, Clone, PartialEq)]
+    struct Everything {
+        a: u8,
+        b: u16,
+        c: u32,
+        d: u64,
+        e: i8,
+        f: i16,
+        g: i32,
+        h: i64,
+        flag: bool,
+    }
+}
+
+#[test]
+fn a_record_round_trips_through_memory() {
+    assert_eq!(Everything::STRIPES, 9);
+    let stm = STM::new();
+    let values = [
+        Everything {
+            a: 0,
+            b: 0,
+            c: 0,
+            d: 0,
+            e: 0,
+            f: 0,
+            g: 0,
+            h: 0,
+            flag: false,
+        },
+        Everything {
+            a: u8::MAX,
+            b: u16::MAX,
+            c: u32::MAX,
+            d: u64::MAX,
+            e: i8::MIN,
+            f: i16::MIN,
+            g: i32::MIN,
+            h: i64::MIN,
+            flag: true,
+        },
+        Everything {
+            a: 1,
+            b: 2,
+            c: 3,
+            d: 4,
+            e: -5,
+            f: -6,
+            g: -7,
+            h: -8,
+            flag: true,
+        },
+    ];
+
+    for (i, v) in values.iter().enumerate() {
+        let base = i * Everything::STRIPES * 8;
+        stm.write_transaction(|tr| {
+            tr.store_record(base, v);
+            STMResult::Ok(())
+        })
+        .unwrap();
+    }
+    for (i, v) in values.iter().enumerate() {
+        let base = i * Everything::STRIPES * 8;
+        let back = stm.read_transaction(|tr| STMResult::Ok(tr.load_record::<Everything>(base)));
+        assert_eq!(back, Some(Some(v.clone())));
+    }
+
+    // a write transaction reads its own stores back
+    let back = stm.write_transaction(|tr| {
+        tr.store_record(0, &values[2]);
+        STMResult::Ok(tr.load_record::<Everything>(0))
+    });
+    assert_eq!(back, Some(Some(values[2].clone())));
+}
@@ -0,0 +1,87 @@
+use std::thread;
+
+use tl2::{STMResult, StripeValue, STM};
+
+const COUNT: usize = 0;
+const PAYLOAD: usize = 8;
+const FREED: u64 = 0xdead;
+const THREADS: u64 = 8;
+const ROUNDS: u64 = 500;
+
+fn get(stm: &STM, addr: usize) -> u64 {
+    stm.read_transaction(|tr| STMResult::Ok(u64::from_stripe(tl2::load!(tr, addr))))
+        .unwrap()
+}
+
+// Drop a reference; the one dropping the last frees the payload in the
+// same transaction and gets true.
+fn release(stm: &STM) -> bool {
+    stm.write_transaction(|tr| {
+        let n = match tr.decref(COUNT) {
+            Some(n) => n,
+            None => return STMResult::Retry,
+        };
+        if n == 0 {
+            tr.store(PAYLOAD, FREED.to_stripe());
+        }
+        STMResult::Ok(n == 0)
+    })
+    .unwrap()
+}
+
+#[test]
+fn counts_are_never_lost_and_the_last_release_frees_once() {
+    let stm = STM::new();
+    // one reference per thread
+    stm.write_transaction(|tr| {
+        tr.store(COUNT, THREADS.to_stripe());
+        tr.store(PAYLOAD, 42u64.to_stripe());
+        STMResult::Ok(())
+    })
+    .unwrap();
+
+    let freed: Vec<bool> = thread::scope(|s| {
+        let handles: Vec<_> = (0..THREADS)
+            .map(|_| {
+                s.spawn(|| {
+                    for n in 0..ROUNDS {
+                        let new = stm
+                            .write_transaction(|tr| match tr.incref(COUNT) {
+                                Some(c) => STMResult::Ok(c),
+                                None => STMResult::Retry,
+                            })
+                            .unwrap();
+                        // our own reference keeps it above one
+                        assert!(new >= 2, "count {} after incref", new);
+                        if n % 2 == 0 {
+                            assert!(!release(&stm));
+                        } else {
+                            // a clone dropped in the same transaction
+                            stm.write_transaction(|tr| {
+                                match (tr.incref(COUNT), tr.decref(COUNT), tr.decref(COUNT)) {
+                                    (Some(_), Some(_), Some(c)) => STMResult::Ok(c),
+                                    _ => STMResult::Retry,
+                                }
+                            })
+                            .unwrap();
+                        }
+                        assert_ne!(get(&stm, PAYLOAD), FREED);
+                    }
+                    release(&stm)
+                })
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    assert_eq!(freed.iter().filter(|f| **f).count(), 1);
+    assert_eq!(get(&stm, COUNT), 0);
+    assert_eq!(get(&stm, PAYLOAD), FREED);
+}
+
+#[test]
+#[should_panic(expected = "decref of a zero refcount")]
+fn decref_of_zero_panics() {
+    let stm = STM::new();
+    release(&stm);
+}
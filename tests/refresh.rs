@@ -0,0 +1,96 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::thread;
+
+use tl2::{STMResult, StripeValue, STM};
+
+fn write(stm: &STM, addr: usize, v: u64) {
+    thread::scope(|s| {
+        s.spawn(|| {
+            stm.write_transaction(|tr| {
+                tr.store(addr, v.to_stripe());
+                STMResult::Ok(())
+            })
+            .unwrap()
+        });
+    });
+}
+
+#[test]
+fn a_stale_stripe_is_refreshed_and_the_read_goes_on() {
+    let stm = STM::builder().stats(true).build();
+    write(&stm, 0, 1);
+    write(&stm, 8, 10);
+
+    let runs = AtomicU32::new(0);
+    let got = stm
+        .read_transaction(|tr| {
+            runs.fetch_add(1, Ordering::SeqCst);
+            let a = u64::from_stripe(tl2::load!(tr, 0));
+            write(&stm, 8, 20);
+            // 8 is newer than the read version now
+            assert_eq!(tr.load(8), None);
+            let b = u64::from_stripe(tr.refresh(8).unwrap());
+            // later loads of it see the refreshed value
+            assert_eq!(tr.load(8), Some(20u64.to_stripe()));
+            assert!(tr.validate());
+            STMResult::Ok((a, b))
+        })
+        .unwrap();
+    assert_eq!(got, (1, 20));
+    assert_eq!(runs.into_inner(), 1);
+    assert_eq!(stm.stats().unwrap().restarts(), 0);
+}
+
+#[test]
+fn a_stripe_loaded_before_the_write_can_be_refreshed() {
+    let stm = STM::new();
+    let runs = AtomicU32::new(0);
+    let got = stm
+        .read_transaction(|tr| {
+            runs.fetch_add(1, Ordering::SeqCst);
+            let before = u64::from_stripe(tl2::load!(tr, 0));
+            write(&stm, 0, 7);
+            let after = u64::from_stripe(tr.refresh(0).unwrap());
+            let again = u64::from_stripe(tl2::load!(tr, 0));
+            STMResult::Ok((before, after, again))
+        })
+        .unwrap();
+    assert_eq!(got, (0, 7, 7));
+    assert_eq!(runs.into_inner(), 1);
+}
+
+#[test]
+fn a_write_after_the_refresh_is_still_caught() {
+    let stm = STM::new();
+    let runs = AtomicU32::new(0);
+    stm.read_transaction(|tr| {
+        let run = runs.fetch_add(1, Ordering::SeqCst);
+        if run == 0 {
+            write(&stm, 0, 1);
+        }
+        tr.refresh(0).unwrap();
+        if run == 0 {
+            write(&stm, 0, 2);
+            assert!(!tr.validate());
+        }
+        STMResult::Ok(())
+    })
+    .unwrap();
+    assert_eq!(runs.into_inner(), 2);
+}
+
+#[test]
+fn a_refresh_does_not_clear_a_conflict_on_another_stripe() {
+    let stm = STM::new();
+    let runs = AtomicU32::new(0);
+    stm.read_transaction(|tr| {
+        if runs.fetch_add(1, Ordering::SeqCst) == 0 {
+            write(&stm, 0, 1);
+            assert_eq!(tr.load(0), None);
+            assert_eq!(tr.refresh(8), None);
+        }
+        STMResult::Ok(())
+    })
+    .unwrap();
+    assert_eq!(runs.into_inner(), 2);
+}
@@ -0,0 +1,149 @@
+use std::convert::TryInto;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+use tl2::{STMResult, StripeValue, STM};
+
+// A region of WORDS u64s whose last one is a checksum of the others, kept
+// up to date by every writer, then a counter of exports.
+const WORDS: usize = 64;
+const REGION: usize = WORDS * 8;
+const EXPORTS: usize = REGION;
+const CAPACITY: usize = 2 * REGION;
+
+fn checksum(words: &[u64]) -> u64 {
+    words.iter().fold(0u64, |h, w| {
+        h.rotate_left(7) ^ w.wrapping_mul(0x9e37_79b9_7f4a_7c15)
+    })
+}
+
+fn words(blob: &[u8]) -> Vec<u64> {
+    blob.chunks_exact(8)
+        .map(|c| u64::from_le_bytes(c.try_into().unwrap()))
+        .collect()
+}
+
+fn consistent(blob: &[u8]) -> bool {
+    let w = words(blob);
+    checksum(&w[..WORDS - 1]) == w[WORDS - 1]
+}
+
+// Rewrite a few words at random and the checksum with them.
+fn scribble(stm: &STM, x: &mut u64) {
+    *x ^= *x << 13;
+    *x ^= *x >> 7;
+    *x ^= *x << 17;
+    let seed = *x;
+    stm.write_transaction(|tr| {
+        let mut w = Vec::with_capacity(WORDS);
+        for i in 0..WORDS - 1 {
+            w.push(u64::from_stripe(tl2::load!(tr, i * 8)));
+        }
+        for k in 0..3 {
+            let i = (seed >> (k * 16)) as usize % (WORDS - 1);
+            w[i] = seed.rotate_left(k as u32 * 8);
+            tr.store(i * 8, w[i].to_stripe());
+        }
+        tr.store((WORDS - 1) * 8, checksum(&w).to_stripe());
+        STMResult::Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn exports_under_concurrent_writes_are_consistent() {
+    let stm = STM::builder().capacity(CAPACITY).build();
+    let done = AtomicBool::new(false);
+
+    thread::scope(|s| {
+        let writers: Vec<_> = (1..=4u64)
+            .map(|t| {
+                let stm = &stm;
+                s.spawn(move || {
+                    let mut x = t;
+                    for _ in 0..2_000 {
+                        scribble(stm, &mut x);
+                    }
+                })
+            })
+            .collect();
+        let exporter = s.spawn(|| {
+            let mut exports = 0u64;
+            while !done.load(Ordering::SeqCst) || exports == 0 {
+                // the blob and the counter move together
+                let (blob, n) = stm
+                    .write_transaction(|tr| {
+                        let Some(blob) = tr.export_region(0, REGION) else {
+                            return STMResult::Retry;
+                        };
+                        let n = u64::from_stripe(tl2::load!(tr, EXPORTS)) + 1;
+                        tr.store(EXPORTS, n.to_stripe());
+                        STMResult::Ok((blob, n))
+                    })
+                    .unwrap();
+                assert!(consistent(&blob), "export {} is torn", n);
+                exports += 1;
+                assert_eq!(n, exports);
+            }
+            exports
+        });
+        for w in writers {
+            w.join().unwrap();
+        }
+        done.store(true, Ordering::SeqCst);
+        assert!(exporter.join().unwrap() > 0);
+    });
+}
+
+#[test]
+fn an_imported_blob_replaces_the_region_in_one_commit() {
+    let stm = STM::builder().capacity(CAPACITY).build();
+    let mut x = 1;
+    scribble(&stm, &mut x);
+    let blob = stm
+        .write_transaction(|tr| {
+            tr.export_region(0, REGION)
+                .map_or(STMResult::Retry, STMResult::Ok)
+        })
+        .unwrap();
+
+    // copy it to the second half, then back over a scribbled first half
+    let before = stm.current_version();
+    stm.write_transaction(|tr| {
+        tr.import_region(REGION, &blob);
+        STMResult::Ok(())
+    })
+    .unwrap();
+    assert_eq!(stm.current_version(), before + 1);
+    assert_eq!(stm.snapshot_range(REGION, REGION).unwrap(), blob);
+
+    scribble(&stm, &mut x);
+    assert_ne!(stm.snapshot_range(0, REGION).unwrap(), blob);
+    stm.write_transaction(|tr| {
+        tr.import_region(0, &blob);
+        STMResult::Ok(())
+    })
+    .unwrap();
+    assert_eq!(stm.snapshot_range(0, REGION).unwrap(), blob);
+    assert!(consistent(&blob));
+}
+
+#[test]
+#[should_panic(expected = "region length")]
+fn a_blob_of_part_of_a_stripe_is_refused() {
+    let stm = STM::builder().capacity(CAPACITY).build();
+    stm.write_transaction(|tr| {
+        tr.import_region(0, &[0; 12]);
+        STMResult::Ok(())
+    });
+}
+
+#[test]
+#[should_panic(expected = "region out of bounds")]
+fn a_region_past_the_end_is_refused() {
+    let stm = STM::builder().capacity(CAPACITY).build();
+    stm.write_transaction(|tr| {
+        tr.export_region(REGION, CAPACITY);
+        STMResult::Ok(())
+    });
+}
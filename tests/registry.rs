@@ -0,0 +1,114 @@
+use std::sync::{Arc, Barrier};
+use std::thread;
+
+use tl2::{STMResult, StripeValue, STM};
+
+const THREADS: usize = 64;
+const BATCH: usize = 16;
+const COMMITS: u64 = 10;
+const READS: u64 = 5;
+
+// A short-lived thread's work: commits, reads and one abort.
+fn work(stm: &STM, t: usize) {
+    let addr = t % 8 * 8;
+    for _ in 0..COMMITS {
+        stm.write_transaction(|tr| {
+            let v = u64::from_stripe(tl2::load!(tr, addr));
+            tr.store(addr, (v + 1).to_stripe());
+            STMResult::Ok(())
+        })
+        .unwrap();
+    }
+    for _ in 0..READS {
+        stm.read_transaction(|tr| STMResult::Ok(tr.load(addr)))
+            .unwrap();
+    }
+    stm.write_transaction(|_| STMResult::<()>::Abort);
+}
+
+// Plain threads rather than scoped ones: `join` returns only once a
+// thread's TLS destructors, which retire its stats, have run.
+#[test]
+fn short_lived_threads_lose_no_counts_and_leave_no_entries() {
+    let stm = Arc::new(STM::builder().stats(true).build());
+
+    for batch in 0..THREADS / BATCH {
+        let handles: Vec<_> = (0..BATCH)
+            .map(|i| {
+                let stm = stm.clone();
+                thread::spawn(move || work(&stm, batch * BATCH + i))
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(stm.live_threads(), 0, "batch {}", batch);
+    }
+
+    let s = stm.stats().unwrap();
+    let threads = THREADS as u64;
+    assert_eq!(s.commits, threads * COMMITS);
+    assert_eq!(s.reads, threads * READS);
+    assert_eq!(s.aborts, threads);
+    let total = stm
+        .read_transaction(|tr| {
+            let mut sum = 0;
+            for i in 0..8 {
+                sum += u64::from_stripe(tl2::load!(tr, i * 8));
+            }
+            STMResult::Ok(sum)
+        })
+        .unwrap();
+    assert_eq!(total, threads * COMMITS);
+}
+
+#[test]
+fn live_threads_are_counted_until_they_exit() {
+    let stm = Arc::new(STM::builder().stats(true).build());
+    let ran = Arc::new(Barrier::new(BATCH + 1));
+    let exit = Arc::new(Barrier::new(BATCH + 1));
+
+    let handles: Vec<_> = (0..BATCH)
+        .map(|i| {
+            let (stm, ran, exit) = (stm.clone(), ran.clone(), exit.clone());
+            thread::spawn(move || {
+                work(&stm, i);
+                ran.wait();
+                exit.wait();
+                // counts taken after the barrier still land somewhere
+                work(&stm, i);
+            })
+        })
+        .collect();
+
+    ran.wait();
+    assert_eq!(stm.live_threads(), BATCH);
+    // the snapshot adds up the live threads
+    assert_eq!(stm.stats().unwrap().commits, BATCH as u64 * COMMITS);
+    assert!(stm.debug_report().contains(&format!("threads: {}", BATCH)));
+    exit.wait();
+    for h in handles {
+        h.join().unwrap();
+    }
+
+    assert_eq!(stm.live_threads(), 0);
+    assert_eq!(stm.stats().unwrap().commits, 2 * BATCH as u64 * COMMITS);
+}
+
+#[test]
+fn a_thread_running_on_two_stms_is_retired_from_both() {
+    let a = Arc::new(STM::builder().stats(true).build());
+    let b = Arc::new(STM::builder().stats(true).build());
+    let (ta, tb) = (a.clone(), b.clone());
+    thread::spawn(move || {
+        work(&ta, 0);
+        work(&tb, 1);
+        work(&ta, 2);
+    })
+    .join()
+    .unwrap();
+
+    assert_eq!((a.live_threads(), b.live_threads()), (0, 0));
+    assert_eq!(a.stats().unwrap().commits, 2 * COMMITS);
+    assert_eq!(b.stats().unwrap().commits, COMMITS);
+}
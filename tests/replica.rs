@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::sync::mpsc::Receiver;
+
+use tl2::{CommitRecord, ReplicaError, ReplicaSnapshot, STMResult, STM};
+
+const SIZE: usize = 512;
+
+fn contents(stm: &STM) -> Vec<u8> {
+    stm.snapshot_range(0, SIZE).unwrap()
+}
+
+fn stripe(snapshot: &ReplicaSnapshot, addr: usize) -> [u8; 8] {
+    snapshot.data[addr..addr + 8].try_into().unwrap()
+}
+
+// xorshift64*
+fn rng(x: &mut u64) -> u64 {
+    *x ^= *x >> 12;
+    *x ^= *x << 25;
+    *x ^= *x >> 27;
+    x.wrapping_mul(0x2545_f491_4f6c_dd1d)
+}
+
+// Run `n` random commits on the primary and return its memory after each
+// of them by version.
+fn workload(stm: &STM, seed: u64, n: usize) -> HashMap<u64, Vec<u8>> {
+    let mut x = seed | 1;
+    let mut states = HashMap::new();
+    states.insert(stm.current_version(), contents(stm));
+    for _ in 0..n {
+        let writes: Vec<(usize, u64)> = (0..1 + rng(&mut x) % 4)
+            .map(|_| ((rng(&mut x) as usize % (SIZE / 8)) * 8, rng(&mut x)))
+            .collect();
+        stm.write_transaction(|tr| {
+            for (addr, val) in writes.iter() {
+                tr.store(*addr, val.to_le_bytes());
+            }
+            STMResult::Ok(())
+        })
+        .unwrap();
+        states.insert(stm.current_version(), contents(stm));
+    }
+    states
+}
+
+fn follower(primary: &STM) -> (STM, Receiver<CommitRecord>) {
+    let (snapshot, rx) = primary.replicate();
+    let follower = STM::builder().capacity(SIZE).build();
+    follower.resync_replica(&snapshot);
+    (follower, rx)
+}
+
+#[test]
+fn records_applied_in_order_match_the_primary_at_every_version() {
+    let primary = STM::builder().capacity(SIZE).build();
+    let (follower, rx) = follower(&primary);
+    let states = workload(&primary, 1, 200);
+
+    for record in rx.try_iter() {
+        let version = record.version;
+        assert_eq!(follower.apply_replica(record), Ok(version));
+        assert_eq!(contents(&follower), states[&version]);
+    }
+    assert_eq!(follower.replica_version(), primary.current_version());
+}
+
+#[test]
+fn records_out_of_order_wait_for_the_earlier_ones() {
+    let primary = STM::builder().capacity(SIZE).build();
+    let (follower, rx) = follower(&primary);
+    let states = workload(&primary, 2, 200);
+
+    let mut records: Vec<_> = rx.try_iter().collect();
+    // reverse runs of 7 records
+    for run in records.chunks_mut(7) {
+        run.reverse();
+    }
+    let mut last = 0;
+    for record in records {
+        let applied = follower.apply_replica(record).unwrap();
+        assert!(applied >= last);
+        last = applied;
+        assert_eq!(contents(&follower), states[&applied]);
+    }
+    assert_eq!(last, primary.current_version());
+}
+
+#[test]
+fn duplicates_are_stale_and_bad_addresses_refused() {
+    let primary = STM::builder().capacity(SIZE).build();
+    let (follower, rx) = follower(&primary);
+    workload(&primary, 3, 2);
+
+    let first = rx.recv().unwrap();
+    assert_eq!(follower.apply_replica(first.clone()), Ok(1));
+    assert_eq!(
+        follower.apply_replica(first),
+        Err(ReplicaError::Stale {
+            applied: 1,
+            version: 1
+        })
+    );
+    let mut second = rx.recv().unwrap();
+    second.entries.push((SIZE, [0; 8]));
+    assert_eq!(
+        follower.apply_replica(second),
+        Err(ReplicaError::OutOfRange {
+            version: 2,
+            addr: SIZE
+        })
+    );
+}
+
+#[test]
+fn a_gap_is_refused_until_a_resync() {
+    let primary = STM::builder().capacity(SIZE).feed_buffer(4).build();
+    let (follower, rx) = follower(&primary);
+
+    // the subscriber falls behind and loses records
+    workload(&primary, 4, 10);
+    let mut buffered: Vec<_> = rx.try_iter().collect();
+    assert_eq!(buffered.len(), 4);
+    for record in buffered.drain(..) {
+        follower.apply_replica(record).unwrap();
+    }
+    let states = workload(&primary, 5, 3);
+    let next = rx.try_recv().unwrap();
+    assert!(next.gap);
+    assert!(matches!(
+        follower.apply_replica(next),
+        Err(ReplicaError::Gap { applied: 4, .. })
+    ));
+    // and keeps refusing, whatever comes
+    let later = rx.try_recv().unwrap();
+    assert!(matches!(
+        follower.apply_replica(later),
+        Err(ReplicaError::Gap { .. })
+    ));
+    let _ = rx.try_iter().count();
+
+    // a resync catches up, and records after its snapshot apply again
+    let (snapshot, rx) = primary.replicate();
+    follower.resync_replica(&snapshot);
+    assert_eq!(follower.replica_version(), primary.current_version());
+    assert_eq!(contents(&follower), states[&primary.current_version()]);
+    for seed in 6..12 {
+        // within the feed buffer this time
+        let states = workload(&primary, seed, 3);
+        for record in rx.try_iter() {
+            match follower.apply_replica(record) {
+                Ok(_) | Err(ReplicaError::Stale { .. }) => (),
+                Err(e) => panic!("{}", e),
+            }
+        }
+        assert_eq!(contents(&follower), states[&primary.current_version()]);
+    }
+}
+
+#[test]
+fn zeroed_regions_reach_the_follower() {
+    let mut primary = STM::builder().capacity(SIZE).build();
+    workload(&primary, 7, 50);
+    let (follower, rx) = follower(&primary);
+    workload(&primary, 8, 50);
+    primary.zero_region(13, 100);
+    workload(&primary, 9, 5);
+
+    for record in rx.try_iter() {
+        follower.apply_replica(record).unwrap();
+    }
+    assert_eq!(follower.replica_version(), primary.current_version());
+    assert_eq!(contents(&follower), contents(&primary));
+}
+
+#[test]
+fn a_diff_lists_exactly_the_stripes_written_between_snapshots() {
+    let primary = STM::builder().capacity(SIZE).build();
+    workload(&primary, 10, 20);
+    let (before, _) = primary.replicate();
+    let copy = before.clone();
+    assert_eq!(copy, before);
+    assert!(copy.diff(&before).is_empty());
+
+    let old = stripe(&before, 0x48);
+    let new = (u64::from_le_bytes(old) ^ 1).to_le_bytes();
+    primary
+        .write_transaction(|tr| {
+            tr.store(0x48, new);
+            // storing the bytes already there changes nothing
+            tr.store(0x50, stripe(&before, 0x50));
+            STMResult::Ok(())
+        })
+        .unwrap();
+    let (after, _) = primary.replicate();
+
+    assert_eq!(before.diff(&after), [(0x48, old, new)]);
+    assert_eq!(after.diff(&before), [(0x48, new, old)]);
+}
+
+#[test]
+#[should_panic(expected = "snapshot size")]
+fn snapshots_of_different_sizes_do_not_diff() {
+    let (small, _) = STM::builder().capacity(64).build().replicate();
+    let (large, _) = STM::builder().capacity(128).build().replicate();
+    small.diff(&large);
+}
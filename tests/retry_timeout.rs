@@ -0,0 +1,85 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tl2::{RetryTimeout, STMResult, StripeValue, TxError, WriteTrans, STM};
+
+// Take one item from the counter at 0, waiting while it is empty.
+fn take(tr: &mut WriteTrans) -> STMResult<u64> {
+    let n = u64::from_stripe(tl2::load!(tr, 0));
+    if n == 0 {
+        return STMResult::Retry;
+    }
+    tr.store(0, (n - 1).to_stripe());
+    STMResult::Ok(n)
+}
+
+fn put(stm: &STM, n: u64) {
+    stm.write_transaction(|tr| {
+        tr.store(0, n.to_stripe());
+        STMResult::Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn a_consumer_with_no_producer_times_out() {
+    let stm = STM::new();
+    let timeout = Duration::from_millis(50);
+    let start = Instant::now();
+    let r = stm.write_transaction_retry_timeout(timeout, take);
+    let waited = start.elapsed();
+
+    assert_eq!(r, Err(RetryTimeout::TimedOut));
+    assert_eq!(
+        r.unwrap_err().to_string(),
+        "timed out waiting to stop retrying"
+    );
+    assert!(waited >= timeout, "gave up after {:?}", waited);
+    assert!(waited < timeout + Duration::from_secs(5), "{:?}", waited);
+    assert_eq!(stm.waiters(), 0);
+    assert!(stm.locked_stripes().is_empty());
+
+    // commits elsewhere wake the consumer without ending its wait
+    let r = thread::scope(|s| {
+        let consumer = s.spawn(|| stm.write_transaction_retry_timeout(timeout, take));
+        while !consumer.is_finished() {
+            stm.write_transaction(|tr| {
+                tr.store(8, 1u64.to_stripe());
+                STMResult::Ok(())
+            })
+            .unwrap();
+            thread::sleep(Duration::from_millis(1));
+        }
+        consumer.join().unwrap()
+    });
+    assert_eq!(r, Err(RetryTimeout::TimedOut));
+}
+
+#[test]
+fn a_producer_in_time_ends_the_wait() {
+    let stm = STM::new();
+    let r = thread::scope(|s| {
+        let consumer =
+            s.spawn(|| stm.write_transaction_retry_timeout(Duration::from_secs(30), take));
+        // let the consumer find the counter empty and wait
+        thread::sleep(Duration::from_millis(20));
+        put(&stm, 3);
+        consumer.join().unwrap()
+    });
+    assert_eq!(r, Ok(3));
+
+    // nothing to wait for: no timeout applies at all
+    assert_eq!(
+        stm.write_transaction_retry_timeout(Duration::ZERO, take),
+        Ok(2)
+    );
+}
+
+#[test]
+fn a_failing_body_is_not_a_timeout() {
+    let stm = STM::new();
+    let r =
+        stm.write_transaction_retry_timeout(Duration::from_secs(30), |_| STMResult::<()>::Abort);
+    assert_eq!(r, Err(RetryTimeout::Failed(TxError::Abort)));
+    assert_eq!(r.unwrap_err().to_string(), "transaction aborted");
+}
@@ -0,0 +1,178 @@
+#![cfg(all(unix, feature = "shm"))]
+
+// The two-location invariant across processes. The children are this test
+// binary run again on `shm_child`, told what to do through TL2_SHM_CHILD.
+
+use std::env;
+use std::process::{self, Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tl2::{load, store, STMResult, STM};
+
+const TOTAL: u64 = 1_000_000;
+const TRANSFERS: u64 = 2_000;
+const CHILD: &str = "TL2_SHM_CHILD";
+
+// Removes the shared memory object when the test ends, passing or not.
+struct Shared(String);
+
+impl Shared {
+    fn create(test: &str, size: usize) -> (Shared, STM) {
+        let name = format!("/tl2-test-{}-{}", test, process::id());
+        let stm = STM::create_shared(&name, size).expect("create");
+        (Shared(name), stm)
+    }
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        let _ = STM::unlink_shared(&self.0);
+    }
+}
+
+fn sum(stm: &STM) -> u64 {
+    stm.read_transaction(|tr| {
+        let a = u64::from_le_bytes(load!(tr, 0));
+        let b = u64::from_le_bytes(load!(tr, 8));
+        STMResult::Ok(a + b)
+    })
+    .unwrap()
+}
+
+fn transfers(stm: &STM, seed: u64) {
+    let mut x = seed | 1;
+    for _ in 0..TRANSFERS {
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        let (from, to) = if x & 1 == 0 { (0, 8) } else { (8, 0) };
+        let amount = (x >> 8) % 100;
+        stm.write_transaction(|tr| {
+            let a = u64::from_le_bytes(load!(tr, from));
+            if a < amount {
+                return STMResult::Ok(());
+            }
+            let b = u64::from_le_bytes(load!(tr, to));
+            store!(tr, from, (a - amount).to_le_bytes());
+            store!(tr, to, (b + amount).to_le_bytes());
+            STMResult::Ok(())
+        })
+        .unwrap();
+    }
+}
+
+// Swap stripes 0 and 8, holding their locks forever.
+fn hang(stm: &STM) {
+    stm.write_transaction(|tr| {
+        let a = u64::from_le_bytes(load!(tr, 0));
+        let b = u64::from_le_bytes(load!(tr, 8));
+        store!(tr, 0, b.to_le_bytes());
+        store!(tr, 8, a.to_le_bytes());
+        tr.commit_when(|_| loop {
+            thread::sleep(Duration::from_secs(1));
+        });
+        STMResult::Ok(())
+    });
+}
+
+#[test]
+fn shm_child() {
+    let Ok(task) = env::var(CHILD) else {
+        return;
+    };
+    let args: Vec<&str> = task.split(' ').collect();
+    let stm = STM::attach_shared(args[1]).expect("attach");
+    match args[..] {
+        ["transfers", _, seed] => transfers(&stm, seed.parse().unwrap()),
+        ["hang", _] => hang(&stm),
+        _ => panic!("unknown task {:?}", task),
+    }
+}
+
+fn spawn(task: &str) -> Child {
+    Command::new(env::current_exe().unwrap())
+        .args(["shm_child", "--exact", "--quiet"])
+        .env(CHILD, task)
+        .stdout(Stdio::null())
+        .spawn()
+        .expect("spawn a child")
+}
+
+#[test]
+fn the_invariant_holds_across_processes() {
+    let (shared, stm) = Shared::create("invariant", 16);
+    stm.write_transaction(|tr| {
+        store!(tr, 0, TOTAL.to_le_bytes());
+        STMResult::Ok(())
+    })
+    .unwrap();
+
+    let children: Vec<Child> = (1..=3)
+        .map(|seed| spawn(&format!("transfers {} {}", shared.0, seed)))
+        .collect();
+    let mut snapshots = 0;
+    for mut child in children {
+        while child.try_wait().unwrap().is_none() {
+            assert_eq!(sum(&stm), TOTAL, "after {} snapshots", snapshots);
+            snapshots += 1;
+        }
+        assert!(child.wait().unwrap().success());
+    }
+    assert!(snapshots > 0);
+    assert_eq!(sum(&stm), TOTAL);
+    // the children's commits moved the shared clock
+    assert!(stm.global_clock() > TRANSFERS);
+}
+
+#[test]
+fn the_locks_of_a_killed_process_are_recovered() {
+    let (shared, stm) = Shared::create("recover", 16);
+    stm.write_transaction(|tr| {
+        store!(tr, 0, 1u64.to_le_bytes());
+        store!(tr, 8, 2u64.to_le_bytes());
+        STMResult::Ok(())
+    })
+    .unwrap();
+
+    let mut child = spawn(&format!("hang {}", shared.0));
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while stm.locked_stripes().len() < 2 {
+        assert!(Instant::now() < deadline, "the child never locked");
+        thread::sleep(Duration::from_millis(1));
+    }
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    assert_eq!(stm.locked_stripes(), [0, 8]);
+    assert_eq!(stm.recover_dead_owner(child.id()), 2);
+    assert!(stm.locked_stripes().is_empty());
+    // the swap never got to write back
+    assert_eq!(sum(&stm), 3);
+    stm.write_transaction(|tr| {
+        store!(tr, 0, 5u64.to_le_bytes());
+        STMResult::Ok(())
+    })
+    .unwrap();
+    assert_eq!(sum(&stm), 7);
+    // nothing is left to recover
+    assert_eq!(stm.recover_dead_owner(child.id()), 0);
+}
+
+#[test]
+fn attaching_checks_the_region() {
+    let (shared, stm) = Shared::create("attach", 16);
+    assert!(STM::create_shared(&shared.0, 16).is_err(), "it exists");
+    assert!(STM::create_shared("/tl2-test-odd-size", 12).is_err());
+    assert!(STM::attach_shared("/tl2-test-missing").is_err());
+
+    stm.write_transaction(|tr| {
+        store!(tr, 8, 9u64.to_le_bytes());
+        STMResult::Ok(())
+    })
+    .unwrap();
+    let other = STM::attach_shared(&shared.0).unwrap();
+    assert_eq!(other.capacity(), 16);
+    assert_eq!(sum(&other), 9);
+    assert_eq!(other.global_clock(), stm.global_clock());
+}
@@ -0,0 +1,75 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::thread;
+
+use tl2::{STMResult, StripeValue, STM};
+
+const STRIPE_SIZE: usize = 8;
+
+fn write_other(stm: &STM, addr: usize, val: u64) {
+    thread::scope(|s| {
+        s.spawn(|| {
+            stm.write_transaction(|tr| {
+                tr.store(addr, val.to_stripe());
+                STMResult::Ok(())
+            })
+        });
+    });
+}
+
+#[test]
+fn a_body_restarts_once_a_writer_invalidates_it() {
+    let stm = STM::builder().stats(true).build();
+    let runs = AtomicU32::new(0);
+
+    let seen = stm
+        .write_transaction(|tr| {
+            let run = runs.fetch_add(1, Ordering::SeqCst);
+            let val = match tr.load(0) {
+                Some(v) => u64::from_stripe(v),
+                None => return STMResult::Retry,
+            };
+            // a long body polls between steps
+            for step in 0..4 {
+                if run == 0 && step == 2 {
+                    write_other(&stm, 0, 5);
+                }
+                if tr.should_yield() {
+                    return STMResult::Retry;
+                }
+            }
+            tr.store(STRIPE_SIZE, (val + 1).to_stripe());
+            STMResult::Ok(val)
+        })
+        .unwrap();
+
+    // the first run saw the write and restarted, the second committed
+    assert_eq!(runs.load(Ordering::SeqCst), 2);
+    assert_eq!(seen, 5);
+    let stats = stm.stats().unwrap();
+    assert_eq!(stats.commits, 2);
+    assert_eq!(stats.retries, 0, "a yield is a restart, not a give-up");
+    assert_eq!(stats.validation, 1);
+}
+
+#[test]
+fn writes_outside_the_read_set_do_not_yield() {
+    let stm = STM::new();
+    let runs = AtomicU32::new(0);
+
+    stm.write_transaction(|tr| {
+        runs.fetch_add(1, Ordering::SeqCst);
+        tr.load(0);
+        assert!(!tr.should_yield());
+        // only stored, never read, so it is not in the read-set
+        tr.store(2 * STRIPE_SIZE, 1u64.to_stripe());
+        if runs.load(Ordering::SeqCst) == 1 {
+            write_other(&stm, STRIPE_SIZE, 1);
+            write_other(&stm, 2 * STRIPE_SIZE, 2);
+        }
+        assert!(!tr.should_yield());
+        STMResult::Ok(())
+    })
+    .unwrap();
+
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+}
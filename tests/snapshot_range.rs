@@ -0,0 +1,75 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+use tl2::{STMResult, STM};
+
+const STRIPES: usize = 64;
+const WRITES: u8 = 200;
+
+// Every write transaction fills all stripes with one byte, so a snapshot
+// holding two different bytes mixed two commits.
+fn fill(stm: &STM, byte: u8) {
+    stm.write_transaction(|tr| {
+        for a in (0..STRIPES * 8).step_by(8) {
+            tr.store(a, [byte; 8]);
+        }
+        STMResult::Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn snapshots_are_consistent_under_writers() {
+    let stm = STM::builder().capacity(STRIPES * 8).build();
+    let done = AtomicBool::new(false);
+
+    thread::scope(|s| {
+        let writers: Vec<_> = (0..2)
+            .map(|w| {
+                let stm = &stm;
+                s.spawn(move || {
+                    for i in 0..WRITES {
+                        fill(stm, i.wrapping_mul(2).wrapping_add(w));
+                    }
+                })
+            })
+            .collect();
+
+        let readers: Vec<_> = [(0, STRIPES * 8), (3, STRIPES * 8 - 6), (13, 100)]
+            .iter()
+            .map(|&(addr, len)| {
+                let (stm, done) = (&stm, &done);
+                s.spawn(move || {
+                    let mut snapshots = 0;
+                    while !done.load(Ordering::Relaxed) || snapshots == 0 {
+                        let snap = stm.snapshot_range(addr, len).unwrap();
+                        assert_eq!(snap.len(), len);
+                        assert!(
+                            snap.iter().all(|b| *b == snap[0]),
+                            "torn snapshot of {}..{}: {:?}",
+                            addr,
+                            addr + len,
+                            snap
+                        );
+                        snapshots += 1;
+                    }
+                })
+            })
+            .collect();
+
+        for w in writers {
+            w.join().unwrap();
+        }
+        done.store(true, Ordering::Relaxed);
+        for r in readers {
+            r.join().unwrap();
+        }
+    });
+}
+
+#[test]
+fn out_of_bounds_is_none() {
+    let stm = STM::builder().capacity(STRIPES * 8).build();
+    assert_eq!(stm.snapshot_range(STRIPES * 8 - 4, 8), None);
+    assert_eq!(stm.snapshot_range(STRIPES * 8, 0), Some(vec![]));
+}
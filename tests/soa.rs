@@ -0,0 +1,121 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::thread;
+
+use tl2::{STMResult, SoaStm, STM};
+
+tl2::trans_record! {
+    #[derive(Debug, Clone, PartialEq)]
+    struct Counters {
+        hits: u64,
+        misses: u64,
+        evictions: u64,
+    }
+}
+
+const LEN: usize = 16;
+const ROUNDS: u64 = 300;
+
+fn stm() -> STM {
+    STM::builder()
+        .capacity(64 + LEN * 3 * 8)
+        .stats(true)
+        .build()
+}
+
+#[test]
+fn threads_updating_different_fields_of_one_element_never_conflict() {
+    let stm = stm();
+    let arr = SoaStm::<Counters>::new(64, LEN);
+    thread::scope(|s| {
+        for field in 0..3 {
+            let (stm, arr) = (&stm, &arr);
+            s.spawn(move || {
+                for _ in 0..ROUNDS {
+                    stm.write_transaction(|tr| {
+                        let n: u64 = arr.get_field(tr, 5, field).unwrap();
+                        // let the others run between the load and the store
+                        thread::yield_now();
+                        arr.set_field(tr, 5, field, n + 1);
+                        STMResult::Ok(())
+                    })
+                    .unwrap();
+                }
+            });
+        }
+    });
+    assert_eq!(stm.stats().unwrap().restarts(), 0);
+    let got = stm.read_transaction(|tr| arr.get(tr, 5).map_or(STMResult::Retry, STMResult::Ok));
+    let want = Counters {
+        hits: ROUNDS,
+        misses: ROUNDS,
+        evictions: ROUNDS,
+    };
+    assert_eq!(got, Some(want));
+}
+
+#[test]
+fn a_field_update_commits_past_another_field_but_a_record_update_does_not() {
+    let stm = stm();
+    let arr = SoaStm::<Counters>::new(64, LEN);
+    let bump_misses = || {
+        thread::scope(|s| {
+            s.spawn(|| {
+                stm.write_transaction(|tr| {
+                    let n: u64 = arr.get_field(tr, 3, 1).unwrap();
+                    arr.set_field(tr, 3, 1, n + 1);
+                    STMResult::Ok(())
+                })
+                .unwrap()
+            });
+        });
+    };
+
+    let runs = AtomicU32::new(0);
+    stm.write_transaction(|tr| {
+        let n: u64 = arr.get_field(tr, 3, 0).unwrap();
+        if runs.fetch_add(1, Ordering::SeqCst) == 0 {
+            bump_misses();
+        }
+        arr.set_field(tr, 3, 0, n + 1);
+        STMResult::Ok(())
+    })
+    .unwrap();
+    assert_eq!(runs.swap(0, Ordering::SeqCst), 1);
+
+    // the whole element read, then written: the same commit makes it restart
+    stm.write_transaction(|tr| {
+        let mut c = arr.get(tr, 3).unwrap();
+        if runs.fetch_add(1, Ordering::SeqCst) == 0 {
+            bump_misses();
+        }
+        c.hits += 1;
+        arr.set(tr, 3, &c);
+        STMResult::Ok(())
+    })
+    .unwrap();
+    assert_eq!(runs.into_inner(), 2);
+
+    let got = stm.read_transaction(|tr| arr.get(tr, 3).map_or(STMResult::Retry, STMResult::Ok));
+    let want = Counters {
+        hits: 2,
+        misses: 2,
+        evictions: 0,
+    };
+    assert_eq!(got, Some(want));
+}
+
+#[test]
+fn each_field_is_a_run_of_len_stripes() {
+    let arr = SoaStm::<Counters>::new(64, LEN);
+    assert_eq!((arr.len(), arr.is_empty()), (LEN, false));
+    assert_eq!(arr.addr(0, 0), 64);
+    assert_eq!(arr.addr(1, 0), 72);
+    assert_eq!(arr.addr(0, 1), 64 + LEN * 8);
+    assert_eq!(arr.addr(LEN - 1, 2), 64 + (3 * LEN - 1) * 8);
+}
+
+#[test]
+#[should_panic(expected = "no field 3")]
+fn a_field_past_the_record_is_refused() {
+    SoaStm::<Counters>::new(0, LEN).addr(0, 3);
+}
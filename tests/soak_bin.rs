@@ -0,0 +1,39 @@
+use std::process::Command;
+
+// Run tl2-soak for `seconds`, with the clock jumping near the lock bit so
+// that the bounded run also covers large versions, and check that it held
+// every invariant. Returns how many reports it printed.
+fn soak(seconds: u32) -> usize {
+    let out = Command::new(env!("CARGO_BIN_EXE_tl2-soak"))
+        .args(["--seconds", &seconds.to_string(), "--report-secs", "1"])
+        .args(["--threads", "4", "--accounts", "64"])
+        .args(["--clock-jump", "1000000000000000000"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    assert!(
+        out.status.success(),
+        "tl2-soak failed:\n{}{}",
+        stdout,
+        stderr
+    );
+
+    let last = stdout.lines().last().unwrap();
+    assert!(last.starts_with("ok: "), "{}", stdout);
+    let clock: u64 = last.rsplit(' ').next().unwrap().parse().unwrap();
+    assert!(clock > 1_000_000_000_000_000_000, "{}", stdout);
+    stdout.lines().filter(|l| l.contains(" clock=")).count()
+}
+
+#[test]
+fn a_smoke_soak_run_holds_every_invariant() {
+    assert!(soak(3) >= 2);
+}
+
+// The 30-second configuration of the test suite; release qualification
+// runs it for hours with a larger `--seconds`.
+#[test]
+fn a_thirty_second_soak_run_holds_every_invariant() {
+    assert!(soak(30) >= 25);
+}
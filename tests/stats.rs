@@ -0,0 +1,157 @@
+use std::thread;
+
+use tl2::{STMResult, StatsSnapshot, StripeValue, TxError, STM};
+
+fn write_other(stm: &STM, addr: usize) {
+    thread::scope(|s| {
+        s.spawn(|| {
+            stm.write_transaction(|tr| {
+                tr.store(addr, 7u64.to_stripe());
+                STMResult::Ok(())
+            })
+        });
+    });
+}
+
+fn stm() -> STM {
+    STM::builder().stats(true).build()
+}
+
+#[test]
+fn counters_follow_a_scripted_run() {
+    let stm = stm();
+
+    // a commit and a read
+    stm.write_transaction(|tr| {
+        tr.store(0, 1u64.to_stripe());
+        STMResult::Ok(())
+    })
+    .unwrap();
+    stm.read_transaction(|tr| STMResult::Ok(tr.load(0)))
+        .unwrap();
+
+    // a user abort and a retry without a conflict
+    assert_eq!(
+        stm.try_write_transaction(|_| STMResult::<()>::Abort),
+        Err(TxError::Abort)
+    );
+    assert_eq!(
+        stm.try_write_transaction(|_| STMResult::<()>::Retry),
+        Err(TxError::Retry)
+    );
+
+    // a stripe written after the start is met by the load
+    let first = std::cell::Cell::new(true);
+    stm.write_transaction(|tr| {
+        if first.replace(false) {
+            write_other(&stm, 8);
+        }
+        match tr.load(8) {
+            Some(v) => {
+                tr.store(16, v);
+                STMResult::Ok(())
+            }
+            None => STMResult::Retry,
+        }
+    })
+    .unwrap();
+
+    // a stripe written after the load is met by commit validation
+    let first = std::cell::Cell::new(true);
+    stm.write_transaction(|tr| {
+        let v = match tr.load(8) {
+            Some(v) => v,
+            None => return STMResult::Retry,
+        };
+        if first.replace(false) {
+            write_other(&stm, 8);
+        }
+        tr.store(24, v);
+        STMResult::Ok(())
+    })
+    .unwrap();
+
+    assert_eq!(
+        stm.stats().unwrap(),
+        StatsSnapshot {
+            commits: 5, // two of them by `write_other`
+            reads: 1,
+            aborts: 1,
+            retries: 1,
+            pre_validation: 1,
+            validation: 1,
+            ..StatsSnapshot::default()
+        }
+    );
+
+    stm.reset_stats();
+    assert_eq!(stm.stats().unwrap(), StatsSnapshot::default());
+}
+
+#[test]
+fn counters_are_summed_over_threads() {
+    let stm = stm();
+    thread::scope(|s| {
+        for t in 0..4 {
+            let stm = &stm;
+            s.spawn(move || {
+                for _ in 0..100 {
+                    stm.write_transaction(|tr| {
+                        tr.store(8 * t, 1u64.to_stripe());
+                        STMResult::Ok(())
+                    })
+                    .unwrap();
+                    stm.read_transaction(|tr| STMResult::Ok(tr.load(8 * t)))
+                        .unwrap();
+                }
+            });
+        }
+    });
+    let stats = stm.stats().unwrap();
+    assert_eq!((stats.commits, stats.reads), (400, 400));
+}
+
+#[test]
+fn disabled_stats_read_as_none() {
+    assert_eq!(STM::new().stats(), None);
+}
+
+// "b" tries to lock a stripe while "a" holds it, then waits for "a" to
+// commit before its second attempt.
+#[cfg(feature = "testing")]
+#[test]
+fn a_held_lock_counts_as_a_lock_restart() {
+    use std::sync::Arc;
+    use tl2::{STMBuilder, Script, YieldPoint};
+
+    let script = Arc::new(Script::new([
+        ("b", YieldPoint::Sampled),
+        ("a", YieldPoint::Locked),
+        ("b", YieldPoint::Locked),
+        ("a", YieldPoint::Publishing),
+        ("a", YieldPoint::Committed),
+        ("b", YieldPoint::Sampled),
+    ]));
+    let stm = STMBuilder::new()
+        .stats(true)
+        .scheduler(script.clone())
+        .build();
+    thread::scope(|s| {
+        for (name, val) in [("a", 1u64), ("b", 2)] {
+            let stm = &stm;
+            thread::Builder::new()
+                .name(name.into())
+                .spawn_scoped(s, move || {
+                    stm.write_transaction(|tr| {
+                        tr.store(0, val.to_stripe());
+                        STMResult::Ok(())
+                    })
+                    .unwrap()
+                })
+                .unwrap();
+        }
+    });
+    assert!(script.is_done());
+    let stats = stm.stats().unwrap();
+    assert_eq!((stats.commits, stats.lock, stats.restarts()), (2, 1, 1));
+}
@@ -0,0 +1,62 @@
+// Behaviour particular to each backend; tests/invariant.rs runs the
+// invariant suite on all of them.
+
+use tl2::{ExternalStorage, STMResult, StripeValue, STM};
+
+fn read(stm: &STM, addr: usize) -> u64 {
+    stm.read_transaction(|tr| STMResult::Ok(u64::from_stripe(tl2::load!(tr, addr))))
+        .unwrap()
+}
+
+fn write(stm: &STM, addr: usize, v: u64) {
+    stm.write_transaction(|tr| {
+        tr.store(addr, v.to_stripe());
+        STMResult::Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn external_bytes_are_the_initial_memory_and_take_the_commits() {
+    let mut buf = vec![0u8; 64];
+    buf[8..16].copy_from_slice(&7u64.to_le_bytes());
+    // SAFETY: `buf` outlives the STM and is not touched until it is dropped
+    let storage = unsafe { ExternalStorage::from_raw_parts(buf.as_mut_ptr(), buf.len()) };
+    let stm = STM::builder().storage(storage).build();
+    assert_eq!(stm.capacity(), 64);
+    assert_eq!(read(&stm, 8), 7);
+
+    write(&stm, 16, 9);
+    drop(stm);
+    assert_eq!(buf[16..24], 9u64.to_le_bytes());
+    assert_eq!(buf[8..16], 7u64.to_le_bytes());
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn synced_commits_are_in_the_file() {
+    use tl2::MmapStorage;
+
+    let path = std::env::temp_dir().join(format!("tl2-storage-{}", std::process::id()));
+    let stm = STM::builder()
+        .storage(MmapStorage::open(&path, 64).unwrap())
+        .build();
+    assert_eq!(read(&stm, 0), 0);
+    write(&stm, 0, 1);
+    write(&stm, 56, 2);
+    stm.sync().unwrap();
+    drop(stm);
+
+    let bytes = std::fs::read(&path).unwrap();
+    assert_eq!(bytes.len(), 64);
+    assert_eq!(bytes[0..8], 1u64.to_le_bytes());
+    assert_eq!(bytes[56..64], 2u64.to_le_bytes());
+
+    // mapped again, the file carries the memory over
+    let stm = STM::builder()
+        .storage(MmapStorage::open(&path, 64).unwrap())
+        .build();
+    assert_eq!((read(&stm, 0), read(&stm, 56)), (1, 2));
+    drop(stm);
+    std::fs::remove_file(&path).unwrap();
+}
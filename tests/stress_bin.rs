@@ -0,0 +1,108 @@
+use std::process::Command;
+
+fn stress(args: &[&str]) -> String {
+    let out = Command::new(env!("CARGO_BIN_EXE_tl2-stress"))
+        .args(["--millis", "1000", "--threads", "4", "--stripes", "64"])
+        .args(["--skew", "zipf:1.2", "--write-pct", "80"])
+        .args(args)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(out.status.success(), "tl2-stress failed:\n{}", stdout);
+    stdout
+}
+
+#[test]
+fn a_one_second_run_holds_the_invariant_and_reports_hot_stripes() {
+    let out = stress(&[]);
+    assert!(out.contains("invariant: sum"), "{}", out);
+    assert!(out.trim_end().ends_with("ok"), "{}", out);
+
+    let hot: Vec<&str> = out
+        .lines()
+        .skip_while(|l| *l != "hottest stripes:")
+        .skip(1)
+        .take_while(|l| l.starts_with("  "))
+        .collect();
+    assert!(!hot.is_empty() && hot.len() <= 10, "{}", out);
+    let counts: Vec<u64> = hot
+        .iter()
+        .map(|l| l.split_whitespace().nth(2).unwrap().parse().unwrap())
+        .collect();
+    assert!(
+        counts.windows(2).all(|w| w[0] >= w[1]),
+        "not hottest first: {:?}",
+        counts
+    );
+}
+
+#[test]
+fn the_json_report_lists_hot_stripes() {
+    let out = stress(&["--json"]);
+    assert!(out.contains("\"hottest\":[{\"stripe\":"), "{}", out);
+    assert!(out.contains("\"invariant\":true"), "{}", out);
+}
+
+#[test]
+fn a_uniform_run_reports_throughput_restarts_and_latency() {
+    let out = stress(&[
+        "--skew",
+        "uniform",
+        "--write-pct",
+        "50",
+        "--reads",
+        "3",
+        "--writes",
+        "3",
+        "--backoff",
+        "spin:4",
+    ]);
+    let first = out.lines().next().unwrap();
+    // threads=4 1.00s: N ops/s (R reads, W writes)
+    let nums: Vec<u64> = first
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse().unwrap())
+        .collect();
+    assert_eq!(nums[0], 4, "{}", out);
+    let (ops, reads, writes) = (nums[3], nums[4], nums[5]);
+    assert!(ops > 0 && reads > 0 && writes > 0, "{}", out);
+    assert!(out.contains("restarts per attempt: "), "{}", out);
+    for phase in ["execute", "lock", "validate", "publish"] {
+        assert!(
+            out.lines()
+                .any(|l| l.trim_start().starts_with(phase) && l.contains(" p99=")),
+            "no {} latency in {}",
+            phase,
+            out
+        );
+    }
+    assert!(out.trim_end().ends_with("ok"), "{}", out);
+}
+
+#[test]
+fn the_json_report_has_every_section() {
+    let out = stress(&["--json", "--write-pct", "0"]);
+    assert_eq!(out.lines().count(), 1, "{}", out);
+    for key in [
+        "\"ops_per_sec\":",
+        "\"writes\":0,",
+        "\"restart_rate\":{\"pre_validation\":",
+        "\"latency_ns\":{\"execute\":{\"p50\":",
+        "\"publish\":{\"p50\":",
+    ] {
+        assert!(out.contains(key), "no {} in {}", key, out);
+    }
+}
+
+#[test]
+fn bad_flags_print_the_usage() {
+    let out = Command::new(env!("CARGO_BIN_EXE_tl2-stress"))
+        .args(["--writes", "0"])
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(2));
+    assert!(String::from_utf8(out.stderr)
+        .unwrap()
+        .starts_with("usage: tl2-stress"));
+}
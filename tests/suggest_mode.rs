@@ -0,0 +1,83 @@
+use std::thread;
+
+use tl2::{Mode, STMResult, StripeValue, STM};
+
+fn read(stm: &STM) {
+    stm.read_transaction(|tr| STMResult::Ok(tl2::load!(tr, 0)))
+        .unwrap();
+}
+
+fn write(stm: &STM) {
+    stm.write_transaction(|tr| {
+        let n = u64::from_stripe(tl2::load!(tr, 0));
+        tr.store(0, (n + 1).to_stripe());
+        STMResult::Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn many_reads_lean_read_only() {
+    let stm = STM::builder().track_mode(true).build();
+    assert_eq!(stm.suggest_mode(), Mode::Write);
+    for _ in 0..1_000 {
+        read(&stm);
+    }
+    assert_eq!(stm.suggest_mode(), Mode::Read);
+    // a write transaction storing nothing counts as read-only
+    for _ in 0..1_000 {
+        stm.write_transaction(|tr| STMResult::Ok(tl2::load!(tr, 8)))
+            .unwrap();
+    }
+    assert_eq!(stm.suggest_mode(), Mode::Read);
+}
+
+#[test]
+fn the_suggestion_follows_the_last_64_transactions() {
+    let stm = STM::builder().track_mode(true).build();
+    for _ in 0..64 {
+        read(&stm);
+    }
+    for _ in 0..31 {
+        write(&stm);
+    }
+    assert_eq!(stm.suggest_mode(), Mode::Read);
+    // half and half is not most
+    write(&stm);
+    assert_eq!(stm.suggest_mode(), Mode::Write);
+    for _ in 0..64 {
+        write(&stm);
+    }
+    assert_eq!(stm.suggest_mode(), Mode::Write);
+    for _ in 0..33 {
+        read(&stm);
+    }
+    assert_eq!(stm.suggest_mode(), Mode::Read);
+}
+
+#[test]
+fn each_thread_gets_its_own_suggestion() {
+    let stm = STM::builder().track_mode(true).build();
+    for _ in 0..100 {
+        read(&stm);
+    }
+    thread::scope(|s| {
+        s.spawn(|| {
+            assert_eq!(stm.suggest_mode(), Mode::Write);
+            for _ in 0..100 {
+                write(&stm);
+            }
+            assert_eq!(stm.suggest_mode(), Mode::Write);
+        });
+    });
+    assert_eq!(stm.suggest_mode(), Mode::Read);
+}
+
+#[test]
+fn without_tracking_the_suggestion_is_write() {
+    let stm = STM::new();
+    for _ in 0..100 {
+        read(&stm);
+    }
+    assert_eq!(stm.suggest_mode(), Mode::Write);
+}
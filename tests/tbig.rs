@@ -0,0 +1,189 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use tl2::{STMResult, TBig, STM};
+
+const N: usize = 16;
+
+fn big() -> TBig<[u64; N]> {
+    TBig::new(64)
+}
+
+#[test]
+fn get_never_sees_a_torn_value_under_concurrent_sets() {
+    let stm = Arc::new(STM::new());
+    let done = Arc::new(AtomicBool::new(false));
+
+    let writers: Vec<_> = (1..=2u64)
+        .map(|w| {
+            let stm = stm.clone();
+            thread::spawn(move || {
+                let big = big();
+                for n in 0..300 {
+                    let val = [w * 1000 + n; N];
+                    stm.write_transaction(|tr| STMResult::Ok(big.set(tr, &val)))
+                        .unwrap();
+                }
+            })
+        })
+        .collect();
+
+    let readers: Vec<_> = (0..2)
+        .map(|_| {
+            let (stm, done) = (stm.clone(), done.clone());
+            thread::spawn(move || {
+                let big = big();
+                let mut reads = 0;
+                while !done.load(Ordering::Relaxed) || reads == 0 {
+                    let (val, ver) = stm
+                        .read_transaction(|tr| match (big.get(tr), big.version(tr)) {
+                            (Some(val), Some(ver)) => STMResult::Ok((val, ver)),
+                            _ => STMResult::Retry,
+                        })
+                        .unwrap();
+                    assert!(val.iter().all(|v| *v == val[0]), "torn value {:?}", val);
+                    assert_eq!(val[0] == 0, ver == 0);
+                    reads += 1;
+                }
+            })
+        })
+        .collect();
+
+    for w in writers {
+        w.join().unwrap();
+    }
+    done.store(true, Ordering::Relaxed);
+    for r in readers {
+        r.join().unwrap();
+    }
+    let ver = stm.read_transaction(|tr| STMResult::Ok(big().version(tr).unwrap()));
+    assert_eq!(ver, Some(600));
+}
+
+// A `get` reads the version stripe too, so a bump of the version alone
+// is a conflict.
+#[cfg(feature = "testing")]
+#[test]
+fn a_version_bump_alone_restarts_a_get() {
+    use std::sync::atomic::AtomicU32;
+    use tl2::StripeValue;
+
+    let stm = STM::new();
+    stm.inject_conflict_before_commit(|stm| {
+        stm.write_transaction(|tr| {
+            tr.store(64, 7u64.to_stripe());
+            STMResult::Ok(())
+        })
+        .unwrap()
+    });
+    let runs = AtomicU32::new(0);
+    stm.write_transaction(|tr| {
+        runs.fetch_add(1, Ordering::SeqCst);
+        match big().get(tr) {
+            Some(val) => {
+                tr.store(0, val[0].to_stripe());
+                STMResult::Ok(())
+            }
+            None => STMResult::Retry,
+        }
+    })
+    .unwrap();
+    assert_eq!(runs.into_inner(), 2);
+}
+
+const SLOTS: usize = 8;
+
+fn slot(i: usize) -> TBig<Option<u64>> {
+    TBig::new(i * TBig::<Option<u64>>::size())
+}
+
+fn items(stm: &STM) -> Vec<u64> {
+    let mut items = stm
+        .read_transaction(|tr| {
+            let mut items = Vec::new();
+            for i in 0..SLOTS {
+                match slot(i).get(tr) {
+                    Some(Some(v)) => items.push(v),
+                    Some(None) => (),
+                    None => return STMResult::Retry,
+                }
+            }
+            STMResult::Ok(items)
+        })
+        .unwrap();
+    items.sort_unstable();
+    items
+}
+
+#[test]
+fn transfer_keeps_zero_apart_from_empty() {
+    let stm = STM::new();
+    let (a, b) = (slot(0), slot(1));
+    stm.write_transaction(|tr| STMResult::Ok(a.set(tr, &Some(0))))
+        .unwrap();
+
+    let moved = stm.write_transaction(|tr| STMResult::Ok(a.transfer(tr, &b)));
+    assert_eq!(moved, Some(Some(Some(0))));
+    let (va, vb) = stm
+        .read_transaction(|tr| STMResult::Ok((a.get(tr).unwrap(), b.get(tr).unwrap())))
+        .unwrap();
+    assert_eq!((va, vb), (None, Some(0)));
+
+    // nothing to move: `b` keeps its value
+    let moved = stm.write_transaction(|tr| STMResult::Ok(a.transfer(tr, &b)));
+    assert_eq!(moved, Some(Some(None)));
+    assert_eq!(items(&stm), [0]);
+}
+
+// Threads pass items around a ring of slots, each moving an item on only
+// into an empty slot, while a reader checks that the items are always the
+// same ones: none lost, none duplicated.
+#[test]
+fn handoffs_never_lose_or_duplicate_items() {
+    let stm = Arc::new(STM::new());
+    let start: Vec<u64> = (0..SLOTS as u64 / 2).collect();
+    stm.write_transaction(|tr| {
+        for (i, v) in start.iter().enumerate() {
+            slot(2 * i).set(tr, &Some(*v)).unwrap();
+        }
+        STMResult::Ok(())
+    })
+    .unwrap();
+    let done = Arc::new(AtomicBool::new(false));
+
+    let movers: Vec<_> = (0..4)
+        .map(|t| {
+            let stm = stm.clone();
+            thread::spawn(move || {
+                for n in 0..2_000 {
+                    let i = (t * 3 + n) % SLOTS;
+                    let (from, to) = (slot(i), slot((i + 1) % SLOTS));
+                    stm.write_transaction(|tr| match to.get(tr) {
+                        Some(None) => STMResult::Ok(from.transfer(tr, &to)),
+                        _ => STMResult::Ok(None),
+                    })
+                    .unwrap();
+                }
+            })
+        })
+        .collect();
+
+    let reader = {
+        let (stm, done, start) = (stm.clone(), done.clone(), start.clone());
+        thread::spawn(move || {
+            let mut reads = 0;
+            while !done.load(Ordering::Relaxed) || reads == 0 {
+                assert_eq!(items(&stm), start);
+                reads += 1;
+            }
+        })
+    };
+
+    for m in movers {
+        m.join().unwrap();
+    }
+    done.store(true, Ordering::Relaxed);
+    reader.join().unwrap();
+    assert_eq!(items(&stm), start);
+}
@@ -0,0 +1,232 @@
+#![cfg(feature = "testkit")]
+
+mod common;
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use common::{THashMap, TQueue};
+use tl2::{Call, Harness, STMResult, StripeValue, TLog, TSet, STM};
+
+const LOG_SLOTS: usize = 12;
+const QUEUE_SLOTS: u64 = 6;
+const MAP_BUCKETS: u64 = 8;
+
+// fetch-add on stripe 0: the result depends on the commit order
+fn counter(cap: u64) -> Harness<(), u64, u64> {
+    Harness::new(|| (STM::new(), ()), 0).op(
+        "add",
+        |_, _| true,
+        move |_, tr, n| {
+            let old = u64::from_stripe(tl2::load!(tr, 0));
+            tr.store(0, (old + n).min(cap).to_stripe());
+            STMResult::Ok(old)
+        },
+        |model, n| {
+            let old = *model;
+            *model += n;
+            old
+        },
+    )
+}
+
+#[test]
+fn a_set_matches_a_hash_set() {
+    Harness::new(
+        || {
+            (
+                STM::builder().capacity(1024).build(),
+                TSet::<u64>::new(0, 1024),
+            )
+        },
+        HashSet::new(),
+    )
+    .op(
+        "insert",
+        |_, _| true,
+        |set, tr, v| {
+            set.insert(tr, v)
+                .map_or(STMResult::Retry, |r| STMResult::Ok(r.ok()))
+        },
+        |model, v| Some(model.insert(v)),
+    )
+    .op(
+        "remove",
+        |model, v| model.contains(&v),
+        |set, tr, v| {
+            set.remove(tr, &v)
+                .map_or(STMResult::Retry, |r| STMResult::Ok(Some(r)))
+        },
+        |model, v| Some(model.remove(&v)),
+    )
+    .op(
+        "contains",
+        |_, _| true,
+        |set, tr, v| {
+            set.contains(tr, &v)
+                .map_or(STMResult::Retry, |r| STMResult::Ok(Some(r)))
+        },
+        |model, v| Some(model.contains(&v)),
+    )
+    .cases(32)
+    .run();
+}
+
+#[test]
+fn a_log_matches_a_vec_up_to_full() {
+    let len = (LOG_SLOTS + 1) * 8;
+    Harness::new(
+        move || (STM::builder().capacity(len).build(), TLog::new(0, len)),
+        Vec::new(),
+    )
+    // the slot taken, or None once full
+    .op(
+        "append",
+        |_, _| true,
+        |log, tr, v| {
+            log.append(tr, v.to_stripe()).map_or(STMResult::Retry, |r| {
+                STMResult::Ok(r.ok().map(|i| i as u64))
+            })
+        },
+        |model: &mut Vec<u64>, v| {
+            if model.len() == LOG_SLOTS {
+                return None;
+            }
+            model.push(v);
+            Some(model.len() as u64 - 1)
+        },
+    )
+    .op(
+        "get",
+        |_, _| true,
+        |log, tr, i| {
+            log.get(tr, i as usize)
+                .map_or(STMResult::Retry, |e| STMResult::Ok(e.map(u64::from_stripe)))
+        },
+        |model, i| model.get(i as usize).copied(),
+    )
+    .op(
+        "len",
+        |_, _| true,
+        |log, tr, _| {
+            log.len(tr)
+                .map_or(STMResult::Retry, |n| STMResult::Ok(Some(n as u64)))
+        },
+        |model, _| Some(model.len() as u64),
+    )
+    .calls(24)
+    .cases(32)
+    .run();
+}
+
+#[test]
+fn a_queue_matches_a_vec_deque_up_to_full() {
+    Harness::new(
+        || {
+            let len = TQueue::size(QUEUE_SLOTS);
+            (
+                STM::builder().capacity(len).build(),
+                TQueue::new(0, QUEUE_SLOTS),
+            )
+        },
+        VecDeque::new(),
+    )
+    // the value pushed, or None once full
+    .op(
+        "push",
+        |_, _| true,
+        |q, tr, v| {
+            q.push(tr, v)
+                .map_or(STMResult::Retry, |ok| STMResult::Ok(Some(v).filter(|_| ok)))
+        },
+        |model: &mut VecDeque<u64>, v| {
+            if model.len() as u64 == QUEUE_SLOTS {
+                return None;
+            }
+            model.push_back(v);
+            Some(v)
+        },
+    )
+    .op(
+        "pop",
+        |_, _| true,
+        |q, tr, _| q.pop(tr).map_or(STMResult::Retry, STMResult::Ok),
+        |model, _| model.pop_front(),
+    )
+    .op(
+        "len",
+        |_, _| true,
+        |q, tr, _| {
+            q.len(tr)
+                .map_or(STMResult::Retry, |n| STMResult::Ok(Some(n)))
+        },
+        |model, _| Some(model.len() as u64),
+    )
+    .calls(24)
+    .cases(32)
+    .run();
+}
+
+// Keys are the argument modulo 4, so calls keep meeting the same keys.
+#[test]
+fn a_hash_map_matches_a_hash_map() {
+    Harness::new(
+        || {
+            let len = THashMap::size(MAP_BUCKETS);
+            (
+                STM::builder().capacity(len).build(),
+                THashMap::new(0, MAP_BUCKETS),
+            )
+        },
+        HashMap::new(),
+    )
+    // the value replaced
+    .op(
+        "insert",
+        |_, _| true,
+        |map, tr, v| {
+            map.insert(tr, v % 4, v)
+                .map_or(STMResult::Retry, |r| STMResult::Ok(r.unwrap()))
+        },
+        |model: &mut HashMap<u64, u64>, v| model.insert(v % 4, v),
+    )
+    .op(
+        "remove",
+        |_, _| true,
+        |map, tr, v| {
+            map.remove(tr, v % 4)
+                .map_or(STMResult::Retry, STMResult::Ok)
+        },
+        |model, v| model.remove(&(v % 4)),
+    )
+    .op(
+        "get",
+        |_, _| true,
+        |map, tr, v| map.get(tr, v % 4).map_or(STMResult::Retry, STMResult::Ok),
+        |model, v| model.get(&(v % 4)).copied(),
+    )
+    .cases(32)
+    .run();
+}
+
+#[test]
+fn concurrent_fetch_adds_replay_in_commit_order() {
+    counter(u64::MAX).threads(8).calls(64).cases(16).run();
+}
+
+#[test]
+fn a_structure_differing_from_its_model_is_reported() {
+    let add = Call { op: 0, arg: 1 };
+    let h = counter(2);
+    assert_eq!(h.check(&vec![vec![add; 2]]), Ok(()));
+
+    let m = h.check(&vec![vec![add; 2], vec![add; 2]]).unwrap_err();
+    // the add after the one that hit the cap sees 2 instead of 3
+    assert_eq!((m.position, m.op, m.arg), (3, "add", 1));
+    assert_eq!((m.expected.as_str(), m.actual.as_str()), ("3", "2"));
+}
+
+#[test]
+#[should_panic(expected = "returned")]
+fn run_panics_with_the_shrunk_schedule() {
+    counter(2).args(1..4).run();
+}
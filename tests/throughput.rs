@@ -0,0 +1,95 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::thread;
+
+use tl2::{STMResult, StripeValue, STM};
+
+fn commit(stm: &STM, n: u64) {
+    for i in 0..n {
+        stm.write_transaction(|tr| {
+            tr.store(0, i.to_stripe());
+            STMResult::Ok(())
+        })
+        .unwrap();
+    }
+}
+
+#[test]
+fn the_window_counts_commits_attempts_and_clock_movement() {
+    let stm = STM::builder().stats(true).build();
+    let before = stm.clock_sample();
+    commit(&stm, 10);
+    for _ in 0..5 {
+        stm.read_transaction(|tr| STMResult::Ok(tr.load(0)))
+            .unwrap();
+    }
+    // one commit that restarts once: two attempts
+    let runs = AtomicU32::new(0);
+    stm.write_transaction(|tr| {
+        tr.load(0);
+        if runs.fetch_add(1, Ordering::SeqCst) == 0 {
+            thread::scope(|s| {
+                s.spawn(|| commit(&stm, 1));
+            });
+        }
+        tr.store(8, 1u64.to_stripe());
+        STMResult::Ok(())
+    })
+    .unwrap();
+
+    let w = stm.throughput_window().unwrap();
+    assert_eq!(w.commits, 12);
+    assert_eq!(w.attempts, 18);
+    // the commit that failed validation took a version too
+    assert_eq!(w.clock_delta, 13);
+    assert_eq!(stm.global_clock(), 13);
+    assert!(w.commits_per_sec() > 0.0);
+    assert!(w.clock_per_sec() > w.commits_per_sec());
+    assert_eq!(stm.clock_sample().since(&before).delta, 13);
+
+    stm.reset_stats();
+    let w = stm.throughput_window().unwrap();
+    assert_eq!((w.commits, w.attempts, w.clock_delta), (0, 0, 0));
+}
+
+#[test]
+fn there_is_no_window_without_stats() {
+    assert_eq!(STM::new().throughput_window(), None);
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn old_buckets_roll_out_of_the_window() {
+    use std::time::Duration;
+    use tl2::WINDOW_SECS;
+
+    let stm = STM::builder().stats(true).build();
+    let secs = Duration::from_secs;
+
+    commit(&stm, 5); // second 0
+    stm.advance_throughput_window(secs(30));
+    commit(&stm, 3); // second 30
+    let w = stm.throughput_window().unwrap();
+    assert_eq!((w.commits, w.attempts, w.clock_delta), (8, 8, 8));
+    assert!(w.span >= secs(30) && w.span < secs(31), "{:?}", w.span);
+
+    // second 61: second 0 is out, second 30 still in
+    stm.advance_throughput_window(secs(31));
+    let w = stm.throughput_window().unwrap();
+    assert_eq!((w.commits, w.attempts), (3, 3));
+    // the clock stood at 5 when second 30 began
+    assert_eq!(w.clock_delta, 3);
+    assert!(w.span >= secs(WINDOW_SECS - 1) && w.span < secs(WINDOW_SECS));
+    assert!((w.commits_per_sec() - 3.0 / w.span.as_secs_f64()).abs() < 1e-9);
+
+    // second 90 reuses the ring slot of second 30, which starts empty
+    stm.advance_throughput_window(secs(29));
+    commit(&stm, 2);
+    let w = stm.throughput_window().unwrap();
+    assert_eq!((w.commits, w.attempts, w.clock_delta), (2, 2, 2));
+
+    // a quiet minute empties the window
+    stm.advance_throughput_window(secs(WINDOW_SECS));
+    let w = stm.throughput_window().unwrap();
+    assert_eq!((w.commits, w.attempts, w.clock_delta), (0, 0, 0));
+    assert_eq!(w.commits_per_sec(), 0.0);
+}
@@ -0,0 +1,52 @@
+use tl2::{RegionFull, STMResult, TLog, STM};
+
+fn append(stm: &STM, log: &TLog, v: u64) -> Result<usize, RegionFull> {
+    stm.write_transaction(|tr| match log.append(tr, v.to_le_bytes()) {
+        Some(r) => STMResult::Ok(r),
+        None => STMResult::Retry,
+    })
+    .unwrap()
+}
+
+fn len(stm: &STM, log: &TLog) -> usize {
+    stm.read_transaction(|tr| log.len(tr).map_or(STMResult::Retry, STMResult::Ok))
+        .unwrap()
+}
+
+#[test]
+fn append_to_a_full_log_fails_and_keeps_the_entries() {
+    let stm = STM::builder().capacity(64).build();
+    let log = TLog::new(0, 64);
+    assert_eq!(log.capacity(), 7);
+
+    for v in 0..7 {
+        assert_eq!(append(&stm, &log, v * 10), Ok(v as usize));
+    }
+    assert_eq!(append(&stm, &log, 70), Err(RegionFull));
+    assert_eq!(len(&stm, &log), 7);
+
+    let entries = stm
+        .read_transaction(|tr| {
+            let e: Option<Vec<_>> = (0..8).map(|i| log.get(tr, i)).collect();
+            e.map_or(STMResult::Retry, STMResult::Ok)
+        })
+        .unwrap();
+    let expected: Vec<_> = (0..7u64).map(|v| Some((v * 10).to_le_bytes())).collect();
+    assert_eq!(entries[..7], expected[..]);
+    assert_eq!(entries[7], None);
+}
+
+#[test]
+fn a_head_past_the_region_reads_as_full() {
+    let stm = STM::builder().capacity(64).build();
+    let log = TLog::new(0, 64);
+    for head in [8, u64::MAX] {
+        stm.write_transaction(|tr| {
+            tr.store(0, head.to_le_bytes());
+            STMResult::Ok(())
+        })
+        .unwrap();
+        assert_eq!(len(&stm, &log), 7);
+        assert_eq!(append(&stm, &log, 1), Err(RegionFull));
+    }
+}
@@ -0,0 +1,165 @@
+#![cfg(feature = "trace")]
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::thread;
+
+use tl2::{load, store, Replayer, STMResult, Trace, TraceError, TraceRecorder, STM};
+
+const PHILOSOPHERS: usize = 5;
+const MEALS: usize = 100;
+
+fn chopstick(n: usize) -> usize {
+    8 * n
+}
+
+fn meals_addr(n: usize) -> usize {
+    8 * (PHILOSOPHERS + n)
+}
+
+fn stm(recorder: &Arc<TraceRecorder>) -> STM {
+    STM::builder()
+        .capacity(16 * PHILOSOPHERS)
+        .conflict_heatmap(true)
+        .trace_recorder(recorder.clone())
+        .build()
+}
+
+// The philosophers of tests/philosophers.rs, yielding between looking at
+// the chopsticks and taking them so that they conflict even on one CPU.
+fn dine(stm: &STM) {
+    thread::scope(|s| {
+        for i in 0..PHILOSOPHERS {
+            s.spawn(move || {
+                let (left, right) = (chopstick(i), chopstick((i + 1) % PHILOSOPHERS));
+                for _ in 0..MEALS {
+                    while !stm
+                        .write_transaction_labeled("pickup", |tr| {
+                            let l = load!(tr, left);
+                            let r = load!(tr, right);
+                            thread::yield_now();
+                            if l[0] != 0 || r[0] != 0 {
+                                return STMResult::Ok(false);
+                            }
+                            store!(tr, left, [1; 8]);
+                            store!(tr, right, [1; 8]);
+                            STMResult::Ok(true)
+                        })
+                        .unwrap()
+                    {
+                        thread::yield_now();
+                    }
+                    stm.write_transaction_labeled("release", |tr| {
+                        let n = u64::from_le_bytes(load!(tr, meals_addr(i)));
+                        store!(tr, left, [0; 8]);
+                        store!(tr, right, [0; 8]);
+                        store!(tr, meals_addr(i), (n + 1).to_le_bytes());
+                        STMResult::Ok(())
+                    })
+                    .unwrap();
+                }
+            });
+        }
+    });
+}
+
+// How often each address was written by a committed transaction.
+fn writes(trace: &Trace) -> BTreeMap<usize, usize> {
+    let mut n = BTreeMap::new();
+    for r in trace.records().iter().filter(|r| r.committed) {
+        for a in r.writes.iter() {
+            *n.entry(*a).or_insert(0) += 1;
+        }
+    }
+    n
+}
+
+#[test]
+fn a_replayed_dinner_conflicts_where_the_recorded_one_did() {
+    let recorder = Arc::new(TraceRecorder::new(1));
+    let original = stm(&recorder);
+    dine(&original);
+    let trace = recorder.take();
+    assert_eq!(Trace::from_bytes(&trace.to_bytes()), Ok(trace.clone()));
+
+    let labels = trace.labels();
+    let released = trace
+        .records()
+        .iter()
+        .filter(|r| r.label.map(|l| labels[l].as_str()) == Some("release"))
+        .count();
+    assert_eq!(released, PHILOSOPHERS * MEALS);
+    assert!(trace.capacity() <= 16 * PHILOSOPHERS);
+
+    let again = Arc::new(TraceRecorder::new(1));
+    let replay = stm(&again);
+    Replayer::new(trace.clone()).run(&replay);
+    let replayed = again.take();
+    assert_eq!(replayed.records().len(), trace.records().len());
+    assert_eq!(writes(&replayed), writes(&trace));
+
+    // Both dinners restart on chopsticks and never on a meal counter,
+    // which only its own philosopher touches. The counts vary run to run
+    // with the scheduling.
+    let chopsticks = chopstick(0)..chopstick(PHILOSOPHERS);
+    for heat in [original.conflict_heatmap(), replay.conflict_heatmap()] {
+        let heat = heat.unwrap();
+        assert!(!heat.is_empty());
+        assert!(
+            heat.iter().all(|(a, _)| chopsticks.contains(a)),
+            "{:?}",
+            heat
+        );
+    }
+
+    // on one thread the same transactions have no one to conflict with
+    let serial = stm(&again);
+    Replayer::new(trace)
+        .threads(1)
+        .speed(f64::INFINITY)
+        .run(&serial);
+    assert_eq!(serial.conflict_heatmap(), Some(vec![]));
+}
+
+#[test]
+fn sampling_records_every_nth_transaction_of_a_thread() {
+    let recorder = Arc::new(TraceRecorder::new(10));
+    let stm = stm(&recorder);
+    // a fresh thread, so that its count starts at 0
+    thread::scope(|s| {
+        s.spawn(|| {
+            for i in 0..100u64 {
+                stm.write_transaction(|tr| {
+                    store!(tr, 8, i.to_le_bytes());
+                    STMResult::Ok(())
+                })
+                .unwrap();
+            }
+        });
+    });
+    let trace = recorder.take();
+    assert_eq!(trace.records().len(), 10);
+    assert!(trace
+        .records()
+        .iter()
+        .all(|r| r.writes == [8] && r.committed));
+    assert!(trace.labels().is_empty());
+}
+
+#[test]
+fn bytes_that_are_not_a_whole_trace_are_refused() {
+    let recorder = Arc::new(TraceRecorder::new(1));
+    let stm = stm(&recorder);
+    stm.write_transaction_labeled("one", |tr| {
+        store!(tr, 0, [1; 8]);
+        STMResult::Ok(())
+    })
+    .unwrap();
+    let bytes = recorder.take().to_bytes();
+
+    assert_eq!(Trace::from_bytes(b"not a trace"), Err(TraceError::Header));
+    assert_eq!(
+        Trace::from_bytes(&bytes[..bytes.len() - 1]),
+        Err(TraceError::Corrupt)
+    );
+}
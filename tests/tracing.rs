@@ -0,0 +1,145 @@
+#![cfg(feature = "tracing")]
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+use tl2::{STMResult, StripeValue, STM};
+
+// The fields of a span or event, as strings.
+#[derive(Debug, Default, Clone)]
+struct Fields(HashMap<String, String>);
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{:?}", value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+}
+
+#[derive(Default)]
+struct Log {
+    spans: HashMap<u64, (&'static str, Fields)>,
+    // the event's fields and the span it was emitted in
+    events: Vec<(Fields, Option<&'static str>)>,
+    stack: Vec<u64>,
+}
+
+// Records spans and events of the thread it is the default for.
+#[derive(Clone, Default)]
+struct Recorder {
+    next: Arc<AtomicU64>,
+    log: Arc<Mutex<Log>>,
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let id = self.next.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut fields = Fields::default();
+        span.record(&mut fields);
+        let mut log = self.log.lock().unwrap();
+        log.spans.insert(id, (span.metadata().name(), fields));
+        Id::from_u64(id)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let mut log = self.log.lock().unwrap();
+        values.record(&mut log.spans.get_mut(&span.into_u64()).unwrap().1);
+    }
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let mut log = self.log.lock().unwrap();
+        let span = log.stack.last().map(|id| log.spans[id].0);
+        log.events.push((fields, span));
+    }
+
+    fn enter(&self, span: &Id) {
+        self.log.lock().unwrap().stack.push(span.into_u64());
+    }
+
+    fn exit(&self, _: &Id) {
+        self.log.lock().unwrap().stack.pop();
+    }
+}
+
+fn write_other(stm: &STM) {
+    thread::scope(|s| {
+        s.spawn(|| {
+            stm.write_transaction(|tr| {
+                tr.store(8, 5u64.to_stripe());
+                STMResult::Ok(())
+            })
+        });
+    });
+}
+
+fn span<'a>(log: &'a Log, name: &str) -> &'a Fields {
+    let found: Vec<_> = log.spans.values().filter(|(n, _)| *n == name).collect();
+    assert_eq!(found.len(), 1, "{} spans named {}", found.len(), name);
+    &found[0].1
+}
+
+#[test]
+fn a_forced_conflict_shows_up_as_a_restart_event_in_the_span() {
+    let stm = STM::new();
+    let recorder = Recorder::default();
+    tracing::subscriber::with_default(recorder.clone(), || {
+        let first = Cell::new(true);
+        stm.write_transaction_labeled("move", |tr| {
+            let v = match tr.load(8) {
+                Some(v) => v,
+                None => return STMResult::Retry,
+            };
+            // another thread, not recorded, writes the stripe just read
+            if first.replace(false) {
+                write_other(&stm);
+            }
+            tr.store(16, v);
+            tr.store(24, v);
+            STMResult::Ok(())
+        })
+        .unwrap();
+        stm.read_transaction(|tr| STMResult::Ok(tr.load(16)))
+            .unwrap();
+    });
+
+    let log = recorder.log.lock().unwrap();
+    let w = &span(&log, "write_transaction").0;
+    assert_eq!(w["label"], "move");
+    assert_eq!(w["attempts"], "2");
+    assert_eq!(w["read_set"], "1");
+    assert_eq!(w["write_set"], "2");
+    let r = &span(&log, "read_transaction").0;
+    assert_eq!(r["attempts"], "1");
+
+    let restarts: Vec<_> = log
+        .events
+        .iter()
+        .filter(|(f, _)| f.0["message"] == "transaction restarted")
+        .collect();
+    assert_eq!(restarts.len(), 1, "{:?}", log.events);
+    let (f, in_span) = restarts[0];
+    assert_eq!(*in_span, Some("write_transaction"));
+    assert_eq!(f.0["cause"], "Validation");
+    assert_eq!(f.0["addr"], "Some(8)");
+    assert_eq!(f.0["attempt"], "1");
+}
@@ -0,0 +1,73 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+
+use tl2::{STMResult, StripeValue, STM};
+
+// A job moves Idle -> Running -> Done and never back.
+const IDLE: u64 = 0;
+const RUNNING: u64 = 1;
+const DONE: u64 = 2;
+const JOBS: usize = 64;
+const THREADS: usize = 8;
+
+fn transition(stm: &STM, job: usize, from: u64, to: u64) -> bool {
+    stm.write_transaction(|tr| {
+        tr.transition(job * 8, from.to_stripe(), to.to_stripe())
+            .map_or(STMResult::Retry, STMResult::Ok)
+    })
+    .unwrap()
+}
+
+fn state(stm: &STM, job: usize) -> u64 {
+    stm.read_transaction(|tr| STMResult::Ok(u64::from_stripe(tl2::load!(tr, job * 8))))
+        .unwrap()
+}
+
+#[test]
+fn racing_workers_claim_and_finish_each_job_once() {
+    let stm = STM::new();
+    let claims: Vec<AtomicU64> = (0..JOBS).map(|_| AtomicU64::new(0)).collect();
+
+    thread::scope(|s| {
+        for t in 0..THREADS {
+            let (stm, claims) = (&stm, &claims);
+            s.spawn(move || {
+                for n in 0..JOBS {
+                    let job = (n + t * 5) % JOBS;
+                    if transition(stm, job, IDLE, RUNNING) {
+                        claims[job].fetch_add(1, Ordering::SeqCst);
+                        // only the claimer can finish it, and only once
+                        assert!(transition(stm, job, RUNNING, DONE));
+                        assert!(!transition(stm, job, RUNNING, DONE));
+                    }
+                    // a job seen by the others is never back to idle
+                    assert!(!transition(stm, job, IDLE, RUNNING));
+                }
+            });
+        }
+    });
+
+    for (job, c) in claims.iter().enumerate() {
+        assert_eq!(c.load(Ordering::SeqCst), 1, "job {}", job);
+        assert_eq!(state(&stm, job), DONE);
+    }
+}
+
+#[test]
+fn an_invalid_transition_leaves_the_stripe_alone() {
+    let stm = STM::new();
+    assert!(!transition(&stm, 0, RUNNING, DONE));
+    assert_eq!(state(&stm, 0), IDLE);
+
+    // the failed move stores nothing, so the stripe keeps its version
+    let version = || {
+        stm.read_transaction_versioned(|tr| STMResult::Ok(tl2::load!(tr, 0)))
+            .unwrap()
+            .1
+    };
+    assert!(transition(&stm, 0, IDLE, RUNNING));
+    let before = version();
+    assert!(!transition(&stm, 0, IDLE, DONE));
+    assert_eq!(version(), before);
+    assert_eq!(state(&stm, 0), RUNNING);
+}
@@ -0,0 +1,44 @@
+use tl2::{RegionFull, STMResult, TSet, STM};
+
+fn insert(stm: &STM, set: &TSet<u64>, v: u64) -> Result<bool, RegionFull> {
+    stm.write_transaction(|tr| match set.insert(tr, v) {
+        Some(r) => STMResult::Ok(r),
+        None => STMResult::Retry,
+    })
+    .unwrap()
+}
+
+#[test]
+fn insert_into_a_full_region_fails_and_keeps_the_set() {
+    // a header and 8 slots, the smallest set
+    let size = 16 + 8 * 16;
+    let stm = STM::builder().capacity(size).build();
+    let set = TSet::<u64>::new(0, size);
+
+    for v in 0..8 {
+        assert_eq!(insert(&stm, &set, v), Ok(true));
+    }
+    assert_eq!(insert(&stm, &set, 3), Ok(false));
+    assert_eq!(insert(&stm, &set, 8), Err(RegionFull));
+
+    let (len, members) = stm
+        .read_transaction(|tr| {
+            let len = set.len(tr);
+            let members: Option<Vec<bool>> = (0..9).map(|v| set.contains(tr, &v)).collect();
+            match (len, members) {
+                (Some(len), Some(members)) => STMResult::Ok((len, members)),
+                _ => STMResult::Retry,
+            }
+        })
+        .unwrap();
+    assert_eq!(len, 8);
+    assert_eq!(
+        members,
+        [true, true, true, true, true, true, true, true, false]
+    );
+
+    // room made by a removal is used again
+    stm.write_transaction(|tr| set.remove(tr, &0).map_or(STMResult::Retry, STMResult::Ok))
+        .unwrap();
+    assert_eq!(insert(&stm, &set, 8), Ok(true));
+}
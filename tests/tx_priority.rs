@@ -0,0 +1,222 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Barrier;
+use std::thread;
+use std::time::Duration;
+
+use tl2::{LabelStats, STMResult, StripeValue, TxPriority, STM};
+
+fn write(stm: &STM, addr: usize, v: u64) {
+    stm.write_transaction(|tr| {
+        tr.store(addr, v.to_stripe());
+        STMResult::Ok(())
+    })
+    .unwrap();
+}
+
+fn read(stm: &STM, addr: usize) -> u64 {
+    stm.read_transaction(|tr| STMResult::Ok(u64::from_stripe(tl2::load!(tr, addr))))
+        .unwrap()
+}
+
+// Increment stripe 0 at `priority`, with stripe 0 written from another
+// thread during the first run, so that it restarts once.
+fn bump_with_restart(stm: &STM, priority: TxPriority) {
+    let runs = AtomicU32::new(0);
+    stm.write_transaction_with_priority(priority, |tr| {
+        let n = u64::from_stripe(tl2::load!(tr, 0));
+        if runs.fetch_add(1, Ordering::SeqCst) == 0 {
+            thread::scope(|s| {
+                s.spawn(|| write(stm, 0, n));
+            });
+        }
+        tr.store(0, (n + 1).to_stripe());
+        STMResult::Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn stats_are_kept_per_hint() {
+    let stm = STM::builder().stats(true).build();
+    bump_with_restart(&stm, TxPriority::High);
+    stm.write_transaction_with_priority(TxPriority::Low, |_| STMResult::<()>::Abort);
+    stm.read_transaction_with_priority(TxPriority::Low, |tr| STMResult::Ok(tl2::load!(tr, 0)))
+        .unwrap();
+    stm.read_transaction(|tr| STMResult::Ok(tl2::load!(tr, 0)))
+        .unwrap();
+
+    let by = stm.stats_by_priority().unwrap();
+    assert_eq!(
+        by,
+        [
+            (
+                TxPriority::Low,
+                LabelStats {
+                    reads: 1,
+                    aborts: 1,
+                    ..Default::default()
+                }
+            ),
+            (
+                TxPriority::Normal,
+                LabelStats {
+                    // the write made during the high one, then the read
+                    commits: 1,
+                    reads: 1,
+                    ..Default::default()
+                }
+            ),
+            (
+                TxPriority::High,
+                LabelStats {
+                    commits: 1,
+                    restarts: 1,
+                    ..Default::default()
+                }
+            ),
+        ]
+    );
+
+    stm.reset_stats();
+    assert!(stm
+        .stats_by_priority()
+        .unwrap()
+        .iter()
+        .all(|(_, s)| *s == LabelStats::default()));
+    assert_eq!(STM::new().stats_by_priority(), None);
+}
+
+#[test]
+fn a_low_writer_waits_for_a_restarting_high_one() {
+    let stm = STM::new();
+    let high_runs = AtomicU32::new(0);
+    let high_done = AtomicBool::new(false);
+    let low_saw_done = AtomicBool::new(false);
+
+    thread::scope(|s| {
+        s.spawn(|| {
+            while high_runs.load(Ordering::SeqCst) < 2 {
+                thread::yield_now();
+            }
+            // the high one is on its second run now
+            stm.write_transaction_with_priority(TxPriority::Low, |tr| {
+                low_saw_done.store(high_done.load(Ordering::SeqCst), Ordering::SeqCst);
+                tr.store(8, 1u64.to_stripe());
+                STMResult::Ok(())
+            })
+            .unwrap();
+        });
+
+        stm.write_transaction_with_priority(TxPriority::High, |tr| {
+            let n = u64::from_stripe(tl2::load!(tr, 0));
+            match high_runs.fetch_add(1, Ordering::SeqCst) {
+                0 => thread::scope(|s| {
+                    s.spawn(|| write(&stm, 0, n + 1));
+                }),
+                // give the low one time to start, were it not held back
+                _ => thread::sleep(Duration::from_millis(50)),
+            }
+            high_done.store(true, Ordering::SeqCst);
+            tr.store(0, (n + 1).to_stripe());
+            STMResult::Ok(())
+        })
+        .unwrap();
+    });
+
+    assert_eq!(high_runs.into_inner(), 2);
+    assert!(low_saw_done.into_inner());
+    assert_eq!((read(&stm, 0), read(&stm, 8)), (2, 1));
+}
+
+const LOW: u32 = 3;
+
+// Runs of each of 100 transactions of a critical writer reading four
+// stripes that `LOW` other writers keep incrementing, sorted. A critical
+// transaction gives up on its run past `CAP`.
+fn critical_runs(critical: TxPriority, others: TxPriority) -> Vec<u32> {
+    const CAP: u32 = 30;
+    let stm = STM::new();
+    let done = AtomicBool::new(false);
+    let mut all = Vec::new();
+
+    thread::scope(|s| {
+        for t in 0..LOW {
+            let (stm, done) = (&stm, &done);
+            s.spawn(move || {
+                let mut i = t as usize;
+                while !done.load(Ordering::SeqCst) {
+                    let addr = i % 4 * 8;
+                    stm.write_transaction_with_priority(others, |tr| {
+                        let n = u64::from_stripe(tl2::load!(tr, addr));
+                        thread::yield_now();
+                        tr.store(addr, (n + 1).to_stripe());
+                        STMResult::Ok(())
+                    })
+                    .unwrap();
+                    i += 1;
+                }
+            });
+        }
+
+        for _ in 0..100 {
+            let runs = AtomicU32::new(0);
+            stm.write_transaction_with_priority(critical, |tr| {
+                if runs.fetch_add(1, Ordering::SeqCst) == CAP {
+                    return STMResult::Abort;
+                }
+                let mut sum = 0;
+                for addr in (0..32).step_by(8) {
+                    sum += u64::from_stripe(tl2::load!(tr, addr));
+                    thread::yield_now();
+                }
+                tr.store(64, sum.to_stripe());
+                STMResult::Ok(())
+            });
+            all.push(runs.into_inner());
+        }
+        done.store(true, Ordering::SeqCst);
+    });
+    all.sort_unstable();
+    all
+}
+
+fn p99(runs: &[u32]) -> u32 {
+    runs[runs.len() * 99 / 100]
+}
+
+#[test]
+fn a_high_writer_among_low_ones_beats_the_same_writer_without_hints() {
+    let baseline = critical_runs(TxPriority::Normal, TxPriority::Normal);
+    let hinted = critical_runs(TxPriority::High, TxPriority::Low);
+
+    assert!(
+        p99(&hinted) < p99(&baseline),
+        "p99 runs {} hinted, {} without hints",
+        p99(&hinted),
+        p99(&baseline)
+    );
+    // once it restarted, only the low attempts already running can beat it
+    let most = *hinted.last().unwrap();
+    assert!(most <= 2 + LOW, "{} runs", most);
+}
+
+#[test]
+fn the_watchdog_reports_the_hint() {
+    let stm = STM::builder().watchdog(true).build();
+    let (started, finish) = (Barrier::new(2), Barrier::new(2));
+    thread::scope(|s| {
+        s.spawn(|| {
+            stm.write_transaction_with_priority(TxPriority::High, |_| {
+                started.wait();
+                finish.wait();
+                STMResult::Ok(())
+            })
+        });
+        started.wait();
+        let stalled = stm.check_stalled(Duration::ZERO, 0);
+        finish.wait();
+        assert_eq!(stalled.len(), 1);
+        assert_eq!(stalled[0].priority, TxPriority::High);
+        assert!(!stalled[0].read_only);
+    });
+}
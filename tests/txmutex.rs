@@ -0,0 +1,229 @@
+use std::sync::{Arc, Barrier, Mutex};
+use std::thread;
+
+use tl2::{TxError, TxMutex, STM};
+
+const SLOTS: usize = 4;
+
+// A small module written against a mutex, and its port to `TxMutex`.
+trait Ledger: Send + Sync {
+    fn deposit(&self, slot: usize, amount: i64);
+    // Move `amount` between slots if `from` covers it.
+    fn transfer(&self, from: usize, to: usize, amount: i64) -> bool;
+    fn balances(&self) -> [i64; SLOTS];
+}
+
+struct MutexLedger(Mutex<[i64; SLOTS]>);
+
+impl Ledger for MutexLedger {
+    fn deposit(&self, slot: usize, amount: i64) {
+        self.0.lock().unwrap()[slot] += amount;
+    }
+
+    fn transfer(&self, from: usize, to: usize, amount: i64) -> bool {
+        let mut b = self.0.lock().unwrap();
+        if b[from] < amount {
+            return false;
+        }
+        b[from] -= amount;
+        b[to] += amount;
+        true
+    }
+
+    fn balances(&self) -> [i64; SLOTS] {
+        *self.0.lock().unwrap()
+    }
+}
+
+struct TxLedger(TxMutex<[i64; SLOTS]>);
+
+impl Ledger for TxLedger {
+    fn deposit(&self, slot: usize, amount: i64) {
+        self.0.update(|b| b[slot] += amount).unwrap();
+    }
+
+    fn transfer(&self, from: usize, to: usize, amount: i64) -> bool {
+        self.0
+            .update(|b| {
+                if b[from] < amount {
+                    return false;
+                }
+                b[from] -= amount;
+                b[to] += amount;
+                true
+            })
+            .unwrap()
+    }
+
+    fn balances(&self) -> [i64; SLOTS] {
+        *self.0.lock().unwrap()
+    }
+}
+
+// Deposits, then transfers in a ring, from several threads at once. The
+// transfers never fail as every slot holds enough, so the outcome does not
+// depend on the interleaving.
+fn run(ledger: Arc<dyn Ledger>) -> ([i64; SLOTS], usize) {
+    let threads: Vec<_> = (0..4)
+        .map(|t| {
+            let ledger = ledger.clone();
+            thread::spawn(move || {
+                let mut moved = 0;
+                for n in 0..200 {
+                    ledger.deposit((t + n) % SLOTS, 5);
+                    moved += ledger.transfer(t, (t + 1) % SLOTS, 1) as usize;
+                }
+                moved
+            })
+        })
+        .collect();
+    let moved = threads.into_iter().map(|t| t.join().unwrap()).sum();
+    (ledger.balances(), moved)
+}
+
+#[test]
+fn behaves_like_a_mutex_under_contention() {
+    let expected = run(Arc::new(MutexLedger(Mutex::new([100; SLOTS]))));
+
+    let stm = Arc::new(STM::new());
+    let ledger = TxLedger(TxMutex::new(stm, 0));
+    let mut g = ledger.0.lock().unwrap();
+    *g = [100; SLOTS];
+    g.commit().unwrap();
+
+    assert_eq!(run(Arc::new(ledger)), expected);
+    assert_eq!(expected.1, 800);
+}
+
+// A module setting a record whole under a mutex, and its port to
+// `TxMutex` guards: the last guard dropped wins, as with the mutex.
+trait Board: Send + Sync {
+    fn set(&self, t: u64, n: u64);
+    fn get(&self) -> [u64; 4];
+}
+
+struct MutexBoard(Mutex<[u64; 4]>);
+
+impl Board for MutexBoard {
+    fn set(&self, t: u64, n: u64) {
+        let mut b = self.0.lock().unwrap();
+        *b = [t, n, t, n];
+    }
+
+    fn get(&self) -> [u64; 4] {
+        *self.0.lock().unwrap()
+    }
+}
+
+struct TxBoard(TxMutex<[u64; 4]>);
+
+impl Board for TxBoard {
+    fn set(&self, t: u64, n: u64) {
+        let mut b = self.0.lock().unwrap();
+        *b = [t, n, t, n];
+    }
+
+    fn get(&self) -> [u64; 4] {
+        *self.0.lock().unwrap()
+    }
+}
+
+// Every thread sets the board, checking it whole in between; the last set
+// of all is the last of some thread. No set leaves the board as it was.
+fn post(board: Arc<dyn Board>) -> [u64; 4] {
+    let threads: Vec<_> = (1..=4)
+        .map(|t| {
+            let board = board.clone();
+            thread::spawn(move || {
+                for n in 0..200 {
+                    let [a, b, c, d] = board.get();
+                    assert_eq!((a, b), (c, d), "torn record");
+                    board.set(t, n);
+                }
+            })
+        })
+        .collect();
+    for t in threads {
+        t.join().unwrap();
+    }
+    board.get()
+}
+
+#[test]
+fn lock_guards_commit_like_a_mutex_under_contention() {
+    let [t, n, ..] = post(Arc::new(MutexBoard(Mutex::new([0; 4]))));
+    assert!((1..=4).contains(&t) && n == 199);
+
+    let stm = Arc::new(STM::builder().stats(true).build());
+    let board = post(Arc::new(TxBoard(TxMutex::new(stm.clone(), 0))));
+    let [t, n, ..] = board;
+    assert_eq!(board, [t, n, t, n]);
+    assert!((1..=4).contains(&t) && n == 199);
+    // every guard dropped committed, none panicked
+    assert_eq!(stm.stats().unwrap().commits, 800);
+}
+
+#[test]
+fn a_guard_overtaken_by_another_commit_overwrites_it() {
+    let m = TxMutex::<u64>::new(Arc::new(STM::new()), 0);
+    let mut g1 = m.lock().unwrap();
+    let mut g2 = m.lock().unwrap();
+    *g2 += 1;
+    g2.commit().unwrap();
+    *g1 += 10;
+    assert_eq!(g1.commit(), Ok(()));
+    assert_eq!(*m.lock().unwrap(), 10);
+
+    // and so does one dropped
+    let mut g1 = m.lock().unwrap();
+    m.update(|v| *v += 1).unwrap();
+    *g1 += 5;
+    drop(g1);
+    assert_eq!(*m.lock().unwrap(), 15);
+}
+
+#[test]
+#[should_panic(expected = "TxMutexGuard dropped without committing")]
+fn dropping_a_guard_on_a_poisoned_stm_panics() {
+    let stm = Arc::new(STM::new());
+    let m = TxMutex::<u64>::new(stm.clone(), 0);
+    let mut g = m.lock().unwrap();
+    stm.poison();
+    *g += 1;
+}
+
+#[test]
+fn guards_taken_in_opposite_orders_do_not_deadlock() {
+    let stm = Arc::new(STM::new());
+    let m = Arc::new([TxMutex::<u64>::new(stm.clone(), 0), TxMutex::new(stm, 8)]);
+    let both = Arc::new(Barrier::new(2));
+
+    let threads: Vec<_> = (0..2)
+        .map(|t| {
+            let (m, both) = (m.clone(), both.clone());
+            thread::spawn(move || {
+                let mut first = m[t].lock().unwrap();
+                both.wait();
+                let second = m[1 - t].lock().unwrap();
+                both.wait();
+                *first += *second + 1;
+                drop(second);
+                first.commit()
+            })
+        })
+        .collect();
+    for t in threads {
+        assert_eq!(t.join().unwrap(), Ok(()));
+    }
+    assert_eq!(*m[0].lock().unwrap(), 1);
+    assert_eq!(*m[1].lock().unwrap(), 1);
+}
+
+#[test]
+fn lock_on_a_poisoned_stm_fails() {
+    let stm = Arc::new(STM::new());
+    let m = TxMutex::<u64>::new(stm.clone(), 0);
+    stm.poison();
+    assert!(matches!(m.lock(), Err(TxError::Poisoned)));
+    assert_eq!(m.update(|v| *v += 1), Err(TxError::Poisoned));
+}
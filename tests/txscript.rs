@@ -0,0 +1,111 @@
+#![cfg(feature = "testing")]
+
+use std::fs;
+use std::path::Path;
+
+use tl2::{TxScript, TxStep};
+
+// xorshift64*
+fn rng(x: &mut u64) -> u64 {
+    *x ^= *x >> 12;
+    *x ^= *x << 25;
+    *x ^= *x >> 27;
+    x.wrapping_mul(0x2545_f491_4f6c_dd1d)
+}
+
+#[test]
+fn every_seed_in_the_fuzz_corpus_runs_clean() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus/tx_script");
+    let mut seeds = 0;
+    for entry in fs::read_dir(&dir).unwrap() {
+        let path = entry.unwrap().path();
+        let script = TxScript::decode(&fs::read(&path).unwrap());
+        assert!(!script.steps().is_empty(), "{}", path.display());
+        // re-encoding is stable, so a seed can be edited as steps
+        assert_eq!(TxScript::decode(&script.encode()), script);
+        script.run();
+        seeds += 1;
+    }
+    assert!(seeds >= 10, "only {} seeds in {}", seeds, dir.display());
+}
+
+#[test]
+fn random_bytes_run_clean() {
+    let mut x = 0x9e37_79b9_7f4a_7c15;
+    for _ in 0..2_000 {
+        let len = rng(&mut x) as usize % 96;
+        let data: Vec<u8> = (0..len).map(|_| rng(&mut x) as u8).collect();
+        TxScript::decode(&data).run();
+    }
+}
+
+#[test]
+fn a_lost_update_is_caught_at_commit() {
+    use TxStep::*;
+    // both increment stripe 0 from the same read; only the first commits
+    TxScript::new(vec![
+        Begin {
+            thread: 0,
+            write: true,
+        },
+        Begin {
+            thread: 1,
+            write: true,
+        },
+        Load {
+            thread: 0,
+            stripe: 0,
+        },
+        Load {
+            thread: 1,
+            stripe: 0,
+        },
+        Store {
+            thread: 0,
+            stripe: 0,
+            val: 1,
+        },
+        Store {
+            thread: 1,
+            stripe: 0,
+            val: 1,
+        },
+        Commit { thread: 0 },
+        Commit { thread: 1 },
+        // the retry sees the first commit
+        Begin {
+            thread: 1,
+            write: true,
+        },
+        Load {
+            thread: 1,
+            stripe: 0,
+        },
+        Store {
+            thread: 1,
+            stripe: 0,
+            val: 2,
+        },
+        Commit { thread: 1 },
+    ])
+    .run();
+}
+
+#[test]
+fn decoding_stops_at_a_truncated_step_and_the_step_limit() {
+    // a load whose stripe byte is missing
+    assert_eq!(
+        TxScript::decode(&[1, 2]).steps(),
+        [TxStep::Begin {
+            thread: 0,
+            write: true
+        }]
+    );
+    assert_eq!(TxScript::decode(&[4; 1000]).steps().len(), 256);
+    assert_eq!(
+        TxScript::new(vec![TxStep::Abort { thread: 2 }; 300])
+            .steps()
+            .len(),
+        256
+    );
+}
@@ -0,0 +1,105 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+use tl2::{STMResult, VClock, STM};
+
+const NODES: usize = 4;
+const EACH: u64 = 300;
+
+fn snapshot(stm: &STM, clock: &VClock) -> Vec<u64> {
+    stm.read_transaction(|tr| clock.snapshot(tr).map_or(STMResult::Retry, STMResult::Ok))
+        .unwrap()
+}
+
+fn merge(stm: &STM, clock: &VClock, other: &[u64]) {
+    stm.write_transaction(|tr| {
+        clock
+            .merge(tr, other)
+            .map_or(STMResult::Retry, STMResult::Ok)
+    })
+    .unwrap();
+}
+
+fn versions(stm: &STM, clock: &VClock) -> Vec<(usize, u64)> {
+    stm.read_transaction_versioned(|tr| clock.snapshot(tr).map_or(STMResult::Retry, STMResult::Ok))
+        .unwrap()
+        .1
+}
+
+#[test]
+fn concurrent_increments_merge_into_the_sum_of_ticks() {
+    let stm = STM::new();
+    let local = VClock::new(0, NODES);
+    let merged = VClock::new(NODES * 8, NODES);
+
+    // two threads tick each node, and every tick is merged into a second
+    // clock in the same transaction
+    thread::scope(|s| {
+        for t in 0..NODES * 2 {
+            let (stm, local, merged) = (&stm, &local, &merged);
+            s.spawn(move || {
+                let node = t % NODES;
+                for _ in 0..EACH {
+                    stm.write_transaction(|tr| {
+                        let Some(_) = local.increment(tr, node) else {
+                            return STMResult::Retry;
+                        };
+                        match local.snapshot(tr) {
+                            Some(now) => merged
+                                .merge(tr, &now)
+                                .map_or(STMResult::Retry, STMResult::Ok),
+                            None => STMResult::Retry,
+                        }
+                    })
+                    .unwrap();
+                }
+            });
+        }
+    });
+
+    assert_eq!(snapshot(&stm, &local), [2 * EACH; NODES]);
+    assert_eq!(snapshot(&stm, &merged), [2 * EACH; NODES]);
+}
+
+#[test]
+fn merge_takes_the_element_wise_max() {
+    let stm = STM::new();
+    let clock = VClock::new(0, 3);
+    merge(&stm, &clock, &[3, 0, 5]);
+    merge(&stm, &clock, &[1, 4, 5]);
+    assert_eq!(snapshot(&stm, &clock), [3, 4, 5]);
+
+    // a merge with nothing newer stores nothing
+    let before = versions(&stm, &clock);
+    merge(&stm, &clock, &[0, 4, 2]);
+    assert_eq!(versions(&stm, &clock), before);
+    assert_eq!(snapshot(&stm, &clock), [3, 4, 5]);
+}
+
+#[test]
+fn readers_never_see_half_a_merge() {
+    let stm = STM::new();
+    let clock = VClock::new(0, NODES);
+    let done = AtomicBool::new(false);
+
+    thread::scope(|s| {
+        s.spawn(|| {
+            for k in 1..=EACH {
+                merge(&stm, &clock, &[k; NODES]);
+            }
+            done.store(true, Ordering::SeqCst);
+        });
+        for _ in 0..2 {
+            s.spawn(|| {
+                let mut last = 0;
+                while !done.load(Ordering::SeqCst) {
+                    let seen = snapshot(&stm, &clock);
+                    assert!(seen.iter().all(|&v| v == seen[0]), "{:?}", seen);
+                    assert!(seen[0] >= last);
+                    last = seen[0];
+                }
+            });
+        }
+    });
+    assert_eq!(snapshot(&stm, &clock), [EACH; NODES]);
+}
@@ -0,0 +1,72 @@
+use tl2::{STMResult, StripeValue, STM};
+
+const STRIPE_SIZE: usize = 8;
+
+fn write(stm: &STM, addr: usize, v: u64) {
+    stm.write_transaction(|tr| {
+        tr.store(addr, v.to_stripe());
+        STMResult::Ok(())
+    })
+    .unwrap();
+}
+
+// Sum stripes 2, 0 and 1, loading stripe 0 twice, with the versions seen.
+fn cached_sum(stm: &STM) -> (u64, Vec<(usize, u64)>) {
+    stm.read_transaction_versioned(|tr| {
+        let mut sum = 0;
+        for i in [2, 0, 1, 0] {
+            sum += u64::from_stripe(tl2::load!(tr, i * STRIPE_SIZE));
+        }
+        STMResult::Ok(sum)
+    })
+    .unwrap()
+}
+
+// Addresses whose version differs between two runs.
+fn changed(old: &[(usize, u64)], new: &[(usize, u64)]) -> Vec<usize> {
+    assert_eq!(old.len(), new.len());
+    old.iter()
+        .zip(new)
+        .filter(|(o, n)| {
+            assert_eq!(o.0, n.0);
+            o.1 != n.1
+        })
+        .map(|(o, _)| o.0)
+        .collect()
+}
+
+#[test]
+fn a_write_shows_up_in_the_versions_of_the_next_read() {
+    let stm = STM::new();
+    write(&stm, 0, 1); // version 1
+    write(&stm, STRIPE_SIZE, 2); // version 2
+    write(&stm, 2 * STRIPE_SIZE, 3); // version 3
+
+    let (sum, seen) = cached_sum(&stm);
+    assert_eq!(sum, 1 + 1 + 2 + 3);
+    // each stripe once, sorted by address, at its commit version
+    assert_eq!(seen, [(0, 1), (STRIPE_SIZE, 2), (2 * STRIPE_SIZE, 3)]);
+
+    // a stripe the body did not read changes nothing
+    write(&stm, 3 * STRIPE_SIZE, 9);
+    let (_, again) = cached_sum(&stm);
+    assert!(changed(&seen, &again).is_empty());
+
+    // rewriting the same value still moves the version
+    write(&stm, STRIPE_SIZE, 2);
+    let (sum, now) = cached_sum(&stm);
+    assert_eq!(sum, 7);
+    assert_eq!(changed(&seen, &now), [STRIPE_SIZE]);
+    assert_eq!(now[1], (STRIPE_SIZE, 5));
+}
+
+#[test]
+fn a_body_that_reads_nothing_observes_nothing() {
+    let stm = STM::new();
+    write(&stm, 0, 1);
+    let (v, seen) = stm
+        .read_transaction_versioned(|_| STMResult::Ok(7))
+        .unwrap();
+    assert_eq!(v, 7);
+    assert!(seen.is_empty());
+}
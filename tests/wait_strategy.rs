@@ -0,0 +1,135 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use tl2::{ParkWait, STMResult, SpinWait, StripeValue, WaitStrategy, WaitToken, WriteTrans, STM};
+
+// A bounded FIFO queue of u64: head and tail counters, then SLOTS slots.
+// Pushing to a full queue or popping an empty one retries, so with few
+// slots producers and consumers keep blocking on each other.
+const HEAD: usize = 0;
+const TAIL: usize = 8;
+const SLOTS: u64 = 2;
+const PRODUCERS: u64 = 3;
+const CONSUMERS: u64 = 3;
+const EACH: u64 = 100;
+
+fn slot(i: u64) -> usize {
+    16 + (i % SLOTS) as usize * 8
+}
+
+fn push(tr: &mut WriteTrans, v: u64) -> STMResult<()> {
+    let head = u64::from_stripe(tl2::load!(tr, HEAD));
+    let tail = u64::from_stripe(tl2::load!(tr, TAIL));
+    if tail - head == SLOTS {
+        return STMResult::Retry;
+    }
+    tr.store(slot(tail), v.to_stripe());
+    tr.store(TAIL, (tail + 1).to_stripe());
+    STMResult::Ok(())
+}
+
+fn pop(tr: &mut WriteTrans) -> STMResult<u64> {
+    let head = u64::from_stripe(tl2::load!(tr, HEAD));
+    let tail = u64::from_stripe(tl2::load!(tr, TAIL));
+    if head == tail {
+        return STMResult::Retry;
+    }
+    let v = u64::from_stripe(tl2::load!(tr, slot(head)));
+    tr.store(HEAD, (head + 1).to_stripe());
+    STMResult::Ok(v)
+}
+
+// Run the queue on another thread and fail if it does not finish in
+// time: with a strategy that never wakes on its own, a lost wakeup
+// hangs it.
+fn run_queue(strategy: Arc<dyn WaitStrategy>) {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let stm = STM::builder().wait_strategy(strategy).build();
+        let mut got: Vec<u64> = thread::scope(|s| {
+            for p in 0..PRODUCERS {
+                let stm = &stm;
+                s.spawn(move || {
+                    for i in 0..EACH {
+                        stm.write_transaction_blocking(|tr| push(tr, p * EACH + i))
+                            .unwrap();
+                    }
+                });
+            }
+            let consumers: Vec<_> = (0..CONSUMERS)
+                .map(|_| {
+                    s.spawn(|| {
+                        (0..PRODUCERS * EACH / CONSUMERS)
+                            .map(|_| stm.write_transaction_blocking(pop).unwrap())
+                            .collect::<Vec<u64>>()
+                    })
+                })
+                .collect();
+            consumers
+                .into_iter()
+                .flat_map(|c| c.join().unwrap())
+                .collect()
+        });
+        got.sort_unstable();
+        assert_eq!(got, (0..PRODUCERS * EACH).collect::<Vec<_>>());
+        assert_eq!(stm.waiters(), 0);
+        tx.send(()).unwrap();
+    });
+    rx.recv_timeout(Duration::from_secs(30))
+        .expect("the queue stalled or failed");
+}
+
+// Blocks on a condvar until notified, with no timeout to hide a missed
+// notify; counts the calls.
+#[derive(Default)]
+struct CondvarWait {
+    lock: Mutex<()>,
+    moved: Condvar,
+    waits: AtomicU64,
+    notifies: AtomicU64,
+}
+
+impl WaitStrategy for CondvarWait {
+    fn wait(&self, token: WaitToken<'_>) {
+        self.waits.fetch_add(1, Ordering::SeqCst);
+        let mut guard = self.lock.lock().unwrap();
+        while !token.is_done() {
+            guard = self.moved.wait(guard).unwrap();
+        }
+    }
+
+    fn notify(&self, _: WaitToken<'_>) {
+        self.notifies.fetch_add(1, Ordering::SeqCst);
+        // taken so a waiter between its check and its wait is not missed
+        let _guard = self.lock.lock().unwrap();
+        self.moved.notify_all();
+    }
+}
+
+#[test]
+fn no_wakeup_is_lost_without_a_timeout_to_fall_back_on() {
+    let strategy = Arc::new(CondvarWait::default());
+    run_queue(strategy.clone());
+    assert!(strategy.waits.load(Ordering::SeqCst) > 0, "nobody waited");
+    assert!(strategy.notifies.load(Ordering::SeqCst) > 0);
+}
+
+#[test]
+fn the_queue_runs_under_spinning() {
+    run_queue(Arc::new(SpinWait));
+}
+
+#[test]
+fn the_queue_runs_under_parking() {
+    run_queue(Arc::new(ParkWait::new(0)));
+    run_queue(Arc::new(ParkWait::default()));
+}
+
+#[cfg(all(target_os = "linux", feature = "futex"))]
+#[test]
+fn the_queue_runs_under_a_futex() {
+    run_queue(Arc::new(tl2::FutexWait));
+}
@@ -0,0 +1,150 @@
+use std::sync::{Arc, Barrier, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use tl2::{STMResult, StalledTx, TxObserver, TxPriority, STM};
+
+#[derive(Default)]
+struct Stalls(Mutex<Vec<StalledTx>>);
+
+impl TxObserver for Stalls {
+    fn on_stall(&self, tx: &StalledTx) {
+        self.0.lock().unwrap().push(*tx);
+    }
+}
+
+#[test]
+fn a_transaction_past_the_age_limit_is_reported() {
+    let stm = STM::builder().watchdog(true).build();
+    let stalls = Arc::new(Stalls::default());
+    stm.set_observer(stalls.clone());
+    let (started, finish) = (Barrier::new(2), Barrier::new(2));
+
+    thread::scope(|s| {
+        s.spawn(|| {
+            stm.read_transaction_labeled("slow", |tr| {
+                started.wait();
+                finish.wait();
+                STMResult::Ok(tr.load(0))
+            })
+        });
+        started.wait();
+        thread::sleep(Duration::from_millis(50));
+
+        let young = stm.check_stalled(Duration::from_secs(60), u32::MAX);
+        let stalled = stm.check_stalled(Duration::from_millis(10), u32::MAX);
+        finish.wait();
+
+        assert!(young.is_empty());
+        assert_eq!(stalled.len(), 1);
+        let tx = stalled[0];
+        assert_eq!(tx.label, Some("slow"));
+        assert!(tx.read_only);
+        assert_eq!(tx.priority, TxPriority::Normal);
+        assert_eq!(tx.attempts, 1);
+        assert!(tx.age >= Duration::from_millis(50), "{:?}", tx.age);
+        assert_eq!(*stalls.0.lock().unwrap(), stalled);
+    });
+
+    // finished transactions free their slots
+    assert!(stm.check_stalled(Duration::ZERO, 0).is_empty());
+}
+
+#[test]
+fn nothing_is_watched_unless_enabled() {
+    let stm = STM::new();
+    let (started, finish) = (Barrier::new(2), Barrier::new(2));
+    thread::scope(|s| {
+        s.spawn(|| {
+            stm.write_transaction(|_| {
+                started.wait();
+                finish.wait();
+                STMResult::Ok(())
+            })
+        });
+        started.wait();
+        let stalled = stm.check_stalled(Duration::ZERO, 0);
+        finish.wait();
+        assert!(stalled.is_empty());
+    });
+}
+
+// A rogue transaction holds the lock of stripe 0 at `Locked`, so a writer
+// of that stripe keeps restarting until it is let go.
+#[cfg(feature = "testing")]
+#[test]
+fn a_writer_spinning_on_a_held_lock_is_reported() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Instant;
+    use tl2::{STMBuilder, Scheduler, StripeValue, YieldPoint};
+
+    #[derive(Default)]
+    struct HoldRogue {
+        release: AtomicBool,
+    }
+
+    impl Scheduler for HoldRogue {
+        fn reached(&self, point: YieldPoint) {
+            if point == YieldPoint::Locked && thread::current().name() == Some("rogue") {
+                while !self.release.load(Ordering::SeqCst) {
+                    thread::yield_now();
+                }
+            }
+        }
+    }
+
+    let hold = Arc::new(HoldRogue::default());
+    let stm = STMBuilder::new()
+        .watchdog(true)
+        .scheduler(hold.clone())
+        .build();
+
+    thread::scope(|s| {
+        thread::Builder::new()
+            .name("rogue".into())
+            .spawn_scoped(s, || {
+                stm.write_transaction_labeled("rogue", |tr| {
+                    tr.store(0, 1u64.to_stripe());
+                    STMResult::Ok(())
+                })
+            })
+            .unwrap();
+        while stm.locked_stripes().is_empty() {
+            thread::yield_now();
+        }
+        s.spawn(|| {
+            stm.write_transaction_labeled("victim", |tr| {
+                let v = match tr.load(0) {
+                    Some(v) => u64::from_stripe(v),
+                    None => return STMResult::Retry,
+                };
+                tr.store(0, (v + 1).to_stripe());
+                STMResult::Ok(())
+            })
+        });
+
+        // the rogue is on its first attempt, so only the victim shows up
+        let start = Instant::now();
+        let mut stalled = Vec::new();
+        while stalled.is_empty() && start.elapsed() < Duration::from_secs(10) {
+            thread::sleep(Duration::from_millis(1));
+            stalled = stm.check_stalled(Duration::from_secs(60), 3);
+        }
+        // let go before asserting, so a failure does not hang the scope
+        hold.release.store(true, Ordering::SeqCst);
+
+        assert_eq!(stalled.len(), 1, "{:?}", stalled);
+        assert_eq!(stalled[0].label, Some("victim"));
+        assert!(!stalled[0].read_only);
+        assert!(stalled[0].attempts > 3);
+    });
+
+    assert!(stm.check_stalled(Duration::ZERO, 0).is_empty());
+    let total = stm
+        .read_transaction(|tr| match tr.load(0) {
+            Some(v) => STMResult::Ok(u64::from_stripe(v)),
+            None => STMResult::Retry,
+        })
+        .unwrap();
+    assert_eq!(total, 2);
+}
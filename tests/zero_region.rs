@@ -0,0 +1,73 @@
+use tl2::{STMResult, STM};
+
+const SIZE: usize = 1 << 16;
+
+fn fill(stm: &STM) {
+    stm.write_transaction(|tr| {
+        for a in (0..SIZE).step_by(8) {
+            tr.store(a, [0xab; 8]);
+        }
+        STMResult::Ok(())
+    })
+    .unwrap();
+}
+
+fn read(stm: &STM, addr: usize, len: usize) -> (Vec<u8>, Vec<(usize, u64)>) {
+    stm.read_transaction_versioned(|tr| {
+        let mut buf = Vec::new();
+        for a in (addr & !7..addr + len).step_by(8) {
+            buf.extend_from_slice(&tl2::load!(tr, a));
+        }
+        STMResult::Ok(buf)
+    })
+    .unwrap()
+}
+
+#[test]
+fn zeroed_stripes_read_zero_at_a_fresh_version() {
+    let mut stm = STM::builder().capacity(SIZE).build();
+    fill(&stm);
+    let (_, before) = read(&stm, 0, SIZE);
+    let clock = stm.current_version();
+
+    stm.zero_region(0, SIZE);
+    let (bytes, after) = read(&stm, 0, SIZE);
+    assert!(bytes.iter().all(|b| *b == 0));
+    assert_eq!(stm.current_version(), clock + 1);
+    for ((addr, old), (_, new)) in before.iter().zip(after.iter()) {
+        assert!(new > old, "stripe {} kept version {}", addr, old);
+        assert_eq!(*new, clock + 1);
+    }
+
+    // later commits go past the zeroing
+    fill(&stm);
+    let (_, last) = read(&stm, 0, 8);
+    assert_eq!(last[0].1, clock + 2);
+}
+
+#[test]
+fn an_unaligned_range_zeroes_only_its_bytes() {
+    let mut stm = STM::builder().capacity(SIZE).build();
+    fill(&stm);
+    let clock = stm.current_version();
+
+    stm.zero_region(13, 6);
+    let (bytes, versions) = read(&stm, 0, 32);
+    let mut expected = [0xab; 32];
+    expected[13..19].fill(0);
+    assert_eq!(bytes, expected);
+    let bumped: Vec<u64> = versions.iter().map(|(_, v)| *v).collect();
+    assert_eq!(bumped, [clock, clock + 1, clock + 1, clock]);
+
+    stm.zero_region(40, 0);
+    assert_eq!(stm.current_version(), clock + 1);
+}
+
+#[test]
+#[should_panic(expected = "region out of bounds")]
+fn a_range_past_the_memory_panics() {
+    STM::builder()
+        .capacity(SIZE)
+        .build()
+        .zero_region(SIZE - 8, 16);
+}
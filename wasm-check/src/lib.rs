@@ -0,0 +1,80 @@
+// tl2 on wasm32-unknown-unknown, through plain exports (no wasm-bindgen,
+// so any host can load the module).
+//
+// Single-threaded, the default target: transactions work, conflicts cannot
+// happen and fences compile to nothing. tl2's `std` feature builds too, but
+// std has no clock there, so leave stats, the watchdog, observers and
+// journals off.
+//
+//     cargo build -p wasm-check --release --target wasm32-unknown-unknown
+//
+// Threaded: every worker instantiates the module on one shared memory and
+// calls `writer`, the main thread calls `check`. Blocking waits use
+// `tl2::Wait` (`memory.atomic.wait32`), which browsers forbid on the main
+// thread: there, read with `check` only, or build the STM with `tl2::Spin`.
+//
+// Both are tested under node with wasm-bindgen-test; the commands are at
+// the top of tests/single.rs and tests/threads.rs.
+
+use std::sync::OnceLock;
+
+use nostd_check::{setup, transfer, Rng};
+use tl2::{load, store, STMResult, STM};
+
+static SHARED: OnceLock<STM> = OnceLock::new();
+
+fn stm() -> &'static STM {
+    SHARED.get_or_init(|| {
+        let stm = STM::new();
+        setup(&stm);
+        stm
+    })
+}
+
+/// Run `n` transfers and checks on one thread and return how many
+/// transactions had to restart, which must be 0, or `u32::MAX` if the
+/// invariant broke.
+#[no_mangle]
+pub extern "C" fn single(n: u32) -> u32 {
+    let stm = STM::new();
+    setup(&stm);
+    let mut rng = Rng::new(1);
+    let mut restarts = 0;
+    for _ in 0..n {
+        transfer(&stm, &mut rng);
+        // a write transaction that reads what another one just wrote
+        let r = stm.write_transaction_poll(
+            |tr| {
+                let a = load!(tr, 0);
+                store!(tr, 0, a);
+                STMResult::Ok(())
+            },
+            |_| restarts += 1,
+        );
+        if r.is_none() || !nostd_check::check(&stm) {
+            return u32::MAX;
+        }
+    }
+    restarts
+}
+
+/// Set up the shared STM; call it once before starting the workers.
+#[no_mangle]
+pub extern "C" fn init() {
+    stm();
+}
+
+/// Run `n` transfers on the shared STM.
+#[no_mangle]
+pub extern "C" fn writer(seed: u32, n: u32) {
+    let mut rng = Rng::new(seed as u64);
+    for _ in 0..n {
+        transfer(stm(), &mut rng);
+    }
+}
+
+/// Whether the shared STM holds the invariant.
+#[no_mangle]
+pub extern "C" fn check() -> u32 {
+    nostd_check::check(stm()) as u32
+}
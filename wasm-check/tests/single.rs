@@ -0,0 +1,20 @@
+// The single-threaded check, run by wasm-bindgen-test:
+//
+//     CARGO_TARGET_WASM32_UNKNOWN_UNKNOWN_RUNNER=wasm-bindgen-test-runner \
+//       cargo test -p wasm-check --target wasm32-unknown-unknown
+
+#![cfg(target_arch = "wasm32")]
+
+use wasm_bindgen_test::wasm_bindgen_test;
+
+#[wasm_bindgen_test]
+fn transfers_without_restarts() {
+    assert_eq!(wasm_check::single(10_000), 0);
+}
+
+#[wasm_bindgen_test]
+fn shared_stm_holds_the_invariant() {
+    wasm_check::init();
+    wasm_check::writer(1, 10_000);
+    assert_eq!(wasm_check::check(), 1);
+}
@@ -0,0 +1,106 @@
+// The threaded check, run by wasm-bindgen-test: node workers instantiate
+// this test module on its shared memory and run transfers while the main
+// thread checks the invariant. std has to be rebuilt with atomics, which
+// takes a nightly toolchain with rust-src:
+//
+//     RUSTFLAGS="-C target-feature=+atomics,+bulk-memory,+mutable-globals \
+//       -C link-arg=--shared-memory -C link-arg=--import-memory \
+//       -C link-arg=--max-memory=67108864 -C link-arg=--export=__tls_base \
+//       -C link-arg=--export=__tls_size -C link-arg=--export=__tls_align \
+//       -C link-arg=--export=__wasm_init_tls" \
+//     CARGO_TARGET_WASM32_UNKNOWN_UNKNOWN_RUNNER=wasm-bindgen-test-runner \
+//       cargo +nightly test -p wasm-check --target wasm32-unknown-unknown \
+//       -Z build-std=std,panic_abort --test threads
+
+#![cfg(all(target_arch = "wasm32", target_feature = "atomics"))]
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+const WORKERS: u32 = 4;
+const TRANSFERS: u32 = 20_000;
+
+#[wasm_bindgen(inline_js = r#"
+const WORKER = `
+const { parentPort, workerData } = require("node:worker_threads");
+const { module, memory, seed, n } = workerData;
+const imports = {};
+for (const { module: m, name, kind } of WebAssembly.Module.imports(module)) {
+  imports[m] = imports[m] || {};
+  if (kind === "memory") {
+    imports[m][name] = memory;
+  } else if (name === "__wbindgen_init_externref_table") {
+    imports[m][name] = () => {}; // nothing here passes JS values
+  } else {
+    imports[m][name] = () => {
+      throw new Error(m + "." + name + " called on a worker");
+    };
+  }
+}
+const { exports } = new WebAssembly.Instance(module, imports);
+if (exports.__wbindgen_start) exports.__wbindgen_start();
+exports.worker_writer(seed, n);
+parentPort.postMessage("done");
+`;
+
+export async function start(module, memory, workers, n) {
+  const { Worker } = await import("node:worker_threads");
+  const writers = { running: workers, failure: undefined };
+  for (let seed = 1; seed <= workers; seed++) {
+    const w = new Worker(WORKER, { eval: true, workerData: { module, memory, seed, n } });
+    w.on("message", () => writers.running--);
+    w.on("error", (e) => {
+      writers.failure = writers.failure || String(e);
+      writers.running--;
+    });
+  }
+  return writers;
+}
+
+export function running(writers) {
+  return writers.running;
+}
+
+export function failure(writers) {
+  return writers.failure;
+}
+
+export function tick() {
+  return new Promise((resolve) => setImmediate(resolve));
+}
+"#)]
+extern "C" {
+    fn start(module: JsValue, memory: JsValue, workers: u32, n: u32) -> js_sys::Promise;
+    fn running(writers: &JsValue) -> u32;
+    fn failure(writers: &JsValue) -> Option<String>;
+    fn tick() -> js_sys::Promise;
+}
+
+/// Run on the workers.
+#[wasm_bindgen]
+pub fn worker_writer(seed: u32, n: u32) {
+    wasm_check::writer(seed, n);
+}
+
+#[wasm_bindgen_test]
+async fn transfers_keep_the_invariant() {
+    wasm_check::init();
+    let writers = JsFuture::from(start(
+        wasm_bindgen::module(),
+        wasm_bindgen::memory(),
+        WORKERS,
+        TRANSFERS,
+    ))
+    .await
+    .unwrap();
+    let mut checks = 0;
+    while running(&writers) > 0 {
+        assert_eq!(wasm_check::check(), 1, "broken after {} checks", checks);
+        checks += 1;
+        JsFuture::from(tick()).await.unwrap();
+    }
+    assert_eq!(failure(&writers), None);
+    assert_eq!(wasm_check::check(), 1);
+    assert!(checks > 0);
+}